@@ -1,5 +1,15 @@
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+// the layout reflection and code generation are shared with the main crate
+#[path = "src/vkn/shader/struct_layout.rs"]
+mod struct_layout;
+
+#[path = "src/vkn/shader/layout_reflect.rs"]
+mod layout_reflect;
+
+#[path = "src/vkn/shader/rust_codegen.rs"]
+mod rust_codegen;
 
 #[macro_export]
 macro_rules! log {
@@ -19,6 +29,215 @@ fn dump_env() {
     println!("cargo:rustc-env=TARGET_DIR={}/", target_dir);
 }
 
+/// Reflects the buffer blocks of every shader and writes their Rust mirrors to
+/// `$OUT_DIR/shader_structs.rs`, one module per shader file.
+fn generate_shader_structs(shader_macros: &[(String, String)]) {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let shader_dir = manifest_dir.join("shader");
+    let include_dir = shader_dir.join("include");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=shader");
+    for file in ["struct_layout.rs", "layout_reflect.rs", "rust_codegen.rs"] {
+        println!("cargo:rerun-if-changed=src/vkn/shader/{}", file);
+    }
+
+    let compiler = shaderc::Compiler::new().expect("Failed to create shader compiler");
+    let mut options = shaderc::CompileOptions::new().expect("Failed to create compile options");
+    options.set_target_env(
+        shaderc::TargetEnv::Vulkan,
+        shaderc::EnvVersion::Vulkan1_3 as u32,
+    );
+    options.set_target_spirv(shaderc::SpirvVersion::V1_6);
+    options.set_source_language(shaderc::SourceLanguage::GLSL);
+    // same as the reflection pass at runtime, keeps unused bindings and names
+    options.set_optimization_level(shaderc::OptimizationLevel::Zero);
+    for (name, value) in shader_macros {
+        options.add_macro_definition(name, Some(value));
+    }
+    options.set_include_callback(move |requested, include_type, requesting, _depth| {
+        let mut candidates = vec![include_dir.join(requested)];
+        if include_type == shaderc::IncludeType::Relative {
+            if let Some(parent) = Path::new(requesting).parent() {
+                candidates.insert(0, parent.join(requested));
+            }
+        }
+        let full_path = candidates
+            .iter()
+            .find_map(|c| c.canonicalize().ok())
+            .ok_or_else(|| format!("{}: not found", requested))?;
+        let content = std::fs::read_to_string(&full_path).map_err(|e| e.to_string())?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: full_path.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+
+    let mut shader_paths = Vec::new();
+    collect_shader_paths(&shader_dir, &mut shader_paths);
+    shader_paths.sort();
+
+    let mut output = String::from("// generated by build.rs, do not edit\n\n");
+    for path in shader_paths {
+        let relative = path.strip_prefix(&shader_dir).unwrap();
+        let module_name: String = relative
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        let layouts = match reflect_buffer_layouts(&compiler, &options, &path) {
+            Ok(layouts) => layouts,
+            Err(e) => {
+                log!("skipping struct generation for {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if layouts.is_empty() {
+            continue;
+        }
+
+        let mut block_names: Vec<&String> = layouts.keys().collect();
+        block_names.sort();
+        let mut generator = rust_codegen::RustStructGenerator::new();
+        for block_name in block_names {
+            // blocks holding only a runtime array have no fixed-size part to mirror
+            if layouts[block_name].get_size_bytes() == 0 {
+                continue;
+            }
+            if let Err(e) = generator.add_buffer_layout(&layouts[block_name]) {
+                log!("skipping {} in {}: {}", block_name, path.display(), e);
+            }
+        }
+
+        output.push_str(&format!(
+            "/// Generated from `shader/{}`.\npub mod {} {{\n{}}}\n\n",
+            relative.to_string_lossy().replace('\\', "/"),
+            module_name,
+            generator.finish()
+        ));
+    }
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("shader_structs.rs");
+    std::fs::write(out_path, output).expect("Failed to write generated shader structs");
+
+    fn collect_shader_paths(dir: &Path, result: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                collect_shader_paths(&path, result);
+            } else if matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("comp" | "vert" | "frag")
+            ) {
+                result.push(path);
+            }
+        }
+    }
+
+    fn reflect_buffer_layouts(
+        compiler: &shaderc::Compiler,
+        options: &shaderc::CompileOptions,
+        path: &Path,
+    ) -> Result<std::collections::HashMap<String, struct_layout::BufferLayout>, String> {
+        let shader_kind = match path.extension().and_then(|e| e.to_str()) {
+            Some("vert") => shaderc::ShaderKind::Vertex,
+            Some("frag") => shaderc::ShaderKind::Fragment,
+            _ => shaderc::ShaderKind::Compute,
+        };
+        let code = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let artifact = compiler
+            .compile_into_spirv(
+                &code,
+                shader_kind,
+                &path.to_string_lossy(),
+                "main",
+                Some(options),
+            )
+            .map_err(|e| e.to_string())?;
+        let reflect_module = spirv_reflect::ShaderModule::load_u8_data(artifact.as_binary_u8())?;
+        layout_reflect::extract_buffer_layouts(&reflect_module)
+    }
+}
+
+#[derive(Debug)]
+enum ConstantValue {
+    UInt(u32),
+    Float(f32),
+}
+
+/// Generates `$OUT_DIR/constants.rs` from `constants.toml`. The shaders get the constants as
+/// macros, see `SHADER_MACROS`, which are returned for the struct reflection pass.
+fn generate_shared_constants() -> Vec<(String, String)> {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=constants.toml");
+
+    let source = std::fs::read_to_string(manifest_dir.join("constants.toml"))
+        .expect("Failed to read constants.toml");
+    let constants = parse_constants(&source);
+
+    let mut rust = String::from("// generated by build.rs from constants.toml, do not edit\n\n");
+    let mut shader_macros = Vec::new();
+    for (name, value) in &constants {
+        let macro_value = match value {
+            ConstantValue::UInt(v) => {
+                rust.push_str(&format!("pub const {}: u32 = {};\n", name, v));
+                v.to_string()
+            }
+            ConstantValue::Float(v) => {
+                rust.push_str(&format!("pub const {}: f32 = {:?};\n", name, v));
+                format!("{:?}", v)
+            }
+        };
+        shader_macros.push((name.clone(), macro_value));
+    }
+
+    rust.push_str(
+        "\n/// The constants above as `(name, value)` macro definitions for the shaders.\n",
+    );
+    rust.push_str("pub const SHADER_MACROS: &[(&str, &str)] = &[\n");
+    for (name, value) in &shader_macros {
+        rust.push_str(&format!("    ({:?}, {:?}),\n", name, value));
+    }
+    rust.push_str("];\n");
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("constants.rs");
+    std::fs::write(out_path, rust).expect("Failed to write generated constants");
+
+    return shader_macros;
+
+    fn parse_constants(source: &str) -> Vec<(String, ConstantValue)> {
+        let mut constants = Vec::new();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .unwrap_or_else(|| panic!("constants.toml:{}: expected NAME = value", line_no + 1));
+            let (name, value) = (name.trim(), value.trim());
+            let value =
+                if value.contains('.') {
+                    ConstantValue::Float(value.parse().unwrap_or_else(|e| {
+                        panic!("constants.toml:{}: {}: {}", line_no + 1, value, e)
+                    }))
+                } else {
+                    ConstantValue::UInt(value.parse().unwrap_or_else(|e| {
+                        panic!("constants.toml:{}: {}: {}", line_no + 1, value, e)
+                    }))
+                };
+            constants.push((name.to_string(), value));
+        }
+        constants
+    }
+}
+
 fn main() {
     dump_env();
+    let shader_macros = generate_shared_constants();
+    generate_shader_structs(&shader_macros);
 }
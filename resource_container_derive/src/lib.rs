@@ -1,8 +1,12 @@
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{Data, DeriveInput, Fields, PathArguments, Type, TypePath, parse_macro_input};
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, PathArguments, Type, TypePath,
+    parse_macro_input,
+};
 
-#[proc_macro_derive(ResourceContainer)]
+#[proc_macro_derive(ResourceContainer, attributes(resource))]
 pub fn derive_resource_container(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let struct_name = input.ident;
@@ -30,26 +34,75 @@ pub fn derive_resource_container(input: TokenStream) -> TokenStream {
         }
     };
 
-    // collect different types of fields
-    let mut resource_idents = Vec::<syn::Ident>::new();
-    let mut other_field_idents = Vec::<syn::Ident>::new();
+    // sort the fields by how they take part in the lookups, the names are what the shaders bind
+    let mut resource_idents = Vec::<Ident>::new();
+    let mut resource_names = Vec::<String>::new();
+    let mut optional_idents = Vec::<Ident>::new();
+    let mut optional_names = Vec::<String>::new();
+    let mut vec_idents = Vec::<Ident>::new();
+    let mut vec_names = Vec::<String>::new();
+    let mut other_field_idents = Vec::<Ident>::new();
     let mut other_field_types = Vec::<Type>::new();
+    let mut error: Option<syn::Error> = None;
 
     for field in fields {
-        if let Some(ident) = &field.ident {
-            if is_resource_type(&field.ty) {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        let classified = parse_field_attrs(&field).and_then(|attrs| {
+            if attrs.is_skipped {
+                return Ok(None);
+            }
+            let kind = classify(&field.ty)?;
+            let is_resource = matches!(
+                kind,
+                Some(FieldKind::Resource | FieldKind::OptionalResource | FieldKind::ResourceVec)
+            );
+            if attrs.name.is_some() && !is_resource {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "only Resource<T> fields can be renamed, nested containers keep the names of \
+                     their resources",
+                ));
+            }
+            let name = attrs.name.unwrap_or_else(|| ident.to_string());
+            Ok(kind.map(|kind| (kind, name)))
+        });
+        match classified {
+            Ok(Some((FieldKind::Resource, name))) => {
                 resource_idents.push(ident.clone());
-            } else if is_potential_resource_container(&field.ty) {
-                // only include types that could potentially be ResourceContainer implementors
+                resource_names.push(name);
+            }
+            Ok(Some((FieldKind::OptionalResource, name))) => {
+                optional_idents.push(ident.clone());
+                optional_names.push(name);
+            }
+            Ok(Some((FieldKind::ResourceVec, name))) => {
+                vec_idents.push(ident.clone());
+                vec_names.push(name);
+            }
+            Ok(Some((FieldKind::Nested, _))) => {
                 other_field_idents.push(ident.clone());
                 other_field_types.push(field.ty.clone());
             }
-            // skip primitive types, standard library types, etc.
+            // skipped fields, primitive types, standard library types, etc.
+            Ok(None) => {}
+            Err(e) => match error.as_mut() {
+                Some(error) => error.combine(e),
+                None => error = Some(e),
+            },
         }
     }
+    if let Some(error) = error {
+        return error.to_compile_error().into();
+    }
 
     // check if we have at least one field that could be a resource
-    if resource_idents.is_empty() && other_field_idents.is_empty() {
+    if resource_idents.is_empty()
+        && optional_idents.is_empty()
+        && vec_idents.is_empty()
+        && other_field_idents.is_empty()
+    {
         return syn::Error::new_spanned(
             struct_name,
             "no Resource<T> fields found; cannot derive ResourceContainer",
@@ -58,144 +111,248 @@ pub fn derive_resource_container(input: TokenStream) -> TokenStream {
         .into();
     }
 
-    // generate match arms for direct Resource<Buffer> fields
-    let buffer_match_arms = resource_idents.iter().map(|ident| {
-        quote! {
-            stringify!(#ident) => self.#ident.as_any().downcast_ref::<crate::vkn::Buffer>(),
-        }
-    });
-
-    // generate match arms for direct Resource<Texture> fields
-    let texture_match_arms = resource_idents.iter().map(|ident| {
-        quote! {
-            stringify!(#ident) => self.#ident.as_any().downcast_ref::<crate::vkn::Texture>(),
-        }
-    });
-
-    // generate nested lookup code for buffers
-    let nested_buffer_lookup_code = if other_field_idents.is_empty() {
-        quote! {}
-    } else {
-        quote! {
-            // try nested ResourceContainer fields recursively
-            #(
-                if let Some(result) = self.#other_field_idents.get_buffer(name) {
-                    return Some(result);
-                }
-            )*
-        }
-    };
-
-    // generate nested lookup code for textures
-    let nested_texture_lookup_code = if other_field_idents.is_empty() {
-        quote! {}
-    } else {
-        quote! {
-            // try nested ResourceContainer fields recursively
-            #(
-                if let Some(result) = self.#other_field_idents.get_texture(name) {
-                    return Some(result);
-                }
-            )*
-        }
+    let fields = LookupFields {
+        resources: (&resource_idents, &resource_names),
+        optionals: (&optional_idents, &optional_names),
+        vecs: (&vec_idents, &vec_names),
+        nested: &other_field_idents,
     };
+    let buffer_lookup = fields.lookup(
+        quote! { crate::vkn::Buffer },
+        quote! {},
+        quote! { get_buffer },
+    );
+    let texture_lookup = fields.lookup(
+        quote! { crate::vkn::Texture },
+        quote! {},
+        quote! { get_texture },
+    );
+    let texture_array_lookup = fields.lookup(
+        quote! { Vec<crate::vkn::Texture> },
+        quote! { .map(|textures| textures.as_slice()) },
+        quote! { get_texture_array },
+    );
 
-    // generate resource names for conflict detection
-    let direct_resource_names = resource_idents.iter().map(|ident| {
-        quote! { stringify!(#ident) }
-    });
-
-    let _nested_resource_names = other_field_types.iter().map(|ty| {
-        quote! { #ty::get_resource_names() }
-    });
-
-    // generate compile-time conflict detection
-    let direct_names_array = if resource_idents.is_empty() {
-        quote! { &[] }
-    } else {
-        let names = resource_idents.iter().map(|ident| {
-            quote! { stringify!(#ident) }
-        });
-        quote! { &[#(#names),*] }
-    };
-
-    // runtime conflict detection (since we removed the const)
-    let runtime_checks = if !other_field_types.is_empty() {
-        quote! {
-            // runtime checks for name conflicts
-            let direct_names = #direct_names_array;
-            #(
-                let nested_names = self.#other_field_idents.get_resource_names();
-                for direct_name in direct_names {
-                    for nested_name in &nested_names {
-                        if direct_name == nested_name {
-                            panic!("Resource name conflict detected: '{}'", direct_name);
-                        }
-                    }
-                }
-            )*
-        }
-    } else {
-        quote! {}
-    };
+    let namespace = namespace_of(&struct_name.to_string());
 
     let expanded = quote! {
         impl crate::resource::ResourceContainer for #struct_name {
             fn get_buffer(&self, name: &str) -> Option<&crate::vkn::Buffer> {
-                #runtime_checks
-                match name {
-                    // direct Resource<Buffer> fields take priority
-                    #(#buffer_match_arms)*
-                    _ => {
-                        // try nested ResourceContainer fields
-                        #nested_buffer_lookup_code
-                        None
-                    }
-                }
+                #buffer_lookup
             }
 
             fn get_texture(&self, name: &str) -> Option<&crate::vkn::Texture> {
-                match name {
-                    // direct Resource<Texture> fields take priority
-                    #(#texture_match_arms)*
-                    _ => {
-                        // try nested ResourceContainer fields
-                        #nested_texture_lookup_code
-                        None
-                    }
-                }
+                #texture_lookup
+            }
+
+            fn get_texture_array(&self, name: &str) -> Option<&[crate::vkn::Texture]> {
+                #texture_array_lookup
             }
 
-            fn get_resource_names(&self) -> Vec<&'static str> {
+            fn get_resource_names(&self) -> Vec<String> {
                 let mut names = Vec::new();
-                let mut seen = std::collections::HashSet::new();
 
                 // add direct resource names
+                #(names.push(#resource_names.to_string());)*
+                // optional resources only while they are set
                 #(
-                    let name = #direct_resource_names;
-                    if !seen.insert(name) {
-                        panic!("Duplicate resource name '{}' found in {}", name, stringify!(#struct_name));
+                    if self.#optional_idents.is_some() {
+                        names.push(#optional_names.to_string());
                     }
-                    names.push(name);
                 )*
-
-                // add nested resource names
+                // collections name their elements with an index suffix
                 #(
-                    for nested_name in self.#other_field_idents.get_resource_names() {
-                        if !seen.insert(nested_name) {
-                            panic!("Duplicate resource name '{}' found in {} (conflicts with nested resource)", nested_name, stringify!(#struct_name));
-                        }
-                        names.push(nested_name);
+                    for idx in 0..self.#vec_idents.len() {
+                        names.push(format!("{}_{}", #vec_names, idx));
                     }
                 )*
-
+                // add nested resource names
+                #(names.extend(self.#other_field_idents.get_resource_names());)*
                 names
             }
+
+            fn namespace(&self) -> &'static str {
+                #namespace
+            }
+        }
+
+        impl crate::resource::StaticResourceNames for #struct_name {
+            const RESOURCE_NAMES: crate::resource::ResourceNameTree =
+                crate::resource::ResourceNameTree {
+                    names: &[
+                        #(#resource_names,)*
+                        #(#optional_names,)*
+                    ],
+                    nested: &[
+                        #(&<#other_field_types as crate::resource::StaticResourceNames>::RESOURCE_NAMES,)*
+                    ],
+                };
         }
+
+        // name conflicts across the nested containers fail the build instead of a lookup
+        const _: () = assert!(
+            !<#struct_name as crate::resource::StaticResourceNames>::RESOURCE_NAMES.has_duplicates(),
+            concat!(
+                "resource names clash in ",
+                stringify!(#struct_name),
+                ", list them with ResourceContainer::get_resource_names"
+            )
+        );
     };
     TokenStream::from(expanded)
 }
 
+/// How a field takes part in the lookups.
+enum FieldKind {
+    /// `Resource<T>`, found by the field name.
+    Resource,
+    /// `Option<Resource<T>>`, found by the field name while it's set.
+    OptionalResource,
+    /// `Vec<Resource<T>>`, element `i` is found as `<field name>_<i>`.
+    ResourceVec,
+    /// May be a ResourceContainer itself, searched after the own resources.
+    Nested,
+}
+
+/// `None` for fields left out of the lookups, an error for resources wrapped in a way the lookups
+/// can't reach, so they aren't skipped silently.
+fn classify(ty: &Type) -> Result<Option<FieldKind>, syn::Error> {
+    if is_resource_type(ty) {
+        return Ok(Some(FieldKind::Resource));
+    }
+    if generic_argument_of(ty, "Option").is_some_and(is_resource_type) {
+        return Ok(Some(FieldKind::OptionalResource));
+    }
+    if generic_argument_of(ty, "Vec").is_some_and(is_resource_type) {
+        return Ok(Some(FieldKind::ResourceVec));
+    }
+    if contains_resource_type(ty) {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "unsupported wrapper around Resource<T>; ResourceContainer looks up Resource<T>, \
+             Option<Resource<T>> and Vec<Resource<T>> fields only",
+        ));
+    }
+    Ok(is_potential_resource_container(ty).then_some(FieldKind::Nested))
+}
+
+/// What the `#[resource(...)]` attributes of a field say.
+#[derive(Default)]
+struct FieldAttrs {
+    /// `#[resource(name = "...")]`, the name the shaders bind the resource by when it isn't the
+    /// field name.
+    name: Option<String>,
+    /// `#[resource(skip)]`, keeps the field out of the lookups, for staging or debug buffers.
+    is_skipped: bool,
+}
+
+fn parse_field_attrs(field: &syn::Field) -> Result<FieldAttrs, syn::Error> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("resource"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                attrs.is_skipped = true;
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                let name: syn::LitStr = meta.value()?.parse()?;
+                if name.value().is_empty() {
+                    return Err(syn::Error::new_spanned(
+                        name,
+                        "resource names can't be empty",
+                    ));
+                }
+                attrs.name = Some(name.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"` or `skip`"))
+            }
+        })?;
+    }
+    if attrs.is_skipped && attrs.name.is_some() {
+        return Err(syn::Error::new_spanned(
+            field,
+            "a skipped field isn't looked up, it can't be renamed",
+        ));
+    }
+    Ok(attrs)
+}
+
+/// The fields of a struct the getters look resources up in, next to the names they're bound by.
+struct LookupFields<'a> {
+    resources: (&'a [Ident], &'a [String]),
+    optionals: (&'a [Ident], &'a [String]),
+    vecs: (&'a [Ident], &'a [String]),
+    nested: &'a [Ident],
+}
+
+impl LookupFields<'_> {
+    /// The body of a getter that downcasts the own resources to `target` and maps the result with
+    /// `convert`, the nested containers are asked through `getter` after them.
+    fn lookup(
+        &self,
+        target: TokenStream2,
+        convert: TokenStream2,
+        getter: TokenStream2,
+    ) -> TokenStream2 {
+        let (resources, resource_names) = self.resources;
+        let (optionals, optional_names) = self.optionals;
+        let (vecs, vec_names) = self.vecs;
+        let nested = self.nested;
+        quote! {
+            match name {
+                // direct Resource<T> fields take priority
+                #(#resource_names => self.#resources.as_any().downcast_ref::<#target>()#convert,)*
+                #(
+                    #optional_names => self
+                        .#optionals
+                        .as_ref()
+                        .and_then(|resource| resource.as_any().downcast_ref::<#target>())#convert,
+                )*
+                _ => {
+                    // elements of Vec<Resource<T>> fields
+                    #(
+                        if let Some(resource) = name
+                            .strip_prefix(concat!(#vec_names, "_"))
+                            .and_then(|idx| idx.parse::<usize>().ok())
+                            .and_then(|idx| self.#vecs.get(idx))
+                        {
+                            return resource.as_any().downcast_ref::<#target>()#convert;
+                        }
+                    )*
+                    // try nested ResourceContainer fields recursively
+                    #(
+                        if let Some(result) = self.#nested.#getter(name) {
+                            return Some(result);
+                        }
+                    )*
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// snake case struct name without a trailing `Resources`, `TracerResources` becomes `tracer`
+fn namespace_of(struct_name: &str) -> String {
+    let base = struct_name.strip_suffix("Resources").unwrap_or(struct_name);
+    let mut namespace = String::new();
+    for (i, c) in base.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                namespace.push('_');
+            }
+            namespace.extend(c.to_lowercase());
+        } else {
+            namespace.push(c);
+        }
+    }
+    namespace
+}
+
 /// returns true if the type is exactly Resource<...>
 fn is_resource_type(ty: &Type) -> bool {
     match ty {
@@ -211,6 +368,50 @@ fn is_resource_type(ty: &Type) -> bool {
     }
 }
 
+/// the single generic argument of `wrapper<...>`, `Option<Resource<Buffer>>` gives
+/// `Resource<Buffer>` for `Option`
+fn generic_argument_of<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(TypePath { path, .. }) = ty else {
+        return None;
+    };
+    let last = path.segments.last()?;
+    if last.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// returns true if a Resource<...> appears anywhere in the type
+fn contains_resource_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(TypePath { path, .. }) => path.segments.iter().any(|segment| {
+            if segment.ident == "Resource" {
+                return true;
+            }
+            let PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return false;
+            };
+            args.args.iter().any(|arg| match arg {
+                GenericArgument::Type(inner) => contains_resource_type(inner),
+                _ => false,
+            })
+        }),
+        Type::Array(array) => contains_resource_type(&array.elem),
+        Type::Slice(slice) => contains_resource_type(&slice.elem),
+        Type::Reference(reference) => contains_resource_type(&reference.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().any(contains_resource_type),
+        Type::Paren(paren) => contains_resource_type(&paren.elem),
+        Type::Group(group) => contains_resource_type(&group.elem),
+        _ => false,
+    }
+}
+
 /// returns true if the type could potentially be a ResourceContainer implementor
 /// excludes obvious non-ResourceContainer types but doesn't make assumptions
 fn is_potential_resource_container(ty: &Type) -> bool {
@@ -0,0 +1,13 @@
+#ifndef DENSITY_GLSL
+#define DENSITY_GLSL
+
+// far outside the clip volume, so every triangle of a dropped instance is clipped away
+const vec4 DROPPED_VERT_POS = vec4(2.0, 2.0, 2.0, 1.0);
+
+// keeps the instances whose hash lies below the density, the same ones at any density, so the
+// foliage thins out steadily as the density goes down
+bool is_instance_kept(uvec3 instance_pos, float density) {
+    return construct_float_01(murmur_hash_13(instance_pos)) < density;
+}
+
+#endif // DENSITY_GLSL
@@ -1,7 +1,17 @@
 #version 450
 
+#extension GL_GOOGLE_include_directive : require
+
+#include "./lod_dither.glsl"
+
 layout(location = 0) in vec3 vert_color;
+layout(location = 1) flat in float lod_fade;
 
 layout(location = 0) out vec4 out_color;
 
-void main() { out_color = vec4(vert_color, 1.0); }
+void main() {
+    if (lod_dither_threshold(gl_FragCoord.xy) >= lod_fade) {
+        discard;
+    }
+    out_color = vec4(vert_color, 1.0);
+}
@@ -6,8 +6,16 @@
 
 layout(push_constant) uniform PC {
     float time;
+    float wind_sway; // scales the wind offset, 1.0 for grass
+    float density;   // fraction of the instances drawn, 1.0 for grass
     vec3 bottom_color;
     vec3 tip_color;
+    float lod_fade; // fraction of the pixels this LOD draws, below 1.0 in the transition band
+    // while the range is above 0.0 each instance works out its own fade instead
+    float lod_switch_size;
+    float lod_transition_range;
+    float lod_size_scale;
+    uint is_far_lod;
 }
 pc;
 
@@ -19,6 +27,7 @@ layout(location = 1) in uvec3 in_instance_pos;
 layout(location = 2) in uint in_instance_ty;
 
 layout(location = 0) out vec3 vert_color;
+layout(location = 1) flat out float lod_fade;
 
 layout(set = 0, binding = 0) uniform U_GuiInput {
     float debug_float;
@@ -63,11 +72,22 @@ layout(set = 0, binding = 4) uniform U_ShadowCameraInfo {
 shadow_camera_info;
 
 layout(set = 0, binding = 5) uniform sampler2D shadow_map_tex_for_vsm_ping;
+layout(set = 0, binding = 6) uniform sampler2D sky_visibility_tex;
+
+layout(set = 0, binding = 7) uniform U_WindField {
+    uvec2 cell_count;
+    vec2 cell_size;                   // in world units
+    vec4 cells[MAX_WIND_FIELD_CELLS]; // xy the wind, row major in x
+}
+wind_field;
 
 #include "../include/core/color.glsl"
 #include "../include/core/fast_noise_lite.glsl"
 #include "../include/core/hash.glsl"
+#include "../include/sky_visibility.glsl"
 #include "../include/vsm.glsl"
+#include "./density.glsl"
+#include "./lod_dither.glsl"
 #include "./unpacker.glsl"
 #include "./wind.glsl"
 
@@ -93,7 +113,7 @@ void main() {
 
     vec3 instance_pos = in_instance_pos * scaling_factor;
 
-    vec3 wind_offset = get_wind_offset(instance_pos.xz, wind_gradient, pc.time);
+    vec3 wind_offset = get_wind_offset(instance_pos.xz, wind_gradient, pc.time, pc.wind_sway);
     vec3 anchor_pos  = (vox_local_pos + wind_offset) * scaling_factor + instance_pos;
     vec3 voxel_pos   = anchor_pos + vec3(0.5) * scaling_factor;
     vec3 vert_pos    = anchor_pos + vert_offset_in_vox * scaling_factor;
@@ -102,11 +122,22 @@ void main() {
         get_shadow_weight_vsm(shadow_camera_info.view_proj_mat, vec4(voxel_pos, 1.0));
     shadow_weight *= get_shadow_weight(vox_local_pos);
 
-    gl_Position = camera_info.view_proj_mat * vec4(vert_pos, 1.0);
+    gl_Position = is_instance_kept(in_instance_pos, pc.density)
+                      ? camera_info.view_proj_mat * vec4(vert_pos, 1.0)
+                      : DROPPED_VERT_POS;
 
     vec3 interpolated_color =
         mix(srgb_to_linear(pc.bottom_color), srgb_to_linear(pc.tip_color), color_gradient);
 
+    float sky_visibility = sample_sky_visibility(instance_pos);
+    interpolated_color   = tint_by_sky_visibility(interpolated_color, sky_visibility);
+
     vec3 sun_light = sun_info.sun_color * sun_info.sun_luminance;
-    vert_color     = interpolated_color * (sun_light * shadow_weight + shading_info.ambient_light);
+    vert_color     = interpolated_color *
+                 (sun_light * shadow_weight + shading_info.ambient_light * sky_visibility);
+    lod_fade = pc.lod_transition_range > 0.0
+                   ? instance_lod_fade(pc.lod_switch_size, pc.lod_transition_range,
+                                       pc.lod_size_scale, pc.is_far_lod != 0,
+                                       distance(instance_pos, camera_info.pos.xyz))
+                   : pc.lod_fade;
 }
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) in vec3 vert_color;
+layout(location = 1) flat in float lod_fade;
+
+layout(location = 0) out vec4 out_color;
+
+// alpha blended counterpart of flora.frag and flora_lod.frag, the LOD fade is the coverage, the
+// instances have to be drawn back to front
+void main() { out_color = vec4(vert_color * lod_fade, lod_fade); }
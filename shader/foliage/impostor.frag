@@ -0,0 +1,25 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "./lod_dither.glsl"
+
+layout(location = 0) in vec2 atlas_uv;
+layout(location = 1) flat in vec3 bottom_color;
+layout(location = 2) flat in vec3 tip_color;
+layout(location = 3) flat in float lod_fade;
+
+layout(location = 0) out vec4 out_color;
+
+layout(set = 0, binding = 7) uniform sampler2D impostor_atlas_tex;
+
+void main() {
+    // the color gradient in r, the coverage in g
+    vec2 bake = texture(impostor_atlas_tex, atlas_uv).rg;
+    // the far LOD of the leaves keeps the upper thresholds, see flora_lod.frag, the impostor
+    // takes the pixels below
+    if (bake.g < 0.5 || lod_dither_threshold(gl_FragCoord.xy) >= lod_fade) {
+        discard;
+    }
+    out_color = vec4(mix(bottom_color, tip_color, bake.r), 1.0);
+}
@@ -0,0 +1,114 @@
+//! Far trees as quads that turn to the camera around the y axis, textured from their bake
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+layout(push_constant) uniform PC {
+    float time;
+    float wind_sway;
+    float density; // baked into the atlas
+    vec3 bottom_color;
+    vec3 tip_color;
+    // the rest of the flora block, the fade comes with each instance
+    float lod_fade;
+    float lod_switch_size;
+    float lod_transition_range;
+    float lod_size_scale;
+    uint is_far_lod;
+}
+pc;
+
+layout(location = 0) out vec2 atlas_uv;
+layout(location = 1) flat out vec3 bottom_color;
+layout(location = 2) flat out vec3 tip_color;
+layout(location = 3) flat out float lod_fade;
+
+layout(set = 0, binding = 0) uniform U_SunInfo {
+    vec3 sun_dir;
+    float sun_size;
+    vec3 sun_color;
+    float sun_luminance;
+    float sun_altitude;
+    float sun_azimuth;
+}
+sun_info;
+
+layout(set = 0, binding = 1) uniform U_ShadingInfo { vec3 ambient_light; }
+shading_info;
+
+layout(set = 0, binding = 2) uniform U_CameraInfo {
+    vec4 pos;
+    mat4 view_mat;
+    mat4 view_mat_inv;
+    mat4 proj_mat;
+    mat4 proj_mat_inv;
+    mat4 view_proj_mat;
+    mat4 view_proj_mat_inv;
+}
+camera_info;
+
+layout(set = 0, binding = 3) uniform U_ShadowCameraInfo {
+    vec4 pos;
+    mat4 view_mat;
+    mat4 view_mat_inv;
+    mat4 proj_mat;
+    mat4 proj_mat_inv;
+    mat4 view_proj_mat;
+    mat4 view_proj_mat_inv;
+}
+shadow_camera_info;
+
+layout(set = 0, binding = 4) uniform sampler2D shadow_map_tex_for_vsm_ping;
+layout(set = 0, binding = 5) uniform sampler2D sky_visibility_tex;
+
+// mirrors ImpostorInstance in impostor.rs
+struct ImpostorInstance {
+    vec3 center;
+    float half_size;
+    uint slot;
+    float lod_fade;
+};
+
+layout(set = 0, binding = 6) readonly buffer B_ImpostorInstances { ImpostorInstance data[]; }
+impostor_instances;
+
+#include "../include/core/color.glsl"
+#include "../include/sky_visibility.glsl"
+#include "../include/vsm.glsl"
+
+// IMPOSTOR_ATLAS_SIZE / IMPOSTOR_SLOT_SIZE in impostor.rs
+const uint SLOTS_PER_ROW = 16;
+
+// two triangles, x runs across the quad and y up it
+const vec2 QUAD_CORNERS[6] =
+    vec2[](vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, -1.0), vec2(1.0, 1.0),
+           vec2(-1.0, 1.0));
+
+void main() {
+    ImpostorInstance instance = impostor_instances.data[gl_InstanceIndex];
+    vec2 corner               = QUAD_CORNERS[gl_VertexIndex];
+
+    // the bake looks along -z, so its right is the x axis when the camera sits on +z. straight
+    // above the tree any side will do
+    vec3 to_camera = camera_info.pos.xyz - instance.center;
+    vec3 right     = normalize(vec3(to_camera.z, 0.0, -to_camera.x) + vec3(1e-6, 0.0, 0.0));
+    vec3 up        = vec3(0.0, 1.0, 0.0);
+    vec3 vert_pos  = instance.center + (right * corner.x + up * corner.y) * instance.half_size;
+    gl_Position    = camera_info.view_proj_mat * vec4(vert_pos, 1.0);
+
+    // the bake is flipped in y like the camera, its top row holds the top of the tree
+    uvec2 slot_pos  = uvec2(instance.slot % SLOTS_PER_ROW, instance.slot / SLOTS_PER_ROW);
+    vec2 uv_in_slot = vec2(corner.x, -corner.y) * 0.5 + 0.5;
+    atlas_uv        = (vec2(slot_pos) + uv_in_slot) / float(SLOTS_PER_ROW);
+
+    // the whole tree is lit like its center
+    float shadow_weight =
+        get_shadow_weight_vsm(shadow_camera_info.view_proj_mat, vec4(instance.center, 1.0));
+    float sky_visibility = sample_sky_visibility(instance.center);
+    vec3 sun_light       = sun_info.sun_color * sun_info.sun_luminance;
+    vec3 light           = sun_light * shadow_weight + shading_info.ambient_light * sky_visibility;
+
+    bottom_color = tint_by_sky_visibility(srgb_to_linear(pc.bottom_color), sky_visibility) * light;
+    tip_color    = tint_by_sky_visibility(srgb_to_linear(pc.tip_color), sky_visibility) * light;
+    lod_fade     = instance.lod_fade;
+}
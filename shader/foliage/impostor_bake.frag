@@ -0,0 +1,8 @@
+#version 450
+
+layout(location = 0) in float color_gradient;
+
+layout(location = 0) out vec4 out_color;
+
+// the colors are picked when drawing, so changing them needs no new bake
+void main() { out_color = vec4(color_gradient, 1.0, 0.0, 1.0); }
@@ -0,0 +1,46 @@
+//! The far LOD leaves of one tree into its impostor slot, still and unlit
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "../include/core/packer.glsl"
+
+layout(push_constant) uniform PC {
+    mat4 view_proj_mat; // the orthographic camera of the slot, looking along -z
+    float density;      // fraction of the leaves baked
+}
+pc;
+
+// these are vertex-rate attributes
+layout(location = 0) in uint in_packed_data;
+
+// these are instance-rate attributes
+layout(location = 1) in uvec3 in_instance_pos;
+layout(location = 2) in uint in_instance_ty;
+
+layout(location = 0) out float color_gradient;
+
+#include "../include/core/hash.glsl"
+#include "./billboard.glsl"
+#include "./density.glsl"
+#include "./unpacker.glsl"
+
+const float scaling_factor = 1.0 / 256.0;
+
+void main() {
+    ivec3 vox_local_pos;
+    uvec3 vert_offset_in_vox;
+    float wind_gradient;
+    unpack_vertex_data(vox_local_pos, vert_offset_in_vox, color_gradient, wind_gradient,
+                       in_packed_data);
+
+    vec3 instance_pos = in_instance_pos * scaling_factor;
+    vec3 voxel_pos    = (vox_local_pos + vec3(0.5)) * scaling_factor + instance_pos;
+    // looking along -z the right and up of the camera are the world axes
+    vec3 vert_pos =
+        get_vert_pos_with_billboard(mat4(1.0), voxel_pos, vert_offset_in_vox, scaling_factor);
+
+    gl_Position = is_instance_kept(in_instance_pos, pc.density)
+                      ? pc.view_proj_mat * vec4(vert_pos, 1.0)
+                      : DROPPED_VERT_POS;
+}
@@ -6,8 +6,16 @@
 
 layout(push_constant) uniform PC {
     float time;
+    float wind_sway; // scales the wind offset, 1.0 for grass
+    float density;   // fraction of the instances drawn, 1.0 for grass
     vec3 bottom_color;
     vec3 tip_color;
+    // the rest of the flora block, unused here
+    float lod_fade;
+    float lod_switch_size;
+    float lod_transition_range;
+    float lod_size_scale;
+    uint is_far_lod;
 }
 pc;
 
@@ -51,9 +59,17 @@ shadow_camera_info;
 
 layout(set = 0, binding = 5) uniform sampler2D shadow_map_tex_for_vsm_ping;
 
+layout(set = 0, binding = 6) uniform U_WindField {
+    uvec2 cell_count;
+    vec2 cell_size;                   // in world units
+    vec4 cells[MAX_WIND_FIELD_CELLS]; // xy the wind, row major in x
+}
+wind_field;
+
 #include "../include/core/fast_noise_lite.glsl"
 #include "../include/core/hash.glsl"
 #include "./billboard.glsl"
+#include "./density.glsl"
 #include "./unpacker.glsl"
 #include "./wind.glsl"
 
@@ -69,11 +85,13 @@ void main() {
 
     vec3 instance_pos = in_instance_pos * scaling_factor;
 
-    vec3 wind_offset = get_wind_offset(instance_pos.xz, wind_gradient, pc.time);
+    vec3 wind_offset = get_wind_offset(instance_pos.xz, wind_gradient, pc.time, pc.wind_sway);
     vec3 anchor_pos  = (vox_local_pos + wind_offset) * scaling_factor + instance_pos;
     vec3 voxel_pos   = anchor_pos + vec3(0.5) * scaling_factor;
     vec3 vert_pos    = get_vert_pos_with_billboard(shadow_camera_info.view_mat, voxel_pos,
                                                    vert_offset_in_vox, scaling_factor);
 
-    gl_Position = shadow_camera_info.view_proj_mat * vec4(vert_pos, 1.0);
+    gl_Position = is_instance_kept(in_instance_pos, pc.density)
+                      ? shadow_camera_info.view_proj_mat * vec4(vert_pos, 1.0)
+                      : DROPPED_VERT_POS;
 }
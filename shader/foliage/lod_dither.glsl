@@ -0,0 +1,25 @@
+#ifndef LOD_DITHER_GLSL
+#define LOD_DITHER_GLSL
+
+// ordered 4x4 bayer threshold in [0, 1), the near LOD keeps the pixels below its fade and the
+// far LOD keeps the rest, so inside the transition band every pixel is drawn by exactly one
+float lod_dither_threshold(vec2 frag_coord) {
+    const float BAYER_4X4[16] = float[](0.0, 8.0, 2.0, 10.0,  //
+                                        12.0, 4.0, 14.0, 6.0, //
+                                        3.0, 11.0, 1.0, 9.0,  //
+                                        15.0, 7.0, 13.0, 5.0);
+    ivec2 p = ivec2(frag_coord) & 3;
+    return BAYER_4X4[p.y * 4 + p.x] / 16.0;
+}
+
+// the fade of one instance inside the transition band, like LodSelector::select does it for a
+// whole chunk. size_scale is the projected size of a chunk one world unit away
+float instance_lod_fade(float switch_size, float transition_range, float size_scale,
+                        bool is_far_lod, float distance_to_camera) {
+    float size      = size_scale / max(distance_to_camera, 1e-4);
+    float band_end  = switch_size + transition_range * 0.5;
+    float far_share = clamp((band_end - size) / transition_range, 0.0, 1.0);
+    return is_far_lod ? far_share : 1.0 - far_share;
+}
+
+#endif // LOD_DITHER_GLSL
@@ -1,6 +1,33 @@
+/// Sways the foliage in the wind field of WindField.
+/// Requires:
+/// uniform U_WindField { uvec2 cell_count; vec2 cell_size; vec4 cells[]; } wind_field;
+
 #ifndef WIND_GLSL
 #define WIND_GLSL
 
+// the flutter grows with the wind up to a storm, calm air still stirs the foliage a little
+const float WIND_FLUTTER_BASE  = 0.25;
+const float WIND_FLUTTER_SLOPE = 2.5;
+// how far the tips lean over in a wind of 1.0, in voxels, gusts make them lean further
+const float WIND_BEND = 4.0;
+
+vec2 _wind_field_cell(ivec2 cell) {
+    cell = clamp(cell, ivec2(0), ivec2(wind_field.cell_count) - 1);
+    return wind_field.cells[uint(cell.y) * wind_field.cell_count.x + uint(cell.x)].xy;
+}
+
+// the wind at pos_xz (in world units), bilinearly filtered between the cell centers, the edge
+// cells reach past the field
+vec2 sample_wind_field(vec2 pos_xz) {
+    vec2 cell_pos = pos_xz / wind_field.cell_size - 0.5;
+    ivec2 base    = ivec2(floor(cell_pos));
+    vec2 t        = cell_pos - vec2(base);
+    vec2 bottom   = mix(_wind_field_cell(base), _wind_field_cell(base + ivec2(1, 0)), t.x);
+    vec2 top      = mix(_wind_field_cell(base + ivec2(0, 1)),
+                        _wind_field_cell(base + ivec2(1, 1)), t.x);
+    return mix(bottom, top, t.y);
+}
+
 vec2 rand_offset(vec2 instance_pos, float time) {
     const float wind_speed            = 0.6;
     const float wind_strength         = 5.0;
@@ -35,9 +62,14 @@ vec2 rand_offset(vec2 instance_pos, float time) {
     return vec2(noise_x, noise_z) * wind_strength + natual_state;
 }
 
-vec3 get_wind_offset(vec2 instance_pos, float gradient, float time) {
-    vec2 rand_off = rand_offset(instance_pos, time) * gradient * gradient;
-    return vec3(rand_off.x, 0.0, rand_off.y);
+// the foliage leans with the wind field and flutters around that, sway scales the offset, 1.0
+// for grass
+vec3 get_wind_offset(vec2 instance_pos, float gradient, float time, float sway) {
+    vec2 wind        = sample_wind_field(instance_pos);
+    float flutter    = WIND_FLUTTER_BASE + WIND_FLUTTER_SLOPE * min(length(wind), 1.0);
+    vec2 offset      = rand_offset(instance_pos, time) * flutter + wind * WIND_BEND;
+    vec2 wind_offset = offset * gradient * gradient * sway;
+    return vec3(wind_offset.x, 0.0, wind_offset.y);
 }
 
 #endif // WIND_GLSL
@@ -0,0 +1,21 @@
+#ifndef INDIRECT_DISPATCH_GLSL
+#define INDIRECT_DISPATCH_GLSL
+
+// the real device limits are injected by the shader compiler, the fallbacks are the minimum the
+// vulkan spec guarantees
+#ifndef MAX_DISPATCH_GROUP_COUNT_X
+#define MAX_DISPATCH_GROUP_COUNT_X 65535
+#endif
+#ifndef MAX_DISPATCH_GROUP_COUNT_Y
+#define MAX_DISPATCH_GROUP_COUNT_Y 65535
+#endif
+#ifndef MAX_DISPATCH_GROUP_COUNT_Z
+#define MAX_DISPATCH_GROUP_COUNT_Z 65535
+#endif
+
+uvec3 clamp_dispatch_group_count(uvec3 group_count) {
+    return min(group_count, uvec3(MAX_DISPATCH_GROUP_COUNT_X, MAX_DISPATCH_GROUP_COUNT_Y,
+                                  MAX_DISPATCH_GROUP_COUNT_Z));
+}
+
+#endif // INDIRECT_DISPATCH_GLSL
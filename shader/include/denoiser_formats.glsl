@@ -0,0 +1,18 @@
+//! Format qualifiers of the denoiser intermediates, they must match the formats of the textures
+//! created by the tracer. The tracer overrides them through macros, see `DenoiserPrecision`.
+#ifndef DENOISER_FORMATS_GLSL
+#define DENOISER_FORMATS_GLSL
+
+#ifndef DENOISER_RADIANCE_FORMAT
+#define DENOISER_RADIANCE_FORMAT r11f_g11f_b10f
+#endif
+
+#ifndef DENOISER_MOTION_FORMAT
+#define DENOISER_MOTION_FORMAT rg16f
+#endif
+
+#ifndef DENOISER_HIST_LEN_FORMAT
+#define DENOISER_HIST_LEN_FORMAT r8ui
+#endif
+
+#endif // DENOISER_FORMATS_GLSL
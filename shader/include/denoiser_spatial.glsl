@@ -0,0 +1,83 @@
+//! Bindings and weights shared by the A-Trous passes, `spatial.comp` and its tiled variant.
+#ifndef DENOISER_SPATIAL_GLSL
+#define DENOISER_SPATIAL_GLSL
+
+layout(push_constant) uniform PC { uint iteration; }
+pc;
+
+layout(set = 0, binding = 0) uniform U_SpatialInfo {
+    float phi_c;
+    float phi_n;
+    float phi_p;
+    float min_phi_z;
+    float max_phi_z;
+    float phi_z_stable_sample_count;
+    uint is_changing_lum_phi;
+    uint is_spatial_denoising_enabled;
+}
+spatial_info;
+
+layout(set = 1, binding = 0, r32f) readonly uniform image2D compute_depth_tex;
+
+#include "./denoiser_formats.glsl"
+
+layout(set = 2, binding = 0, r32ui) uniform uimage2D denoiser_normal_tex_write;
+layout(set = 2, binding = 1, r32ui) uniform uimage2D denoiser_normal_tex_read;
+layout(set = 2, binding = 2, rgba32f) uniform image2D denoiser_position_tex_write;
+layout(set = 2, binding = 3, rgba32f) uniform image2D denoiser_position_tex_read;
+layout(set = 2, binding = 4, r32ui) uniform uimage2D denoiser_vox_id_tex_write;
+layout(set = 2, binding = 5, r32ui) uniform uimage2D denoiser_vox_id_tex_read;
+layout(set = 2, binding = 6, r32ui) uniform uimage2D denoiser_accumed_tex_write;
+layout(set = 2, binding = 7, r32ui) uniform uimage2D denoiser_accumed_tex_read;
+layout(set = 2, binding = 8, DENOISER_MOTION_FORMAT) uniform image2D denoiser_motion_tex;
+layout(set = 2, binding = 9,
+       DENOISER_HIST_LEN_FORMAT) uniform uimage2D denoiser_temporal_hist_len_tex;
+layout(set = 2, binding = 10, r8ui) uniform uimage2D denoiser_hit_tex;
+layout(set = 2, binding = 11, DENOISER_RADIANCE_FORMAT) uniform image2D denoiser_spatial_ping_tex;
+layout(set = 2, binding = 12, DENOISER_RADIANCE_FORMAT) uniform image2D denoiser_spatial_pong_tex;
+
+#include "./core/packer.glsl"
+
+const float WAVELET_FAC = 0.5;
+const float KERNEL3x3[2][2] =
+    float[2][2](float[2](1.0, WAVELET_FAC), float[2](WAVELET_FAC, WAVELET_FAC *WAVELET_FAC));
+
+ivec2 img_size() { return imageSize(denoiser_normal_tex_write); }
+
+void load_from_pingpong(out vec3 n, out vec3 c, out vec3 p, ivec2 uv, uint iter) {
+    n = unpack_normal_v2(imageLoad(denoiser_normal_tex_write, uv).x);
+    c = (iter % 2u == 0u) ? imageLoad(denoiser_spatial_ping_tex, uv).rgb
+                          : imageLoad(denoiser_spatial_pong_tex, uv).rgb;
+    p = imageLoad(denoiser_position_tex_write, uv).xyz;
+}
+
+void save_to_pingpong(ivec2 uv, vec3 col, uint iter) {
+    if (iter % 2u == 0u)
+        imageStore(denoiser_spatial_pong_tex, uv, vec4(col, 0.0));
+    else
+        imageStore(denoiser_spatial_ping_tex, uv, vec4(col, 0.0));
+}
+
+float lum(vec3 c) { return dot(c, vec3(0.299, 0.587, 0.114)); }
+
+// edge-stopping weight of a tap from its luminance, normal and position difference to the center
+float a_trous_edge_weight(uint iter, float lum_c, vec3 n_c, vec3 p_c, vec3 c_s, vec3 n_s, vec3 p_s) {
+    float phi_c = spatial_info.phi_c;
+    if (spatial_info.is_changing_lum_phi != 0u) phi_c *= pow(2.0, -float(iter));
+    float w_c = exp(-abs(lum(c_s) - lum_c) / phi_c);
+
+    float w_n = max(0.0, pow(dot(n_c, n_s), spatial_info.phi_n));
+    float w_p = exp(-distance(p_s, p_c) / spatial_info.phi_p);
+    return w_c * w_n * w_p;
+}
+
+// weight of a tap on another voxel close to the camera, only applies once `depth_falloff` exceeds
+// `min_phi_z`, fades in with the history length so fresh pixels still get blurred
+float a_trous_voxel_weight(float depth_falloff, float hist_c) {
+    float dist_w = smoothstep(spatial_info.min_phi_z,
+                              max(spatial_info.min_phi_z, spatial_info.max_phi_z), depth_falloff);
+    dist_w *= smoothstep(0.0, spatial_info.phi_z_stable_sample_count * 256.0, hist_c);
+    return 1.0 - dist_w;
+}
+
+#endif // DENOISER_SPATIAL_GLSL
@@ -0,0 +1,76 @@
+//! Bindings and history blending shared by `temporal.comp` and its tiled variant.
+#ifndef DENOISER_TEMPORAL_GLSL
+#define DENOISER_TEMPORAL_GLSL
+
+layout(set = 0, binding = 0) uniform U_TemporalInfo {
+    float temporal_position_phi;
+    float temporal_alpha;
+    uvec4 history_invalid_rect; // min xy, max xy exclusive, in pixels
+}
+temporal_info;
+layout(set = 0, binding = 1, r32ui) uniform uimage2D compute_output_tex;
+
+#include "./denoiser_formats.glsl"
+
+layout(set = 1, binding = 0, r32ui) uniform uimage2D denoiser_normal_tex_write;
+layout(set = 1, binding = 1, r32ui) uniform uimage2D denoiser_normal_tex_read;
+layout(set = 1, binding = 2, rgba32f) uniform image2D denoiser_position_tex_write;
+layout(set = 1, binding = 3, rgba32f) uniform image2D denoiser_position_tex_read;
+layout(set = 1, binding = 4, r32ui) uniform uimage2D denoiser_vox_id_tex_write;
+layout(set = 1, binding = 5, r32ui) uniform uimage2D denoiser_vox_id_tex_read;
+layout(set = 1, binding = 6, r32ui) uniform uimage2D denoiser_accumed_tex_write;
+layout(set = 1, binding = 7, r32ui) uniform uimage2D denoiser_accumed_tex_read;
+layout(set = 1, binding = 8, DENOISER_MOTION_FORMAT) uniform image2D denoiser_motion_tex;
+layout(set = 1, binding = 9,
+       DENOISER_HIST_LEN_FORMAT) uniform uimage2D denoiser_temporal_hist_len_tex;
+layout(set = 1, binding = 10, r8ui) uniform uimage2D denoiser_hit_tex;
+layout(set = 1, binding = 11, DENOISER_RADIANCE_FORMAT) uniform image2D denoiser_spatial_ping_tex;
+layout(set = 1, binding = 12, DENOISER_RADIANCE_FORMAT) uniform image2D denoiser_spatial_pong_tex;
+
+#include "./core/packer.glsl"
+
+ivec2 img_size() { return imageSize(denoiser_normal_tex_write); }
+
+vec3 fetch_accum_color(ivec2 uv, ivec2 size) {
+    if (any(lessThan(uv, ivec2(0))) || any(greaterThanEqual(uv, size))) {
+        return vec3(0.0);
+    }
+    return unpack_rgbe(imageLoad(denoiser_accumed_tex_read, uv).x);
+}
+
+bool is_consistent(vec3 n, vec3 n_prev, vec3 p, vec3 p_prev) {
+    float n_fac = dot(n, n_prev);
+    float p_fac = exp(-distance(p, p_prev));
+    return n_fac > 0.9 && p_fac > temporal_info.temporal_position_phi;
+}
+
+// pixels in the rect drop their history for one frame, see `Tracer::invalidate_history`
+bool is_history_invalid(ivec2 uv) {
+    uvec4 rect = temporal_info.history_invalid_rect;
+    return all(greaterThanEqual(uvec2(uv), rect.xy)) && all(lessThan(uvec2(uv), rect.zw));
+}
+
+// blends the reprojected history with this frame's sample and writes the result, `sum_*` are the
+// weighted sums over the consistent history taps
+void temporal_resolve(ivec2 uv, float sum_w, vec3 sum_color, float sum_hist) {
+    vec3 raw_color = unpack_rgbe(imageLoad(compute_output_tex, uv).x);
+
+    float hist_len;
+    vec3 out_color;
+
+    if (sum_w >= 1e-6 && !is_history_invalid(uv)) {
+        sum_hist /= sum_w;
+        sum_color /= sum_w;
+        hist_len  = min(255.0, sum_hist + 1.0);
+        float a   = max(temporal_info.temporal_alpha, 1.0 / hist_len);
+        out_color = mix(sum_color, raw_color, a);
+    } else {
+        hist_len  = 1.0;
+        out_color = raw_color;
+    }
+
+    imageStore(denoiser_spatial_ping_tex, uv, vec4(out_color, 0.0));
+    imageStore(denoiser_temporal_hist_len_tex, uv, uvec4(uint(hist_len), 0u, 0u, 0u));
+}
+
+#endif // DENOISER_TEMPORAL_GLSL
@@ -0,0 +1,20 @@
+#ifndef HIZ_GLSL
+#define HIZ_GLSL
+
+// where a level of the Hi-Z pyramid lies in the pyramid texture, offset in xy and size in zw,
+// mirrors hiz_level_rect in hiz.rs
+uvec4 hiz_level_rect(uvec2 depth_extent, uint level) {
+    uvec2 size   = (depth_extent + 1) / 2;
+    uvec2 offset = uvec2(0);
+    for (uint i = 1; i <= level; i++) {
+        if (i == 1) {
+            offset = uvec2(size.x, 0);
+        } else {
+            offset.y += size.y;
+        }
+        size = (size + 1) / 2;
+    }
+    return uvec4(offset, size);
+}
+
+#endif // HIZ_GLSL
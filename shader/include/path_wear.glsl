@@ -0,0 +1,27 @@
+/// Samples the path wear map of PathWearMap.
+/// Requires:
+/// buffer B_PathWear { float data[]; } path_wear;
+
+#ifndef PATH_WEAR_GLSL
+#define PATH_WEAR_GLSL
+
+float _path_wear_texel(ivec2 texel, uvec2 map_extent) {
+    if (any(lessThan(texel, ivec2(0))) || any(greaterThanEqual(texel, ivec2(map_extent)))) {
+        return 0.0;
+    }
+    return path_wear.data[uint(texel.y) * map_extent.x + uint(texel.x)];
+}
+
+// how worn the ground at world_pos_xz (in world units) is, bilinearly filtered, 0.0 off the map
+float sample_path_wear(vec2 world_pos_xz, uvec2 map_extent) {
+    vec2 texel_pos = world_pos_xz * float(PATH_WEAR_TEXELS_PER_CHUNK) - 0.5;
+    ivec2 base     = ivec2(floor(texel_pos));
+    vec2 t         = texel_pos - vec2(base);
+    float bottom   = mix(_path_wear_texel(base, map_extent),
+                         _path_wear_texel(base + ivec2(1, 0), map_extent), t.x);
+    float top      = mix(_path_wear_texel(base + ivec2(0, 1), map_extent),
+                         _path_wear_texel(base + ivec2(1, 1), map_extent), t.x);
+    return mix(bottom, top, t.y);
+}
+
+#endif // PATH_WEAR_GLSL
@@ -0,0 +1,23 @@
+/// Octahedral mapping of the sky map written by sky_map.comp.
+
+#ifndef SKY_MAP_GLSL
+#define SKY_MAP_GLSL
+
+// y is the pole of the octahedron, so the folded seams of the lower half fall below the horizon
+vec2 sky_map_uv_of(vec3 dir) {
+    vec2 p = dir.xz / (abs(dir.x) + abs(dir.y) + abs(dir.z));
+    if (dir.y < 0.0) {
+        p = (1.0 - abs(p.yx)) * mix(vec2(-1.0), vec2(1.0), greaterThanEqual(p, vec2(0.0)));
+    }
+    return p * 0.5 + 0.5;
+}
+
+vec3 sky_map_dir_of(vec2 uv) {
+    vec2 p  = uv * 2.0 - 1.0;
+    vec3 d  = vec3(p.x, 1.0 - abs(p.x) - abs(p.y), p.y);
+    float t = max(0.0, -d.y);
+    d.xz += mix(vec2(t), vec2(-t), greaterThanEqual(d.xz, vec2(0.0)));
+    return normalize(d);
+}
+
+#endif // SKY_MAP_GLSL
@@ -0,0 +1,21 @@
+/// Samples the sky visibility map written by sky_visibility.comp.
+/// Requires:
+/// uniform sampler2D sky_visibility_tex;
+
+#ifndef SKY_VISIBILITY_GLSL
+#define SKY_VISIBILITY_GLSL
+
+// the fraction of the sky seen from the ground below world_pos, 1.0 in the open
+float sample_sky_visibility(vec3 world_pos) {
+    vec2 map_size = vec2(textureSize(sky_visibility_tex, 0));
+    vec2 uv       = world_pos.xz * float(SKY_VISIBILITY_TEXELS_PER_CHUNK) / map_size;
+    return texture(sky_visibility_tex, uv).r;
+}
+
+// darker and slightly bluer where the canopy filters the light
+vec3 tint_by_sky_visibility(vec3 color, float sky_visibility) {
+    const vec3 CANOPY_TINT = vec3(0.85, 0.95, 0.9);
+    return color * mix(CANOPY_TINT, vec3(1.0), sky_visibility);
+}
+
+#endif // SKY_VISIBILITY_GLSL
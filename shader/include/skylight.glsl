@@ -176,10 +176,10 @@ vec3 get_sky_color(vec3 view_dir, vec3 sun_dir) {
     return sky_color;
 }
 
-vec3 get_sky_color_with_sun(vec3 view_dir, vec3 sun_dir, vec3 sun_color, float sun_luminance,
-                            float sun_size) {
-    vec3 sky_color_linear = get_sky_color(view_dir, sun_dir);
-    float sun_dist        = 1.0 - dot(view_dir, sun_dir);
+// blends the sun disk over sky_color_linear
+vec3 apply_sun_disk(vec3 sky_color_linear, vec3 view_dir, vec3 sun_dir, vec3 sun_color,
+                    float sun_luminance, float sun_size) {
+    float sun_dist = 1.0 - dot(view_dir, sun_dir);
     sun_dist /= sun_size;
 
     float sun = 0.05 / max(sun_dist, 0.001) + 0.02;
@@ -198,4 +198,10 @@ vec3 get_sky_color_with_sun(vec3 view_dir, vec3 sun_dir, vec3 sun_color, float s
     return mix(sky_color_linear, luminance_sun_color, sun_blend_factor);
 }
 
+vec3 get_sky_color_with_sun(vec3 view_dir, vec3 sun_dir, vec3 sun_color, float sun_luminance,
+                            float sun_size) {
+    return apply_sun_disk(get_sky_color(view_dir, sun_dir), view_dir, sun_dir, sun_color,
+                          sun_luminance, sun_size);
+}
+
 #endif // SKYLIGHT_GLSL
@@ -0,0 +1,43 @@
+/// The terrain shape, shared by the chunk init and the world gen preview.
+/// Requires:
+/// uniform U_TerrainGenInfo {
+///     int seed;
+///     float height_scale;
+///     float frequency;
+///     int octaves;
+///     float lacunarity;
+///     float gain;
+///     float sea_level;
+/// } terrain_gen_info;
+
+#ifndef TERRAIN_GEN_GLSL
+#define TERRAIN_GEN_GLSL
+
+#include "./core/fast_noise_lite.glsl"
+#include "./voxel_types.glsl"
+
+// https://auburn.github.io/FastNoiseLite/
+float terrain_height(vec3 world_pos) {
+    fnl_state state    = fnlCreateState(terrain_gen_info.seed);
+    state.noise_type   = FNL_NOISE_PERLIN;
+    state.fractal_type = FNL_FRACTAL_FBM;
+    state.frequency    = terrain_gen_info.frequency;
+    state.octaves      = terrain_gen_info.octaves;
+    state.lacunarity   = terrain_gen_info.lacunarity;
+    state.gain         = terrain_gen_info.gain;
+    float noise_01     = fnlGetNoise3D(state, world_pos.x, world_pos.y, world_pos.z) * 0.5 + 0.5;
+    return noise_01 * terrain_gen_info.height_scale;
+}
+
+// positive below the ground, world_pos is in chunks
+float terrain_weight(vec3 world_pos) { return terrain_height(world_pos) - world_pos.y; }
+
+// what fills the voxel at world_pos, in chunks, from its terrain weight
+uint terrain_voxel_type(float weight, vec3 world_pos) {
+    if (weight >= 0.0) {
+        return VOXEL_TYPE_DIRT;
+    }
+    return world_pos.y < terrain_gen_info.sea_level ? VOXEL_TYPE_WATER : VOXEL_TYPE_EMPTY;
+}
+
+#endif // TERRAIN_GEN_GLSL
@@ -17,6 +17,8 @@ vec3 _voxel_color_by_type_srgb(uint voxel_type) {
         return voxel_colors.rock_color;
     } else if (voxel_type == VOXEL_TYPE_TRUNK) {
         return voxel_colors.trunk_color;
+    } else if (voxel_type == VOXEL_TYPE_WATER) {
+        return voxel_colors.water_color;
     }
     return vec3(0.0);
 }
@@ -9,4 +9,6 @@ const uint VOXEL_TYPE_ROCK  = 3;
 const uint VOXEL_TYPE_LEAF  = 4;
 const uint VOXEL_TYPE_TRUNK = 5;
 
+const uint VOXEL_TYPE_WATER = 6;
+
 #endif // VOXEL_TYPES_GLSL
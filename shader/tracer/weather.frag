@@ -0,0 +1,17 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "../foliage/lod_dither.glsl"
+
+layout(location = 0) in vec3 vert_color;
+layout(location = 1) flat in float coverage;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    if (lod_dither_threshold(gl_FragCoord.xy) >= coverage) {
+        discard;
+    }
+    out_color = vec4(vert_color, 1.0);
+}
@@ -0,0 +1,106 @@
+//! Rain streaks and snowflakes, one quad per particle drawn around the camera
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) out vec3 vert_color;
+layout(location = 1) flat out float coverage;
+
+layout(set = 0, binding = 0) uniform U_CameraInfo {
+    vec4 pos;
+    mat4 view_mat;
+    mat4 view_mat_inv;
+    mat4 proj_mat;
+    mat4 proj_mat_inv;
+    mat4 view_proj_mat;
+    mat4 view_proj_mat_inv;
+}
+camera_info;
+
+layout(set = 0, binding = 1) uniform U_SunInfo {
+    vec3 sun_dir;
+    float sun_size;
+    vec3 sun_color;
+    float sun_luminance;
+    float sun_altitude;
+    float sun_azimuth;
+}
+sun_info;
+
+layout(set = 0, binding = 2) uniform U_ShadingInfo { vec3 ambient_light; }
+shading_info;
+
+layout(set = 0, binding = 3) uniform U_WeatherInfo {
+    vec3 wind_velocity;      // in world units per second
+    float precipitation;     // 0 when dry, 1 in a downpour
+    float wetness;           // 0 on dry ground, 1 when soaked
+    float wind_strength;     // 0 when calm, 1 in a storm
+    uint precipitation_kind; // 0 for rain, 1 for snow
+    float time;              // in seconds
+}
+weather_info;
+
+// xyz is the place in the box around the camera in [0, 1), w a random value in [0, 1)
+layout(set = 0, binding = 4) readonly buffer B_WeatherParticles { vec4 data[]; }
+weather_particles;
+
+#include "../include/core/definitions.glsl"
+
+// the particles fill a box of this size around the camera, in world units
+const float BOX_SIZE           = 0.6;
+const float RAIN_FALL_SPEED    = 0.45;
+const float SNOW_FALL_SPEED    = 0.05;
+const float RAIN_STREAK_LENGTH = 0.02;
+const float RAIN_STREAK_WIDTH  = 0.0006;
+const float SNOWFLAKE_SIZE     = 0.0015;
+// how far the snowflakes sway while falling
+const float SNOW_FLUTTER = 0.01;
+// rain is drawn into every other pixel so the streaks look see-through
+const float RAIN_COVERAGE = 0.5;
+const vec3 RAIN_COLOR     = vec3(0.55, 0.6, 0.65);
+const vec3 SNOW_COLOR     = vec3(0.95, 0.97, 1.0);
+
+// two triangles, x runs across the particle and y along it
+const vec2 QUAD_CORNERS[6] =
+    vec2[](vec2(-1.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(-1.0, 0.0), vec2(1.0, 1.0),
+           vec2(-1.0, 1.0));
+
+void main() {
+    vec4 particle    = weather_particles.data[gl_InstanceIndex];
+    bool is_snow     = weather_info.precipitation_kind == 1;
+    float fall_speed = is_snow ? SNOW_FALL_SPEED : RAIN_FALL_SPEED;
+    // so the particles don't fall as one sheet
+    float speed_scale = mix(0.8, 1.2, particle.w);
+    vec3 velocity     = (vec3(0.0, -fall_speed, 0.0) + weather_info.wind_velocity) * speed_scale;
+
+    // the box wraps around the camera, so the particles keep their place in the world while it
+    // moves
+    vec3 camera_pos = camera_info.pos.xyz;
+    vec3 drifted    = particle.xyz * BOX_SIZE + velocity * weather_info.time;
+    vec3 pos        = camera_pos + (fract((drifted - camera_pos) / BOX_SIZE) - 0.5) * BOX_SIZE;
+
+    vec2 corner = QUAD_CORNERS[gl_VertexIndex];
+    vec3 vert_pos;
+    if (is_snow) {
+        float phase = particle.w * 2.0 * PI + weather_info.time * 1.5;
+        pos.xz += vec2(sin(phase), cos(phase * 0.7)) * SNOW_FLUTTER;
+
+        // faces the camera
+        vec3 right = camera_info.view_mat_inv[0].xyz;
+        vec3 up    = camera_info.view_mat_inv[1].xyz;
+        float size = SNOWFLAKE_SIZE * mix(0.6, 1.4, particle.w);
+        vert_pos   = pos + (right * corner.x + up * (corner.y * 2.0 - 1.0)) * size;
+    } else {
+        // trails behind the drop and turns its face to the camera
+        vec3 along = -normalize(velocity);
+        vec3 side  = normalize(cross(along, camera_pos - pos));
+
+        vert_pos =
+            pos + side * corner.x * RAIN_STREAK_WIDTH + along * corner.y * RAIN_STREAK_LENGTH;
+    }
+    gl_Position = camera_info.view_proj_mat * vec4(vert_pos, 1.0);
+
+    vec3 sun_light = sun_info.sun_color * sun_info.sun_luminance * max(sun_info.sun_dir.y, 0.0);
+    vert_color     = (is_snow ? SNOW_COLOR : RAIN_COLOR) * (sun_light + shading_info.ambient_light);
+    coverage       = is_snow ? 1.0 : RAIN_COVERAGE;
+}
@@ -0,0 +1,341 @@
+use crate::audio::{AudioBus, BusSettings, Mixer};
+use crate::tracer::{DenoiserPrecision, PassScales};
+use crate::util::full_path_from_relative;
+use anyhow::{anyhow, bail, Context, Result};
+use glam::UVec3;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Relative to the project root.
+const APP_CONFIG_PATH: &str = "app_config.toml";
+
+const MB: u64 = 1024 * 1024;
+
+/// Settings read once at startup, so they can be changed without recompiling. The mixer can
+/// also be saved back from the GUI.
+///
+/// The file is a small subset of TOML: `[section]` headers, `key = value` pairs with numbers,
+/// quoted strings or arrays of numbers, and `#` comments. A missing file is written with the
+/// defaults, missing keys keep their default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    /// Chunks of the world along each axis, a chunk spans `VOXEL_DIM` voxels, which the shaders
+    /// are compiled with and so stays in constants.toml.
+    pub chunk_dim: UVec3,
+    pub node_pool_size_in_bytes: u64,
+    pub leaf_pool_size_in_bytes: u64,
+    /// The render resolution relative to the window.
+    pub scaling_factor: f32,
+    pub denoiser_precision: DenoiserPrecision,
+    /// Frames the CPU may record ahead of the GPU, from 1 to 3.
+    pub frames_in_flight: usize,
+    /// Resolutions of the secondary passes relative to the render resolution.
+    pub pass_scales: PassScales,
+    /// Raindrops running down the camera lens while it rains.
+    pub is_rain_streaks_enabled: bool,
+    /// Raindrops landing on the camera lens while it rains.
+    pub is_lens_wetness_enabled: bool,
+    /// Volume of the music at full stem gain.
+    pub music_volume_db: f32,
+    /// Volume of a single tree's ambience, clustered sources are louder.
+    pub tree_volume_db: f32,
+    pub grass_cut_volume_db: f32,
+    /// Volume of the wind ambience in the heart of a storm's gust.
+    pub wind_volume_db: f32,
+    /// Gain and mute of the audio buses.
+    pub mixer: Mixer,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            chunk_dim: UVec3::new(5, 2, 5),
+            node_pool_size_in_bytes: 512 * MB,
+            leaf_pool_size_in_bytes: 512 * MB,
+            scaling_factor: 0.5,
+            denoiser_precision: DenoiserPrecision::Reduced,
+            frames_in_flight: 2,
+            pass_scales: PassScales::default(),
+            is_rain_streaks_enabled: true,
+            is_lens_wetness_enabled: true,
+            music_volume_db: -12.0,
+            tree_volume_db: -16.0,
+            grass_cut_volume_db: -6.0,
+            wind_volume_db: -10.0,
+            mixer: Mixer::default(),
+        }
+    }
+}
+
+fn config_path() -> PathBuf {
+    PathBuf::from(full_path_from_relative(APP_CONFIG_PATH))
+}
+
+impl AppConfig {
+    /// Reads the config file, writing the defaults to it first when there is none.
+    ///
+    /// Falls back to the defaults when the file can't be used, the file is left alone then so a
+    /// typo doesn't cost the user their settings.
+    pub fn load_or_create() -> Self {
+        let path = config_path();
+        if !path.is_file() {
+            let config = Self::default();
+            match std::fs::write(&path, config.serialize()) {
+                Ok(()) => log::info!("Wrote the default app config to {}", path.display()),
+                Err(e) => log::warn!("Failed to write {}: {}", path.display(), e),
+            }
+            return config;
+        }
+
+        let result = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))
+            .and_then(|source| {
+                Self::parse(&source)
+                    .with_context(|| format!("Malformed app config {}", path.display()))
+            });
+        match result {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!("Using the default app config: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Overwrites the config file with these settings, comments the user added are lost.
+    pub fn save(&self) -> Result<()> {
+        let path = config_path();
+        std::fs::write(&path, self.serialize())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn serialize(&self) -> String {
+        let denoiser_precision = match self.denoiser_precision {
+            DenoiserPrecision::Reduced => "reduced",
+            DenoiserPrecision::Full => "full",
+        };
+        let mut text = format!(
+            "# Startup settings, read once at launch. Delete this file to get the defaults back.\n\
+             \n\
+             [world]\n\
+             # chunks along x, y and z, the world snapshot is ignored when they change\n\
+             chunk_dim = [{}, {}, {}]\n\
+             \n\
+             [contree]\n\
+             # pool sizes in MB\n\
+             node_pool_mb = {}\n\
+             leaf_pool_mb = {}\n\
+             \n\
+             [render]\n\
+             # render resolution relative to the window\n\
+             scaling_factor = {:?}\n\
+             # \"reduced\" or \"full\"\n\
+             denoiser_precision = \"{}\"\n\
+             # frames recorded ahead of the gpu, 1 to 3\n\
+             frames_in_flight = {}\n\
+             \n\
+             [quality]\n\
+             # god ray resolution relative to the render resolution, 0.25 to 1\n\
+             god_ray_scale = {:?}\n\
+             \n\
+             [camera_effects]\n\
+             # raindrops on the lens while it rains\n\
+             rain_streaks = {}\n\
+             lens_wetness = {}\n\
+             \n\
+             [audio]\n\
+             music_volume_db = {:?}\n\
+             tree_volume_db = {:?}\n\
+             grass_cut_volume_db = {:?}\n\
+             wind_volume_db = {:?}\n",
+            self.chunk_dim.x,
+            self.chunk_dim.y,
+            self.chunk_dim.z,
+            self.node_pool_size_in_bytes / MB,
+            self.leaf_pool_size_in_bytes / MB,
+            self.scaling_factor,
+            denoiser_precision,
+            self.frames_in_flight,
+            self.pass_scales.god_ray,
+            self.is_rain_streaks_enabled,
+            self.is_lens_wetness_enabled,
+            self.music_volume_db,
+            self.tree_volume_db,
+            self.grass_cut_volume_db,
+            self.wind_volume_db,
+        );
+        text.push_str("\n[mixer]\n# gain of each bus in dB, muted buses are silent\n");
+        for bus in AudioBus::ALL {
+            let settings = self.mixer.bus(bus);
+            text.push_str(&format!(
+                "{name}_gain_db = {:?}\n{name}_muted = {}\n",
+                settings.gain_db,
+                settings.is_muted,
+                name = bus.name(),
+            ));
+        }
+        text
+    }
+
+    fn parse(source: &str) -> Result<Self> {
+        let mut entries = parse_entries(source)?;
+        let mut config = Self::default();
+
+        if let Some(value) = entries.remove("world.chunk_dim") {
+            let dims = parse_u32_array(&value)?;
+            let [x, y, z] = dims[..] else {
+                bail!("world.chunk_dim: expected 3 values, got {}", dims.len());
+            };
+            config.chunk_dim = UVec3::new(x, y, z);
+            if config.chunk_dim.min_element() == 0 {
+                bail!("world.chunk_dim: every axis needs at least one chunk");
+            }
+        }
+        if let Some(value) = entries.remove("contree.node_pool_mb") {
+            config.node_pool_size_in_bytes =
+                parse_number::<u64>("contree.node_pool_mb", &value)? * MB;
+        }
+        if let Some(value) = entries.remove("contree.leaf_pool_mb") {
+            config.leaf_pool_size_in_bytes =
+                parse_number::<u64>("contree.leaf_pool_mb", &value)? * MB;
+        }
+        if let Some(value) = entries.remove("render.scaling_factor") {
+            config.scaling_factor = parse_number("render.scaling_factor", &value)?;
+            if !(0.1..=2.0).contains(&config.scaling_factor) {
+                bail!(
+                    "render.scaling_factor: {} is outside of [0.1, 2.0]",
+                    config.scaling_factor
+                );
+            }
+        }
+        if let Some(value) = entries.remove("render.denoiser_precision") {
+            config.denoiser_precision = match parse_string(&value)? {
+                "reduced" => DenoiserPrecision::Reduced,
+                "full" => DenoiserPrecision::Full,
+                other => bail!("render.denoiser_precision: unknown precision {}", other),
+            };
+        }
+        if let Some(value) = entries.remove("render.frames_in_flight") {
+            config.frames_in_flight = parse_number("render.frames_in_flight", &value)?;
+            if !(1..=3).contains(&config.frames_in_flight) {
+                bail!(
+                    "render.frames_in_flight: {} is outside of [1, 3]",
+                    config.frames_in_flight
+                );
+            }
+        }
+        if let Some(value) = entries.remove("quality.god_ray_scale") {
+            config.pass_scales.god_ray = parse_number("quality.god_ray_scale", &value)?;
+            if !(0.25..=1.0).contains(&config.pass_scales.god_ray) {
+                bail!(
+                    "quality.god_ray_scale: {} is outside of [0.25, 1.0]",
+                    config.pass_scales.god_ray
+                );
+            }
+        }
+        if let Some(value) = entries.remove("camera_effects.rain_streaks") {
+            config.is_rain_streaks_enabled = parse_bool("camera_effects.rain_streaks", &value)?;
+        }
+        if let Some(value) = entries.remove("camera_effects.lens_wetness") {
+            config.is_lens_wetness_enabled = parse_bool("camera_effects.lens_wetness", &value)?;
+        }
+        if let Some(value) = entries.remove("audio.music_volume_db") {
+            config.music_volume_db = parse_number("audio.music_volume_db", &value)?;
+        }
+        if let Some(value) = entries.remove("audio.tree_volume_db") {
+            config.tree_volume_db = parse_number("audio.tree_volume_db", &value)?;
+        }
+        if let Some(value) = entries.remove("audio.grass_cut_volume_db") {
+            config.grass_cut_volume_db = parse_number("audio.grass_cut_volume_db", &value)?;
+        }
+        if let Some(value) = entries.remove("audio.wind_volume_db") {
+            config.wind_volume_db = parse_number("audio.wind_volume_db", &value)?;
+        }
+        for bus in AudioBus::ALL {
+            let mut settings = BusSettings::default();
+            let key = format!("mixer.{}_gain_db", bus.name());
+            if let Some(value) = entries.remove(&key) {
+                settings.gain_db = parse_number(&key, &value)?;
+            }
+            let key = format!("mixer.{}_muted", bus.name());
+            if let Some(value) = entries.remove(&key) {
+                settings.is_muted = parse_bool(&key, &value)?;
+            }
+            config.mixer.set_bus(bus, settings);
+        }
+
+        for key in entries.keys() {
+            log::warn!("Ignoring unknown app config key {}", key);
+        }
+        Ok(config)
+    }
+}
+
+/// Returns: the raw values keyed by `section.key`
+fn parse_entries(source: &str) -> Result<HashMap<String, String>> {
+    let mut entries = HashMap::new();
+    let mut section = String::new();
+    for (line_no, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("line {}: unclosed section header", line_no + 1))?;
+            section = name.trim().to_string();
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected key = value", line_no + 1))?;
+        let key = format!("{}.{}", section, key.trim());
+        if entries
+            .insert(key.clone(), value.trim().to_string())
+            .is_some()
+        {
+            bail!("line {}: {} is set twice", line_no + 1, key);
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("{}: {} is not a valid number", key, value))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        other => bail!("{}: expected true or false, got {}", key, other),
+    }
+}
+
+fn parse_string(value: &str) -> Result<&str> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("expected a quoted string, got {}", value))
+}
+
+fn parse_u32_array(value: &str) -> Result<Vec<u32>> {
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or_else(|| anyhow!("expected an array, got {}", value))?;
+    inner
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse()
+                .with_context(|| format!("{} is not a valid integer", v.trim()))
+        })
+        .collect()
+}
@@ -1,22 +1,45 @@
 use super::core::App;
+use super::headless_render::{HeadlessRenderDesc, HeadlessRenderer};
+use crate::engine::Engine;
+use anyhow::Result;
 use winit::{
     application::ApplicationHandler, event::WindowEvent, event_loop::ActiveEventLoop,
     window::WindowId,
 };
 
-#[derive(Default)]
 pub struct AppController {
-    initialized: Option<App>,
+    initialized: Option<Engine>,
+    /// Called right before each frame is rendered.
+    update: Box<dyn FnMut(&mut Engine)>,
+}
+
+impl AppController {
+    pub fn new(update: Box<dyn FnMut(&mut Engine)>) -> Self {
+        Self {
+            initialized: None,
+            update,
+        }
+    }
+
+    /// Renders the sequence of `desc` into image files without opening a window or running an
+    /// event loop.
+    pub fn run_headless(desc: &HeadlessRenderDesc) -> Result<()> {
+        let mut renderer = HeadlessRenderer::new(desc.width, desc.height)?;
+        renderer.render(desc)
+    }
 }
 
 impl ApplicationHandler for AppController {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.initialized = Some(App::new(event_loop).unwrap());
+        self.initialized = Some(Engine::new(App::new(event_loop).unwrap()));
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
         if let Some(initialized) = &mut self.initialized {
-            initialized.on_window_event(event_loop, id, event);
+            if matches!(event, WindowEvent::RedrawRequested) {
+                (self.update)(initialized);
+            }
+            initialized.app_mut().on_window_event(event_loop, id, event);
         } else {
             panic!("App is not initialized");
         }
@@ -29,7 +52,9 @@ impl ApplicationHandler for AppController {
         event: winit::event::DeviceEvent,
     ) {
         if let Some(initialized) = &mut self.initialized {
-            initialized.on_device_event(event_loop, device_id, event);
+            initialized
+                .app_mut()
+                .on_device_event(event_loop, device_id, event);
         } else {
             panic!("App is not initialized");
         }
@@ -37,7 +62,7 @@ impl ApplicationHandler for AppController {
 
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
         if let Some(initialized) = &mut self.initialized {
-            initialized.on_about_to_wait(_event_loop);
+            initialized.app_mut().on_about_to_wait(_event_loop);
         } else {
             panic!("App is not initialized");
         }
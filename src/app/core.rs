@@ -1,33 +1,64 @@
 #[allow(unused)]
-use crate::util::Timer;
-
-use crate::audio::{SpatialSoundManager, TreeAudioManager};
-use crate::builder::{ContreeBuilder, PlainBuilder, SceneAccelBuilder, SurfaceBuilder};
-use crate::geom::{build_bvh, UAabb3};
-use crate::procedual_placer::{generate_positions, PlacerDesc};
-use crate::tracer::{Tracer, TracerDesc};
+use crate::util::{profile_scope, Timer};
+
+use super::app_config::AppConfig;
+use super::forest_generation::ForestGeneration;
+use super::planting::{PlantRequest, PlantingTool};
+use super::probe_volume::ProbeVolumeTool;
+use super::save_slot::{save_slot_time, SaveSlot, WorldEdit, SAVE_SLOT_COUNT};
+use super::self_test::{
+    check_prefix_sum, check_radix_sort, check_scene_accel, check_terrain_query, SelfTest,
+};
+use super::voxel_edit::VoxelEditTool;
+use super::world_snapshot::{ChunkSnapshot, TreePlacement, WorldSnapshot};
+use crate::audio::{
+    AudioAutomation, AudioBus, AutomationInputs, MusicManager, SoundOcclusion, SoundOcclusionDesc,
+    SpatialSoundManager, TreeAudioManager, WindAmbience,
+};
+use crate::builder::{
+    ChunkContreeInfo, ChunkStreamer, ChunkStreamerDesc, ChunkStreamingWork, ContreeBuilder,
+    FloraType, PlainBuilder, PoolExhausted, SceneAccelBuilder, SurfaceBuilder, TerrainGenDesc,
+    WorldGenPreview, FLORA_TYPES,
+};
+use crate::constants::VOXEL_DIM;
+use crate::gameplay::{
+    CameraFeelDesc, CameraPathPlayer, CharacterControllerDesc, PathWearDesc, PathWearMap,
+    PhotoMode, PhotoModeReturn, SeasonalLeaves, Weather, WindField, WindFieldDesc, WorldClock,
+};
+use crate::geom::{build_bvh, Aabb3, BvhNode, ChunkIdx, RoundCone, UAabb3, VoxelPos, WorldPos};
+use crate::procedual_placer::{generate_positions, PlacerDesc, PropPlacer};
+use crate::tracer::{
+    FloraBlendMode, FloraLodDesc, RainLensSettings, TerrainMiss, Tracer, TracerDesc,
+    TracerSettings, VoxelMaterial,
+};
 use crate::tree_gen::{Tree, TreeDesc};
-use crate::util::{get_sun_dir, ShaderCompiler};
+use crate::util::{debug_heat_color, full_path_from_relative, DebugDraw, ShaderCompiler};
+use crate::util::{write_diagnostics_bundle, CapturedFrame, DIAGNOSTICS};
 use crate::util::{TimeInfo, BENCH};
-use crate::vkn::{Allocator, CommandBuffer, Fence, Semaphore, SwapchainDesc};
+use crate::vkn::{
+    is_validation_layer_active, is_validation_requested, reset_validation_messages,
+    set_validation_settings, validation_settings, validation_stats, Allocator, FramesInFlight,
+    MemoryBarrier, PipelineBarrier, SwapchainDesc, VALIDATION_ENV_VAR,
+};
 use crate::{
     egui_renderer::EguiRenderer,
     vkn::{Swapchain, VulkanContext, VulkanContextDesc},
     window::{WindowMode, WindowState, WindowStateDesc},
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use ash::vk;
 use egui::{Color32, RichText};
-use glam::{UVec3, Vec2, Vec3};
+use glam::{UVec3, Vec2, Vec3, Vec3Swizzles};
 use gpu_allocator::vulkan::AllocatorCreateDesc;
-use rand::Rng;
-use std::collections::HashSet;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 use winit::event::DeviceEvent;
 use winit::{
-    event::{ElementState, WindowEvent},
+    event::{ElementState, MouseButton, WindowEvent},
     event_loop::ActiveEventLoop,
     keyboard::KeyCode,
     window::WindowId,
@@ -157,18 +188,36 @@ impl TreeVariationConfig {
     }
 }
 
+/// A frame whose swapchain image is acquired, handed from one phase of [`App::render_frame`] to
+/// the next. It has to be submitted and presented once it exists.
+struct FrameContext {
+    /// Seconds since the previous frame.
+    delta_time: f32,
+    /// The swapchain image the frame is presented to.
+    image_idx: u32,
+}
+
+/// Chunk operations requested from the world debug panel, applied after the gui pass.
+#[derive(Debug, Clone, Copy)]
+enum ChunkDebugAction {
+    Evict(ChunkIdx),
+    Rebuild(ChunkIdx),
+    Checksum(ChunkIdx),
+}
+
 pub struct App {
+    config: AppConfig,
     egui_renderer: EguiRenderer,
-    cmdbuf: CommandBuffer,
     window_state: WindowState,
     is_resize_pending: bool,
     swapchain: Swapchain,
-    image_available_semaphore: Semaphore,
-    render_finished_semaphore: Semaphore,
-    fence: Fence,
+    frames_in_flight: FramesInFlight,
     time_info: TimeInfo,
     accumulated_mouse_delta: Vec2,
     smoothed_mouse_delta: Vec2,
+    /// In physical pixels from the top left of the window.
+    cursor_position: Vec2,
+    is_shift_held: bool,
 
     tracer: Tracer,
 
@@ -177,127 +226,143 @@ pub struct App {
     surface_builder: SurfaceBuilder,
     contree_builder: ContreeBuilder,
     scene_accel_builder: SceneAccelBuilder,
+    world_gen_preview: WorldGenPreview,
 
     // gui adjustables
-    debug_float: f32,
-    debug_bool: bool,
-    debug_uint: u32,
-    lod_distance: f32,
+    tracer_settings: TracerSettings,
+    flora_lod_desc: FloraLodDesc,
+    flora_blend_mode: FloraBlendMode,
     leaves_inner_density: f32,
     leaves_outer_density: f32,
     leaves_inner_radius: f32,
     leaves_outer_radius: f32,
-    sun_altitude: f32,
-    sun_azimuth: f32,
-    sun_size: f32,
-    auto_daynight_cycle: bool,
-    time_of_day: f32,
-    latitude: f32,
-    season: f32,
-    day_cycle_minutes: f32,
-    sun_color: egui::Color32,
-    sun_luminance: f32,
-    ambient_light: egui::Color32,
-    temporal_position_phi: f32,
-    temporal_alpha: f32,
-    god_ray_max_depth: f32,
-    god_ray_max_checks: u32,
-    god_ray_weight: f32,
-    god_ray_color: egui::Color32,
-    phi_c: f32,
-    phi_n: f32,
-    phi_p: f32,
-    min_phi_z: f32,
-    max_phi_z: f32,
-    phi_z_stable_sample_count: f32,
-    is_changing_lum_phi: bool,
-    is_spatial_denoising_enabled: bool,
-    a_trous_iteration_count: u32,
-    is_taa_enabled: bool,
+    world_clock: WorldClock,
+    /// Periodically read back the rendered frame so a crash bundle can include it.
+    is_frame_capture_enabled: bool,
     debug_tree_pos: Vec3,
     config_panel_visible: bool,
     is_fly_mode: bool,
+    camera_feel_desc: CameraFeelDesc,
+    character_controller_desc: CharacterControllerDesc,
+    camera_path_player: CameraPathPlayer,
+    photo_mode: PhotoMode,
+    /// Seconds the foliage swayed for, it holds still in photo mode.
+    scene_time: f32,
+    debug_draw: DebugDraw,
+    planting_tool: PlantingTool,
+    voxel_edit_tool: VoxelEditTool,
+    probe_volume_tool: ProbeVolumeTool,
+    /// Player edits since the world was last rebuilt from its seed, written to the save slots.
+    world_edits: Vec<WorldEdit>,
+    /// Set once the whole procedural forest is planted, a cancelled forest isn't saved.
+    is_forest_generated: bool,
+    /// The forest being planted over the coming frames.
+    forest_generation: Option<ForestGeneration>,
+    /// Last checksums computed from the world debug panel.
+    chunk_checksums: HashMap<ChunkIdx, u64>,
+    chunk_streamer: ChunkStreamer,
+    is_chunk_streaming_enabled: bool,
+    path_wear: PathWearMap,
 
     debug_tree_desc: TreeDesc,
     tree_variation_config: TreeVariationConfig,
     regenerate_trees_requested: bool,
+    /// Terrain being tried out in the world gen preview, the world keeps its own until applied.
+    terrain_gen_desc: TerrainGenDesc,
+    tree_placer_desc: PlacerDesc,
+    /// Cell size of the tree placer, in voxels.
+    tree_grid_size: f32,
+    prop_placer: PropPlacer,
+    is_world_gen_preview_visible: bool,
+    /// The preview doesn't show the current parameters or chunk yet.
+    is_world_gen_preview_dirty: bool,
+    world_gen_preview_chunk: UVec3,
+    apply_world_gen_requested: bool,
+    pending_slot_load: Option<usize>,
     prev_bound: UAabb3,
 
     // multi-tree management
     next_tree_id: u32,
     single_tree_id: u32, // ID for GUI single tree mode
+    /// Clicks on the world plant the GUI tree where the cursor points while the cursor is shown,
+    /// shift-clicks remove the nearest tree.
+    is_tree_picking_active: bool,
+    /// Every tree with leaves in the scene, by tree id, written to world snapshots.
+    placed_trees: HashMap<u32, TreePlacement>,
+
+    // (bottom, tip) colors of each flora type
+    flora_colors: Vec<(egui::Color32, egui::Color32)>,
 
-    // starlight parameters
-    starlight_iterations: i32,
-    starlight_formuparam: f32,
-    starlight_volsteps: i32,
-    starlight_stepsize: f32,
-    starlight_zoom: f32,
-    starlight_tile: f32,
-    starlight_speed: f32,
-    starlight_brightness: f32,
-    starlight_darkmatter: f32,
-    starlight_distfading: f32,
-    starlight_saturation: f32,
-
-    // grass colors
-    grass_bottom_color: egui::Color32,
-    grass_tip_color: egui::Color32,
-
-    // lavender colors
-    lavender_bottom_color: egui::Color32,
-    lavender_tip_color: egui::Color32,
+    // grass cutting, radius in world units
+    grass_cut_radius: f32,
+    grass_regrowth_hours: f32,
 
     // leaf colors
     leaves_bottom_color: egui::Color32,
     leaves_tip_color: egui::Color32,
-
-    // voxel colors
-    voxel_sand_color: egui::Color32,
-    voxel_dirt_color: egui::Color32,
-    voxel_rock_color: egui::Color32,
-    voxel_leaf_color: egui::Color32,
-    voxel_trunk_color: egui::Color32,
+    seasonal_leaves: SeasonalLeaves,
 
     // note: always keep the context to end, as it has to be destroyed last
     vulkan_ctx: VulkanContext,
 
     // Keep ownership so the shared PetalSonic engine outlives every subsystem.
-    #[allow(dead_code)]
     spatial_sound_manager: SpatialSoundManager,
     tree_audio_manager: TreeAudioManager,
+    music_manager: MusicManager,
+    wind_ambience: WindAmbience,
+    sound_occlusion: SoundOcclusion,
+    audio_automation: AudioAutomation,
+    /// The audio automation follows its wind.
+    weather: Weather,
+    /// Gusts along the wind of the weather, sways the foliage and the wind ambience.
+    wind_field: WindField,
+    /// Set by F12, the screenshot is taken once the frame is presented.
+    is_screenshot_requested: bool,
 }
 
-const VOXEL_DIM_PER_CHUNK: UVec3 = UVec3::new(256, 256, 256);
-const CHUNK_DIM: UVec3 = UVec3::new(5, 2, 5);
-const FREE_ATLAS_DIM: UVec3 = UVec3::new(512, 512, 512);
+pub(super) const VOXEL_DIM_PER_CHUNK: UVec3 = UVec3::splat(VOXEL_DIM);
+pub(super) const FREE_ATLAS_DIM: UVec3 = UVec3::new(512, 512, 512);
+pub(super) const LEAVES_BOTTOM_COLOR: Color32 = Color32::from_rgb(232, 142, 0);
+pub(super) const LEAVES_TIP_COLOR: Color32 = Color32::from_rgb(255, 219, 71);
+/// How far below the eye grass gets cut, in world units.
+const GRASS_CUT_HEIGHT: f32 = 0.15;
+const GRASS_CUT_SOUND_PATH: &str =
+    "assets/sfx/Footsteps SFX - Undergrowth & Leaves/TomWinandySFX - FS_UndergrowthLeaves_jump_03.wav";
+/// Where the camera starts, the height comes from the ground there.
+const SPAWN_POS_XZ: Vec2 = Vec2::new(0.5, 0.5);
+/// Seed of the procedural tree placement.
+const TREE_PLACER_SEED: u32 = 42;
+/// Time spent planting procedural trees each frame, at least one tree is planted per frame.
+const FOREST_GENERATION_FRAME_BUDGET: Duration = Duration::from_millis(8);
+const SCREENSHOT_DIR: &str = "screenshots/";
+/// Relative to the project root, shared with the headless renderer.
+pub(super) const CAMERA_PATH_FILE: &str = "saves/camera_path.txt";
+/// How far from a tree's base a shift-click in tree picking still removes it, in world units.
+const TREE_REMOVAL_PICK_RADIUS: f32 = 0.25;
 
 impl App {
     pub fn new(_event_loop: &ActiveEventLoop) -> Result<Self> {
         let sum = fora_audio::add(1, 2);
         log::info!("sum: {}", sum);
 
-        let chunk_bound = UAabb3::new(UVec3::ZERO, CHUNK_DIM);
+        let config = AppConfig::load_or_create();
+        let chunk_bound = UAabb3::new(UVec3::ZERO, config.chunk_dim);
         let window_state = Self::create_window_state(_event_loop);
         let vulkan_ctx = Self::create_vulkan_context(&window_state);
+        {
+            let mut diagnostics = DIAGNOSTICS.lock().unwrap();
+            diagnostics.gpu_info = vulkan_ctx.device_summary();
+            diagnostics.world_seed = TREE_PLACER_SEED as u64;
+        }
 
-        let shader_compiler = ShaderCompiler::new().unwrap();
-
-        let device = vulkan_ctx.device();
+        let mut shader_compiler = ShaderCompiler::new().unwrap();
+        shader_compiler.set_dispatch_group_limits(vulkan_ctx.max_compute_work_group_count());
+        // set it to "full" in the config to compare the denoiser against 32-bit intermediates
+        config
+            .denoiser_precision
+            .define_shader_macros(&mut shader_compiler);
 
-        let gpu_allocator = {
-            let allocator_create_info = AllocatorCreateDesc {
-                instance: vulkan_ctx.instance().as_raw().clone(),
-                device: device.as_raw().clone(),
-                physical_device: vulkan_ctx.physical_device().as_raw(),
-                debug_settings: Default::default(),
-                buffer_device_address: true,
-                allocation_sizes: Default::default(),
-            };
-            gpu_allocator::vulkan::Allocator::new(&allocator_create_info)
-                .expect("Failed to create gpu allocator")
-        };
-        let allocator = Allocator::new(device, Arc::new(Mutex::new(gpu_allocator)));
+        let allocator = Self::create_allocator(&vulkan_ctx);
 
         let swapchain = Swapchain::new(
             vulkan_ctx.clone(),
@@ -308,12 +373,11 @@ impl App {
             },
         );
 
-        let image_available_semaphore = Semaphore::new(device);
-        let render_finished_semaphore = Semaphore::new(device);
-
-        let fence = Fence::new(device, true);
-
-        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.command_pool());
+        let frames_in_flight = FramesInFlight::new(
+            vulkan_ctx.clone(),
+            config.frames_in_flight,
+            swapchain.image_count(),
+        );
 
         let renderer = EguiRenderer::new(
             vulkan_ctx.clone(),
@@ -321,13 +385,14 @@ impl App {
             allocator.clone(),
             &shader_compiler,
             swapchain.get_render_pass(),
+            frames_in_flight.frame_count(),
         );
 
         let mut plain_builder = PlainBuilder::new(
             vulkan_ctx.clone(),
             &shader_compiler,
             allocator.clone(),
-            CHUNK_DIM * VOXEL_DIM_PER_CHUNK,
+            config.chunk_dim * VOXEL_DIM_PER_CHUNK,
             FREE_ATLAS_DIM,
         );
 
@@ -346,8 +411,8 @@ impl App {
             &shader_compiler,
             surface_builder.get_resources(),
             VOXEL_DIM_PER_CHUNK,
-            512 * 1024 * 1024, // node buffer pool size
-            512 * 1024 * 1024, // leaf buffer pool size
+            config.node_pool_size_in_bytes,
+            config.leaf_pool_size_in_bytes,
         );
 
         let mut scene_accel_builder = SceneAccelBuilder::new(
@@ -357,19 +422,56 @@ impl App {
             chunk_bound,
         )?;
 
-        Self::init(
+        let world_gen_preview = WorldGenPreview::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            VOXEL_DIM,
+        )?;
+
+        let mut self_test = SelfTest::new_if_due(&vulkan_ctx);
+        if let Some(self_test) = self_test.as_mut() {
+            self_test.run("prefix_sum", || {
+                check_prefix_sum(&vulkan_ctx, &allocator, &shader_compiler)
+            });
+            self_test.run("radix_sort", || {
+                check_radix_sort(&vulkan_ctx, &allocator, &shader_compiler)
+            });
+            self_test.run("scene_accel", || {
+                check_scene_accel(&vulkan_ctx, &mut scene_accel_builder)
+            });
+        }
+
+        let world_snapshot = Self::load_world_snapshot(
+            config.chunk_dim,
             &mut plain_builder,
             &mut surface_builder,
             &mut contree_builder,
             &mut scene_accel_builder,
-        )?;
+        );
+        if world_snapshot.is_none() {
+            Self::init(
+                config.chunk_dim,
+                &mut plain_builder,
+                &mut surface_builder,
+                &mut contree_builder,
+                &mut scene_accel_builder,
+            )?;
+        }
 
         // Shared spatial audio engine (PetalSonic) used by both the tracer (camera)
         // and the app-level tree ambience sources.
         let spatial_sound_manager = SpatialSoundManager::new(1024)?;
-        let tree_audio_manager = TreeAudioManager::new(spatial_sound_manager.clone());
-
-        let tracer = Tracer::new(
+        spatial_sound_manager.set_mixer(config.mixer)?;
+        let tree_audio_manager =
+            TreeAudioManager::new(spatial_sound_manager.clone(), config.tree_volume_db);
+        let music_manager =
+            MusicManager::new(spatial_sound_manager.clone(), config.music_volume_db);
+        let wind_ambience = WindAmbience::new(spatial_sound_manager.clone(), config.wind_volume_db);
+        let sound_occlusion =
+            SoundOcclusion::new(SoundOcclusionDesc::default(), spatial_sound_manager.clone());
+
+        let mut tracer = Tracer::new(
             vulkan_ctx.clone(),
             allocator.clone(),
             &shader_compiler,
@@ -378,26 +480,51 @@ impl App {
             contree_builder.get_resources(),
             scene_accel_builder.get_resources(),
             TracerDesc {
-                scaling_factor: 0.5,
+                scaling_factor: config.scaling_factor,
+                pass_scales: config.pass_scales,
+                denoiser_precision: config.denoiser_precision,
+                autotune_workgroup_sizes: true,
+                frames_in_flight: frames_in_flight.frame_count(),
             },
             spatial_sound_manager.clone(),
         )?;
+        tracer.set_sea_level(plain_builder.terrain_gen_desc().sea_level);
+
+        if let Some(mut self_test) = self_test {
+            self_test.run("terrain_query", || {
+                check_terrain_query(&mut tracer, config.chunk_dim)
+            });
+            self_test.finish();
+        }
 
         let debug_tree_pos = Vec3::new(2.0, 0.2, 2.0);
 
+        let tracer_settings = TracerSettings {
+            rain_lens: RainLensSettings {
+                is_streaks_enabled: config.is_rain_streaks_enabled,
+                is_lens_wetness_enabled: config.is_lens_wetness_enabled,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let terrain_gen_desc = plain_builder.terrain_gen_desc().clone();
+        let mut tree_placer_desc = PlacerDesc::new(TREE_PLACER_SEED);
+        tree_placer_desc.threshold = 0.55;
+
         let mut app = Self {
+            config,
             vulkan_ctx,
             egui_renderer: renderer,
             window_state,
 
             accumulated_mouse_delta: Vec2::ZERO,
             smoothed_mouse_delta: Vec2::ZERO,
+            cursor_position: Vec2::ZERO,
+            is_shift_held: false,
 
-            cmdbuf,
             swapchain,
-            image_available_semaphore,
-            render_finished_semaphore,
-            fence,
+            frames_in_flight,
 
             tracer,
 
@@ -405,94 +532,99 @@ impl App {
             surface_builder,
             contree_builder,
             scene_accel_builder,
+            world_gen_preview,
 
             is_resize_pending: false,
             time_info: TimeInfo::default(),
 
-            debug_float: 0.0,
-            debug_bool: true,
-            debug_uint: 0,
-            lod_distance: 1.5,
+            tracer_settings,
+            flora_lod_desc: FloraLodDesc::default(),
+            flora_blend_mode: FloraBlendMode::default(),
             leaves_inner_density: 0.38,
             leaves_outer_density: 0.45,
             leaves_inner_radius: 12.0,
             leaves_outer_radius: 17.0,
-            temporal_position_phi: 0.8,
-            temporal_alpha: 0.08,
-            god_ray_max_depth: 2.0,
-            god_ray_max_checks: 32,
-            god_ray_weight: 0.4,
-            god_ray_color: egui::Color32::from_rgb(255, 240, 178),
-            phi_c: 0.75,
-            phi_n: 20.0,
-            phi_p: 0.05,
-            min_phi_z: 0.0,
-            max_phi_z: 0.5,
-            phi_z_stable_sample_count: 0.05,
-            is_changing_lum_phi: true,
-            is_spatial_denoising_enabled: true,
-            a_trous_iteration_count: 3,
-            is_taa_enabled: false,
-            sun_altitude: 0.25,
-            sun_azimuth: 0.8,
-            sun_size: 0.1,
-            auto_daynight_cycle: true,
-            time_of_day: 0.65,
-            latitude: 0.5,
-            season: 0.25,
-            day_cycle_minutes: 30.0,
-            sun_color: egui::Color32::from_rgb(255, 233, 144),
-            sun_luminance: 1.0,
-            ambient_light: egui::Color32::from_rgb(100, 48, 3),
+            is_frame_capture_enabled: false,
+            world_clock: WorldClock::default(),
             debug_tree_pos,
             debug_tree_desc: TreeDesc::default(),
             tree_variation_config: TreeVariationConfig::default(),
             regenerate_trees_requested: false,
+            is_screenshot_requested: false,
+            terrain_gen_desc,
+            tree_placer_desc,
+            tree_grid_size: 120.0,
+            prop_placer: PropPlacer::new(TREE_PLACER_SEED),
+            is_world_gen_preview_visible: false,
+            is_world_gen_preview_dirty: true,
+            world_gen_preview_chunk: UVec3::ZERO,
+            apply_world_gen_requested: false,
+            pending_slot_load: None,
             prev_bound: Default::default(),
             config_panel_visible: false,
             is_fly_mode: true,
+            camera_feel_desc: CameraFeelDesc::default(),
+            character_controller_desc: CharacterControllerDesc::default(),
+            camera_path_player: CameraPathPlayer::default(),
+            photo_mode: PhotoMode::default(),
+            scene_time: 0.0,
+            debug_draw: DebugDraw::new(),
+            planting_tool: PlantingTool::default(),
+            voxel_edit_tool: VoxelEditTool::default(),
+            probe_volume_tool: ProbeVolumeTool::default(),
+            world_edits: Vec::new(),
+            is_forest_generated: false,
+            forest_generation: None,
+            chunk_checksums: HashMap::new(),
+            chunk_streamer: ChunkStreamer::new(ChunkStreamerDesc::default(), chunk_bound),
+            is_chunk_streaming_enabled: false,
+            path_wear: PathWearMap::new(PathWearDesc::default(), &chunk_bound),
+
+            flora_colors: FLORA_TYPES
+                .iter()
+                .map(|desc| {
+                    let [r, g, b] = desc.bottom_color;
+                    let [tip_r, tip_g, tip_b] = desc.tip_color;
+                    (
+                        egui::Color32::from_rgb(r, g, b),
+                        egui::Color32::from_rgb(tip_r, tip_g, tip_b),
+                    )
+                })
+                .collect(),
+
+            grass_cut_radius: 0.06,
+            grass_regrowth_hours: 6.0,
 
-            starlight_iterations: 18,
-            starlight_formuparam: 0.5,
-            starlight_volsteps: 10,
-            starlight_stepsize: 0.12,
-            starlight_zoom: 0.88,
-            starlight_tile: 1.1,
-            starlight_speed: 0.01,
-            starlight_brightness: 0.0005,
-            starlight_darkmatter: 0.8,
-            starlight_distfading: 0.885,
-            starlight_saturation: 1.0,
-
-            grass_bottom_color: egui::Color32::from_rgb(61, 163, 59),
-            grass_tip_color: egui::Color32::from_rgb(168, 227, 0),
-
-            lavender_bottom_color: egui::Color32::from_rgb(74, 165, 0),
-            lavender_tip_color: egui::Color32::from_rgb(85, 0, 207),
-
-            leaves_bottom_color: egui::Color32::from_rgb(232, 142, 0),
-            leaves_tip_color: egui::Color32::from_rgb(255, 219, 71),
-
-            voxel_sand_color: egui::Color32::from_rgb(245, 222, 179),
-            voxel_dirt_color: egui::Color32::from_rgb(68, 192, 0),
-            voxel_rock_color: egui::Color32::from_rgb(235, 92, 0),
-            voxel_leaf_color: egui::Color32::from_rgb(242, 199, 36),
-            voxel_trunk_color: egui::Color32::from_rgb(215, 194, 168),
+            leaves_bottom_color: LEAVES_BOTTOM_COLOR,
+            leaves_tip_color: LEAVES_TIP_COLOR,
+            seasonal_leaves: SeasonalLeaves::default(),
 
             // multi-tree management
             next_tree_id: 1, // Start from 1, use 0 for GUI single tree
             single_tree_id: 0,
+            is_tree_picking_active: false,
+            placed_trees: HashMap::new(),
 
             spatial_sound_manager,
             tree_audio_manager,
+            music_manager,
+            wind_ambience,
+            sound_occlusion,
+            audio_automation: AudioAutomation::default(),
+            weather: Weather::default(),
+            wind_field: WindField::new(WindFieldDesc::default(), &chunk_bound),
         };
 
-        app.add_tree(
-            app.debug_tree_desc.clone(),
-            Vec2::new(app.debug_tree_pos.x, app.debug_tree_pos.z),
-            false,
-            false,
-        )?;
+        if let Some(world_snapshot) = world_snapshot {
+            app.restore_snapshot_trees(world_snapshot)?;
+        } else {
+            app.add_tree(
+                app.debug_tree_desc.clone(),
+                Vec2::new(app.debug_tree_pos.x, app.debug_tree_pos.z),
+                false,
+                false,
+            )?;
+        }
 
         // configure leaves with the app's actual density values (now that app struct exists)
         app.tracer.regenerate_leaves(
@@ -502,53 +634,228 @@ impl App {
             app.leaves_outer_radius,
         )?;
 
+        // the ground under the start position changes whenever the world does
+        match app.tracer.find_safe_spawn(SPAWN_POS_XZ) {
+            Ok(spawn_pos) => {
+                let (yaw, pitch) = app.tracer.camera_orientation();
+                app.tracer.set_camera_pose(spawn_pos, yaw, pitch);
+            }
+            Err(e) => log::warn!("Failed to find a safe spawn position: {:#}", e),
+        }
+
         Ok(app)
     }
 
-    fn generate_procedural_trees(&mut self) -> Result<()> {
+    /// Plants the whole procedural forest before returning.
+    pub(crate) fn generate_procedural_trees(&mut self) -> Result<()> {
+        self.start_forest_generation()?;
+        self.step_forest_generation(Duration::MAX)
+    }
+
+    /// Clears the trees and queues the procedural forest, which is planted by
+    /// `step_forest_generation` over the coming frames.
+    fn start_forest_generation(&mut self) -> Result<()> {
+        self.forest_generation = None;
         // clear all procedural trees (keep single tree with ID 0)
         self.clear_procedural_trees()?;
         // remove the standalone debug tree so only procedural forest remains
         self.remove_tree_resources(self.single_tree_id)?;
+        // the terrain gets rebuilt below, which takes hand planted trees and voxel edits with it
+        self.world_edits.retain(|edit| {
+            !matches!(
+                edit,
+                WorldEdit::PlantTree { .. }
+                    | WorldEdit::RemoveTree { .. }
+                    | WorldEdit::ModifyVoxels { .. }
+            )
+        });
+        self.is_forest_generated = false;
 
-        self.plain_builder.chunk_init(
-            self.prev_bound.min(),
-            self.prev_bound.max() - self.prev_bound.min(),
-        )?;
+        self.plain_builder
+            .chunk_init(self.prev_bound.min(), self.prev_bound.dimensions())?;
+
+        let tree_positions_2d = self.generate_tree_positions();
+
+        log::info!("Generated {} procedural trees", tree_positions_2d.len());
+
+        // the placer works in voxels, the scene in chunks
+        let tree_positions_2d = tree_positions_2d
+            .into_iter()
+            .map(|pos| pos / VOXEL_DIM as f32)
+            .collect::<Vec<_>>();
+
+        // batch query all terrain heights at once
+        let tree_positions_3d = self.query_terrain_heights_for_positions(&tree_positions_2d)?;
+
+        // after the query, so the trees stand on the terrain rather than on the props
+        self.place_props()?;
+
+        // seeded, so save slots can rebuild the same forest before replaying their edits
+        let rng = StdRng::seed_from_u64(TREE_PLACER_SEED as u64);
+        self.forest_generation = Some(ForestGeneration::new(tree_positions_3d, rng));
+        Ok(())
+    }
 
-        let world_size = CHUNK_DIM * VOXEL_DIM_PER_CHUNK;
+    /// The dimensions and offset of the area the placers scatter over, in voxels. It keeps off
+    /// the edges of the world.
+    fn placement_area(&self) -> (Vec2, Vec2) {
+        let world_size = self.config.chunk_dim * VOXEL_DIM_PER_CHUNK;
         let map_padding = 50.0;
         let map_dimensions = Vec2::new(
             world_size.x as f32 - map_padding * 2.0,
             world_size.z as f32 - map_padding * 2.0,
         );
-        let grid_size = 120.0;
-        let mut placer_desc = PlacerDesc::new(42);
-        placer_desc.threshold = 0.55;
+        (map_dimensions, Vec2::new(map_padding, map_padding))
+    }
 
-        let tree_positions_2d = generate_positions(
+    /// Where the tree placer puts the procedural trees, in voxels.
+    fn generate_tree_positions(&self) -> Vec<Vec2> {
+        let (map_dimensions, map_offset) = self.placement_area();
+        generate_positions(
             map_dimensions,
-            Vec2::new(map_padding, map_padding),
-            grid_size,
-            &placer_desc,
+            map_offset,
+            self.tree_grid_size,
+            &self.tree_placer_desc,
+        )
+    }
+
+    /// Carves the props of every prop layer into the terrain, one `chunk_modify` per batch and
+    /// one rebuild per touched chunk at the end.
+    fn place_props(&mut self) -> Result<()> {
+        let (map_dimensions, map_offset) = self.placement_area();
+        let mut changed_chunks = HashSet::new();
+        for layer_idx in 0..self.prop_placer.layers.len() {
+            let positions_2d = self
+                .prop_placer
+                .generate_positions(layer_idx, map_dimensions, map_offset)
+                .into_iter()
+                .map(|pos| pos / VOXEL_DIM as f32)
+                .collect::<Vec<_>>();
+            let bases = self
+                .query_terrain_heights_for_positions(&positions_2d)?
+                .into_iter()
+                .map(|pos| WorldPos(pos).to_voxel_space())
+                .collect::<Vec<_>>();
+
+            let layer = &self.prop_placer.layers[layer_idx];
+            log::info!("Placing {} procedural {}", bases.len(), layer.name);
+            let voxel_type = layer.material.voxel_type();
+            for round_cones in self.prop_placer.build_batches(layer_idx, &bases) {
+                let aabbs = round_cones.iter().map(RoundCone::aabb).collect::<Vec<_>>();
+                let leaves_data = (0..round_cones.len() as u32).collect::<Vec<_>>();
+                let bvh_nodes = build_bvh(&aabbs, &leaves_data).map_err(anyhow::Error::msg)?;
+                let this_bound = UAabb3::from(&bvh_nodes[0].aabb);
+
+                self.plain_builder
+                    .chunk_modify(&bvh_nodes, &round_cones, voxel_type)?;
+                changed_chunks.extend(this_bound.iter_chunks(VOXEL_DIM_PER_CHUNK).map(ChunkIdx));
+                self.prev_bound = this_bound.union_with(&self.prev_bound);
+            }
+        }
+        for chunk_idx in changed_chunks {
+            self.rebuild_chunk(chunk_idx)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the preview when it's shown and out of date, it follows the chunk under the
+    /// camera. Call once per frame.
+    fn update_world_gen_preview(&mut self) -> Result<()> {
+        if !self.is_world_gen_preview_visible {
+            return Ok(());
+        }
+        let camera_pos = self.tracer.camera_position();
+        let chunk_idx = UVec3::new(
+            (camera_pos.x.max(0.0) as u32).min(self.config.chunk_dim.x - 1),
+            0,
+            (camera_pos.z.max(0.0) as u32).min(self.config.chunk_dim.z - 1),
         );
+        if chunk_idx != self.world_gen_preview_chunk {
+            self.world_gen_preview_chunk = chunk_idx;
+            self.is_world_gen_preview_dirty = true;
+        }
+        // a pending build is left to finish instead of stalling the frame on it
+        if !self.is_world_gen_preview_dirty || self.world_gen_preview.is_busy()? {
+            return Ok(());
+        }
 
-        log::info!("Generated {} procedural trees", tree_positions_2d.len());
+        let chunk_origin = Vec2::new(
+            (chunk_idx.x * VOXEL_DIM) as f32,
+            (chunk_idx.z * VOXEL_DIM) as f32,
+        );
+        let tree_positions = self
+            .generate_tree_positions()
+            .into_iter()
+            .map(|pos| (pos - chunk_origin) / VOXEL_DIM as f32)
+            .filter(|pos| pos.cmpge(Vec2::ZERO).all() && pos.cmplt(Vec2::ONE).all())
+            .collect::<Vec<_>>();
+        let grass_color = self.flora_colors[FloraType::GRASS.index()].0;
+        self.world_gen_preview.build(
+            chunk_idx,
+            &self.terrain_gen_desc,
+            &tree_positions,
+            self.tracer_settings.sun.sun_dir(),
+            Vec3::new(
+                grass_color.r() as f32 / 255.0,
+                grass_color.g() as f32 / 255.0,
+                grass_color.b() as f32 / 255.0,
+            ),
+            Vec3::new(
+                self.leaves_bottom_color.r() as f32 / 255.0,
+                self.leaves_bottom_color.g() as f32 / 255.0,
+                self.leaves_bottom_color.b() as f32 / 255.0,
+            ),
+        )?;
+        self.is_world_gen_preview_dirty = false;
+        Ok(())
+    }
 
-        // batch query all terrain heights at once
-        let tree_positions_3d = self.query_terrain_heights_for_positions(&tree_positions_2d)?;
+    /// Regenerates the whole world with the terrain of the preview and replants the forest.
+    fn apply_world_gen(&mut self) -> Result<()> {
+        self.plain_builder
+            .set_terrain_gen_desc(self.terrain_gen_desc.clone());
+        self.tracer.set_sea_level(self.terrain_gen_desc.sea_level);
+        let world_bound = Self::world_voxel_bound(self.config.chunk_dim);
+        self.plain_builder
+            .chunk_init(world_bound.min(), world_bound.dimensions() + UVec3::ONE)?;
+        let built_chunks = self.contree_builder.built_chunks().collect::<Vec<_>>();
+        for chunk_idx in built_chunks {
+            self.rebuild_chunk(chunk_idx)?;
+        }
+        // the trees sit on the old ground, the forest is planted anew
+        self.start_forest_generation()
+    }
 
-        let mut rng = rand::rng();
+    /// Plants queued procedural trees until `budget` runs out.
+    fn step_forest_generation(&mut self, budget: Duration) -> Result<()> {
+        let Some(mut forest_generation) = self.forest_generation.take() else {
+            return Ok(());
+        };
 
+        let start = Instant::now();
         // plant all trees with known heights and unique IDs
-        for tree_pos in tree_positions_3d.iter() {
+        while let Some((tree_pos, rng)) = forest_generation.next_tree() {
             let mut tree_desc = self.debug_tree_desc.clone();
             tree_desc.seed = rng.random_range(1..10000);
 
-            self.apply_tree_variations(&mut tree_desc, &mut rng);
-            self.add_tree_at_pos(tree_desc, *tree_pos, true)?;
+            self.apply_tree_variations(&mut tree_desc, rng);
+            self.add_tree_at_pos(tree_desc, tree_pos, true)?;
+            if start.elapsed() >= budget {
+                break;
+            }
         }
 
+        if forest_generation.is_done() {
+            self.is_forest_generated = true;
+            log::info!(
+                "Planted all {} procedural trees",
+                forest_generation.tree_count()
+            );
+            // the world is built, so the traces hold a whole build
+            self.contree_builder.save_allocation_traces()?;
+        } else {
+            self.forest_generation = Some(forest_generation);
+        }
         Ok(())
     }
 
@@ -576,6 +883,7 @@ impl App {
         self.tracer
             .remove_tree_leaves(&mut self.surface_builder.resources, tree_id)?;
         self.tree_audio_manager.remove_tree(tree_id);
+        self.placed_trees.remove(&tree_id);
         Ok(())
     }
 
@@ -593,62 +901,122 @@ impl App {
             self.single_tree_id
         };
 
+        self.placed_trees.insert(
+            tree_id,
+            TreePlacement {
+                tree_id,
+                desc: tree_desc.clone(),
+                position: tree_pos,
+            },
+        );
         let tree = Tree::new(tree_desc);
-        let mut round_cones = Vec::new();
-        for tree_trunk in tree.trunks() {
-            let mut round_cone = tree_trunk.clone();
-            round_cone.transform(tree_pos * 256.0);
-            round_cones.push(round_cone);
-        }
-
-        let mut leaves_data_sequential = vec![0; round_cones.len()];
-        for (i, item) in leaves_data_sequential
-            .iter_mut()
-            .enumerate()
-            .take(round_cones.len())
-        {
-            *item = i as u32;
-        }
-        let mut aabbs = Vec::new();
-        for round_cone in &round_cones {
-            aabbs.push(round_cone.aabb());
-        }
-        let bvh_nodes = build_bvh(&aabbs, &leaves_data_sequential).unwrap();
+        let (bvh_nodes, round_cones) = Self::trunk_round_cones(&tree, tree_pos);
 
-        let this_bound = UAabb3::new(bvh_nodes[0].aabb.min_uvec3(), bvh_nodes[0].aabb.max_uvec3());
+        let this_bound = UAabb3::from(&bvh_nodes[0].aabb);
 
-        self.plain_builder.chunk_modify(&bvh_nodes, &round_cones)?;
+        self.plain_builder.chunk_modify(
+            &bvh_nodes,
+            &round_cones,
+            VoxelMaterial::Trunk.voxel_type(),
+        )?;
 
-        let relative_leaf_positions = tree.relative_leaf_positions();
-        let offseted_leaf_positions = relative_leaf_positions
+        self.add_leaves_of_tree(tree_id, &tree, tree_pos)?;
+
+        Self::mesh_generate(
+            &mut self.surface_builder,
+            &mut self.contree_builder,
+            &mut self.scene_accel_builder,
+            this_bound.union_with(&self.prev_bound),
+        )?;
+
+        let this_region = Aabb3::from_voxel_bound(&this_bound);
+        self.tracer.mark_sky_visibility_dirty(Some(&this_region));
+        self.tracer.mark_shadows_dirty();
+        self.tracer.invalidate_history(Some(this_region));
+        self.prev_bound = this_bound.union_with(&self.prev_bound);
+
+        self.add_tree_audio(tree_id, false, tree, tree_pos)?;
+
+        Ok(())
+    }
+
+    /// The trunks of the tree standing at `tree_pos` in voxel space, with their bvh.
+    fn trunk_round_cones(tree: &Tree, tree_pos: Vec3) -> (Vec<BvhNode>, Vec<RoundCone>) {
+        let round_cones = tree
+            .trunks()
             .iter()
-            .map(|leaf_pos| *leaf_pos + tree_pos * 256.0)
+            .map(|tree_trunk| {
+                let mut round_cone = tree_trunk.clone();
+                round_cone.transform(WorldPos(tree_pos).to_voxel_space());
+                round_cone
+            })
             .collect::<Vec<_>>();
+        let aabbs = round_cones.iter().map(RoundCone::aabb).collect::<Vec<_>>();
+        let leaves_data = (0..round_cones.len() as u32).collect::<Vec<_>>();
+        let bvh_nodes = build_bvh(&aabbs, &leaves_data).unwrap();
+        (bvh_nodes, round_cones)
+    }
 
-        let quantized_leaf_positions = quantize(&offseted_leaf_positions);
-        self.tracer.add_tree_leaves(
-            &mut self.surface_builder.resources,
-            tree_id,
-            &quantized_leaf_positions,
+    /// Takes a planted tree out of the scene. Only its trunk voxels are carved out, so where the
+    /// trunk reached into the ground a shallow pit is left.
+    fn remove_tree(&mut self, tree_id: u32) -> Result<()> {
+        let Some(placement) = self.placed_trees.get(&tree_id).cloned() else {
+            anyhow::bail!("No tree with id {}", tree_id);
+        };
+        let tree = Tree::new(placement.desc);
+        let (bvh_nodes, round_cones) = Self::trunk_round_cones(&tree, placement.position);
+        let this_bound = UAabb3::from(&bvh_nodes[0].aabb);
+
+        self.plain_builder.chunk_replace(
+            &bvh_nodes,
+            &round_cones,
+            VoxelMaterial::Trunk.voxel_type(),
+            0,
         )?;
+        self.remove_tree_resources(tree_id)?;
 
         Self::mesh_generate(
             &mut self.surface_builder,
             &mut self.contree_builder,
             &mut self.scene_accel_builder,
-            this_bound.union_with(&self.prev_bound),
+            this_bound,
         )?;
 
-        self.prev_bound = this_bound.union_with(&self.prev_bound);
+        let this_region = Aabb3::from_voxel_bound(&this_bound);
+        self.tracer.mark_sky_visibility_dirty(Some(&this_region));
+        self.tracer.mark_shadows_dirty();
+        self.tracer.invalidate_history(Some(this_region));
+        Ok(())
+    }
 
-        self.add_tree_audio(tree_id, false, tree, tree_pos)?;
+    /// The planted tree standing closest to `position`, measured along the ground.
+    fn nearest_tree(&self, position: Vec3) -> Option<&TreePlacement> {
+        let horizontal_distance =
+            |placement: &TreePlacement| placement.position.xz().distance(position.xz());
+        self.placed_trees
+            .values()
+            .min_by(|a, b| horizontal_distance(a).total_cmp(&horizontal_distance(b)))
+    }
+
+    fn add_leaves_of_tree(&mut self, tree_id: u32, tree: &Tree, tree_pos: Vec3) -> Result<()> {
+        let relative_leaf_positions = tree.relative_leaf_positions();
+        let offseted_leaf_positions = relative_leaf_positions
+            .iter()
+            .map(|leaf_pos| *leaf_pos + WorldPos(tree_pos).to_voxel_space())
+            .collect::<Vec<_>>();
 
+        let quantized_leaf_positions = quantize(&offseted_leaf_positions);
+        self.tracer.add_tree_leaves(
+            &mut self.surface_builder.resources,
+            tree_id,
+            &quantized_leaf_positions,
+        )?;
         return Ok(());
 
-        fn quantize(positions: &[Vec3]) -> Vec<UVec3> {
+        fn quantize(positions: &[Vec3]) -> Vec<VoxelPos> {
             let set = positions
                 .iter()
-                .map(|pos| pos.as_uvec3())
+                .map(|pos| VoxelPos(pos.as_uvec3()))
                 .collect::<HashSet<_>>();
             set.into_iter().collect::<Vec<_>>()
         }
@@ -664,7 +1032,7 @@ impl App {
         let relative_leaf_positions = tree.relative_leaf_positions();
         let audio_positions = relative_leaf_positions
             .iter()
-            .map(|leaf_pos| *leaf_pos / 256.0 + tree_pos)
+            .map(|leaf_pos| WorldPos::from_voxel_space(*leaf_pos).0 + tree_pos)
             .collect::<Vec<_>>();
 
         let cluster_distance: f32 = 0.08;
@@ -701,119 +1069,574 @@ impl App {
         (tree_changed, regenerate_pressed)
     }
 
-    fn calculate_sun_position(&mut self, time_of_day: f32, latitude: f32, season: f32) {
-        use std::f32::consts::PI;
-
-        // time of day: 0.0 = midnight, 0.5 = noon, 1.0 = midnight
-        // latitude: -1.0 = south pole, 0.0 = equator, 1.0 = north pole
-        // season: 0.0 = winter solstice, 0.25 = spring equinox, 0.5 = summer solstice, 0.75 = autumn equinox
-
-        // convert time to hour angle (radians)
-        // solar noon is at time_of_day = 0.5
-        let hour_angle = (time_of_day - 0.5) * 2.0 * PI;
-
-        // solar declination based on season
-        // season of 0.0 = winter solstice (max negative declination)
-        // season of 0.5 = summer solstice (max positive declination)
-        let seasonal_angle = season * 2.0 * PI;
-        let declination = -23.44_f32.to_radians() * (seasonal_angle).cos(); // Earth's axial tilt
+    /// Stamps the species selected in the planting tool at the aimed spot.
+    fn plant(&mut self) -> Result<()> {
+        let edit = match self.planting_tool.plant_request(&mut self.tracer)? {
+            Some(PlantRequest::Tree { position, rotation }) => WorldEdit::PlantTree {
+                position,
+                seed: self.debug_tree_desc.seed,
+                rotation,
+            },
+            Some(PlantRequest::Flora {
+                flora_type,
+                positions,
+            }) => WorldEdit::PlantFlora {
+                flora_type,
+                positions,
+            },
+            None => return Ok(()),
+        };
+        self.apply_world_edit(edit)?;
+        Ok(())
+    }
 
-        // calculate solar elevation (altitude)
-        let elevation = (declination.sin() * (latitude * PI * 0.5).sin()
-            + declination.cos() * (latitude * PI * 0.5).cos() * hour_angle.cos())
-        .asin();
+    /// Plants a tree with the GUI tree settings where the cursor points at the scene, or with
+    /// shift held removes the tree standing nearest to it.
+    fn pick_tree_spot(&mut self) -> Result<()> {
+        let window_size = self.window_state.window().inner_size();
+        let screen_uv =
+            self.cursor_position / Vec2::new(window_size.width as f32, window_size.height as f32);
+        let (origin, direction) = self.tracer.screen_ray(screen_uv);
+        let Some(hit) = self.tracer.raycast_scene(origin, direction)? else {
+            return Ok(());
+        };
 
-        // calculate solar azimuth
-        let azimuth = if hour_angle.cos() == 0.0 {
-            if hour_angle > 0.0 {
-                PI
-            } else {
-                0.0
+        let edit = if self.is_shift_held {
+            let Some(placement) = self.nearest_tree(hit.position) else {
+                return Ok(());
+            };
+            if placement.position.xz().distance(hit.position.xz()) > TREE_REMOVAL_PICK_RADIUS {
+                return Ok(());
+            }
+            WorldEdit::RemoveTree {
+                position: placement.position,
             }
         } else {
-            (declination.sin() * (latitude * PI * 0.5).cos()
-                - declination.cos() * (latitude * PI * 0.5).sin() * hour_angle.cos())
-            .atan2(hour_angle.sin())
+            WorldEdit::PlantTree {
+                position: hit.position,
+                seed: self.debug_tree_desc.seed,
+                rotation: self.debug_tree_desc.rotation,
+            }
         };
-
-        // normalize elevation to -1.0 to 1.0 range (matching current altitude range)
-        self.sun_altitude = (elevation / (PI * 0.5)).clamp(-1.0, 1.0);
-
-        // normalize azimuth to 0.0 to 1.0 range (matching current azimuth range)
-        self.sun_azimuth = ((azimuth + PI) / (2.0 * PI)) % 1.0;
+        self.apply_world_edit(edit)?;
+        Ok(())
     }
 
-    fn apply_tree_variations(&self, tree_desc: &mut TreeDesc, rng: &mut impl Rng) {
-        let config = &self.tree_variation_config;
+    /// Digs or builds at the spot aimed at with the voxel edit tool.
+    fn edit_voxels(&mut self) -> Result<()> {
+        let Some(edit) = self.voxel_edit_tool.edit_request() else {
+            return Ok(());
+        };
+        self.apply_world_edit(edit)?;
+        Ok(())
+    }
 
-        if config.size_variance > 0.0 {
-            tree_desc.size *= 1.0 + rng.random_range(-config.size_variance..=config.size_variance);
+    /// Fills a sphere with `material`, or carves it out with `None`, and rebuilds the surface,
+    /// contree and scene entry of the chunks it touches. `center` and `radius` are in world units.
+    pub(crate) fn modify_voxel_sphere(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        material: Option<VoxelMaterial>,
+    ) -> Result<()> {
+        let voxel_dim = VOXEL_DIM as f32;
+        let radius = radius * voxel_dim;
+        // the chunk atlas ends with the world, the sphere is kept inside with a voxel to spare
+        // since its bound rounds outwards
+        let world_size = (self.config.chunk_dim * VOXEL_DIM_PER_CHUNK).as_vec3();
+        let center = (center * voxel_dim)
+            .max(Vec3::splat(radius))
+            .min(world_size - radius - 1.0);
+
+        let sphere = RoundCone::new(radius, center, radius, center);
+        let bvh_nodes = build_bvh(&[sphere.aabb()], &[0]).unwrap();
+        let this_bound = UAabb3::from(&bvh_nodes[0].aabb);
+
+        self.plain_builder.chunk_modify(
+            &bvh_nodes,
+            &[sphere],
+            material.map_or(0, VoxelMaterial::voxel_type),
+        )?;
+        for chunk_idx in this_bound.iter_chunks(VOXEL_DIM_PER_CHUNK).map(ChunkIdx) {
+            self.rebuild_chunk(chunk_idx)?;
         }
+        // reset along with the trees when the world is rebuilt from its seed
+        self.prev_bound = this_bound.union_with(&self.prev_bound);
+        Ok(())
+    }
 
-        if config.trunk_thickness_variance > 0.0 {
-            tree_desc.trunk_thickness *= 1.0
-                + rng.random_range(
-                    -config.trunk_thickness_variance..=config.trunk_thickness_variance,
-                );
+    /// Cuts the grass around the player and plays a swish.
+    fn cut_grass(&mut self) {
+        let edit = WorldEdit::CutGrass {
+            center: self.tracer.camera_position(),
+            radius: self.grass_cut_radius,
+            height: GRASS_CUT_HEIGHT,
+        };
+        let cut_len = match self.apply_world_edit(edit) {
+            Ok(cut_len) => cut_len,
+            Err(e) => {
+                log::error!("Failed to cut grass: {}", e);
+                return;
+            }
+        };
+        if cut_len == 0 {
+            return;
         }
-
-        if config.trunk_thickness_min_variance > 0.0 {
-            tree_desc.trunk_thickness_min *= 1.0
-                + rng.random_range(
-                    -config.trunk_thickness_min_variance..=config.trunk_thickness_min_variance,
-                );
+        if let Err(e) = self.spatial_sound_manager.add_non_spatial_source(
+            GRASS_CUT_SOUND_PATH,
+            self.config.grass_cut_volume_db,
+            AudioBus::Sfx,
+        ) {
+            log::error!("Failed to play grass cut sound: {}", e);
         }
+    }
 
-        if config.spread_variance > 0.0 {
-            tree_desc.spread *=
-                1.0 + rng.random_range(-config.spread_variance..=config.spread_variance);
+    /// Applies an edit and records it for the save slots, returns the number of cut grass
+    /// instances for grass cuts and 0 otherwise.
+    pub(crate) fn apply_world_edit(&mut self, edit: WorldEdit) -> Result<u32> {
+        let mut cut_len = 0;
+        match &edit {
+            WorldEdit::PlantTree {
+                position,
+                seed,
+                rotation,
+            } => {
+                let mut tree_desc = self.debug_tree_desc.clone();
+                tree_desc.seed = *seed;
+                tree_desc.rotation = *rotation;
+                self.add_tree_at_pos(tree_desc, *position, true)?;
+            }
+            WorldEdit::RemoveTree { position } => {
+                let tree_id = self
+                    .nearest_tree(*position)
+                    .map(|placement| placement.tree_id)
+                    .with_context(|| format!("No tree to remove near {}", position))?;
+                self.remove_tree(tree_id)?;
+            }
+            WorldEdit::PlantFlora {
+                flora_type,
+                positions,
+            } => {
+                self.surface_builder.plant_flora(*flora_type, positions)?;
+                if !positions.is_empty() {
+                    let roots = positions
+                        .iter()
+                        .map(|pos| pos.to_world().0)
+                        .collect::<Vec<_>>();
+                    let roots = Aabb3::from_points(&roots);
+                    // flora doesn't grow taller than what the scythe reaches
+                    let voxel_size = 1.0 / VOXEL_DIM as f32;
+                    self.tracer.invalidate_history(Some(Aabb3::new(
+                        roots.min() - Vec3::splat(voxel_size),
+                        roots.max() + Vec3::new(voxel_size, GRASS_CUT_HEIGHT, voxel_size),
+                    )));
+                }
+            }
+            WorldEdit::CutGrass {
+                center,
+                radius,
+                height,
+            } => {
+                cut_len = self.surface_builder.cut_grass(
+                    WorldPos(*center),
+                    *radius,
+                    *height,
+                    self.grass_regrowth_hours,
+                )?;
+                // cutting nothing changes nothing, no need to replay it
+                if cut_len == 0 {
+                    return Ok(0);
+                }
+                self.tracer.invalidate_history(Some(Aabb3::new(
+                    *center - Vec3::new(*radius, *height, *radius),
+                    *center + Vec3::new(*radius, 0.0, *radius),
+                )));
+            }
+            WorldEdit::ModifyVoxels {
+                center,
+                radius,
+                material,
+            } => {
+                self.modify_voxel_sphere(*center, *radius, *material)?;
+            }
         }
+        self.world_edits.push(edit);
+        Ok(cut_len)
+    }
 
-        if config.randomness_variance > 0.0 {
-            tree_desc.randomness = (tree_desc.randomness
-                + rng.random_range(-config.randomness_variance..=config.randomness_variance))
-            .clamp(0.0, 1.0);
+    pub(crate) fn save_to_slot(&self, slot: usize) -> Result<()> {
+        let (camera_yaw, camera_pitch) = self.tracer.camera_orientation();
+        let mut chunk_checksums = Vec::new();
+        for info in self.contree_builder.chunk_residency() {
+            if let Some(checksum) = self.contree_builder.chunk_checksum(info.chunk_idx)? {
+                chunk_checksums.push((info.chunk_idx, checksum));
+            }
         }
-
-        if config.vertical_tendency_variance > 0.0 {
-            tree_desc.vertical_tendency = (tree_desc.vertical_tendency
-                + rng.random_range(
-                    -config.vertical_tendency_variance..=config.vertical_tendency_variance,
-                ))
-            .clamp(-1.0, 1.0);
+        SaveSlot {
+            camera_position: self.tracer.camera_position(),
+            camera_yaw,
+            camera_pitch,
+            time_of_day: self.world_clock.time_of_day,
+            season: self.world_clock.season,
+            moon_phase: self.world_clock.moon_phase,
+            has_forest: self.is_forest_generated,
+            edits: self.world_edits.clone(),
+            chunk_checksums,
         }
+        .save(slot)?;
+        log::info!("Saved slot {}", slot);
+        Ok(())
+    }
 
-        if config.branch_probability_variance > 0.0 {
-            tree_desc.branch_probability = (tree_desc.branch_probability
-                + rng.random_range(
-                    -config.branch_probability_variance..=config.branch_probability_variance,
-                ))
-            .clamp(0.0, 1.0);
-        }
+    /// Rebuilds the seeded world and replays the slot's edits on top of it.
+    pub(crate) fn load_from_slot(&mut self, slot: usize) -> Result<()> {
+        let save = SaveSlot::load(slot)?;
 
-        if config.tree_height_variance > 0.0 {
-            tree_desc.tree_height *=
-                1.0 + rng.random_range(-config.tree_height_variance..=config.tree_height_variance);
+        self.forest_generation = None;
+        self.tracer
+            .clear_all_tree_leaves(&mut self.surface_builder.resources)?;
+        self.placed_trees.clear();
+        self.next_tree_id = 1;
+        self.clean_up_prev_tree()?;
+        self.surface_builder.reset_flora_edits()?;
+        self.world_edits.clear();
+
+        if save.has_forest {
+            self.generate_procedural_trees()?;
+        } else {
+            self.is_forest_generated = false;
+            self.add_tree(
+                self.debug_tree_desc.clone(),
+                Vec2::new(self.debug_tree_pos.x, self.debug_tree_pos.z),
+                false,
+                false,
+            )?;
         }
 
-        if config.length_dropoff_variance > 0.0 {
-            tree_desc.length_dropoff = (tree_desc.length_dropoff
-                + rng.random_range(
-                    -config.length_dropoff_variance..=config.length_dropoff_variance,
-                ))
-            .clamp(0.1, 1.0);
+        let edit_count = save.edits.len();
+        for edit in save.edits {
+            self.apply_world_edit(edit)?;
         }
 
-        if config.thickness_reduction_variance > 0.0 {
-            tree_desc.thickness_reduction = (tree_desc.thickness_reduction
-                + rng.random_range(
-                    -config.thickness_reduction_variance..=config.thickness_reduction_variance,
-                ))
-            .clamp(0.0, 1.0);
+        // the world is rebuilt from seeds, a mismatch means the reconstruction isn't deterministic
+        for (chunk_idx, saved_checksum) in save.chunk_checksums {
+            match self.contree_builder.chunk_checksum(chunk_idx)? {
+                Some(checksum) if checksum == saved_checksum => {}
+                Some(checksum) => log::warn!(
+                    "Chunk {} diverged after loading slot {}: {:016x}, saved {:016x}",
+                    chunk_idx,
+                    slot,
+                    checksum,
+                    saved_checksum
+                ),
+                None => log::warn!(
+                    "Chunk {} is not resident after loading slot {}",
+                    chunk_idx,
+                    slot
+                ),
+            }
         }
 
-        if config.iterations_variance > 0.0 {
-            let variation =
+        self.teleport_camera(save.camera_position, save.camera_yaw, save.camera_pitch);
+        self.world_clock.set_time_of_day(save.time_of_day);
+        self.world_clock.season = save.season;
+        self.world_clock.moon_phase = save.moon_phase;
+
+        log::info!("Loaded slot {} with {} edits", slot, edit_count);
+        Ok(())
+    }
+
+    /// Writes the built world to the snapshot file, which replaces world generation at the next
+    /// startup.
+    pub(crate) fn save_world_snapshot(&self) -> Result<()> {
+        if self.forest_generation.is_some() {
+            anyhow::bail!("The forest is still being planted");
+        }
+        // the readbacks must not race the frame in flight
+        self.vulkan_ctx.device().wait_idle();
+
+        let mut chunks = Vec::new();
+        for chunk_idx in Self::world_voxel_bound(self.config.chunk_dim)
+            .iter_chunks(VOXEL_DIM_PER_CHUNK)
+            .map(ChunkIdx)
+        {
+            chunks.push(ChunkSnapshot {
+                chunk_idx,
+                voxels: self.plain_builder.read_chunk_voxels(chunk_idx)?,
+                contree: self.contree_builder.read_chunk_contree(chunk_idx)?,
+            });
+        }
+        let mut trees = self.placed_trees.values().cloned().collect::<Vec<_>>();
+        trees.sort_by_key(|tree| tree.tree_id);
+
+        let snapshot = WorldSnapshot {
+            chunks,
+            trees,
+            next_tree_id: self.next_tree_id,
+            tree_bound: self.prev_bound,
+            has_forest: self.is_forest_generated,
+        };
+        snapshot.save()?;
+        log::info!(
+            "Saved world snapshot with {} chunks and {} trees",
+            snapshot.chunks.len(),
+            snapshot.trees.len()
+        );
+        Ok(())
+    }
+
+    /// Builds the world from the snapshot file if there is one, in place of [`Self::init`].
+    ///
+    /// Returns `None` when the world has to be generated, also when the snapshot can't be used.
+    fn load_world_snapshot(
+        chunk_dim: UVec3,
+        plain_builder: &mut PlainBuilder,
+        surface_builder: &mut SurfaceBuilder,
+        contree_builder: &mut ContreeBuilder,
+        scene_accel_builder: &mut SceneAccelBuilder,
+    ) -> Option<WorldSnapshot> {
+        if !WorldSnapshot::exists() {
+            return None;
+        }
+        let start = Instant::now();
+        let result = WorldSnapshot::load().and_then(|snapshot| {
+            Self::init_from_snapshot(
+                chunk_dim,
+                plain_builder,
+                surface_builder,
+                contree_builder,
+                scene_accel_builder,
+                &snapshot,
+            )?;
+            Ok(snapshot)
+        });
+        match result {
+            Ok(snapshot) => {
+                log::info!(
+                    "Loaded world snapshot with {} chunks and {} trees in {:?}",
+                    snapshot.chunks.len(),
+                    snapshot.trees.len(),
+                    start.elapsed()
+                );
+                Some(snapshot)
+            }
+            Err(e) => {
+                // generating the world overwrites whatever was restored so far
+                log::warn!("Ignoring the world snapshot, generating the world: {:#}", e);
+                None
+            }
+        }
+    }
+
+    fn init_from_snapshot(
+        chunk_dim: UVec3,
+        plain_builder: &mut PlainBuilder,
+        surface_builder: &mut SurfaceBuilder,
+        contree_builder: &mut ContreeBuilder,
+        scene_accel_builder: &mut SceneAccelBuilder,
+        snapshot: &WorldSnapshot,
+    ) -> Result<()> {
+        let world_chunks = Self::world_voxel_bound(chunk_dim)
+            .iter_chunks(VOXEL_DIM_PER_CHUNK)
+            .map(ChunkIdx)
+            .collect::<HashSet<_>>();
+        let snapshot_chunks = snapshot
+            .chunks
+            .iter()
+            .map(|chunk| chunk.chunk_idx)
+            .collect::<HashSet<_>>();
+        if snapshot_chunks != world_chunks {
+            anyhow::bail!(
+                "The snapshot holds {} chunks that don't match the {} chunks of the world",
+                snapshot_chunks.len(),
+                world_chunks.len()
+            );
+        }
+
+        for chunk in &snapshot.chunks {
+            plain_builder.write_chunk_voxels(chunk.chunk_idx, &chunk.voxels)?;
+        }
+        for chunk in &snapshot.chunks {
+            surface_builder.build_surface(chunk.chunk_idx)?;
+            // chunks unloaded by streaming when the snapshot was taken are built from their voxels
+            let offsets = match &chunk.contree {
+                Some(contree) => {
+                    Some(contree_builder.restore_chunk_contree(chunk.chunk_idx, contree)?)
+                }
+                None => contree_builder.build_and_alloc(chunk.chunk_idx.atlas_offset())?,
+            };
+            if let Some((node_offset, leaf_offset)) = offsets {
+                scene_accel_builder.update_scene_tex(chunk.chunk_idx, node_offset, leaf_offset)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives the snapshot's trees their leaves and sounds back, their trunks are in the voxels.
+    fn restore_snapshot_trees(&mut self, snapshot: WorldSnapshot) -> Result<()> {
+        for placement in snapshot.trees {
+            let tree = Tree::new(placement.desc.clone());
+            self.add_leaves_of_tree(placement.tree_id, &tree, placement.position)?;
+            self.add_tree_audio(placement.tree_id, false, tree, placement.position)?;
+            self.placed_trees.insert(placement.tree_id, placement);
+        }
+        self.next_tree_id = snapshot.next_tree_id;
+        self.prev_bound = snapshot.tree_bound;
+        self.is_forest_generated = snapshot.has_forest;
+        Ok(())
+    }
+
+    pub(crate) fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// Moves the camera to `position`, or to the closest safe spawn when it would end up below the
+    /// ground there. Positions outside the world are kept as they are.
+    pub(crate) fn teleport_camera(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        let pos_xz = Vec2::new(position.x, position.z);
+        let position = match self.tracer.query_terrain_batch(&[pos_xz]) {
+            Ok(hits) => match hits[0] {
+                Ok(hit) if position.y >= hit.height + self.tracer.camera_standing_height() => {
+                    position
+                }
+                Err(TerrainMiss::OutOfBounds) => position,
+                _ => self.tracer.find_safe_spawn(pos_xz).unwrap_or_else(|e| {
+                    log::warn!("Teleporting without a safe spawn position: {:#}", e);
+                    position
+                }),
+            },
+            Err(e) => {
+                log::warn!("Failed to query the ground below the teleport: {:#}", e);
+                position
+            }
+        };
+        self.tracer.set_camera_pose(position, yaw, pitch);
+        self.tracer.invalidate_history(None);
+    }
+
+    /// Freezes the world and frees the camera to frame a shot, or puts the player back where it
+    /// was.
+    fn toggle_photo_mode(&mut self) {
+        if let Some(saved) = self.photo_mode.exit() {
+            self.is_fly_mode = saved.is_fly_mode;
+            self.tracer_settings.dof.is_enabled = saved.is_dof_enabled;
+            self.tracer
+                .set_camera_pose(saved.position, saved.yaw, saved.pitch);
+            self.tracer.reset_camera_velocity();
+            self.tracer.invalidate_history(None);
+            return;
+        }
+
+        let (yaw, pitch) = self.tracer.camera_orientation();
+        self.photo_mode.enter(PhotoModeReturn {
+            position: self.tracer.camera_position(),
+            yaw,
+            pitch,
+            is_fly_mode: self.is_fly_mode,
+            is_dof_enabled: self.tracer_settings.dof.is_enabled,
+        });
+        self.is_fly_mode = true;
+        self.tracer_settings.dof.is_enabled = true;
+        self.tracer.reset_camera_velocity();
+        if self.camera_path_player.is_playing() {
+            self.camera_path_player.toggle_playback();
+        }
+        self.planting_tool.is_active = false;
+        self.voxel_edit_tool.is_active = false;
+    }
+
+    pub(crate) fn time_of_day(&self) -> f32 {
+        self.world_clock.time_of_day
+    }
+
+    /// The sun follows on the next frame, the day/night cycle carries on from there if it's on.
+    pub(crate) fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.world_clock.set_time_of_day(time_of_day);
+    }
+
+    pub(crate) fn spatial_sound_manager(&self) -> &SpatialSoundManager {
+        &self.spatial_sound_manager
+    }
+
+    pub(crate) fn music_manager(&self) -> &MusicManager {
+        &self.music_manager
+    }
+
+    pub(crate) fn music_manager_mut(&mut self) -> &mut MusicManager {
+        &mut self.music_manager
+    }
+
+    fn apply_tree_variations(&self, tree_desc: &mut TreeDesc, rng: &mut impl Rng) {
+        let config = &self.tree_variation_config;
+
+        if config.size_variance > 0.0 {
+            tree_desc.size *= 1.0 + rng.random_range(-config.size_variance..=config.size_variance);
+        }
+
+        if config.trunk_thickness_variance > 0.0 {
+            tree_desc.trunk_thickness *= 1.0
+                + rng.random_range(
+                    -config.trunk_thickness_variance..=config.trunk_thickness_variance,
+                );
+        }
+
+        if config.trunk_thickness_min_variance > 0.0 {
+            tree_desc.trunk_thickness_min *= 1.0
+                + rng.random_range(
+                    -config.trunk_thickness_min_variance..=config.trunk_thickness_min_variance,
+                );
+        }
+
+        if config.spread_variance > 0.0 {
+            tree_desc.spread *=
+                1.0 + rng.random_range(-config.spread_variance..=config.spread_variance);
+        }
+
+        if config.randomness_variance > 0.0 {
+            tree_desc.randomness = (tree_desc.randomness
+                + rng.random_range(-config.randomness_variance..=config.randomness_variance))
+            .clamp(0.0, 1.0);
+        }
+
+        if config.vertical_tendency_variance > 0.0 {
+            tree_desc.vertical_tendency = (tree_desc.vertical_tendency
+                + rng.random_range(
+                    -config.vertical_tendency_variance..=config.vertical_tendency_variance,
+                ))
+            .clamp(-1.0, 1.0);
+        }
+
+        if config.branch_probability_variance > 0.0 {
+            tree_desc.branch_probability = (tree_desc.branch_probability
+                + rng.random_range(
+                    -config.branch_probability_variance..=config.branch_probability_variance,
+                ))
+            .clamp(0.0, 1.0);
+        }
+
+        if config.tree_height_variance > 0.0 {
+            tree_desc.tree_height *=
+                1.0 + rng.random_range(-config.tree_height_variance..=config.tree_height_variance);
+        }
+
+        if config.length_dropoff_variance > 0.0 {
+            tree_desc.length_dropoff = (tree_desc.length_dropoff
+                + rng.random_range(
+                    -config.length_dropoff_variance..=config.length_dropoff_variance,
+                ))
+            .clamp(0.1, 1.0);
+        }
+
+        if config.thickness_reduction_variance > 0.0 {
+            tree_desc.thickness_reduction = (tree_desc.thickness_reduction
+                + rng.random_range(
+                    -config.thickness_reduction_variance..=config.thickness_reduction_variance,
+                ))
+            .clamp(0.0, 1.0);
+        }
+
+        if config.iterations_variance > 0.0 {
+            let variation =
                 rng.random_range(-config.iterations_variance..=config.iterations_variance);
             tree_desc.iterations =
                 ((tree_desc.iterations as f32 + variation).round() as u32).clamp(1, 12);
@@ -828,36 +1651,423 @@ impl App {
         }
     }
 
-    fn init(
+    /// The voxels of the whole world, inclusive on both ends.
+    fn world_voxel_bound(chunk_dim: UVec3) -> UAabb3 {
+        UAabb3::new(UVec3::ZERO, VOXEL_DIM_PER_CHUNK * chunk_dim - UVec3::ONE)
+    }
+
+    pub(super) fn init(
+        chunk_dim: UVec3,
         plain_builder: &mut PlainBuilder,
         surface_builder: &mut SurfaceBuilder,
         contree_builder: &mut ContreeBuilder,
         scene_accel_builder: &mut SceneAccelBuilder,
     ) -> Result<()> {
-        plain_builder.chunk_init(UVec3::new(0, 0, 0), VOXEL_DIM_PER_CHUNK * CHUNK_DIM)?;
-
-        let chunk_pos_to_build_min = UVec3::new(0, 0, 0);
-        let chunk_pos_to_build_max = CHUNK_DIM;
-
-        for x in chunk_pos_to_build_min.x..chunk_pos_to_build_max.x {
-            for y in chunk_pos_to_build_min.y..chunk_pos_to_build_max.y {
-                for z in chunk_pos_to_build_min.z..chunk_pos_to_build_max.z {
-                    let chunk_idx = UVec3::new(x, y, z);
-                    let this_bound = UAabb3::new(
-                        chunk_idx * VOXEL_DIM_PER_CHUNK,
-                        (chunk_idx + UVec3::ONE) * VOXEL_DIM_PER_CHUNK - UVec3::ONE,
-                    );
-                    Self::mesh_generate(
-                        surface_builder,
-                        contree_builder,
-                        scene_accel_builder,
-                        this_bound,
-                    )?;
+        let world_bound = Self::world_voxel_bound(chunk_dim);
+        plain_builder.chunk_init(world_bound.min(), world_bound.dimensions() + UVec3::ONE)?;
+
+        for chunk_idx in world_bound.iter_chunks(VOXEL_DIM_PER_CHUNK) {
+            Self::mesh_generate(
+                surface_builder,
+                contree_builder,
+                scene_accel_builder,
+                ChunkIdx(chunk_idx).voxel_bound(),
+            )?;
+        }
+
+        BENCH.lock().unwrap().summary();
+        Ok(())
+    }
+
+    /// Refreshes what a crash bundle reports about the app, called after each completed frame.
+    fn update_diagnostics(&mut self) {
+        let settings = vec![
+            ("flora_lod_desc", format!("{:?}", self.flora_lod_desc)),
+            ("flora_blend_mode", format!("{:?}", self.flora_blend_mode)),
+            ("time_of_day", self.world_clock.time_of_day.to_string()),
+            (
+                "auto_daynight_cycle",
+                self.world_clock.is_running.to_string(),
+            ),
+            ("moon_phase", self.world_clock.moon_phase.to_string()),
+            (
+                "sun_altitude",
+                self.tracer_settings.sun.altitude.to_string(),
+            ),
+            ("sun_azimuth", self.tracer_settings.sun.azimuth.to_string()),
+            (
+                "temporal_alpha",
+                self.tracer_settings.denoiser.temporal.alpha.to_string(),
+            ),
+            (
+                "temporal_position_phi",
+                self.tracer_settings
+                    .denoiser
+                    .temporal
+                    .position_phi
+                    .to_string(),
+            ),
+            (
+                "is_spatial_denoising_enabled",
+                self.tracer_settings.denoiser.spatial.is_enabled.to_string(),
+            ),
+            (
+                "a_trous_iteration_count",
+                self.tracer_settings
+                    .denoiser
+                    .spatial
+                    .a_trous_iteration_count
+                    .to_string(),
+            ),
+            (
+                "is_taa_enabled",
+                self.tracer_settings.is_taa_enabled.to_string(),
+            ),
+            (
+                "is_shadow_caching_enabled",
+                self.tracer_settings.is_shadow_caching_enabled.to_string(),
+            ),
+            (
+                "god_ray_max_checks",
+                self.tracer_settings.god_ray.max_checks.to_string(),
+            ),
+            ("is_fly_mode", self.is_fly_mode.to_string()),
+            ("is_photo_mode", self.photo_mode.is_active().to_string()),
+            (
+                "window_extent",
+                format!("{:?}", self.window_state.window_extent()),
+            ),
+        ];
+
+        // the frame is read back before taking the lock, so a failing readback can't panic while
+        // the crash handler needs it
+        let is_frame_capture_due =
+            self.is_frame_capture_enabled && DIAGNOSTICS.lock().unwrap().is_frame_capture_due();
+        let captured_frame = if is_frame_capture_due {
+            let image = self.tracer.get_screen_output_tex().get_image();
+            let extent = image.get_desc().extent;
+            match image.fetch_data(
+                &self.vulkan_ctx.get_general_queue(),
+                self.vulkan_ctx.command_pool(),
+            ) {
+                Ok(rgba) => Some(CapturedFrame {
+                    width: extent.width,
+                    height: extent.height,
+                    rgba,
+                }),
+                Err(e) => {
+                    log::warn!("Failed to capture frame for diagnostics: {}", e);
+                    None
                 }
             }
+        } else {
+            None
+        };
+
+        let mut diagnostics = DIAGNOSTICS.lock().unwrap();
+        diagnostics.camera_pos = self.tracer.camera_position();
+        diagnostics.camera_front = self.tracer.camera_vectors().front;
+        diagnostics.settings = settings;
+        if let Some(frame) = captured_frame {
+            diagnostics.set_last_frame(frame);
         }
+    }
 
-        BENCH.lock().unwrap().summary();
+    /// Runtime controls for how validation messages are logged. Loading the layer itself is decided
+    /// at startup.
+    fn validation_gui(ui: &mut egui::Ui) {
+        if !is_validation_layer_active() {
+            ui.label(format!(
+                "Validation layer is off, start with {}=1 to enable it.",
+                VALIDATION_ENV_VAR
+            ));
+            return;
+        }
+
+        let mut settings = validation_settings();
+        egui::ComboBox::from_label("Min Severity")
+            .selected_text(settings.min_level.as_str())
+            .show_ui(ui, |ui| {
+                for level in [
+                    log::Level::Error,
+                    log::Level::Warn,
+                    log::Level::Info,
+                    log::Level::Debug,
+                ] {
+                    ui.selectable_value(&mut settings.min_level, level, level.as_str());
+                }
+            });
+        ui.add(egui::Checkbox::new(
+            &mut settings.deduplicate,
+            "Deduplicate Messages",
+        ));
+        ui.add(egui::Checkbox::new(
+            &mut settings.pretty_print,
+            "Pretty Print",
+        ));
+        ui.add(egui::Checkbox::new(
+            &mut settings.break_on_error,
+            "Break On Error",
+        ));
+        if settings != validation_settings() {
+            set_validation_settings(settings);
+        }
+
+        let stats = validation_stats();
+        ui.label(format!(
+            "{} unique messages, {} duplicates suppressed",
+            stats.unique_messages, stats.suppressed_duplicates
+        ));
+        if ui.button("Reset Seen Messages").clicked() {
+            reset_validation_messages();
+        }
+    }
+
+    /// Per-pass GPU milliseconds of the tracer, measured with timestamp queries.
+    fn gpu_timings_gui(ui: &mut egui::Ui, tracer: &mut Tracer) {
+        let mut is_enabled = tracer.is_gpu_profiling_enabled();
+        if ui
+            .checkbox(&mut is_enabled, "Measure GPU Pass Times")
+            .changed()
+        {
+            tracer.set_gpu_profiling_enabled(is_enabled);
+        }
+
+        let timings = tracer.gpu_pass_timings();
+        egui::Grid::new("gpu_timings_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Pass");
+                ui.strong("Last (ms)");
+                ui.strong("Average (ms)");
+                ui.end_row();
+
+                for timing in timings {
+                    ui.label(timing.name);
+                    ui.label(format!("{:.3}", timing.last_ms));
+                    ui.label(format!("{:.3}", timing.average_ms));
+                    ui.end_row();
+                }
+
+                ui.strong("Total");
+                ui.strong(format!(
+                    "{:.3}",
+                    timings.iter().map(|timing| timing.last_ms).sum::<f32>()
+                ));
+                ui.strong(format!(
+                    "{:.3}",
+                    timings.iter().map(|timing| timing.average_ms).sum::<f32>()
+                ));
+                ui.end_row();
+            });
+    }
+
+    /// Lists every chunk with its contree pool usage and last rebuild time, plus buttons to
+    /// evict/rebuild it.
+    fn chunk_residency_gui(
+        ui: &mut egui::Ui,
+        chunk_dim: UVec3,
+        contree_builder: &ContreeBuilder,
+        scene_accel_builder: &SceneAccelBuilder,
+        checksums: &HashMap<UVec3, u64>,
+        actions: &mut Vec<ChunkDebugAction>,
+    ) {
+        const MB: f64 = 1024.0 * 1024.0;
+
+        let (node_stats, leaf_stats) = contree_builder.pool_stats();
+        for (name, stats) in [("Node Pool", node_stats), ("Leaf Pool", leaf_stats)] {
+            ui.label(format!(
+                "{}: {:.1} / {:.1} MB, {} free blocks, largest {:.1} MB, fragmentation {:.0}%",
+                name,
+                stats.used as f64 / MB,
+                stats.total as f64 / MB,
+                stats.free_block_count,
+                stats.largest_free_block as f64 / MB,
+                stats.fragmentation * 100.0,
+            ));
+        }
+        ui.label(format!(
+            "Chunks in scene texture: {}",
+            scene_accel_builder.resident_chunk_count()
+        ));
+        ui.add_space(4.0);
+
+        let residency: HashMap<ChunkIdx, ChunkContreeInfo> = contree_builder
+            .chunk_residency()
+            .into_iter()
+            .map(|info| (info.chunk_idx, info))
+            .collect();
+
+        egui::ScrollArea::vertical()
+            .id_salt("chunk_residency_scroll")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("chunk_residency_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Chunk");
+                        ui.strong("Nodes (KB)");
+                        ui.strong("Leaves (KB)");
+                        ui.strong("Last Build");
+                        ui.strong("Checksum");
+                        ui.strong("");
+                        ui.end_row();
+
+                        for x in 0..chunk_dim.x {
+                            for y in 0..chunk_dim.y {
+                                for z in 0..chunk_dim.z {
+                                    let chunk_idx = ChunkIdx::new(x, y, z);
+                                    ui.label(format!("({}, {}, {})", x, y, z));
+
+                                    match residency.get(&chunk_idx) {
+                                        Some(info) => {
+                                            ui.label(format!(
+                                                "{:.1}",
+                                                info.node_size_in_bytes as f64 / 1024.0
+                                            ));
+                                            ui.label(format!(
+                                                "{:.1}",
+                                                info.leaf_size_in_bytes as f64 / 1024.0
+                                            ));
+                                            ui.label(format!(
+                                                "{:.0}s ago ({:.1} ms)",
+                                                info.last_build_time.elapsed().as_secs_f32(),
+                                                info.last_build_duration.as_secs_f32() * 1000.0
+                                            ));
+                                        }
+                                        None => {
+                                            ui.label("-");
+                                            ui.label("-");
+                                            ui.label(RichText::new("evicted").color(Color32::GRAY));
+                                        }
+                                    }
+                                    match checksums.get(&chunk_idx) {
+                                        Some(checksum) => {
+                                            ui.monospace(format!("{:016x}", checksum));
+                                        }
+                                        None => {
+                                            ui.label("-");
+                                        }
+                                    }
+
+                                    ui.horizontal(|ui| {
+                                        if ui
+                                            .add_enabled(
+                                                residency.contains_key(&chunk_idx),
+                                                egui::Button::new("Evict").small(),
+                                            )
+                                            .clicked()
+                                        {
+                                            actions.push(ChunkDebugAction::Evict(chunk_idx));
+                                        }
+                                        if ui.small_button("Rebuild").clicked() {
+                                            actions.push(ChunkDebugAction::Rebuild(chunk_idx));
+                                        }
+                                        if ui
+                                            .add_enabled(
+                                                residency.contains_key(&chunk_idx),
+                                                egui::Button::new("Hash").small(),
+                                            )
+                                            .clicked()
+                                        {
+                                            actions.push(ChunkDebugAction::Checksum(chunk_idx));
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            }
+                        }
+                    });
+            });
+    }
+
+    fn apply_chunk_debug_action(&mut self, action: ChunkDebugAction) -> Result<()> {
+        match action {
+            ChunkDebugAction::Evict(chunk_idx) => {
+                self.vulkan_ctx.device().wait_idle();
+                self.evict_chunk(chunk_idx)?;
+                log::info!("Evicted chunk {}", chunk_idx);
+            }
+            ChunkDebugAction::Rebuild(chunk_idx) => {
+                self.rebuild_chunk(chunk_idx)?;
+                log::info!("Rebuilt chunk {}", chunk_idx);
+            }
+            ChunkDebugAction::Checksum(chunk_idx) => {
+                if let Some(checksum) = self.contree_builder.chunk_checksum(chunk_idx)? {
+                    log::info!("Chunk {} checksum {:016x}", chunk_idx, checksum);
+                    self.chunk_checksums.insert(chunk_idx, checksum);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Frees the chunk's contree pool space and drops it from the scene, the device must be idle.
+    fn evict_chunk(&mut self, chunk_idx: ChunkIdx) -> Result<()> {
+        self.chunk_checksums.remove(&chunk_idx);
+        // remove from the scene texture first so the tracer never reads freed pool data
+        self.scene_accel_builder.evict_chunk(chunk_idx)?;
+        self.contree_builder.evict_chunk(chunk_idx.atlas_offset())?;
+        self.surface_builder.clear_surface(chunk_idx);
+        self.on_chunk_changed(chunk_idx);
+        Ok(())
+    }
+
+    fn rebuild_chunk(&mut self, chunk_idx: ChunkIdx) -> Result<()> {
+        self.chunk_checksums.remove(&chunk_idx);
+        Self::mesh_generate(
+            &mut self.surface_builder,
+            &mut self.contree_builder,
+            &mut self.scene_accel_builder,
+            chunk_idx.voxel_bound(),
+        )?;
+        self.on_chunk_changed(chunk_idx);
+        Ok(())
+    }
+
+    fn on_chunk_changed(&mut self, chunk_idx: ChunkIdx) {
+        let chunk_region = Aabb3::from_voxel_bound(&chunk_idx.voxel_bound());
+        self.tracer.mark_sky_visibility_dirty(Some(&chunk_region));
+        self.tracer.mark_shadows_dirty();
+        self.tracer.invalidate_history(Some(chunk_region));
+    }
+
+    /// Loads and unloads the chunks around the camera, turning streaming off loads every chunk
+    /// back. Call once per frame.
+    fn update_chunk_streaming(&mut self) -> Result<()> {
+        let work = if self.is_chunk_streaming_enabled {
+            self.chunk_streamer.update(
+                self.tracer.camera_position(),
+                self.contree_builder.built_chunks().collect::<Vec<_>>(),
+            )
+        } else {
+            ChunkStreamingWork {
+                loads: self.chunk_streamer.take_unloaded(),
+                unloads: Vec::new(),
+            }
+        };
+        if work.is_empty() {
+            return Ok(());
+        }
+
+        if !work.unloads.is_empty() {
+            self.vulkan_ctx.device().wait_idle();
+        }
+        for chunk_idx in work.unloads {
+            self.evict_chunk(chunk_idx)?;
+            log::debug!("Streamed out chunk {}", chunk_idx);
+        }
+        for chunk_idx in work.loads {
+            match self.rebuild_chunk(chunk_idx) {
+                Ok(()) => log::debug!("Streamed in chunk {}", chunk_idx),
+                Err(e) => {
+                    self.chunk_streamer.forget_load(chunk_idx);
+                    log::error!("Failed to stream in chunk {}: {:#}", chunk_idx, e);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -879,11 +2089,29 @@ impl App {
         WindowState::new(event_loop, &window_descriptor)
     }
 
+    pub(super) fn create_allocator(vulkan_ctx: &VulkanContext) -> Allocator {
+        let device = vulkan_ctx.device();
+        let gpu_allocator = {
+            let allocator_create_info = AllocatorCreateDesc {
+                instance: vulkan_ctx.instance().as_raw().clone(),
+                device: device.as_raw().clone(),
+                physical_device: vulkan_ctx.physical_device().as_raw(),
+                debug_settings: Default::default(),
+                buffer_device_address: true,
+                allocation_sizes: Default::default(),
+            };
+            gpu_allocator::vulkan::Allocator::new(&allocator_create_info)
+                .expect("Failed to create gpu allocator")
+        };
+        Allocator::new(device, Arc::new(Mutex::new(gpu_allocator)))
+    }
+
     fn create_vulkan_context(window_state: &WindowState) -> VulkanContext {
         VulkanContext::new(
             &window_state.window(),
             VulkanContextDesc {
                 name: "Re: Flora".into(),
+                enable_validation: is_validation_requested(),
             },
         )
     }
@@ -894,6 +2122,7 @@ impl App {
         event_loop.exit();
     }
 
+    /// Positions without ground below them are left out with a warning.
     fn query_terrain_heights_for_positions(&mut self, positions_2d: &[Vec2]) -> Result<Vec<Vec3>> {
         if positions_2d.is_empty() {
             return Ok(vec![]);
@@ -908,11 +2137,13 @@ impl App {
         let terrain_heights = self.tracer.query_terrain_heights_batch(&query_positions)?;
 
         // convert back to world coordinates and create Vec3s
-        let positions_3d = positions_2d
-            .iter()
-            .zip(terrain_heights.iter())
-            .map(|(pos_2d, &height)| Vec3::new(pos_2d.x, height, pos_2d.y))
-            .collect();
+        let mut positions_3d = Vec::with_capacity(positions_2d.len());
+        for (pos_2d, height) in positions_2d.iter().zip(terrain_heights) {
+            match height {
+                Ok(height) => positions_3d.push(Vec3::new(pos_2d.x, height, pos_2d.y)),
+                Err(miss) => log::warn!("Skipped the placement at {}: {}", pos_2d, miss),
+            }
+        }
 
         Ok(positions_3d)
     }
@@ -924,10 +2155,8 @@ impl App {
         // don't leak looping sounds when rebuilding the tree geometry.
         self.tree_audio_manager.remove_all();
 
-        self.plain_builder.chunk_init(
-            self.prev_bound.min(),
-            self.prev_bound.max() - self.prev_bound.min(),
-        )?;
+        self.plain_builder
+            .chunk_init(self.prev_bound.min(), self.prev_bound.dimensions())?;
 
         // force mesh regeneration after cleanup to ensure terrain is properly accessible for querying
         Self::mesh_generate(
@@ -936,6 +2165,10 @@ impl App {
             &mut self.scene_accel_builder,
             self.prev_bound,
         )?;
+        let prev_region = Aabb3::from_voxel_bound(&self.prev_bound);
+        self.tracer.mark_sky_visibility_dirty(Some(&prev_region));
+        self.tracer.mark_shadows_dirty();
+        self.tracer.invalidate_history(Some(prev_region));
 
         Ok(())
     }
@@ -953,7 +2186,8 @@ impl App {
 
         let terrain_height = self
             .tracer
-            .query_terrain_height(glam::Vec2::new(tree_hori_position.x, tree_hori_position.y))?;
+            .query_terrain_height(glam::Vec2::new(tree_hori_position.x, tree_hori_position.y))
+            .with_context(|| format!("Can't place a tree at {}", tree_hori_position))?;
 
         let tree_pos = Vec3::new(tree_hori_position.x, terrain_height, tree_hori_position.y);
         self.add_tree_at_pos(tree_desc, tree_pos, increment)?;
@@ -967,10 +2201,9 @@ impl App {
         scene_accel_builder: &mut SceneAccelBuilder,
         bound: UAabb3,
     ) -> Result<()> {
-        let affected_chunk_indices = get_affected_chunk_indices(bound.min(), bound.max());
-
-        for chunk_id in affected_chunk_indices {
-            let atlas_offset = chunk_id * VOXEL_DIM_PER_CHUNK;
+        profile_scope!("mesh_generate");
+        for chunk_id in bound.iter_chunks(VOXEL_DIM_PER_CHUNK).map(ChunkIdx) {
+            let atlas_offset = chunk_id.atlas_offset();
 
             let now = Instant::now();
             let res = surface_builder.build_surface(chunk_id);
@@ -983,7 +2216,24 @@ impl App {
             BENCH.lock().unwrap().record("build_surface", now.elapsed());
 
             let now = Instant::now();
-            let res = contree_builder.build_and_alloc(atlas_offset).unwrap();
+            let res = match contree_builder.build_and_alloc(atlas_offset) {
+                Err(e) if e.is::<PoolExhausted>() => {
+                    // packing the pools may free a block large enough
+                    log::warn!("{}, compacting the pools", e);
+                    Self::compact_contree_pools(contree_builder, scene_accel_builder, true)?;
+                    contree_builder.build_and_alloc(atlas_offset)
+                }
+                res => res,
+            };
+            let res = match res {
+                Ok(res) => res,
+                Err(e) if e.is::<PoolExhausted>() => {
+                    // the chunk keeps its old contree, the edit doesn't show up in it
+                    log::error!("Refused to rebuild chunk {}: {}", chunk_id, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
             BENCH
                 .lock()
                 .unwrap()
@@ -1000,22 +2250,34 @@ impl App {
                 log::debug!("Don't need to update scene tex because the chunk is empty");
             }
         }
-        return Ok(());
-
-        fn get_affected_chunk_indices(min_bound: UVec3, max_bound: UVec3) -> Vec<UVec3> {
-            let min_chunk_idx = min_bound / VOXEL_DIM_PER_CHUNK;
-            let max_chunk_idx = max_bound / VOXEL_DIM_PER_CHUNK;
+        Self::compact_contree_pools(contree_builder, scene_accel_builder, false)
+    }
 
-            let mut affacted = Vec::new();
-            for x in min_chunk_idx.x..=max_chunk_idx.x {
-                for y in min_chunk_idx.y..=max_chunk_idx.y {
-                    for z in min_chunk_idx.z..=max_chunk_idx.z {
-                        affacted.push(UVec3::new(x, y, z));
-                    }
-                }
+    /// Edits keep reallocating chunks, once the pools are scattered, or `force` is set, the
+    /// chunks are packed and the scene texture follows the chunks that moved.
+    fn compact_contree_pools(
+        contree_builder: &mut ContreeBuilder,
+        scene_accel_builder: &mut SceneAccelBuilder,
+        force: bool,
+    ) -> Result<()> {
+        let moved_chunks = if force {
+            contree_builder.compact_pools()?
+        } else {
+            contree_builder.compact_pools_if_fragmented()?
+        };
+        if moved_chunks.is_empty() {
+            return Ok(());
+        }
+        log::info!(
+            "Compacted the contree pools, {} chunks moved",
+            moved_chunks.len()
+        );
+        for (chunk_idx, (node_offset, leaf_offset)) in moved_chunks {
+            if scene_accel_builder.chunk_entry(chunk_idx).is_some() {
+                scene_accel_builder.update_scene_tex(chunk_idx, node_offset, leaf_offset)?;
             }
-            affacted
         }
+        Ok(())
     }
 
     pub fn on_window_event(
@@ -1076,7 +2338,15 @@ impl App {
                     self.window_state.toggle_fullscreen();
                 }
 
-                if event.state == ElementState::Pressed && event.physical_key == KeyCode::KeyG {
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::KeyO {
+                    self.toggle_photo_mode();
+                }
+
+                // photo mode keeps the camera free
+                if event.state == ElementState::Pressed
+                    && event.physical_key == KeyCode::KeyG
+                    && !self.photo_mode.is_active()
+                {
                     let was_fly_mode = self.is_fly_mode;
                     self.is_fly_mode = !self.is_fly_mode;
 
@@ -1086,896 +2356,1223 @@ impl App {
                     }
                 }
 
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::F5 {
+                    if let Err(e) = self.save_to_slot(0) {
+                        log::error!("Failed to quick save: {}", e);
+                    }
+                }
+
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::F9 {
+                    if let Err(e) = self.load_from_slot(0) {
+                        log::error!("Failed to quick load: {}", e);
+                    }
+                }
+
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::F6 {
+                    let (yaw, pitch) = self.tracer.camera_orientation();
+                    self.camera_path_player.record_keyframe(
+                        self.tracer.camera_position(),
+                        yaw,
+                        pitch,
+                    );
+                }
+
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::F7 {
+                    self.camera_path_player.toggle_playback();
+                }
+
+                if event.state == ElementState::Pressed && event.physical_key == KeyCode::F12 {
+                    self.is_screenshot_requested = true;
+                }
+
+                if event.state == ElementState::Pressed
+                    && event.physical_key == KeyCode::KeyP
+                    && !self.photo_mode.is_active()
+                {
+                    self.planting_tool.is_active = !self.planting_tool.is_active;
+                    self.voxel_edit_tool.is_active &= !self.planting_tool.is_active;
+                }
+
+                if event.state == ElementState::Pressed
+                    && event.physical_key == KeyCode::KeyV
+                    && !self.photo_mode.is_active()
+                {
+                    self.voxel_edit_tool.is_active = !self.voxel_edit_tool.is_active;
+                    self.planting_tool.is_active &= !self.voxel_edit_tool.is_active;
+                }
+
+                if event.state == ElementState::Pressed
+                    && event.physical_key == KeyCode::KeyX
+                    && !event.repeat
+                    && !self.window_state.is_cursor_visible()
+                    && !self.photo_mode.is_active()
+                {
+                    self.cut_grass();
+                }
+
                 if !self.window_state.is_cursor_visible() {
                     self.tracer.handle_keyboard(&event);
                 }
             }
 
             // redraw the window
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if self.planting_tool.is_active && !self.window_state.is_cursor_visible() {
+                    if let Err(e) = self.plant() {
+                        log::error!("Failed to plant: {}", e);
+                    }
+                } else if self.voxel_edit_tool.is_active && !self.window_state.is_cursor_visible() {
+                    if let Err(e) = self.edit_voxels() {
+                        log::error!("Failed to edit voxels: {}", e);
+                    }
+                } else if self.is_tree_picking_active && self.window_state.is_cursor_visible() {
+                    if let Err(e) = self.pick_tree_spot() {
+                        log::error!("Failed to pick a tree spot: {}", e);
+                    }
+                }
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Vec2::new(position.x as f32, position.y as f32);
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.is_shift_held = modifiers.state().shift_key();
+            }
+
             WindowEvent::RedrawRequested => {
                 // when the windiw is resized, redraw is called afterwards, so when the window is minimized, return
                 if self.window_state.is_minimized() {
                     return;
                 }
+                self.render_frame();
+            }
+            _ => (),
+        }
+    }
 
-                // resize the window if needed
-                if self.is_resize_pending {
-                    self.on_resize();
-                }
+    /// Runs the phases of one frame in order. Each phase is its own method, so a mode that
+    /// changes how frames are made, like pausing or rendering offscreen, replaces or repeats one
+    /// phase instead of forking the whole loop:
+    ///
+    /// - [`Self::begin_frame`] finishes the previous frame and steps the clock and the camera
+    /// - [`Self::update_frame`] updates the tools, the GUI and the world
+    /// - [`Self::acquire_frame`] waits for a free frame slot and a swapchain image
+    /// - [`Self::record_frame`] records the trace, the overlays and the GUI
+    /// - [`Self::submit_frame`] and [`Self::present_frame`] hand the frame to the GPU
+    /// - [`Self::end_frame`] updates what follows the rendered frame, like the music
+    fn render_frame(&mut self) {
+        profile_scope!("frame");
+        let delta_time = self.begin_frame();
+        self.update_frame(delta_time);
+        let Some(frame) = self.acquire_frame(delta_time) else {
+            return;
+        };
+        self.record_frame(&frame);
+        self.submit_frame(&frame);
+        self.present_frame(&frame);
+        self.end_frame(&frame);
+    }
 
-                self.time_info.update();
-                let frame_delta_time = self.time_info.delta_time();
+    /// Returns the seconds since the previous frame.
+    fn begin_frame(&mut self) -> f32 {
+        if self.is_resize_pending {
+            self.on_resize();
+        }
 
-                if !self.window_state.is_cursor_visible() {
-                    // grab the value and immediately reset the accumulator
-                    let mouse_delta = self.accumulated_mouse_delta;
-                    self.accumulated_mouse_delta = Vec2::ZERO;
+        self.time_info.update();
+        let frame_delta_time = self.time_info.delta_time();
 
-                    let alpha = 0.4; // mouse smoothing factor: 0 = no smoothing, 1 = infinite smoothing
-                    self.smoothed_mouse_delta =
-                        self.smoothed_mouse_delta * alpha + mouse_delta * (1.0 - alpha);
+        if !self.window_state.is_cursor_visible() {
+            // grab the value and immediately reset the accumulator
+            let mouse_delta = self.accumulated_mouse_delta;
+            self.accumulated_mouse_delta = Vec2::ZERO;
 
-                    self.tracer.handle_mouse(self.smoothed_mouse_delta);
-                }
+            let alpha = 0.4; // mouse smoothing factor: 0 = no smoothing, 1 = infinite smoothing
+            self.smoothed_mouse_delta =
+                self.smoothed_mouse_delta * alpha + mouse_delta * (1.0 - alpha);
 
-                let mut tree_desc_changed = false;
-                self.egui_renderer
-                    .update(&self.window_state.window(), |ctx| {
-                        let mut style = (*ctx.style()).clone();
-                        style.visuals.override_text_color = Some(egui::Color32::WHITE);
-                        ctx.set_style(style);
-
-                        let mut config_panel_open = self.config_panel_visible;
-                        if config_panel_open {
-                            let config_frame = egui::containers::Frame {
-                                fill: Color32::from_rgba_premultiplied(20, 22, 30, 245),
-                                inner_margin: egui::Margin::symmetric(18, 12),
-                                corner_radius: egui::CornerRadius::same(14),
-                                shadow: egui::epaint::Shadow {
-                                    offset: [0, 12],
-                                    blur: 32,
-                                    spread: 6,
-                                    color: Color32::from_rgba_premultiplied(0, 0, 0, 180),
-                                },
-                                stroke: egui::Stroke::new(
-                                    1.0,
-                                    Color32::from_rgba_premultiplied(255, 255, 255, 26),
-                                ),
-                                ..Default::default()
-                            };
-
-                            egui::Window::new("Configuration")
-                                .id(egui::Id::new("config_panel"))
-                                .open(&mut config_panel_open)
-                                .frame(config_frame)
-                                .resizable(true)
-                                .movable(true)
-                                .default_pos(egui::pos2(24.0, 24.0))
-                                .default_width(380.0)
-                                .min_width(280.0)
-                                .show(ctx, |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.heading(RichText::new("Scene Configuration").size(18.0));
+            self.tracer.handle_mouse(self.smoothed_mouse_delta);
+        }
+
+        self.finish_previous_frame(frame_delta_time);
+        frame_delta_time
+    }
+
+    fn update_frame(&mut self, delta_time: f32) {
+        if let Err(e) = self.planting_tool.update(&mut self.tracer) {
+            log::error!("Failed to raycast the planting spot: {}", e);
+        }
+        self.planting_tool.draw_ghost(&mut self.debug_draw);
+        if let Err(e) = self.voxel_edit_tool.update(&mut self.tracer) {
+            log::error!("Failed to raycast the voxel edit spot: {}", e);
+        }
+        self.voxel_edit_tool.draw_ghost(&mut self.debug_draw);
+        if let Err(e) = self.probe_volume_tool.update(&self.tracer) {
+            log::error!("Failed to read back the probe irradiance: {}", e);
+        }
+        self.probe_volume_tool.draw(&mut self.debug_draw);
+        if let Err(e) = self.update_chunk_streaming() {
+            log::error!("Failed to stream chunks: {:#}", e);
+        }
+
+        self.update_gui();
+
+        if let Some(slot) = self.pending_slot_load.take() {
+            if let Err(e) = self.load_from_slot(slot) {
+                log::error!("Failed to load slot {}: {}", slot, e);
+            }
+        }
+
+        if self.regenerate_trees_requested {
+            self.regenerate_trees_requested = false;
+            if let Err(e) = self.start_forest_generation() {
+                log::error!("Failed to regenerate procedural trees: {}", e);
+            }
+        }
+        if let Err(e) = self.step_forest_generation(FOREST_GENERATION_FRAME_BUDGET) {
+            log::error!("Failed to plant procedural trees: {}", e);
+            self.forest_generation = None;
+        }
+
+        if self.apply_world_gen_requested {
+            self.apply_world_gen_requested = false;
+            if let Err(e) = self.apply_world_gen() {
+                log::error!("Failed to apply the world gen parameters: {}", e);
+            }
+        }
+        if let Err(e) = self.update_world_gen_preview() {
+            log::error!("Failed to update the world gen preview: {}", e);
+        }
+
+        // the world stands still while a photo is framed
+        let delta_time = self.photo_mode.simulation_delta_time(delta_time);
+        self.scene_time += delta_time;
+        let days = self.world_clock.advance(delta_time);
+        if days > 0.0 {
+            self.surface_builder.regrow_grass(days * 24.0);
+            self.path_wear.fade(days);
+        }
+        self.tracer
+            .update_world_clock(&self.world_clock, &mut self.tracer_settings.sun);
+        self.tracer
+            .set_leaf_density(self.seasonal_leaves.density(self.world_clock.season));
+        self.weather.advance(delta_time, days);
+        self.tracer.update_weather(&self.weather);
+        self.wind_field.update(delta_time, &self.weather);
+        if let Err(e) = self.tracer.update_wind_field(&self.wind_field) {
+            log::error!("Failed to update the wind field: {}", e);
+        }
+    }
+
+    /// Builds the GUI and applies the actions picked in it.
+    fn update_gui(&mut self) {
+        let mut tree_desc_changed = false;
+        let mut is_photo_mode_toggled = false;
+        let mut chunk_debug_actions = Vec::new();
+        self.egui_renderer
+            .update(&self.window_state.window(), |ctx| {
+                let mut style = (*ctx.style()).clone();
+                style.visuals.override_text_color = Some(egui::Color32::WHITE);
+                ctx.set_style(style);
+
+                let mut config_panel_open = self.config_panel_visible;
+                if config_panel_open {
+                    let config_frame = egui::containers::Frame {
+                        fill: Color32::from_rgba_premultiplied(20, 22, 30, 245),
+                        inner_margin: egui::Margin::symmetric(18, 12),
+                        corner_radius: egui::CornerRadius::same(14),
+                        shadow: egui::epaint::Shadow {
+                            offset: [0, 12],
+                            blur: 32,
+                            spread: 6,
+                            color: Color32::from_rgba_premultiplied(0, 0, 0, 180),
+                        },
+                        stroke: egui::Stroke::new(
+                            1.0,
+                            Color32::from_rgba_premultiplied(255, 255, 255, 26),
+                        ),
+                        ..Default::default()
+                    };
+
+                    egui::Window::new("Configuration")
+                        .id(egui::Id::new("config_panel"))
+                        .open(&mut config_panel_open)
+                        .frame(config_frame)
+                        .resizable(true)
+                        .movable(true)
+                        .default_pos(egui::pos2(24.0, 24.0))
+                        .default_width(380.0)
+                        .min_width(280.0)
+                        .show(ctx, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.heading(RichText::new("Scene Configuration").size(18.0));
+                            });
+
+                            ui.add_space(4.0);
+                            ui.separator();
+                            ui.add_space(4.0);
+
+                            egui::ScrollArea::vertical()
+                                .auto_shrink([false; 2])
+                                .show(ui, |ui| {
+                                ui.collapsing("Debug Settings", |ui| {
+                                    self.tracer_settings.debug.edit_by_gui(ui);
+                                });
+
+
+                                ui.collapsing("Camera Feel", |ui| {
+                                    self.camera_feel_desc.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Player Physics", |ui| {
+                                    self.character_controller_desc.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Camera Path", |ui| {
+                                    let file_path =
+                                        PathBuf::from(full_path_from_relative(CAMERA_PATH_FILE));
+                                    self.camera_path_player.edit_by_gui(ui, &file_path);
+                                });
+
+                                ui.collapsing("Photo Mode", |ui| {
+                                    ui.label("O freezes the world and frees the camera");
+                                    let label = if self.photo_mode.is_active() {
+                                        "Exit Photo Mode"
+                                    } else {
+                                        "Enter Photo Mode"
+                                    };
+                                    is_photo_mode_toggled |= ui.button(label).clicked();
+                                    ui.separator();
+                                    ui.label("Depth Of Field");
+                                    self.tracer_settings.dof.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Flora LOD", |ui| {
+                                    self.flora_lod_desc.edit_by_gui(ui);
+                                    ui.separator();
+                                    self.flora_blend_mode.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Audio", |ui| {
+                                    let mut smoothing = self
+                                        .spatial_sound_manager
+                                        .listener_rotation_smoothing();
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(&mut smoothing, 0.0..=0.5)
+                                                .text("Listener Rotation Smoothing (s)"),
+                                        )
+                                        .changed()
+                                    {
+                                        self.spatial_sound_manager
+                                            .set_listener_rotation_smoothing(smoothing);
+                                    }
+
+                                    let mut max_voices =
+                                        self.spatial_sound_manager.max_voices();
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(&mut max_voices, 1..=128)
+                                                .text("Max Voices"),
+                                        )
+                                        .changed()
+                                    {
+                                        self.spatial_sound_manager
+                                            .set_max_voices(max_voices);
+                                    }
+
+                                    ui.collapsing("Mixer", |ui| {
+                                        let mut mixer = self.spatial_sound_manager.mixer();
+                                        if mixer.edit_by_gui(ui) {
+                                            if let Err(e) =
+                                                self.spatial_sound_manager.set_mixer(mixer)
+                                            {
+                                                log::error!("Failed to apply the mixer: {}", e);
+                                            }
+                                        }
+                                        if ui.button("Save To Config").clicked() {
+                                            self.config.mixer = mixer;
+                                            if let Err(e) = self.config.save() {
+                                                log::error!("Failed to save the mixer: {:#}", e);
+                                            }
+                                        }
+                                    });
+
+                                    ui.collapsing("Occlusion", |ui| {
+                                        self.sound_occlusion.desc.edit_by_gui(ui);
                                     });
 
-                                    ui.add_space(4.0);
                                     ui.separator();
-                                    ui.add_space(4.0);
+                                    let mut music_volume = self.music_manager.volume_db();
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut music_volume,
+                                                -60.0..=6.0,
+                                            )
+                                            .text("Music Volume (dB)"),
+                                        )
+                                        .changed()
+                                    {
+                                        self.music_manager.set_volume_db(music_volume);
+                                    }
+                                    let mut crossfade_time =
+                                        self.music_manager.crossfade_time();
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut crossfade_time,
+                                                0.0..=20.0,
+                                            )
+                                            .text("Music Crossfade (s)"),
+                                        )
+                                        .changed()
+                                    {
+                                        self.music_manager
+                                            .set_crossfade_time(crossfade_time);
+                                    }
+                                    for (stem, gain) in self.music_manager.stem_gains() {
+                                        ui.label(format!("{}: {:.2}", stem.name(), gain));
+                                    }
+
+                                    let automation_inputs = AutomationInputs {
+                                        time_of_day: self.world_clock.time_of_day,
+                                        wind_strength: self.weather.wind_strength,
+                                    };
+                                    ui.collapsing("Automation", |ui| {
+                                        self.audio_automation
+                                            .edit_by_gui(ui, &automation_inputs);
+                                    });
+                                });
 
-                                    egui::ScrollArea::vertical()
-                                        .auto_shrink([false; 2])
-                                        .show(ui, |ui| {
-                                        ui.collapsing("Debug Settings", |ui| {
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.debug_float,
-                                                    0.0..=10.0,
-                                                )
-                                                .text("Debug Float"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.debug_uint, 0..=100)
-                                                    .text("Debug UInt"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.lod_distance, 0.0..=10.0)
-                                                    .text("LOD Distance"),
-                                            );
-                                            ui.add(egui::Checkbox::new(
-                                                &mut self.debug_bool,
-                                                "Debug Bool",
-                                            ));
-                                        });
+                                ui.collapsing("Planting", |ui| {
+                                    self.planting_tool.edit_by_gui(ui);
+                                });
 
+                                ui.collapsing("Voxel Editing", |ui| {
+                                    self.voxel_edit_tool.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Sky Settings", |ui| {
-                                            ui.add(egui::Checkbox::new(
-                                                &mut self.auto_daynight_cycle,
-                                                "Auto Day/Night Cycle",
-                                            ));
+                                ui.collapsing("Light Probes", |ui| {
+                                    self.probe_volume_tool.edit_by_gui(ui);
+                                });
 
-                                            if self.auto_daynight_cycle {
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.time_of_day,
-                                                        0.0..=1.0,
-                                                    )
-                                                    .text("Time of Day (0:00 - 23:59)")
-                                                    .custom_formatter(|n, _| {
-                                                        let hour = (n * 24.0) as u32 % 24;
-                                                        let minute = (n * 24.0 * 60.0) as u32 % 60;
-                                                        format!("{:02}:{:02}", hour, minute)
-                                                    }),
-                                                );
-
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.latitude,
-                                                        -1.0..=1.0,
-                                                    )
-                                                    .text("Latitude (South Pole to North Pole)")
-                                                    .custom_formatter(|n, _| {
-                                                        if n < -0.5 {
-                                                            format!("South ({:.1})", n)
-                                                        } else if n > 0.5 {
-                                                            format!("North ({:.1})", n)
-                                                        } else {
-                                                            format!("Equator ({:.1})", n)
-                                                        }
-                                                    }),
-                                                );
-
-                                                ui.add(
-                                                    egui::Slider::new(&mut self.season, 0.0..=1.0)
-                                                        .text("Season (Winter to Summer)")
-                                                        .custom_formatter(|n, _| {
-                                                            if n < 0.125 {
-                                                                "Winter".to_string()
-                                                            } else if n < 0.375 {
-                                                                "Spring".to_string()
-                                                            } else if n < 0.625 {
-                                                                "Summer".to_string()
-                                                            } else if n < 0.875 {
-                                                                "Autumn".to_string()
-                                                            } else {
-                                                                "Winter".to_string()
-                                                            }
-                                                        }),
-                                                );
-
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.day_cycle_minutes,
-                                                        0.1..=60.0,
-                                                    )
-                                                    .text("Day Cycle Duration (Real Minutes)")
-                                                    .custom_formatter(|n, _| {
-                                                        if n < 1.0 {
-                                                            format!("{:.1}s", n * 60.0)
-                                                        } else {
-                                                            format!("{:.1}m", n)
-                                                        }
-                                                    }),
-                                                );
-
-// read-only displays for calculated values
-                                                ui.separator();
-                                                ui.label(format!(
-                                                    "Sun Altitude: {:.3}",
-                                                    self.sun_altitude
-                                                ));
-                                                ui.label(format!(
-                                                    "Sun Azimuth: {:.3}",
-                                                    self.sun_azimuth
-                                                ));
-                                            } else {
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.sun_altitude,
-                                                        -1.0..=1.0,
-                                                    )
-                                                    .text("Altitude (normalized)")
-                                                    .smart_aim(false),
-                                                );
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.sun_azimuth,
-                                                        0.0..=1.0,
-                                                    )
-                                                    .text("Azimuth (normalized)"),
-                                                );
-                                            }
-                                            ui.add(
-                                                egui::Slider::new(&mut self.sun_size, 0.0..=1.0)
-                                                    .text("Size (relative)"),
+                                ui.collapsing("Save Slots", |ui| {
+                                    ui.label(format!(
+                                        "{} edits since the world was built, F5/F9 quick save/load slot 0",
+                                        self.world_edits.len()
+                                    ));
+                                    for slot in 0..SAVE_SLOT_COUNT {
+                                        ui.horizontal(|ui| {
+                                            let saved_at = save_slot_time(slot).map_or(
+                                                "empty".to_string(),
+                                                |time| {
+                                                    chrono::DateTime::<chrono::Local>::from(time)
+                                                        .format("%Y-%m-%d %H:%M")
+                                                        .to_string()
+                                                },
                                             );
-                                            ui.horizontal(|ui| {
-                                                ui.label("Sun Color:");
-                                                ui.color_edit_button_srgba(&mut self.sun_color);
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.add(
-                                                    egui::Slider::new(
-                                                        &mut self.sun_luminance,
-                                                        0.0..=10.0,
-                                                    )
-                                                    .text("Sun Luminance"),
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Ambient Light:");
-                                                ui.color_edit_button_srgba(&mut self.ambient_light);
-                                            });
+                                            ui.label(format!("Slot {}: {}", slot, saved_at));
+                                            if ui.button("Save").clicked() {
+                                                if let Err(e) = self.save_to_slot(slot) {
+                                                    log::error!("Failed to save slot {}: {}", slot, e);
+                                                }
+                                            }
+                                            if ui.button("Load").clicked() {
+                                                self.pending_slot_load = Some(slot);
+                                            }
                                         });
+                                    }
+                                    ui.separator();
+                                    ui.horizontal(|ui| {
+                                        if ui
+                                            .button("Save World Snapshot")
+                                            .on_hover_text(
+                                                "Stores the built voxels, contrees and trees, the next startup loads them instead of generating the world",
+                                            )
+                                            .clicked()
+                                        {
+                                            if let Err(e) = self.save_world_snapshot() {
+                                                log::error!("Failed to save the world snapshot: {}", e);
+                                            }
+                                        }
+                                        if ui
+                                            .add_enabled(
+                                                WorldSnapshot::exists(),
+                                                egui::Button::new("Delete Snapshot"),
+                                            )
+                                            .clicked()
+                                        {
+                                            if let Err(e) = WorldSnapshot::delete() {
+                                                log::error!("Failed to delete the world snapshot: {}", e);
+                                            }
+                                        }
+                                    });
+                                });
 
-                                        ui.collapsing("Starlight Settings", |ui| {
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_iterations,
-                                                    1..=30,
-                                                )
-                                                .text("Iterations"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_formuparam,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Form Parameter"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_volsteps,
-                                                    1..=50,
-                                                )
-                                                .text("Volume Steps"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_stepsize,
-                                                    0.01..=1.0,
-                                                )
-                                                .text("Step Size"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_zoom,
-                                                    0.1..=2.0,
-                                                )
-                                                .text("Zoom"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_tile,
-                                                    0.1..=2.0,
-                                                )
-                                                .text("Tile"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_speed,
-                                                    0.001..=0.1,
-                                                )
-                                                .text("Speed"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_brightness,
-                                                    0.0001..=0.01,
-                                                )
-                                                .text("Brightness"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_darkmatter,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Dark Matter"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_distfading,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Distance Fading"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.starlight_saturation,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Saturation"),
-                                            );
-                                        });
+                                ui.collapsing("Player Collider", |ui| {
+                                    self.tracer_settings.player_collider.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Tree Settings", |ui| {
-                                            ui.label("Position:");
-                                            let x_changed = ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.debug_tree_pos.x,
-                                                        0.0..=4.0,
-                                                    )
-                                                    .text("X"),
-                                                )
-                                                .changed();
-                                            tree_desc_changed |= x_changed;
-
-                                            let z_changed = ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.debug_tree_pos.z,
-                                                        0.0..=4.0,
-                                                    )
-                                                    .text("Z"),
-                                                )
-                                                .changed();
-                                            tree_desc_changed |= z_changed;
+                                ui.collapsing("World Debug", |ui| {
+                                    Self::chunk_residency_gui(
+                                        ui,
+                                        self.config.chunk_dim,
+                                        &self.contree_builder,
+                                        &self.scene_accel_builder,
+                                        &self.chunk_checksums,
+                                        &mut chunk_debug_actions,
+                                    );
+                                });
+
+                                ui.collapsing("Chunk Streaming", |ui| {
+                                    ui.checkbox(
+                                        &mut self.is_chunk_streaming_enabled,
+                                        "Stream Chunks Around Camera",
+                                    )
+                                    .on_hover_text(
+                                        "Only the chunks around the camera hold contree pool space, turning it off loads every chunk back",
+                                    );
+                                    self.chunk_streamer.desc.edit_by_gui(ui);
+                                    ui.label(format!(
+                                        "Unloaded chunks: {}",
+                                        self.chunk_streamer.unloaded_count()
+                                    ));
+                                });
+
+                                ui.collapsing("Path Wear", |ui| {
+                                    self.path_wear.desc.edit_by_gui(ui);
+                                    if ui.button("Clear Path Wear").clicked() {
+                                        self.path_wear.clear();
+                                    }
+                                });
+
+                                ui.collapsing("Validation", |ui| {
+                                    Self::validation_gui(ui);
+                                });
+
+                                ui.collapsing("Diagnostics", |ui| {
+                                    ui.add(egui::Checkbox::new(
+                                        &mut self.is_frame_capture_enabled,
+                                        "Capture Frames For Crash Reports",
+                                    ));
+                                    if ui.button("Write Diagnostics Bundle").clicked() {
+                                        match write_diagnostics_bundle("requested by user") {
+                                            Ok(path) => log::info!(
+                                                "Wrote diagnostics bundle to {}",
+                                                path.display()
+                                            ),
+                                            Err(e) => log::error!(
+                                                "Failed to write diagnostics bundle: {}",
+                                                e
+                                            ),
+                                        }
+                                    }
+                                });
+
+                                ui.collapsing("GPU Timings", |ui| {
+                                    Self::gpu_timings_gui(ui, &mut self.tracer);
+                                });
+
+                                ui.collapsing("Sky Settings", |ui| {
+                                    self.world_clock.edit_by_gui(ui);
+                                    ui.separator();
+                                    self.tracer_settings.sun.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Weather", |ui| {
+                                    self.weather.edit_by_gui(ui);
+                                    ui.separator();
+                                    self.wind_field.desc.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Starlight Settings", |ui| {
+                                    self.tracer_settings.starlight.edit_by_gui(ui);
+                                });
+
+                                ui.collapsing("Tree Settings", |ui| {
+                                    ui.checkbox(
+                                        &mut self.is_tree_picking_active,
+                                        "Pick Tree Spots",
+                                    )
+                                    .on_hover_text(
+                                        "Click the world to plant this tree there, shift-click to remove the nearest tree",
+                                    );
+                                    ui.label("Position:");
+                                    let x_changed = ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.debug_tree_pos.x,
+                                                0.0..=4.0,
+                                            )
+                                            .text("X"),
+                                        )
+                                        .changed();
+                                    tree_desc_changed |= x_changed;
+
+                                    let z_changed = ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.debug_tree_pos.z,
+                                                0.0..=4.0,
+                                            )
+                                            .text("Z"),
+                                        )
+                                        .changed();
+                                    tree_desc_changed |= z_changed;
 
 // debug terrain height when X or Z position changes
-                                            if x_changed || z_changed {
+                                    if x_changed || z_changed {
 // clean up existing tree chunks before querying to avoid blocking the ray
-                                                if let Err(e) = self.plain_builder.chunk_init(
-                                                    self.prev_bound.min(),
-                                                    self.prev_bound.max() - self.prev_bound.min(),
-                                                ) {
-                                                    log::error!("Failed to clean up chunks for terrain query: {}", e);
-                                                } else {
+                                        if let Err(e) = self.plain_builder.chunk_init(
+                                            self.prev_bound.min(),
+                                            self.prev_bound.dimensions(),
+                                        ) {
+                                            log::error!("Failed to clean up chunks for terrain query: {}", e);
+                                        } else {
 // force mesh regeneration after cleanup
-                                                    if let Err(e) = Self::mesh_generate(
-                                                        &mut self.surface_builder,
-                                                        &mut self.contree_builder,
-                                                        &mut self.scene_accel_builder,
-                                                        self.prev_bound,
-                                                    ) {
-                                                        log::error!("Failed to regenerate mesh after cleanup: {}", e);
-                                                    } else {
+                                            if let Err(e) = Self::mesh_generate(
+                                                &mut self.surface_builder,
+                                                &mut self.contree_builder,
+                                                &mut self.scene_accel_builder,
+                                                self.prev_bound,
+                                            ) {
+                                                log::error!("Failed to regenerate mesh after cleanup: {}", e);
+                                            } else {
 // now query terrain height with clean terrain
-                                                        match self.tracer.query_terrain_height(glam::Vec2::new(
-                                                            self.debug_tree_pos.x,
-                                                            self.debug_tree_pos.z,
-                                                        )) {
-                                                            Ok(terrain_height) => {
-                                                                let terrain_height_scaled = terrain_height * 256.0;
-                                                                log::info!("Debug terrain query - Position: ({}, {}), Terrain height: {}", 
-                                                                    self.debug_tree_pos.x, self.debug_tree_pos.z, terrain_height_scaled);
-                                                            }
-                                                            Err(e) => {
-                                                                log::error!("Failed to query terrain height: {}", e);
-                                                            }
-                                                        }
+                                                match self.tracer.query_terrain_height(glam::Vec2::new(
+                                                    self.debug_tree_pos.x,
+                                                    self.debug_tree_pos.z,
+                                                )) {
+                                                    Ok(terrain_height) => {
+                                                        let terrain_height_scaled = terrain_height * 256.0;
+                                                        log::info!("Debug terrain query - Position: ({}, {}), Terrain height: {}", 
+                                                            self.debug_tree_pos.x, self.debug_tree_pos.z, terrain_height_scaled);
+                                                    }
+                                                    Err(e) => {
+                                                        log::error!("Failed to query terrain height: {}", e);
                                                     }
                                                 }
                                             }
+                                        }
+                                    }
 
-                                            ui.separator();
-
-                                            let (tree_changed, regenerate_pressed) =
-                                                Self::edit_tree_with_variance(
-                                                    &mut self.debug_tree_desc,
-                                                    &mut self.tree_variation_config,
-                                                    ui,
-                                                );
-                                            tree_desc_changed |= tree_changed;
-
-                                            if regenerate_pressed {
-                                                self.regenerate_trees_requested = true;
-                                            }
-                                        });
+                                    ui.separator();
 
-                                        ui.collapsing("Temporal Settings", |ui| {
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.temporal_position_phi,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Position Phi"),
-                                            );
+                                    let (tree_changed, regenerate_pressed) =
+                                        Self::edit_tree_with_variance(
+                                            &mut self.debug_tree_desc,
+                                            &mut self.tree_variation_config,
+                                            ui,
+                                        );
+                                    tree_desc_changed |= tree_changed;
+
+                                    if regenerate_pressed {
+                                        self.regenerate_trees_requested = true;
+                                    }
+
+                                    let mut cancel_pressed = false;
+                                    if let Some(forest_generation) =
+                                        &self.forest_generation
+                                    {
+                                        ui.horizontal(|ui| {
                                             ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.temporal_alpha,
-                                                    0.0..=1.0,
+                                                egui::ProgressBar::new(
+                                                    forest_generation.progress(),
                                                 )
-                                                .text("Alpha"),
+                                                .desired_width(200.0)
+                                                .text(format!(
+                                                    "{}/{} trees",
+                                                    forest_generation.planted_count(),
+                                                    forest_generation.tree_count()
+                                                )),
                                             );
+                                            cancel_pressed = ui.button("Cancel").clicked();
                                         });
-
-                                        ui.collapsing("God Ray Settings", |ui| {
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.god_ray_max_depth,
-                                                    0.1..=10.0,
-                                                )
-                                                .text("Max Depth"),
+                                    }
+                                    if cancel_pressed {
+                                        if let Some(forest_generation) =
+                                            self.forest_generation.take()
+                                        {
+                                            log::info!(
+                                                "Cancelled the procedural forest after {} of {} trees",
+                                                forest_generation.planted_count(),
+                                                forest_generation.tree_count()
                                             );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.god_ray_max_checks,
-                                                    1..=64,
-                                                )
-                                                .text("Max Checks"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.god_ray_weight,
-                                                    0.0..=2.0,
-                                                )
-                                                .text("Weight"),
-                                            );
-                                            ui.horizontal(|ui| {
-                                                ui.label("Color:");
-                                                ui.color_edit_button_srgba(&mut self.god_ray_color);
-                                            });
-                                        });
+                                        }
+                                    }
+                                });
 
-                                        ui.collapsing("Spatial Settings", |ui| {
-                                            ui.add(
-                                                egui::Slider::new(&mut self.phi_c, 0.0..=1.0)
-                                                    .text("Phi C"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.phi_n, 0.0..=1.0)
-                                                    .text("Phi N"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.phi_p, 0.0..=1.0)
-                                                    .text("Phi P"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.min_phi_z, 0.0..=1.0)
-                                                    .text("Min Phi Z"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(&mut self.max_phi_z, 0.0..=1.0)
-                                                    .text("Max Phi Z"),
-                                            );
-                                            ui.add(
-                                                egui::Slider::new(
-                                                    &mut self.phi_z_stable_sample_count,
-                                                    0.0..=1.0,
-                                                )
-                                                .text("Phi Z Stable Sample Count"),
-                                            );
-                                            ui.add(egui::Checkbox::new(
-                                                &mut self.is_changing_lum_phi,
-                                                "Changing Luminance Phi",
-                                            ));
-                                            ui.add(egui::Checkbox::new(
-                                                &mut self.is_spatial_denoising_enabled,
-                                                "Enable Spatial Denoising",
-                                            ));
-                                            ui.horizontal(|ui| {
-                                                ui.label("A-Trous Iterations:");
-                                                let mut iteration_value = self.a_trous_iteration_count as i32;
-                                                if ui.add(egui::Slider::new(&mut iteration_value, 1..=5).step_by(2.0)).changed() {
-                                                    // Ensure only odd values (1, 3, 5)
-                                                    if iteration_value % 2 == 0 {
-                                                        iteration_value += 1;
-                                                    }
-                                                    self.a_trous_iteration_count = iteration_value as u32;
-                                                }
-                                            });
-                                        });
+                                ui.collapsing("World Gen Preview", |ui| {
+                                    ui.checkbox(
+                                        &mut self.is_world_gen_preview_visible,
+                                        "Show Preview",
+                                    )
+                                    .on_hover_text(
+                                        "Renders the chunk under the camera with these parameters, the world is left as is",
+                                    );
+                                    let mut changed =
+                                        self.terrain_gen_desc.edit_by_gui(ui);
+                                    changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.tree_placer_desc.threshold,
+                                                0.0..=1.0,
+                                            )
+                                            .text("Tree Threshold"),
+                                        )
+                                        .changed();
+                                    changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.tree_grid_size,
+                                                20.0..=400.0,
+                                            )
+                                            .text("Tree Spacing"),
+                                        )
+                                        .changed();
+                                    self.is_world_gen_preview_dirty |= changed;
+                                    if ui.button("Apply To World").clicked() {
+                                        self.apply_world_gen_requested = true;
+                                    }
+                                });
 
-                                        ui.collapsing("Anti-Aliasing", |ui| {
-                                            ui.add(egui::Checkbox::new(
-                                                &mut self.is_taa_enabled,
-                                                "Enable Temporal Anti-Aliasing",
-                                            ));
-                                        });
+                                ui.collapsing("Props", |ui| {
+                                    self.prop_placer.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Grass Settings", |ui| {
-                                            ui.horizontal(|ui| {
-                                                ui.label("Bottom Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.grass_bottom_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Tip Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.grass_tip_color,
-                                                );
-                                            });
-                                        });
+                                ui.collapsing("Temporal Settings", |ui| {
+                                    self.tracer_settings.denoiser.temporal.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Lavender Settings", |ui| {
-                                            ui.horizontal(|ui| {
-                                                ui.label("Bottom Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.lavender_bottom_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Tip Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.lavender_tip_color,
-                                                );
-                                            });
-                                        });
+                                ui.collapsing("God Ray Settings", |ui| {
+                                    self.tracer_settings.god_ray.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Leaves Settings", |ui| {
-                                            let mut leaves_changed = false;
-                                            leaves_changed |= ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.leaves_inner_density,
-                                                        0.0..=1.0,
-                                                    )
-                                                    .text("Inner Density"),
-                                                )
-                                                .changed();
-                                            leaves_changed |= ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.leaves_outer_density,
-                                                        0.0..=1.0,
-                                                    )
-                                                    .text("Outer Density"),
-                                                )
-                                                .changed();
-                                            leaves_changed |= ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.leaves_inner_radius,
-                                                        1.0..=64.0,
-                                                    )
-                                                    .text("Inner Radius"),
-                                                )
-                                                .changed();
-                                            leaves_changed |= ui
-                                                .add(
-                                                    egui::Slider::new(
-                                                        &mut self.leaves_outer_radius,
-                                                        1.0..=64.0,
-                                                    )
-                                                    .text("Outer Radius"),
-                                                )
-                                                .changed();
+                                ui.collapsing("Fog Settings", |ui| {
+                                    self.tracer_settings.fog.edit_by_gui(ui);
+                                });
 
-                                            if leaves_changed {
-                                                // ensure inner_radius is always <= outer_radius
-                                                if self.leaves_inner_radius > self.leaves_outer_radius {
-                                                    self.leaves_outer_radius = self.leaves_inner_radius;
-                                                }
+                                ui.collapsing("Water Settings", |ui| {
+                                    self.tracer_settings.water.edit_by_gui(ui);
+                                });
 
-                                                if let Err(e) = self.tracer.regenerate_leaves(
-                                                    self.leaves_inner_density,
-                                                    self.leaves_outer_density,
-                                                    self.leaves_inner_radius,
-                                                    self.leaves_outer_radius,
-                                                ) {
-                                                    log::error!(
-                                                        "Failed to regenerate leaves: {}",
-                                                        e
-                                                    );
-                                                }
-                                            }
+                                ui.collapsing("Rain On Lens", |ui| {
+                                    self.tracer_settings.rain_lens.edit_by_gui(ui);
+                                });
 
-                                            ui.separator();
-                                            ui.horizontal(|ui| {
-                                                ui.label("Bottom Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.leaves_bottom_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Tip Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.leaves_tip_color,
-                                                );
-                                            });
-                                        });
+                                ui.collapsing("Advanced Quality", |ui| {
+                                    let mut pass_scales = self.tracer.pass_scales();
+                                    if pass_scales.edit_by_gui(ui) {
+                                        self.tracer.set_pass_scales(pass_scales);
+                                        // recreates the textures at the new resolutions
+                                        self.is_resize_pending = true;
+                                    }
+                                    ui.separator();
+                                    self.tracer_settings.dynamic_resolution.edit_by_gui(ui);
+                                    ui.label(format!(
+                                        "Render Scale: {:.2}",
+                                        self.tracer.scaling_factor()
+                                    ));
+                                });
+
+                                ui.collapsing("Spatial Settings", |ui| {
+                                    self.tracer_settings.denoiser.spatial.edit_by_gui(ui);
+                                });
 
-                                        ui.collapsing("Voxel Colors", |ui| {
-                                            ui.horizontal(|ui| {
-                                                ui.label("Sand Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.voxel_sand_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Dirt Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.voxel_dirt_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Rock Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.voxel_rock_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Leaf Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.voxel_leaf_color,
-                                                );
-                                            });
-                                            ui.horizontal(|ui| {
-                                                ui.label("Trunk Color:");
-                                                ui.color_edit_button_srgba(
-                                                    &mut self.voxel_trunk_color,
-                                                );
-                                            });
+                                ui.collapsing("Anti-Aliasing", |ui| {
+                                    ui.add(egui::Checkbox::new(
+                                        &mut self.tracer_settings.is_taa_enabled,
+                                        "Enable Temporal Anti-Aliasing",
+                                    ));
+                                });
+
+                                ui.collapsing("Shadows", |ui| {
+                                    ui.add(egui::Checkbox::new(
+                                        &mut self.tracer_settings.is_shadow_caching_enabled,
+                                        "Cache Static Shadows",
+                                    ))
+                                    .on_hover_text(
+                                        "Reuses the shadow map while the sun and the scene stand still, leaf shadows stop swaying meanwhile",
+                                    );
+                                    self.tracer_settings.shadow_bias.edit_by_gui(ui);
+                                });
+
+                                for flora_type in FloraType::all() {
+                                    let idx = flora_type.index();
+                                    let title =
+                                        format!("{} Settings", flora_type.desc().name);
+                                    ui.collapsing(title, |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.label("Bottom Color:");
+                                            ui.color_edit_button_srgba(
+                                                &mut self.flora_colors[idx].0,
+                                            );
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Tip Color:");
+                                            ui.color_edit_button_srgba(
+                                                &mut self.flora_colors[idx].1,
+                                            );
                                         });
+                                        if flora_type != FloraType::GRASS {
+                                            return;
+                                        }
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut self.grass_cut_radius,
+                                                0.01..=0.3,
+                                            )
+                                            .text("Cut Radius (X)"),
+                                        );
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut self.grass_regrowth_hours,
+                                                0.5..=48.0,
+                                            )
+                                            .text("Regrowth (in-game hours)"),
+                                        );
+                                    });
+                                }
+
+                                ui.collapsing("Leaves Settings", |ui| {
+                                    let mut leaves_changed = false;
+                                    leaves_changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.leaves_inner_density,
+                                                0.0..=1.0,
+                                            )
+                                            .text("Inner Density"),
+                                        )
+                                        .changed();
+                                    leaves_changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.leaves_outer_density,
+                                                0.0..=1.0,
+                                            )
+                                            .text("Outer Density"),
+                                        )
+                                        .changed();
+                                    leaves_changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.leaves_inner_radius,
+                                                1.0..=64.0,
+                                            )
+                                            .text("Inner Radius"),
+                                        )
+                                        .changed();
+                                    leaves_changed |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.leaves_outer_radius,
+                                                1.0..=64.0,
+                                            )
+                                            .text("Outer Radius"),
+                                        )
+                                        .changed();
+
+                                    if leaves_changed {
+                                        // ensure inner_radius is always <= outer_radius
+                                        if self.leaves_inner_radius > self.leaves_outer_radius {
+                                            self.leaves_outer_radius = self.leaves_inner_radius;
+                                        }
+
+                                        if let Err(e) = self.tracer.regenerate_leaves(
+                                            self.leaves_inner_density,
+                                            self.leaves_outer_density,
+                                            self.leaves_inner_radius,
+                                            self.leaves_outer_radius,
+                                        ) {
+                                            log::error!(
+                                                "Failed to regenerate leaves: {}",
+                                                e
+                                            );
+                                        }
+                                    }
 
+                                    ui.separator();
+                                    ui.horizontal(|ui| {
+                                        ui.label("Bottom Color:");
+                                        ui.color_edit_button_srgba(
+                                            &mut self.leaves_bottom_color,
+                                        );
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("Tip Color:");
+                                        ui.color_edit_button_srgba(
+                                            &mut self.leaves_tip_color,
+                                        );
                                     });
+
+                                    ui.separator();
+                                    self.seasonal_leaves.edit_by_gui(ui);
                                 });
-                        }
-                        self.config_panel_visible = config_panel_open;
-
-                        // FPS counter in bottom right
-                        egui::Area::new("fps_counter".into())
-                            .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10.0, -10.0))
-                            .show(ctx, |ui| {
-                                let fps_frame = egui::containers::Frame {
-                                    fill: Color32::from_rgba_premultiplied(0, 0, 0, 180),
-                                    inner_margin: egui::Margin::same(6),
-                                    corner_radius: egui::CornerRadius::same(4),
-                                    ..Default::default()
-                                };
-
-                                fps_frame.show(ui, |ui| {
-                                    ui.allocate_ui_with_layout(
-                                        egui::Vec2::new(80.0, 20.0),
-                                        egui::Layout::left_to_right(egui::Align::Center),
-                                        |ui| {
-                                            ui.label(
-                                                RichText::new(format!(
-                                                    "{:.1}",
-                                                    self.time_info.display_fps()
-                                                ))
-                                                .color(Color32::LIGHT_GRAY),
-                                            );
-                                        },
-                                    );
+
+                                ui.collapsing("Voxel Colors", |ui| {
+                                    self.tracer_settings.voxel_colors.edit_by_gui(ui);
                                 });
-                            });
-                    });
 
-                if tree_desc_changed {
-                    self.add_tree(
-                        self.debug_tree_desc.clone(),
-                        Vec2::new(self.debug_tree_pos.x, self.debug_tree_pos.z),
-                        true, // clean up before adding a new tree
-                        false,
-                    )
-                    .unwrap();
+                            });
+                        });
                 }
-
-                if self.regenerate_trees_requested {
-                    self.regenerate_trees_requested = false;
-                    match self.generate_procedural_trees() {
-                        Ok(_) => {
-                            log::info!("Procedural trees regenerated successfully");
+                self.config_panel_visible = config_panel_open;
+
+                if self.tracer_settings.player_collider.show_rings {
+                    match self.tracer.read_player_collision_result() {
+                        Ok(result) => {
+                            let samples = self
+                                .tracer
+                                .player_collider_ring_samples(&result.ring_distances);
+                            let ring_radius = self.tracer_settings.player_collider.ring_radius;
+                            for (sample, distance) in
+                                samples.iter().zip(result.ring_distances.iter())
+                            {
+                                self.debug_draw.point(
+                                    *sample,
+                                    3.0,
+                                    debug_heat_color(*distance / ring_radius),
+                                );
+                            }
+                            // skip the forward ray, the remaining ones form the ring
+                            if samples.len() > 2 {
+                                self.debug_draw
+                                    .line_loop(&samples[1..], Color32::from_gray(200));
+                            }
                         }
                         Err(e) => {
-                            log::error!("Failed to regenerate procedural trees: {}", e);
+                            log::error!("Failed to read player collision result: {}", e);
                         }
                     }
                 }
+                self.debug_draw.paint(ctx, self.tracer.view_proj_mat());
+
+                // FPS counter in bottom right
+                egui::Area::new("fps_counter".into())
+                    .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10.0, -10.0))
+                    .show(ctx, |ui| {
+                        let fps_frame = egui::containers::Frame {
+                            fill: Color32::from_rgba_premultiplied(0, 0, 0, 180),
+                            inner_margin: egui::Margin::same(6),
+                            corner_radius: egui::CornerRadius::same(4),
+                            ..Default::default()
+                        };
+
+                        fps_frame.show(ui, |ui| {
+                            ui.allocate_ui_with_layout(
+                                egui::Vec2::new(80.0, 20.0),
+                                egui::Layout::top_down(egui::Align::Min),
+                                |ui| {
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "{:.1}",
+                                            self.time_info.display_fps()
+                                        ))
+                                        .color(Color32::LIGHT_GRAY),
+                                    );
+                                    let (playing, total) =
+                                        self.spatial_sound_manager.voice_counts();
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "voices {}/{}",
+                                            playing, total
+                                        ))
+                                        .color(Color32::LIGHT_GRAY),
+                                    );
+                                    let shadow_stats =
+                                        self.tracer.shadow_cull_stats();
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "shadow trees {}/{}",
+                                            shadow_stats.drawn,
+                                            shadow_stats.total()
+                                        ))
+                                        .color(Color32::LIGHT_GRAY),
+                                    )
+                                    .on_hover_text(format!(
+                                        "{} outside the shadow camera, {} casting outside the view",
+                                        shadow_stats.light_culled,
+                                        shadow_stats.receiver_culled
+                                    ));
+                                },
+                            );
+                        });
+                    });
+            });
+
+        if is_photo_mode_toggled {
+            self.toggle_photo_mode();
+        }
 
-                // update sun position if auto day/night cycle is enabled
-                if self.auto_daynight_cycle {
-                    // update time of day based on delta time and day cycle speed
-                    // day_cycle_minutes is the real-world minutes for a full day cycle
-                    // convert to time progression per second: 1.0 / (day_cycle_minutes * 60.0)
-                    let time_speed = 1.0 / (self.day_cycle_minutes * 60.0);
-                    self.time_of_day += frame_delta_time * time_speed;
+        for action in chunk_debug_actions {
+            if let Err(e) = self.apply_chunk_debug_action(action) {
+                log::error!("Failed to apply {:?}: {}", action, e);
+            }
+        }
 
-                    // keep time_of_day in 0.0 to 1.0 range (wrap around)
-                    self.time_of_day %= 1.0;
+        if tree_desc_changed {
+            if let Err(e) = self.add_tree(
+                self.debug_tree_desc.clone(),
+                Vec2::new(self.debug_tree_pos.x, self.debug_tree_pos.z),
+                true, // clean up before adding a new tree
+                false,
+            ) {
+                log::warn!("{:#}", e);
+            }
+        }
+    }
 
-                    self.calculate_sun_position(self.time_of_day, self.latitude, self.season);
-                }
+    /// `None` when the swapchain is out of date, the frame is skipped and the swapchain resized
+    /// before the next one.
+    fn acquire_frame(&mut self, delta_time: f32) -> Option<FrameContext> {
+        // the slot's last frame is done, see `finish_previous_frame`
+        let frame_slot = self.frames_in_flight.current();
 
-                let device = self.vulkan_ctx.device();
+        let image_idx = match self
+            .swapchain
+            .acquire_next(&frame_slot.image_available_semaphore)
+        {
+            Ok((image_index, _)) => image_index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.is_resize_pending = true;
+                return None;
+            }
+            Err(error) => panic!("Error while acquiring next image. Cause: {}", error),
+        };
 
-                let image_idx = match self.swapchain.acquire_next(&self.image_available_semaphore) {
-                    Ok((image_index, _)) => image_index,
-                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                        self.is_resize_pending = true;
-                        return;
-                    }
-                    Err(error) => panic!("Error while acquiring next image. Cause: {}", error),
-                };
-
-                unsafe {
-                    device
-                        .as_raw()
-                        .reset_fences(&[self.fence.as_raw()])
-                        .expect("Failed to reset fences")
-                };
-
-                let cmdbuf = &self.cmdbuf;
-                cmdbuf.begin(false);
-
-                self.tracer
-                    .update_buffers(
-                        &self.time_info,
-                        self.debug_float,
-                        self.debug_bool,
-                        self.debug_uint,
-                        get_sun_dir(
-                            self.sun_altitude.asin().to_degrees(),
-                            self.sun_azimuth * 360.0,
-                        ),
-                        self.sun_size,
-                        Vec3::new(
-                            self.sun_color.r() as f32 / 255.0,
-                            self.sun_color.g() as f32 / 255.0,
-                            self.sun_color.b() as f32 / 255.0,
-                        ),
-                        self.sun_luminance,
-                        self.sun_altitude,
-                        self.sun_azimuth,
-                        Vec3::new(
-                            self.ambient_light.r() as f32 / 255.0,
-                            self.ambient_light.g() as f32 / 255.0,
-                            self.ambient_light.b() as f32 / 255.0,
-                        ),
-                        self.temporal_position_phi,
-                        self.temporal_alpha,
-                        self.phi_c,
-                        self.phi_n,
-                        self.phi_p,
-                        self.min_phi_z,
-                        self.max_phi_z,
-                        self.phi_z_stable_sample_count,
-                        self.is_changing_lum_phi,
-                        self.is_spatial_denoising_enabled,
-                        self.a_trous_iteration_count,
-                        self.is_taa_enabled,
-                        self.god_ray_max_depth,
-                        self.god_ray_max_checks,
-                        self.god_ray_weight,
-                        Vec3::new(
-                            self.god_ray_color.r() as f32 / 255.0,
-                            self.god_ray_color.g() as f32 / 255.0,
-                            self.god_ray_color.b() as f32 / 255.0,
-                        ),
-                        self.starlight_iterations,
-                        self.starlight_formuparam,
-                        self.starlight_volsteps,
-                        self.starlight_stepsize,
-                        self.starlight_zoom,
-                        self.starlight_tile,
-                        self.starlight_speed,
-                        self.starlight_brightness,
-                        self.starlight_darkmatter,
-                        self.starlight_distfading,
-                        self.starlight_saturation,
-                        Vec3::new(
-                            self.voxel_sand_color.r() as f32 / 255.0,
-                            self.voxel_sand_color.g() as f32 / 255.0,
-                            self.voxel_sand_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.voxel_dirt_color.r() as f32 / 255.0,
-                            self.voxel_dirt_color.g() as f32 / 255.0,
-                            self.voxel_dirt_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.voxel_rock_color.r() as f32 / 255.0,
-                            self.voxel_rock_color.g() as f32 / 255.0,
-                            self.voxel_rock_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.voxel_leaf_color.r() as f32 / 255.0,
-                            self.voxel_leaf_color.g() as f32 / 255.0,
-                            self.voxel_leaf_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.voxel_trunk_color.r() as f32 / 255.0,
-                            self.voxel_trunk_color.g() as f32 / 255.0,
-                            self.voxel_trunk_color.b() as f32 / 255.0,
-                        ),
-                    )
-                    .unwrap();
-
-                self.tracer
-                    .record_trace(
-                        cmdbuf,
-                        self.surface_builder.get_resources(),
-                        self.lod_distance,
-                        self.time_info.time_since_start(),
-                        Vec3::new(
-                            self.grass_bottom_color.r() as f32 / 255.0,
-                            self.grass_bottom_color.g() as f32 / 255.0,
-                            self.grass_bottom_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.grass_tip_color.r() as f32 / 255.0,
-                            self.grass_tip_color.g() as f32 / 255.0,
-                            self.grass_tip_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.lavender_bottom_color.r() as f32 / 255.0,
-                            self.lavender_bottom_color.g() as f32 / 255.0,
-                            self.lavender_bottom_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.lavender_tip_color.r() as f32 / 255.0,
-                            self.lavender_tip_color.g() as f32 / 255.0,
-                            self.lavender_tip_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.leaves_bottom_color.r() as f32 / 255.0,
-                            self.leaves_bottom_color.g() as f32 / 255.0,
-                            self.leaves_bottom_color.b() as f32 / 255.0,
-                        ),
-                        Vec3::new(
-                            self.leaves_tip_color.r() as f32 / 255.0,
-                            self.leaves_tip_color.g() as f32 / 255.0,
-                            self.leaves_tip_color.b() as f32 / 255.0,
-                        ),
-                    )
-                    .unwrap();
+        self.frames_in_flight.reset_current();
+        Some(FrameContext {
+            delta_time,
+            image_idx,
+        })
+    }
 
-                self.swapchain.record_blit(
-                    self.tracer.get_screen_output_tex().get_image(),
-                    cmdbuf,
-                    image_idx,
-                );
+    fn record_frame(&mut self, frame: &FrameContext) {
+        let device = self.vulkan_ctx.device();
+        let cmdbuf = &self.frames_in_flight.current().cmdbuf;
+        cmdbuf.begin(false);
+        // the frames in flight share the tracer's textures, the previous one goes first
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::MEMORY_WRITE,
+                vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            )],
+        )
+        .record_insert(device, cmdbuf);
 
-                let render_area = self.window_state.window_extent();
-
-                self.swapchain
-                    .record_begin_render_pass_cmdbuf(cmdbuf, image_idx, render_area);
-
-                self.egui_renderer
-                    .record_command_buffer(device, cmdbuf, render_area);
-
-                unsafe {
-                    device.cmd_end_render_pass(cmdbuf.as_raw());
-                };
-
-                cmdbuf.end();
-
-                let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-                let wait_semaphores = [self.image_available_semaphore.as_raw()];
-                let signal_semaphores = [self.render_finished_semaphore.as_raw()];
-                let command_buffers = [self.cmdbuf.as_raw()];
-                let submit_info = [vk::SubmitInfo::default()
-                    .wait_semaphores(&wait_semaphores)
-                    .wait_dst_stage_mask(&wait_stages)
-                    .command_buffers(&command_buffers)
-                    .signal_semaphores(&signal_semaphores)];
-
-                unsafe {
-                    self.vulkan_ctx
-                        .device()
-                        .as_raw()
-                        .queue_submit(
-                            self.vulkan_ctx.get_general_queue().as_raw(),
-                            &submit_info,
-                            self.fence.as_raw(),
-                        )
-                        .expect("Failed to submit work to gpu.")
-                };
-
-                let present_result = self.swapchain.present(&signal_semaphores, image_idx);
-
-                match present_result {
-                    Ok(is_suboptimal) if is_suboptimal => {
-                        self.is_resize_pending = true;
-                    }
-                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                        self.is_resize_pending = true;
-                    }
-                    Err(error) => panic!("Failed to present queue. Cause: {}", error),
-                    _ => {}
-                }
+        let is_wear_changed = self.path_wear.take_dirty();
+        self.surface_builder
+            .update_path_wear(&self.path_wear, is_wear_changed)
+            .unwrap();
+        self.tracer
+            .update_path_wear(&self.path_wear, is_wear_changed)
+            .unwrap();
 
-                self.vulkan_ctx
-                    .wait_for_fences(&[self.fence.as_raw()])
-                    .unwrap();
+        self.tracer
+            .update_buffers(&self.time_info, &self.tracer_settings)
+            .unwrap();
 
-                self.tracer
-                    .update_camera(frame_delta_time, self.is_fly_mode);
+        let flora_colors: Vec<(Vec3, Vec3)> = self
+            .flora_colors
+            .iter()
+            .map(|(bottom, tip)| {
+                (
+                    Vec3::new(
+                        bottom.r() as f32 / 255.0,
+                        bottom.g() as f32 / 255.0,
+                        bottom.b() as f32 / 255.0,
+                    ),
+                    Vec3::new(
+                        tip.r() as f32 / 255.0,
+                        tip.g() as f32 / 255.0,
+                        tip.b() as f32 / 255.0,
+                    ),
+                )
+            })
+            .collect();
+        let (leaf_bottom_color, leaf_tip_color) = self.seasonal_leaves.colors(
+            self.world_clock.season,
+            (
+                Vec3::new(
+                    self.leaves_bottom_color.r() as f32 / 255.0,
+                    self.leaves_bottom_color.g() as f32 / 255.0,
+                    self.leaves_bottom_color.b() as f32 / 255.0,
+                ),
+                Vec3::new(
+                    self.leaves_tip_color.r() as f32 / 255.0,
+                    self.leaves_tip_color.g() as f32 / 255.0,
+                    self.leaves_tip_color.b() as f32 / 255.0,
+                ),
+            ),
+        );
+        self.tracer
+            .record_trace(
+                cmdbuf,
+                self.surface_builder.get_resources(),
+                &self.flora_lod_desc,
+                self.flora_blend_mode,
+                self.scene_time,
+                &flora_colors,
+                leaf_bottom_color,
+                leaf_tip_color,
+            )
+            .unwrap();
+
+        if self.is_world_gen_preview_visible {
+            self.world_gen_preview
+                .record_overlay(cmdbuf, self.tracer.get_screen_output_tex().get_image());
+        }
+
+        self.swapchain.record_blit(
+            self.tracer.get_screen_output_tex().get_image(),
+            cmdbuf,
+            frame.image_idx,
+        );
+
+        let render_area = self.window_state.window_extent();
+
+        self.swapchain
+            .record_begin_render_pass_cmdbuf(cmdbuf, frame.image_idx, render_area);
+
+        self.egui_renderer.record_command_buffer(
+            device,
+            cmdbuf,
+            render_area,
+            self.frames_in_flight.current_idx(),
+        );
+
+        unsafe {
+            device.cmd_end_render_pass(cmdbuf.as_raw());
+        };
+
+        cmdbuf.end();
+    }
+
+    fn submit_frame(&mut self, frame: &FrameContext) {
+        let frame_slot = self.frames_in_flight.current();
+        // the builders' compute queue work and the streamed texture uploads are read by this frame
+        let compute_timeline = self.vulkan_ctx.compute_timeline();
+        let (transfer_timeline, upload_value) = self.tracer.texture_upload_wait();
+        let wait_stages = [
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        ];
+        let render_finished_semaphore = self
+            .frames_in_flight
+            .render_finished_semaphore(frame.image_idx);
+        let wait_semaphores = [
+            frame_slot.image_available_semaphore.as_raw(),
+            compute_timeline.as_raw(),
+            transfer_timeline.as_raw(),
+        ];
+        // the frame timeline tells the instance pool when the slices drawn here are free
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        let signal_semaphores = [render_finished_semaphore.as_raw(), frame_timeline.as_raw()];
+        let wait_values = [0, compute_timeline.submitted_value(), upload_value];
+        let signal_values = [0, frame_timeline.advance()];
+        let mut timeline_submit_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let command_buffers = [frame_slot.cmdbuf.as_raw()];
+        let submit_info = [vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_submit_info)];
+
+        unsafe {
+            self.vulkan_ctx
+                .device()
+                .as_raw()
+                .queue_submit(
+                    self.vulkan_ctx.get_general_queue().as_raw(),
+                    &submit_info,
+                    frame_slot.fence.as_raw(),
+                )
+                .expect("Failed to submit work to gpu.")
+        };
+        self.frames_in_flight.advance();
+    }
+
+    fn present_frame(&mut self, frame: &FrameContext) {
+        let present_wait_semaphores = [self
+            .frames_in_flight
+            .render_finished_semaphore(frame.image_idx)
+            .as_raw()];
+        let present_result = self
+            .swapchain
+            .present(&present_wait_semaphores, frame.image_idx);
+
+        match present_result {
+            Ok(is_suboptimal) if is_suboptimal => {
+                self.is_resize_pending = true;
             }
-            _ => (),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.is_resize_pending = true;
+            }
+            Err(error) => panic!("Failed to present queue. Cause: {}", error),
+            _ => {}
+        }
+    }
+
+    fn end_frame(&mut self, frame: &FrameContext) {
+        self.audio_automation.apply(
+            &AutomationInputs {
+                time_of_day: self.world_clock.time_of_day,
+                wind_strength: self.weather.wind_strength,
+            },
+            &mut self.music_manager,
+        );
+        if let Err(e) = self
+            .music_manager
+            .update(self.tracer.camera_position(), frame.delta_time)
+        {
+            log::error!("Failed to update music: {}", e);
+        }
+        let camera_pos = self.tracer.camera_position();
+        if let Err(e) = self.wind_ambience.update(
+            self.weather.wind_strength,
+            self.wind_field.gust_at(camera_pos.xz()),
+            frame.delta_time,
+        ) {
+            log::error!("Failed to update the wind ambience: {}", e);
+        }
+        self.tree_audio_manager
+            .update_wind(&self.wind_field, self.weather.wind_strength);
+        if let Err(e) = self
+            .sound_occlusion
+            .update(&mut self.tracer, camera_pos, frame.delta_time)
+        {
+            log::error!("Failed to update the sound occlusion: {}", e);
+        }
+
+        if std::mem::take(&mut self.is_screenshot_requested) {
+            self.take_screenshot();
+        }
+
+        crate::util::mark_frame();
+    }
+
+    /// Saves the current frame to a timestamped PNG in the screenshot directory.
+    fn take_screenshot(&self) {
+        let path = PathBuf::from(full_path_from_relative(SCREENSHOT_DIR)).join(format!(
+            "screenshot_{}.png",
+            chrono::Local::now().format("%Y%m%d_%H%M%S_%3f")
+        ));
+        match self.tracer.capture_screenshot(&path) {
+            Ok(()) => log::info!("Saved a screenshot to {}", path.display()),
+            Err(e) => log::error!("Failed to save a screenshot: {}", e),
         }
     }
 
@@ -1998,12 +3595,71 @@ impl App {
         }
     }
 
+    /// Waits for the frame last recorded with the slot about to be reused and reads back what it
+    /// computed, the other frames in flight keep running.
+    ///
+    /// The tracer's per-frame buffers are only written for the reused slot after this, see
+    /// [`Tracer::select_frame_slot`].
+    fn finish_previous_frame(&mut self, frame_delta_time: f32) {
+        profile_scope!("finish_previous_frame");
+        let is_finished = self.frames_in_flight.finish_current();
+        self.tracer
+            .select_frame_slot(self.frames_in_flight.current_idx());
+        if !is_finished {
+            return;
+        }
+
+        self.update_diagnostics();
+        if self
+            .tracer
+            .update_dynamic_resolution(&self.tracer_settings.dynamic_resolution)
+        {
+            // amortized by the controller, it changes the scale at most every few dozen frames
+            self.is_resize_pending = true;
+        }
+
+        self.tracer
+            .run_pending_workgroup_autotune(
+                self.contree_builder.get_resources(),
+                self.scene_accel_builder.get_resources(),
+            )
+            .unwrap();
+
+        let is_ground_bare = self
+            .path_wear
+            .is_bare_at(self.tracer.camera_position().xz());
+        self.tracer.set_player_ground_bare(is_ground_bare);
+        // a playing camera path overrides the controls
+        let path_pose = self.camera_path_player.update(frame_delta_time);
+        match path_pose {
+            Some(pose) => self.tracer.update_camera_scripted(
+                frame_delta_time,
+                pose.position,
+                pose.yaw,
+                pose.pitch,
+            ),
+            None => self.tracer.update_camera(
+                frame_delta_time,
+                self.is_fly_mode,
+                &self.camera_feel_desc,
+                &self.character_controller_desc,
+            ),
+        }
+        let camera_pos = self.tracer.camera_position();
+        let is_walking = !self.is_fly_mode && path_pose.is_none();
+        let foot_pos = (is_walking && self.tracer.is_player_on_ground())
+            .then_some(Vec2::new(camera_pos.x, camera_pos.z));
+        self.path_wear.walk(foot_pos);
+    }
+
     fn on_resize(&mut self) {
         self.vulkan_ctx.device().wait_idle();
 
         let window_extent = self.window_state.window_extent();
 
         self.swapchain.on_resize(window_extent);
+        self.frames_in_flight
+            .on_swapchain_recreated(self.swapchain.image_count());
         self.tracer.on_resize(
             window_extent,
             self.contree_builder.get_resources(),
@@ -0,0 +1,51 @@
+use glam::Vec3;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+
+/// A procedural forest being planted a few trees per frame, so regenerating it doesn't freeze
+/// the app.
+///
+/// The trees are planted in placement order with one seeded rng, so the forest comes out the same
+/// however the work is spread over the frames.
+pub struct ForestGeneration {
+    /// Where the trunks of the trees still to plant meet the ground.
+    pending: VecDeque<Vec3>,
+    tree_count: usize,
+    rng: StdRng,
+}
+
+impl ForestGeneration {
+    pub fn new(tree_positions: Vec<Vec3>, rng: StdRng) -> Self {
+        Self {
+            tree_count: tree_positions.len(),
+            pending: tree_positions.into(),
+            rng,
+        }
+    }
+
+    /// The position of the next tree, along with the rng its settings are drawn from.
+    pub fn next_tree(&mut self) -> Option<(Vec3, &mut StdRng)> {
+        let tree_pos = self.pending.pop_front()?;
+        Some((tree_pos, &mut self.rng))
+    }
+
+    pub fn planted_count(&self) -> usize {
+        self.tree_count - self.pending.len()
+    }
+
+    pub fn tree_count(&self) -> usize {
+        self.tree_count
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// From 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.tree_count == 0 {
+            return 1.0;
+        }
+        self.planted_count() as f32 / self.tree_count as f32
+    }
+}
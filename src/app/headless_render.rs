@@ -0,0 +1,363 @@
+use super::app_config::AppConfig;
+use super::core::{
+    App, CAMERA_PATH_FILE, FREE_ATLAS_DIM, LEAVES_BOTTOM_COLOR, LEAVES_TIP_COLOR,
+    VOXEL_DIM_PER_CHUNK,
+};
+use crate::audio::SpatialSoundManager;
+use crate::builder::{
+    ContreeBuilder, PlainBuilder, SceneAccelBuilder, SurfaceBuilder, FLORA_TYPES,
+};
+use crate::gameplay::{self, SeasonalLeaves, Weather, WindField, WindFieldDesc, WorldClock};
+use crate::geom::UAabb3;
+use crate::tracer::{FloraBlendMode, FloraLodDesc, Tracer, TracerDesc, TracerSettings};
+use crate::util::{full_path_from_relative, ShaderCompiler, TimeInfo};
+use crate::vkn::{
+    is_validation_requested, CommandBuffer, Extent2D, VulkanContext, VulkanContextDesc,
+};
+use anyhow::{bail, Result};
+use egui::Color32;
+use glam::{UVec3, Vec3};
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+/// Where the camera is and what it looks at, in world units.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraKeyframe {
+    pub position: Vec3,
+    pub target: Vec3,
+}
+
+/// A scripted camera move over a rendered sequence.
+#[derive(Debug, Clone)]
+pub enum CameraPath {
+    /// Circles `center` once, `radius` away and `height` above it, looking at it. The last frame
+    /// stops one step short of the first, so the sequence loops.
+    Turntable {
+        center: Vec3,
+        radius: f32,
+        height: f32,
+    },
+    /// Moves linearly from keyframe to keyframe, they are spread evenly from the first frame to
+    /// the last.
+    Keyframes(Vec<CameraKeyframe>),
+    /// A path recorded in the app, stretched over the sequence.
+    Recorded(gameplay::CameraPath),
+}
+
+impl CameraPath {
+    /// The path last saved from the camera path panel of the app.
+    pub fn load_recorded() -> Result<Self> {
+        let path = PathBuf::from(full_path_from_relative(CAMERA_PATH_FILE));
+        let recorded = gameplay::CameraPath::load(&path)?;
+        if recorded.is_empty() {
+            bail!("{} has no keyframes", path.display());
+        }
+        Ok(Self::Recorded(recorded))
+    }
+
+    /// The camera of frame `frame_idx` out of `frame_count`.
+    pub fn keyframe(&self, frame_idx: u32, frame_count: u32) -> CameraKeyframe {
+        match self {
+            Self::Turntable {
+                center,
+                radius,
+                height,
+            } => {
+                let angle = TAU * frame_idx as f32 / frame_count.max(1) as f32;
+                CameraKeyframe {
+                    position: *center
+                        + Vec3::new(angle.cos() * radius, *height, angle.sin() * radius),
+                    target: *center,
+                }
+            }
+            Self::Keyframes(keyframes) => {
+                let Some(last) = keyframes.last() else {
+                    return CameraKeyframe {
+                        position: Vec3::ZERO,
+                        target: Vec3::NEG_Z,
+                    };
+                };
+                let t = frame_idx as f32 / frame_count.saturating_sub(1).max(1) as f32;
+                let segment = t * (keyframes.len() - 1) as f32;
+                let idx = segment.floor() as usize;
+                if idx + 1 >= keyframes.len() {
+                    return *last;
+                }
+                let (from, to) = (&keyframes[idx], &keyframes[idx + 1]);
+                let s = segment - idx as f32;
+                CameraKeyframe {
+                    position: from.position.lerp(to.position, s),
+                    target: from.target.lerp(to.target, s),
+                }
+            }
+            Self::Recorded(path) => {
+                let t = frame_idx as f32 / frame_count.saturating_sub(1).max(1) as f32;
+                let Some(pose) = path.sample(t * path.duration()) else {
+                    return CameraKeyframe {
+                        position: Vec3::ZERO,
+                        target: Vec3::NEG_Z,
+                    };
+                };
+                // matches `CameraVectors::update`
+                let front = Vec3::new(
+                    pose.yaw.sin() * pose.pitch.cos(),
+                    pose.pitch.sin(),
+                    -pose.yaw.cos() * pose.pitch.cos(),
+                );
+                CameraKeyframe {
+                    position: pose.position,
+                    target: pose.position + front,
+                }
+            }
+        }
+    }
+}
+
+/// A sequence rendered without a window, see [`HeadlessRenderer`].
+#[derive(Debug, Clone)]
+pub struct HeadlessRenderDesc {
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: u32,
+    /// Frames rendered at the first camera before the sequence and thrown away, so the temporal
+    /// passes have a history by the first written frame.
+    pub warmup_frame_count: u32,
+    /// The time between two frames in seconds, the time doesn't follow the wall clock, so the
+    /// same sequence renders the same frames on every run.
+    pub frame_delta_time: f32,
+    pub camera_path: CameraPath,
+    /// The frames are written as `frame_00000.png` and up into it.
+    pub output_dir: PathBuf,
+}
+
+impl Default for HeadlessRenderDesc {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            frame_count: 120,
+            warmup_frame_count: 16,
+            frame_delta_time: 1.0 / 30.0,
+            camera_path: CameraPath::Turntable {
+                center: Vec3::new(2.5, 0.3, 2.5),
+                radius: 1.5,
+                height: 0.6,
+            },
+            output_dir: PathBuf::from("renders"),
+        }
+    }
+}
+
+/// Builds the world from the app config and renders it with the tracer on a context without a
+/// surface, the frames are read back into image files instead of being presented.
+///
+/// Only the terrain and the flora are built, the forest and the GUI are left out.
+pub struct HeadlessRenderer {
+    vulkan_ctx: VulkanContext,
+    tracer: Tracer,
+    surface_builder: SurfaceBuilder,
+    #[allow(dead_code)]
+    plain_builder: PlainBuilder,
+    #[allow(dead_code)]
+    contree_builder: ContreeBuilder,
+    #[allow(dead_code)]
+    scene_accel_builder: SceneAccelBuilder,
+    cmdbuf: CommandBuffer,
+    time_info: TimeInfo,
+    world_clock: WorldClock,
+    seasonal_leaves: SeasonalLeaves,
+    wind_field: WindField,
+    tracer_settings: TracerSettings,
+    flora_lod_desc: FloraLodDesc,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("Can't render {}x{} frames", width, height);
+        }
+        let config = AppConfig::load_or_create();
+        let chunk_bound = UAabb3::new(UVec3::ZERO, config.chunk_dim);
+        let vulkan_ctx = VulkanContext::new_headless(VulkanContextDesc {
+            name: "Re: Flora".into(),
+            enable_validation: is_validation_requested(),
+        });
+
+        let mut shader_compiler = ShaderCompiler::new().unwrap();
+        shader_compiler.set_dispatch_group_limits(vulkan_ctx.max_compute_work_group_count());
+        config
+            .denoiser_precision
+            .define_shader_macros(&mut shader_compiler);
+
+        let allocator = App::create_allocator(&vulkan_ctx);
+
+        let mut plain_builder = PlainBuilder::new(
+            vulkan_ctx.clone(),
+            &shader_compiler,
+            allocator.clone(),
+            config.chunk_dim * VOXEL_DIM_PER_CHUNK,
+            FREE_ATLAS_DIM,
+        );
+        let mut surface_builder = SurfaceBuilder::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            plain_builder.get_resources(),
+            VOXEL_DIM_PER_CHUNK,
+            chunk_bound,
+        );
+        let mut contree_builder = ContreeBuilder::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            surface_builder.get_resources(),
+            VOXEL_DIM_PER_CHUNK,
+            config.node_pool_size_in_bytes,
+            config.leaf_pool_size_in_bytes,
+        );
+        let mut scene_accel_builder = SceneAccelBuilder::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            chunk_bound,
+        )?;
+        App::init(
+            config.chunk_dim,
+            &mut plain_builder,
+            &mut surface_builder,
+            &mut contree_builder,
+            &mut scene_accel_builder,
+        )?;
+
+        let mut tracer = Tracer::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            chunk_bound,
+            Extent2D::new(width, height),
+            contree_builder.get_resources(),
+            scene_accel_builder.get_resources(),
+            TracerDesc {
+                // the render resolution is the output resolution
+                scaling_factor: 1.0,
+                pass_scales: config.pass_scales,
+                denoiser_precision: config.denoiser_precision,
+                // tuning times the passes, which would make runs differ
+                autotune_workgroup_sizes: false,
+                // every frame is waited for before the next one is recorded
+                frames_in_flight: 1,
+            },
+            SpatialSoundManager::new(1024)?,
+        )?;
+        tracer.set_sea_level(plain_builder.terrain_gen_desc().sea_level);
+
+        let cmdbuf = CommandBuffer::new(vulkan_ctx.device(), vulkan_ctx.command_pool());
+
+        Ok(Self {
+            vulkan_ctx,
+            tracer,
+            surface_builder,
+            plain_builder,
+            contree_builder,
+            scene_accel_builder,
+            cmdbuf,
+            time_info: TimeInfo::default(),
+            world_clock: WorldClock::default(),
+            seasonal_leaves: SeasonalLeaves::default(),
+            wind_field: WindField::new(WindFieldDesc::default(), &chunk_bound),
+            tracer_settings: TracerSettings::default(),
+            flora_lod_desc: FloraLodDesc::default(),
+        })
+    }
+
+    /// Renders the sequence of `desc` and writes every frame after the warmup.
+    pub fn render(&mut self, desc: &HeadlessRenderDesc) -> Result<()> {
+        std::fs::create_dir_all(&desc.output_dir)?;
+        for frame_idx in 0..desc.warmup_frame_count {
+            log::debug!("Warming up, frame {}", frame_idx);
+            self.set_camera(desc.camera_path.keyframe(0, desc.frame_count));
+            self.render_frame(desc.frame_delta_time)?;
+        }
+        for frame_idx in 0..desc.frame_count {
+            self.set_camera(desc.camera_path.keyframe(frame_idx, desc.frame_count));
+            self.render_frame(desc.frame_delta_time)?;
+            let path = desc.output_dir.join(format!("frame_{:05}.png", frame_idx));
+            self.tracer.capture_screenshot(&path)?;
+            log::info!(
+                "Rendered frame {} of {} to {}",
+                frame_idx + 1,
+                desc.frame_count,
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn set_camera(&mut self, keyframe: CameraKeyframe) {
+        let front = (keyframe.target - keyframe.position).normalize_or(Vec3::NEG_Z);
+        // inverts `CameraVectors::update`
+        let yaw = front.x.atan2(-front.z);
+        let pitch = front.y.clamp(-1.0, 1.0).asin();
+        self.tracer.set_camera_pose(keyframe.position, yaw, pitch);
+    }
+
+    /// Records, submits and waits for one frame.
+    fn render_frame(&mut self, delta_time: f32) -> Result<()> {
+        self.time_info.step_fixed(delta_time);
+        self.world_clock.advance(delta_time);
+        self.tracer
+            .update_world_clock(&self.world_clock, &mut self.tracer_settings.sun);
+        self.tracer
+            .set_leaf_density(self.seasonal_leaves.density(self.world_clock.season));
+        self.wind_field.update(delta_time, &Weather::default());
+        self.tracer.update_wind_field(&self.wind_field)?;
+
+        self.cmdbuf.begin(false);
+        self.tracer
+            .update_buffers(&self.time_info, &self.tracer_settings)?;
+        let flora_colors = FLORA_TYPES
+            .iter()
+            .map(|desc| {
+                (
+                    Vec3::from_array(desc.bottom_color.map(|c| c as f32 / 255.0)),
+                    Vec3::from_array(desc.tip_color.map(|c| c as f32 / 255.0)),
+                )
+            })
+            .collect::<Vec<_>>();
+        let (leaf_bottom_color, leaf_tip_color) = self.seasonal_leaves.colors(
+            self.world_clock.season,
+            (
+                color_to_vec3(LEAVES_BOTTOM_COLOR),
+                color_to_vec3(LEAVES_TIP_COLOR),
+            ),
+        );
+        self.tracer.record_trace(
+            &self.cmdbuf,
+            self.surface_builder.get_resources(),
+            &self.flora_lod_desc,
+            FloraBlendMode::default(),
+            self.time_info.time_since_start(),
+            &flora_colors,
+            leaf_bottom_color,
+            leaf_tip_color,
+        )?;
+        self.cmdbuf.end();
+
+        // the frame timeline tells the instance pool when the slices drawn here are free
+        let compute_timeline = self.vulkan_ctx.compute_timeline();
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        let done_value = self.cmdbuf.submit_with_timelines(
+            &self.vulkan_ctx.get_general_queue(),
+            &[
+                (compute_timeline, compute_timeline.submitted_value()),
+                self.tracer.texture_upload_wait(),
+            ],
+            frame_timeline,
+        );
+        frame_timeline.wait(done_value)
+    }
+}
+
+fn color_to_vec3(color: Color32) -> Vec3 {
+    Vec3::new(color.r() as f32, color.g() as f32, color.b() as f32) / 255.0
+}
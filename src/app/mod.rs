@@ -1,4 +1,16 @@
+mod app_config;
 mod app_controller;
 mod core;
+mod forest_generation;
+mod headless_render;
+mod planting;
+mod probe_volume;
+mod save_slot;
+mod self_test;
+mod voxel_edit;
+mod world_snapshot;
 
+pub use self::core::App;
 pub use app_controller::AppController;
+pub use headless_render::{CameraKeyframe, CameraPath, HeadlessRenderDesc};
+pub use save_slot::WorldEdit;
@@ -0,0 +1,262 @@
+use crate::builder::FloraType;
+use crate::constants::VOXEL_DIM;
+use crate::geom::VoxelPos;
+use crate::tracer::{Tracer, VoxelMaterial};
+use crate::util::DebugDraw;
+use anyhow::Result;
+use egui::Color32;
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use rand::Rng;
+use std::f32::consts::TAU;
+
+/// How far the placement ray reaches, in world units.
+const RAYCAST_MAX_DISTANCE: f32 = 1.5;
+const RAYCAST_SAMPLES: usize = 96;
+
+/// Segments of the footprint circle of the ghost overlay.
+const FOOTPRINT_SEGMENTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlantSpecies {
+    Tree,
+    GrassPatch,
+    LavenderClump,
+}
+
+impl PlantSpecies {
+    pub const ALL: [PlantSpecies; 3] = [
+        PlantSpecies::Tree,
+        PlantSpecies::GrassPatch,
+        PlantSpecies::LavenderClump,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlantSpecies::Tree => "Tree",
+            PlantSpecies::GrassPatch => "Grass Patch",
+            PlantSpecies::LavenderClump => "Lavender Clump",
+        }
+    }
+
+    /// Radius of the area the species covers, in world units.
+    pub fn footprint_radius(self) -> f32 {
+        match self {
+            PlantSpecies::Tree => 0.1,
+            PlantSpecies::GrassPatch => 0.05,
+            PlantSpecies::LavenderClump => 0.025,
+        }
+    }
+
+    fn ghost_color(self) -> Color32 {
+        match self {
+            PlantSpecies::Tree => Color32::from_rgb(200, 160, 110),
+            PlantSpecies::GrassPatch => Color32::from_rgb(140, 220, 60),
+            PlantSpecies::LavenderClump => Color32::from_rgb(180, 140, 230),
+        }
+    }
+
+    /// Flora type and voxel grid spacing of the instance species.
+    fn flora_layout(self) -> Option<(FloraType, u32)> {
+        match self {
+            PlantSpecies::Tree => None,
+            PlantSpecies::GrassPatch => Some((FloraType::GRASS, 2)),
+            PlantSpecies::LavenderClump => Some((FloraType::LAVENDER, 3)),
+        }
+    }
+}
+
+/// What the player asked to plant, resolved by the app.
+pub enum PlantRequest {
+    Tree {
+        position: Vec3,
+        rotation: f32,
+    },
+    Flora {
+        flora_type: FloraType,
+        positions: Vec<VoxelPos>,
+    },
+}
+
+/// Hand placement of single trees and flora patches at the point the camera looks at.
+pub struct PlantingTool {
+    pub is_active: bool,
+    pub species: PlantSpecies,
+    pub is_snapping: bool,
+    /// Snapping grid spacing in world units.
+    pub snap_size: f32,
+    pub is_random_rotation: bool,
+    hit_point: Option<Vec3>,
+}
+
+impl Default for PlantingTool {
+    fn default() -> Self {
+        Self {
+            is_active: false,
+            species: PlantSpecies::Tree,
+            is_snapping: false,
+            snap_size: 0.1,
+            is_random_rotation: true,
+            hit_point: None,
+        }
+    }
+}
+
+impl PlantingTool {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_active, "Placement Mode (P)");
+        ui.horizontal(|ui| {
+            for species in PlantSpecies::ALL {
+                ui.selectable_value(&mut self.species, species, species.name());
+            }
+        });
+        ui.checkbox(&mut self.is_snapping, "Snap To Grid");
+        if self.is_snapping {
+            ui.add(egui::Slider::new(&mut self.snap_size, 0.01..=0.5).text("Grid Size"));
+        }
+        ui.checkbox(&mut self.is_random_rotation, "Random Rotation");
+        ui.label("Left click to plant at the marked spot.");
+    }
+
+    /// Raycasts the terrain from the camera, call once per frame while active.
+    pub fn update(&mut self, tracer: &mut Tracer) -> Result<()> {
+        if !self.is_active {
+            self.hit_point = None;
+            return Ok(());
+        }
+        let origin = tracer.camera_position();
+        let direction = tracer.camera_vectors().front;
+        self.hit_point = raycast_terrain(tracer, origin, direction)?.map(|hit| self.snap(hit));
+        Ok(())
+    }
+
+    fn snap(&self, point: Vec3) -> Vec3 {
+        if !self.is_snapping || self.snap_size <= 0.0 {
+            return point;
+        }
+        let snapped = (point.xz() / self.snap_size).round() * self.snap_size;
+        Vec3::new(snapped.x, point.y, snapped.y)
+    }
+
+    /// Queues the footprint of the selected species at the hit point.
+    pub fn draw_ghost(&self, debug_draw: &mut DebugDraw) {
+        let Some(hit_point) = self.hit_point else {
+            return;
+        };
+        let radius = self.species.footprint_radius();
+        let color = self.species.ghost_color();
+        let circle: Vec<Vec3> = (0..FOOTPRINT_SEGMENTS)
+            .map(|i| {
+                let angle = TAU * i as f32 / FOOTPRINT_SEGMENTS as f32;
+                hit_point + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius
+            })
+            .collect();
+        debug_draw.line_loop(&circle, color);
+        debug_draw.point(hit_point, 4.0, color);
+        if self.species == PlantSpecies::Tree {
+            debug_draw.line(hit_point, hit_point + Vec3::Y * radius * 2.0, color);
+        }
+    }
+
+    /// Turns the current hit point into what has to be planted, `None` when nothing is aimed at.
+    pub fn plant_request(&self, tracer: &mut Tracer) -> Result<Option<PlantRequest>> {
+        let Some(hit_point) = self.hit_point else {
+            return Ok(None);
+        };
+        let mut rng = rand::rng();
+
+        let Some((flora_type, grid_spacing)) = self.species.flora_layout() else {
+            let rotation = if self.is_random_rotation {
+                rng.random_range(0.0..TAU)
+            } else {
+                0.0
+            };
+            return Ok(Some(PlantRequest::Tree {
+                position: hit_point,
+                rotation,
+            }));
+        };
+
+        let voxel_dim = VOXEL_DIM as f32;
+        let center = hit_point.xz() * voxel_dim;
+        let radius = self.species.footprint_radius() * voxel_dim;
+        let spacing = grid_spacing as f32;
+        let min = ((center - radius) / spacing).floor() * spacing;
+        let max = center + radius;
+
+        let mut cells = Vec::new();
+        let mut z = min.y;
+        while z <= max.y {
+            let mut x = min.x;
+            while x <= max.x {
+                let cell = Vec2::new(x, z);
+                // thin out towards the rim so patches blend into the terrain
+                let falloff = 1.0 - cell.distance(center) / radius;
+                if falloff > 0.0 && rng.random::<f32>() < falloff.sqrt() {
+                    cells.push(cell);
+                }
+                x += spacing;
+            }
+            z += spacing;
+        }
+
+        let query_positions: Vec<Vec2> = cells.iter().map(|cell| *cell / voxel_dim).collect();
+        let hits = tracer.query_terrain_batch(&query_positions)?;
+        let positions = cells
+            .iter()
+            .zip(hits)
+            .filter_map(|(cell, hit)| Some((cell, hit.ok()?)))
+            // flora doesn't grow on trees or on water
+            .filter(|(_, hit)| {
+                !matches!(
+                    hit.material,
+                    None | Some(VoxelMaterial::Leaf)
+                        | Some(VoxelMaterial::Trunk)
+                        | Some(VoxelMaterial::Water)
+                )
+            })
+            .map(|(cell, hit)| {
+                VoxelPos(Vec3::new(cell.x, (hit.height * voxel_dim).round(), cell.y).as_uvec3())
+            })
+            .collect();
+        Ok(Some(PlantRequest::Flora {
+            flora_type,
+            positions,
+        }))
+    }
+}
+
+/// Marches along the ray against the terrain heights and refines the first crossing linearly.
+pub(super) fn raycast_terrain(
+    tracer: &mut Tracer,
+    origin: Vec3,
+    direction: Vec3,
+) -> Result<Option<Vec3>> {
+    let samples: Vec<Vec3> = (0..=RAYCAST_SAMPLES)
+        .map(|i| origin + direction * (RAYCAST_MAX_DISTANCE * i as f32 / RAYCAST_SAMPLES as f32))
+        .collect();
+    let query_positions: Vec<Vec2> = samples.iter().map(|sample| sample.xz()).collect();
+    let heights = tracer.query_terrain_heights_batch(&query_positions)?;
+
+    let mut prev: Option<(Vec3, f32, f32)> = None;
+    for (sample, height) in samples.into_iter().zip(heights) {
+        // the ray can't cross terrain where there's none
+        let Ok(height) = height else {
+            prev = None;
+            continue;
+        };
+        let above = sample.y - height;
+        if above <= 0.0 {
+            let hit = match prev {
+                Some((prev_sample, prev_above, prev_height)) => {
+                    let t = prev_above / (prev_above - above);
+                    let hit = prev_sample.lerp(sample, t);
+                    Vec3::new(hit.x, prev_height + (height - prev_height) * t, hit.z)
+                }
+                None => Vec3::new(sample.x, height, sample.z),
+            };
+            return Ok(Some(hit));
+        }
+        prev = Some((sample, above, height));
+    }
+    Ok(None)
+}
@@ -0,0 +1,205 @@
+use crate::constants::SKY_VISIBILITY_TEXELS_PER_CHUNK;
+use crate::geom::Aabb3;
+use crate::tracer::Tracer;
+use crate::util::DebugDraw;
+use anyhow::Result;
+use egui::Color32;
+use glam::{UVec2, Vec2, Vec3, Vec3Swizzles};
+
+/// Probes of one volume shown at most, a finer spacing is coarsened to stay below it.
+const MAX_PROBES_PER_VOLUME: usize = 4096;
+
+const BOUNDS_COLOR: Color32 = Color32::from_rgb(120, 200, 255);
+
+/// A box filled with a regular grid of light probes, in world units.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeVolume {
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Distance between neighbouring probes.
+    pub spacing: f32,
+}
+
+impl Default for ProbeVolume {
+    fn default() -> Self {
+        Self {
+            min: Vec3::new(2.0, 0.3, 2.0),
+            max: Vec3::new(3.0, 0.8, 3.0),
+            spacing: 0.25,
+        }
+    }
+}
+
+impl ProbeVolume {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        for (label, corner) in [("Min", &mut self.min), ("Max", &mut self.max)] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(
+                    egui::DragValue::new(&mut corner.x)
+                        .speed(0.01)
+                        .prefix("x: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut corner.y)
+                        .speed(0.01)
+                        .prefix("y: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut corner.z)
+                        .speed(0.01)
+                        .prefix("z: "),
+                );
+            });
+        }
+        self.max = self.max.max(self.min);
+        ui.add(egui::Slider::new(&mut self.spacing, 0.05..=1.0).text("Probe Spacing"));
+    }
+
+    /// Probe counts along each axis, with a probe on both faces of the box.
+    fn grid_dim(&self) -> [usize; 3] {
+        let mut spacing = self.spacing.max(1e-3);
+        loop {
+            let dim = ((self.max - self.min) / spacing).floor().as_uvec3() + 1;
+            if dim.x as usize * dim.y as usize * dim.z as usize <= MAX_PROBES_PER_VOLUME {
+                return [dim.x as usize, dim.y as usize, dim.z as usize];
+            }
+            spacing *= 2.0;
+        }
+    }
+
+    pub fn probe_count(&self) -> usize {
+        self.grid_dim().iter().product()
+    }
+
+    pub fn probe_positions(&self) -> Vec<Vec3> {
+        let dim = self.grid_dim();
+        let step = (self.max - self.min)
+            / Vec3::new(
+                (dim[0].max(2) - 1) as f32,
+                (dim[1].max(2) - 1) as f32,
+                (dim[2].max(2) - 1) as f32,
+            );
+        let mut positions = Vec::with_capacity(dim[0] * dim[1] * dim[2]);
+        for z in 0..dim[2] {
+            for y in 0..dim[1] {
+                for x in 0..dim[0] {
+                    positions.push(self.min + Vec3::new(x as f32, y as f32, z as f32) * step);
+                }
+            }
+        }
+        positions
+    }
+
+    fn draw_bounds(&self, debug_draw: &mut DebugDraw) {
+        let [c0, c1, c2, c3, c4, c5, c6, c7] = Aabb3::new(self.min, self.max).get_corners();
+        debug_draw.line_loop(&[c0, c1, c3, c2], BOUNDS_COLOR);
+        debug_draw.line_loop(&[c4, c5, c7, c6], BOUNDS_COLOR);
+        for (bottom, top) in [(c0, c4), (c1, c5), (c2, c6), (c3, c7)] {
+            debug_draw.line(bottom, top, BOUNDS_COLOR);
+        }
+    }
+}
+
+/// Shows where the light probes sit and how much light reaches them, so the coverage around
+/// clearings and under the canopy can be checked.
+///
+/// The probes are shaded with the sky visibility of their column, which is the only irradiance
+/// the renderer stores so far.
+pub struct ProbeVolumeTool {
+    pub is_visible: bool,
+    pub volumes: Vec<ProbeVolume>,
+    /// In world units.
+    probe_radius: f32,
+    /// The extent and texels of the sky visibility map, read back on demand as it's not needed
+    /// on the CPU otherwise.
+    sky_visibility: Option<(UVec2, Vec<u8>)>,
+    is_irradiance_stale: bool,
+}
+
+impl Default for ProbeVolumeTool {
+    fn default() -> Self {
+        Self {
+            is_visible: false,
+            volumes: vec![ProbeVolume::default()],
+            probe_radius: 0.01,
+            sky_visibility: None,
+            is_irradiance_stale: true,
+        }
+    }
+}
+
+impl ProbeVolumeTool {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        if ui.checkbox(&mut self.is_visible, "Show Probes").changed() && self.is_visible {
+            self.is_irradiance_stale = true;
+        }
+        ui.add(egui::Slider::new(&mut self.probe_radius, 0.002..=0.05).text("Probe Radius"));
+        if ui.button("Refresh Irradiance").clicked() {
+            self.is_irradiance_stale = true;
+        }
+
+        let mut removed_idx = None;
+        for (idx, volume) in self.volumes.iter_mut().enumerate() {
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(format!("Volume {}, {} probes", idx, volume.probe_count()));
+                if ui.button("Remove").clicked() {
+                    removed_idx = Some(idx);
+                }
+            });
+            volume.edit_by_gui(ui);
+        }
+        if let Some(idx) = removed_idx {
+            self.volumes.remove(idx);
+        }
+        ui.separator();
+        if ui.button("Add Volume").clicked() {
+            self.volumes.push(ProbeVolume::default());
+        }
+    }
+
+    /// Reads the irradiance back when it's asked for, call once per frame.
+    pub fn update(&mut self, tracer: &Tracer) -> Result<()> {
+        if !self.is_visible || !self.is_irradiance_stale {
+            return Ok(());
+        }
+        self.is_irradiance_stale = false;
+        self.sky_visibility = Some(tracer.read_sky_visibility()?);
+        Ok(())
+    }
+
+    /// Queues the volume bounds and the probes shaded with their irradiance.
+    pub fn draw(&self, debug_draw: &mut DebugDraw) {
+        if !self.is_visible {
+            return;
+        }
+        for volume in &self.volumes {
+            volume.draw_bounds(debug_draw);
+            for probe_pos in volume.probe_positions() {
+                let irradiance = self.irradiance_at(probe_pos);
+                debug_draw.sphere(probe_pos, self.probe_radius, irradiance_color(irradiance));
+            }
+        }
+    }
+
+    /// From 0.0 for no light to 1.0 for the open sky, full until the map is read back.
+    fn irradiance_at(&self, pos: Vec3) -> f32 {
+        let Some((extent, texels)) = &self.sky_visibility else {
+            return 1.0;
+        };
+        let texel = (pos.xz() * SKY_VISIBILITY_TEXELS_PER_CHUNK as f32)
+            .floor()
+            .max(Vec2::ZERO)
+            .as_uvec2()
+            .min(*extent - UVec2::ONE);
+        texels[(texel.y * extent.x + texel.x) as usize] as f32 / 255.0
+    }
+}
+
+fn irradiance_color(irradiance: f32) -> Color32 {
+    let dark = Vec3::new(20.0, 20.0, 35.0);
+    let sky = Vec3::new(200.0, 225.0, 255.0);
+    let color = dark.lerp(sky, irradiance.clamp(0.0, 1.0));
+    Color32::from_rgb(color.x as u8, color.y as u8, color.z as u8)
+}
@@ -0,0 +1,416 @@
+use crate::builder::FloraType;
+use crate::geom::{ChunkIdx, VoxelPos};
+use crate::tracer::VoxelMaterial;
+use crate::util::full_path_from_relative;
+use anyhow::{anyhow, bail, Context, Result};
+use glam::Vec3;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Relative to the project root.
+const SAVE_DIR: &str = "saves/";
+
+pub const SAVE_SLOT_COUNT: usize = 3;
+
+/// Bumped whenever the line format changes, older saves are refused instead of misread.
+/// 2: flora saved under its registry key.
+/// 3: the moon_phase record.
+/// 4: the water voxel material.
+const SAVE_VERSION: u32 = 4;
+
+/// A change the player made on top of the seeded world, replayed in order on load.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldEdit {
+    /// Planted with the tree settings that are current at load time.
+    PlantTree {
+        position: Vec3,
+        seed: u64,
+        rotation: f32,
+    },
+    /// Removes the tree standing nearest to `position`, where it was planted.
+    RemoveTree { position: Vec3 },
+    PlantFlora {
+        flora_type: FloraType,
+        positions: Vec<VoxelPos>,
+    },
+    CutGrass {
+        center: Vec3,
+        radius: f32,
+        height: f32,
+    },
+    /// A sphere of voxels, carved out when `material` is `None`.
+    ModifyVoxels {
+        center: Vec3,
+        radius: f32,
+        material: Option<VoxelMaterial>,
+    },
+}
+
+/// Everything needed to get back to a moment of play, without any voxel data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSlot {
+    pub camera_position: Vec3,
+    pub camera_yaw: f32,
+    pub camera_pitch: f32,
+    pub time_of_day: f32,
+    pub season: f32,
+    pub moon_phase: f32,
+    /// Whether the procedural forest was grown, it is part of the base world when set.
+    pub has_forest: bool,
+    pub edits: Vec<WorldEdit>,
+    /// Contree checksums of the resident chunks at save time, to verify the replay against.
+    pub chunk_checksums: Vec<(ChunkIdx, u64)>,
+}
+
+fn slot_path(slot: usize) -> PathBuf {
+    PathBuf::from(full_path_from_relative(SAVE_DIR)).join(format!("slot_{}.txt", slot))
+}
+
+/// When the slot was last written, `None` for an empty slot.
+pub fn save_slot_time(slot: usize) -> Option<SystemTime> {
+    std::fs::metadata(slot_path(slot))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+fn parse_flora_type(name: &str) -> Result<FloraType> {
+    FloraType::from_key(name).ok_or_else(|| anyhow!("unknown flora type {}", name))
+}
+
+fn voxel_material_name(material: Option<VoxelMaterial>) -> &'static str {
+    match material {
+        None => "empty",
+        Some(VoxelMaterial::Sand) => "sand",
+        Some(VoxelMaterial::Dirt) => "dirt",
+        Some(VoxelMaterial::Rock) => "rock",
+        Some(VoxelMaterial::Leaf) => "leaf",
+        Some(VoxelMaterial::Trunk) => "trunk",
+        Some(VoxelMaterial::Water) => "water",
+    }
+}
+
+fn parse_voxel_material(name: &str) -> Result<Option<VoxelMaterial>> {
+    match name {
+        "empty" => Ok(None),
+        "sand" => Ok(Some(VoxelMaterial::Sand)),
+        "dirt" => Ok(Some(VoxelMaterial::Dirt)),
+        "rock" => Ok(Some(VoxelMaterial::Rock)),
+        "leaf" => Ok(Some(VoxelMaterial::Leaf)),
+        "trunk" => Ok(Some(VoxelMaterial::Trunk)),
+        "water" => Ok(Some(VoxelMaterial::Water)),
+        _ => Err(anyhow!("unknown voxel material {}", name)),
+    }
+}
+
+fn parse_vec3(parts: &[&str]) -> Result<Vec3> {
+    match parts {
+        [x, y, z] => Ok(Vec3::new(x.parse()?, y.parse()?, z.parse()?)),
+        _ => Err(anyhow!("expected 3 components, got {}", parts.len())),
+    }
+}
+
+impl SaveSlot {
+    pub fn save(&self, slot: usize) -> Result<()> {
+        let path = slot_path(slot);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, self.serialize())?;
+        Ok(())
+    }
+
+    pub fn load(slot: usize) -> Result<Self> {
+        let path = slot_path(slot);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Malformed save {}", path.display()))
+    }
+
+    /// One record per line, a keyword followed by whitespace separated values.
+    fn serialize(&self) -> String {
+        let p = self.camera_position;
+        let mut out = format!(
+            "version {}\ncamera {} {} {} {} {}\ntime_of_day {}\nseason {}\nmoon_phase {}\nforest {}\n",
+            SAVE_VERSION,
+            p.x,
+            p.y,
+            p.z,
+            self.camera_yaw,
+            self.camera_pitch,
+            self.time_of_day,
+            self.season,
+            self.moon_phase,
+            self.has_forest as u32,
+        );
+        for edit in &self.edits {
+            let line = match edit {
+                WorldEdit::PlantTree {
+                    position,
+                    seed,
+                    rotation,
+                } => format!(
+                    "tree {} {} {} {} {}",
+                    position.x, position.y, position.z, seed, rotation
+                ),
+                WorldEdit::RemoveTree { position } => {
+                    format!("remove_tree {} {} {}", position.x, position.y, position.z)
+                }
+                WorldEdit::PlantFlora {
+                    flora_type,
+                    positions,
+                } => {
+                    let mut line = format!("flora {}", flora_type.desc().key);
+                    for pos in positions {
+                        line.push_str(&format!(" {} {} {}", pos.0.x, pos.0.y, pos.0.z));
+                    }
+                    line
+                }
+                WorldEdit::CutGrass {
+                    center,
+                    radius,
+                    height,
+                } => format!(
+                    "cut {} {} {} {} {}",
+                    center.x, center.y, center.z, radius, height
+                ),
+                WorldEdit::ModifyVoxels {
+                    center,
+                    radius,
+                    material,
+                } => format!(
+                    "voxels {} {} {} {} {}",
+                    center.x,
+                    center.y,
+                    center.z,
+                    radius,
+                    voxel_material_name(*material)
+                ),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        for (chunk_idx, checksum) in &self.chunk_checksums {
+            out.push_str(&format!(
+                "checksum {} {} {} {:016x}\n",
+                chunk_idx.0.x, chunk_idx.0.y, chunk_idx.0.z, checksum
+            ));
+        }
+        out
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut save = SaveSlot {
+            camera_position: Vec3::ZERO,
+            camera_yaw: 0.0,
+            camera_pitch: 0.0,
+            time_of_day: 0.5,
+            season: 0.0,
+            // hand-edited saves without the record
+            moon_phase: 0.5,
+            has_forest: false,
+            edits: Vec::new(),
+            chunk_checksums: Vec::new(),
+        };
+        let mut version = None;
+        for (line_no, line) in content.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let Some((keyword, values)) = parts.split_first() else {
+                continue;
+            };
+            let parsed: Result<()> = (|| {
+                match *keyword {
+                    "version" => version = Some(values.first().unwrap_or(&"").parse::<u32>()?),
+                    "camera" => {
+                        if values.len() != 5 {
+                            bail!("expected position, yaw and pitch");
+                        }
+                        save.camera_position = parse_vec3(&values[..3])?;
+                        save.camera_yaw = values[3].parse()?;
+                        save.camera_pitch = values[4].parse()?;
+                    }
+                    "time_of_day" => save.time_of_day = values.first().unwrap_or(&"").parse()?,
+                    "season" => save.season = values.first().unwrap_or(&"").parse()?,
+                    "moon_phase" => save.moon_phase = values.first().unwrap_or(&"").parse()?,
+                    "forest" => save.has_forest = values.first() == Some(&"1"),
+                    "tree" => {
+                        if values.len() != 5 {
+                            bail!("expected position, seed and rotation");
+                        }
+                        save.edits.push(WorldEdit::PlantTree {
+                            position: parse_vec3(&values[..3])?,
+                            seed: values[3].parse()?,
+                            rotation: values[4].parse()?,
+                        });
+                    }
+                    "remove_tree" => {
+                        save.edits.push(WorldEdit::RemoveTree {
+                            position: parse_vec3(values)?,
+                        });
+                    }
+                    "flora" => {
+                        let (name, coords) = values
+                            .split_first()
+                            .ok_or_else(|| anyhow!("missing flora type"))?;
+                        if coords.len() % 3 != 0 {
+                            bail!("positions need 3 components each");
+                        }
+                        let positions = coords
+                            .chunks(3)
+                            .map(|c| Ok(VoxelPos::new(c[0].parse()?, c[1].parse()?, c[2].parse()?)))
+                            .collect::<Result<Vec<_>>>()?;
+                        save.edits.push(WorldEdit::PlantFlora {
+                            flora_type: parse_flora_type(name)?,
+                            positions,
+                        });
+                    }
+                    "cut" => {
+                        if values.len() != 5 {
+                            bail!("expected center, radius and height");
+                        }
+                        save.edits.push(WorldEdit::CutGrass {
+                            center: parse_vec3(&values[..3])?,
+                            radius: values[3].parse()?,
+                            height: values[4].parse()?,
+                        });
+                    }
+                    "voxels" => {
+                        if values.len() != 5 {
+                            bail!("expected center, radius and material");
+                        }
+                        save.edits.push(WorldEdit::ModifyVoxels {
+                            center: parse_vec3(&values[..3])?,
+                            radius: values[3].parse()?,
+                            material: parse_voxel_material(values[4])?,
+                        });
+                    }
+                    "checksum" => {
+                        if values.len() != 4 {
+                            bail!("expected chunk index and checksum");
+                        }
+                        let chunk_idx = ChunkIdx::new(
+                            values[0].parse()?,
+                            values[1].parse()?,
+                            values[2].parse()?,
+                        );
+                        save.chunk_checksums
+                            .push((chunk_idx, u64::from_str_radix(values[3], 16)?));
+                    }
+                    _ => bail!("unknown record {}", keyword),
+                }
+                Ok(())
+            })();
+            parsed.with_context(|| format!("line {}", line_no + 1))?;
+        }
+        if version != Some(SAVE_VERSION) {
+            bail!(
+                "unsupported version {:?}, expected {}",
+                version,
+                SAVE_VERSION
+            );
+        }
+        Ok(save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_edit_slot() -> SaveSlot {
+        SaveSlot {
+            camera_position: Vec3::new(12.5, 140.25, -3.0),
+            camera_yaw: 1.25,
+            camera_pitch: -0.375,
+            time_of_day: 0.8,
+            season: 0.3,
+            moon_phase: 0.125,
+            has_forest: true,
+            edits: vec![
+                WorldEdit::PlantTree {
+                    position: Vec3::new(100.0, 64.5, 200.0),
+                    seed: 0xdead_beef_cafe,
+                    rotation: 2.5,
+                },
+                WorldEdit::RemoveTree {
+                    position: Vec3::new(300.0, 70.0, 12.0),
+                },
+                WorldEdit::PlantFlora {
+                    flora_type: FloraType::LAVENDER,
+                    positions: vec![VoxelPos::new(1, 2, 3), VoxelPos::new(400, 70, 9)],
+                },
+                WorldEdit::PlantFlora {
+                    flora_type: FloraType::GRASS,
+                    positions: Vec::new(),
+                },
+                WorldEdit::CutGrass {
+                    center: Vec3::new(5.0, 6.0, 7.0),
+                    radius: 3.5,
+                    height: 0.25,
+                },
+                WorldEdit::ModifyVoxels {
+                    center: Vec3::new(50.0, 60.0, 70.0),
+                    radius: 4.0,
+                    material: Some(VoxelMaterial::Water),
+                },
+                WorldEdit::ModifyVoxels {
+                    center: Vec3::new(51.0, 61.0, 71.0),
+                    radius: 2.0,
+                    material: None,
+                },
+            ],
+            chunk_checksums: vec![
+                (ChunkIdx::new(0, 0, 0), 0),
+                (ChunkIdx::new(1, 0, 2), u64::MAX),
+            ],
+        }
+    }
+
+    #[test]
+    fn serialize_parse_round_trip() {
+        let save = every_edit_slot();
+        assert_eq!(SaveSlot::parse(&save.serialize()).unwrap(), save);
+    }
+
+    #[test]
+    fn every_voxel_material_round_trips() {
+        for material in [
+            None,
+            Some(VoxelMaterial::Sand),
+            Some(VoxelMaterial::Dirt),
+            Some(VoxelMaterial::Rock),
+            Some(VoxelMaterial::Leaf),
+            Some(VoxelMaterial::Trunk),
+            Some(VoxelMaterial::Water),
+        ] {
+            let name = voxel_material_name(material);
+            assert_eq!(parse_voxel_material(name).unwrap(), material);
+        }
+    }
+
+    #[test]
+    fn every_flora_type_round_trips() {
+        for flora_type in FloraType::all() {
+            assert_eq!(parse_flora_type(flora_type.desc().key).unwrap(), flora_type);
+        }
+    }
+
+    #[test]
+    fn parse_refuses_other_versions() {
+        let content = every_edit_slot().serialize();
+        let current = format!("version {}\n", SAVE_VERSION);
+        assert!(content.starts_with(&current));
+
+        for version in [0, SAVE_VERSION - 1, SAVE_VERSION + 1] {
+            let other = content.replacen(&current, &format!("version {}\n", version), 1);
+            assert!(SaveSlot::parse(&other).is_err());
+        }
+        let unversioned = content.replacen(&current, "", 1);
+        assert!(SaveSlot::parse(&unversioned).is_err());
+    }
+
+    #[test]
+    fn parse_refuses_unknown_records() {
+        let content = every_edit_slot().serialize() + "sapling 1 2 3\n";
+        assert!(SaveSlot::parse(&content).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::builder::SceneAccelBuilder;
+use crate::geom::ChunkIdx;
+use crate::tracer::Tracer;
+use crate::util::{full_path_from_relative, ShaderCompiler};
+use crate::vkn::{
+    exclusive_scan_cpu, execute_one_time_command, radix_sort_cpu, Allocator, Buffer, BufferUsage,
+    GpuPrefixSum, GpuRadixSort, VulkanContext,
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use glam::{UVec3, Vec2};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+/// Set to `1` to run the self-test even if it already passed on this device.
+pub const SELF_TEST_ENV_VAR: &str = "REFLORA_SELF_TEST";
+
+/// Relative to the project root, holds the last report per device.
+const SELF_TEST_DIR: &str = ".cache/self_test/";
+
+/// Element count of the prefix sum and radix sort checks, spans a few blocks of both.
+const PRIMITIVE_TEST_LEN: u32 = 1000;
+
+struct SelfTestResult {
+    subsystem: &'static str,
+    outcome: Result<(), String>,
+}
+
+/// Runs small GPU workloads with known results, so a driver that miscompiles a shader shows up as
+/// a failing subsystem instead of a black screen.
+pub struct SelfTest {
+    report_path: PathBuf,
+    results: Vec<SelfTestResult>,
+}
+
+impl SelfTest {
+    /// Returns a self-test if it hasn't run on this device and driver yet, or was requested via
+    /// [`SELF_TEST_ENV_VAR`].
+    pub fn new_if_due(vulkan_ctx: &VulkanContext) -> Option<Self> {
+        let report_path = PathBuf::from(full_path_from_relative(SELF_TEST_DIR))
+            .join(format!("{}.txt", vulkan_ctx.device_cache_key()));
+        let is_forced = std::env::var(SELF_TEST_ENV_VAR).as_deref() == Ok("1");
+        if !is_forced && report_path.exists() {
+            return None;
+        }
+        Some(Self {
+            report_path,
+            results: Vec::new(),
+        })
+    }
+
+    /// Runs one check, panics are caught and reported as failures of `subsystem`.
+    pub fn run(&mut self, subsystem: &'static str, check: impl FnOnce() -> Result<()>) {
+        let outcome = match catch_unwind(AssertUnwindSafe(check)) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!("{:#}", e)),
+            Err(panic) => Err(panic
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "panicked".to_string())),
+        };
+        match &outcome {
+            Ok(()) => log::info!("Self-test {}: passed", subsystem),
+            Err(e) => log::error!("Self-test {}: FAILED, {}", subsystem, e),
+        }
+        self.results.push(SelfTestResult { subsystem, outcome });
+    }
+
+    /// Logs the summary and stores the report, a stored report marks the device as tested.
+    pub fn finish(self) {
+        let failed: Vec<&str> = self
+            .results
+            .iter()
+            .filter(|r| r.outcome.is_err())
+            .map(|r| r.subsystem)
+            .collect();
+        if failed.is_empty() {
+            log::info!("Self-test passed all {} checks", self.results.len());
+        } else {
+            log::error!(
+                "Self-test failed {} of {} checks: {}, please attach {} to the bug report",
+                failed.len(),
+                self.results.len(),
+                failed.join(", "),
+                self.report_path.display()
+            );
+        }
+
+        let report: String = self
+            .results
+            .iter()
+            .map(|r| match &r.outcome {
+                Ok(()) => format!("{}: passed\n", r.subsystem),
+                Err(e) => format!("{}: FAILED, {}\n", r.subsystem, e),
+            })
+            .collect();
+        let write_result = self
+            .report_path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&self.report_path, report));
+        if let Err(e) = write_result {
+            log::warn!("Failed to store the self-test report: {}", e);
+        }
+    }
+}
+
+/// Deterministic pseudo random input for the primitive checks.
+fn test_input(len: u32, seed: u32) -> Vec<u32> {
+    (0..len)
+        .map(|i| (i ^ seed).wrapping_mul(2654435761) >> 8)
+        .collect()
+}
+
+fn host_buffer(vulkan_ctx: &VulkanContext, allocator: &Allocator, data: &[u32]) -> Result<Buffer> {
+    let buffer = Buffer::new_sized(
+        vulkan_ctx.device().clone(),
+        allocator.clone(),
+        BufferUsage::from_flags(
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        ),
+        gpu_allocator::MemoryLocation::GpuToCpu,
+        std::mem::size_of_val(data) as u64,
+    );
+    buffer.fill(data)?;
+    Ok(buffer)
+}
+
+fn read_u32s(buffer: &Buffer) -> Result<Vec<u32>> {
+    Ok(bytemuck::pod_collect_to_vec(&buffer.read_back()?))
+}
+
+/// Reports the first mismatching element, which is more useful than dumping both arrays.
+fn compare(name: &str, gpu: &[u32], cpu: &[u32]) -> Result<()> {
+    if let Some(i) = gpu.iter().zip(cpu).position(|(g, c)| g != c) {
+        bail!(
+            "{} mismatch at element {}: gpu {}, expected {}",
+            name,
+            i,
+            gpu[i],
+            cpu[i]
+        );
+    }
+    Ok(())
+}
+
+pub fn check_prefix_sum(
+    vulkan_ctx: &VulkanContext,
+    allocator: &Allocator,
+    shader_compiler: &ShaderCompiler,
+) -> Result<()> {
+    let input = test_input(PRIMITIVE_TEST_LEN, 0x5eed);
+    let prefix_sum = GpuPrefixSum::new(
+        vulkan_ctx.clone(),
+        allocator.clone(),
+        shader_compiler,
+        PRIMITIVE_TEST_LEN,
+    )?;
+    let buffer = host_buffer(vulkan_ctx, allocator, &input)?;
+    prefix_sum.exclusive_scan(&buffer, &buffer, PRIMITIVE_TEST_LEN)?;
+    compare(
+        "exclusive scan",
+        &read_u32s(&buffer)?,
+        &exclusive_scan_cpu(&input),
+    )
+}
+
+pub fn check_radix_sort(
+    vulkan_ctx: &VulkanContext,
+    allocator: &Allocator,
+    shader_compiler: &ShaderCompiler,
+) -> Result<()> {
+    let mut keys = test_input(PRIMITIVE_TEST_LEN, 0xabc);
+    let mut values: Vec<u32> = (0..PRIMITIVE_TEST_LEN).collect();
+    let radix_sort = GpuRadixSort::new(
+        vulkan_ctx.clone(),
+        allocator.clone(),
+        shader_compiler,
+        PRIMITIVE_TEST_LEN,
+    )?;
+    let key_buffer = host_buffer(vulkan_ctx, allocator, &keys)?;
+    let value_buffer = host_buffer(vulkan_ctx, allocator, &values)?;
+    radix_sort.sort(&key_buffer, Some(&value_buffer), PRIMITIVE_TEST_LEN)?;
+
+    radix_sort_cpu(&mut keys, Some(&mut values));
+    compare("sorted keys", &read_u32s(&key_buffer)?, &keys)?;
+    compare("sorted values", &read_u32s(&value_buffer)?, &values)
+}
+
+/// Writes a known entry for chunk 0 into the scene texture, reads it back and evicts it again.
+/// Has to run before the world is built, as it owns chunk 0's entry meanwhile.
+pub fn check_scene_accel(
+    vulkan_ctx: &VulkanContext,
+    scene_accel_builder: &mut SceneAccelBuilder,
+) -> Result<()> {
+    let read_chunk_0_entry = |scene_accel_builder: &SceneAccelBuilder| -> Result<[u32; 2]> {
+        // the update runs on the compute queue without blocking the host
+        vulkan_ctx.wait_compute()?;
+        let image = scene_accel_builder.get_resources().scene_tex.get_image();
+        let data = image.fetch_data(&vulkan_ctx.get_general_queue(), vulkan_ctx.command_pool())?;
+        // the builder's pre-recorded update expects the texture in general layout
+        execute_one_time_command(
+            vulkan_ctx.device(),
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| image.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL),
+        );
+        let texel: Vec<u32> = bytemuck::pod_collect_to_vec(&data[..8]);
+        Ok([texel[0], texel[1]])
+    };
+
+    let (node_offset, leaf_offset) = (12, 34);
+    scene_accel_builder.update_scene_tex(ChunkIdx::default(), node_offset, leaf_offset)?;
+    // entries are stored off by one so zero can mean empty
+    let expected = [node_offset as u32 + 1, leaf_offset as u32 + 1];
+    let entry = read_chunk_0_entry(scene_accel_builder)?;
+    if entry != expected {
+        bail!("chunk entry is {:?}, expected {:?}", entry, expected);
+    }
+
+    scene_accel_builder.evict_chunk(ChunkIdx::default())?;
+    let entry = read_chunk_0_entry(scene_accel_builder)?;
+    if entry != [0, 0] {
+        bail!("evicted chunk entry is {:?}, expected empty", entry);
+    }
+    Ok(())
+}
+
+/// Shoots terrain queries down at a few chunk centers of the built world, every one has to land
+/// on the ground with a material and a unit normal. This covers the scene texture lookup and the
+/// contree traversal.
+pub fn check_terrain_query(tracer: &mut Tracer, chunk_dim: UVec3) -> Result<()> {
+    let positions: Vec<Vec2> = [(0, 0), (chunk_dim.x - 1, 0), (0, chunk_dim.z - 1)]
+        .iter()
+        .map(|&(x, z)| Vec2::new(x as f32 + 0.5, z as f32 + 0.5))
+        .collect();
+    let hits = tracer.query_terrain_batch(&positions)?;
+    for (pos, hit) in positions.iter().zip(hits) {
+        let hit = match hit {
+            Ok(hit) => hit,
+            Err(miss) => bail!("terrain query at {:?} found no ground: {}", pos, miss),
+        };
+        if !(hit.height > 0.0 && hit.height < chunk_dim.y as f32) {
+            bail!(
+                "terrain query at {:?} returned {}, expected ground in (0, {})",
+                pos,
+                hit.height,
+                chunk_dim.y
+            );
+        }
+        if hit.material.is_none() {
+            bail!("terrain query at {:?} hit ground without a material", pos);
+        }
+        if (hit.normal.length() - 1.0).abs() > 1e-3 {
+            bail!(
+                "terrain query at {:?} returned normal {}, expected unit length",
+                pos,
+                hit.normal
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use super::planting::raycast_terrain;
+use super::save_slot::WorldEdit;
+use crate::tracer::{Tracer, VoxelMaterial};
+use crate::util::DebugDraw;
+use anyhow::Result;
+use egui::Color32;
+use glam::Vec3;
+use std::f32::consts::TAU;
+
+/// Segments of each circle of the ghost overlay.
+const GHOST_SEGMENTS: usize = 32;
+
+const DIG_COLOR: Color32 = Color32::from_rgb(255, 120, 80);
+const BUILD_COLOR: Color32 = Color32::from_rgb(120, 200, 255);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelEditMode {
+    Dig,
+    Build,
+}
+
+/// Digging and building with spheres of voxels at the point the camera looks at.
+pub struct VoxelEditTool {
+    pub is_active: bool,
+    pub mode: VoxelEditMode,
+    /// What is built, digging always leaves air.
+    pub material: VoxelMaterial,
+    /// Radius of the sphere in world units.
+    pub radius: f32,
+    hit_point: Option<Vec3>,
+}
+
+impl Default for VoxelEditTool {
+    fn default() -> Self {
+        Self {
+            is_active: false,
+            mode: VoxelEditMode::Dig,
+            material: VoxelMaterial::Dirt,
+            radius: 0.03,
+            hit_point: None,
+        }
+    }
+}
+
+impl VoxelEditTool {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_active, "Voxel Edit Mode (V)");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, VoxelEditMode::Dig, "Dig");
+            ui.selectable_value(&mut self.mode, VoxelEditMode::Build, "Build");
+        });
+        if self.mode == VoxelEditMode::Build {
+            ui.horizontal(|ui| {
+                for (material, name) in [
+                    (VoxelMaterial::Dirt, "Dirt"),
+                    (VoxelMaterial::Sand, "Sand"),
+                    (VoxelMaterial::Rock, "Rock"),
+                ] {
+                    ui.selectable_value(&mut self.material, material, name);
+                }
+            });
+        }
+        ui.add(egui::Slider::new(&mut self.radius, 0.005..=0.2).text("Radius"));
+        ui.label("Left click to edit at the marked spot.");
+    }
+
+    /// Raycasts the terrain from the camera, call once per frame while active.
+    pub fn update(&mut self, tracer: &mut Tracer) -> Result<()> {
+        if !self.is_active {
+            self.hit_point = None;
+            return Ok(());
+        }
+        let origin = tracer.camera_position();
+        let direction = tracer.camera_vectors().front;
+        self.hit_point = raycast_terrain(tracer, origin, direction)?;
+        Ok(())
+    }
+
+    /// Digging centers the sphere on the ground, building sets it on top.
+    fn sphere_center(&self, hit_point: Vec3) -> Vec3 {
+        match self.mode {
+            VoxelEditMode::Dig => hit_point,
+            VoxelEditMode::Build => hit_point + Vec3::Y * self.radius * 0.5,
+        }
+    }
+
+    /// Queues the outline of the sphere the next click edits.
+    pub fn draw_ghost(&self, debug_draw: &mut DebugDraw) {
+        let Some(hit_point) = self.hit_point else {
+            return;
+        };
+        let center = self.sphere_center(hit_point);
+        let color = match self.mode {
+            VoxelEditMode::Dig => DIG_COLOR,
+            VoxelEditMode::Build => BUILD_COLOR,
+        };
+        for (u, v) in [(Vec3::X, Vec3::Z), (Vec3::X, Vec3::Y), (Vec3::Z, Vec3::Y)] {
+            let circle: Vec<Vec3> = (0..GHOST_SEGMENTS)
+                .map(|i| {
+                    let angle = TAU * i as f32 / GHOST_SEGMENTS as f32;
+                    center + (u * angle.cos() + v * angle.sin()) * self.radius
+                })
+                .collect();
+            debug_draw.line_loop(&circle, color);
+        }
+        debug_draw.point(hit_point, 4.0, color);
+    }
+
+    /// The edit a click at the current hit point makes, `None` when nothing is aimed at.
+    pub fn edit_request(&self) -> Option<WorldEdit> {
+        let hit_point = self.hit_point?;
+        let material = match self.mode {
+            VoxelEditMode::Dig => None,
+            VoxelEditMode::Build => Some(self.material),
+        };
+        Some(WorldEdit::ModifyVoxels {
+            center: self.sphere_center(hit_point),
+            radius: self.radius,
+            material,
+        })
+    }
+}
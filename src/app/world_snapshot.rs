@@ -0,0 +1,526 @@
+use crate::builder::ContreeChunkData;
+use crate::constants::VOXEL_DIM;
+use crate::geom::{ChunkIdx, UAabb3};
+use crate::tree_gen::TreeDesc;
+use crate::util::full_path_from_relative;
+use anyhow::{bail, Context, Result};
+use glam::{UVec3, Vec3};
+use std::path::PathBuf;
+
+/// Relative to the project root.
+const WORLD_SNAPSHOT_PATH: &str = "saves/world.bin";
+
+const MAGIC: &[u8; 4] = b"RFWS";
+
+/// Bumped whenever the layout changes, older snapshots are refused instead of misread.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Every chunk stores all of its voxels, one byte each.
+const VOXELS_PER_CHUNK: usize = (VOXEL_DIM as usize).pow(3);
+
+/// The fewest bytes a chunk takes: its index, the voxel and run counts and the contree flag.
+const MIN_CHUNK_BYTES: usize = 12 + 4 + 4 + 1;
+/// The length and the value.
+const RUN_BYTES: usize = 4 + 1;
+/// The fewest bytes a tree takes: its id and position, its description is larger still.
+const MIN_TREE_BYTES: usize = 4 + 12;
+
+/// The voxels and contree data of one chunk.
+#[derive(Debug, Clone)]
+pub struct ChunkSnapshot {
+    pub chunk_idx: ChunkIdx,
+    /// As read from the chunk atlas, see `PlainBuilder::read_chunk_voxels`.
+    pub voxels: Vec<u8>,
+    /// `None` for a chunk that wasn't resident in the contree pools, it's built from its voxels.
+    pub contree: Option<ContreeChunkData>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TreePlacement {
+    pub tree_id: u32,
+    pub desc: TreeDesc,
+    pub position: Vec3,
+}
+
+/// The generated world as the builders hold it, so startup can skip generating it again.
+///
+/// Unlike a save slot, which replays edits on top of the seeded world, the snapshot stores the
+/// built data itself. The trees' trunks are part of the voxels, their leaves and sounds are
+/// rebuilt from the placements. Flora is regrown from the voxels, planted and cut flora is lost.
+#[derive(Debug, Clone)]
+pub struct WorldSnapshot {
+    pub chunks: Vec<ChunkSnapshot>,
+    pub trees: Vec<TreePlacement>,
+    pub next_tree_id: u32,
+    /// The voxels touched by tree placements, see `App::prev_bound`.
+    pub tree_bound: UAabb3,
+    pub has_forest: bool,
+}
+
+fn snapshot_path() -> PathBuf {
+    PathBuf::from(full_path_from_relative(WORLD_SNAPSHOT_PATH))
+}
+
+impl WorldSnapshot {
+    pub fn exists() -> bool {
+        snapshot_path().is_file()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = snapshot_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, self.serialize())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = snapshot_path();
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&bytes, VOXELS_PER_CHUNK)
+            .with_context(|| format!("Malformed world snapshot {}", path.display()))
+    }
+
+    pub fn delete() -> Result<()> {
+        let path = snapshot_path();
+        if path.is_file() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Little endian throughout, the voxels are run length encoded since most of a chunk is air
+    /// or solid ground.
+    fn serialize(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        w.bytes(MAGIC);
+        w.u32(SNAPSHOT_VERSION);
+        w.u32(self.next_tree_id);
+        w.uvec3(self.tree_bound.min());
+        w.uvec3(self.tree_bound.max());
+        w.bool(self.has_forest);
+
+        w.u32(self.chunks.len() as u32);
+        for chunk in &self.chunks {
+            w.uvec3(chunk.chunk_idx.0);
+            let runs = run_length_encode(&chunk.voxels);
+            w.u32(chunk.voxels.len() as u32);
+            w.u32(runs.len() as u32);
+            for (len, value) in runs {
+                w.u32(len);
+                w.u8(value);
+            }
+            match &chunk.contree {
+                Some(contree) => {
+                    w.bool(true);
+                    w.blob(&contree.nodes);
+                    w.blob(&contree.leaves);
+                }
+                None => w.bool(false),
+            }
+        }
+
+        w.u32(self.trees.len() as u32);
+        for tree in &self.trees {
+            w.u32(tree.tree_id);
+            w.vec3(tree.position);
+            write_tree_desc(&mut w, &tree.desc);
+        }
+        w.0
+    }
+
+    /// The counts in the file are checked against the bytes left before anything is allocated
+    /// for them, so a truncated or corrupt snapshot fails instead of exhausting the memory.
+    fn parse(bytes: &[u8], voxels_per_chunk: usize) -> Result<Self> {
+        let mut r = ByteReader { bytes, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            bail!("not a world snapshot");
+        }
+        let version = r.u32()?;
+        if version != SNAPSHOT_VERSION {
+            bail!(
+                "snapshot version {} is not supported, expected {}",
+                version,
+                SNAPSHOT_VERSION
+            );
+        }
+        let next_tree_id = r.u32()?;
+        let tree_bound = UAabb3::new(r.uvec3()?, r.uvec3()?);
+        let has_forest = r.bool()?;
+
+        let chunk_count = r.count(MIN_CHUNK_BYTES)?;
+        let mut chunks = Vec::with_capacity(chunk_count);
+        for _ in 0..chunk_count {
+            let chunk_idx = ChunkIdx(r.uvec3()?);
+            let voxel_count = r.u32()? as usize;
+            if voxel_count != voxels_per_chunk {
+                bail!(
+                    "chunk {} has {} voxels, expected {}",
+                    chunk_idx,
+                    voxel_count,
+                    voxels_per_chunk
+                );
+            }
+            let run_count = r.count(RUN_BYTES)?;
+            let mut voxels = Vec::with_capacity(voxel_count);
+            for _ in 0..run_count {
+                let len = r.u32()? as usize;
+                let value = r.u8()?;
+                if len > voxel_count - voxels.len() {
+                    bail!("chunk {} has more than {} voxels", chunk_idx, voxel_count);
+                }
+                voxels.resize(voxels.len() + len, value);
+            }
+            if voxels.len() != voxel_count {
+                bail!(
+                    "chunk {} decodes to {} voxels, expected {}",
+                    chunk_idx,
+                    voxels.len(),
+                    voxel_count
+                );
+            }
+            let contree = if r.bool()? {
+                Some(ContreeChunkData {
+                    nodes: r.blob()?,
+                    leaves: r.blob()?,
+                })
+            } else {
+                None
+            };
+            chunks.push(ChunkSnapshot {
+                chunk_idx,
+                voxels,
+                contree,
+            });
+        }
+
+        let tree_count = r.count(MIN_TREE_BYTES)?;
+        let mut trees = Vec::with_capacity(tree_count);
+        for _ in 0..tree_count {
+            trees.push(TreePlacement {
+                tree_id: r.u32()?,
+                position: r.vec3()?,
+                desc: read_tree_desc(&mut r)?,
+            });
+        }
+        if r.pos != bytes.len() {
+            bail!("{} trailing bytes", bytes.len() - r.pos);
+        }
+
+        Ok(Self {
+            chunks,
+            trees,
+            next_tree_id,
+            tree_bound,
+            has_forest,
+        })
+    }
+}
+
+/// Returns: (run_length, value) pairs
+fn run_length_encode(data: &[u8]) -> Vec<(u32, u8)> {
+    let mut runs: Vec<(u32, u8)> = Vec::new();
+    for &value in data {
+        match runs.last_mut() {
+            Some((len, last)) if *last == value && *len < u32::MAX => *len += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+fn write_tree_desc(w: &mut ByteWriter, desc: &TreeDesc) {
+    w.f32(desc.size);
+    w.f32(desc.trunk_thickness);
+    w.f32(desc.trunk_thickness_min);
+    w.f32(desc.spread);
+    w.f32(desc.randomness);
+    w.f32(desc.vertical_tendency);
+    w.f32(desc.branch_angle_min);
+    w.f32(desc.branch_angle_max);
+    w.f32(desc.branch_probability);
+    w.u32(desc.branch_count_min);
+    w.u32(desc.branch_count_max);
+    w.u32(desc.leaves_size_level);
+    w.u32(desc.leaf_offset);
+    w.u32(desc.iterations);
+    w.f32(desc.segment_length_variation);
+    w.f32(desc.tree_height);
+    w.f32(desc.length_dropoff);
+    w.f32(desc.thickness_reduction);
+    w.u64(desc.seed);
+    w.bool(desc.enable_subdivision);
+    w.u32(desc.subdivision_count_min);
+    w.u32(desc.subdivision_count_max);
+    w.f32(desc.subdivision_randomness);
+    w.f32(desc.subdivision_randomness_progression);
+    w.f32(desc.rotation);
+}
+
+/// Reads the fields in the order [`write_tree_desc`] writes them.
+fn read_tree_desc(r: &mut ByteReader) -> Result<TreeDesc> {
+    Ok(TreeDesc {
+        size: r.f32()?,
+        trunk_thickness: r.f32()?,
+        trunk_thickness_min: r.f32()?,
+        spread: r.f32()?,
+        randomness: r.f32()?,
+        vertical_tendency: r.f32()?,
+        branch_angle_min: r.f32()?,
+        branch_angle_max: r.f32()?,
+        branch_probability: r.f32()?,
+        branch_count_min: r.u32()?,
+        branch_count_max: r.u32()?,
+        leaves_size_level: r.u32()?,
+        leaf_offset: r.u32()?,
+        iterations: r.u32()?,
+        segment_length_variation: r.f32()?,
+        tree_height: r.f32()?,
+        length_dropoff: r.f32()?,
+        thickness_reduction: r.f32()?,
+        seed: r.u64()?,
+        enable_subdivision: r.bool()?,
+        subdivision_count_min: r.u32()?,
+        subdivision_count_max: r.u32()?,
+        subdivision_randomness: r.f32()?,
+        subdivision_randomness_progression: r.f32()?,
+        rotation: r.f32()?,
+    })
+}
+
+#[derive(Default)]
+struct ByteWriter(Vec<u8>);
+
+impl ByteWriter {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn uvec3(&mut self, value: UVec3) {
+        value.to_array().into_iter().for_each(|v| self.u32(v));
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        value.to_array().into_iter().for_each(|v| self.f32(v));
+    }
+
+    /// Length prefixed.
+    fn blob(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.bytes(bytes);
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl ByteReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let Some(end) = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+        else {
+            bail!("unexpected end of data at byte {}", self.pos);
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => bail!("invalid bool {} at byte {}", other, self.pos - 1),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn uvec3(&mut self) -> Result<UVec3> {
+        Ok(UVec3::new(self.u32()?, self.u32()?, self.u32()?))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    /// Reads the count of the elements that follow, each at least `min_element_bytes` long,
+    /// fails when they can't all fit in the bytes left.
+    fn count(&mut self, min_element_bytes: usize) -> Result<usize> {
+        let count = self.u32()? as usize;
+        let remaining = self.bytes.len() - self.pos;
+        if count.saturating_mul(min_element_bytes) > remaining {
+            bail!(
+                "{} elements can't fit in the {} bytes left at byte {}",
+                count,
+                remaining,
+                self.pos
+            );
+        }
+        Ok(count)
+    }
+
+    fn blob(&mut self) -> Result<Vec<u8>> {
+        let len = usize::try_from(self.u64()?)?;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_VOXELS_PER_CHUNK: usize = 64;
+
+    fn test_snapshot() -> WorldSnapshot {
+        let mut ground = vec![0u8; TEST_VOXELS_PER_CHUNK];
+        ground[..40].fill(2);
+        ground[17] = 5;
+        let mut tree_desc = TreeDesc::default();
+        tree_desc.seed = 42;
+        tree_desc.size = 3.5;
+        WorldSnapshot {
+            chunks: vec![
+                ChunkSnapshot {
+                    chunk_idx: ChunkIdx(UVec3::new(0, 0, 1)),
+                    voxels: ground,
+                    contree: Some(ContreeChunkData {
+                        nodes: vec![1, 2, 3, 4],
+                        leaves: vec![9; 12],
+                    }),
+                },
+                ChunkSnapshot {
+                    chunk_idx: ChunkIdx(UVec3::new(1, 0, 1)),
+                    voxels: vec![0; TEST_VOXELS_PER_CHUNK],
+                    contree: None,
+                },
+            ],
+            trees: vec![
+                TreePlacement {
+                    tree_id: 3,
+                    desc: TreeDesc::default(),
+                    position: Vec3::new(0.5, 0.2, 1.5),
+                },
+                TreePlacement {
+                    tree_id: 7,
+                    desc: tree_desc,
+                    position: Vec3::new(1.25, 0.3, 1.75),
+                },
+            ],
+            next_tree_id: 8,
+            tree_bound: UAabb3::new(UVec3::new(10, 0, 256), UVec3::new(400, 90, 500)),
+            has_forest: true,
+        }
+    }
+
+    #[test]
+    fn serialize_parse_round_trip() {
+        let snapshot = test_snapshot();
+        let parsed = WorldSnapshot::parse(&snapshot.serialize(), TEST_VOXELS_PER_CHUNK).unwrap();
+
+        assert_eq!(parsed.next_tree_id, snapshot.next_tree_id);
+        assert_eq!(parsed.tree_bound.min(), snapshot.tree_bound.min());
+        assert_eq!(parsed.tree_bound.max(), snapshot.tree_bound.max());
+        assert_eq!(parsed.has_forest, snapshot.has_forest);
+        assert_eq!(parsed.chunks.len(), snapshot.chunks.len());
+        for (parsed, chunk) in parsed.chunks.iter().zip(&snapshot.chunks) {
+            assert_eq!(parsed.chunk_idx, chunk.chunk_idx);
+            assert_eq!(parsed.voxels, chunk.voxels);
+            assert_eq!(
+                parsed.contree.as_ref().map(|c| (&c.nodes, &c.leaves)),
+                chunk.contree.as_ref().map(|c| (&c.nodes, &c.leaves))
+            );
+        }
+        assert_eq!(parsed.trees.len(), snapshot.trees.len());
+        for (parsed, tree) in parsed.trees.iter().zip(&snapshot.trees) {
+            assert_eq!(parsed.tree_id, tree.tree_id);
+            assert_eq!(parsed.position, tree.position);
+            // every field of the description is plain data
+            assert_eq!(format!("{:?}", parsed.desc), format!("{:?}", tree.desc));
+        }
+    }
+
+    #[test]
+    fn truncated_snapshots_are_refused() {
+        let bytes = test_snapshot().serialize();
+        for len in 0..bytes.len() {
+            assert!(
+                WorldSnapshot::parse(&bytes[..len], TEST_VOXELS_PER_CHUNK).is_err(),
+                "{} of {} bytes parsed",
+                len,
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_counts_are_refused() {
+        let bytes = test_snapshot().serialize();
+        // magic, version, next tree id, tree bound and forest flag
+        let chunk_count_pos = 4 + 4 + 4 + 24 + 1;
+        let voxel_count_pos = chunk_count_pos + 4 + 12;
+        let run_count_pos = voxel_count_pos + 4;
+        let first_run_pos = run_count_pos + 4;
+        for pos in [
+            chunk_count_pos,
+            voxel_count_pos,
+            run_count_pos,
+            first_run_pos,
+        ] {
+            let mut corrupt = bytes.clone();
+            corrupt[pos..pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(WorldSnapshot::parse(&corrupt, TEST_VOXELS_PER_CHUNK).is_err());
+        }
+    }
+
+    #[test]
+    fn chunks_of_another_size_are_refused() {
+        let bytes = test_snapshot().serialize();
+        assert!(WorldSnapshot::parse(&bytes, TEST_VOXELS_PER_CHUNK * 2).is_err());
+    }
+}
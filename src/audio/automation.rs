@@ -0,0 +1,283 @@
+use crate::audio::{MusicManager, MusicStem};
+use egui::{Color32, Pos2, Sense, Stroke, Vec2};
+use std::f32::consts::PI;
+
+/// Pixels a control point can be grabbed from.
+const GRAB_RADIUS: f32 = 6.0;
+
+/// Piecewise linear curve over an input in 0 to 1, the output is in 0 to 1 as well and mapped to
+/// the parameter's range by the binding.
+#[derive(Debug, Clone)]
+pub struct AutomationCurve {
+    /// Sorted by x, the first and last point hold the curve's value outside of them.
+    points: Vec<[f32; 2]>,
+    dragged_point: Option<usize>,
+}
+
+impl AutomationCurve {
+    pub fn constant(value: f32) -> Self {
+        Self {
+            points: vec![[0.0, value], [1.0, value]],
+            dragged_point: None,
+        }
+    }
+
+    /// Samples `f` at `point_count` evenly spaced inputs.
+    pub fn from_fn(point_count: usize, f: impl Fn(f32) -> f32) -> Self {
+        let last = point_count.max(2) - 1;
+        Self {
+            points: (0..=last)
+                .map(|i| {
+                    let x = i as f32 / last as f32;
+                    [x, f(x).clamp(0.0, 1.0)]
+                })
+                .collect(),
+            dragged_point: None,
+        }
+    }
+
+    pub fn sample(&self, x: f32) -> f32 {
+        let upper_idx = self.points.partition_point(|point| point[0] < x);
+        if upper_idx == 0 {
+            return self.points[0][1];
+        }
+        if upper_idx == self.points.len() {
+            return self.points[upper_idx - 1][1];
+        }
+        let [x0, y0] = self.points[upper_idx - 1];
+        let [x1, y1] = self.points[upper_idx];
+        let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 0.0 };
+        y0 + (y1 - y0) * t
+    }
+
+    /// Drag the control points around, double click to add one and right click to remove one.
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        let size = Vec2::new(ui.available_width().min(280.0), 90.0);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        let to_screen = |point: [f32; 2]| {
+            Pos2::new(
+                rect.left() + point[0] * rect.width(),
+                rect.bottom() - point[1] * rect.height(),
+            )
+        };
+        let from_screen = |pos: Pos2| {
+            [
+                ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+                ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0),
+            ]
+        };
+        let point_at = |points: &[[f32; 2]], pos: Pos2| {
+            points
+                .iter()
+                .position(|point| to_screen(*point).distance(pos) <= GRAB_RADIUS)
+        };
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            if response.drag_started() {
+                self.dragged_point = point_at(&self.points, pos);
+            }
+            if let Some(idx) = self.dragged_point {
+                let [x, y] = from_screen(pos);
+                // a point can't be dragged past its neighbours, so the points stay sorted
+                let min_x = if idx == 0 {
+                    0.0
+                } else {
+                    self.points[idx - 1][0]
+                };
+                let max_x = self
+                    .points
+                    .get(idx + 1)
+                    .map_or(1.0, |next_point| next_point[0]);
+                self.points[idx] = [x.clamp(min_x, max_x), y];
+            }
+            if response.double_clicked() && point_at(&self.points, pos).is_none() {
+                let point = from_screen(pos);
+                let idx = self.points.partition_point(|p| p[0] < point[0]);
+                self.points.insert(idx, point);
+            }
+            if response.secondary_clicked() && self.points.len() > 2 {
+                if let Some(idx) = point_at(&self.points, pos) {
+                    self.points.remove(idx);
+                }
+            }
+        }
+        if response.drag_stopped() {
+            self.dragged_point = None;
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, Color32::from_gray(30));
+        let line = (0..=64)
+            .map(|i| {
+                let x = i as f32 / 64.0;
+                to_screen([x, self.sample(x)])
+            })
+            .collect::<Vec<_>>();
+        painter.add(egui::Shape::line(
+            line,
+            Stroke::new(1.5, Color32::from_rgb(120, 200, 255)),
+        ));
+        for point in &self.points {
+            painter.circle_filled(to_screen(*point), 3.5, Color32::WHITE);
+        }
+        painter.rect_stroke(
+            rect,
+            2.0,
+            Stroke::new(1.0, Color32::from_gray(80)),
+            egui::StrokeKind::Inside,
+        );
+    }
+}
+
+/// What drives an automation curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomationSource {
+    /// The day cycle, 0 and 1 are midnight.
+    TimeOfDay,
+    /// From calm at 0 to a storm at 1.
+    WindStrength,
+}
+
+impl AutomationSource {
+    pub const ALL: [AutomationSource; 2] =
+        [AutomationSource::TimeOfDay, AutomationSource::WindStrength];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AutomationSource::TimeOfDay => "Time Of Day",
+            AutomationSource::WindStrength => "Wind Strength",
+        }
+    }
+}
+
+/// An audio parameter curves can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioParam {
+    MusicVolumeDb,
+    /// Scales the stem's mix from the player state, from silent to full.
+    StemLevel(MusicStem),
+}
+
+impl AudioParam {
+    pub fn name(self) -> String {
+        match self {
+            AudioParam::MusicVolumeDb => "Music Volume (dB)".to_string(),
+            AudioParam::StemLevel(stem) => format!("{} Level", stem.name()),
+        }
+    }
+
+    /// The values a curve output of 0 and 1 map to.
+    fn range(self) -> (f32, f32) {
+        match self {
+            AudioParam::MusicVolumeDb => (-60.0, 6.0),
+            AudioParam::StemLevel(_) => (0.0, 1.0),
+        }
+    }
+}
+
+/// The signals automation curves are evaluated at this frame.
+#[derive(Debug, Clone, Copy)]
+pub struct AutomationInputs {
+    pub time_of_day: f32,
+    pub wind_strength: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutomationBinding {
+    pub param: AudioParam,
+    pub source: AutomationSource,
+    pub curve: AutomationCurve,
+    pub is_enabled: bool,
+}
+
+impl AutomationBinding {
+    pub fn value(&self, inputs: &AutomationInputs) -> f32 {
+        let x = match self.source {
+            AutomationSource::TimeOfDay => inputs.time_of_day.rem_euclid(1.0),
+            AutomationSource::WindStrength => inputs.wind_strength.clamp(0.0, 1.0),
+        };
+        let (min, max) = self.param.range();
+        min + (max - min) * self.curve.sample(x)
+    }
+}
+
+/// Drives audio parameters from curves over the day cycle or the wind, one binding per
+/// parameter.
+pub struct AudioAutomation {
+    bindings: Vec<AutomationBinding>,
+}
+
+impl Default for AudioAutomation {
+    /// The stems follow the day the way the music was mixed, the volume is left to the slider.
+    fn default() -> Self {
+        // 0 at midnight, 1 at solar noon
+        let daylight = |time_of_day: f32| 0.5 - 0.5 * (time_of_day * 2.0 * PI).cos();
+        let stem_curve = |stem: MusicStem| match stem {
+            // the pad carries the night, the melody and percussion come in with the day
+            MusicStem::Pad => AutomationCurve::from_fn(9, |t| 1.0 - 0.5 * daylight(t)),
+            MusicStem::Melody | MusicStem::Percussion => AutomationCurve::from_fn(9, daylight),
+        };
+
+        let mut bindings = vec![AutomationBinding {
+            param: AudioParam::MusicVolumeDb,
+            source: AutomationSource::WindStrength,
+            curve: AutomationCurve::constant(0.7),
+            is_enabled: false,
+        }];
+        bindings.extend(MusicStem::ALL.map(|stem| AutomationBinding {
+            param: AudioParam::StemLevel(stem),
+            source: AutomationSource::TimeOfDay,
+            curve: stem_curve(stem),
+            is_enabled: true,
+        }));
+        Self { bindings }
+    }
+}
+
+impl AudioAutomation {
+    /// Sets every bound parameter to its curve's value, call once per frame before the managers
+    /// update.
+    pub fn apply(&self, inputs: &AutomationInputs, music_manager: &mut MusicManager) {
+        for binding in &self.bindings {
+            let value = if binding.is_enabled {
+                binding.value(inputs)
+            } else {
+                match binding.param {
+                    AudioParam::MusicVolumeDb => continue,
+                    // an unbound stem plays its full mix
+                    AudioParam::StemLevel(_) => 1.0,
+                }
+            };
+            match binding.param {
+                AudioParam::MusicVolumeDb => {
+                    // a new volume resends every stem, skip inaudible changes
+                    if (music_manager.volume_db() - value).abs() > 0.05 {
+                        music_manager.set_volume_db(value);
+                    }
+                }
+                AudioParam::StemLevel(stem) => music_manager.set_stem_level(stem, value),
+            }
+        }
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui, inputs: &AutomationInputs) {
+        for (idx, binding) in self.bindings.iter_mut().enumerate() {
+            ui.push_id(idx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut binding.is_enabled, binding.param.name());
+                    egui::ComboBox::from_id_salt("source")
+                        .selected_text(binding.source.name())
+                        .show_ui(ui, |ui| {
+                            for source in AutomationSource::ALL {
+                                ui.selectable_value(&mut binding.source, source, source.name());
+                            }
+                        });
+                });
+                if binding.is_enabled {
+                    binding.curve.edit_by_gui(ui);
+                    ui.label(format!("Now: {:.2}", binding.value(inputs)));
+                }
+            });
+        }
+    }
+}
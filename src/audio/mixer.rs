@@ -0,0 +1,95 @@
+/// The gain of a muted bus in dB, far below anything audible.
+const MUTED_GAIN_DB: f32 = -120.0;
+
+/// The groups the sources are mixed in, every source plays through one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    /// Trees, wind and the other loops of the world.
+    Ambient,
+    /// Footsteps and the sounds of the player's actions.
+    Sfx,
+    Ui,
+    Music,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 4] = [
+        AudioBus::Ambient,
+        AudioBus::Sfx,
+        AudioBus::Ui,
+        AudioBus::Music,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AudioBus::Ambient => "ambient",
+            AudioBus::Sfx => "sfx",
+            AudioBus::Ui => "ui",
+            AudioBus::Music => "music",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusSettings {
+    /// Added to the volume of every source on the bus.
+    pub gain_db: f32,
+    pub is_muted: bool,
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        Self {
+            gain_db: 0.0,
+            is_muted: false,
+        }
+    }
+}
+
+/// The gain and mute of every [`AudioBus`], applied on top of the volumes of the sources.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Mixer {
+    buses: [BusSettings; AudioBus::ALL.len()],
+}
+
+impl Mixer {
+    pub fn bus(&self, bus: AudioBus) -> BusSettings {
+        self.buses[bus.index()]
+    }
+
+    pub fn set_bus(&mut self, bus: AudioBus, settings: BusSettings) {
+        self.buses[bus.index()] = settings;
+    }
+
+    /// What the bus adds to the volume of its sources, in dB.
+    pub fn output_gain_db(&self, bus: AudioBus) -> f32 {
+        let settings = self.bus(bus);
+        if settings.is_muted {
+            MUTED_GAIN_DB
+        } else {
+            settings.gain_db
+        }
+    }
+
+    /// Returns whether anything changed.
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut is_changed = false;
+        for bus in AudioBus::ALL {
+            let settings = &mut self.buses[bus.index()];
+            ui.horizontal(|ui| {
+                is_changed |= ui.checkbox(&mut settings.is_muted, "Mute").changed();
+                is_changed |= ui
+                    .add(
+                        egui::Slider::new(&mut settings.gain_db, -40.0..=12.0)
+                            .text(format!("{} (dB)", bus.name())),
+                    )
+                    .changed();
+            });
+        }
+        is_changed
+    }
+}
@@ -1,10 +1,28 @@
 mod audio_clip_cache;
 
+mod mixer;
+pub use mixer::*;
+
 mod spatial_sound_manager;
 pub use spatial_sound_manager::*;
 
+mod sound_occlusion;
+pub use sound_occlusion::*;
+
 mod source_clustering;
 pub use source_clustering::*;
 
 mod tree_audio_manager;
 pub use tree_audio_manager::*;
+
+mod voice_manager;
+pub use voice_manager::*;
+
+mod music_manager;
+pub use music_manager::*;
+
+mod automation;
+pub use automation::*;
+
+mod wind_ambience;
+pub use wind_ambience::*;
@@ -0,0 +1,215 @@
+use crate::audio::{AudioBus, SpatialSoundManager};
+use crate::util::profile_scope;
+use anyhow::Result;
+use glam::Vec3;
+use uuid::Uuid;
+
+/// Relative to the project root, lists the stem files, one `stem path` line each.
+pub const MUSIC_MANIFEST_PATH: &str = "assets/music/manifest.txt";
+
+/// Seconds a stem takes to get most of the way to its new volume.
+const DEFAULT_CROSSFADE_TIME: f32 = 4.0;
+
+/// Horizontal speed above which the player counts as exploring.
+const EXPLORING_SPEED: f32 = 0.1;
+
+/// Seconds the exploring state takes to settle after the player starts or stops moving.
+const EXPLORING_SMOOTHING: f32 = 2.0;
+
+/// Below this gain a stem is played at this level instead of a log of zero.
+const MIN_GAIN: f32 = 1e-3;
+
+/// The layers of the music, all stems loop in sync and only their volumes change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusicStem {
+    Pad,
+    Melody,
+    Percussion,
+}
+
+impl MusicStem {
+    pub const ALL: [MusicStem; 3] = [MusicStem::Pad, MusicStem::Melody, MusicStem::Percussion];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MusicStem::Pad => "pad",
+            MusicStem::Melody => "melody",
+            MusicStem::Percussion => "percussion",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|stem| stem.name() == name)
+    }
+
+    /// Volume of the stem from 0 to 1 before its level, `exploring` is in 0 to 1 as well.
+    ///
+    /// The pad always plays, the melody swells and the percussion only plays while the player
+    /// moves around. How they follow the day is up to the level curves of the
+    /// [`crate::audio::AudioAutomation`].
+    fn target_gain(self, exploring: f32) -> f32 {
+        match self {
+            MusicStem::Pad => 1.0,
+            MusicStem::Melody => 0.5 + 0.5 * exploring,
+            MusicStem::Percussion => exploring,
+        }
+    }
+}
+
+/// Reads `stem path` lines, paths may contain spaces and `#` starts a comment line.
+fn parse_manifest(content: &str) -> Vec<(MusicStem, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, path) = line.split_once(char::is_whitespace)?;
+            match MusicStem::from_name(name) {
+                Some(stem) => Some((stem, path.trim().to_string())),
+                None => {
+                    log::warn!("Unknown music stem in manifest: {}", name);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+struct StemVoice {
+    stem: MusicStem,
+    uuid: Uuid,
+    /// Scales the target gain, from 0 to 1.
+    level: f32,
+    gain: f32,
+    /// Gain last sent to the engine, `None` forces the next update to send it.
+    applied_gain: Option<f32>,
+}
+
+/// Plays the layered music stems and crossfades them with their levels and whether the player is
+/// exploring or standing still.
+pub struct MusicManager {
+    spatial_sound_manager: SpatialSoundManager,
+    voices: Vec<StemVoice>,
+    volume_db: f32,
+    crossfade_time: f32,
+    /// 0 is standing still, 1 is exploring, eased so short stops don't drop the percussion.
+    exploring: f32,
+    last_player_pos: Option<Vec3>,
+}
+
+impl MusicManager {
+    /// Starts the stems listed in [`MUSIC_MANIFEST_PATH`] silently, a missing manifest leaves the
+    /// music off.
+    pub fn new(spatial_sound_manager: SpatialSoundManager, volume_db: f32) -> Self {
+        let manifest_path = format!("{}{}", crate::util::get_project_root(), MUSIC_MANIFEST_PATH);
+        let stems = match std::fs::read_to_string(&manifest_path) {
+            Ok(content) => parse_manifest(&content),
+            Err(_) => {
+                log::info!("No music manifest at {}, music is off", manifest_path);
+                Vec::new()
+            }
+        };
+
+        let mut voices = Vec::new();
+        for (stem, path) in stems {
+            match spatial_sound_manager.add_looping_non_spatial_file(
+                &path,
+                volume_db + 20.0 * MIN_GAIN.log10(),
+                AudioBus::Music,
+            ) {
+                Ok(uuid) => voices.push(StemVoice {
+                    stem,
+                    uuid,
+                    level: 1.0,
+                    gain: 0.0,
+                    applied_gain: Some(0.0),
+                }),
+                Err(e) => log::error!("Failed to load music stem {}: {}", path, e),
+            }
+        }
+
+        Self {
+            spatial_sound_manager,
+            voices,
+            volume_db,
+            crossfade_time: DEFAULT_CROSSFADE_TIME,
+            exploring: 0.0,
+            last_player_pos: None,
+        }
+    }
+
+    pub fn volume_db(&self) -> f32 {
+        self.volume_db
+    }
+
+    pub fn set_volume_db(&mut self, volume_db: f32) {
+        self.volume_db = volume_db;
+        for voice in &mut self.voices {
+            voice.applied_gain = None;
+        }
+    }
+
+    pub fn crossfade_time(&self) -> f32 {
+        self.crossfade_time
+    }
+
+    pub fn set_crossfade_time(&mut self, seconds: f32) {
+        self.crossfade_time = seconds.max(0.0);
+    }
+
+    /// Scales the mix of `stem`, the gain eases towards the new level over the crossfade time.
+    pub fn set_stem_level(&mut self, stem: MusicStem, level: f32) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.stem == stem) {
+            voice.level = level.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Current gain of each playing stem, from 0 to 1.
+    pub fn stem_gains(&self) -> impl Iterator<Item = (MusicStem, f32)> + '_ {
+        self.voices.iter().map(|voice| (voice.stem, voice.gain))
+    }
+
+    /// Eases the stem volumes towards the mix of their levels and the player state, call once per
+    /// frame.
+    pub fn update(&mut self, player_pos: Vec3, delta_time: f32) -> Result<()> {
+        profile_scope!("music_update");
+        if delta_time <= 0.0 {
+            return Ok(());
+        }
+
+        let horizontal_speed = self.last_player_pos.map_or(0.0, |last| {
+            (player_pos - last).with_y(0.0).length() / delta_time
+        });
+        self.last_player_pos = Some(player_pos);
+        let is_exploring = if horizontal_speed > EXPLORING_SPEED {
+            1.0
+        } else {
+            0.0
+        };
+        let t = 1.0 - (-delta_time / EXPLORING_SMOOTHING).exp();
+        self.exploring += (is_exploring - self.exploring) * t;
+
+        let t = if self.crossfade_time > 0.0 {
+            1.0 - (-delta_time / self.crossfade_time).exp()
+        } else {
+            1.0
+        };
+        for voice in &mut self.voices {
+            let target = voice.level * voice.stem.target_gain(self.exploring);
+            voice.gain += (target - voice.gain) * t;
+            // volume updates cross to the audio thread, skip inaudible changes
+            if voice
+                .applied_gain
+                .is_some_and(|applied| (voice.gain - applied).abs() < 1e-3)
+            {
+                continue;
+            }
+            voice.applied_gain = Some(voice.gain);
+            self.spatial_sound_manager.set_non_spatial_volume(
+                voice.uuid,
+                self.volume_db + 20.0 * voice.gain.max(MIN_GAIN).log10(),
+            )?;
+        }
+        Ok(())
+    }
+}
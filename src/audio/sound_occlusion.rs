@@ -0,0 +1,234 @@
+use crate::audio::SpatialSoundManager;
+use crate::constants::MAX_TERRAIN_QUERIES;
+use crate::tracer::{RayHit, Tracer, VoxelMaterial};
+use crate::util::profile_scope;
+use anyhow::Result;
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Rays cast towards each source: at it, above it and to either side of it.
+const RAYS_PER_SOURCE: usize = 4;
+
+/// Seconds the occlusion of a source takes to get most of the way to a new measurement.
+const OCCLUSION_SMOOTHING: f32 = 0.3;
+
+/// Occlusion changes smaller than this in dB aren't sent to the engine.
+const OCCLUSION_STEP_DB: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SoundOcclusionDesc {
+    pub is_enabled: bool,
+    /// Gain of a source completely hidden behind the terrain, in dB.
+    pub occluded_gain_db: f32,
+    /// How much of a ray the leaves block, the terrain and the trunks block all of it.
+    pub foliage_opacity: f32,
+    /// Hits this close to a source are the foliage it sits in, in world units.
+    pub source_radius: f32,
+    /// How far the side rays aim from the source, in world units.
+    pub ray_spread: f32,
+    /// Seconds between two rounds of rays.
+    pub update_interval: f32,
+}
+
+impl Default for SoundOcclusionDesc {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            occluded_gain_db: -15.0,
+            foliage_opacity: 0.35,
+            source_radius: 0.06,
+            ray_spread: 0.05,
+            update_interval: 0.2,
+        }
+    }
+}
+
+impl SoundOcclusionDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Enabled");
+        ui.add(
+            egui::Slider::new(&mut self.occluded_gain_db, -40.0..=0.0).text("Occluded Gain (dB)"),
+        );
+        ui.add(egui::Slider::new(&mut self.foliage_opacity, 0.0..=1.0).text("Foliage Opacity"));
+        ui.add(egui::Slider::new(&mut self.source_radius, 0.0..=0.3).text("Source Radius"));
+        ui.add(egui::Slider::new(&mut self.ray_spread, 0.0..=0.3).text("Ray Spread"));
+        ui.add(
+            egui::Slider::new(&mut self.update_interval, 0.05..=1.0).text("Update Interval (s)"),
+        );
+    }
+}
+
+/// A round of rays cast by a frame that isn't done yet.
+struct PendingRays {
+    sources: Vec<(Uuid, Vec3)>,
+    listener_pos: Vec3,
+    /// From the listener to the aimed point, one per ray.
+    lengths: Vec<f32>,
+}
+
+/// Muffles the looping spatial sources the scene hides from the listener.
+///
+/// A few rays are cast through the contree from the listener towards each source holding a
+/// voice, the share of them the hills and foliage block sets how far the source is turned down.
+/// The rays ride along with a frame and are read back once it's done, the last measurement holds
+/// until then.
+pub struct SoundOcclusion {
+    pub desc: SoundOcclusionDesc,
+    spatial_sound_manager: SpatialSoundManager,
+    /// From 0 in the open to 1 completely hidden, eased towards `targets`.
+    occlusions: HashMap<Uuid, f32>,
+    /// The occlusion the last rays measured.
+    targets: HashMap<Uuid, f32>,
+    /// Occlusion last sent to the engine, in dB.
+    applied_db: HashMap<Uuid, f32>,
+    pending_rays: Option<PendingRays>,
+    time_since_rays: f32,
+}
+
+impl SoundOcclusion {
+    pub fn new(desc: SoundOcclusionDesc, spatial_sound_manager: SpatialSoundManager) -> Self {
+        Self {
+            desc,
+            spatial_sound_manager,
+            occlusions: HashMap::new(),
+            targets: HashMap::new(),
+            applied_db: HashMap::new(),
+            pending_rays: None,
+            time_since_rays: f32::INFINITY,
+        }
+    }
+
+    /// Takes the hits of the last round once its frame is done, queues a new round once
+    /// `update_interval` passed and eases the volumes towards the latest measurement.
+    pub fn update(
+        &mut self,
+        tracer: &mut Tracer,
+        listener_pos: Vec3,
+        delta_time: f32,
+    ) -> Result<()> {
+        profile_scope!("sound_occlusion_update");
+        if !self.desc.is_enabled {
+            self.clear();
+            return Ok(());
+        }
+
+        // a round queued before the occlusion was turned off is still taken, and dropped
+        if let Some(hits) = tracer.take_queued_raycast_hits()? {
+            if let Some(pending_rays) = self.pending_rays.take() {
+                self.apply_hits(pending_rays, &hits);
+            }
+        }
+
+        self.time_since_rays += delta_time;
+        if self.time_since_rays >= self.desc.update_interval && self.pending_rays.is_none() {
+            self.queue_rays(tracer, listener_pos)?;
+        }
+
+        let t = 1.0 - (-delta_time / OCCLUSION_SMOOTHING).exp();
+        for (uuid, target) in &self.targets {
+            let occlusion = self.occlusions.entry(*uuid).or_insert(0.0);
+            *occlusion += (target - *occlusion) * t;
+
+            let occlusion_db = *occlusion * self.desc.occluded_gain_db;
+            let applied_db = self.applied_db.entry(*uuid).or_insert(0.0);
+            if (occlusion_db - *applied_db).abs() >= OCCLUSION_STEP_DB {
+                *applied_db = occlusion_db;
+                self.spatial_sound_manager
+                    .set_occlusion_db(*uuid, occlusion_db);
+            }
+        }
+        Ok(())
+    }
+
+    fn queue_rays(&mut self, tracer: &mut Tracer, listener_pos: Vec3) -> Result<()> {
+        let mut sources = self.spatial_sound_manager.playing_spatial_sources();
+        sources.truncate(MAX_TERRAIN_QUERIES as usize / RAYS_PER_SOURCE);
+        let mut rays = Vec::with_capacity(sources.len() * RAYS_PER_SOURCE);
+        let mut lengths = Vec::with_capacity(rays.capacity());
+        for (_, position) in &sources {
+            let to_source = *position - listener_pos;
+            let side = to_source.cross(Vec3::Y).normalize_or_zero() * self.desc.ray_spread;
+            let up = Vec3::Y * self.desc.ray_spread;
+            for target in [
+                *position,
+                *position + up,
+                *position + side,
+                *position - side,
+            ] {
+                let direction = target - listener_pos;
+                rays.push((listener_pos, direction));
+                lengths.push(direction.length());
+            }
+        }
+        if tracer.queue_raycast_scene_batch(&rays)? {
+            self.time_since_rays = 0.0;
+            self.pending_rays = Some(PendingRays {
+                sources,
+                listener_pos,
+                lengths,
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_hits(&mut self, pending_rays: PendingRays, hits: &[Option<RayHit>]) {
+        let PendingRays {
+            sources,
+            listener_pos,
+            lengths,
+        } = pending_rays;
+
+        // sources that lost their voice play at full volume again once they win it back
+        let playing = sources
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .collect::<HashSet<_>>();
+        self.targets.retain(|uuid, _| playing.contains(uuid));
+        for (uuid, _) in self
+            .applied_db
+            .iter()
+            .filter(|(uuid, _)| !playing.contains(uuid))
+        {
+            self.spatial_sound_manager.set_occlusion_db(*uuid, 0.0);
+        }
+        self.occlusions.retain(|uuid, _| playing.contains(uuid));
+        self.applied_db.retain(|uuid, _| playing.contains(uuid));
+
+        for (i, (uuid, _)) in sources.iter().enumerate() {
+            let range = i * RAYS_PER_SOURCE..(i + 1) * RAYS_PER_SOURCE;
+            let blocked: f32 = hits[range.clone()]
+                .iter()
+                .zip(&lengths[range])
+                .map(|(hit, length)| self.ray_opacity(*hit, listener_pos, *length))
+                .sum();
+            self.targets.insert(*uuid, blocked / RAYS_PER_SOURCE as f32);
+        }
+    }
+
+    /// How much of the sound along a ray of `length` the first voxel it hit blocks.
+    fn ray_opacity(&self, hit: Option<RayHit>, listener_pos: Vec3, length: f32) -> f32 {
+        let Some(hit) = hit else {
+            return 0.0;
+        };
+        if hit.position.distance(listener_pos) >= length - self.desc.source_radius {
+            return 0.0;
+        }
+        match hit.material {
+            Some(VoxelMaterial::Leaf) => self.desc.foliage_opacity,
+            _ => 1.0,
+        }
+    }
+
+    /// Lets every source play in the open again.
+    fn clear(&mut self) {
+        for uuid in self.applied_db.keys() {
+            self.spatial_sound_manager.set_occlusion_db(*uuid, 0.0);
+        }
+        self.occlusions.clear();
+        self.targets.clear();
+        self.applied_db.clear();
+        self.pending_rays = None;
+        self.time_since_rays = f32::INFINITY;
+    }
+}
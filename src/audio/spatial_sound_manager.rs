@@ -1,8 +1,11 @@
 use crate::audio::audio_clip_cache::AudioClipCache;
+use crate::audio::{AudioBus, Mixer, VoiceAction, VoiceManager, DEFAULT_MAX_VOICES};
 use crate::gameplay::camera::vectors::CameraVectors;
+use crate::util::profile_scope;
 use anyhow::Result;
-use glam::Vec3;
+use glam::{Quat, Vec3};
 use petalsonic::{
+    audio_data::PetalSonicAudioData,
     config::PetalSonicWorldDesc,
     engine::PetalSonicEngine,
     math::{Pose, Quat as PetalQuat, Vec3 as PetalVec3},
@@ -19,6 +22,13 @@ use uuid::Uuid;
 struct SourceInfo {
     source_id: SourceId,
     volume: f32,
+    bus: AudioBus,
+    /// Spatial sources get their volume through the voice budget.
+    is_spatial: bool,
+    /// One-shots play out at the bus gain they started with.
+    is_looping: bool,
+    /// How much the scene between the listener and the source muffles it, 0 in the open.
+    occlusion_db: f32,
 }
 
 /// Spatial sound manager using PetalSonic
@@ -34,29 +44,49 @@ pub struct SpatialSoundManager {
 
     // Cache listener state to avoid unnecessary updates
     listener_state: Arc<Mutex<ListenerState>>,
+
+    // Budget of concurrently playing looping spatial sources
+    voice_manager: Arc<Mutex<VoiceManager>>,
+
+    // Bus gains applied on top of every source's volume
+    mixer: Arc<Mutex<Mixer>>,
 }
 
+/// Default time constant of the listener rotation smoothing, in seconds.
+const DEFAULT_LISTENER_ROTATION_SMOOTHING: f32 = 0.06;
+
 #[derive(Clone, Debug)]
 struct ListenerState {
     position: Vec3,
-    up: Vec3,
-    front: Vec3,
-    right: Vec3,
+    /// Smoothed listener rotation, trails the camera rotation.
+    rotation: Quat,
+    /// Time constant of the rotation smoothing in seconds, 0 follows the camera exactly.
+    rotation_smoothing: f32,
+    is_initialized: bool,
 }
 
 impl Default for ListenerState {
     fn default() -> Self {
-        let mut dummy_vectors = CameraVectors::new();
-        dummy_vectors.update(0.0, 0.0);
         Self {
             position: Vec3::ZERO,
-            up: dummy_vectors.up,
-            front: dummy_vectors.front,
-            right: dummy_vectors.right,
+            rotation: Quat::IDENTITY,
+            rotation_smoothing: DEFAULT_LISTENER_ROTATION_SMOOTHING,
+            is_initialized: false,
         }
     }
 }
 
+/// Converts the camera basis to the listener rotation.
+fn listener_rotation(camera_vectors: &CameraVectors) -> Quat {
+    // glam uses right-handed coordinates where +X=right, +Y=up, +Z=backward (so -Z=forward)
+    let rotation_matrix = glam::Mat3::from_cols(
+        camera_vectors.right,
+        camera_vectors.up,
+        -camera_vectors.front, // Negate because glam's +Z points backward
+    );
+    Quat::from_mat3(&rotation_matrix).normalize()
+}
+
 impl SpatialSoundManager {
     pub fn new(frame_window_size: usize) -> Result<Self> {
         let sample_rate = 48000;
@@ -99,6 +129,8 @@ impl SpatialSoundManager {
             clip_cache,
             uuid_to_source: Arc::new(Mutex::new(HashMap::new())),
             listener_state: Arc::new(Mutex::new(ListenerState::default())),
+            voice_manager: Arc::new(Mutex::new(VoiceManager::new(DEFAULT_MAX_VOICES))),
+            mixer: Arc::new(Mutex::new(Mixer::default())),
         })
     }
 
@@ -108,6 +140,7 @@ impl SpatialSoundManager {
         volume: f32,
         position: Vec3,
         loop_mode: LoopMode,
+        bus: AudioBus,
     ) -> Result<Uuid> {
         // Get audio data from cache instead of loading from disk
         let audio_data = self
@@ -124,7 +157,7 @@ impl SpatialSoundManager {
         // Register in PetalSonic world with spatial configuration
         let source_id = self.world.register_audio(
             audio_data,
-            SourceConfig::spatial_with_volume_db(petal_pose, volume),
+            SourceConfig::spatial_with_volume_db(petal_pose, volume + self.bus_gain_db(bus)),
         )?;
 
         // Start playback
@@ -132,10 +165,17 @@ impl SpatialSoundManager {
 
         // Generate UUID and map to SourceId with metadata
         let uuid = Uuid::new_v4();
-        self.uuid_to_source
-            .lock()
-            .unwrap()
-            .insert(uuid, SourceInfo { source_id, volume });
+        self.uuid_to_source.lock().unwrap().insert(
+            uuid,
+            SourceInfo {
+                source_id,
+                volume,
+                bus,
+                is_spatial: true,
+                is_looping: matches!(loop_mode, LoopMode::Infinite),
+                occlusion_db: 0.0,
+            },
+        );
 
         Ok(uuid)
     }
@@ -151,8 +191,13 @@ impl SpatialSoundManager {
         volume_db: f32,
         position: Vec3,
         shuffle_phase: bool,
+        bus: AudioBus,
     ) -> Result<Uuid> {
-        let uuid = self.add_source(path, volume_db, position, LoopMode::Infinite)?;
+        let uuid = self.add_source(path, volume_db, position, LoopMode::Infinite, bus)?;
+        self.voice_manager
+            .lock()
+            .unwrap()
+            .add(uuid, position, volume_db);
 
         // Apply random phase offset if shuffle_phase is enabled
         if shuffle_phase {
@@ -185,8 +230,13 @@ impl SpatialSoundManager {
         base_volume_db + gain_db
     }
 
+    /// Whether `path` (relative to the project root) was preloaded from `assets/sfx`.
+    pub fn has_clip(&self, path: &str) -> bool {
+        self.clip_cache.get(path).is_some()
+    }
+
     /// Add a non-spatial audio source (e.g., for UI sounds or player footsteps)
-    pub fn add_non_spatial_source(&self, path: &str, volume: f32) -> Result<Uuid> {
+    pub fn add_non_spatial_source(&self, path: &str, volume: f32, bus: AudioBus) -> Result<Uuid> {
         // Get audio data from cache instead of loading from disk
         let audio_data = self
             .clip_cache
@@ -194,68 +244,201 @@ impl SpatialSoundManager {
             .ok_or_else(|| anyhow::anyhow!("Audio clip not found in cache: {}", path))?;
 
         // Register in PetalSonic world with non-spatial configuration and volume
-        let source_id = self
-            .world
-            .register_audio(audio_data, SourceConfig::non_spatial_with_volume_db(volume))?;
+        let source_id = self.world.register_audio(
+            audio_data,
+            SourceConfig::non_spatial_with_volume_db(volume + self.bus_gain_db(bus)),
+        )?;
 
         // Start playback with one-shot mode
         self.world.play(source_id, LoopMode::Once)?;
 
         // Generate UUID and map to SourceId with metadata
         let uuid = Uuid::new_v4();
-        self.uuid_to_source
+        self.uuid_to_source.lock().unwrap().insert(
+            uuid,
+            SourceInfo {
+                source_id,
+                volume,
+                bus,
+                is_spatial: false,
+                is_looping: false,
+                occlusion_db: 0.0,
+            },
+        );
+
+        Ok(uuid)
+    }
+
+    /// Add a looping non-spatial source from a file outside the clip cache, e.g. a music stem.
+    ///
+    /// `path` is relative to the project root.
+    pub fn add_looping_non_spatial_file(
+        &self,
+        path: &str,
+        volume_db: f32,
+        bus: AudioBus,
+    ) -> Result<Uuid> {
+        let full_path = format!("{}{}", crate::util::get_project_root(), path);
+        let audio_data = PetalSonicAudioData::from_path(&full_path)?;
+
+        let source_id = self.world.register_audio(
+            audio_data,
+            SourceConfig::non_spatial_with_volume_db(volume_db + self.bus_gain_db(bus)),
+        )?;
+        self.world.play(source_id, LoopMode::Infinite)?;
+
+        let uuid = Uuid::new_v4();
+        self.uuid_to_source.lock().unwrap().insert(
+            uuid,
+            SourceInfo {
+                source_id,
+                volume: volume_db,
+                bus,
+                is_spatial: false,
+                is_looping: true,
+                occlusion_db: 0.0,
+            },
+        );
+
+        Ok(uuid)
+    }
+
+    pub fn set_non_spatial_volume(&self, source_uuid: Uuid, volume_db: f32) -> Result<()> {
+        let mut uuid_map = self.uuid_to_source.lock().unwrap();
+        if let Some(source_info) = uuid_map.get_mut(&source_uuid) {
+            source_info.volume = volume_db;
+            self.world.update_source_config(
+                source_info.source_id,
+                SourceConfig::non_spatial_with_volume_db(
+                    self.output_volume_db(source_info, volume_db),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn mixer(&self) -> Mixer {
+        *self.mixer.lock().unwrap()
+    }
+
+    /// Applies the bus gains to the looping sources, one-shots already playing keep the gain they
+    /// started with.
+    pub fn set_mixer(&self, mixer: Mixer) -> Result<()> {
+        *self.mixer.lock().unwrap() = mixer;
+        for source_info in self.uuid_to_source.lock().unwrap().values() {
+            if !source_info.is_looping || source_info.is_spatial {
+                continue;
+            }
+            self.world.update_source_config(
+                source_info.source_id,
+                SourceConfig::non_spatial_with_volume_db(
+                    self.output_volume_db(source_info, source_info.volume),
+                ),
+            )?;
+        }
+        // the spatial ones are resent along with their fades
+        self.voice_manager
             .lock()
             .unwrap()
-            .insert(uuid, SourceInfo { source_id, volume });
+            .mark_all_volumes_changed();
+        Ok(())
+    }
 
-        Ok(uuid)
+    /// Muffles a looping spatial source by `occlusion_db`, applied on the next
+    /// [`Self::update_voices`].
+    pub fn set_occlusion_db(&self, source_uuid: Uuid, occlusion_db: f32) {
+        if let Some(source_info) = self.uuid_to_source.lock().unwrap().get_mut(&source_uuid) {
+            source_info.occlusion_db = occlusion_db;
+            self.voice_manager
+                .lock()
+                .unwrap()
+                .mark_volume_changed(&source_uuid);
+        }
+    }
+
+    /// The uuids and positions of the looping spatial sources holding a voice.
+    pub fn playing_spatial_sources(&self) -> Vec<(Uuid, Vec3)> {
+        self.voice_manager
+            .lock()
+            .unwrap()
+            .playing_voices()
+            .collect()
+    }
+
+    fn bus_gain_db(&self, bus: AudioBus) -> f32 {
+        self.mixer.lock().unwrap().output_gain_db(bus)
+    }
+
+    /// `volume_db` with the bus gain and the occlusion of the source applied.
+    fn output_volume_db(&self, source_info: &SourceInfo, volume_db: f32) -> f32 {
+        volume_db + self.bus_gain_db(source_info.bus) + source_info.occlusion_db
+    }
+
+    /// Sets the full volume of a looping spatial source, the voice budget applies it on the next
+    /// [`Self::update_voices`] along with the source's fade.
+    pub fn set_spatial_volume(&self, source_uuid: Uuid, volume_db: f32) {
+        if let Some(source_info) = self.uuid_to_source.lock().unwrap().get_mut(&source_uuid) {
+            source_info.volume = volume_db;
+        }
+        self.voice_manager
+            .lock()
+            .unwrap()
+            .set_volume_db(&source_uuid, volume_db);
+    }
+
+    /// Time constant of the listener rotation smoothing in seconds, 0 disables smoothing.
+    pub fn listener_rotation_smoothing(&self) -> f32 {
+        self.listener_state.lock().unwrap().rotation_smoothing
+    }
+
+    pub fn set_listener_rotation_smoothing(&self, seconds: f32) {
+        self.listener_state.lock().unwrap().rotation_smoothing = seconds.max(0.0);
     }
 
-    pub fn update_player_pos(
+    /// Moves the listener to the player and turns it towards where the camera looks.
+    ///
+    /// The rotation eases towards the camera's with an exponential decay, so fast mouse flicks
+    /// don't make emitters jump between the ears.
+    pub fn update_listener(
         &self,
         player_pos: Vec3,
         camera_vectors: &CameraVectors,
+        delta_time: f32,
     ) -> Result<()> {
+        profile_scope!("update_listener");
         let mut listener_state = self.listener_state.lock().unwrap();
+        let target_rotation = listener_rotation(camera_vectors);
 
         // Check if anything changed
-        if listener_state.position == player_pos
-            && listener_state.up == camera_vectors.up
-            && listener_state.front == camera_vectors.front
-            && listener_state.right == camera_vectors.right
+        if listener_state.is_initialized
+            && listener_state.position == player_pos
+            && listener_state.rotation.abs_diff_eq(target_rotation, 1e-5)
         {
             return Ok(());
         }
 
+        let rotation = if listener_state.is_initialized && listener_state.rotation_smoothing > 0.0 {
+            // frame rate independent exponential decay towards the target
+            let t = 1.0 - (-delta_time / listener_state.rotation_smoothing).exp();
+            listener_state
+                .rotation
+                .slerp(target_rotation, t)
+                .normalize()
+        } else {
+            target_rotation
+        };
+
         // Update cached state
         listener_state.position = player_pos;
-        listener_state.up = camera_vectors.up;
-        listener_state.front = camera_vectors.front;
-        listener_state.right = camera_vectors.right;
-
-        // Convert camera vectors to quaternion rotation using the full camera basis
-        // Build a rotation matrix from the camera's right, up, and front vectors
-        // glam uses right-handed coordinates where +X=right, +Y=up, +Z=backward (so -Z=forward)
-        let rotation_matrix = glam::Mat3::from_cols(
-            camera_vectors.right,
-            camera_vectors.up,
-            -camera_vectors.front, // Negate because glam's +Z points backward
-        );
-        let rotation_glam = glam::Quat::from_mat3(&rotation_matrix);
-
-        // Convert to PetalQuat
-        let rotation = PetalQuat::from_xyzw(
-            rotation_glam.x,
-            rotation_glam.y,
-            rotation_glam.z,
-            rotation_glam.w,
-        );
+        listener_state.rotation = rotation;
+        listener_state.is_initialized = true;
 
-        // Convert position to PetalVec3
+        // Convert to PetalSonic types
+        let petal_rotation = PetalQuat::from_xyzw(rotation.x, rotation.y, rotation.z, rotation.w);
         let petal_pos = PetalVec3::new(player_pos.x, player_pos.y, player_pos.z);
 
         // Update listener pose in PetalSonic
-        let pose = Pose::new(petal_pos, rotation);
+        let pose = Pose::new(petal_pos, petal_rotation);
         self.world.set_listener_pose(pose);
 
         Ok(())
@@ -266,6 +449,11 @@ impl SpatialSoundManager {
         let uuid_map = self.uuid_to_source.lock().unwrap();
 
         if let Some(source_info) = uuid_map.get(&source_uuid) {
+            self.voice_manager
+                .lock()
+                .unwrap()
+                .set_position(&source_uuid, target_pos);
+
             // Convert position to PetalVec3
             let petal_pose = Pose::new(
                 PetalVec3::new(target_pos.x, target_pos.y, target_pos.z),
@@ -275,7 +463,10 @@ impl SpatialSoundManager {
             // Update the source configuration with new position, preserving volume
             self.world.update_source_config(
                 source_info.source_id,
-                SourceConfig::spatial_with_volume_db(petal_pose, source_info.volume),
+                SourceConfig::spatial_with_volume_db(
+                    petal_pose,
+                    self.output_volume_db(source_info, source_info.volume),
+                ),
             )?;
         }
 
@@ -284,6 +475,7 @@ impl SpatialSoundManager {
 
     #[allow(dead_code)]
     pub fn remove_source(&self, id: Uuid) {
+        self.voice_manager.lock().unwrap().remove(&id);
         if let Some(source_info) = self.uuid_to_source.lock().unwrap().remove(&id) {
             // Stop the source and remove from world
             let _ = self.world.stop(source_info.source_id);
@@ -291,6 +483,78 @@ impl SpatialSoundManager {
         }
     }
 
+    /// Re-ranks the looping spatial sources against the voice budget and advances their fades,
+    /// call once per frame after [`Self::update_listener`].
+    pub fn update_voices(&self, delta_time: f32) -> Result<()> {
+        profile_scope!("update_voices");
+        let listener_pos = self.listener_state.lock().unwrap().position;
+        let actions = self
+            .voice_manager
+            .lock()
+            .unwrap()
+            .update(listener_pos, delta_time);
+        let uuid_map = self.uuid_to_source.lock().unwrap();
+
+        for action in actions {
+            match action {
+                VoiceAction::Play(uuid) => {
+                    if let Some(source_info) = uuid_map.get(&uuid) {
+                        self.world.play(source_info.source_id, LoopMode::Infinite)?;
+                        // restarting in phase with the other copies of the clip would be audible
+                        let random_phase = rand::rng().random_range(0.0..1.0);
+                        self.world
+                            .seek(source_info.source_id, random_phase)
+                            .map_err(|e| {
+                                anyhow::anyhow!("Failed to seek to random phase: {}", e)
+                            })?;
+                    }
+                }
+                VoiceAction::SetVolume {
+                    uuid,
+                    position,
+                    volume_db,
+                } => {
+                    if let Some(source_info) = uuid_map.get(&uuid) {
+                        let petal_pose = Pose::new(
+                            PetalVec3::new(position.x, position.y, position.z),
+                            PetalQuat::IDENTITY,
+                        );
+                        self.world.update_source_config(
+                            source_info.source_id,
+                            SourceConfig::spatial_with_volume_db(
+                                petal_pose,
+                                self.output_volume_db(source_info, volume_db),
+                            ),
+                        )?;
+                    }
+                }
+                VoiceAction::Stop(uuid) => {
+                    if let Some(source_info) = uuid_map.get(&uuid) {
+                        let _ = self.world.stop(source_info.source_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns (playing, total) counts of the budgeted looping spatial sources.
+    pub fn voice_counts(&self) -> (usize, usize) {
+        let voice_manager = self.voice_manager.lock().unwrap();
+        (voice_manager.playing_count(), voice_manager.total_count())
+    }
+
+    pub fn max_voices(&self) -> usize {
+        self.voice_manager.lock().unwrap().max_voices()
+    }
+
+    pub fn set_max_voices(&self, max_voices: usize) {
+        self.voice_manager
+            .lock()
+            .unwrap()
+            .set_max_voices(max_voices);
+    }
+
     /// Poll events from the engine (e.g., for cleanup of completed sources)
     #[allow(dead_code)]
     pub fn poll_events(&self) -> Vec<petalsonic::PetalSonicEvent> {
@@ -307,6 +571,8 @@ impl Clone for SpatialSoundManager {
             clip_cache: self.clip_cache.clone(),
             uuid_to_source: self.uuid_to_source.clone(),
             listener_state: self.listener_state.clone(),
+            voice_manager: self.voice_manager.clone(),
+            mixer: self.mixer.clone(),
         }
     }
 }
@@ -1,13 +1,22 @@
 use crate::audio::cluster_positions;
-use crate::audio::SpatialSoundManager;
+use crate::audio::{AudioBus, SpatialSoundManager};
+use crate::gameplay::WindField;
 use anyhow::Result;
-use glam::Vec3;
+use glam::{Vec3, Vec3Swizzles};
 use log::{debug, warn};
 use std::collections::HashMap;
 use uuid::Uuid;
 
 const TREE_LOOP_PATH: &str = "assets/sfx/tree_sound_48k.wav";
-const DEFAULT_BASE_VOLUME_DB: f32 = -16.0;
+
+/// The rustling gain in still air, the wind strength adds to it.
+const RUSTLE_CALM_GAIN: f32 = 0.2;
+/// The share of the rustling left between the gusts.
+const RUSTLE_LULL_GAIN: f32 = 0.5;
+/// Smaller changes of the rustling volume aren't sent to the engine.
+const RUSTLE_VOLUME_STEP_DB: f32 = 0.5;
+/// Below this gain an emitter is played at this level instead of a log of zero.
+const MIN_GAIN: f32 = 1e-3;
 
 /// Metadata tracked for each managed tree audio source.
 #[allow(dead_code)]
@@ -17,10 +26,12 @@ pub struct ManagedTreeAudioSource {
     pub tree_id: u32,
     pub position: Vec3,
     pub cluster_size: u32,
+    /// What the wind last added to the clustered volume, in dB.
+    pub wind_gain_db: f32,
 }
 
-/// Keeps track of all looping tree ambience sources so we can later
-/// drive them with wind simulations, recluster them, etc.
+/// Keeps track of all looping tree ambience sources and swells their rustling with the gusts of
+/// the wind field.
 pub struct TreeAudioManager {
     spatial_sound_manager: SpatialSoundManager,
     base_volume_db: f32,
@@ -29,10 +40,11 @@ pub struct TreeAudioManager {
 }
 
 impl TreeAudioManager {
-    pub fn new(spatial_sound_manager: SpatialSoundManager) -> Self {
+    /// `base_volume_db` is the volume of a single tree, clustered sources are louder.
+    pub fn new(spatial_sound_manager: SpatialSoundManager, base_volume_db: f32) -> Self {
         Self {
             spatial_sound_manager,
-            base_volume_db: DEFAULT_BASE_VOLUME_DB,
+            base_volume_db,
             sources_by_tree: HashMap::new(),
             sources: HashMap::new(),
         }
@@ -134,6 +146,27 @@ impl TreeAudioManager {
         self.sources.clear();
     }
 
+    /// Scales the rustling of every emitter with `wind_strength` and the gust of `wind_field`
+    /// where it stands, call once per frame.
+    ///
+    /// The spatializer attenuates the emitters with their distance to the listener, the voice
+    /// budget keeps the loudest of them playing, so near trees in a gust win over far ones.
+    pub fn update_wind(&mut self, wind_field: &WindField, wind_strength: f32) {
+        let strength_gain = RUSTLE_CALM_GAIN + wind_strength.clamp(0.0, 1.0);
+        for source in self.sources.values_mut() {
+            let gust = wind_field.gust_at(source.position.xz());
+            let gain = strength_gain * (RUSTLE_LULL_GAIN + (1.0 - RUSTLE_LULL_GAIN) * gust);
+            let wind_gain_db = 20.0 * gain.max(MIN_GAIN).log10();
+            if (wind_gain_db - source.wind_gain_db).abs() < RUSTLE_VOLUME_STEP_DB {
+                continue;
+            }
+            source.wind_gain_db = wind_gain_db;
+            let volume_db = Self::clustered_volume_db(self.base_volume_db, source.cluster_size);
+            self.spatial_sound_manager
+                .set_spatial_volume(source.uuid, volume_db + wind_gain_db);
+        }
+    }
+
     /// Iterate all tracked sources.
     #[allow(dead_code)]
     pub fn sources(&self) -> impl Iterator<Item = &ManagedTreeAudioSource> {
@@ -159,6 +192,7 @@ impl TreeAudioManager {
             volume_db,
             position,
             shuffle_phase,
+            AudioBus::Ambient,
         )?;
 
         self.register_source(tree_id, uuid, position, cluster_size);
@@ -171,6 +205,7 @@ impl TreeAudioManager {
             tree_id,
             position,
             cluster_size,
+            wind_gain_db: 0.0,
         };
         self.sources_by_tree.entry(tree_id).or_default().push(uuid);
         self.sources.insert(uuid, entry);
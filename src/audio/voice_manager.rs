@@ -0,0 +1,190 @@
+use crate::util::profile_scope;
+use glam::Vec3;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// How many managed sources may play at once.
+pub const DEFAULT_MAX_VOICES: usize = 24;
+
+/// Seconds a voice takes to fade in or out when it gains or loses its slot.
+const VOICE_FADE_TIME: f32 = 0.5;
+
+/// Below this fade level a fading voice counts as silent and gets stopped.
+const MIN_FADE: f32 = 1e-3;
+
+/// A change the engine has to apply to a source after [`VoiceManager::update`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceAction {
+    /// Restart a source that won a voice slot back.
+    Play(Uuid),
+    /// Set the effective volume of a fading source.
+    SetVolume {
+        uuid: Uuid,
+        position: Vec3,
+        volume_db: f32,
+    },
+    /// Stop a source that has fully faded out.
+    Stop(Uuid),
+}
+
+#[derive(Debug, Clone)]
+struct Voice {
+    position: Vec3,
+    volume_db: f32,
+    /// 0 is silent, 1 is full volume.
+    fade: f32,
+    is_playing: bool,
+    /// The volume changed since the engine was last told.
+    is_volume_changed: bool,
+}
+
+impl Voice {
+    /// Rough perceived loudness at the listener, only used to rank voices.
+    fn score(&self, listener_pos: Vec3) -> f32 {
+        let amplitude = 10f32.powf(self.volume_db / 20.0);
+        amplitude / (1.0 + self.position.distance_squared(listener_pos))
+    }
+}
+
+/// Keeps the number of playing looping sources within a budget.
+///
+/// Voices are ranked by their volume attenuated with the distance to the listener, the loudest
+/// ones keep playing and the rest fade out and stop until they rank high enough again.
+pub struct VoiceManager {
+    max_voices: usize,
+    voices: HashMap<Uuid, Voice>,
+}
+
+impl VoiceManager {
+    pub fn new(max_voices: usize) -> Self {
+        Self {
+            max_voices,
+            voices: HashMap::new(),
+        }
+    }
+
+    pub fn max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices;
+    }
+
+    /// Tracks a source that has just started playing at full volume.
+    pub fn add(&mut self, uuid: Uuid, position: Vec3, volume_db: f32) {
+        self.voices.insert(
+            uuid,
+            Voice {
+                position,
+                volume_db,
+                fade: 1.0,
+                is_playing: true,
+                is_volume_changed: false,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, uuid: &Uuid) {
+        self.voices.remove(uuid);
+    }
+
+    pub fn set_position(&mut self, uuid: &Uuid, position: Vec3) {
+        if let Some(voice) = self.voices.get_mut(uuid) {
+            voice.position = position;
+        }
+    }
+
+    /// Sets the full volume of a source, a playing one is updated on the next
+    /// [`Self::update`].
+    pub fn set_volume_db(&mut self, uuid: &Uuid, volume_db: f32) {
+        if let Some(voice) = self.voices.get_mut(uuid) {
+            voice.volume_db = volume_db;
+            voice.is_volume_changed = true;
+        }
+    }
+
+    /// Resends the volume of a playing source on the next [`Self::update`], e.g. after its
+    /// occlusion changed.
+    pub fn mark_volume_changed(&mut self, uuid: &Uuid) {
+        if let Some(voice) = self.voices.get_mut(uuid) {
+            voice.is_volume_changed = true;
+        }
+    }
+
+    /// Resends the volume of every playing source on the next [`Self::update`], e.g. after the
+    /// gain of their bus changed.
+    pub fn mark_all_volumes_changed(&mut self) {
+        for voice in self.voices.values_mut() {
+            voice.is_volume_changed = true;
+        }
+    }
+
+    /// Number of sources currently playing, including the ones still fading out.
+    pub fn playing_count(&self) -> usize {
+        self.voices.values().filter(|v| v.is_playing).count()
+    }
+
+    pub fn total_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// The uuids and positions of the sources currently playing.
+    pub fn playing_voices(&self) -> impl Iterator<Item = (Uuid, Vec3)> + '_ {
+        self.voices
+            .iter()
+            .filter(|(_, voice)| voice.is_playing)
+            .map(|(uuid, voice)| (*uuid, voice.position))
+    }
+
+    /// Re-ranks the voices and advances their fades by `delta_time`.
+    pub fn update(&mut self, listener_pos: Vec3, delta_time: f32) -> Vec<VoiceAction> {
+        profile_scope!("voice_update");
+        let mut ranked: Vec<(Uuid, f32)> = self
+            .voices
+            .iter()
+            .map(|(uuid, voice)| (*uuid, voice.score(listener_pos)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let fade_step = delta_time / VOICE_FADE_TIME;
+        let mut actions = Vec::new();
+        for (rank, (uuid, _)) in ranked.into_iter().enumerate() {
+            let voice = self.voices.get_mut(&uuid).unwrap();
+            let is_audible = rank < self.max_voices;
+
+            if is_audible && !voice.is_playing {
+                voice.is_playing = true;
+                voice.fade = 0.0;
+                actions.push(VoiceAction::Play(uuid));
+            }
+            if !voice.is_playing {
+                continue;
+            }
+
+            let target = if is_audible { 1.0 } else { 0.0 };
+            if voice.fade == target && !voice.is_volume_changed {
+                continue;
+            }
+            voice.is_volume_changed = false;
+            voice.fade = if is_audible {
+                (voice.fade + fade_step).min(1.0)
+            } else {
+                (voice.fade - fade_step).max(0.0)
+            };
+
+            if voice.fade <= MIN_FADE && !is_audible {
+                voice.is_playing = false;
+                voice.fade = 0.0;
+                actions.push(VoiceAction::Stop(uuid));
+            } else {
+                actions.push(VoiceAction::SetVolume {
+                    uuid,
+                    position: voice.position,
+                    volume_db: voice.volume_db + 20.0 * voice.fade.max(MIN_FADE).log10(),
+                });
+            }
+        }
+        actions
+    }
+}
@@ -0,0 +1,73 @@
+use crate::audio::{AudioBus, SpatialSoundManager};
+use anyhow::Result;
+use uuid::Uuid;
+
+/// Relative to the project root.
+const WIND_LOOP_PATH: &str =
+    "assets/sfx/Gentle Wind/WINDDsgn_Wind, Gentle, Designed 01_SARM_Wind.wav";
+
+/// The share of the full volume left between the gusts.
+const LULL_GAIN: f32 = 0.3;
+
+/// Seconds the volume takes to get most of the way to a new gust.
+const GUST_SMOOTHING: f32 = 0.5;
+
+/// Below this gain the loop is played at this level instead of a log of zero.
+const MIN_GAIN: f32 = 1e-3;
+
+/// Loops the sound of the wind around the player, louder as the wind picks up and swelling with
+/// the gusts of the [`crate::gameplay::WindField`] where the player stands.
+pub struct WindAmbience {
+    spatial_sound_manager: SpatialSoundManager,
+    /// `None` when the loop failed to load.
+    uuid: Option<Uuid>,
+    volume_db: f32,
+    gain: f32,
+    /// Gain last sent to the engine.
+    applied_gain: f32,
+}
+
+impl WindAmbience {
+    /// Starts the loop silently, `volume_db` is its volume in a storm's gust.
+    pub fn new(spatial_sound_manager: SpatialSoundManager, volume_db: f32) -> Self {
+        let uuid = match spatial_sound_manager.add_looping_non_spatial_file(
+            WIND_LOOP_PATH,
+            volume_db + 20.0 * MIN_GAIN.log10(),
+            AudioBus::Ambient,
+        ) {
+            Ok(uuid) => Some(uuid),
+            Err(e) => {
+                log::error!("Failed to load the wind ambience {}: {}", WIND_LOOP_PATH, e);
+                None
+            }
+        };
+        Self {
+            spatial_sound_manager,
+            uuid,
+            volume_db,
+            gain: 0.0,
+            applied_gain: 0.0,
+        }
+    }
+
+    /// Eases the volume towards the wind at the player, `wind_strength` from calm at 0 to a storm
+    /// at 1 and `gust` from 0 to 1.
+    pub fn update(&mut self, wind_strength: f32, gust: f32, delta_time: f32) -> Result<()> {
+        let Some(uuid) = self.uuid else {
+            return Ok(());
+        };
+        let strength = wind_strength.clamp(0.0, 1.0);
+        let target = strength * (LULL_GAIN + (1.0 - LULL_GAIN) * gust.clamp(0.0, 1.0));
+        let t = 1.0 - (-delta_time / GUST_SMOOTHING).exp();
+        self.gain += (target - self.gain) * t;
+        // volume updates cross to the audio thread, skip inaudible changes
+        if (self.gain - self.applied_gain).abs() < 1e-3 {
+            return Ok(());
+        }
+        self.applied_gain = self.gain;
+        self.spatial_sound_manager.set_non_spatial_volume(
+            uuid,
+            self.volume_db + 20.0 * self.gain.max(MIN_GAIN).log10(),
+        )
+    }
+}
@@ -0,0 +1,140 @@
+use crate::geom::{ChunkIdx, UAabb3};
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use std::collections::HashSet;
+
+/// How far around the camera chunks are kept built, in world units (one chunk per unit).
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkStreamerDesc {
+    /// Chunks whose column is closer to the camera than this are loaded.
+    pub load_radius: f32,
+    /// Extra distance a loaded chunk may move away before it's unloaded, so a camera sitting
+    /// on the boundary doesn't rebuild the same chunk every frame.
+    pub unload_margin: f32,
+    /// Chunks built per frame at most, the builders block the frame while they run.
+    pub max_loads_per_frame: usize,
+    /// Chunks unloaded per frame at most, each unload waits for the device to be idle.
+    pub max_unloads_per_frame: usize,
+}
+
+impl Default for ChunkStreamerDesc {
+    fn default() -> Self {
+        Self {
+            load_radius: 2.5,
+            unload_margin: 0.5,
+            max_loads_per_frame: 1,
+            max_unloads_per_frame: 2,
+        }
+    }
+}
+
+impl ChunkStreamerDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.load_radius, 0.5..=8.0).text("Load Radius"));
+        ui.add(egui::Slider::new(&mut self.unload_margin, 0.0..=2.0).text("Unload Margin"));
+        ui.add(egui::Slider::new(&mut self.max_loads_per_frame, 1..=8).text("Loads Per Frame"));
+        ui.add(egui::Slider::new(&mut self.max_unloads_per_frame, 1..=8).text("Unloads Per Frame"));
+    }
+}
+
+/// The chunks to build and to tear down this frame, nearest loads and furthest unloads first.
+#[derive(Debug, Default)]
+pub struct ChunkStreamingWork {
+    pub loads: Vec<ChunkIdx>,
+    pub unloads: Vec<ChunkIdx>,
+}
+
+impl ChunkStreamingWork {
+    pub fn is_empty(&self) -> bool {
+        self.loads.is_empty() && self.unloads.is_empty()
+    }
+}
+
+/// Keeps the chunks around the camera built and frees the contree pools of the ones it left
+/// behind, a few chunks per frame.
+///
+/// Streaming is by chunk column, chunks are loaded whole from the bottom of the world to the top.
+/// The chunks still have to fit into the chunk atlas, so the world can't outgrow `chunk_bound`,
+/// but only the ones around the camera hold contree pool space.
+pub struct ChunkStreamer {
+    pub desc: ChunkStreamerDesc,
+    /// In chunks, max exclusive.
+    chunk_bound: UAabb3,
+    /// Chunks unloaded by the streamer. Chunks it never touched are left to whoever built them,
+    /// the app builds them all at startup.
+    unloaded: HashSet<ChunkIdx>,
+}
+
+impl ChunkStreamer {
+    pub fn new(desc: ChunkStreamerDesc, chunk_bound: UAabb3) -> Self {
+        Self {
+            desc,
+            chunk_bound,
+            unloaded: HashSet::new(),
+        }
+    }
+
+    /// Horizontal distance from `camera_pos` to the closest point of the chunk's column.
+    fn column_distance(chunk_idx: ChunkIdx, camera_pos: Vec3) -> f32 {
+        let column_min = chunk_idx.0.xz().as_vec2();
+        let closest = camera_pos.xz().clamp(column_min, column_min + Vec2::ONE);
+        closest.distance(camera_pos.xz())
+    }
+
+    /// Picks this frame's work for a camera at `camera_pos` in world units, and counts the
+    /// returned chunks as loaded and unloaded.
+    ///
+    /// `built_chunks` are the chunks holding contree pool space, chunks rebuilt by edits since
+    /// they were unloaded are unloaded again.
+    pub fn update(
+        &mut self,
+        camera_pos: Vec3,
+        built_chunks: impl IntoIterator<Item = ChunkIdx>,
+    ) -> ChunkStreamingWork {
+        let unload_radius = self.desc.load_radius + self.desc.unload_margin;
+
+        let mut loads: Vec<(ChunkIdx, f32)> = self
+            .unloaded
+            .iter()
+            .map(|chunk_idx| (*chunk_idx, Self::column_distance(*chunk_idx, camera_pos)))
+            .filter(|(_, distance)| *distance < self.desc.load_radius)
+            .collect();
+        loads.sort_by(|a, b| a.1.total_cmp(&b.1));
+        loads.truncate(self.desc.max_loads_per_frame);
+
+        let mut unloads: Vec<(ChunkIdx, f32)> = built_chunks
+            .into_iter()
+            .filter(|chunk_idx| self.chunk_bound.in_bound(chunk_idx.0))
+            .map(|chunk_idx| (chunk_idx, Self::column_distance(chunk_idx, camera_pos)))
+            .filter(|(_, distance)| *distance > unload_radius)
+            .collect();
+        unloads.sort_by(|a, b| b.1.total_cmp(&a.1));
+        unloads.truncate(self.desc.max_unloads_per_frame);
+
+        let work = ChunkStreamingWork {
+            loads: loads.into_iter().map(|(chunk_idx, _)| chunk_idx).collect(),
+            unloads: unloads
+                .into_iter()
+                .map(|(chunk_idx, _)| chunk_idx)
+                .collect(),
+        };
+        for chunk_idx in &work.loads {
+            self.unloaded.remove(chunk_idx);
+        }
+        self.unloaded.extend(work.unloads.iter().copied());
+        work
+    }
+
+    /// A chunk whose load failed, so it's tried again later.
+    pub fn forget_load(&mut self, chunk_idx: ChunkIdx) {
+        self.unloaded.insert(chunk_idx);
+    }
+
+    /// Loads every unloaded chunk at once, for when streaming is turned off.
+    pub fn take_unloaded(&mut self) -> Vec<ChunkIdx> {
+        self.unloaded.drain().collect()
+    }
+
+    pub fn unloaded_count(&self) -> usize {
+        self.unloaded.len()
+    }
+}
@@ -2,11 +2,21 @@ mod resources;
 pub use resources::*;
 
 use super::SurfaceResources;
+use crate::geom::{AtlasOffset, ChunkIdx};
+use crate::util::fragmentation_of;
+use crate::util::profile_scope;
+use crate::util::AllocationEvent;
 use crate::util::AllocationStrategy;
+use crate::util::AllocationTrace;
+use crate::util::AllocatorStats;
+use crate::util::BufferAllocation;
 use crate::util::FirstFitAllocator;
 use crate::util::ShaderCompiler;
+use crate::util::ALLOC_TRACE_ENV_VAR;
+use crate::vkn::execute_one_time_command;
 use crate::vkn::Allocator;
 use crate::vkn::Buffer;
+use crate::vkn::BufferUsage;
 use crate::vkn::CommandBuffer;
 use crate::vkn::ComputePipeline;
 use crate::vkn::DescriptorPool;
@@ -22,12 +32,56 @@ use anyhow::Result;
 use ash::vk;
 use glam::UVec3;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 const SIZE_OF_NODE_ELEMENT: u64 = 3 * std::mem::size_of::<u32>() as u64;
 const SIZE_OF_LEAF_ELEMENT: u64 = std::mem::size_of::<u32>() as u64;
 
-pub struct ContreeBuilder {
+/// A pool is compacted once its free space is scattered more than this, see [`fragmentation_of`].
+const POOL_COMPACTION_FRAGMENTATION: f32 = 0.5;
+
+/// Residency info of a single chunk inside the contree node/leaf pools.
+#[derive(Debug, Clone)]
+pub struct ChunkContreeInfo {
+    pub chunk_idx: ChunkIdx,
+    pub node_offset_in_bytes: u64,
+    pub node_size_in_bytes: u64,
+    pub leaf_offset_in_bytes: u64,
+    pub leaf_size_in_bytes: u64,
+    pub last_build_time: Instant,
+    pub last_build_duration: Duration,
+}
+
+/// A contree pool has no block left that fits a chunk. The pools are left as they were, so the
+/// chunk keeps its old data if it had any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "contree {pool} pool exhausted, {requested} bytes requested but the largest free block is \
+     {largest_free_block} bytes"
+)]
+pub struct PoolExhausted {
+    /// "node" or "leaf"
+    pub pool: &'static str,
+    pub requested: u64,
+    pub largest_free_block: u64,
+}
+
+/// The node and leaf data of one chunk as stored in the pools.
+///
+/// Child pointers are relative to the chunk's first node and leaf, so the data can be written
+/// back at any offset.
+#[derive(Debug, Clone)]
+pub struct ContreeChunkData {
+    pub nodes: Vec<u8>,
+    pub leaves: Vec<u8>,
+}
+
+/// Generic over the strategy handing out the node and leaf pools, first-fit unless another one
+/// is picked at construction, e.g. `ContreeBuilder::<TlsfAllocator>::new(..)`.
+pub struct ContreeBuilder<A: AllocationStrategy = FirstFitAllocator> {
     vulkan_ctx: VulkanContext,
+    allocator: Allocator,
     resources: ContreeBuilderResources,
 
     #[allow(dead_code)]
@@ -42,6 +96,7 @@ pub struct ContreeBuilder {
     contree_last_buffer_update_ppl: ComputePipeline,
     #[allow(dead_code)]
     contree_concat_ppl: ComputePipeline,
+    chunk_checksum_ppl: ComputePipeline,
 
     #[allow(dead_code)]
     fixed_pool: DescriptorPool,
@@ -49,15 +104,21 @@ pub struct ContreeBuilder {
     /// Atlas offset <-> (node_alloc_id, leaf_alloc_id)
     chunk_offset_allocation_table: HashMap<UVec3, (u64, u64)>,
 
+    /// Atlas offset <-> (last build time, last build duration)
+    chunk_build_times: HashMap<UVec3, (Instant, Duration)>,
+
     contree_cmdbuf: CommandBuffer,
 
-    leaf_allocator: FirstFitAllocator,
-    node_allocator: FirstFitAllocator,
+    leaf_allocator: A,
+    node_allocator: A,
+
+    /// Calls made on the (node, leaf) allocators, recorded when [`ALLOC_TRACE_ENV_VAR`] is set.
+    allocation_traces: Option<(AllocationTrace, AllocationTrace)>,
 
     voxel_dim_per_chunk: UVec3,
 }
 
-impl ContreeBuilder {
+impl<A: AllocationStrategy> ContreeBuilder<A> {
     pub fn new(
         vulkan_ctx: VulkanContext,
         allocator: Allocator,
@@ -119,16 +180,25 @@ impl ContreeBuilder {
         )
         .unwrap();
 
+        let chunk_checksum_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/builder/contree/chunk_checksum.comp",
+            "main",
+        )
+        .unwrap();
+
         let resources = ContreeBuilderResources::new(
             device.clone(),
             allocator.clone(),
             voxel_dim_per_chunk,
+            UVec3::from_array(vulkan_ctx.max_compute_work_group_count()),
             node_pool_size_in_bytes,
             leaf_pool_size_in_bytes,
             &contree_buffer_setup_sm,
             &contree_leaf_write_sm,
             &contree_tree_write_sm,
-            &contree_last_buffer_update_sm,
+            &chunk_checksum_sm,
         );
 
         let fixed_pool = DescriptorPool::new(device).unwrap();
@@ -157,6 +227,8 @@ impl ContreeBuilder {
         );
         let contree_concat_ppl =
             ComputePipeline::new(device, &contree_concat_sm, &fixed_pool, &[&resources]);
+        let chunk_checksum_ppl =
+            ComputePipeline::new(device, &chunk_checksum_sm, &fixed_pool, &[&resources]);
 
         // // --- Descriptor Sets ---
         // let alloc_set_fn = |ppl: &ComputePipeline| -> DescriptorSet {
@@ -199,11 +271,20 @@ impl ContreeBuilder {
             &contree_concat_ppl,
         );
 
-        let node_allocator = FirstFitAllocator::new(node_pool_size_in_bytes);
-        let leaf_allocator = FirstFitAllocator::new(leaf_pool_size_in_bytes);
+        // the offsets are turned into element indices, so they have to be multiples of the
+        // element size
+        let node_allocator = A::with_alignment(node_pool_size_in_bytes, SIZE_OF_NODE_ELEMENT);
+        let leaf_allocator = A::with_alignment(leaf_pool_size_in_bytes, SIZE_OF_LEAF_ELEMENT);
+        let allocation_traces = std::env::var_os(ALLOC_TRACE_ENV_VAR).map(|_| {
+            (
+                AllocationTrace::new(node_pool_size_in_bytes),
+                AllocationTrace::new(leaf_pool_size_in_bytes),
+            )
+        });
 
         Self {
             vulkan_ctx,
+            allocator,
             resources,
             contree_buffer_setup_ppl,
             contree_leaf_write_ppl,
@@ -211,11 +292,14 @@ impl ContreeBuilder {
             contree_buffer_update_ppl,
             contree_last_buffer_update_ppl,
             contree_concat_ppl,
+            chunk_checksum_ppl,
             fixed_pool,
             chunk_offset_allocation_table: HashMap::new(),
+            chunk_build_times: HashMap::new(),
             contree_cmdbuf,
             node_allocator,
             leaf_allocator,
+            allocation_traces,
             voxel_dim_per_chunk,
         }
     }
@@ -247,7 +331,7 @@ impl ContreeBuilder {
         );
 
         let device = vulkan_ctx.device();
-        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.command_pool());
+        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.compute_command_pool());
         cmdbuf.begin(false);
 
         let dispatch_1x1x1 = Extent3D {
@@ -261,7 +345,11 @@ impl ContreeBuilder {
         shader_access_pipeline_barrier.record_insert(vulkan_ctx.device(), &cmdbuf);
         indirect_access_pipeline_barrier.record_insert(vulkan_ctx.device(), &cmdbuf);
 
-        contree_leaf_write_ppl.record_indirect(&cmdbuf, &resources.level_dispatch_indirect, None);
+        contree_leaf_write_ppl.record_indirect(
+            &cmdbuf,
+            resources.level_dispatch_indirect.buffer(),
+            None,
+        );
 
         shader_access_pipeline_barrier.record_insert(vulkan_ctx.device(), &cmdbuf);
 
@@ -273,7 +361,7 @@ impl ContreeBuilder {
         for i in 0..(total_levels - 2) {
             contree_tree_write_ppl.record_indirect(
                 &cmdbuf,
-                &resources.level_dispatch_indirect,
+                resources.level_dispatch_indirect.buffer(),
                 None,
             );
 
@@ -289,7 +377,11 @@ impl ContreeBuilder {
             indirect_access_pipeline_barrier.record_insert(vulkan_ctx.device(), &cmdbuf);
         }
 
-        contree_concat_ppl.record_indirect(&cmdbuf, &resources.concat_dispatch_indirect, None);
+        contree_concat_ppl.record_indirect(
+            &cmdbuf,
+            resources.concat_dispatch_indirect.buffer(),
+            None,
+        );
 
         cmdbuf.end();
         cmdbuf
@@ -332,8 +424,6 @@ impl ContreeBuilder {
         node_write_offset: u64,
         leaf_write_offset: u64,
     ) -> Result<()> {
-        let device = self.vulkan_ctx.device();
-
         update_buffers(
             &self.resources.contree_build_info,
             contree_dim,
@@ -342,9 +432,23 @@ impl ContreeBuilder {
             leaf_write_offset as u32,
         )?;
 
-        let cmdbuf = self.contree_cmdbuf.clone();
-        cmdbuf.submit(&self.vulkan_ctx.get_general_queue(), None);
-        device.wait_queue_idle(&self.vulkan_ctx.get_general_queue());
+        // the pools may be written where a chunk freed earlier sat, so the frames in flight that
+        // could still read it go first, the render loop itself keeps going meanwhile
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        let compute_timeline = self.vulkan_ctx.compute_timeline();
+        let build_done_value = self.vulkan_ctx.submit_compute(
+            &self.contree_cmdbuf,
+            &[(frame_timeline, frame_timeline.submitted_value())],
+        );
+        // the allocation is confirmed from the sizes the build wrote
+        compute_timeline.wait(build_done_value)?;
+
+        self.resources
+            .level_dispatch_indirect
+            .validate(&self.vulkan_ctx)?;
+        self.resources
+            .concat_dispatch_indirect
+            .validate(&self.vulkan_ctx)?;
 
         return Ok(());
 
@@ -372,9 +476,15 @@ impl ContreeBuilder {
         }
     }
 
+    /// Fails with [`PoolExhausted`] if the pools have no room left for the build, the chunk
+    /// keeps its old contree then.
+    ///
     /// Returns: (node_alloc_offset, leaf_alloc_offset)
-    pub fn build_and_alloc(&mut self, atlas_offset: UVec3) -> Result<Option<(u64, u64)>> {
+    pub fn build_and_alloc(&mut self, atlas_offset: AtlasOffset) -> Result<Option<(u64, u64)>> {
+        profile_scope!("build_and_alloc");
+        let atlas_offset = atlas_offset.0;
         let atlas_dim = self.voxel_dim_per_chunk;
+        let build_start = Instant::now();
 
         // preallocate 10MB for both the currentl node and leaf buffer to be built
         const MAX_NODE_BUFFER_SIZE_IN_BYTES: u64 = 10 * 1024 * 1024;
@@ -383,7 +493,7 @@ impl ContreeBuilder {
             MAX_NODE_BUFFER_SIZE_IN_BYTES,
             MAX_LEAF_BUFFER_SIZE_IN_BYTES,
             atlas_offset,
-        );
+        )?;
         // the offset's unit is in bytes, we need to convert it to array idx, each element is a 3*u32
         let node_alloc_offset = node_alloc_offset_in_bytes / SIZE_OF_NODE_ELEMENT;
         // the element of leaf data is a u32
@@ -400,42 +510,470 @@ impl ContreeBuilder {
             atlas_offset,
         );
 
+        self.chunk_build_times
+            .insert(atlas_offset, (Instant::now(), build_start.elapsed()));
+
         Ok(Some((node_alloc_offset, leaf_alloc_offset)))
     }
 
+    /// The chunks owning space in the node/leaf pools, in no particular order.
+    pub fn built_chunks(&self) -> impl Iterator<Item = ChunkIdx> + '_ {
+        self.chunk_offset_allocation_table
+            .keys()
+            .map(|atlas_offset| AtlasOffset(*atlas_offset).chunk_idx())
+    }
+
+    /// Lists every chunk that currently owns space in the node/leaf pools, sorted by chunk index.
+    pub fn chunk_residency(&self) -> Vec<ChunkContreeInfo> {
+        let mut infos: Vec<ChunkContreeInfo> = self
+            .chunk_offset_allocation_table
+            .iter()
+            .filter_map(|(atlas_offset, (node_alloc_id, leaf_alloc_id))| {
+                let node_allocation = self.node_allocator.lookup(*node_alloc_id)?;
+                let leaf_allocation = self.leaf_allocator.lookup(*leaf_alloc_id)?;
+                let (last_build_time, last_build_duration) = self
+                    .chunk_build_times
+                    .get(atlas_offset)
+                    .copied()
+                    .unwrap_or((Instant::now(), Duration::ZERO));
+                Some(ChunkContreeInfo {
+                    chunk_idx: AtlasOffset(*atlas_offset).chunk_idx(),
+                    node_offset_in_bytes: node_allocation.offset,
+                    node_size_in_bytes: node_allocation.size,
+                    leaf_offset_in_bytes: leaf_allocation.offset,
+                    leaf_size_in_bytes: leaf_allocation.size,
+                    last_build_time,
+                    last_build_duration,
+                })
+            })
+            .collect();
+        infos.sort_by_key(|info| (info.chunk_idx.0.x, info.chunk_idx.0.y, info.chunk_idx.0.z));
+        infos
+    }
+
+    /// Returns: (node_pool_stats, leaf_pool_stats)
+    pub fn pool_stats(&self) -> (AllocatorStats, AllocatorStats) {
+        (
+            AllocatorStats::of(&self.node_allocator),
+            AllocatorStats::of(&self.leaf_allocator),
+        )
+    }
+
+    /// Hashes the node and leaf data of a chunk on the gpu, `None` if the chunk isn't resident.
+    ///
+    /// Child pointers are left out and the element hashes are summed, so two builds of the same
+    /// voxels match even though the builder lays out their data in a different order.
+    pub fn chunk_checksum(&self, chunk_idx: ChunkIdx) -> Result<Option<u64>> {
+        profile_scope!("chunk_checksum");
+        let Some((node_alloc_id, leaf_alloc_id)) = self
+            .chunk_offset_allocation_table
+            .get(&chunk_idx.atlas_offset().0)
+        else {
+            return Ok(None);
+        };
+        let (Some(node_allocation), Some(leaf_allocation)) = (
+            self.node_allocator.lookup(*node_alloc_id),
+            self.leaf_allocator.lookup(*leaf_alloc_id),
+        ) else {
+            return Ok(None);
+        };
+        let node_len = (node_allocation.size / SIZE_OF_NODE_ELEMENT) as u32;
+        let leaf_len = (leaf_allocation.size / SIZE_OF_LEAF_ELEMENT) as u32;
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.chunk_checksum_info)
+            .set_field(
+                "node_offset",
+                PlainMemberTypeWithData::UInt(
+                    (node_allocation.offset / SIZE_OF_NODE_ELEMENT) as u32,
+                ),
+            )
+            .set_field("node_len", PlainMemberTypeWithData::UInt(node_len))
+            .set_field(
+                "leaf_offset",
+                PlainMemberTypeWithData::UInt(
+                    (leaf_allocation.offset / SIZE_OF_LEAF_ELEMENT) as u32,
+                ),
+            )
+            .set_field("leaf_len", PlainMemberTypeWithData::UInt(leaf_len))
+            .build()?;
+        self.resources.chunk_checksum_info.fill_with_raw_u8(&data)?;
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.chunk_checksum_result)
+            .set_field("node_hash", PlainMemberTypeWithData::UInt(0))
+            .set_field("leaf_hash", PlainMemberTypeWithData::UInt(0))
+            .build()?;
+        self.resources
+            .chunk_checksum_result
+            .fill_with_raw_u8(&data)?;
+
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                self.chunk_checksum_ppl.record(
+                    cmdbuf,
+                    Extent3D::new(node_len.max(leaf_len), 1, 1),
+                    None,
+                );
+            },
+        );
+
+        let layout = &self
+            .resources
+            .chunk_checksum_result
+            .get_layout()
+            .unwrap()
+            .root_member;
+        let raw_data = self.resources.chunk_checksum_result.read_back()?;
+        let reader = StructMemberDataReader::new(layout, &raw_data);
+        let read_hash = |name: &str| match reader.get_field(name) {
+            Ok(PlainMemberTypeWithData::UInt(val)) => Ok(val as u64),
+            Ok(_) => Err(anyhow::anyhow!("Expected UInt type for {}", name)),
+            Err(e) => Err(anyhow::anyhow!(e)),
+        };
+        Ok(Some(
+            (read_hash("node_hash")? << 32) | read_hash("leaf_hash")?,
+        ))
+    }
+
+    /// Copies the node and leaf data of a chunk out of the pools, `None` if the chunk isn't
+    /// resident.
+    pub fn read_chunk_contree(&self, chunk_idx: ChunkIdx) -> Result<Option<ContreeChunkData>> {
+        let Some((node_alloc_id, leaf_alloc_id)) = self
+            .chunk_offset_allocation_table
+            .get(&chunk_idx.atlas_offset().0)
+        else {
+            return Ok(None);
+        };
+        let (Some(node_allocation), Some(leaf_allocation)) = (
+            self.node_allocator.lookup(*node_alloc_id),
+            self.leaf_allocator.lookup(*leaf_alloc_id),
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(ContreeChunkData {
+            nodes: self.read_pool_range(
+                &self.resources.contree_node_data,
+                node_allocation.offset,
+                node_allocation.size,
+            )?,
+            leaves: self.read_pool_range(
+                &self.resources.contree_leaf_data,
+                leaf_allocation.offset,
+                leaf_allocation.size,
+            )?,
+        }))
+    }
+
+    /// Allocates pool space for a chunk and writes `data` into it, in place of building the
+    /// chunk from the atlas. Replaces the chunk's old allocation if it had one.
+    ///
+    /// Returns: (node_alloc_offset, leaf_alloc_offset), in elements like [`Self::build_and_alloc`]
+    pub fn restore_chunk_contree(
+        &mut self,
+        chunk_idx: ChunkIdx,
+        data: &ContreeChunkData,
+    ) -> Result<(u64, u64)> {
+        if data.nodes.len() as u64 % SIZE_OF_NODE_ELEMENT != 0
+            || data.leaves.len() as u64 % SIZE_OF_LEAF_ELEMENT != 0
+        {
+            anyhow::bail!(
+                "Contree data of chunk {} isn't made of whole elements",
+                chunk_idx
+            );
+        }
+        let (node_alloc_offset_in_bytes, leaf_alloc_offset_in_bytes) = self.pre_allocate_chunk(
+            data.nodes.len() as u64,
+            data.leaves.len() as u64,
+            chunk_idx.atlas_offset().0,
+        )?;
+        self.write_pool_range(
+            &self.resources.contree_node_data,
+            node_alloc_offset_in_bytes,
+            &data.nodes,
+        )?;
+        self.write_pool_range(
+            &self.resources.contree_leaf_data,
+            leaf_alloc_offset_in_bytes,
+            &data.leaves,
+        )?;
+        self.chunk_build_times
+            .insert(chunk_idx.atlas_offset().0, (Instant::now(), Duration::ZERO));
+        Ok((
+            node_alloc_offset_in_bytes / SIZE_OF_NODE_ELEMENT,
+            leaf_alloc_offset_in_bytes / SIZE_OF_LEAF_ELEMENT,
+        ))
+    }
+
+    fn read_pool_range(&self, pool: &Buffer, byte_offset: u64, size: u64) -> Result<Vec<u8>> {
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        let staging = Buffer::new_sized(
+            self.vulkan_ctx.device().clone(),
+            self.allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::TRANSFER_DST),
+            gpu_allocator::MemoryLocation::GpuToCpu,
+            size,
+        );
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| pool.record_copy_to_buffer(cmdbuf, &staging, size, byte_offset, 0),
+        );
+        staging.read_back()
+    }
+
+    fn write_pool_range(&self, pool: &Buffer, byte_offset: u64, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let staging = Buffer::new_sized(
+            self.vulkan_ctx.device().clone(),
+            self.allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::TRANSFER_SRC),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            data.len() as u64,
+        );
+        staging.fill(data)?;
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| staging.record_copy_to_buffer(cmdbuf, pool, data.len() as u64, 0, byte_offset),
+        );
+        Ok(())
+    }
+
+    /// Packs the chunks of both pools together once either pool is too fragmented, so long
+    /// sessions of edits keep finding room for the space a build reserves.
+    ///
+    /// Returns: the chunks whose data moved with their new (node_offset, leaf_offset), in elements
+    /// like [`Self::build_and_alloc`]. The scene texture has to point at them before the next
+    /// frame is drawn.
+    pub fn compact_pools_if_fragmented(&mut self) -> Result<Vec<(ChunkIdx, (u64, u64))>> {
+        let is_fragmented =
+            |allocator: &A| fragmentation_of(allocator) > POOL_COMPACTION_FRAGMENTATION;
+        if !is_fragmented(&self.node_allocator) && !is_fragmented(&self.leaf_allocator) {
+            return Ok(Vec::new());
+        }
+        self.compact_pools()
+    }
+
+    /// Packs the chunks of both pools together and moves their data along.
+    ///
+    /// Returns: the chunks whose data moved, see [`Self::compact_pools_if_fragmented`]
+    pub fn compact_pools(&mut self) -> Result<Vec<(ChunkIdx, (u64, u64))>> {
+        profile_scope!("compact_pools");
+        // the frames in flight may still trace the data where it is now
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        frame_timeline.wait(frame_timeline.submitted_value())?;
+
+        let old_allocations: HashMap<UVec3, (BufferAllocation, BufferAllocation)> = self
+            .chunk_offset_allocation_table
+            .iter()
+            .filter_map(|(atlas_offset, (node_alloc_id, leaf_alloc_id))| {
+                Some((
+                    *atlas_offset,
+                    (
+                        self.node_allocator.lookup(*node_alloc_id)?,
+                        self.leaf_allocator.lookup(*leaf_alloc_id)?,
+                    ),
+                ))
+            })
+            .collect();
+        self.node_allocator.cleanup();
+        self.leaf_allocator.cleanup();
+        self.record_allocation_events(AllocationEvent::Cleanup, AllocationEvent::Cleanup);
+
+        // (src_offset, dst_offset, size) in bytes
+        let mut node_moves = Vec::new();
+        let mut leaf_moves = Vec::new();
+        let mut moved_chunks = Vec::new();
+        for (atlas_offset, (old_node, old_leaf)) in &old_allocations {
+            let (node_alloc_id, leaf_alloc_id) = self.chunk_offset_allocation_table[atlas_offset];
+            let node = self.node_allocator.lookup(node_alloc_id).unwrap();
+            let leaf = self.leaf_allocator.lookup(leaf_alloc_id).unwrap();
+            if node.offset == old_node.offset && leaf.offset == old_leaf.offset {
+                continue;
+            }
+            if node.offset != old_node.offset {
+                node_moves.push((old_node.offset, node.offset, node.size));
+            }
+            if leaf.offset != old_leaf.offset {
+                leaf_moves.push((old_leaf.offset, leaf.offset, leaf.size));
+            }
+            moved_chunks.push((
+                AtlasOffset(*atlas_offset).chunk_idx(),
+                (
+                    node.offset / SIZE_OF_NODE_ELEMENT,
+                    leaf.offset / SIZE_OF_LEAF_ELEMENT,
+                ),
+            ));
+        }
+        self.move_pool_ranges(&self.resources.contree_node_data, &node_moves)?;
+        self.move_pool_ranges(&self.resources.contree_leaf_data, &leaf_moves)?;
+        Ok(moved_chunks)
+    }
+
+    /// Moves `(src_offset, dst_offset, size)` byte ranges inside a pool. The ranges may overlap
+    /// each other, so they go through a scratch buffer.
+    fn move_pool_ranges(&self, pool: &Buffer, moves: &[(u64, u64, u64)]) -> Result<()> {
+        let scratch_size: u64 = moves.iter().map(|(_, _, size)| size).sum();
+        if scratch_size == 0 {
+            return Ok(());
+        }
+        let scratch = Buffer::new_sized(
+            self.vulkan_ctx.device().clone(),
+            self.allocator.clone(),
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            gpu_allocator::MemoryLocation::GpuOnly,
+            scratch_size,
+        );
+        let transfer_barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE,
+            )],
+        );
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                let moves = moves.iter().filter(|(_, _, size)| *size > 0);
+                let mut scratch_offset = 0;
+                for &(src_offset, _, size) in moves.clone() {
+                    pool.record_copy_to_buffer(cmdbuf, &scratch, size, src_offset, scratch_offset);
+                    scratch_offset += size;
+                }
+                transfer_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+                let mut scratch_offset = 0;
+                for &(_, dst_offset, size) in moves {
+                    scratch.record_copy_to_buffer(cmdbuf, pool, size, scratch_offset, dst_offset);
+                    scratch_offset += size;
+                }
+            },
+        );
+        Ok(())
+    }
+
+    /// Releases the node and leaf allocations of a chunk.
+    ///
+    /// The chunk must also be removed from the scene texture, otherwise the tracer keeps
+    /// reading stale pool data. Returns false if the chunk wasn't resident.
+    pub fn evict_chunk(&mut self, atlas_offset: AtlasOffset) -> Result<bool> {
+        let Some((node_alloc_id, leaf_alloc_id)) =
+            self.chunk_offset_allocation_table.remove(&atlas_offset.0)
+        else {
+            return Ok(false);
+        };
+        self.chunk_build_times.remove(&atlas_offset.0);
+        self.node_allocator
+            .deallocate(node_alloc_id)
+            .map_err(anyhow::Error::msg)?;
+        self.leaf_allocator
+            .deallocate(leaf_alloc_id)
+            .map_err(anyhow::Error::msg)?;
+        self.record_allocation_events(
+            AllocationEvent::Deallocate { id: node_alloc_id },
+            AllocationEvent::Deallocate { id: leaf_alloc_id },
+        );
+        Ok(true)
+    }
+
+    /// Writes the recorded allocation traces into the directory [`ALLOC_TRACE_ENV_VAR`] names,
+    /// does nothing if it isn't set.
+    pub fn save_allocation_traces(&self) -> Result<()> {
+        let (Some((node_trace, leaf_trace)), Some(trace_dir)) = (
+            &self.allocation_traces,
+            std::env::var_os(ALLOC_TRACE_ENV_VAR),
+        ) else {
+            return Ok(());
+        };
+        let trace_dir = PathBuf::from(trace_dir);
+        std::fs::create_dir_all(&trace_dir)?;
+        node_trace.save(&trace_dir.join("contree_node.trace"))?;
+        leaf_trace.save(&trace_dir.join("contree_leaf.trace"))?;
+        log::info!(
+            "Saved {} node and {} leaf allocation events to {}",
+            node_trace.events.len(),
+            leaf_trace.events.len(),
+            trace_dir.display()
+        );
+        Ok(())
+    }
+
+    fn record_allocation_events(
+        &mut self,
+        node_event: AllocationEvent,
+        leaf_event: AllocationEvent,
+    ) {
+        if let Some((node_trace, leaf_trace)) = &mut self.allocation_traces {
+            node_trace.record(node_event);
+            leaf_trace.record(leaf_event);
+        }
+    }
+
     /// Allocate a chunk of data and store the allocation id in the offset_allocation_table.
     ///
     /// Returns: (node_alloc_offset_in_bytes, leaf_alloc_offset_in_bytes)
-    /// If the chunk already exists, deallocate it first.
+    /// If the chunk already exists, its old allocation is released once the new one is made, so
+    /// a failed allocation leaves it untouched.
     fn pre_allocate_chunk(
         &mut self,
         max_node_buffer_size_in_bytes: u64,
         max_leaf_buffer_size_in_bytes: u64,
         atlas_offset: UVec3,
-    ) -> (u64, u64) {
-        if self
+    ) -> Result<(u64, u64), PoolExhausted> {
+        let node_allocation = self
+            .node_allocator
+            .allocate(max_node_buffer_size_in_bytes)
+            .map_err(|_| PoolExhausted {
+                pool: "node",
+                requested: max_node_buffer_size_in_bytes,
+                largest_free_block: self.node_allocator.largest_free_block(),
+            })?;
+        let leaf_allocation = match self.leaf_allocator.allocate(max_leaf_buffer_size_in_bytes) {
+            Ok(leaf_allocation) => leaf_allocation,
+            Err(_) => {
+                self.node_allocator.deallocate(node_allocation.id).unwrap();
+                return Err(PoolExhausted {
+                    pool: "leaf",
+                    requested: max_leaf_buffer_size_in_bytes,
+                    largest_free_block: self.leaf_allocator.largest_free_block(),
+                });
+            }
+        };
+        self.record_allocation_events(
+            AllocationEvent::Allocate {
+                id: node_allocation.id,
+                size: max_node_buffer_size_in_bytes,
+            },
+            AllocationEvent::Allocate {
+                id: leaf_allocation.id,
+                size: max_leaf_buffer_size_in_bytes,
+            },
+        );
+
+        if let Some((node_alloc_id, leaf_alloc_id)) = self
             .chunk_offset_allocation_table
-            .contains_key(&atlas_offset)
+            .insert(atlas_offset, (node_allocation.id, leaf_allocation.id))
         {
-            let (node_alloc_id, leaf_alloc_id) = self
-                .chunk_offset_allocation_table
-                .remove(&atlas_offset)
-                .unwrap();
             self.node_allocator.deallocate(node_alloc_id).unwrap();
             self.leaf_allocator.deallocate(leaf_alloc_id).unwrap();
+            self.record_allocation_events(
+                AllocationEvent::Deallocate { id: node_alloc_id },
+                AllocationEvent::Deallocate { id: leaf_alloc_id },
+            );
         }
-        let node_allocation = self
-            .node_allocator
-            .allocate(max_node_buffer_size_in_bytes)
-            .unwrap();
-        let leaf_allocation = self
-            .leaf_allocator
-            .allocate(max_leaf_buffer_size_in_bytes)
-            .unwrap();
-
-        self.chunk_offset_allocation_table
-            .insert(atlas_offset, (node_allocation.id, leaf_allocation.id));
-        (node_allocation.offset, leaf_allocation.offset)
+        Ok((node_allocation.offset, leaf_allocation.offset))
     }
 
     fn confirm_allocation_of_chunk(
@@ -444,17 +982,27 @@ impl ContreeBuilder {
         confirmed_leaf_buffer_size_in_bytes: u64,
         atlas_offset: UVec3,
     ) {
-        let (node_alloc_id, leaf_alloc_id) = self
+        let (node_alloc_id, leaf_alloc_id) = *self
             .chunk_offset_allocation_table
             .get(&atlas_offset)
             .expect("Chunk not found in allocation table");
 
         self.node_allocator
-            .resize(*node_alloc_id, confirmed_node_buffer_size_in_bytes)
+            .resize(node_alloc_id, confirmed_node_buffer_size_in_bytes)
             .unwrap();
         self.leaf_allocator
-            .resize(*leaf_alloc_id, confirmed_leaf_buffer_size_in_bytes)
+            .resize(leaf_alloc_id, confirmed_leaf_buffer_size_in_bytes)
             .unwrap();
+        self.record_allocation_events(
+            AllocationEvent::Resize {
+                id: node_alloc_id,
+                size: confirmed_node_buffer_size_in_bytes,
+            },
+            AllocationEvent::Resize {
+                id: leaf_alloc_id,
+                size: confirmed_leaf_buffer_size_in_bytes,
+            },
+        );
     }
 }
 
@@ -1,6 +1,6 @@
 use crate::{
     resource::Resource,
-    vkn::{Allocator, Buffer, BufferUsage, Device, ShaderModule},
+    vkn::{Allocator, Buffer, BufferUsage, Device, IndirectDispatch, ShaderModule},
 };
 use ash::vk;
 use glam::UVec3;
@@ -10,8 +10,8 @@ use resource_container_derive::ResourceContainer;
 pub struct ContreeBuilderResources {
     pub contree_build_info: Resource<Buffer>,
     pub contree_build_state: Resource<Buffer>,
-    pub level_dispatch_indirect: Resource<Buffer>,
-    pub concat_dispatch_indirect: Resource<Buffer>,
+    pub level_dispatch_indirect: IndirectDispatch,
+    pub concat_dispatch_indirect: IndirectDispatch,
     pub counter_for_levels: Resource<Buffer>,
     pub node_offset_for_levels: Resource<Buffer>,
     pub sparse_nodes: Resource<Buffer>,
@@ -20,6 +20,9 @@ pub struct ContreeBuilderResources {
     pub contree_leaf_data: Resource<Buffer>,
     pub contree_node_data: Resource<Buffer>,
     pub contree_build_result: Resource<Buffer>,
+
+    pub chunk_checksum_info: Resource<Buffer>,
+    pub chunk_checksum_result: Resource<Buffer>,
 }
 
 impl ContreeBuilderResources {
@@ -28,12 +31,13 @@ impl ContreeBuilderResources {
         device: Device,
         allocator: Allocator,
         max_voxel_dim_per_chunk: UVec3,
+        max_dispatch_group_count: UVec3,
         node_pool_size_in_bytes: u64,
         leaf_pool_size_in_bytes: u64,
         contree_buffer_setup_sm: &ShaderModule,
         leaf_write_sm: &ShaderModule,
         tree_write_sm: &ShaderModule,
-        last_buffer_update_sm: &ShaderModule,
+        chunk_checksum_sm: &ShaderModule,
     ) -> Self {
         fn log_4(n: u32) -> u32 {
             // trailing_zeros gives 2*k, so divide by 2:
@@ -62,26 +66,17 @@ impl ContreeBuilderResources {
             gpu_allocator::MemoryLocation::GpuOnly,
         );
 
-        let level_dispatch_indirect_layout = contree_buffer_setup_sm
-            .get_buffer_layout("B_LevelDispatchIndirect")
-            .unwrap();
-        let level_dispatch_indirect = Buffer::from_buffer_layout(
+        let level_dispatch_indirect = IndirectDispatch::new(
             device.clone(),
             allocator.clone(),
-            level_dispatch_indirect_layout.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::INDIRECT_BUFFER),
-            gpu_allocator::MemoryLocation::GpuOnly,
+            "level_dispatch_indirect",
+            max_dispatch_group_count,
         );
-
-        let concat_dispatch_indirect_layout = last_buffer_update_sm
-            .get_buffer_layout("B_ConcatDispatchIndirect")
-            .unwrap();
-        let concat_dispatch_indirect = Buffer::from_buffer_layout(
+        let concat_dispatch_indirect = IndirectDispatch::new(
             device.clone(),
             allocator.clone(),
-            concat_dispatch_indirect_layout.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::INDIRECT_BUFFER),
-            gpu_allocator::MemoryLocation::GpuOnly,
+            "concat_dispatch_indirect",
+            max_dispatch_group_count,
         );
 
         let max_level = log_4(max_voxel_dim_per_chunk.x) + 1;
@@ -134,7 +129,11 @@ impl ContreeBuilderResources {
         let leaf_data = Buffer::new_sized(
             device.clone(),
             allocator.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
             gpu_allocator::MemoryLocation::GpuOnly,
             leaf_pool_size_in_bytes,
         );
@@ -142,7 +141,11 @@ impl ContreeBuilderResources {
         let node_data = Buffer::new_sized(
             device.clone(),
             allocator.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
             gpu_allocator::MemoryLocation::GpuOnly,
             node_pool_size_in_bytes,
         );
@@ -158,11 +161,33 @@ impl ContreeBuilderResources {
             gpu_allocator::MemoryLocation::GpuToCpu,
         );
 
+        let chunk_checksum_info_layout = chunk_checksum_sm
+            .get_buffer_layout("U_ChunkChecksumInfo")
+            .unwrap();
+        let chunk_checksum_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            chunk_checksum_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let chunk_checksum_result_layout = chunk_checksum_sm
+            .get_buffer_layout("B_ChunkChecksumResult")
+            .unwrap();
+        let chunk_checksum_result = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            chunk_checksum_result_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
         Self {
             contree_build_info: Resource::new(contree_build_info),
             contree_build_state: Resource::new(contree_build_state),
-            level_dispatch_indirect: Resource::new(level_dispatch_indirect),
-            concat_dispatch_indirect: Resource::new(concat_dispatch_indirect),
+            level_dispatch_indirect,
+            concat_dispatch_indirect,
             counter_for_levels: Resource::new(counter_for_levels),
             node_offset_for_levels: Resource::new(node_offset_for_levels),
             sparse_nodes: Resource::new(sparse_nodes),
@@ -170,6 +195,8 @@ impl ContreeBuilderResources {
             contree_leaf_data: Resource::new(leaf_data),
             contree_node_data: Resource::new(node_data),
             contree_build_result: Resource::new(contree_build_result),
+            chunk_checksum_info: Resource::new(chunk_checksum_info),
+            chunk_checksum_result: Resource::new(chunk_checksum_result),
         }
     }
 }
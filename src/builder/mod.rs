@@ -1,3 +1,6 @@
+mod chunk_streamer;
+pub use chunk_streamer::*;
+
 mod contree;
 pub use contree::*;
 
@@ -9,3 +12,6 @@ pub use scene_accel::*;
 
 mod surface;
 pub use surface::*;
+
+mod world_gen_preview;
+pub use world_gen_preview::*;
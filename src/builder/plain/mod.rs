@@ -1,10 +1,13 @@
 mod resources;
+mod terrain_gen;
 use crate::geom::BvhNode;
+use crate::geom::ChunkIdx;
 use crate::geom::RoundCone;
-use crate::util::ShaderCompiler;
+use crate::util::{profile_scope, AtlasAllocation, AtlasAllocator, ShaderCompiler};
 use crate::vkn::execute_one_time_command;
 use crate::vkn::Allocator;
 use crate::vkn::Buffer;
+use crate::vkn::BufferUsage;
 use crate::vkn::ClearValue;
 use crate::vkn::ColorClearValue;
 use crate::vkn::CommandBuffer;
@@ -15,13 +18,19 @@ use crate::vkn::MemoryBarrier;
 use crate::vkn::PipelineBarrier;
 use crate::vkn::PlainMemberTypeWithData;
 use crate::vkn::ShaderModule;
+use crate::vkn::StructArrayDataBuilder;
 use crate::vkn::StructMemberDataBuilder;
 use crate::vkn::Texture;
+use crate::vkn::TextureRegion;
 use crate::vkn::VulkanContext;
 use anyhow::Result;
 use ash::vk;
 use glam::UVec3;
 pub use resources::*;
+pub use terrain_gen::*;
+
+/// Mirrors `CHUNK_MODIFY_ANY_VOXEL` in `chunk_modify.comp`.
+const CHUNK_MODIFY_ANY_VOXEL: u32 = u32::MAX;
 
 pub struct PlainBuilder {
     vulkan_ctx: VulkanContext,
@@ -37,6 +46,14 @@ pub struct PlainBuilder {
     pool: DescriptorPool,
 
     build_cmdbuf: CommandBuffer,
+    /// The compute timeline value of the latest chunk init, the region info can't be rewritten
+    /// and the command buffer can't be re-recorded before it.
+    build_done_value: u64,
+
+    terrain_gen_desc: TerrainGenDesc,
+
+    /// Hands out bricks of the free atlas as scratch space for voxelization passes.
+    free_atlas_allocator: AtlasAllocator,
 }
 
 impl PlainBuilder {
@@ -76,7 +93,9 @@ impl PlainBuilder {
             allocator.clone(),
             plain_atlas_dim,
             free_atlas_dim,
+            UVec3::from_array(vulkan_ctx.max_compute_work_group_count()),
             &buffer_setup_sm,
+            &chunk_init_sm,
             &chunk_modify_sm,
         );
 
@@ -91,7 +110,7 @@ impl PlainBuilder {
         let build_cmdbuf = Self::record_build_cmdbuf(
             &vulkan_ctx,
             &resources.chunk_atlas,
-            &resources.region_indirect,
+            resources.region_indirect.buffer(),
             &buffer_setup_ppl,
             &chunk_init_ppl,
         );
@@ -104,6 +123,9 @@ impl PlainBuilder {
             chunk_modify_ppl,
             pool,
             build_cmdbuf,
+            build_done_value: 0,
+            terrain_gen_desc: TerrainGenDesc::default(),
+            free_atlas_allocator: AtlasAllocator::new(free_atlas_dim),
         };
 
         fn init_atlas_images(vulkan_context: &VulkanContext, resources: &PlainBuilderResources) {
@@ -150,13 +172,15 @@ impl PlainBuilder {
             vec![indirect_access_memory_barrier],
         );
 
-        let cmdbuf = CommandBuffer::new(vulkan_ctx.device(), vulkan_ctx.command_pool());
+        // recorded for the compute queue, which can't do the layout transitions, the atlas is
+        // kept in general layout since `init_atlas_images`
+        debug_assert_eq!(
+            chunk_atlas.get_image().get_layout(0),
+            vk::ImageLayout::GENERAL
+        );
+        let cmdbuf = CommandBuffer::new(vulkan_ctx.device(), vulkan_ctx.compute_command_pool());
         cmdbuf.begin(false);
 
-        chunk_atlas
-            .get_image()
-            .record_transition_barrier(&cmdbuf, 0, vk::ImageLayout::GENERAL);
-
         buffer_setup_ppl.record(
             &cmdbuf,
             Extent3D {
@@ -180,26 +204,129 @@ impl PlainBuilder {
         &self.resources
     }
 
+    fn chunk_region(chunk_idx: ChunkIdx) -> TextureRegion {
+        let offset = chunk_idx.atlas_offset().0.as_ivec3();
+        let dim = chunk_idx.voxel_bound().dimensions() + UVec3::ONE;
+        TextureRegion {
+            offset: offset.to_array(),
+            extent: Extent3D::new(dim.x, dim.y, dim.z),
+        }
+    }
+
+    /// Reads the voxels of a chunk back from the chunk atlas, one byte per voxel with x varying
+    /// fastest.
+    pub fn read_chunk_voxels(&self, chunk_idx: ChunkIdx) -> Result<Vec<u8>> {
+        self.vulkan_ctx.wait_compute()?;
+        let image = self.resources.chunk_atlas.get_image();
+        let region = Self::chunk_region(chunk_idx);
+        let mut staging = Buffer::new_sized(
+            self.vulkan_ctx.device().clone(),
+            image.get_allocator().clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::TRANSFER_DST),
+            gpu_allocator::MemoryLocation::GpuToCpu,
+            region.extent.width as u64 * region.extent.height as u64 * region.extent.depth as u64,
+        );
+        image.copy_image_to_buffer(
+            &mut staging,
+            &self.vulkan_ctx.get_general_queue(),
+            self.vulkan_ctx.command_pool(),
+            vk::ImageLayout::GENERAL,
+            0,
+            region,
+        );
+        staging.read_back()
+    }
+
+    /// Overwrites the voxels of a chunk in the chunk atlas, laid out as in
+    /// [`Self::read_chunk_voxels`]. The chunk's surface and contree have to be rebuilt after.
+    pub fn write_chunk_voxels(&self, chunk_idx: ChunkIdx, voxels: &[u8]) -> Result<()> {
+        let region = Self::chunk_region(chunk_idx);
+        let expected_len = region.extent.width as usize
+            * region.extent.height as usize
+            * region.extent.depth as usize;
+        if voxels.len() != expected_len {
+            anyhow::bail!(
+                "Chunk {} needs {} voxels, got {}",
+                chunk_idx,
+                expected_len,
+                voxels.len()
+            );
+        }
+        self.vulkan_ctx.wait_compute()?;
+        self.resources.chunk_atlas.get_image().fill_with_raw_u8(
+            &self.vulkan_ctx.get_general_queue(),
+            self.vulkan_ctx.command_pool(),
+            region,
+            voxels,
+            0,
+            Some(vk::ImageLayout::GENERAL),
+        )
+    }
+
+    /// Reserves a region of the free atlas to voxelize into before the result is merged into the
+    /// chunk atlas, the texels hold whatever the previous user left there.
+    #[allow(dead_code)]
+    pub fn allocate_scratch(&mut self, dim: UVec3) -> Result<AtlasAllocation> {
+        self.free_atlas_allocator
+            .allocate(dim)
+            .map_err(anyhow::Error::msg)
+    }
+
+    #[allow(dead_code)]
+    pub fn free_scratch(&mut self, allocation_id: u64) -> Result<()> {
+        self.free_atlas_allocator
+            .deallocate(allocation_id)
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// Returns: (free_volume, largest_free_brick) of the free atlas, in texels.
+    #[allow(dead_code)]
+    pub fn scratch_stats(&self) -> (u64, UVec3) {
+        (
+            self.free_atlas_allocator.free_volume(),
+            self.free_atlas_allocator.largest_free_brick(),
+        )
+    }
+
+    pub fn terrain_gen_desc(&self) -> &TerrainGenDesc {
+        &self.terrain_gen_desc
+    }
+
+    /// Takes effect with the next `chunk_init`.
+    pub fn set_terrain_gen_desc(&mut self, terrain_gen_desc: TerrainGenDesc) {
+        self.terrain_gen_desc = terrain_gen_desc;
+    }
+
     pub fn chunk_init(&mut self, atlas_offset: UVec3, atlas_dim: UVec3) -> Result<()> {
+        profile_scope!("chunk_init");
         if atlas_dim.x == 0 || atlas_dim.y == 0 || atlas_dim.z == 0 {
             return Ok(());
         }
+        self.vulkan_ctx
+            .compute_timeline()
+            .wait(self.build_done_value)?;
         update_buffers(&self.resources, atlas_offset, atlas_dim)?;
+        self.terrain_gen_desc
+            .update_info(&self.resources.terrain_gen_info)?;
 
         // re-record the command buffer with updated descriptor sets
         self.build_cmdbuf = Self::record_build_cmdbuf(
             &self.vulkan_ctx,
             &self.resources.chunk_atlas,
-            &self.resources.region_indirect,
+            self.resources.region_indirect.buffer(),
             &self.buffer_setup_ppl,
             &self.chunk_init_ppl,
         );
 
-        self.build_cmdbuf
-            .submit(&self.vulkan_ctx.get_general_queue(), None);
-        self.vulkan_ctx
-            .device()
-            .wait_queue_idle(&self.vulkan_ctx.get_general_queue());
+        // the surface and contree builds reading the atlas are chained after it on the compute
+        // queue, so the host only waits when it reads the atlas or the dispatch itself
+        self.build_done_value = self.vulkan_ctx.submit_compute(&self.build_cmdbuf, &[]);
+        if self.resources.region_indirect.is_validation_enabled() {
+            self.vulkan_ctx
+                .compute_timeline()
+                .wait(self.build_done_value)?;
+            self.resources.region_indirect.validate(&self.vulkan_ctx)?;
+        }
         return Ok(());
 
         fn update_buffers(
@@ -216,11 +343,55 @@ impl PlainBuilder {
         }
     }
 
-    pub fn chunk_modify(&mut self, bvh_nodes: &[BvhNode], round_cones: &[RoundCone]) -> Result<()> {
-        let (offset, dim) = calculate_offset_and_dim(bvh_nodes);
+    /// Fills the voxels inside the round cones with `fill_voxel_type`, 0 carves them out.
+    pub fn chunk_modify(
+        &mut self,
+        bvh_nodes: &[BvhNode],
+        round_cones: &[RoundCone],
+        fill_voxel_type: u32,
+    ) -> Result<()> {
+        self.modify_voxels(
+            bvh_nodes,
+            round_cones,
+            fill_voxel_type,
+            CHUNK_MODIFY_ANY_VOXEL,
+        )
+    }
 
-        update_buffers(&self.resources, offset, dim, round_cones, bvh_nodes)?;
+    /// Like [`Self::chunk_modify`], but only the voxels of `replaced_voxel_type` inside the
+    /// round cones are overwritten.
+    pub fn chunk_replace(
+        &mut self,
+        bvh_nodes: &[BvhNode],
+        round_cones: &[RoundCone],
+        replaced_voxel_type: u32,
+        fill_voxel_type: u32,
+    ) -> Result<()> {
+        self.modify_voxels(bvh_nodes, round_cones, fill_voxel_type, replaced_voxel_type)
+    }
+
+    fn modify_voxels(
+        &mut self,
+        bvh_nodes: &[BvhNode],
+        round_cones: &[RoundCone],
+        fill_voxel_type: u32,
+        replaced_voxel_type: u32,
+    ) -> Result<()> {
+        profile_scope!("chunk_modify");
+        let (offset, dim) = calculate_offset_and_dim(bvh_nodes);
 
+        update_buffers(
+            &self.resources,
+            offset,
+            dim,
+            round_cones,
+            bvh_nodes,
+            fill_voxel_type,
+            replaced_voxel_type,
+        )?;
+
+        // chunk inits on the compute queue may still write the atlas
+        self.vulkan_ctx.wait_compute()?;
         execute_one_time_command(
             self.vulkan_ctx.device(),
             self.vulkan_ctx.command_pool(),
@@ -253,8 +424,10 @@ impl PlainBuilder {
             dim: UVec3,
             round_cones: &[RoundCone],
             bvh_nodes: &[BvhNode],
+            fill_voxel_type: u32,
+            replaced_voxel_type: u32,
         ) -> Result<()> {
-            update_chunk_modify_info(resources, offset, dim, 1)?;
+            update_chunk_modify_info(resources, offset, dim, fill_voxel_type, replaced_voxel_type)?;
             update_round_cones(resources, round_cones)?;
             update_trunk_bvh_nodes(resources, bvh_nodes)?;
             return Ok(());
@@ -264,6 +437,7 @@ impl PlainBuilder {
                 offset: UVec3,
                 dim: UVec3,
                 fill_voxel_type: u32,
+                replaced_voxel_type: u32,
             ) -> Result<()> {
                 let data = StructMemberDataBuilder::from_buffer(&resources.chunk_modify_info)
                     .set_field("offset", PlainMemberTypeWithData::UVec3(offset.to_array()))
@@ -272,6 +446,10 @@ impl PlainBuilder {
                         "fill_voxel_type",
                         PlainMemberTypeWithData::UInt(fill_voxel_type),
                     )
+                    .set_field(
+                        "replaced_voxel_type",
+                        PlainMemberTypeWithData::UInt(replaced_voxel_type),
+                    )
                     .build()?;
                 resources.chunk_modify_info.fill_with_raw_u8(&data)?;
                 Ok(())
@@ -281,29 +459,30 @@ impl PlainBuilder {
                 resources: &PlainBuilderResources,
                 round_cones: &[RoundCone],
             ) -> Result<()> {
-                for (i, round_cone) in round_cones.iter().enumerate() {
-                    let data = StructMemberDataBuilder::from_buffer(&resources.round_cones)
-                        .set_field(
-                            "data.center_a",
-                            PlainMemberTypeWithData::Vec3(round_cone.center_a().to_array()),
-                        )
-                        .set_field(
-                            "data.center_b",
-                            PlainMemberTypeWithData::Vec3(round_cone.center_b().to_array()),
-                        )
-                        .set_field(
-                            "data.radius_a",
-                            PlainMemberTypeWithData::Float(round_cone.radius_a()),
-                        )
-                        .set_field(
-                            "data.radius_b",
-                            PlainMemberTypeWithData::Float(round_cone.radius_b()),
-                        )
-                        .build()?;
-                    resources
-                        .round_cones
-                        .fill_element_with_raw_u8(&data, i as u64)?;
+                let mut builder = StructArrayDataBuilder::from_buffer(&resources.round_cones);
+                for round_cone in round_cones {
+                    builder.push_element(|element| {
+                        element
+                            .set_field(
+                                "data.center_a",
+                                PlainMemberTypeWithData::Vec3(round_cone.center_a().to_array()),
+                            )
+                            .set_field(
+                                "data.center_b",
+                                PlainMemberTypeWithData::Vec3(round_cone.center_b().to_array()),
+                            )
+                            .set_field(
+                                "data.radius_a",
+                                PlainMemberTypeWithData::Float(round_cone.radius_a()),
+                            )
+                            .set_field(
+                                "data.radius_b",
+                                PlainMemberTypeWithData::Float(round_cone.radius_b()),
+                            );
+                    });
                 }
+                let data = builder.build()?;
+                resources.round_cones.fill_elements_with_raw_u8(&data, 0)?;
                 Ok(())
             }
 
@@ -311,31 +490,34 @@ impl PlainBuilder {
                 resources: &PlainBuilderResources,
                 bvh_nodes: &[BvhNode],
             ) -> Result<()> {
-                for (i, bvh_node) in bvh_nodes.iter().enumerate() {
+                let mut builder = StructArrayDataBuilder::from_buffer(&resources.trunk_bvh_nodes);
+                for bvh_node in bvh_nodes {
                     let combined_offset: u32 = if bvh_node.is_leaf {
                         let primitive_idx = bvh_node.data_offset;
                         0x8000_0000 | primitive_idx
                     } else {
                         bvh_node.left
                     };
-                    let data = StructMemberDataBuilder::from_buffer(&resources.trunk_bvh_nodes)
-                        .set_field(
-                            "data.aabb_min",
-                            PlainMemberTypeWithData::Vec3(bvh_node.aabb.min().to_array()),
-                        )
-                        .set_field(
-                            "data.aabb_max",
-                            PlainMemberTypeWithData::Vec3(bvh_node.aabb.max().to_array()),
-                        )
-                        .set_field(
-                            "data.offset",
-                            PlainMemberTypeWithData::UInt(combined_offset),
-                        )
-                        .build()?;
-                    resources
-                        .trunk_bvh_nodes
-                        .fill_element_with_raw_u8(&data, i as u64)?;
+                    builder.push_element(|element| {
+                        element
+                            .set_field(
+                                "data.aabb_min",
+                                PlainMemberTypeWithData::Vec3(bvh_node.aabb.min().to_array()),
+                            )
+                            .set_field(
+                                "data.aabb_max",
+                                PlainMemberTypeWithData::Vec3(bvh_node.aabb.max().to_array()),
+                            )
+                            .set_field(
+                                "data.offset",
+                                PlainMemberTypeWithData::UInt(combined_offset),
+                            );
+                    });
                 }
+                let data = builder.build()?;
+                resources
+                    .trunk_bvh_nodes
+                    .fill_elements_with_raw_u8(&data, 0)?;
                 Ok(())
             }
         }
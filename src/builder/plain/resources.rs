@@ -1,6 +1,9 @@
 use crate::{
     resource::Resource,
-    vkn::{Allocator, Buffer, BufferUsage, Device, Extent3D, ImageDesc, ShaderModule, Texture},
+    vkn::{
+        Allocator, Buffer, BufferUsage, Device, Extent3D, ImageDesc, IndirectDispatch,
+        ShaderModule, Texture,
+    },
 };
 use ash::vk;
 use glam::UVec3;
@@ -12,7 +15,8 @@ pub struct PlainBuilderResources {
     pub free_atlas: Resource<Texture>,
 
     pub region_info: Resource<Buffer>,
-    pub region_indirect: Resource<Buffer>,
+    pub terrain_gen_info: Resource<Buffer>,
+    pub region_indirect: IndirectDispatch,
     pub chunk_modify_info: Resource<Buffer>,
     pub round_cones: Resource<Buffer>,
     pub trunk_bvh_nodes: Resource<Buffer>,
@@ -24,13 +28,18 @@ impl PlainBuilderResources {
         allocator: Allocator,
         plain_atlas_dim: UVec3,
         free_atlas_dim: UVec3,
+        max_dispatch_group_count: UVec3,
         buffer_setup_sm: &ShaderModule,
+        chunk_init_sm: &ShaderModule,
         chunk_modify_sm: &ShaderModule,
     ) -> Self {
         let tex_desc = ImageDesc {
             extent: Extent3D::new(plain_atlas_dim.x, plain_atlas_dim.y, plain_atlas_dim.z),
             format: vk::Format::R8_UINT,
-            usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
+            // read back and written whole chunks at a time by world snapshots
+            usage: vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST,
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
@@ -93,15 +102,20 @@ impl PlainBuilderResources {
             gpu_allocator::MemoryLocation::CpuToGpu,
         );
 
-        let region_indirect_layout = buffer_setup_sm
-            .get_buffer_layout("B_RegionIndirect")
-            .unwrap();
-        let region_indirect = Buffer::from_buffer_layout(
+        let terrain_gen_info_layout = chunk_init_sm.get_buffer_layout("U_TerrainGenInfo").unwrap();
+        let terrain_gen_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            terrain_gen_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let region_indirect = IndirectDispatch::new(
             device.clone(),
             allocator.clone(),
-            region_indirect_layout.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::INDIRECT_BUFFER),
-            gpu_allocator::MemoryLocation::GpuOnly,
+            "region_indirect",
+            max_dispatch_group_count,
         );
 
         Self {
@@ -111,7 +125,8 @@ impl PlainBuilderResources {
             round_cones: Resource::new(round_cones),
             trunk_bvh_nodes: Resource::new(trunk_bvh_nodes),
             region_info: Resource::new(region_info),
-            region_indirect: Resource::new(region_indirect),
+            terrain_gen_info: Resource::new(terrain_gen_info),
+            region_indirect,
         }
     }
 }
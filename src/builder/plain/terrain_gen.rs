@@ -0,0 +1,80 @@
+use crate::vkn::{Buffer, PlainMemberTypeWithData, StructMemberDataBuilder};
+use anyhow::Result;
+
+/// The noise the terrain is shaped by, see `shader/include/terrain_gen.glsl`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainGenDesc {
+    pub seed: i32,
+    /// Height of the tallest hills, in chunks.
+    pub height_scale: f32,
+    pub frequency: f32,
+    pub octaves: i32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    /// Empty voxels below it are filled with water, in chunks.
+    pub sea_level: f32,
+}
+
+impl Default for TerrainGenDesc {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            height_scale: 0.4,
+            frequency: 1.0,
+            octaves: 5,
+            lacunarity: 2.6,
+            gain: 0.2,
+            sea_level: 0.1,
+        }
+    }
+}
+
+impl TerrainGenDesc {
+    /// Returns: whether anything was changed
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        changed |= ui
+            .add(egui::DragValue::new(&mut self.seed).prefix("Seed: "))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.height_scale, 0.05..=1.0).text("Height Scale"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.frequency, 0.1..=4.0).text("Frequency"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.octaves, 1..=8).text("Octaves"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.lacunarity, 1.0..=4.0).text("Lacunarity"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.gain, 0.0..=1.0).text("Gain"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.sea_level, 0.0..=1.0).text("Sea Level"))
+            .changed();
+        changed
+    }
+
+    /// Writes `U_TerrainGenInfo`.
+    pub fn update_info(&self, terrain_gen_info: &Buffer) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(terrain_gen_info)
+            .set_field("seed", PlainMemberTypeWithData::Int(self.seed))
+            .set_field(
+                "height_scale",
+                PlainMemberTypeWithData::Float(self.height_scale),
+            )
+            .set_field("frequency", PlainMemberTypeWithData::Float(self.frequency))
+            .set_field("octaves", PlainMemberTypeWithData::Int(self.octaves))
+            .set_field(
+                "lacunarity",
+                PlainMemberTypeWithData::Float(self.lacunarity),
+            )
+            .set_field("gain", PlainMemberTypeWithData::Float(self.gain))
+            .set_field("sea_level", PlainMemberTypeWithData::Float(self.sea_level))
+            .build()?;
+        terrain_gen_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+}
@@ -3,14 +3,16 @@ use anyhow::Result;
 use ash::vk;
 use glam::UVec3;
 pub use resources::*;
+use std::collections::HashMap;
 
 use crate::{
-    geom::UAabb3,
-    util::ShaderCompiler,
+    geom::{ChunkIdx, UAabb3},
+    util::{profile_scope, ShaderCompiler},
     vkn::{
-        execute_one_time_command, Allocator, Buffer, ClearValue, ColorClearValue, CommandBuffer,
-        ComputePipeline, DescriptorPool, Extent3D, PlainMemberTypeWithData, ShaderModule,
-        StructMemberDataBuilder, VulkanContext,
+        execute_one_time_command,
+        shader_structs::builder_scene_accel_update_scene_tex_comp::USceneTexUpdateInfo, Allocator,
+        Buffer, ClearValue, ColorClearValue, CommandBuffer, ComputePipeline, DescriptorPool,
+        Extent3D, ShaderModule, VulkanContext,
     },
 };
 
@@ -24,6 +26,12 @@ pub struct SceneAccelBuilder {
     #[allow(dead_code)]
     update_scene_tex_ppl: ComputePipeline,
     update_scene_tex_cmdbuf: CommandBuffer,
+    /// The compute timeline value of the latest scene texture update, the update info can't be
+    /// rewritten before it.
+    update_done_value: u64,
+
+    /// Chunk index <-> (node_offset, leaf_offset) currently written into the scene texture
+    resident_chunks: HashMap<ChunkIdx, (u64, u64)>,
 }
 
 impl SceneAccelBuilder {
@@ -65,6 +73,8 @@ impl SceneAccelBuilder {
             pool,
             update_scene_tex_ppl,
             update_scene_tex_cmdbuf,
+            update_done_value: 0,
+            resident_chunks: HashMap::new(),
         })
     }
 
@@ -73,7 +83,7 @@ impl SceneAccelBuilder {
         update_scene_tex_ppl: &ComputePipeline,
     ) -> CommandBuffer {
         let device = vulkan_ctx.device();
-        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.command_pool());
+        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.compute_command_pool());
         cmdbuf.begin(false);
 
         let extent = Extent3D {
@@ -108,22 +118,68 @@ impl SceneAccelBuilder {
 
     pub fn update_scene_tex(
         &mut self,
-        chunk_idx: UVec3,
+        chunk_idx: ChunkIdx,
         node_offset_for_chunk: u64,
         node_count_for_chunk: u64,
     ) -> Result<()> {
+        self.write_scene_tex(
+            chunk_idx,
+            node_offset_for_chunk,
+            node_count_for_chunk,
+            false,
+        )?;
+        self.resident_chunks
+            .insert(chunk_idx, (node_offset_for_chunk, node_count_for_chunk));
+        Ok(())
+    }
+
+    /// Marks the chunk as empty in the scene texture so the tracer skips it.
+    ///
+    /// Returns false if the chunk wasn't resident.
+    pub fn evict_chunk(&mut self, chunk_idx: ChunkIdx) -> Result<bool> {
+        if self.resident_chunks.remove(&chunk_idx).is_none() {
+            return Ok(false);
+        }
+        self.write_scene_tex(chunk_idx, 0, 0, true)?;
+        Ok(true)
+    }
+
+    /// Returns: (node_offset, leaf_offset) of the chunk if it is resident in the scene texture
+    pub fn chunk_entry(&self, chunk_idx: ChunkIdx) -> Option<(u64, u64)> {
+        self.resident_chunks.get(&chunk_idx).copied()
+    }
+
+    pub fn resident_chunk_count(&self) -> usize {
+        self.resident_chunks.len()
+    }
+
+    fn write_scene_tex(
+        &mut self,
+        chunk_idx: ChunkIdx,
+        node_offset_for_chunk: u64,
+        leaf_offset_for_chunk: u64,
+        clear_chunk: bool,
+    ) -> Result<()> {
+        profile_scope!("update_scene_tex");
+        // the previous update still reads the info and holds the command buffer
+        self.vulkan_ctx
+            .compute_timeline()
+            .wait(self.update_done_value)?;
         update_buffers(
             &self.resources.scene_tex_update_info,
-            chunk_idx,
+            chunk_idx.0,
             node_offset_for_chunk as u32,
-            node_count_for_chunk as u32,
+            leaf_offset_for_chunk as u32,
+            clear_chunk,
         )?;
 
-        self.update_scene_tex_cmdbuf
-            .submit(&self.vulkan_ctx.get_general_queue(), None);
-        self.vulkan_ctx
-            .device()
-            .wait_queue_idle(&self.vulkan_ctx.get_general_queue());
+        // nothing is read back and the frames wait for the update on the GPU, so the host moves
+        // on. The update itself waits for the frames in flight, they may trace the old entry
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        self.update_done_value = self.vulkan_ctx.submit_compute(
+            &self.update_scene_tex_cmdbuf,
+            &[(frame_timeline, frame_timeline.submitted_value())],
+        );
         return Ok(());
 
         fn update_buffers(
@@ -131,23 +187,14 @@ impl SceneAccelBuilder {
             chunk_idx: UVec3,
             node_offset_for_chunk: u32,
             leaf_offset_for_chunk: u32,
+            clear_chunk: bool,
         ) -> Result<()> {
-            let data = StructMemberDataBuilder::from_buffer(scene_tex_update_info)
-                .set_field(
-                    "chunk_idx",
-                    PlainMemberTypeWithData::UVec3(chunk_idx.to_array()),
-                )
-                .set_field(
-                    "node_offset_for_chunk",
-                    PlainMemberTypeWithData::UInt(node_offset_for_chunk),
-                )
-                .set_field(
-                    "leaf_offset_for_chunk",
-                    PlainMemberTypeWithData::UInt(leaf_offset_for_chunk),
-                )
-                .build()?;
-            scene_tex_update_info.fill_with_raw_u8(&data)?;
-            Ok(())
+            scene_tex_update_info.fill_struct(&USceneTexUpdateInfo {
+                chunk_idx: chunk_idx.to_array(),
+                node_offset_for_chunk,
+                leaf_offset_for_chunk,
+                clear_chunk: clear_chunk as u32,
+            })
         }
     }
 
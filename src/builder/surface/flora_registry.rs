@@ -0,0 +1,82 @@
+use crate::tracer::{gen_grass, gen_lavender, LodThresholds, Vertex};
+use anyhow::Result;
+use glam::Vec2;
+
+/// Index of a flora species in [`FLORA_TYPES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FloraType(u32);
+
+impl FloraType {
+    pub const GRASS: Self = Self(0);
+    pub const LAVENDER: Self = Self(1);
+
+    /// Every registered species, in registry order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..FLORA_TYPES.len() as u32).map(Self)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn desc(self) -> &'static FloraTypeDesc {
+        &FLORA_TYPES[self.index()]
+    }
+
+    /// The species saved under `key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::all().find(|flora_type| flora_type.desc().key == key)
+    }
+}
+
+/// Everything that sets one flora species apart, from where it grows to how it's drawn.
+pub struct FloraTypeDesc {
+    /// Shown in the GUI.
+    pub name: &'static str,
+    /// Written to save slots, renaming it breaks the saves that hold the species.
+    pub key: &'static str,
+    /// Builds the voxel mesh of one plant, the argument picks the far LOD's vertex layout.
+    pub mesh: fn(bool) -> Result<(Vec<Vertex>, Vec<u32>)>,
+    /// The species grows where the surface density noise, in [0, 1], falls in (x, y]. The bands
+    /// of different species shouldn't overlap, the first one containing the noise wins.
+    pub density_band: Vec2,
+    /// Default sRGB colors at the root and at the tip, tweakable in the GUI.
+    pub bottom_color: [u8; 3],
+    pub tip_color: [u8; 3],
+    /// How far the plant bends in the wind, 1.0 is the sway of grass.
+    pub wind_sway: f32,
+    /// Default LOD thresholds of the species' chunks.
+    pub lod: LodThresholds,
+}
+
+const CHUNK_FLORA_LOD: LodThresholds = LodThresholds {
+    switch_size: 1.4,
+    hysteresis: 0.1,
+    transition_range: 0.0,
+};
+
+/// Every flora species the surface pass can grow, a new one only needs an entry here. The
+/// order is the [`FloraType`] index, so [`FloraType::GRASS`] and [`FloraType::LAVENDER`] stay
+/// on top.
+pub static FLORA_TYPES: &[FloraTypeDesc] = &[
+    FloraTypeDesc {
+        name: "Grass",
+        key: "grass",
+        mesh: gen_grass,
+        density_band: Vec2::new(0.603, 1.0),
+        bottom_color: [61, 163, 59],
+        tip_color: [168, 227, 0],
+        wind_sway: 1.0,
+        lod: CHUNK_FLORA_LOD,
+    },
+    FloraTypeDesc {
+        name: "Lavender",
+        key: "lavender",
+        mesh: gen_lavender,
+        density_band: Vec2::new(0.6, 0.603),
+        bottom_color: [74, 165, 0],
+        tip_color: [85, 0, 207],
+        wind_sway: 1.0,
+        lod: CHUNK_FLORA_LOD,
+    },
+];
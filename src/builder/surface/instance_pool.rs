@@ -0,0 +1,206 @@
+use super::Instance;
+use crate::{
+    resource::Resource,
+    util::{AllocationStrategy, BufferAllocation, FirstFitAllocator},
+    vkn::{Allocator, Buffer, BufferUsage, Device, TimelineSemaphore},
+};
+use anyhow::Result;
+use ash::vk;
+
+const INSTANCE_SIZE: u64 = std::mem::size_of::<Instance>() as u64;
+
+/// A slice of the [`InstancePool`], holding the instances of one flora type of a chunk or of
+/// one tree's leaves.
+#[derive(Debug, Default)]
+pub struct InstanceResource {
+    allocation: Option<BufferAllocation>,
+    /// Instances drawn, counted from the start of the slice.
+    pub instances_len: u32,
+}
+
+impl InstanceResource {
+    /// The index of the slice's first instance in the pool buffer.
+    pub fn first_instance(&self) -> u32 {
+        (self.byte_offset() / INSTANCE_SIZE) as u32
+    }
+
+    /// Where the slice starts in the pool buffer, in bytes.
+    pub fn byte_offset(&self) -> u64 {
+        self.allocation.as_ref().map_or(0, |a| a.offset)
+    }
+
+    /// Instances the slice has room for.
+    pub fn capacity(&self) -> u32 {
+        self.allocation
+            .as_ref()
+            .map_or(0, |a| (a.size / INSTANCE_SIZE) as u32)
+    }
+}
+
+/// One instance buffer shared by all flora chunks and trees.
+///
+/// Each owner gets an [`InstanceResource`] slice sized to what it holds instead of a buffer
+/// sized for the worst case, the slices are handed out by a [`FirstFitAllocator`].
+///
+/// Rebuilt instances go to a fresh back slice that replaces the drawn one through
+/// [`InstancePool::publish`], so no write lands in a slice a frame in flight is reading. Slices
+/// let go of stay allocated until the frame timeline passes the last frame that could draw them.
+pub struct InstancePool {
+    pub instances_buf: Resource<Buffer>,
+    allocator: FirstFitAllocator,
+    frame_timeline: TimelineSemaphore,
+    /// Slices let go of, along with the timeline value of the last frame that could draw them.
+    retired: Vec<(BufferAllocation, u64)>,
+}
+
+impl InstancePool {
+    pub fn new(
+        device: Device,
+        allocator: Allocator,
+        frame_timeline: TimelineSemaphore,
+        max_instances: u64,
+    ) -> Self {
+        let instances_buf = Buffer::new_sized(
+            device,
+            allocator,
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::VERTEX_BUFFER
+                    | vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            INSTANCE_SIZE * max_instances,
+        );
+        Self {
+            instances_buf: Resource::new(instances_buf),
+            allocator: FirstFitAllocator::new(INSTANCE_SIZE * max_instances),
+            frame_timeline,
+            retired: Vec::new(),
+        }
+    }
+
+    /// Makes room for exactly `capacity` instances in `slice`, the instances it already holds
+    /// are kept as far as they fit, even if the slice has to move.
+    pub fn resize(&mut self, slice: &mut InstanceResource, capacity: u32) -> Result<()> {
+        if capacity == 0 {
+            self.free(slice);
+            return Ok(());
+        }
+        let size = capacity as u64 * INSTANCE_SIZE;
+
+        let Some(old) = slice.allocation.clone() else {
+            let allocation = self.allocate(size).map_err(|e| {
+                anyhow::anyhow!(
+                    "Instance pool has no room for {} instances: {}",
+                    capacity,
+                    e
+                )
+            })?;
+            slice.allocation = Some(allocation);
+            return Ok(());
+        };
+
+        let kept = self
+            .instances_buf
+            .read_back_range(old.offset, old.size.min(size))?;
+        let allocation = self.allocator.resize(old.id, size).map_err(|e| {
+            anyhow::anyhow!(
+                "Instance pool has no room for {} instances: {}",
+                capacity,
+                e
+            )
+        })?;
+        if allocation.offset != old.offset {
+            self.instances_buf.fill_at(&kept, allocation.offset)?;
+        }
+        slice.allocation = Some(allocation);
+        slice.instances_len = slice.instances_len.min(capacity);
+        Ok(())
+    }
+
+    /// Returns the slice's room to the pool once the frames that could draw it are done,
+    /// leaving it empty.
+    pub fn free(&mut self, slice: &mut InstanceResource) {
+        if let Some(allocation) = slice.allocation.take() {
+            // slices only change between frames, so the latest submitted frame is the last
+            // one that could draw it
+            self.retired
+                .push((allocation, self.frame_timeline.submitted_value()));
+        }
+        slice.instances_len = 0;
+    }
+
+    /// Swaps the filled `back` slice in for the drawn `front` one, which is freed.
+    pub fn publish(&mut self, front: &mut InstanceResource, back: InstanceResource) {
+        let mut old_front = std::mem::replace(front, back);
+        self.free(&mut old_front);
+    }
+
+    fn allocate(&mut self, size: u64) -> Result<BufferAllocation> {
+        self.collect_retired(self.frame_timeline.completed_value()?);
+        if let Ok(allocation) = self.allocator.allocate(size) {
+            return Ok(allocation);
+        }
+
+        // the room may still be held by slices of frames in flight, wait for them
+        if let Some(last_value) = self.retired.iter().map(|(_, value)| *value).max() {
+            self.frame_timeline.wait(last_value)?;
+            self.collect_retired(last_value);
+        }
+        self.allocator
+            .allocate(size)
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// Deallocates the retired slices whose frames are done by `completed_value`.
+    fn collect_retired(&mut self, completed_value: u64) {
+        let allocator = &mut self.allocator;
+        self.retired.retain(|(allocation, value)| {
+            if *value > completed_value {
+                return true;
+            }
+            allocator.deallocate(allocation.id).unwrap();
+            false
+        });
+    }
+
+    /// Writes `instances` into `slice`, starting at its `first` instance.
+    pub fn write(
+        &self,
+        slice: &InstanceResource,
+        first: u32,
+        instances: &[Instance],
+    ) -> Result<()> {
+        if first as usize + instances.len() > slice.capacity() as usize {
+            return Err(anyhow::anyhow!(
+                "Writing {} instances at {} overflows a slice of {}",
+                instances.len(),
+                first,
+                slice.capacity()
+            ));
+        }
+        self.instances_buf.fill_at(
+            instances,
+            slice.byte_offset() + first as u64 * INSTANCE_SIZE,
+        )
+    }
+
+    /// The first `len` instances of `slice`.
+    pub fn read(&self, slice: &InstanceResource, len: u32) -> Result<Vec<Instance>> {
+        let len = len.min(slice.capacity());
+        let raw_data = self
+            .instances_buf
+            .read_back_range(slice.byte_offset(), len as u64 * INSTANCE_SIZE)?;
+        Ok(bytemuck::pod_collect_to_vec(&raw_data))
+    }
+
+    /// Instances held by all slices, against what the pool has room for.
+    #[allow(dead_code)]
+    pub fn usage(&self) -> (u64, u64) {
+        (
+            self.allocator.allocated_size() / INSTANCE_SIZE,
+            self.allocator.total_size() / INSTANCE_SIZE,
+        )
+    }
+}
@@ -1,18 +1,27 @@
 mod resources;
+
+mod instance_pool;
+
+mod flora_registry;
 use super::PlainBuilderResources;
 use crate::{
-    geom::UAabb3,
-    util::ShaderCompiler,
+    gameplay::{PathWearDesc, PathWearMap},
+    geom::{ChunkIdx, UAabb3, VoxelPos, WorldPos},
+    util::{profile_scope, ShaderCompiler},
     vkn::{
-        Allocator, Buffer, ClearValue, ColorClearValue, CommandBuffer, ComputePipeline,
-        DescriptorPool, Extent3D, PlainMemberTypeWithData, ShaderModule, StructMemberDataBuilder,
-        StructMemberDataReader, VulkanContext, WriteDescriptorSet,
+        execute_one_time_command, Allocator, Buffer, ColorClearValue, CommandBuffer,
+        ComputePipeline, DescriptorPool, Extent3D, MemoryBarrier, PipelineBarrier,
+        PlainMemberTypeWithData, ShaderModule, StructMemberDataBuilder, StructMemberDataReader,
+        VulkanContext, WriteDescriptorSet,
     },
 };
 use anyhow::Result;
 use ash::vk;
-use glam::UVec3;
+pub use flora_registry::*;
+use glam::{UVec2, UVec3, Vec3};
+pub use instance_pool::*;
 pub use resources::*;
+use std::collections::HashMap;
 
 pub struct SurfaceBuilder {
     vulkan_ctx: VulkanContext,
@@ -22,9 +31,12 @@ pub struct SurfaceBuilder {
     pool: DescriptorPool,
 
     make_surface_ppl: ComputePipeline,
+    cut_flora_ppl: ComputePipeline,
 
     chunk_bound: UAabb3,
     voxel_dim_per_chunk: UVec3,
+    path_wear_extent: UVec2,
+    grass_wear_threshold: f32,
 }
 
 impl SurfaceBuilder {
@@ -45,15 +57,38 @@ impl SurfaceBuilder {
             "main",
         )
         .unwrap();
+        let cut_flora_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/builder/surface/cut_flora.comp",
+            "main",
+        )
+        .unwrap();
 
         let resources = SurfaceResources::new(
             device.clone(),
             allocator,
             voxel_dim_per_chunk,
+            vulkan_ctx.frame_timeline().clone(),
             &make_surface_sm,
+            &cut_flora_sm,
             chunk_bound,
         );
 
+        // the surface stays in GENERAL so rebuilds on the compute queue need no layout transition
+        execute_one_time_command(
+            device,
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                resources.surface.get_image().record_transition_barrier(
+                    cmdbuf,
+                    0,
+                    vk::ImageLayout::GENERAL,
+                );
+            },
+        );
+
         let pool = DescriptorPool::new(device).unwrap();
 
         let make_surface_ppl = ComputePipeline::new(
@@ -62,45 +97,43 @@ impl SurfaceBuilder {
             &pool,
             &[&resources, plain_builder_resources],
         );
+        let cut_flora_ppl = ComputePipeline::new(device, &cut_flora_sm, &pool, &[&resources]);
+        cut_flora_ppl.write_descriptor_set(
+            1,
+            WriteDescriptorSet::new_buffer_write(0, &resources.instances.pool.instances_buf),
+        );
 
         Self {
             vulkan_ctx,
             resources,
             pool,
             make_surface_ppl,
+            cut_flora_ppl,
             chunk_bound,
             voxel_dim_per_chunk,
+            path_wear_extent: PathWearMap::extent_of(&chunk_bound),
+            grass_wear_threshold: PathWearDesc::default().grass_threshold,
         }
     }
 
-    fn update_grass_instance_set(&self, chunk_id: UVec3) {
-        let chunk_resources = &self
-            .resources
-            .instances
-            .chunk_flora_instances
-            .iter()
-            .find(|(_, resources)| resources.chunk_id == chunk_id)
-            .unwrap()
-            .1;
-
-        self.make_surface_ppl.write_descriptor_set(
-            1,
-            WriteDescriptorSet::new_buffer_write(
-                0,
-                &chunk_resources.get(FloraType::Grass).instances_buf,
-            ),
-        );
-        self.make_surface_ppl.write_descriptor_set(
-            1,
-            WriteDescriptorSet::new_buffer_write(
-                1,
-                &chunk_resources.get(FloraType::Lavender).instances_buf,
-            ),
-        );
+    /// Keeps the path wear the next surface rebuilds place flora by up to date, the wear itself is
+    /// only copied when `is_wear_changed`.
+    pub fn update_path_wear(
+        &mut self,
+        path_wear: &PathWearMap,
+        is_wear_changed: bool,
+    ) -> Result<()> {
+        self.grass_wear_threshold = path_wear.desc.grass_threshold;
+        if is_wear_changed {
+            self.resources.path_wear.fill(path_wear.wear())?;
+        }
+        Ok(())
     }
 
     /// Returns active_voxel_len
-    pub fn build_surface(&mut self, chunk_id: UVec3) -> Result<u32> {
+    pub fn build_surface(&mut self, chunk_idx: ChunkIdx) -> Result<u32> {
+        profile_scope!("build_surface");
+        let chunk_id = chunk_idx.0;
         if !self.chunk_bound.in_bound(chunk_id) {
             return Err(anyhow::anyhow!("Chunk ID out of bounds"));
         }
@@ -115,21 +148,44 @@ impl SurfaceBuilder {
             atlas_read_offset,
             atlas_read_dim,
             true,
+            self.path_wear_extent,
+            self.grass_wear_threshold,
         )?;
 
         cleanup_make_surface_result(&self.resources.make_surface_result)?;
+        self.resources
+            .flora_instance_lens
+            .fill(&vec![0u32; FLORA_TYPES.len()])?;
 
-        self.update_grass_instance_set(chunk_id);
-
-        let cmdbuf = CommandBuffer::new(device, self.vulkan_ctx.command_pool());
+        // builder resources only, so the frames in flight aren't waited for
+        let compute_timeline = self.vulkan_ctx.compute_timeline();
+        let cmdbuf = CommandBuffer::new(device, self.vulkan_ctx.compute_command_pool());
         cmdbuf.begin(true);
 
-        self.resources.surface.get_image().record_clear(
+        // the previous chunk's contree build may still read the surface
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::TRANSFER_WRITE,
+            )],
+        )
+        .record_insert(device, &cmdbuf);
+        self.resources.surface.get_image().record_clear_in_general(
             &cmdbuf,
-            Some(vk::ImageLayout::GENERAL),
             0,
-            ClearValue::Color(ColorClearValue::UInt([0, 0, 0, 0])),
+            ColorClearValue::UInt([0, 0, 0, 0]),
         );
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            )],
+        )
+        .record_insert(device, &cmdbuf);
 
         let extent = Extent3D {
             width: self.voxel_dim_per_chunk.x,
@@ -141,22 +197,78 @@ impl SurfaceBuilder {
 
         cmdbuf.end();
 
-        cmdbuf.submit(&self.vulkan_ctx.get_general_queue(), None);
-
-        device.wait_queue_idle(&self.vulkan_ctx.get_general_queue());
+        // the instance counts size the back slices below
+        let surface_done_value = self.vulkan_ctx.submit_compute(&cmdbuf, &[]);
+        compute_timeline.wait(surface_done_value)?;
 
-        let (active_voxel_len, grass_instance_len, lavender_instance_len) =
-            get_result(&self.resources.make_surface_result);
+        let active_voxel_len = get_result(&self.resources.make_surface_result);
+        let flora_instance_lens: Vec<u32> =
+            bytemuck::pod_collect_to_vec(&self.resources.flora_instance_lens.read_back()?);
 
-        let chunk_resources = self
-            .resources
-            .instances
+        let instances = &mut self.resources.instances;
+        let chunk_resources = instances
             .chunk_flora_instances
             .iter_mut()
             .find(|(_, resources)| resources.chunk_id == chunk_id)
             .unwrap();
-        chunk_resources.1.get_mut(FloraType::Grass).instances_len = grass_instance_len;
-        chunk_resources.1.get_mut(FloraType::Lavender).instances_len = lavender_instance_len;
+        // the new instances go to back slices, the drawn ones may still be read by a frame in
+        // flight until they're published
+        let mut backs = Vec::with_capacity(FLORA_TYPES.len());
+        for flora_type in FloraType::all() {
+            // the shader drops what doesn't fit but still counts it
+            let len =
+                flora_instance_lens[flora_type.index()].min(MAX_FLORA_INSTANCES_PER_CHUNK as u32);
+            let mut back = InstanceResource::default();
+            instances.pool.resize(&mut back, len)?;
+            back.instances_len = len;
+            backs.push(back);
+        }
+        // the back slices aren't drawn yet, the copy only has to land before the planted
+        // instances are read back below
+        let instance_size = std::mem::size_of::<Instance>() as u64;
+        let copy_cmdbuf = CommandBuffer::new(device, self.vulkan_ctx.compute_command_pool());
+        copy_cmdbuf.begin(true);
+        for (flora_type, back) in FloraType::all().zip(&backs) {
+            if back.instances_len == 0 {
+                continue;
+            }
+            self.resources
+                .flora_instances_scratch
+                .record_copy_to_buffer(
+                    &copy_cmdbuf,
+                    &instances.pool.instances_buf,
+                    instance_size * back.instances_len as u64,
+                    instance_size * MAX_FLORA_INSTANCES_PER_CHUNK * flora_type.index() as u64,
+                    back.byte_offset(),
+                );
+        }
+        copy_cmdbuf.end();
+        let copy_done_value = self.vulkan_ctx.submit_compute(&copy_cmdbuf, &[]);
+        compute_timeline.wait(copy_done_value)?;
+        for (flora_type, back) in FloraType::all().zip(backs) {
+            instances
+                .pool
+                .publish(chunk_resources.1.get_mut(flora_type), back);
+        }
+        // a rebuilt chunk starts fully grown
+        chunk_resources.1.grass_regrowth = FloraRegrowth {
+            grown_len: chunk_resources.1.get(FloraType::GRASS).instances_len,
+            ..Default::default()
+        };
+        for flora_type in FloraType::all() {
+            let planted: Vec<Instance> = chunk_resources
+                .1
+                .planted
+                .iter()
+                .filter(|(ty, _)| *ty == flora_type)
+                .map(|(_, instance)| *instance)
+                .collect();
+            if !planted.is_empty() {
+                chunk_resources
+                    .1
+                    .append_instances(&mut instances.pool, flora_type, &planted)?;
+            }
+        }
 
         return Ok(active_voxel_len);
 
@@ -165,6 +277,8 @@ impl SurfaceBuilder {
             atlas_read_offset: UVec3,
             atlas_read_dim: UVec3,
             is_crossing_boundary: bool,
+            path_wear_extent: UVec2,
+            grass_wear_threshold: f32,
         ) -> Result<()> {
             let data = StructMemberDataBuilder::from_buffer(make_surface_info)
                 .set_field(
@@ -179,6 +293,22 @@ impl SurfaceBuilder {
                     "is_crossing_boundary",
                     PlainMemberTypeWithData::UInt(if is_crossing_boundary { 1 } else { 0 }),
                 )
+                .set_field(
+                    "path_wear_extent",
+                    PlainMemberTypeWithData::UVec2(path_wear_extent.to_array()),
+                )
+                .set_field(
+                    "grass_wear_threshold",
+                    PlainMemberTypeWithData::Float(grass_wear_threshold),
+                )
+                .set_field(
+                    "flora_type_count",
+                    PlainMemberTypeWithData::UInt(FLORA_TYPES.len() as u32),
+                )
+                .set_field(
+                    "max_flora_instances",
+                    PlainMemberTypeWithData::UInt(MAX_FLORA_INSTANCES_PER_CHUNK as u32),
+                )
                 .build()?;
             make_surface_info.fill_with_raw_u8(&data)?;
             Ok(())
@@ -187,41 +317,276 @@ impl SurfaceBuilder {
         fn cleanup_make_surface_result(make_surface_result: &Buffer) -> Result<()> {
             let data = StructMemberDataBuilder::from_buffer(make_surface_result)
                 .set_field("active_voxel_len", PlainMemberTypeWithData::UInt(0))
-                .set_field("grass_instance_len", PlainMemberTypeWithData::UInt(0))
-                .set_field("lavender_instance_len", PlainMemberTypeWithData::UInt(0))
                 .build()?;
             make_surface_result.fill_with_raw_u8(&data)?;
             Ok(())
         }
 
-        /// Returns: (active_voxel_len, grass_instance_len, lavender_instance_len)
-        fn get_result(frag_img_build_result: &Buffer) -> (u32, u32, u32) {
+        /// Returns active_voxel_len
+        fn get_result(frag_img_build_result: &Buffer) -> u32 {
             let layout = &frag_img_build_result.get_layout().unwrap().root_member;
             let raw_data = frag_img_build_result.read_back().unwrap();
             let reader = StructMemberDataReader::new(layout, &raw_data);
 
-            let active_voxel_len = if let PlainMemberTypeWithData::UInt(val) =
+            if let PlainMemberTypeWithData::UInt(val) =
                 reader.get_field("active_voxel_len").unwrap()
             {
                 val
             } else {
                 panic!("Expected UInt type for active_voxel_len")
+            }
+        }
+    }
+
+    /// Drops the generated flora of a chunk that's unloaded, hand planted flora is kept for the
+    /// next [`Self::build_surface`].
+    pub fn clear_surface(&mut self, chunk_idx: ChunkIdx) {
+        let instances = &mut self.resources.instances;
+        let Some((_, chunk_resources)) = instances
+            .chunk_flora_instances
+            .iter_mut()
+            .find(|(_, resources)| resources.chunk_id == chunk_idx.0)
+        else {
+            return;
+        };
+        for flora_type in FloraType::all() {
+            instances.pool.free(chunk_resources.get_mut(flora_type));
+        }
+        chunk_resources.grass_regrowth = FloraRegrowth::default();
+    }
+
+    /// Hides the grass inside a vertical cylinder of `radius` reaching `height` down from `center`,
+    /// all in world units. The cut grass regrows over `regrowth_hours` in-game hours, see
+    /// [`Self::regrow_grass`]. Returns the number of instances cut.
+    pub fn cut_grass(
+        &mut self,
+        center: WorldPos,
+        radius: f32,
+        height: f32,
+        regrowth_hours: f32,
+    ) -> Result<u32> {
+        profile_scope!("cut_grass");
+        let voxel_scale = self.voxel_dim_per_chunk.as_vec3();
+        let center_voxel = center.to_voxel_space();
+        let radius_voxel = radius * voxel_scale.x;
+        let height_voxel = height * voxel_scale.y;
+        let center = center.0;
+
+        let mut total_cut_len = 0;
+        for chunk_idx in 0..self.resources.instances.chunk_flora_instances.len() {
+            let (chunk_aabb, chunk_resources) =
+                &self.resources.instances.chunk_flora_instances[chunk_idx];
+            let instances_len = chunk_resources.get(FloraType::GRASS).instances_len;
+            let is_overlapping = center.x + radius >= chunk_aabb.min().x
+                && center.x - radius <= chunk_aabb.max().x
+                && center.z + radius >= chunk_aabb.min().z
+                && center.z - radius <= chunk_aabb.max().z
+                && center.y >= chunk_aabb.min().y
+                && center.y - height <= chunk_aabb.max().y;
+            if instances_len == 0 || !is_overlapping {
+                continue;
+            }
+
+            let cut_len = self.cut_chunk_grass(
+                chunk_idx,
+                instances_len,
+                center_voxel,
+                radius_voxel,
+                height_voxel,
+            )?;
+            if cut_len == 0 {
+                continue;
+            }
+            total_cut_len += cut_len;
+
+            let chunk_resources = &mut self.resources.instances.chunk_flora_instances[chunk_idx].1;
+            let grass = chunk_resources.get_mut(FloraType::GRASS);
+            grass.instances_len -= cut_len;
+            let visible_len = grass.instances_len;
+            let regrowth = &mut chunk_resources.grass_regrowth;
+            regrowth.rate = (regrowth.grown_len - visible_len) as f32 / regrowth_hours.max(1e-3);
+        }
+        Ok(total_cut_len)
+    }
+
+    /// Partitions the drawn grass instances of one chunk so the cut ones move behind the kept
+    /// ones, returns the number of cut instances.
+    ///
+    /// The partitioned instances go to a back slice that's published in place of the drawn one.
+    fn cut_chunk_grass(
+        &mut self,
+        chunk_idx: usize,
+        instances_len: u32,
+        center_voxel: Vec3,
+        radius_voxel: f32,
+        height_voxel: f32,
+    ) -> Result<u32> {
+        let device = self.vulkan_ctx.device();
+        let InstanceResources {
+            pool,
+            chunk_flora_instances,
+            ..
+        } = &mut self.resources.instances;
+        let slice = chunk_flora_instances[chunk_idx].1.get(FloraType::GRASS);
+        let mut back = InstanceResource::default();
+        pool.resize(&mut back, slice.capacity())?;
+        back.instances_len = slice.instances_len;
+        let instances_buf = &pool.instances_buf;
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.cut_flora_info)
+            .set_field(
+                "center",
+                PlainMemberTypeWithData::Vec3(center_voxel.to_array()),
+            )
+            .set_field("radius", PlainMemberTypeWithData::Float(radius_voxel))
+            .set_field("height", PlainMemberTypeWithData::Float(height_voxel))
+            .set_field(
+                "instances_offset",
+                PlainMemberTypeWithData::UInt(slice.first_instance()),
+            )
+            .set_field(
+                "instances_len",
+                PlainMemberTypeWithData::UInt(instances_len),
+            )
+            .build()?;
+        self.resources.cut_flora_info.fill_with_raw_u8(&data)?;
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.cut_flora_result)
+            .set_field("kept_len", PlainMemberTypeWithData::UInt(0))
+            .set_field("cut_len", PlainMemberTypeWithData::UInt(0))
+            .build()?;
+        self.resources.cut_flora_result.fill_with_raw_u8(&data)?;
+
+        execute_one_time_command(
+            device,
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                self.cut_flora_ppl
+                    .record(cmdbuf, Extent3D::new(instances_len, 1, 1), None);
+                PipelineBarrier::new(
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vec![MemoryBarrier::new(
+                        vk::AccessFlags::SHADER_WRITE,
+                        vk::AccessFlags::TRANSFER_READ,
+                    )],
+                )
+                .record_insert(device, cmdbuf);
+                self.resources.cut_flora_scratch.record_copy_to_buffer(
+                    cmdbuf,
+                    instances_buf,
+                    std::mem::size_of::<Instance>() as u64 * instances_len as u64,
+                    0,
+                    back.byte_offset(),
+                );
+                // the grass cut earlier and still regrowing sits past the drawn instances
+                let tail_len = slice.capacity() - instances_len;
+                if tail_len > 0 {
+                    let tail_offset = instances_len as u64 * std::mem::size_of::<Instance>() as u64;
+                    instances_buf.record_copy_to_buffer(
+                        cmdbuf,
+                        instances_buf,
+                        std::mem::size_of::<Instance>() as u64 * tail_len as u64,
+                        slice.byte_offset() + tail_offset,
+                        back.byte_offset() + tail_offset,
+                    );
+                }
+            },
+        );
+        pool.publish(
+            chunk_flora_instances[chunk_idx].1.get_mut(FloraType::GRASS),
+            back,
+        );
+
+        let layout = &self
+            .resources
+            .cut_flora_result
+            .get_layout()
+            .unwrap()
+            .root_member;
+        let raw_data = self.resources.cut_flora_result.read_back()?;
+        let reader = StructMemberDataReader::new(layout, &raw_data);
+        match reader
+            .get_field("cut_len")
+            .map_err(|e| anyhow::anyhow!(e))?
+        {
+            PlainMemberTypeWithData::UInt(cut_len) => Ok(cut_len),
+            _ => Err(anyhow::anyhow!("Expected UInt type for cut_len")),
+        }
+    }
+
+    /// Places flora by hand at `positions`, they survive rebuilds of their chunk.
+    pub fn plant_flora(&mut self, flora_type: FloraType, positions: &[VoxelPos]) -> Result<()> {
+        let mut instances_per_chunk: HashMap<ChunkIdx, Vec<Instance>> = HashMap::new();
+        for pos in positions {
+            instances_per_chunk
+                .entry(pos.chunk_idx())
+                .or_default()
+                .push(Instance {
+                    pos: pos.0.to_array(),
+                    ty: GRASS_TYPE_NORMAL,
+                });
+        }
+
+        let InstanceResources {
+            pool,
+            chunk_flora_instances,
+            ..
+        } = &mut self.resources.instances;
+        for (chunk_idx, instances) in instances_per_chunk {
+            let Some((_, chunk_resources)) = chunk_flora_instances
+                .iter_mut()
+                .find(|(_, resources)| resources.chunk_id == chunk_idx.0)
+            else {
+                continue;
             };
-            let grass_instance_len = if let PlainMemberTypeWithData::UInt(val) =
-                reader.get_field("grass_instance_len").unwrap()
-            {
-                val
-            } else {
-                panic!("Expected UInt type for grass_instance_len")
-            };
-            let lavender_instance_len = if let PlainMemberTypeWithData::UInt(val) =
-                reader.get_field("lavender_instance_len").unwrap()
-            {
-                val
-            } else {
-                panic!("Expected UInt type for lavender_instance_len")
-            };
-            (active_voxel_len, grass_instance_len, lavender_instance_len)
+            chunk_resources.append_instances(pool, flora_type, &instances)?;
+            chunk_resources
+                .planted
+                .extend(instances.into_iter().map(|instance| (flora_type, instance)));
+        }
+        Ok(())
+    }
+
+    /// Regrows all cut grass at once and removes the hand placed flora.
+    pub fn reset_flora_edits(&mut self) -> Result<()> {
+        let mut chunks_to_rebuild = Vec::new();
+        for (_, chunk_resources) in &mut self.resources.instances.chunk_flora_instances {
+            let grown_len = chunk_resources.grass_regrowth.grown_len;
+            chunk_resources.get_mut(FloraType::GRASS).instances_len = grown_len;
+            chunk_resources.grass_regrowth.rate = 0.0;
+            chunk_resources.grass_regrowth.progress = 0.0;
+            if !chunk_resources.planted.is_empty() {
+                chunk_resources.planted.clear();
+                chunks_to_rebuild.push(chunk_resources.chunk_id);
+            }
+        }
+        // planted instances are mixed into the buffers, rebuilding is the simplest way out
+        for chunk_id in chunks_to_rebuild {
+            self.build_surface(ChunkIdx(chunk_id))?;
+        }
+        Ok(())
+    }
+
+    /// Grows cut grass back by `delta_hours` in-game hours, restoring a few instances at a time.
+    pub fn regrow_grass(&mut self, delta_hours: f32) {
+        for (_, chunk_resources) in &mut self.resources.instances.chunk_flora_instances {
+            let regrowth = &mut chunk_resources.grass_regrowth;
+            if regrowth.rate <= 0.0 {
+                continue;
+            }
+            regrowth.progress += regrowth.rate * delta_hours;
+            let regrown_len = regrowth.progress.floor();
+            regrowth.progress -= regrown_len;
+            let grown_len = regrowth.grown_len;
+
+            let grass = chunk_resources.get_mut(FloraType::GRASS);
+            grass.instances_len = (grass.instances_len + regrown_len as u32).min(grown_len);
+            if grass.instances_len == grown_len {
+                chunk_resources.grass_regrowth.rate = 0.0;
+                chunk_resources.grass_regrowth.progress = 0.0;
+            }
         }
     }
 
@@ -1,90 +1,84 @@
+use super::{FloraType, InstancePool, InstanceResource, FLORA_TYPES};
 use crate::{
+    gameplay::PathWearMap,
     geom::{Aabb3, UAabb3},
     resource::Resource,
-    vkn::{Allocator, Buffer, BufferUsage, Device, Extent3D, ImageDesc, ShaderModule, Texture},
+    vkn::{
+        Allocator, Buffer, BufferUsage, Device, Extent3D, ImageDesc, ShaderModule, Texture,
+        TimelineSemaphore,
+    },
 };
+use anyhow::Result;
 use ash::vk;
 use glam::{UVec3, Vec3};
 use resource_container_derive::ResourceContainer;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum FloraType {
-    Grass,
-    Lavender,
-}
+/// Most instances of one flora type a chunk can hold.
+pub const MAX_FLORA_INSTANCES_PER_CHUNK: u64 = 10000;
+
+/// Capacity of the instance pool shared by all chunks and trees.
+pub const INSTANCE_POOL_CAPACITY: u64 = 512 * 1024;
+
+/// Mirrors `GRASS_TYPE_NORMAL` in `grass_type.glsl`.
+pub const GRASS_TYPE_NORMAL: u32 = 1;
 
 // TODO: use some reflection from shader side so i don't need to manually define this again
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Instance {
     pub pos: [u32; 3],
     pub ty: u32,
 }
 
-pub struct InstanceResource {
-    pub instances_buf: Resource<Buffer>,
-    pub instances_len: u32,
-}
-
-impl InstanceResource {
-    pub fn new(device: Device, allocator: Allocator, max_instances: u64) -> Self {
-        let instance_size = std::mem::size_of::<Instance>();
-        let instances_buf = Buffer::new_sized(
-            device,
-            allocator,
-            BufferUsage::from_flags(
-                vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
-            ),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-            instance_size as u64 * max_instances,
-        );
-
-        Self {
-            instances_buf: Resource::new(instances_buf),
-            instances_len: 0,
-        }
-    }
-}
-
 pub struct TreeLeavesInstance {
-    #[allow(dead_code)]
     pub tree_id: u32,
     pub aabb: Aabb3,
     pub resources: InstanceResource,
 }
 
 impl TreeLeavesInstance {
-    pub fn new(tree_id: u32, aabb: Aabb3, device: Device, allocator: Allocator) -> Self {
-        let resources = InstanceResource::new(device, allocator, 10000);
+    pub fn new(tree_id: u32, aabb: Aabb3) -> Self {
         Self {
             tree_id,
             aabb,
-            resources,
+            resources: InstanceResource::default(),
         }
     }
 }
 
+/// Tracks the grass of a chunk that was cut and grows back.
+///
+/// Cut instances sit right after the drawn ones in the instance buffer, so regrowing is just
+/// raising the drawn instance count back to `grown_len`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FloraRegrowth {
+    /// Instance count of the chunk with nothing cut.
+    pub grown_len: u32,
+    /// Instances regrown per in-game hour.
+    pub rate: f32,
+    /// Fraction of the next instance regrown so far.
+    pub progress: f32,
+}
+
 pub struct FloraInstanceResources {
-    #[allow(dead_code)]
     pub chunk_id: UVec3,
     pub resources: HashMap<FloraType, InstanceResource>,
+    pub grass_regrowth: FloraRegrowth,
+    /// Hand placed instances, appended again whenever the chunk's surface is rebuilt.
+    pub planted: Vec<(FloraType, Instance)>,
 }
 
 impl FloraInstanceResources {
-    pub fn new(device: Device, allocator: Allocator, chunk_id: UVec3) -> Self {
-        let mut resources = HashMap::new();
-        resources.insert(
-            FloraType::Grass,
-            InstanceResource::new(device.clone(), allocator.clone(), 10000),
-        );
-        resources.insert(
-            FloraType::Lavender,
-            InstanceResource::new(device.clone(), allocator.clone(), 10000),
-        );
+    pub fn new(chunk_id: UVec3) -> Self {
+        let resources = FloraType::all()
+            .map(|flora_type| (flora_type, InstanceResource::default()))
+            .collect();
         Self {
             chunk_id,
             resources,
+            grass_regrowth: FloraRegrowth::default(),
+            planted: Vec::new(),
         }
     }
 
@@ -95,15 +89,57 @@ impl FloraInstanceResources {
     pub fn get_mut(&mut self, flora_type: FloraType) -> &mut InstanceResource {
         self.resources.get_mut(&flora_type).unwrap()
     }
+
+    /// Adds instances right after the drawn ones, ahead of any cut grass still regrowing.
+    pub fn append_instances(
+        &mut self,
+        pool: &mut InstancePool,
+        flora_type: FloraType,
+        instances: &[Instance],
+    ) -> Result<()> {
+        let drawn_len = self.get(flora_type).instances_len as usize;
+        let stored_len = if flora_type == FloraType::GRASS {
+            self.grass_regrowth.grown_len as usize
+        } else {
+            drawn_len
+        };
+        if (stored_len + instances.len()) as u64 > MAX_FLORA_INSTANCES_PER_CHUNK {
+            return Err(anyhow::anyhow!(
+                "Chunk {} has no room for {} more {} instances",
+                self.chunk_id,
+                instances.len(),
+                flora_type.desc().name
+            ));
+        }
+
+        let mut stored = pool.read(self.get(flora_type), stored_len as u32)?;
+        stored.splice(drawn_len..drawn_len, instances.iter().copied());
+        let mut back = InstanceResource::default();
+        pool.resize(&mut back, stored.len() as u32)?;
+        pool.write(&back, 0, &stored)?;
+        back.instances_len = (drawn_len + instances.len()) as u32;
+        pool.publish(self.get_mut(flora_type), back);
+
+        if flora_type == FloraType::GRASS {
+            self.grass_regrowth.grown_len += instances.len() as u32;
+        }
+        Ok(())
+    }
 }
 
 pub struct InstanceResources {
+    pub pool: InstancePool,
     pub chunk_flora_instances: Vec<(Aabb3, FloraInstanceResources)>,
     pub leaves_instances: HashMap<u32, TreeLeavesInstance>,
 }
 
 impl InstanceResources {
-    pub fn new(device: Device, allocator: Allocator, chunk_dim: UAabb3) -> Self {
+    pub fn new(
+        device: Device,
+        allocator: Allocator,
+        frame_timeline: TimelineSemaphore,
+        chunk_dim: UAabb3,
+    ) -> Self {
         /// A margin is added becaues the boundary grasses can sway out of the chunk to a certain extent.
         fn compute_chunk_world_aabb(chunk_id: UVec3, margin: f32) -> Aabb3 {
             let chunk_min = chunk_id.as_vec3();
@@ -122,17 +158,14 @@ impl InstanceResources {
                 for z in chunk_dim.min().z..chunk_dim.max().z {
                     let chunk_offset = UVec3::new(x, y, z);
                     let chunk_aabb = compute_chunk_world_aabb(chunk_offset, 0.2);
-                    let flora_resources = FloraInstanceResources::new(
-                        device.clone(),
-                        allocator.clone(),
-                        chunk_offset,
-                    );
+                    let flora_resources = FloraInstanceResources::new(chunk_offset);
                     chunk_flora_instances.push((chunk_aabb, flora_resources));
                 }
             }
         }
 
         Self {
+            pool: InstancePool::new(device, allocator, frame_timeline, INSTANCE_POOL_CAPACITY),
             chunk_flora_instances,
             leaves_instances: HashMap::new(),
         }
@@ -159,6 +192,18 @@ pub struct SurfaceResources {
     pub surface: Resource<Texture>,
     pub make_surface_info: Resource<Buffer>,
     pub make_surface_result: Resource<Buffer>,
+    pub cut_flora_info: Resource<Buffer>,
+    pub cut_flora_result: Resource<Buffer>,
+    pub cut_flora_scratch: Resource<Buffer>,
+    /// Where the surface pass writes a chunk's instances before they move into the pool, each
+    /// flora type in its own stretch of [`MAX_FLORA_INSTANCES_PER_CHUNK`] instances.
+    pub flora_instances_scratch: Resource<Buffer>,
+    /// Instances the surface pass placed, by flora type.
+    pub flora_instance_lens: Resource<Buffer>,
+    /// The density band of each flora type, see [`super::FloraTypeDesc::density_band`].
+    pub flora_density_bands: Resource<Buffer>,
+    /// A copy of the [`PathWearMap`] read when flora is placed.
+    pub path_wear: Resource<Buffer>,
     pub instances: InstanceResources,
 }
 
@@ -167,7 +212,9 @@ impl SurfaceResources {
         device: Device,
         allocator: Allocator,
         voxel_dim_per_chunk: UVec3,
+        frame_timeline: TimelineSemaphore,
         make_surface_sm: &ShaderModule,
+        cut_flora_sm: &ShaderModule,
         chunk_dim: UAabb3,
     ) -> Self {
         let surface_desc = ImageDesc {
@@ -207,12 +254,100 @@ impl SurfaceResources {
             gpu_allocator::MemoryLocation::CpuToGpu,
         );
 
-        let instances = InstanceResources::new(device.clone(), allocator.clone(), chunk_dim);
+        let cut_flora_info_layout = cut_flora_sm.get_buffer_layout("U_CutFloraInfo").unwrap();
+        let cut_flora_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            cut_flora_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let cut_flora_result_layout = cut_flora_sm.get_buffer_layout("B_CutFloraResult").unwrap();
+        let cut_flora_result = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            cut_flora_result_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let create_scratch = |instance_count: u64| {
+            Buffer::new_sized(
+                device.clone(),
+                allocator.clone(),
+                BufferUsage::from_flags(
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC,
+                ),
+                gpu_allocator::MemoryLocation::GpuOnly,
+                std::mem::size_of::<Instance>() as u64 * instance_count,
+            )
+        };
+        let cut_flora_scratch = create_scratch(MAX_FLORA_INSTANCES_PER_CHUNK);
+        let flora_instances_scratch =
+            create_scratch(MAX_FLORA_INSTANCES_PER_CHUNK * FLORA_TYPES.len() as u64);
+
+        let flora_instance_lens_layout = make_surface_sm
+            .get_buffer_layout("B_FloraInstanceLens")
+            .unwrap();
+        let flora_instance_lens = Buffer::from_buffer_layout_arraylike(
+            device.clone(),
+            allocator.clone(),
+            flora_instance_lens_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            FLORA_TYPES.len() as u64,
+        );
+
+        let flora_density_bands_layout = make_surface_sm
+            .get_buffer_layout("B_FloraDensityBands")
+            .unwrap();
+        let flora_density_bands = Buffer::from_buffer_layout_arraylike(
+            device.clone(),
+            allocator.clone(),
+            flora_density_bands_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            FLORA_TYPES.len() as u64,
+        );
+        flora_density_bands
+            .fill(
+                &FLORA_TYPES
+                    .iter()
+                    .map(|desc| desc.density_band)
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+
+        let path_wear_extent = PathWearMap::extent_of(&chunk_dim);
+        let path_wear = Buffer::new_sized(
+            device.clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            (path_wear_extent.x * path_wear_extent.y * std::mem::size_of::<f32>() as u32) as u64,
+        );
+        path_wear
+            .fill(&vec![
+                0.0f32;
+                (path_wear_extent.x * path_wear_extent.y) as usize
+            ])
+            .unwrap();
+
+        let instances =
+            InstanceResources::new(device.clone(), allocator.clone(), frame_timeline, chunk_dim);
 
         Self {
             surface: Resource::new(surface),
             make_surface_info: Resource::new(make_surface_info),
             make_surface_result: Resource::new(make_surface_result),
+            cut_flora_info: Resource::new(cut_flora_info),
+            cut_flora_result: Resource::new(cut_flora_result),
+            cut_flora_scratch: Resource::new(cut_flora_scratch),
+            flora_instances_scratch: Resource::new(flora_instances_scratch),
+            flora_instance_lens: Resource::new(flora_instance_lens),
+            flora_density_bands: Resource::new(flora_density_bands),
+            path_wear: Resource::new(path_wear),
             instances,
         }
     }
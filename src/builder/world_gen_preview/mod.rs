@@ -0,0 +1,291 @@
+mod resources;
+use anyhow::Result;
+use ash::vk;
+use glam::{UVec3, Vec2, Vec3};
+pub use resources::*;
+
+use crate::{
+    builder::TerrainGenDesc,
+    util::{profile_scope, ShaderCompiler},
+    vkn::{
+        execute_one_time_command, Allocator, CommandBuffer, ComputePipeline, DescriptorPool, Image,
+        MemoryBarrier, PipelineBarrier, PlainMemberTypeWithData, ShaderModule,
+        StructMemberDataBuilder, VulkanContext,
+    },
+};
+
+/// The preview voxelizes the chunk at 1/4 of the full resolution.
+const PREVIEW_DOWNSCALE: u32 = 4;
+const PREVIEW_EXTENT: u32 = 256;
+/// Trees past this count aren't marked in the preview.
+const MAX_PREVIEW_TREE_COUNT: u64 = 64;
+/// Gap between the preview and the corner of the screen, in pixels.
+const PREVIEW_MARGIN: i32 = 16;
+
+/// Renders a thumbnail of one chunk with the given terrain parameters, without touching the
+/// world. The chunk is voxelized at a fraction of the resolution and raymarched from a fixed
+/// orbit camera on the compute queue, the result is blitted over the screen output.
+pub struct WorldGenPreview {
+    vulkan_ctx: VulkanContext,
+    resources: WorldGenPreviewResources,
+
+    #[allow(dead_code)]
+    pool: DescriptorPool,
+    #[allow(dead_code)]
+    preview_init_ppl: ComputePipeline,
+    #[allow(dead_code)]
+    preview_render_ppl: ComputePipeline,
+    preview_cmdbuf: CommandBuffer,
+    /// The compute timeline value of the latest build, the buffers can't be rewritten before it.
+    build_done_value: u64,
+}
+
+impl WorldGenPreview {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        shader_compiler: &ShaderCompiler,
+        voxel_dim: u32,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let preview_init_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/builder/world_gen_preview/preview_init.comp",
+            "main",
+        )
+        .unwrap();
+        let preview_render_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/builder/world_gen_preview/preview_render.comp",
+            "main",
+        )
+        .unwrap();
+
+        let resources = WorldGenPreviewResources::new(
+            device,
+            allocator,
+            voxel_dim / PREVIEW_DOWNSCALE,
+            PREVIEW_EXTENT,
+            MAX_PREVIEW_TREE_COUNT,
+            &preview_init_sm,
+            &preview_render_sm,
+        );
+
+        let preview_init_ppl = ComputePipeline::new(device, &preview_init_sm, &pool, &[&resources]);
+        let preview_render_ppl =
+            ComputePipeline::new(device, &preview_render_sm, &pool, &[&resources]);
+
+        // the compute queue can't transition layouts
+        execute_one_time_command(
+            device,
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                resources
+                    .preview_voxels
+                    .get_image()
+                    .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+                resources.preview_tex.get_image().record_transition_barrier(
+                    cmdbuf,
+                    0,
+                    vk::ImageLayout::GENERAL,
+                );
+            },
+        );
+
+        let preview_cmdbuf = Self::record_preview_cmdbuf(
+            &vulkan_ctx,
+            &resources,
+            &preview_init_ppl,
+            &preview_render_ppl,
+        );
+
+        Ok(Self {
+            vulkan_ctx,
+            resources,
+            pool,
+            preview_init_ppl,
+            preview_render_ppl,
+            preview_cmdbuf,
+            build_done_value: 0,
+        })
+    }
+
+    fn record_preview_cmdbuf(
+        vulkan_ctx: &VulkanContext,
+        resources: &WorldGenPreviewResources,
+        preview_init_ppl: &ComputePipeline,
+        preview_render_ppl: &ComputePipeline,
+    ) -> CommandBuffer {
+        let shader_access_pipeline_barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vec![MemoryBarrier::new_shader_access()],
+        );
+
+        let device = vulkan_ctx.device();
+        let cmdbuf = CommandBuffer::new(device, vulkan_ctx.compute_command_pool());
+        cmdbuf.begin(false);
+
+        preview_init_ppl.record(
+            &cmdbuf,
+            resources.preview_voxels.get_image().get_desc().extent,
+            None,
+        );
+        shader_access_pipeline_barrier.record_insert(device, &cmdbuf);
+        preview_render_ppl.record(
+            &cmdbuf,
+            resources.preview_tex.get_image().get_desc().extent,
+            None,
+        );
+
+        cmdbuf.end();
+        cmdbuf
+    }
+
+    /// Whether the latest build is still running, a new one would have to wait for it.
+    pub fn is_busy(&self) -> Result<bool> {
+        Ok(self.vulkan_ctx.compute_timeline().completed_value()? < self.build_done_value)
+    }
+
+    /// Renders the chunk at `chunk_idx` with `terrain_gen_desc` into the preview.
+    ///
+    /// `tree_positions` are the xz of the trees in the chunk, in chunk local [0, 1] space.
+    pub fn build(
+        &mut self,
+        chunk_idx: UVec3,
+        terrain_gen_desc: &TerrainGenDesc,
+        tree_positions: &[Vec2],
+        sun_dir: Vec3,
+        ground_color: Vec3,
+        tree_color: Vec3,
+    ) -> Result<()> {
+        profile_scope!("world_gen_preview_build");
+        // the previous build still reads the buffers and holds the command buffer
+        self.vulkan_ctx
+            .compute_timeline()
+            .wait(self.build_done_value)?;
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.preview_chunk_info)
+            .set_field(
+                "chunk_idx",
+                PlainMemberTypeWithData::UVec3(chunk_idx.to_array()),
+            )
+            .build()?;
+        self.resources.preview_chunk_info.fill_with_raw_u8(&data)?;
+
+        terrain_gen_desc.update_info(&self.resources.terrain_gen_info)?;
+
+        let tree_positions =
+            &tree_positions[..tree_positions.len().min(MAX_PREVIEW_TREE_COUNT as usize)];
+        if !tree_positions.is_empty() {
+            self.resources.preview_trees.fill(tree_positions)?;
+        }
+
+        // looks at the chunk diagonally from above one of its corners
+        let target = Vec3::new(0.5, 0.2, 0.5);
+        let (yaw, pitch) = (45.0_f32.to_radians(), 35.0_f32.to_radians());
+        let camera_front = -Vec3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        let camera_pos = target - camera_front * 1.6;
+        let camera_right = camera_front.cross(Vec3::Y).normalize();
+        let camera_up = camera_right.cross(camera_front);
+
+        let data = StructMemberDataBuilder::from_buffer(&self.resources.preview_render_info)
+            .set_field(
+                "camera_pos",
+                PlainMemberTypeWithData::Vec3(camera_pos.to_array()),
+            )
+            .set_field(
+                "camera_front",
+                PlainMemberTypeWithData::Vec3(camera_front.to_array()),
+            )
+            .set_field(
+                "camera_right",
+                PlainMemberTypeWithData::Vec3(camera_right.to_array()),
+            )
+            .set_field(
+                "camera_up",
+                PlainMemberTypeWithData::Vec3(camera_up.to_array()),
+            )
+            .set_field(
+                "tan_half_fov",
+                PlainMemberTypeWithData::Float((30.0_f32.to_radians()).tan()),
+            )
+            .set_field("sun_dir", PlainMemberTypeWithData::Vec3(sun_dir.to_array()))
+            .set_field(
+                "ground_color",
+                PlainMemberTypeWithData::Vec3(ground_color.to_array()),
+            )
+            .set_field(
+                "tree_color",
+                PlainMemberTypeWithData::Vec3(tree_color.to_array()),
+            )
+            .set_field(
+                "tree_count",
+                PlainMemberTypeWithData::UInt(tree_positions.len() as u32),
+            )
+            .build()?;
+        self.resources.preview_render_info.fill_with_raw_u8(&data)?;
+
+        // the frames in flight may still blit the previous preview
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        self.build_done_value = self.vulkan_ctx.submit_compute(
+            &self.preview_cmdbuf,
+            &[(frame_timeline, frame_timeline.submitted_value())],
+        );
+        Ok(())
+    }
+
+    /// Blits the preview into the top right corner of `dst`, which is left in `GENERAL`.
+    pub fn record_overlay(&self, cmdbuf: &CommandBuffer, dst: &Image) {
+        let src = self.resources.preview_tex.get_image();
+        let dst_extent = dst.get_desc().extent;
+        // shrinks with small windows so it never covers more than a third of the screen
+        let size = (PREVIEW_EXTENT as i32)
+            .min(dst_extent.width as i32 / 3)
+            .min(dst_extent.height as i32 / 3);
+        if size <= 0 {
+            return;
+        }
+
+        src.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        dst.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+
+        let mut region = src.get_blit_region();
+        let dst_x = dst_extent.width as i32 - PREVIEW_MARGIN - size;
+        region.dst_offsets = [
+            vk::Offset3D {
+                x: dst_x,
+                y: PREVIEW_MARGIN,
+                z: 0,
+            },
+            vk::Offset3D {
+                x: dst_x + size,
+                y: PREVIEW_MARGIN + size,
+                z: 1,
+            },
+        ];
+        unsafe {
+            self.vulkan_ctx.device().cmd_blit_image(
+                cmdbuf.as_raw(),
+                src.as_raw(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                dst.as_raw(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+                vk::Filter::LINEAR,
+            );
+        }
+
+        src.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+        dst.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    resource::Resource,
+    vkn::{Allocator, Buffer, BufferUsage, Device, Extent3D, ImageDesc, ShaderModule, Texture},
+};
+use ash::vk;
+use resource_container_derive::ResourceContainer;
+
+#[derive(ResourceContainer)]
+pub struct WorldGenPreviewResources {
+    pub preview_chunk_info: Resource<Buffer>,
+    pub terrain_gen_info: Resource<Buffer>,
+    pub preview_render_info: Resource<Buffer>,
+    /// Chunk local positions of the trees the placer puts into the previewed chunk.
+    pub preview_trees: Resource<Buffer>,
+    /// The previewed chunk at a fraction of the resolution, voxel types like the chunk atlas.
+    pub preview_voxels: Resource<Texture>,
+    pub preview_tex: Resource<Texture>,
+}
+
+impl WorldGenPreviewResources {
+    pub fn new(
+        device: &Device,
+        allocator: Allocator,
+        voxel_dim: u32,
+        preview_extent: u32,
+        max_tree_count: u64,
+        preview_init_sm: &ShaderModule,
+        preview_render_sm: &ShaderModule,
+    ) -> Self {
+        let preview_chunk_info_layout = preview_init_sm
+            .get_buffer_layout("U_PreviewChunkInfo")
+            .unwrap();
+        let preview_chunk_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            preview_chunk_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let terrain_gen_info_layout = preview_init_sm
+            .get_buffer_layout("U_TerrainGenInfo")
+            .unwrap();
+        let terrain_gen_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            terrain_gen_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let preview_render_info_layout = preview_render_sm
+            .get_buffer_layout("U_PreviewRenderInfo")
+            .unwrap();
+        let preview_render_info = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            preview_render_info_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+
+        let preview_trees_layout = preview_render_sm
+            .get_buffer_layout("B_PreviewTrees")
+            .unwrap();
+        let preview_trees = Buffer::from_buffer_layout_arraylike(
+            device.clone(),
+            allocator.clone(),
+            preview_trees_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            max_tree_count,
+        );
+
+        let preview_voxels_desc = ImageDesc {
+            extent: Extent3D::new(voxel_dim, voxel_dim, voxel_dim),
+            format: vk::Format::R8_UINT,
+            usage: vk::ImageUsageFlags::STORAGE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        };
+        let preview_voxels = Texture::new(
+            device.clone(),
+            allocator.clone(),
+            &preview_voxels_desc,
+            &Default::default(),
+        );
+
+        let preview_tex_desc = ImageDesc {
+            extent: Extent3D::new(preview_extent, preview_extent, 1),
+            format: vk::Format::R8G8B8A8_UNORM,
+            // blitted over the screen output
+            usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        };
+        let preview_tex = Texture::new(
+            device.clone(),
+            allocator.clone(),
+            &preview_tex_desc,
+            &Default::default(),
+        );
+
+        Self {
+            preview_chunk_info: Resource::new(preview_chunk_info),
+            terrain_gen_info: Resource::new(terrain_gen_info),
+            preview_render_info: Resource::new(preview_render_info),
+            preview_trees: Resource::new(preview_trees),
+            preview_voxels: Resource::new(preview_voxels),
+            preview_tex: Resource::new(preview_tex),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Constants shared with the shaders, generated from `constants.toml` by the build script.
+//!
+//! Every shader is compiled with the same values defined as macros, see `SHADER_MACROS`.
+
+include!(concat!(env!("OUT_DIR"), "/constants.rs"));
@@ -4,6 +4,7 @@ use crate::vkn::CommandBuffer;
 use crate::vkn::FormatOverride;
 use crate::vkn::ImageDesc;
 use crate::vkn::RenderPass;
+use crate::vkn::RenderingTarget;
 use crate::vkn::TextureRegion;
 use crate::vkn::Viewport;
 use crate::vkn::VulkanContext;
@@ -34,7 +35,8 @@ pub struct EguiRenderer {
 
     pool: DescriptorPool,
     managed_textures: HashMap<TextureId, Texture>,
-    frames: Option<Mesh>,
+    /// One mesh per frame slot, the frames in flight still read theirs.
+    frames: Vec<Option<Mesh>>,
 
     textures_to_free: Option<Vec<TextureId>>,
 
@@ -52,6 +54,7 @@ impl EguiRenderer {
         allocator: Allocator,
         compiler: &ShaderCompiler,
         render_pass: &RenderPass,
+        frame_count: usize,
     ) -> Self {
         let device = vulkan_ctx.device();
 
@@ -66,7 +69,7 @@ impl EguiRenderer {
             device,
             &egui_vert_sm,
             &egui_frag_sm,
-            render_pass,
+            RenderingTarget::RenderPass(render_pass),
             &GraphicsPipelineDesc {
                 format_overrides: vec![FormatOverride {
                     location: 2,
@@ -78,6 +81,7 @@ impl EguiRenderer {
             &pool,
             &[],
         );
+        Self::add_frame_slots(&gui_ppl, &pool, frame_count);
 
         let egui_context = egui::Context::default();
         let egui_winit_state = egui_winit::State::new(
@@ -97,7 +101,7 @@ impl EguiRenderer {
             egui_frag_sm,
             pool,
             managed_textures: HashMap::new(),
-            frames: None,
+            frames: (0..frame_count).map(|_| None).collect(),
             textures_to_free: None,
 
             egui_context,
@@ -120,7 +124,7 @@ impl EguiRenderer {
             self.vulkan_context.device(),
             &self.egui_vert_sm,
             &self.egui_frag_sm,
-            render_pass,
+            RenderingTarget::RenderPass(render_pass),
             &GraphicsPipelineDesc {
                 format_overrides: vec![FormatOverride {
                     location: 2,
@@ -132,6 +136,13 @@ impl EguiRenderer {
             &self.pool,
             &[],
         );
+        Self::add_frame_slots(&self.gui_ppl, &self.pool, self.frames.len());
+    }
+
+    fn add_frame_slots(gui_ppl: &GraphicsPipeline, pool: &DescriptorPool, frame_count: usize) {
+        for _ in 1..frame_count {
+            gui_ppl.add_frame_slot(pool).unwrap();
+        }
     }
 
     /// Free egui managed textures.
@@ -201,16 +212,7 @@ impl EguiRenderer {
                     )
                     .unwrap();
 
-                self.gui_ppl.write_descriptor_set(
-                    0,
-                    WriteDescriptorSet::new_texture_write(
-                        0,
-                        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                        &texture,
-                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    ),
-                );
-
+                // bound by `cmd_draw`, the descriptor sets of the frames in flight stay untouched
                 self.managed_textures.insert(*id, texture);
             }
         }
@@ -393,16 +395,19 @@ impl EguiRenderer {
         self.clipped_primitives = Some(clipped_primitives);
     }
 
+    /// The GPU must be done with the frame last recorded with `frame_slot`.
     pub fn record_command_buffer(
         &mut self,
         device: &Device,
         cmdbuf: &CommandBuffer,
         render_area: Extent2D,
+        frame_slot: usize,
     ) {
+        self.gui_ppl.select_frame_slot(frame_slot);
         Self::cmd_draw(
             &self.gui_ppl,
             device,
-            &mut self.frames,
+            &mut self.frames[frame_slot],
             &self.gui_ppl,
             &mut self.managed_textures,
             &mut self.allocator,
@@ -0,0 +1,49 @@
+use crate::app::App;
+use crate::audio::{AudioBus, BusSettings};
+use anyhow::Result;
+
+/// Sound effects and music.
+pub struct Audio<'a> {
+    app: &'a mut App,
+}
+
+impl<'a> Audio<'a> {
+    pub(crate) fn new(app: &'a mut App) -> Self {
+        Self { app }
+    }
+
+    /// Plays a sound file once, not positioned in the world.
+    pub fn play_sound(&self, path: &str, volume_db: f32) -> Result<()> {
+        self.app
+            .spatial_sound_manager()
+            .add_non_spatial_source(path, volume_db, AudioBus::Sfx)?;
+        Ok(())
+    }
+
+    /// Plays a sound file once on the ui bus, e.g. for clicks in a menu.
+    pub fn play_ui_sound(&self, path: &str, volume_db: f32) -> Result<()> {
+        self.app
+            .spatial_sound_manager()
+            .add_non_spatial_source(path, volume_db, AudioBus::Ui)?;
+        Ok(())
+    }
+
+    pub fn bus(&self, bus: AudioBus) -> BusSettings {
+        self.app.spatial_sound_manager().mixer().bus(bus)
+    }
+
+    pub fn set_bus(&mut self, bus: AudioBus, settings: BusSettings) -> Result<()> {
+        let spatial_sound_manager = self.app.spatial_sound_manager();
+        let mut mixer = spatial_sound_manager.mixer();
+        mixer.set_bus(bus, settings);
+        spatial_sound_manager.set_mixer(mixer)
+    }
+
+    pub fn music_volume_db(&self) -> f32 {
+        self.app.music_manager().volume_db()
+    }
+
+    pub fn set_music_volume_db(&mut self, volume_db: f32) {
+        self.app.music_manager_mut().set_volume_db(volume_db);
+    }
+}
@@ -0,0 +1,64 @@
+//! The public face of the crate, everything else stays crate private.
+
+mod audio;
+pub use audio::*;
+
+mod renderer;
+pub use renderer::*;
+
+mod world;
+pub use world::*;
+
+pub use crate::app::{CameraKeyframe, CameraPath, HeadlessRenderDesc};
+
+use crate::app::{App, AppController};
+use anyhow::Result;
+use winit::event_loop::EventLoop;
+
+/// A running instance of the game, handed to the update callback of [`Engine::run`] once per
+/// frame.
+pub struct Engine {
+    app: App,
+}
+
+impl Engine {
+    /// Opens the window and runs until it is closed, `update` is called right before each frame
+    /// is rendered.
+    pub fn run(update: impl FnMut(&mut Engine) + 'static) -> Result<()> {
+        crate::util::start_profiler();
+        crate::util::install_crash_handler();
+
+        let mut controller = AppController::new(Box::new(update));
+        let event_loop = EventLoop::builder().build()?;
+        event_loop.run_app(&mut controller)?;
+        Ok(())
+    }
+
+    /// Renders a sequence of frames into image files without a window, for turntables, videos
+    /// and comparing renders across changes.
+    pub fn render_headless(desc: &HeadlessRenderDesc) -> Result<()> {
+        crate::util::start_profiler();
+        crate::util::install_crash_handler();
+        AppController::run_headless(desc)
+    }
+
+    pub(crate) fn new(app: App) -> Self {
+        Self { app }
+    }
+
+    pub(crate) fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn world(&mut self) -> World<'_> {
+        World::new(&mut self.app)
+    }
+
+    pub fn renderer(&mut self) -> Renderer<'_> {
+        Renderer::new(&mut self.app)
+    }
+
+    pub fn audio(&mut self) -> Audio<'_> {
+        Audio::new(&mut self.app)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::app::App;
+use glam::Vec3;
+
+/// The camera and the lighting of the rendered frame.
+pub struct Renderer<'a> {
+    app: &'a mut App,
+}
+
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(app: &'a mut App) -> Self {
+        Self { app }
+    }
+
+    pub fn camera_position(&self) -> Vec3 {
+        self.app.tracer().camera_position()
+    }
+
+    /// Yaw and pitch, in radians.
+    pub fn camera_orientation(&self) -> (f32, f32) {
+        self.app.tracer().camera_orientation()
+    }
+
+    /// Teleports the camera, the accumulated frame history is dropped with it.
+    ///
+    /// A position below the ground is moved up onto the closest spot the player can stand on.
+    pub fn set_camera_pose(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        self.app.teleport_camera(position, yaw, pitch);
+    }
+
+    /// 0.0 is midnight, 0.5 is noon.
+    pub fn time_of_day(&self) -> f32 {
+        self.app.time_of_day()
+    }
+
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.app.set_time_of_day(time_of_day);
+    }
+}
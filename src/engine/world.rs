@@ -0,0 +1,83 @@
+use crate::app::App;
+use crate::app::WorldEdit;
+use anyhow::Result;
+use glam::Vec3;
+
+pub use crate::tracer::VoxelMaterial;
+
+/// Edits to the island, recorded the same way as the player's so they end up in the save slots.
+pub struct World<'a> {
+    app: &'a mut App,
+}
+
+impl<'a> World<'a> {
+    pub(crate) fn new(app: &'a mut App) -> Self {
+        Self { app }
+    }
+
+    /// Plants a tree with the current tree settings, `position` is where its trunk meets the
+    /// ground in world units.
+    pub fn plant_tree(&mut self, position: Vec3, seed: u64, rotation: f32) -> Result<()> {
+        self.app.apply_world_edit(WorldEdit::PlantTree {
+            position,
+            seed,
+            rotation,
+        })?;
+        Ok(())
+    }
+
+    /// Removes the tree standing nearest to `position`, in world units.
+    pub fn remove_tree(&mut self, position: Vec3) -> Result<()> {
+        self.app
+            .apply_world_edit(WorldEdit::RemoveTree { position })?;
+        Ok(())
+    }
+
+    /// Cuts the grass within `radius` of `center` and up to `height` below it, returns how many
+    /// grass blades were cut.
+    pub fn cut_grass(&mut self, center: Vec3, radius: f32, height: f32) -> Result<u32> {
+        self.app.apply_world_edit(WorldEdit::CutGrass {
+            center,
+            radius,
+            height,
+        })
+    }
+
+    /// Carves out the voxels within `radius` of `center`, in world units.
+    pub fn dig_voxels(&mut self, center: Vec3, radius: f32) -> Result<()> {
+        self.app.apply_world_edit(WorldEdit::ModifyVoxels {
+            center,
+            radius,
+            material: None,
+        })?;
+        Ok(())
+    }
+
+    /// Fills the voxels within `radius` of `center` with `material`, in world units.
+    pub fn place_voxels(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        material: VoxelMaterial,
+    ) -> Result<()> {
+        self.app.apply_world_edit(WorldEdit::ModifyVoxels {
+            center,
+            radius,
+            material: Some(material),
+        })?;
+        Ok(())
+    }
+
+    /// Grows the seeded procedural forest, replacing any hand planted trees.
+    pub fn grow_forest(&mut self) -> Result<()> {
+        self.app.generate_procedural_trees()
+    }
+
+    pub fn save(&self, slot: usize) -> Result<()> {
+        self.app.save_to_slot(slot)
+    }
+
+    pub fn load(&mut self, slot: usize) -> Result<()> {
+        self.app.load_from_slot(slot)
+    }
+}
@@ -1,18 +1,96 @@
-use crate::audio::SpatialSoundManager;
+use crate::audio::{AudioBus, SpatialSoundManager};
+use crate::tracer::VoxelMaterial;
 use anyhow::Result;
 use glam::Vec3;
 use rand::Rng;
+use std::collections::HashMap;
 
-pub struct PlayerClipCaches {
+/// The ground the footsteps sound of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FootstepSurface {
+    Grass,
+    Dirt,
+    Rock,
+    Sand,
+}
+
+impl FootstepSurface {
+    const ALL: [FootstepSurface; 4] = [
+        FootstepSurface::Grass,
+        FootstepSurface::Dirt,
+        FootstepSurface::Rock,
+        FootstepSurface::Sand,
+    ];
+
+    /// Grass grows on the dirt, `is_ground_bare` tells the worn paths from it.
+    fn from_material(material: Option<VoxelMaterial>, is_ground_bare: bool) -> Option<Self> {
+        match material? {
+            VoxelMaterial::Dirt if is_ground_bare => Some(FootstepSurface::Dirt),
+            VoxelMaterial::Dirt | VoxelMaterial::Leaf => Some(FootstepSurface::Grass),
+            VoxelMaterial::Rock | VoxelMaterial::Trunk => Some(FootstepSurface::Rock),
+            VoxelMaterial::Sand => Some(FootstepSurface::Sand),
+            // wading makes no footsteps
+            VoxelMaterial::Water => None,
+        }
+    }
+
+    /// The clips are named `{prefix}{sample}_{index:02}.wav`.
+    fn clip_prefix(self) -> &'static str {
+        match self {
+            FootstepSurface::Grass => {
+                "assets/sfx/Footsteps SFX - Undergrowth & Leaves/TomWinandySFX - FS_UndergrowthLeaves_"
+            }
+            FootstepSurface::Dirt => "assets/sfx/Footsteps SFX - Dirt/FS_Dirt_",
+            FootstepSurface::Rock => "assets/sfx/Footsteps SFX - Rock/FS_Rock_",
+            FootstepSurface::Sand => "assets/sfx/Footsteps SFX - Sand/FS_Sand_",
+        }
+    }
+}
+
+/// The footstep clips of one surface.
+#[derive(Clone)]
+struct SurfaceClips {
     // Store file paths for spatial audio
-    pub walk_paths: Vec<String>,
-    pub jump_paths: Vec<String>,
-    pub land_paths: Vec<String>,
-    pub run_paths: Vec<String>,
+    walk_paths: Vec<String>,
+    jump_paths: Vec<String>,
+    land_paths: Vec<String>,
+    run_paths: Vec<String>,
     #[allow(dead_code)]
-    pub sneak_paths: Vec<String>,
+    sneak_paths: Vec<String>,
     #[allow(dead_code)]
-    pub sprint_paths: Vec<String>,
+    sprint_paths: Vec<String>,
+}
+
+impl SurfaceClips {
+    /// Only the clips found in `assets/sfx`, a sample without any takes the one of `fallback`.
+    fn load(
+        surface: FootstepSurface,
+        spatial_sound_manager: &SpatialSoundManager,
+        fallback: Option<&SurfaceClips>,
+    ) -> Self {
+        let load = |sample_name: &str, sample_count: usize, fallback: Option<&Vec<String>>| {
+            let paths: Vec<String> = (0..sample_count)
+                .map(|i| format!("{}{}_{:02}.wav", surface.clip_prefix(), sample_name, i + 1))
+                .filter(|path| spatial_sound_manager.has_clip(path))
+                .collect();
+            match fallback {
+                Some(fallback) if paths.is_empty() => fallback.clone(),
+                _ => paths,
+            }
+        };
+        Self {
+            walk_paths: load("walk", 25, fallback.map(|f| &f.walk_paths)),
+            jump_paths: load("jump", 10, fallback.map(|f| &f.jump_paths)),
+            land_paths: load("land", 10, fallback.map(|f| &f.land_paths)),
+            run_paths: load("run", 25, fallback.map(|f| &f.run_paths)),
+            sneak_paths: load("sneak", 25, fallback.map(|f| &f.sneak_paths)),
+            sprint_paths: load("sprint", 25, fallback.map(|f| &f.sprint_paths)),
+        }
+    }
+}
+
+pub struct PlayerClipCaches {
+    surfaces: HashMap<FootstepSurface, SurfaceClips>,
 
     // foot-step intervals (seconds)
     pub walk_interval: f32,
@@ -20,41 +98,34 @@ pub struct PlayerClipCaches {
 }
 
 impl PlayerClipCaches {
-    fn new() -> Result<Self> {
-        let jump_paths = Self::load_clip_paths("jump", 10);
-        let land_paths = Self::load_clip_paths("land", 10);
-        let walk_paths = Self::load_clip_paths("walk", 25);
-        let sneak_paths = Self::load_clip_paths("sneak", 25);
-        let run_paths = Self::load_clip_paths("run", 25);
-        let sprint_paths = Self::load_clip_paths("sprint", 25);
+    /// The grass clips stand in for the surfaces that have none of their own.
+    fn new(spatial_sound_manager: &SpatialSoundManager) -> Result<Self> {
+        let grass = SurfaceClips::load(FootstepSurface::Grass, spatial_sound_manager, None);
+        let surfaces = FootstepSurface::ALL
+            .into_iter()
+            .map(|surface| {
+                let clips = match surface {
+                    FootstepSurface::Grass => grass.clone(),
+                    _ => SurfaceClips::load(surface, spatial_sound_manager, Some(&grass)),
+                };
+                (surface, clips)
+            })
+            .collect();
 
         Ok(Self {
-            walk_paths,
-            jump_paths,
-            land_paths,
-            sneak_paths,
-            run_paths,
-            sprint_paths,
+            surfaces,
             walk_interval: 0.35,
             run_interval: 0.25,
         })
     }
 
-    fn load_clip_paths(sample_name: &str, sample_count: usize) -> Vec<String> {
-        let prefix_path =
-            "assets/sfx/Footsteps SFX - Undergrowth & Leaves/TomWinandySFX - FS_UndergrowthLeaves_";
-        (0..sample_count)
-            .map(|i| format!("{}{}_{:02}.wav", prefix_path, sample_name, i + 1))
-            .collect()
-    }
-
-    fn get_random_path(paths: &[String]) -> &str {
+    fn get_random_path(paths: &[String]) -> Option<&str> {
         if paths.is_empty() {
-            panic!("Cannot choose from empty path list");
+            return None;
         }
         let mut rng = rand::rng();
         let index = (rng.random::<u32>() as usize) % paths.len();
-        &paths[index]
+        Some(&paths[index])
     }
 }
 
@@ -64,22 +135,47 @@ pub struct PlayerAudioController {
     // time elapsed since last step sound
     time_since_last_step: f32,
     volume_gain: f32,
+    /// What the player stands on, from the player collider.
+    ground_material: Option<VoxelMaterial>,
+    /// Whether the path wear left the ground without grass.
+    is_ground_bare: bool,
 }
 
 impl PlayerAudioController {
     pub fn new(spatial_sound_manager: SpatialSoundManager) -> Result<Self> {
-        let clip_caches = PlayerClipCaches::new()?;
+        let clip_caches = PlayerClipCaches::new(&spatial_sound_manager)?;
         Ok(Self {
             spatial_sound_manager,
             clip_caches,
             time_since_last_step: 0.0,
             volume_gain: 0.0,
+            ground_material: None,
+            is_ground_bare: false,
         })
     }
 
-    fn play_footstep(&self, clip_path: &str, volume: f32) -> Result<()> {
-        self.spatial_sound_manager
-            .add_non_spatial_source(clip_path, volume + self.volume_gain)?;
+    /// Plays a random clip of `pick` from the clips of the ground, nothing where the ground makes
+    /// no sound.
+    fn play_footstep(
+        &self,
+        pick: impl Fn(&SurfaceClips) -> &Vec<String>,
+        volume: f32,
+    ) -> Result<()> {
+        let Some(surface) =
+            FootstepSurface::from_material(self.ground_material, self.is_ground_bare)
+        else {
+            return Ok(());
+        };
+        let Some(clip_path) =
+            PlayerClipCaches::get_random_path(pick(&self.clip_caches.surfaces[&surface]))
+        else {
+            return Ok(());
+        };
+        self.spatial_sound_manager.add_non_spatial_source(
+            clip_path,
+            volume + self.volume_gain,
+            AudioBus::Sfx,
+        )?;
         Ok(())
     }
 
@@ -87,31 +183,38 @@ impl PlayerAudioController {
         self.volume_gain = volume_gain;
     }
 
+    pub fn set_ground_material(&mut self, material: Option<VoxelMaterial>) {
+        self.ground_material = material;
+    }
+
+    pub fn set_ground_bare(&mut self, is_ground_bare: bool) {
+        self.is_ground_bare = is_ground_bare;
+    }
+
     pub fn play_jumping(&mut self, speed: f32, _position: Vec3) {
         let volume = self.calculate_speed_based_volume(speed, -6.0, 6.0);
-        let path = PlayerClipCaches::get_random_path(&self.clip_caches.jump_paths);
-        if let Err(e) = self.play_footstep(path, volume) {
+        if let Err(e) = self.play_footstep(|clips| &clips.jump_paths, volume) {
             log::error!("Failed to play non-spatial jump sound: {}", e);
         }
     }
 
     pub fn play_landing(&mut self, speed: f32, _position: Vec3) {
         let volume = self.calculate_speed_based_volume(speed, -6.0, 6.0);
-        let path = PlayerClipCaches::get_random_path(&self.clip_caches.land_paths);
-        if let Err(e) = self.play_footstep(path, volume) {
+        if let Err(e) = self.play_footstep(|clips| &clips.land_paths, volume) {
             log::error!("Failed to play non-spatial landing sound: {}", e);
         }
     }
 
     pub fn play_step(&mut self, is_running: bool, speed: f32, _position: Vec3) {
         let volume = self.calculate_speed_based_volume(speed, -4.0, 0.0);
-        let paths = if is_running {
-            &self.clip_caches.run_paths
-        } else {
-            &self.clip_caches.walk_paths
+        let pick = |clips: &SurfaceClips| -> &Vec<String> {
+            if is_running {
+                &clips.run_paths
+            } else {
+                &clips.walk_paths
+            }
         };
-        let path = PlayerClipCaches::get_random_path(paths);
-        if let Err(e) = self.play_footstep(path, volume) {
+        if let Err(e) = self.play_footstep(pick, volume) {
             log::error!("Failed to play non-spatial step sound: {}", e);
         }
     }
@@ -149,16 +252,7 @@ impl PlayerAudioController {
 
         self.time_since_last_step += frame_delta_time;
         if self.time_since_last_step >= interval {
-            let volume = self.calculate_speed_based_volume(speed, -4.0, 0.0);
-            let paths = if is_running {
-                &self.clip_caches.run_paths
-            } else {
-                &self.clip_caches.walk_paths
-            };
-            let path = PlayerClipCaches::get_random_path(paths);
-            if let Err(e) = self.play_footstep(path, volume) {
-                log::error!("Failed to play non-spatial walk sound: {}", e);
-            }
+            self.play_step(is_running, speed, _position);
             self.time_since_last_step = 0.0;
         }
     }
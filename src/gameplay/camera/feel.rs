@@ -0,0 +1,217 @@
+use glam::Vec3;
+
+/// Optional walk-mode camera effects.
+///
+/// Every effect can be toggled individually since head bob and landing dips make some players
+/// motion sick.
+#[derive(Debug, Clone)]
+pub struct CameraFeelDesc {
+    pub head_bob_enabled: bool,
+    /// Vertical bob amplitude at normal walking speed (world units).
+    pub head_bob_amplitude: f32,
+    /// Bob cycles per second at normal walking speed, scaled linearly with the actual speed.
+    pub head_bob_frequency: f32,
+
+    pub landing_dip_enabled: bool,
+    /// How far the view dips per unit of vertical landing speed.
+    pub landing_dip_strength: f32,
+    /// Upper limit of the dip so long falls don't bury the camera in the ground.
+    pub landing_dip_max: f32,
+
+    pub crouch_enabled: bool,
+    /// Camera height while fully crouched, relative to the standing height.
+    pub crouch_height_ratio: f32,
+    /// Crouch transitions per second, 1.0 means it takes a full second to crouch.
+    pub crouch_transition_speed: f32,
+    /// Horizontal speed multiplier applied while fully crouched.
+    pub crouch_speed_mul: f32,
+}
+
+impl Default for CameraFeelDesc {
+    fn default() -> Self {
+        Self {
+            head_bob_enabled: false,
+            head_bob_amplitude: 0.002,
+            head_bob_frequency: 1.8,
+            landing_dip_enabled: false,
+            landing_dip_strength: 0.02,
+            landing_dip_max: 0.015,
+            crouch_enabled: true,
+            crouch_height_ratio: 0.55,
+            crouch_transition_speed: 6.0,
+            crouch_speed_mul: 0.5,
+        }
+    }
+}
+
+impl CameraFeelDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Checkbox::new(&mut self.head_bob_enabled, "Head Bob"));
+        ui.add_enabled(
+            self.head_bob_enabled,
+            egui::Slider::new(&mut self.head_bob_amplitude, 0.0..=0.01).text("Bob Amplitude"),
+        );
+        ui.add_enabled(
+            self.head_bob_enabled,
+            egui::Slider::new(&mut self.head_bob_frequency, 0.5..=4.0).text("Bob Frequency"),
+        );
+
+        ui.separator();
+        ui.add(egui::Checkbox::new(
+            &mut self.landing_dip_enabled,
+            "Landing Dip",
+        ));
+        ui.add_enabled(
+            self.landing_dip_enabled,
+            egui::Slider::new(&mut self.landing_dip_strength, 0.0..=0.1).text("Dip Strength"),
+        );
+        ui.add_enabled(
+            self.landing_dip_enabled,
+            egui::Slider::new(&mut self.landing_dip_max, 0.0..=0.05).text("Max Dip"),
+        );
+
+        ui.separator();
+        ui.add(egui::Checkbox::new(&mut self.crouch_enabled, "Crouch (C)"));
+        ui.add_enabled(
+            self.crouch_enabled,
+            egui::Slider::new(&mut self.crouch_height_ratio, 0.2..=1.0).text("Crouch Height"),
+        );
+        ui.add_enabled(
+            self.crouch_enabled,
+            egui::Slider::new(&mut self.crouch_transition_speed, 1.0..=20.0)
+                .text("Crouch Transition Speed"),
+        );
+        ui.add_enabled(
+            self.crouch_enabled,
+            egui::Slider::new(&mut self.crouch_speed_mul, 0.1..=1.0).text("Crouch Speed Mul"),
+        );
+    }
+}
+
+/// Runtime state of the walk-mode camera effects.
+///
+/// Head bob and landing dip only offset the rendered eye position, the collider always sees the
+/// physical position. Crouching on the other hand changes the physical camera height, so the
+/// player collider pass samples from the lowered position.
+#[derive(Debug, Default)]
+pub struct CameraFeel {
+    bob_phase: f32,
+    bob_weight: f32,
+    dip_offset: f32,
+    dip_velocity: f32,
+    /// 0.0 = standing, 1.0 = fully crouched.
+    crouch_amount: f32,
+}
+
+impl CameraFeel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the physical camera height above the ground for the current crouch state.
+    pub fn camera_height(&self, desc: &CameraFeelDesc, standing_height: f32) -> f32 {
+        let crouched_height = standing_height * desc.crouch_height_ratio;
+        standing_height + (crouched_height - standing_height) * self.crouch_amount
+    }
+
+    /// Horizontal speed multiplier for the current crouch state.
+    pub fn speed_mul(&self, desc: &CameraFeelDesc) -> f32 {
+        1.0 + (desc.crouch_speed_mul - 1.0) * self.crouch_amount
+    }
+
+    /// Moves the crouch amount towards the requested state.
+    ///
+    /// `headroom` is the free space above the camera, standing up is held back while there is no
+    /// room for the full standing height.
+    pub fn update_crouch(
+        &mut self,
+        desc: &CameraFeelDesc,
+        crouch_requested: bool,
+        standing_height: f32,
+        headroom: f32,
+        frame_delta_time: f32,
+    ) {
+        let wants_crouch = desc.crouch_enabled && crouch_requested;
+        let mut target = if wants_crouch { 1.0 } else { 0.0 };
+
+        if !wants_crouch && self.crouch_amount > 0.0 {
+            let required_headroom = standing_height - self.camera_height(desc, standing_height);
+            if headroom < required_headroom {
+                target = self.crouch_amount;
+            }
+        }
+
+        let step = desc.crouch_transition_speed * frame_delta_time;
+        self.crouch_amount += (target - self.crouch_amount).clamp(-step, step);
+    }
+
+    /// Kicks off a landing dip, `fall_speed` is the downward speed right before touching ground.
+    pub fn on_landed(&mut self, desc: &CameraFeelDesc, fall_speed: f32) {
+        if !desc.landing_dip_enabled {
+            return;
+        }
+        // the dip is driven as an impulse into a spring, so it eases in and out naturally
+        self.dip_velocity -= fall_speed * desc.landing_dip_strength * 20.0;
+    }
+
+    pub fn update(
+        &mut self,
+        desc: &CameraFeelDesc,
+        horizontal_speed: f32,
+        normal_speed: f32,
+        is_on_ground: bool,
+        frame_delta_time: f32,
+    ) {
+        const BOB_WEIGHT_RESPONSE: f32 = 8.0; // how quickly the bob fades in/out (1/s)
+        const DIP_STIFFNESS: f32 = 120.0; // spring constant pulling the dip back to rest
+        const DIP_DAMPING: f32 = 22.0; // ~2 * sqrt(stiffness), critically damped
+
+        // head bob, both frequency and amplitude follow the movement speed
+        let speed_ratio = if normal_speed > 0.0 {
+            (horizontal_speed / normal_speed).min(2.5)
+        } else {
+            0.0
+        };
+        let target_weight = if desc.head_bob_enabled && is_on_ground {
+            speed_ratio
+        } else {
+            0.0
+        };
+        let t = (BOB_WEIGHT_RESPONSE * frame_delta_time).min(1.0);
+        self.bob_weight += (target_weight - self.bob_weight) * t;
+        self.bob_phase +=
+            std::f32::consts::TAU * desc.head_bob_frequency * speed_ratio * frame_delta_time;
+        self.bob_phase %= std::f32::consts::TAU;
+
+        // landing dip
+        if desc.landing_dip_enabled {
+            let acceleration = -DIP_STIFFNESS * self.dip_offset - DIP_DAMPING * self.dip_velocity;
+            self.dip_velocity += acceleration * frame_delta_time;
+            self.dip_offset += self.dip_velocity * frame_delta_time;
+            self.dip_offset = self.dip_offset.max(-desc.landing_dip_max);
+        } else {
+            self.dip_offset = 0.0;
+            self.dip_velocity = 0.0;
+        }
+    }
+
+    /// Resets view-only effects, used when leaving walk mode.
+    pub fn reset_view_effects(&mut self) {
+        self.bob_weight = 0.0;
+        self.dip_offset = 0.0;
+        self.dip_velocity = 0.0;
+    }
+
+    /// Offset that is added on top of the physical camera position when rendering.
+    pub fn eye_offset(&self, desc: &CameraFeelDesc, right: Vec3) -> Vec3 {
+        let mut offset = Vec3::ZERO;
+        if desc.head_bob_enabled {
+            let amplitude = desc.head_bob_amplitude * self.bob_weight;
+            // two vertical dips per cycle (one per step), one lateral sway per cycle
+            offset.y += (self.bob_phase * 2.0).sin().abs() * amplitude - amplitude * 0.5;
+            offset += right * self.bob_phase.sin() * amplitude * 0.5;
+        }
+        offset.y += self.dip_offset;
+        offset
+    }
+}
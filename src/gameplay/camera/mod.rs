@@ -11,7 +11,10 @@ mod audio;
 // pub use audio::*;
 
 mod movement;
-// pub use movement::*;
+pub use movement::*;
+
+mod feel;
+pub use feel::*;
 
 pub mod vectors;
 pub use vectors::*;
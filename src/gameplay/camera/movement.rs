@@ -1,4 +1,5 @@
-use glam::Vec3;
+use crate::tracer::PlayerCollisionResult;
+use glam::{Vec2, Vec3};
 use winit::{
     event::{ElementState, KeyEvent},
     keyboard::{KeyCode, PhysicalKey},
@@ -22,6 +23,7 @@ pub struct MovementState {
     pub is_boosted: bool,
     pub axes: AxesState,
     pub jump_requested: bool,
+    pub crouch_held: bool,
 }
 
 impl MovementState {
@@ -32,6 +34,7 @@ impl MovementState {
             is_boosted: false,
             axes: AxesState::default(),
             jump_requested: false,
+            crouch_held: false,
         }
     }
 
@@ -85,6 +88,7 @@ impl MovementState {
                         self.jump_requested = true;
                     }
                     KeyCode::ControlLeft => self.axes.down = true,
+                    KeyCode::KeyC => self.crouch_held = true,
                     _ => {}
                 },
                 ElementState::Released => match code {
@@ -95,15 +99,16 @@ impl MovementState {
                     KeyCode::KeyD => self.axes.right = false,
                     KeyCode::Space => self.axes.up = false,
                     KeyCode::ControlLeft => self.axes.down = false,
+                    KeyCode::KeyC => self.crouch_held = false,
                     _ => {}
                 },
             }
         }
     }
 
-    /// Resets the jump request flag after it's been processed
-    pub fn reset_jump_request(&mut self) {
-        self.jump_requested = false;
+    /// Whether Space was pressed since the last call.
+    pub fn take_jump_request(&mut self) -> bool {
+        std::mem::take(&mut self.jump_requested)
     }
 
     /// Checks if the player is currently moving horizontally
@@ -111,3 +116,276 @@ impl MovementState {
         self.axes.forward || self.axes.backward || self.axes.left || self.axes.right
     }
 }
+
+/// Tunables of the walk mode character controller, in world units and seconds.
+#[derive(Debug, Clone)]
+pub struct CharacterControllerDesc {
+    pub gravity: f32,
+    /// Upward speed at the start of a jump.
+    pub jump_speed: f32,
+    pub max_fall_speed: f32,
+    /// How quickly the player gets up to the walking speed on the ground, in 1/s.
+    pub ground_acceleration: f32,
+    /// Like `ground_acceleration`, while airborne.
+    pub air_acceleration: f32,
+    /// Ledges up to this height are stepped onto, higher ones block the way like walls.
+    pub max_step_height: f32,
+    /// How quickly the camera follows the ground up and down steps, in 1/s.
+    pub ground_follow_rate: f32,
+    /// Distance the ring rays keep the player from walls and trunks.
+    pub skin_width: f32,
+    /// A jump still works this long after walking off a ledge.
+    pub coyote_time: f32,
+    /// A jump pressed this long before landing happens on landing.
+    pub jump_buffer_time: f32,
+}
+
+impl Default for CharacterControllerDesc {
+    fn default() -> Self {
+        Self {
+            gravity: 2.0,
+            jump_speed: 0.5,
+            max_fall_speed: 2.0,
+            ground_acceleration: 20.0,
+            air_acceleration: 4.0,
+            max_step_height: 0.03,
+            ground_follow_rate: 12.0,
+            skin_width: 0.03,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.15,
+        }
+    }
+}
+
+impl CharacterControllerDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.gravity, 0.1..=10.0).text("Gravity"));
+        ui.add(egui::Slider::new(&mut self.jump_speed, 0.0..=2.0).text("Jump Speed (Space)"));
+        ui.add(egui::Slider::new(&mut self.max_fall_speed, 0.1..=10.0).text("Max Fall Speed"));
+        ui.add(
+            egui::Slider::new(&mut self.ground_acceleration, 1.0..=50.0)
+                .text("Ground Acceleration"),
+        );
+        ui.add(egui::Slider::new(&mut self.air_acceleration, 0.0..=50.0).text("Air Acceleration"));
+        ui.add(egui::Slider::new(&mut self.max_step_height, 0.0..=0.08).text("Max Step Height"));
+        ui.add(
+            egui::Slider::new(&mut self.ground_follow_rate, 1.0..=60.0).text("Ground Follow Rate"),
+        );
+        ui.add(egui::Slider::new(&mut self.skin_width, 0.005..=0.1).text("Skin Width"));
+        ui.add(egui::Slider::new(&mut self.coyote_time, 0.0..=0.5).text("Coyote Time (s)"));
+        ui.add(egui::Slider::new(&mut self.jump_buffer_time, 0.0..=0.5).text("Jump Buffer (s)"));
+    }
+}
+
+/// What happened during one [`CharacterController::update`], for the sounds and view effects.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ControllerStep {
+    pub is_grounded: bool,
+    pub has_jumped: bool,
+    pub has_landed: bool,
+    /// Downward speed right before this step, non-zero when landing from a fall.
+    pub fall_speed: f32,
+}
+
+/// Moves the player in walk mode: gravity and jumps, stepping onto small ledges and sliding along
+/// the walls the ring rays of the player collider find.
+///
+/// The collision result is the one of the last player collider pass, so it always lags a frame
+/// behind the position.
+#[derive(Debug, Default)]
+pub(super) struct CharacterController {
+    velocity: Vec3,
+    is_grounded: bool,
+    /// Seconds since the player last stood on the ground, infinite right after a jump so the
+    /// coyote time doesn't allow a second one.
+    time_in_air: f32,
+    /// Seconds since Space was pressed, `None` once the jump was used or dropped.
+    jump_request_age: Option<f32>,
+    /// The last position with its ground within a step of the feet. Walking into a ledge too
+    /// high to step onto pushes the player back there.
+    last_supported_pos: Option<Vec3>,
+}
+
+impl CharacterController {
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.is_grounded
+    }
+
+    /// Stops the player and forgets where they last stood, used when teleporting or switching
+    /// modes.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Advances the player at `position` (the camera) by `frame_delta_time`.
+    ///
+    /// `wish_velocity` is the horizontal velocity the input asks for. `camera_height` is the
+    /// current height of the camera above the feet, lowered while crouching, while
+    /// `standing_height` is its full height. `front` and `right` are the horizontal basis the
+    /// ring rays were cast in.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        desc: &CharacterControllerDesc,
+        position: &mut Vec3,
+        wish_velocity: Vec3,
+        is_jump_pressed: bool,
+        camera_height: f32,
+        standing_height: f32,
+        collision_result: &PlayerCollisionResult,
+        (front, right): (Vec3, Vec3),
+        frame_delta_time: f32,
+    ) -> ControllerStep {
+        const GROUND_EPSILON: f32 = 0.01; // tolerance when comparing to ground
+
+        if is_jump_pressed {
+            self.jump_request_age = Some(0.0);
+        } else if let Some(age) = &mut self.jump_request_age {
+            *age += frame_delta_time;
+            if *age > desc.jump_buffer_time {
+                self.jump_request_age = None;
+            }
+        }
+
+        let fall_speed = (-self.velocity.y).max(0.0);
+        let was_grounded = self.is_grounded;
+
+        // how far the ground under the camera sticks up above the feet, falls and jumps land on
+        // whatever they reach
+        let ground_y = position.y - collision_result.ground_distance;
+        let rise = ground_y - (position.y - camera_height);
+        let is_blocked =
+            was_grounded && rise > desc.max_step_height && self.last_supported_pos.is_some();
+        // the standing height is used as tolerance so that crouching down doesn't count as
+        // walking off a ledge
+        let is_ground_in_reach =
+            collision_result.ground_distance <= standing_height + GROUND_EPSILON;
+        if is_blocked {
+            self.block_ledge(position);
+        } else if is_ground_in_reach {
+            self.last_supported_pos = Some(*position);
+        }
+        self.is_grounded = if is_blocked {
+            was_grounded
+        } else {
+            is_ground_in_reach && self.velocity.y <= 0.0
+        };
+
+        if self.is_grounded {
+            self.time_in_air = 0.0;
+        } else {
+            self.time_in_air += frame_delta_time;
+        }
+
+        let can_jump = self.is_grounded || self.time_in_air <= desc.coyote_time;
+        let has_jumped = can_jump && self.jump_request_age.is_some();
+        if has_jumped {
+            self.jump_request_age = None;
+            self.time_in_air = f32::INFINITY;
+            self.is_grounded = false;
+            self.velocity.y = desc.jump_speed;
+        } else if self.is_grounded {
+            self.velocity.y = 0.0;
+            // follow the ground, easing up steps and down slopes, the ground of a blocked ledge
+            // isn't under the player anymore
+            if !is_blocked {
+                let target_y = ground_y + camera_height;
+                let t = 1.0 - (-desc.ground_follow_rate * frame_delta_time).exp();
+                position.y += (target_y - position.y) * t;
+            }
+        } else {
+            self.velocity.y =
+                (self.velocity.y - desc.gravity * frame_delta_time).max(-desc.max_fall_speed);
+        }
+
+        let acceleration = if self.is_grounded {
+            desc.ground_acceleration
+        } else {
+            desc.air_acceleration
+        };
+        let t = 1.0 - (-acceleration * frame_delta_time).exp();
+        let mut horizontal_velocity = Vec3::new(self.velocity.x, 0.0, self.velocity.z);
+        horizontal_velocity +=
+            (Vec3::new(wish_velocity.x, 0.0, wish_velocity.z) - horizontal_velocity) * t;
+
+        self.slide_along_walls(
+            desc,
+            position,
+            &mut horizontal_velocity,
+            &collision_result.ring_distances,
+            (front, right),
+        );
+        self.velocity.x = horizontal_velocity.x;
+        self.velocity.z = horizontal_velocity.z;
+
+        *position += self.velocity * frame_delta_time;
+
+        ControllerStep {
+            is_grounded: self.is_grounded,
+            has_jumped,
+            has_landed: self.is_grounded && !was_grounded,
+            fall_speed,
+        }
+    }
+
+    /// Pushes the player back off a ledge too high to step onto and stops them moving into it.
+    fn block_ledge(&mut self, position: &mut Vec3) {
+        let Some(last_pos) = self.last_supported_pos else {
+            return;
+        };
+        let away = Vec2::new(last_pos.x - position.x, last_pos.z - position.z);
+        position.x = last_pos.x;
+        position.z = last_pos.z;
+        if let Some(normal) = away.try_normalize() {
+            let normal = Vec3::new(normal.x, 0.0, normal.y);
+            let into_ledge = self.velocity.dot(normal).min(0.0);
+            self.velocity -= normal * into_ledge;
+        }
+    }
+
+    /// Pushes the player out of the walls closer than the skin width and removes the part of
+    /// the velocity going into them, so they slide along instead of sticking.
+    fn slide_along_walls(
+        &self,
+        desc: &CharacterControllerDesc,
+        position: &mut Vec3,
+        horizontal_velocity: &mut Vec3,
+        ring_distances: &[f32],
+        (front, right): (Vec3, Vec3),
+    ) {
+        // the walls push back along the rays that hit them, harder the deeper they reach in
+        let mut push = Vec3::ZERO;
+        let mut max_depth = 0.0f32;
+        for (i, distance) in ring_distances.iter().enumerate() {
+            let depth = desc.skin_width - distance;
+            if depth <= 0.0 {
+                continue;
+            }
+            push -= ring_direction(i, ring_distances.len(), front, right) * depth;
+            max_depth = max_depth.max(depth);
+        }
+        let Some(normal) = push.try_normalize() else {
+            return;
+        };
+
+        *position += normal * max_depth;
+        let into_wall = horizontal_velocity.dot(normal);
+        if into_wall < 0.0 {
+            *horizontal_velocity -= normal * into_wall;
+        }
+    }
+}
+
+/// Direction of ring ray `i` of `ring_count`, mirrors `get_ring_direction` in
+/// `player_collider.comp`.
+fn ring_direction(i: usize, ring_count: usize, front: Vec3, right: Vec3) -> Vec3 {
+    if i == 0 || ring_count < 2 {
+        return front;
+    }
+    let angle = std::f32::consts::TAU * (i - 1) as f32 / (ring_count - 1) as f32;
+    (front * angle.cos() + right * angle.sin()).normalize_or_zero()
+}
@@ -0,0 +1,396 @@
+use anyhow::{anyhow, bail, Context, Result};
+use glam::Vec3;
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+/// Bumped whenever the line format changes, older paths are refused instead of misread.
+const CAMERA_PATH_VERSION: u32 = 1;
+
+/// A camera pose on a [`CameraPath`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPathKeyframe {
+    /// Seconds since the first keyframe.
+    pub time: f32,
+    pub position: Vec3,
+    /// In radians, unwrapped against the keyframe before so the camera turns the short way.
+    pub yaw: f32,
+    /// In radians.
+    pub pitch: f32,
+}
+
+/// Camera keyframes recorded in the app, played back along a Catmull-Rom spline through them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<CameraPathKeyframe>,
+}
+
+impl CameraPath {
+    pub fn keyframes(&self) -> &[CameraPathKeyframe] {
+        &self.keyframes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Seconds from the first keyframe to the last.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    /// Appends a keyframe `time` seconds after the first one, it is moved after the last one
+    /// when it would come before it.
+    pub fn push(&mut self, time: f32, position: Vec3, yaw: f32, pitch: f32) {
+        let (time, yaw) = match self.keyframes.last() {
+            Some(last) => {
+                let turn = (yaw - last.yaw + PI).rem_euclid(TAU) - PI;
+                (time.max(last.time), last.yaw + turn)
+            }
+            None => (0.0, yaw),
+        };
+        self.keyframes.push(CameraPathKeyframe {
+            time,
+            position,
+            yaw,
+            pitch,
+        });
+    }
+
+    /// The pose at `time` seconds, held at the ends. `None` for an empty path.
+    pub fn sample(&self, time: f32) -> Option<CameraPathKeyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        let (i1, i2) = (next - 1, next);
+        let i0 = i1.saturating_sub(1);
+        let i3 = (i2 + 1).min(self.keyframes.len() - 1);
+        let [k0, k1, k2, k3] = [i0, i1, i2, i3].map(|i| &self.keyframes[i]);
+        let span = k2.time - k1.time;
+        let t = if span > 0.0 {
+            (time - k1.time) / span
+        } else {
+            1.0
+        };
+        Some(CameraPathKeyframe {
+            time,
+            position: catmull_rom(k0.position, k1.position, k2.position, k3.position, t),
+            yaw: catmull_rom(k0.yaw, k1.yaw, k2.yaw, k3.yaw, t),
+            pitch: catmull_rom(k0.pitch, k1.pitch, k2.pitch, k3.pitch, t)
+                .clamp(-PI * 0.5 + 0.01, PI * 0.5 - 0.01),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.serialize())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Malformed camera path {}", path.display()))
+    }
+
+    /// One `key time x y z yaw pitch` line per keyframe.
+    fn serialize(&self) -> String {
+        let mut out = format!("version {}\n", CAMERA_PATH_VERSION);
+        for k in &self.keyframes {
+            out.push_str(&format!(
+                "key {} {} {} {} {} {}\n",
+                k.time, k.position.x, k.position.y, k.position.z, k.yaw, k.pitch
+            ));
+        }
+        out
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut path = Self::default();
+        let mut version = None;
+        for (line_no, line) in content.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let Some((keyword, values)) = parts.split_first() else {
+                continue;
+            };
+            let parsed: Result<()> = (|| {
+                match *keyword {
+                    "version" => version = Some(values.first().unwrap_or(&"").parse::<u32>()?),
+                    "key" => {
+                        let values = values
+                            .iter()
+                            .map(|v| v.parse::<f32>())
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let [time, x, y, z, yaw, pitch] = values[..] else {
+                            bail!("expected time, position, yaw and pitch");
+                        };
+                        path.push(time, Vec3::new(x, y, z), yaw, pitch);
+                    }
+                    other => bail!("unknown record {}", other),
+                }
+                Ok(())
+            })();
+            parsed.with_context(|| format!("line {}", line_no + 1))?;
+        }
+        match version {
+            Some(CAMERA_PATH_VERSION) => Ok(path),
+            Some(v) => Err(anyhow!("unsupported camera path version {}", v)),
+            None => Err(anyhow!("missing version")),
+        }
+    }
+}
+
+/// The uniform Catmull-Rom spline through `p1` and `p2` at `t` from 0 to 1.
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy
+        + std::ops::Add<Output = T>
+        + std::ops::Sub<Output = T>
+        + std::ops::Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CameraPathMode {
+    Idle,
+    Recording,
+    Playing,
+}
+
+/// Records a [`CameraPath`] keyframe by keyframe and plays it back, the played pose overrides
+/// the camera controls.
+#[derive(Debug)]
+pub struct CameraPathPlayer {
+    pub path: CameraPath,
+    pub is_looping: bool,
+    mode: CameraPathMode,
+    /// Seconds since recording started or along the path while playing.
+    time: f32,
+}
+
+impl Default for CameraPathPlayer {
+    fn default() -> Self {
+        Self {
+            path: CameraPath::default(),
+            is_looping: false,
+            mode: CameraPathMode::Idle,
+            time: 0.0,
+        }
+    }
+}
+
+impl CameraPathPlayer {
+    pub fn is_recording(&self) -> bool {
+        self.mode == CameraPathMode::Recording
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.mode == CameraPathMode::Playing
+    }
+
+    /// Adds the current camera pose, the first keyframe starts a new recording. Ignored while
+    /// playing.
+    pub fn record_keyframe(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        if self.is_playing() {
+            return;
+        }
+        if !self.is_recording() {
+            self.path.clear();
+            self.mode = CameraPathMode::Recording;
+            self.time = 0.0;
+        }
+        self.path.push(self.time, position, yaw, pitch);
+    }
+
+    /// Starts playing from the start, or stops the playback or the recording.
+    pub fn toggle_playback(&mut self) {
+        self.mode = match self.mode {
+            CameraPathMode::Idle if self.path.keyframes().len() >= 2 => CameraPathMode::Playing,
+            _ => CameraPathMode::Idle,
+        };
+        self.time = 0.0;
+    }
+
+    /// Advances the clock, returns the camera pose while playing.
+    pub fn update(&mut self, delta_time: f32) -> Option<CameraPathKeyframe> {
+        match self.mode {
+            CameraPathMode::Idle => None,
+            CameraPathMode::Recording => {
+                self.time += delta_time;
+                None
+            }
+            CameraPathMode::Playing => {
+                self.time += delta_time;
+                let duration = self.path.duration();
+                if self.time > duration {
+                    if self.is_looping && duration > 0.0 {
+                        self.time %= duration;
+                    } else {
+                        self.mode = CameraPathMode::Idle;
+                    }
+                }
+                self.path.sample(self.time.min(duration))
+            }
+        }
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui, file_path: &Path) {
+        let status = match self.mode {
+            CameraPathMode::Idle => format!("{} keyframes", self.path.keyframes().len()),
+            CameraPathMode::Recording => format!(
+                "Recording, {} keyframes, {:.1} s",
+                self.path.keyframes().len(),
+                self.time
+            ),
+            CameraPathMode::Playing => {
+                format!("Playing, {:.1} of {:.1} s", self.time, self.path.duration())
+            }
+        };
+        ui.label(status);
+        ui.label("F6 records a keyframe, F7 plays or stops");
+        ui.checkbox(&mut self.is_looping, "Loop");
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                match self.path.save(file_path) {
+                    Ok(()) => log::info!("Saved the camera path to {}", file_path.display()),
+                    Err(e) => log::error!("Failed to save the camera path: {:#}", e),
+                }
+            }
+            if ui.button("Load").clicked() {
+                match CameraPath::load(file_path) {
+                    Ok(path) => {
+                        self.path = path;
+                        self.mode = CameraPathMode::Idle;
+                    }
+                    Err(e) => log::error!("Failed to load the camera path: {:#}", e),
+                }
+            }
+            if ui.button("Clear").clicked() {
+                self.path.clear();
+                self.mode = CameraPathMode::Idle;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_keyframes_close(a: &[CameraPathKeyframe], b: &[CameraPathKeyframe]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            let is_close = (a.time - b.time).abs() < 1e-5
+                && a.position.abs_diff_eq(b.position, 1e-5)
+                && (a.yaw - b.yaw).abs() < 1e-5
+                && (a.pitch - b.pitch).abs() < 1e-5;
+            assert!(is_close, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn three_keyframe_path() -> CameraPath {
+        let mut path = CameraPath::default();
+        path.push(0.0, Vec3::new(0.1, 0.5, 0.2), 0.3, -0.1);
+        path.push(1.5, Vec3::new(0.4, 0.6, 0.2), 1.2, 0.05);
+        path.push(4.0, Vec3::new(0.9, 0.55, 0.7), 2.9, 0.2);
+        path
+    }
+
+    #[test]
+    fn serialize_parse_round_trip() {
+        let path = three_keyframe_path();
+        let parsed = CameraPath::parse(&path.serialize()).unwrap();
+        assert_keyframes_close(parsed.keyframes(), path.keyframes());
+    }
+
+    #[test]
+    fn parse_rejects_other_versions() {
+        let serialized = three_keyframe_path().serialize();
+        let newer = serialized.replacen(
+            &format!("version {}", CAMERA_PATH_VERSION),
+            &format!("version {}", CAMERA_PATH_VERSION + 1),
+            1,
+        );
+        assert!(CameraPath::parse(&newer).is_err());
+
+        let unversioned = serialized.lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert!(CameraPath::parse(&unversioned).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_short_keyframes() {
+        let content = format!("version {}\nkey 0 1 2 3 0.5\n", CAMERA_PATH_VERSION);
+        assert!(CameraPath::parse(&content).is_err());
+    }
+
+    #[test]
+    fn push_turns_the_short_way() {
+        let mut path = CameraPath::default();
+        path.push(0.0, Vec3::ZERO, PI - 0.1, 0.0);
+        // just past the seam, a turn of 0.2 instead of almost a full circle back
+        path.push(1.0, Vec3::ZERO, -PI + 0.1, 0.0);
+        assert!((path.keyframes()[1].yaw - (PI + 0.1)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn push_keeps_the_times_increasing() {
+        let mut path = CameraPath::default();
+        path.push(5.0, Vec3::ZERO, 0.0, 0.0);
+        path.push(2.0, Vec3::ONE, 0.0, 0.0);
+        path.push(1.0, Vec3::ONE, 0.0, 0.0);
+        // the first keyframe starts the path
+        assert_eq!(path.keyframes()[0].time, 0.0);
+        assert_eq!(path.keyframes()[1].time, 2.0);
+        assert_eq!(path.keyframes()[2].time, 2.0);
+    }
+
+    #[test]
+    fn sample_passes_through_the_keyframes() {
+        let path = three_keyframe_path();
+        for keyframe in path.keyframes() {
+            let sampled = path.sample(keyframe.time).unwrap();
+            assert_keyframes_close(&[sampled], &[*keyframe]);
+        }
+    }
+
+    #[test]
+    fn sample_holds_at_the_ends() {
+        let path = three_keyframe_path();
+        let first = path.keyframes()[0];
+        let last = path.keyframes()[2];
+        assert_eq!(path.sample(-1.0), Some(first));
+        assert_eq!(path.sample(path.duration() + 10.0), Some(last));
+        assert_eq!(CameraPath::default().sample(0.0), None);
+    }
+
+    #[test]
+    fn sample_clamps_the_pitch() {
+        let mut path = CameraPath::default();
+        path.push(0.0, Vec3::ZERO, 0.0, 0.0);
+        path.push(1.0, Vec3::ZERO, 0.0, PI * 0.5);
+        path.push(2.0, Vec3::ZERO, 0.0, PI * 0.5);
+        path.push(3.0, Vec3::ZERO, 0.0, 0.0);
+        // the spline overshoots between the two keyframes looking straight up
+        let pitch = path.sample(1.5).unwrap().pitch;
+        assert!((pitch - (PI * 0.5 - 0.01)).abs() < 1e-5);
+    }
+}
@@ -1,2 +1,23 @@
 pub mod camera;
 pub use camera::*;
+
+mod path_wear;
+pub use path_wear::*;
+
+mod world_clock;
+pub use world_clock::*;
+
+mod weather;
+pub use weather::*;
+
+mod seasonal_leaves;
+pub use seasonal_leaves::*;
+
+mod wind_field;
+pub use wind_field::*;
+
+mod camera_path;
+pub use camera_path::*;
+
+mod photo_mode;
+pub use photo_mode::*;
@@ -0,0 +1,178 @@
+use crate::constants::PATH_WEAR_TEXELS_PER_CHUNK;
+use crate::geom::UAabb3;
+use glam::{IVec2, UVec2, Vec2, Vec3};
+
+/// Feet moving further than this in one frame teleported rather than walked, in world units.
+const MAX_STEP_LENGTH: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PathWearDesc {
+    pub is_enabled: bool,
+    /// Wear added at the center of the footprint per world unit walked, a texel is fully worn at
+    /// 1.0.
+    pub wear_per_unit: f32,
+    /// In world units.
+    pub footprint_radius: f32,
+    /// In-game days a fully worn path takes to grow back.
+    pub fade_days: f32,
+    /// Grass isn't grown above this wear when a chunk's surface is rebuilt.
+    pub grass_threshold: f32,
+    /// The ground color of a fully worn path.
+    pub path_color: egui::Color32,
+}
+
+impl Default for PathWearDesc {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            wear_per_unit: 3.0,
+            footprint_radius: 0.03,
+            fade_days: 3.0,
+            grass_threshold: 0.6,
+            path_color: egui::Color32::from_rgb(118, 90, 58),
+        }
+    }
+}
+
+impl PathWearDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Wear Paths While Walking");
+        ui.add(egui::Slider::new(&mut self.wear_per_unit, 0.1..=20.0).text("Wear Per Unit"));
+        ui.add(egui::Slider::new(&mut self.footprint_radius, 0.005..=0.1).text("Footprint Radius"));
+        ui.add(egui::Slider::new(&mut self.fade_days, 0.1..=30.0).text("Fade Days"));
+        ui.add(egui::Slider::new(&mut self.grass_threshold, 0.0..=1.0).text("Grass Threshold"))
+            .on_hover_text("Applied when a chunk's surface is rebuilt");
+        ui.horizontal(|ui| {
+            ui.label("Path Color:");
+            ui.color_edit_button_srgba(&mut self.path_color);
+        });
+    }
+
+    /// [`Self::path_color`] in [0, 1] sRGB, as the shaders take it.
+    pub fn path_color_vec3(&self) -> Vec3 {
+        Vec3::new(
+            self.path_color.r() as f32 / 255.0,
+            self.path_color.g() as f32 / 255.0,
+            self.path_color.b() as f32 / 255.0,
+        )
+    }
+}
+
+/// How worn the ground is by the player walking over it, one texel per column of the terrain.
+///
+/// Wear builds up under the player's feet and fades back over in-game days. The shading blends
+/// the grassy ground toward [`PathWearDesc::path_color`] with it and surface rebuilds don't grow
+/// grass on well trodden texels.
+pub struct PathWearMap {
+    pub desc: PathWearDesc,
+    extent: UVec2,
+    /// Row major in x, matches `B_PathWear`.
+    wear: Vec<f32>,
+    /// Where the feet were last frame, `None` while off the ground.
+    last_foot_pos: Option<Vec2>,
+    is_dirty: bool,
+}
+
+impl PathWearMap {
+    /// The map spans the xz extent of `chunk_bound`.
+    pub fn new(desc: PathWearDesc, chunk_bound: &UAabb3) -> Self {
+        let extent = Self::extent_of(chunk_bound);
+        Self {
+            desc,
+            extent,
+            wear: vec![0.0; (extent.x * extent.y) as usize],
+            last_foot_pos: None,
+            is_dirty: true,
+        }
+    }
+
+    /// In texels.
+    pub fn extent_of(chunk_bound: &UAabb3) -> UVec2 {
+        UVec2::new(chunk_bound.max().x, chunk_bound.max().z) * PATH_WEAR_TEXELS_PER_CHUNK
+    }
+
+    /// In texels.
+    pub fn extent(&self) -> UVec2 {
+        self.extent
+    }
+
+    pub fn wear(&self) -> &[f32] {
+        &self.wear
+    }
+
+    /// Wears the ground along the feet's way since last frame, `foot_pos` is the xz position of
+    /// the feet in world units, `None` while flying or in the air.
+    pub fn walk(&mut self, foot_pos: Option<Vec2>) {
+        let last_foot_pos = std::mem::replace(&mut self.last_foot_pos, foot_pos);
+        let (Some(from), Some(to)) = (last_foot_pos, foot_pos) else {
+            return;
+        };
+        let step_length = from.distance(to);
+        if !self.desc.is_enabled || step_length == 0.0 || step_length > MAX_STEP_LENGTH {
+            return;
+        }
+        self.stamp(to, self.desc.wear_per_unit * step_length);
+    }
+
+    /// Adds `amount` at `center` (xz in world units), falling off linearly to the footprint's edge.
+    fn stamp(&mut self, center: Vec2, amount: f32) {
+        let texels_per_unit = PATH_WEAR_TEXELS_PER_CHUNK as f32;
+        let radius = self.desc.footprint_radius * texels_per_unit;
+        let center = center * texels_per_unit;
+        let min = (center - Vec2::splat(radius))
+            .floor()
+            .as_ivec2()
+            .max(IVec2::ZERO);
+        let max = (center + Vec2::splat(radius))
+            .ceil()
+            .as_ivec2()
+            .min(self.extent.as_ivec2());
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let texel_center = Vec2::new(x as f32, y as f32) + Vec2::splat(0.5);
+                let falloff = 1.0 - texel_center.distance(center) / radius.max(1e-3);
+                if falloff <= 0.0 {
+                    continue;
+                }
+                let wear = &mut self.wear[(y as u32 * self.extent.x + x as u32) as usize];
+                *wear = (*wear + amount * falloff).min(1.0);
+                self.is_dirty = true;
+            }
+        }
+    }
+
+    /// How worn the texel under `pos_xz` (in world units) is, 0.0 off the map.
+    pub fn wear_at(&self, pos_xz: Vec2) -> f32 {
+        let texel = (pos_xz * PATH_WEAR_TEXELS_PER_CHUNK as f32)
+            .floor()
+            .as_ivec2();
+        if texel.cmplt(IVec2::ZERO).any() || texel.cmpge(self.extent.as_ivec2()).any() {
+            return 0.0;
+        }
+        self.wear[(texel.y as u32 * self.extent.x + texel.x as u32) as usize]
+    }
+
+    /// Whether the ground under `pos_xz` (in world units) is worn past the grass.
+    pub fn is_bare_at(&self, pos_xz: Vec2) -> bool {
+        self.wear_at(pos_xz) > self.desc.grass_threshold
+    }
+
+    /// Grows the paths back by `delta_days` in-game days.
+    pub fn fade(&mut self, delta_days: f32) {
+        let fade = delta_days / self.desc.fade_days.max(1e-3);
+        for wear in self.wear.iter_mut().filter(|wear| **wear > 0.0) {
+            *wear = (*wear - fade).max(0.0);
+            self.is_dirty = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.wear.fill(0.0);
+        self.is_dirty = true;
+    }
+
+    /// Whether the wear changed since the last call, so the GPU copies need updating.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.is_dirty)
+    }
+}
@@ -0,0 +1,44 @@
+use glam::Vec3;
+
+/// What photo mode changed when it started, put back once it ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhotoModeReturn {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub is_fly_mode: bool,
+    pub is_dof_enabled: bool,
+}
+
+/// Freezes the world and frees the camera from the player to frame a shot, the depth of field
+/// is on while it lasts.
+#[derive(Debug, Default)]
+pub struct PhotoMode {
+    /// Set while in photo mode.
+    saved: Option<PhotoModeReturn>,
+}
+
+impl PhotoMode {
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Remembers where the player was, ignored while already in photo mode.
+    pub fn enter(&mut self, saved: PhotoModeReturn) {
+        self.saved.get_or_insert(saved);
+    }
+
+    /// Ends photo mode, returns what to put back. `None` when it wasn't on.
+    pub fn exit(&mut self) -> Option<PhotoModeReturn> {
+        self.saved.take()
+    }
+
+    /// The time the simulation advances by, none while the world is frozen.
+    pub fn simulation_delta_time(&self, delta_time: f32) -> f32 {
+        if self.is_active() {
+            0.0
+        } else {
+            delta_time
+        }
+    }
+}
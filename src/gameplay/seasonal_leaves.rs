@@ -0,0 +1,83 @@
+use super::world_clock::smoothstep;
+use egui::Color32;
+use glam::Vec3;
+
+/// How the leaves follow the season of the world clock: they turn to the autumn palette, thin
+/// out over the winter and grow back green in spring.
+#[derive(Debug, Clone)]
+pub struct SeasonalLeaves {
+    pub is_enabled: bool,
+    pub autumn_bottom_color: Color32,
+    pub autumn_tip_color: Color32,
+    /// Fraction of the leaves left in the dead of winter.
+    pub winter_density: f32,
+}
+
+impl Default for SeasonalLeaves {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            autumn_bottom_color: Color32::from_rgb(150, 45, 10),
+            autumn_tip_color: Color32::from_rgb(230, 120, 20),
+            winter_density: 0.15,
+        }
+    }
+}
+
+impl SeasonalLeaves {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Follow The Season");
+        ui.horizontal(|ui| {
+            ui.label("Autumn Bottom Color:");
+            ui.color_edit_button_srgba(&mut self.autumn_bottom_color);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Autumn Tip Color:");
+            ui.color_edit_button_srgba(&mut self.autumn_tip_color);
+        });
+        ui.add(egui::Slider::new(&mut self.winter_density, 0.0..=1.0).text("Winter Density"));
+    }
+
+    /// How far the leaves turned to the autumn palette, 0.0 while they are green. The few left
+    /// over the winter keep their autumn colors until the new ones are out by the spring
+    /// equinox.
+    pub fn autumn_blend(&self, season: f32) -> f32 {
+        if !self.is_enabled {
+            return 0.0;
+        }
+        let season = season.rem_euclid(1.0);
+        if season < 0.5 {
+            1.0 - smoothstep(0.05, 0.2, season)
+        } else {
+            smoothstep(0.6, 0.75, season)
+        }
+    }
+
+    /// Fraction of the leaves on the trees, they fall late in autumn and grow back in spring.
+    pub fn density(&self, season: f32) -> f32 {
+        if !self.is_enabled {
+            return 1.0;
+        }
+        let season = season.rem_euclid(1.0);
+        let growth = if season < 0.5 {
+            smoothstep(0.05, 0.2, season)
+        } else {
+            1.0 - smoothstep(0.8, 0.95, season)
+        };
+        self.winter_density + (1.0 - self.winter_density) * growth
+    }
+
+    /// The (bottom, tip) leaf colors for the season, from the colors the leaves have while green.
+    pub fn colors(&self, season: f32, green_colors: (Vec3, Vec3)) -> (Vec3, Vec3) {
+        let blend = self.autumn_blend(season);
+        let (bottom_color, tip_color) = green_colors;
+        (
+            bottom_color.lerp(color_to_vec3(self.autumn_bottom_color), blend),
+            tip_color.lerp(color_to_vec3(self.autumn_tip_color), blend),
+        )
+    }
+}
+
+fn color_to_vec3(color: Color32) -> Vec3 {
+    Vec3::new(color.r() as f32, color.g() as f32, color.b() as f32) / 255.0
+}
@@ -0,0 +1,105 @@
+use glam::Vec2;
+use std::f32::consts::TAU;
+
+/// Real seconds a downpour takes to soak dry ground.
+const SOAKING_SECONDS: f32 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrecipitationKind {
+    Rain,
+    Snow,
+}
+
+/// The rain, the snow and the wind, and how wet they left the world.
+#[derive(Debug, Clone)]
+pub struct Weather {
+    pub precipitation_kind: PrecipitationKind,
+    /// What the precipitation eases towards, from 0 when dry to 1 in a downpour.
+    pub target_precipitation: f32,
+    /// Real seconds the precipitation takes to ease from dry to a downpour.
+    pub transition_seconds: f32,
+    /// From calm at 0 to a storm at 1, bends the grass and blows the precipitation sideways.
+    pub wind_strength: f32,
+    /// Where the wind blows to, normalized to [0, 1) like the sun azimuth, 0 blows towards -z.
+    pub wind_azimuth: f32,
+    /// In-game hours soaked ground takes to dry off, it stays wet while the clock stands still.
+    pub drying_hours: f32,
+    precipitation: f32,
+    wetness: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            precipitation_kind: PrecipitationKind::Rain,
+            target_precipitation: 0.0,
+            transition_seconds: 20.0,
+            wind_strength: 0.3,
+            wind_azimuth: 0.2,
+            drying_hours: 2.0,
+            precipitation: 0.0,
+            wetness: 0.0,
+        }
+    }
+}
+
+impl Weather {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(
+                &mut self.precipitation_kind,
+                PrecipitationKind::Rain,
+                "Rain",
+            );
+            ui.selectable_value(
+                &mut self.precipitation_kind,
+                PrecipitationKind::Snow,
+                "Snow",
+            );
+        });
+        ui.add(egui::Slider::new(&mut self.target_precipitation, 0.0..=1.0).text("Precipitation"));
+        ui.add(egui::Slider::new(&mut self.transition_seconds, 0.0..=120.0).text("Transition (s)"));
+        ui.add(egui::Slider::new(&mut self.wind_strength, 0.0..=1.0).text("Wind Strength"));
+        ui.add(egui::Slider::new(&mut self.wind_azimuth, 0.0..=1.0).text("Wind Direction"));
+        ui.add(egui::Slider::new(&mut self.drying_hours, 0.1..=24.0).text("Drying Time (h)"));
+        ui.label(format!(
+            "Precipitation: {:.2}, Wetness: {:.2}",
+            self.precipitation, self.wetness
+        ));
+    }
+
+    /// Eases the precipitation over `delta_time` real seconds and wets or dries the ground, the
+    /// ground dries over the `days` the world clock moved on.
+    pub fn advance(&mut self, delta_time: f32, days: f32) {
+        let step = if self.transition_seconds > 0.0 {
+            delta_time / self.transition_seconds
+        } else {
+            1.0
+        };
+        let difference = self.target_precipitation - self.precipitation;
+        self.precipitation += difference.clamp(-step, step);
+
+        if self.precipitation_kind == PrecipitationKind::Rain && self.precipitation > 0.0 {
+            self.wetness += self.precipitation * delta_time / SOAKING_SECONDS;
+        } else {
+            self.wetness -= days * 24.0 / self.drying_hours;
+        }
+        self.wetness = self.wetness.clamp(0.0, 1.0);
+    }
+
+    /// How hard it's raining or snowing right now, from 0 when dry to 1 in a downpour.
+    pub fn precipitation(&self) -> f32 {
+        self.precipitation
+    }
+
+    /// From 0 on dry ground to 1 when soaked.
+    pub fn wetness(&self) -> f32 {
+        self.wetness
+    }
+
+    /// The horizontal direction the wind blows to.
+    pub fn wind_dir(&self) -> Vec2 {
+        let angle = self.wind_azimuth * TAU;
+        Vec2::new(angle.sin(), -angle.cos())
+    }
+}
@@ -0,0 +1,155 @@
+use super::world_clock::smoothstep;
+use super::Weather;
+use crate::constants::MAX_WIND_FIELD_CELLS;
+use crate::geom::UAabb3;
+use glam::{IVec2, UVec2, Vec2};
+use noise::{NoiseFn, Perlin};
+
+#[derive(Debug, Clone, Copy)]
+pub struct WindFieldDesc {
+    /// How much a full gust adds to the wind, 1.0 doubles it. The culling of the flora leaves
+    /// room for gusts up to that.
+    pub gust_strength: f32,
+    /// Rough size of a gust, in world units.
+    pub gust_scale: f32,
+    /// How fast the gusts sweep along with the wind, in world units per second.
+    pub gust_speed: f32,
+    /// How fast the gusts build up and die down while they travel.
+    pub gust_churn: f32,
+    /// How far a gust turns the wind aside, in radians.
+    pub gust_veer: f32,
+}
+
+impl Default for WindFieldDesc {
+    fn default() -> Self {
+        Self {
+            gust_strength: 1.0,
+            gust_scale: 1.5,
+            gust_speed: 0.8,
+            gust_churn: 0.15,
+            gust_veer: 0.3,
+        }
+    }
+}
+
+impl WindFieldDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.gust_strength, 0.0..=1.0).text("Gust Strength"));
+        ui.add(egui::Slider::new(&mut self.gust_scale, 0.2..=5.0).text("Gust Size"));
+        ui.add(egui::Slider::new(&mut self.gust_speed, 0.0..=4.0).text("Gust Speed"));
+        ui.add(egui::Slider::new(&mut self.gust_churn, 0.0..=1.0).text("Gust Churn"));
+        ui.add(egui::Slider::new(&mut self.gust_veer, 0.0..=1.0).text("Gust Veer"));
+    }
+}
+
+/// The wind across the world, one cell per chunk. Gusts of noise sweep along the wind of the
+/// [`Weather`], strengthen it and turn it aside a little.
+///
+/// The cells are copied to `U_WindField`, which bends the grass and sways the leaves. A world of
+/// more than [`MAX_WIND_FIELD_CELLS`] chunks gets cells spanning several chunks.
+pub struct WindField {
+    pub desc: WindFieldDesc,
+    cell_count: UVec2,
+    /// In world units.
+    cell_size: Vec2,
+    /// Row major in x, the wind of each cell, 1.0 long for a storm without gusts.
+    winds: Vec<Vec2>,
+    /// Row major in x, from 0 in still air to 1 in the heart of a gust.
+    gusts: Vec<f32>,
+    /// Real seconds the wind blew for.
+    time: f32,
+    noise: Perlin,
+}
+
+impl WindField {
+    /// The field spans the xz extent of `chunk_bound`.
+    pub fn new(desc: WindFieldDesc, chunk_bound: &UAabb3) -> Self {
+        let extent = UVec2::new(chunk_bound.max().x, chunk_bound.max().z).max(UVec2::ONE);
+        let cell_count_of = |chunks_per_cell: u32| (extent + chunks_per_cell - 1) / chunks_per_cell;
+        let mut chunks_per_cell = 1;
+        while cell_count_of(chunks_per_cell).element_product() > MAX_WIND_FIELD_CELLS {
+            chunks_per_cell += 1;
+        }
+        let cell_count = cell_count_of(chunks_per_cell);
+        let len = cell_count.element_product() as usize;
+        Self {
+            desc,
+            cell_count,
+            cell_size: extent.as_vec2() / cell_count.as_vec2(),
+            winds: vec![Vec2::ZERO; len],
+            gusts: vec![0.0; len],
+            time: 0.0,
+            noise: Perlin::new(7),
+        }
+    }
+
+    pub fn cell_count(&self) -> UVec2 {
+        self.cell_count
+    }
+
+    /// In world units.
+    pub fn cell_size(&self) -> Vec2 {
+        self.cell_size
+    }
+
+    pub fn winds(&self) -> &[Vec2] {
+        &self.winds
+    }
+
+    /// Blows the gusts on by `delta_time` real seconds along the wind of `weather`.
+    pub fn update(&mut self, delta_time: f32, weather: &Weather) {
+        self.time += delta_time;
+        let wind_dir = weather.wind_dir();
+        // the noise pattern travels with the wind, so a gust sweeps over the chunks downwind
+        let drift = wind_dir * self.time * self.desc.gust_speed;
+        let churn = (self.time * self.desc.gust_churn) as f64;
+        for y in 0..self.cell_count.y {
+            for x in 0..self.cell_count.x {
+                let center = (UVec2::new(x, y).as_vec2() + 0.5) * self.cell_size;
+                let p = (center - drift) / self.desc.gust_scale.max(0.01);
+                let n = self.noise.get([p.x as f64, p.y as f64, churn]) as f32;
+                // most of the time the air is calm, gusts only blow where the noise peaks
+                let gust = smoothstep(0.0, 0.5, n);
+                let veer = self
+                    .noise
+                    .get([p.x as f64 + 31.7, p.y as f64 - 12.3, churn])
+                    as f32;
+                let dir = Vec2::from_angle(veer * self.desc.gust_veer).rotate(wind_dir);
+
+                let idx = (y * self.cell_count.x + x) as usize;
+                self.gusts[idx] = gust;
+                self.winds[idx] =
+                    dir * weather.wind_strength * (1.0 + self.desc.gust_strength * gust);
+            }
+        }
+    }
+
+    /// The wind at `pos_xz` (in world units), bilinearly filtered between the cell centers like
+    /// `sample_wind_field` in `wind.glsl`.
+    pub fn wind_at(&self, pos_xz: Vec2) -> Vec2 {
+        self.sample(pos_xz, &self.winds)
+    }
+
+    /// How gusty it is at `pos_xz` (in world units), from 0 to 1.
+    pub fn gust_at(&self, pos_xz: Vec2) -> f32 {
+        self.sample(pos_xz, &self.gusts)
+    }
+
+    fn sample<T>(&self, pos_xz: Vec2, cells: &[T]) -> T
+    where
+        T: Copy + std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+    {
+        let cell_pos = pos_xz / self.cell_size - 0.5;
+        let base = cell_pos.floor();
+        let t = cell_pos - base;
+        let max_cell = self.cell_count.as_ivec2() - 1;
+        let cell = |dx: i32, dy: i32| {
+            let c = (base.as_ivec2() + IVec2::new(dx, dy)).clamp(IVec2::ZERO, max_cell);
+            cells[(c.y * self.cell_count.x as i32 + c.x) as usize]
+        };
+        let lerp = |a: T, b: T, t: f32| a * (1.0 - t) + b * t;
+        let bottom = lerp(cell(0, 0), cell(1, 0), t.x);
+        let top = lerp(cell(0, 1), cell(1, 1), t.x);
+        lerp(bottom, top, t.y)
+    }
+}
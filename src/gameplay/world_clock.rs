@@ -0,0 +1,210 @@
+use std::f32::consts::{PI, TAU};
+
+/// In-game days from one new moon to the next.
+const LUNAR_MONTH_DAYS: f32 = 29.53;
+/// Earth's axial tilt, the furthest the sun gets from the equator.
+const AXIAL_TILT_DEG: f32 = 23.44;
+
+/// The stars start fading in once the sun sinks below this altitude and are fully out below
+/// the second one.
+const STAR_FADE_ALTITUDES: (f32, f32) = (-0.05, -0.4);
+/// How much of the stars a full moon washes out.
+const FULL_MOON_STAR_DIMMING: f32 = 0.6;
+
+/// Daylight fades out between these sun altitudes.
+const DAYLIGHT_FADE_ALTITUDES: (f32, f32) = (-0.2, 0.05);
+/// The share of the ambient light left in the dead of night.
+const NIGHT_AMBIENT_SCALE: f32 = 0.2;
+
+/// Owns the time of the world and everything the sky derives from it: the sun, the moon and how
+/// much the stars show.
+#[derive(Debug, Clone)]
+pub struct WorldClock {
+    /// Whether the time moves on by itself.
+    pub is_running: bool,
+    /// 0.0 is midnight, 0.5 is noon.
+    pub time_of_day: f32,
+    /// 0.0 is the winter solstice, 0.25 the spring equinox, 0.5 the summer solstice and 0.75 the
+    /// autumn equinox.
+    pub season: f32,
+    /// -1.0 is the south pole, 0.0 the equator and 1.0 the north pole.
+    pub latitude: f32,
+    /// 0.0 is the new moon, 0.5 the full moon.
+    pub moon_phase: f32,
+    /// Real minutes a whole in-game day takes.
+    pub day_cycle_minutes: f32,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self {
+            is_running: true,
+            time_of_day: 0.65,
+            season: 0.25,
+            latitude: 0.5,
+            moon_phase: 0.5,
+            day_cycle_minutes: 30.0,
+        }
+    }
+}
+
+impl WorldClock {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_running, "Auto Day/Night Cycle");
+        ui.add(
+            egui::Slider::new(&mut self.time_of_day, 0.0..=1.0)
+                .text("Time of Day (0:00 - 23:59)")
+                .custom_formatter(|n, _| {
+                    let hour = (n * 24.0) as u32 % 24;
+                    let minute = (n * 24.0 * 60.0) as u32 % 60;
+                    format!("{:02}:{:02}", hour, minute)
+                }),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.latitude, -1.0..=1.0)
+                .text("Latitude (South Pole to North Pole)")
+                .custom_formatter(|n, _| {
+                    if n < -0.5 {
+                        format!("South ({:.1})", n)
+                    } else if n > 0.5 {
+                        format!("North ({:.1})", n)
+                    } else {
+                        format!("Equator ({:.1})", n)
+                    }
+                }),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.season, 0.0..=1.0)
+                .text("Season (Winter to Summer)")
+                .custom_formatter(|n, _| {
+                    let name = if n < 0.125 {
+                        "Winter"
+                    } else if n < 0.375 {
+                        "Spring"
+                    } else if n < 0.625 {
+                        "Summer"
+                    } else if n < 0.875 {
+                        "Autumn"
+                    } else {
+                        "Winter"
+                    };
+                    name.to_string()
+                }),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.moon_phase, 0.0..=1.0)
+                .text("Moon Phase (New to Full)")
+                .custom_formatter(|n, _| {
+                    let name = if n < 0.0625 || n >= 0.9375 {
+                        "New"
+                    } else if n < 0.4375 {
+                        "Waxing"
+                    } else if n < 0.5625 {
+                        "Full"
+                    } else {
+                        "Waning"
+                    };
+                    name.to_string()
+                }),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.day_cycle_minutes, 0.1..=60.0)
+                .text("Day Cycle Duration (Real Minutes)")
+                .custom_formatter(|n, _| {
+                    if n < 1.0 {
+                        format!("{:.1}s", n * 60.0)
+                    } else {
+                        format!("{:.1}m", n)
+                    }
+                }),
+        );
+
+        let (altitude, azimuth) = self.sun_position();
+        ui.separator();
+        ui.label(format!("Sun Altitude: {:.3}", altitude));
+        ui.label(format!("Sun Azimuth: {:.3}", azimuth));
+        ui.label(format!("Star Visibility: {:.2}", self.star_visibility()));
+    }
+
+    /// Moves the time on by `delta_time` real seconds while the clock runs, returns the in-game
+    /// days that passed.
+    pub fn advance(&mut self, delta_time: f32) -> f32 {
+        if !self.is_running {
+            return 0.0;
+        }
+        let days = delta_time / (self.day_cycle_minutes * 60.0);
+        self.time_of_day = (self.time_of_day + days).rem_euclid(1.0);
+        self.moon_phase = (self.moon_phase + days / LUNAR_MONTH_DAYS).rem_euclid(1.0);
+        days
+    }
+
+    /// Jumps to `time_of_day`, the moon stays where it is.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    /// The altitude, normalized to [-1, 1], and the azimuth, normalized to [0, 1), of the sun.
+    pub fn sun_position(&self) -> (f32, f32) {
+        // solar noon is at time_of_day = 0.5
+        let hour_angle = (self.time_of_day - 0.5) * TAU;
+        // the sun is furthest south at the winter solstice
+        let declination = -AXIAL_TILT_DEG.to_radians() * (self.season * TAU).cos();
+        let latitude = self.latitude * PI * 0.5;
+
+        let elevation = (declination.sin() * latitude.sin()
+            + declination.cos() * latitude.cos() * hour_angle.cos())
+        .asin();
+        let azimuth = if hour_angle.cos() == 0.0 {
+            if hour_angle > 0.0 {
+                PI
+            } else {
+                0.0
+            }
+        } else {
+            (declination.sin() * latitude.cos()
+                - declination.cos() * latitude.sin() * hour_angle.cos())
+            .atan2(hour_angle.sin())
+        };
+
+        let altitude = (elevation / (PI * 0.5)).clamp(-1.0, 1.0);
+        (altitude, ((azimuth + PI) / TAU) % 1.0)
+    }
+
+    /// How lit the moon is, 0.0 at the new moon and 1.0 at the full moon.
+    pub fn moon_illumination(&self) -> f32 {
+        (1.0 - (self.moon_phase * TAU).cos()) * 0.5
+    }
+
+    /// 0.0 at night and 1.0 once the sun is up.
+    pub fn daylight(&self) -> f32 {
+        let (altitude, _) = self.sun_position();
+        smoothstep(
+            DAYLIGHT_FADE_ALTITUDES.0,
+            DAYLIGHT_FADE_ALTITUDES.1,
+            altitude,
+        )
+    }
+
+    /// How much the stars show through the sky, the moon outshines part of them.
+    pub fn star_visibility(&self) -> f32 {
+        let (altitude, _) = self.sun_position();
+        let night = smoothstep(STAR_FADE_ALTITUDES.0, STAR_FADE_ALTITUDES.1, altitude);
+        night * (1.0 - FULL_MOON_STAR_DIMMING * self.moon_illumination())
+    }
+
+    /// How much the moon brightens the night, 0.0 by day or at the new moon.
+    pub fn moon_glow(&self) -> f32 {
+        (1.0 - self.daylight()) * self.moon_illumination()
+    }
+
+    /// The share of the ambient light the sun gives at this time, the moon adds its own.
+    pub fn ambient_scale(&self) -> f32 {
+        NIGHT_AMBIENT_SCALE + (1.0 - NIGHT_AMBIENT_SCALE) * self.daylight()
+    }
+}
+
+/// Same as GLSL's `smoothstep`, `edge0` may be above `edge1`.
+pub(super) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
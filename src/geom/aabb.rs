@@ -17,6 +17,13 @@ impl Default for Aabb3 {
     }
 }
 
+impl From<&Aabb3> for UAabb3 {
+    /// Rounds outwards to the voxels the box touches, negative coordinates clamp to 0.
+    fn from(value: &Aabb3) -> Self {
+        Self::new(value.min_uvec3(), value.max_uvec3())
+    }
+}
+
 impl From<UAabb3> for Aabb3 {
     fn from(value: UAabb3) -> Self {
         Self {
@@ -163,7 +170,7 @@ impl Aabb3 {
     /// For a point P, the distance to plane is: dot(P, plane.xyz) + plane.w
     /// - Positive distance = point is on the "inside" (visible) side
     /// - Negative distance = point is on the "outside" (culled) side
-    fn extract_frustum_planes(view_proj_mat: Mat4) -> [Vec4; 6] {
+    pub(super) fn extract_frustum_planes(view_proj_mat: Mat4) -> [Vec4; 6] {
         let m = view_proj_mat.to_cols_array_2d();
 
         [
@@ -285,7 +292,6 @@ impl UAabb3 {
     ///
     /// This will panic if `max` is less than `min` on any axis due to unsigned subtraction.
     /// Consider adding checks or using `saturating_sub` if this is a concern.
-    #[allow(dead_code)]
     pub fn dimensions(&self) -> UVec3 {
         self.max - self.min
     }
@@ -358,6 +364,39 @@ impl UAabb3 {
         self.min.x < self.max.x && self.min.y < self.max.y && self.min.z < self.max.z
     }
 
+    /// Returns the overlap of both AABBs with inclusive bounds, `None` if they don't touch.
+    #[allow(dead_code)]
+    pub fn intersection(&self, other: &UAabb3) -> Option<UAabb3> {
+        let overlap = UAabb3::new(self.min.max(other.min), self.max.min(other.max));
+        overlap.is_valid().then_some(overlap)
+    }
+
+    /// Grows the AABB by `margin` on every side, the min corner stops at 0.
+    #[allow(dead_code)]
+    pub fn expand(&self, margin: u32) -> UAabb3 {
+        UAabb3::new(
+            self.min.saturating_sub(UVec3::splat(margin)),
+            self.max.saturating_add(UVec3::splat(margin)),
+        )
+    }
+
+    /// Iterates the indices of the chunks of `chunk_size` that the AABB touches, treating both
+    /// corners as inclusive.
+    pub fn iter_chunks(&self, chunk_size: UVec3) -> impl Iterator<Item = UVec3> {
+        let min_chunk = self.min / chunk_size;
+        let max_chunk = self.max / chunk_size;
+        (min_chunk.x..=max_chunk.x).flat_map(move |x| {
+            (min_chunk.y..=max_chunk.y)
+                .flat_map(move |y| (min_chunk.z..=max_chunk.z).map(move |z| UVec3::new(x, y, z)))
+        })
+    }
+
+    /// See [`Aabb3::is_inside_frustum`], the corners are taken as they are.
+    #[allow(dead_code)]
+    pub fn is_inside_frustum(&self, view_proj_mat: Mat4) -> bool {
+        Aabb3::from(*self).is_inside_frustum(view_proj_mat)
+    }
+
     pub fn in_bound(&self, element_id: UVec3) -> bool {
         element_id.x >= self.min.x
             && element_id.x < self.max.x
@@ -0,0 +1,280 @@
+use glam::{BVec4A, Mat4, Vec4};
+
+use super::Aabb3;
+
+/// The 6 planes of a view frustum, extracted once to test many boxes against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    pub fn from_view_proj(view_proj_mat: Mat4) -> Self {
+        Self {
+            planes: Aabb3::extract_frustum_planes(view_proj_mat),
+        }
+    }
+
+    /// Same result as [`Aabb3::is_inside_frustum`], a box is only culled when it lies fully
+    /// outside one of the planes.
+    pub fn intersects_aabb(&self, aabb: &Aabb3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal decides
+            let corner = Vec4::new(
+                if plane.x >= 0.0 {
+                    aabb.max().x
+                } else {
+                    aabb.min().x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max().y
+                } else {
+                    aabb.min().y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max().z
+                } else {
+                    aabb.min().z
+                },
+                1.0,
+            );
+            plane.dot(corner) >= 0.0
+        })
+    }
+}
+
+/// AABBs laid out as structure of arrays, so one SIMD op tests a plane against 4 boxes.
+#[derive(Debug, Default)]
+pub struct AabbBatch {
+    min_x: Vec<Vec4>,
+    min_y: Vec<Vec4>,
+    min_z: Vec<Vec4>,
+    max_x: Vec<Vec4>,
+    max_y: Vec<Vec4>,
+    max_z: Vec<Vec4>,
+    len: usize,
+}
+
+impl AabbBatch {
+    pub fn from_aabbs<'a>(aabbs: impl IntoIterator<Item = &'a Aabb3>) -> Self {
+        let mut batch = Self::default();
+        // the padding lanes of the last group stay zero sized boxes, their results are dropped
+        let mut lanes = [[0.0; 4]; 6];
+        for aabb in aabbs {
+            let lane = batch.len % 4;
+            let (min, max) = (aabb.min(), aabb.max());
+            for (axis, value) in [min.x, min.y, min.z, max.x, max.y, max.z]
+                .into_iter()
+                .enumerate()
+            {
+                lanes[axis][lane] = value;
+            }
+            batch.len += 1;
+            if lane == 3 {
+                batch.push_lanes(&lanes);
+                lanes = [[0.0; 4]; 6];
+            }
+        }
+        if batch.len % 4 != 0 {
+            batch.push_lanes(&lanes);
+        }
+        batch
+    }
+
+    fn push_lanes(&mut self, lanes: &[[f32; 4]; 6]) {
+        self.min_x.push(Vec4::from_array(lanes[0]));
+        self.min_y.push(Vec4::from_array(lanes[1]));
+        self.min_z.push(Vec4::from_array(lanes[2]));
+        self.max_x.push(Vec4::from_array(lanes[3]));
+        self.max_y.push(Vec4::from_array(lanes[4]));
+        self.max_z.push(Vec4::from_array(lanes[5]));
+    }
+
+    pub fn aabb_count(&self) -> usize {
+        self.len
+    }
+
+    /// Writes one visibility flag per box into `visible`, in the order the boxes were given.
+    pub fn cull(&self, frustum: &Frustum, visible: &mut Vec<bool>) {
+        visible.clear();
+        visible.reserve(self.len);
+        for group in 0..self.min_x.len() {
+            let mut is_outside = BVec4A::splat(false);
+            for plane in &frustum.planes {
+                // the sign of the normal is the same for all 4 boxes, so is the corner to pick
+                let x = if plane.x >= 0.0 {
+                    self.max_x[group]
+                } else {
+                    self.min_x[group]
+                };
+                let y = if plane.y >= 0.0 {
+                    self.max_y[group]
+                } else {
+                    self.min_y[group]
+                };
+                let z = if plane.z >= 0.0 {
+                    self.max_z[group]
+                } else {
+                    self.min_z[group]
+                };
+                let distance = x * plane.x + y * plane.y + z * plane.z + Vec4::splat(plane.w);
+                is_outside |= distance.cmplt(Vec4::ZERO);
+            }
+
+            let outside_bits = is_outside.bitmask();
+            let lane_count = (self.len - group * 4).min(4);
+            visible.extend((0..lane_count).map(|lane| outside_bits & (1 << lane) == 0));
+        }
+    }
+}
+
+/// Visibility of a set of boxes, only culled again when the view or the boxes change.
+#[derive(Debug, Default)]
+pub struct FrustumCullCache {
+    batch: AabbBatch,
+    view_proj_mat: Option<Mat4>,
+    visible: Vec<bool>,
+}
+
+impl FrustumCullCache {
+    pub fn set_aabbs<'a>(&mut self, aabbs: impl IntoIterator<Item = &'a Aabb3>) {
+        self.batch = AabbBatch::from_aabbs(aabbs);
+        self.view_proj_mat = None;
+    }
+
+    pub fn aabb_count(&self) -> usize {
+        self.batch.aabb_count()
+    }
+
+    /// One flag per box, in the order given to [`Self::set_aabbs`].
+    pub fn visibility(&mut self, view_proj_mat: Mat4) -> &[bool] {
+        if self.view_proj_mat != Some(view_proj_mat) {
+            self.batch
+                .cull(&Frustum::from_view_proj(view_proj_mat), &mut self.visible);
+            self.view_proj_mat = Some(view_proj_mat);
+        }
+        &self.visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn view_proj_mat() -> Mat4 {
+        let proj = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, 0.1, 100.0);
+        let view = Mat4::look_at_rh(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.5, 1.8, -7.0), Vec3::Y);
+        proj * view
+    }
+
+    fn cube(center: Vec3, half_size: f32) -> Aabb3 {
+        Aabb3::new(center - half_size, center + half_size)
+    }
+
+    /// Boxes scattered all around the camera, in and out of view.
+    fn scattered_aabbs(count: usize) -> Vec<Aabb3> {
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1u32 << 24) as f32
+        };
+        (0..count)
+            .map(|_| {
+                let min = Vec3::new(
+                    next() * 120.0 - 60.0,
+                    next() * 60.0 - 30.0,
+                    next() * 140.0 - 120.0,
+                );
+                let size = Vec3::new(next(), next(), next()) * 5.0;
+                Aabb3::new(min, min + size)
+            })
+            .collect()
+    }
+
+    /// For each plane, a small box cut in two by it and one past it, both inside the other
+    /// planes.
+    fn plane_aabbs(frustum: &Frustum) -> (Vec<Aabb3>, Vec<Aabb3>) {
+        let inside = Vec3::new(1.4, 1.9, -7.0);
+        let far_away = 500.0;
+        let directions = [
+            Vec3::NEG_X,
+            Vec3::X,
+            Vec3::NEG_Y,
+            Vec3::Y,
+            // through the eye, the near plane is crossed right in front of it
+            Vec3::new(-0.4, 0.1, 10.0).normalize(),
+            Vec3::new(0.4, -0.1, -10.0).normalize(),
+        ];
+        let mut straddling = Vec::new();
+        let mut outside = Vec::new();
+        for (plane, direction) in frustum.planes.iter().zip(directions) {
+            let outer = inside + direction * far_away;
+            let d0 = plane.dot(inside.extend(1.0));
+            let d1 = plane.dot(outer.extend(1.0));
+            assert!(d0 > 0.0 && d1 < 0.0, "{:?} isn't crossed", plane);
+            let crossing = inside.lerp(outer, d0 / (d0 - d1));
+            let past = inside.lerp(outer, (d0 / (d0 - d1) * 1.5).min(1.0));
+            straddling.push(cube(crossing, 0.01));
+            outside.push(cube(past + direction, 0.01));
+        }
+        (straddling, outside)
+    }
+
+    fn scalar_visibility(aabbs: &[Aabb3], view_proj_mat: Mat4) -> Vec<bool> {
+        aabbs
+            .iter()
+            .map(|aabb| aabb.is_inside_frustum(view_proj_mat))
+            .collect()
+    }
+
+    #[test]
+    fn plane_aabbs_are_culled_like_the_scalar_test() {
+        let view_proj_mat = view_proj_mat();
+        let frustum = Frustum::from_view_proj(view_proj_mat);
+        let (straddling, outside) = plane_aabbs(&frustum);
+
+        let mut visible = Vec::new();
+        AabbBatch::from_aabbs(&straddling).cull(&frustum, &mut visible);
+        assert_eq!(visible, vec![true; straddling.len()]);
+        assert_eq!(visible, scalar_visibility(&straddling, view_proj_mat));
+
+        AabbBatch::from_aabbs(&outside).cull(&frustum, &mut visible);
+        assert_eq!(visible, vec![false; outside.len()]);
+        assert_eq!(visible, scalar_visibility(&outside, view_proj_mat));
+    }
+
+    #[test]
+    fn batch_matches_scalar_test_with_padding_lanes() {
+        let view_proj_mat = view_proj_mat();
+        let frustum = Frustum::from_view_proj(view_proj_mat);
+        let (straddling, outside) = plane_aabbs(&frustum);
+        let mut aabbs = scattered_aabbs(61);
+        // interleaved, so the plane boxes land in every lane
+        for (i, aabb) in straddling.into_iter().chain(outside).enumerate() {
+            aabbs.insert(i * 5, aabb);
+        }
+
+        let mut visible = Vec::new();
+        for count in [1, 2, 3, 5, 6, 7, 13, 30, 31, 73] {
+            let aabbs = &aabbs[..count];
+            let batch = AabbBatch::from_aabbs(aabbs);
+            assert_eq!(batch.aabb_count(), count);
+            batch.cull(&frustum, &mut visible);
+            assert_eq!(
+                visible,
+                scalar_visibility(aabbs, view_proj_mat),
+                "{} boxes",
+                count
+            );
+
+            let single = aabbs
+                .iter()
+                .map(|aabb| frustum.intersects_aabb(aabb))
+                .collect::<Vec<_>>();
+            assert_eq!(visible, single, "{} boxes", count);
+        }
+        // some of the scattered boxes are seen and some aren't
+        assert!(visible.contains(&true) && visible.contains(&false));
+    }
+}
@@ -6,3 +6,9 @@ pub use aabb::*;
 
 mod shape;
 pub use shape::*;
+
+mod space;
+pub use space::*;
+
+mod frustum;
+pub use frustum::*;
@@ -1,4 +1,4 @@
-use glam::Vec3;
+use glam::{Quat, Vec3};
 
 use crate::geom::Aabb3;
 
@@ -42,6 +42,12 @@ impl RoundCone {
         self.center_b += offset;
     }
 
+    /// Rotates both centers around the origin.
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.center_a = rotation * self.center_a;
+        self.center_b = rotation * self.center_b;
+    }
+
     #[allow(dead_code)]
     pub fn scale(&mut self, scale: Vec3) {
         self.radius_a *= scale.x;
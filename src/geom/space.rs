@@ -0,0 +1,132 @@
+//! Newtypes for the coordinate spaces positions travel between.
+//!
+//! World space is measured in chunks, one world unit spans [`VOXEL_DIM`] voxels. Voxel space is
+//! the integer grid of the chunk atlas, and a chunk sits at `chunk_idx * VOXEL_DIM` in it.
+
+use crate::constants::VOXEL_DIM;
+use glam::{UVec3, Vec3};
+use std::fmt;
+
+use super::{Aabb3, UAabb3};
+
+const VOXEL_DIM_PER_CHUNK: UVec3 = UVec3::splat(VOXEL_DIM);
+
+/// A position in world units, the space of the camera and the terrain queries.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WorldPos(pub Vec3);
+
+/// A voxel of the chunk atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct VoxelPos(pub UVec3);
+
+/// The index of a chunk in the chunk grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkIdx(pub UVec3);
+
+/// The first voxel of a chunk inside the chunk atlas, always a multiple of [`VOXEL_DIM`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AtlasOffset(pub UVec3);
+
+impl WorldPos {
+    #[allow(dead_code)]
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Self(Vec3::new(x, y, z))
+    }
+
+    /// Builds a world position from a fractional position in voxel units.
+    pub fn from_voxel_space(pos: Vec3) -> Self {
+        Self(pos / VOXEL_DIM as f32)
+    }
+
+    /// The position in voxel units, without snapping to the grid.
+    pub fn to_voxel_space(self) -> Vec3 {
+        self.0 * VOXEL_DIM as f32
+    }
+
+    #[allow(dead_code)]
+    /// The voxel containing the position, negative coordinates clamp to 0.
+    pub fn to_voxel(self) -> VoxelPos {
+        VoxelPos(self.to_voxel_space().floor().max(Vec3::ZERO).as_uvec3())
+    }
+
+    #[allow(dead_code)]
+    pub fn chunk_idx(self) -> ChunkIdx {
+        self.to_voxel().chunk_idx()
+    }
+}
+
+impl VoxelPos {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self(UVec3::new(x, y, z))
+    }
+
+    /// The world position of the voxel's minimum corner.
+    pub fn to_world(self) -> WorldPos {
+        WorldPos::from_voxel_space(self.0.as_vec3())
+    }
+
+    pub fn chunk_idx(self) -> ChunkIdx {
+        ChunkIdx(self.0 / VOXEL_DIM_PER_CHUNK)
+    }
+}
+
+impl ChunkIdx {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self(UVec3::new(x, y, z))
+    }
+
+    pub fn atlas_offset(self) -> AtlasOffset {
+        AtlasOffset(self.0 * VOXEL_DIM_PER_CHUNK)
+    }
+
+    /// The voxels the chunk covers, inclusive on both ends.
+    pub fn voxel_bound(self) -> UAabb3 {
+        let min = self.atlas_offset().0;
+        UAabb3::new(min, min + VOXEL_DIM_PER_CHUNK - UVec3::ONE)
+    }
+}
+
+impl Aabb3 {
+    /// The world space box covering the voxels of `bound`, which is inclusive on both ends.
+    pub fn from_voxel_bound(bound: &UAabb3) -> Self {
+        Self::new(
+            WorldPos::from_voxel_space(bound.min().as_vec3()).0,
+            WorldPos::from_voxel_space((bound.max() + UVec3::ONE).as_vec3()).0,
+        )
+    }
+}
+
+impl AtlasOffset {
+    pub fn chunk_idx(self) -> ChunkIdx {
+        ChunkIdx(self.0 / VOXEL_DIM_PER_CHUNK)
+    }
+
+    #[allow(dead_code)]
+    pub fn to_voxel(self) -> VoxelPos {
+        VoxelPos(self.0)
+    }
+}
+
+impl fmt::Display for WorldPos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for VoxelPos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for ChunkIdx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for AtlasOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
@@ -0,0 +1,70 @@
+//! Re: Flora, a voxel gardening game rendered with a Vulkan ray tracer.
+//!
+//! [`Engine`] opens the window and drives the game, the [`World`], [`Renderer`] and [`Audio`]
+//! facades it hands out are the only way in, the builders and the Vulkan wrapper stay internal.
+
+mod app;
+mod audio;
+mod builder;
+mod constants;
+mod egui_renderer;
+mod engine;
+mod gameplay;
+mod geom;
+mod procedual_placer;
+mod resource;
+mod tracer;
+mod tree_gen;
+mod util;
+mod vkn;
+mod window;
+
+pub use engine::*;
+
+use env_logger::Env;
+
+/// Logs to stderr and into the diagnostics bundle written on crashes.
+pub fn init_logger() {
+    env_logger::Builder::from_env(
+        Env::default().default_filter_or(
+            "debug,symphonia_core=warn,symphonia_format_riff=warn,petalsonic=info",
+        ),
+    )
+    .format(|buf, record| {
+        use std::io::Write;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let local_time = chrono::DateTime::from_timestamp_millis(now as i64)
+            .unwrap()
+            .with_timezone(&chrono::Local);
+
+        let line = format!(
+            "[{} {} {}] {}",
+            local_time.format("%H:%M:%S%.3f"),
+            record.level(),
+            record.module_path().unwrap_or("<unknown>"),
+            record.args()
+        );
+        writeln!(buf, "{}", line)?;
+        if let Ok(mut diagnostics) = util::DIAGNOSTICS.lock() {
+            diagnostics.push_log_line(line);
+        }
+        Ok(())
+    })
+    .init();
+}
+
+// fn play_audio_with_cpal() -> Result<()> {
+//     use crate::audio::{get_audio_data, play_audio_samples};
+
+//     // Step 1: Decode audio data using symphonia
+//     let audio_path = "assets/sfx/Tree Gusts/WINDGust_Wind, Gust in Trees 01_SARM_Wind.wav";
+//     let (samples, sample_rate) = get_audio_data(audio_path)?;
+
+//     // Step 2: Play audio data using cpal
+//     play_audio_samples(samples, sample_rate)?;
+
+//     Ok(())
+// }
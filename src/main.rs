@@ -1,20 +1,4 @@
-mod app;
-mod audio;
-mod builder;
-mod egui_renderer;
-mod gameplay;
-mod geom;
-mod procedual_placer;
-mod resource;
-mod tracer;
-mod tree_gen;
-mod util;
-mod vkn;
-mod window;
-
-use app::AppController;
-use env_logger::Env;
-use winit::event_loop::EventLoop;
+use re_flora::{CameraPath, Engine, HeadlessRenderDesc};
 
 #[allow(dead_code)]
 fn backtrace_on() {
@@ -22,55 +6,34 @@ fn backtrace_on() {
     env::set_var("RUST_BACKTRACE", "1");
 }
 
-fn init_env_logger() {
-    env_logger::Builder::from_env(
-        Env::default().default_filter_or(
-            "debug,symphonia_core=warn,symphonia_format_riff=warn,petalsonic=info",
-        ),
-    )
-    .format(|buf, record| {
-        use std::io::Write;
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        let local_time = chrono::DateTime::from_timestamp_millis(now as i64)
-            .unwrap()
-            .with_timezone(&chrono::Local);
-
-        writeln!(
-            buf,
-            "[{} {} {}] {}",
-            local_time.format("%H:%M:%S%.3f"),
-            record.level(),
-            record.module_path().unwrap_or("<unknown>"),
-            record.args()
-        )
-    })
-    .init();
-}
-
-// fn play_audio_with_cpal() -> Result<()> {
-//     use crate::audio::{get_audio_data, play_audio_samples};
-
-//     // Step 1: Decode audio data using symphonia
-//     let audio_path = "assets/sfx/Tree Gusts/WINDGust_Wind, Gust in Trees 01_SARM_Wind.wav";
-//     let (samples, sample_rate) = get_audio_data(audio_path)?;
-
-//     // Step 2: Play audio data using cpal
-//     play_audio_samples(samples, sample_rate)?;
-
-//     Ok(())
-// }
-
 pub fn main() {
     // backtrace_on();
 
-    init_env_logger();
-
-    let mut app = AppController::default();
-    let event_loop = EventLoop::builder().build().unwrap();
-    let result = event_loop.run_app(&mut app);
+    re_flora::init_logger();
+
+    // `--turntable [frame_count]` renders a turntable of the island into `renders/` instead of
+    // opening the window, `--flythrough [frame_count]` the camera path last saved in the app
+    let args: Vec<String> = std::env::args().collect();
+    let headless_arg = args
+        .iter()
+        .position(|arg| arg == "--turntable" || arg == "--flythrough");
+    let result = match headless_arg {
+        Some(idx) => {
+            let mut desc = HeadlessRenderDesc::default();
+            if let Some(frame_count) = args.get(idx + 1).and_then(|arg| arg.parse().ok()) {
+                desc.frame_count = frame_count;
+            }
+            if args[idx] == "--flythrough" {
+                CameraPath::load_recorded().and_then(|camera_path| {
+                    desc.camera_path = camera_path;
+                    Engine::render_headless(&desc)
+                })
+            } else {
+                Engine::render_headless(&desc)
+            }
+        }
+        None => Engine::run(|_| {}),
+    };
 
     match result {
         Ok(_) => log::info!("Application exited successfully"),
@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 
+mod prop_placer;
+pub use prop_placer::*;
+
 use glam::Vec2;
 use noise::{Fbm, NoiseFn, OpenSimplex, Perlin, Seedable};
 use rand::{rng, Rng};
@@ -99,7 +102,8 @@ const JITTER_MULTIPLIER: f32 = 0.9;
 ///
 /// The function divides the map into a grid. For each grid cell, it samples a noise
 /// value. If the value is above a threshold, it places an object at a
-/// randomly jittered position within that cell.
+/// randomly jittered position within that cell. The positions are in the units of
+/// `map_dimensions`.
 pub fn generate_positions(
     map_dimensions: Vec2,
     map_offset: Vec2,
@@ -161,6 +165,5 @@ pub fn generate_positions(
         }
     }
     positions.iter_mut().for_each(|p| *p += map_offset);
-    positions.iter_mut().for_each(|p| *p /= 256.0);
     positions
 }
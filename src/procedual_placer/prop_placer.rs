@@ -0,0 +1,203 @@
+use super::{generate_positions, PlacerDesc};
+use crate::constants::VOXEL_DIM;
+use crate::geom::RoundCone;
+use crate::tracer::VoxelMaterial;
+use glam::{Vec2, Vec3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+
+/// Round cones past this count go into another `chunk_modify` call.
+const MAX_ROUND_CONES_PER_BATCH: usize = 256;
+
+/// What a prop is built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropShape {
+    /// A few overlapping lumps half sunk into the ground.
+    Rock,
+    /// A short trunk with roots spreading out at its foot.
+    Stump,
+}
+
+/// One kind of static prop, scattered with its own noise.
+#[derive(Debug, Clone)]
+pub struct PropLayer {
+    pub name: &'static str,
+    pub is_enabled: bool,
+    pub shape: PropShape,
+    pub material: VoxelMaterial,
+    pub placer_desc: PlacerDesc,
+    /// Cell size of the placement grid, in voxels.
+    pub grid_size: f32,
+    /// The size of each prop is drawn from [min_size, max_size), in voxels.
+    pub min_size: f32,
+    pub max_size: f32,
+}
+
+/// Scatters rocks, stumps and other static props over the terrain, the counterpart of the tree
+/// placer for anything that is only voxels. The props are carved into the chunk atlas, so they
+/// are rendered like the terrain.
+pub struct PropPlacer {
+    pub layers: Vec<PropLayer>,
+}
+
+impl PropPlacer {
+    /// The layers are seeded from `seed`, each with its own offset so they don't line up with
+    /// the trees or each other.
+    pub fn new(seed: u32) -> Self {
+        let mut rock_placer_desc = PlacerDesc::new(seed.wrapping_add(1));
+        rock_placer_desc.threshold = 0.6;
+        let mut stump_placer_desc = PlacerDesc::new(seed.wrapping_add(2));
+        stump_placer_desc.threshold = 0.65;
+
+        Self {
+            layers: vec![
+                PropLayer {
+                    name: "Rocks",
+                    is_enabled: true,
+                    shape: PropShape::Rock,
+                    material: VoxelMaterial::Rock,
+                    placer_desc: rock_placer_desc,
+                    grid_size: 60.0,
+                    min_size: 3.0,
+                    max_size: 8.0,
+                },
+                PropLayer {
+                    name: "Stumps",
+                    is_enabled: true,
+                    shape: PropShape::Stump,
+                    material: VoxelMaterial::Trunk,
+                    placer_desc: stump_placer_desc,
+                    grid_size: 150.0,
+                    min_size: 4.0,
+                    max_size: 7.0,
+                },
+            ],
+        }
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        for layer in &mut self.layers {
+            ui.checkbox(&mut layer.is_enabled, layer.name);
+            if !layer.is_enabled {
+                continue;
+            }
+            ui.add(
+                egui::Slider::new(&mut layer.placer_desc.threshold, 0.0..=1.0).text("Threshold"),
+            );
+            ui.add(egui::Slider::new(&mut layer.grid_size, 20.0..=400.0).text("Spacing"));
+            ui.add(egui::Slider::new(&mut layer.min_size, 1.0..=16.0).text("Min Size"));
+            ui.add(egui::Slider::new(&mut layer.max_size, 1.0..=16.0).text("Max Size"));
+            layer.max_size = layer.max_size.max(layer.min_size);
+        }
+        ui.label("Applied the next time the forest is regenerated.");
+    }
+
+    /// Where the layer at `layer_idx` puts its props, in voxels. Disabled layers place nothing.
+    pub fn generate_positions(
+        &self,
+        layer_idx: usize,
+        map_dimensions: Vec2,
+        map_offset: Vec2,
+    ) -> Vec<Vec2> {
+        let layer = &self.layers[layer_idx];
+        if !layer.is_enabled {
+            return Vec::new();
+        }
+        generate_positions(
+            map_dimensions,
+            map_offset,
+            layer.grid_size,
+            &layer.placer_desc,
+        )
+    }
+
+    /// Builds the props of the layer at `layer_idx` standing on `bases`, in voxels, and groups
+    /// their round cones into batches for `PlainBuilder::chunk_modify`.
+    ///
+    /// A batch only holds props of one chunk column, so the region each call voxelizes stays
+    /// small. The shapes are drawn from the layer's seed, the same bases give the same props.
+    pub fn build_batches(&self, layer_idx: usize, bases: &[Vec3]) -> Vec<Vec<RoundCone>> {
+        let layer = &self.layers[layer_idx];
+        let mut rng = StdRng::seed_from_u64(layer.placer_desc.seed as u64);
+
+        let mut columns: BTreeMap<(i32, i32), Vec<RoundCone>> = BTreeMap::new();
+        for &base in bases {
+            let size = if layer.min_size < layer.max_size {
+                rng.random_range(layer.min_size..layer.max_size)
+            } else {
+                layer.min_size
+            };
+            let round_cones = match layer.shape {
+                PropShape::Rock => build_rock(base, size, &mut rng),
+                PropShape::Stump => build_stump(base, size, &mut rng),
+            };
+            let column = (base.xz() / VOXEL_DIM as f32).floor().as_ivec2();
+            columns
+                .entry((column.x, column.y))
+                .or_default()
+                .extend(round_cones);
+        }
+
+        columns
+            .into_values()
+            .flat_map(|round_cones| {
+                round_cones
+                    .chunks(MAX_ROUND_CONES_PER_BATCH)
+                    .map(<[RoundCone]>::to_vec)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// A main lump stretched along a random direction with a few smaller ones leaning on it.
+fn build_rock(base: Vec3, size: f32, rng: &mut StdRng) -> Vec<RoundCone> {
+    let angle = rng.random_range(0.0..TAU);
+    let dir = Vec3::new(angle.cos(), 0.0, angle.sin());
+    // mostly buried, so it sits flush on slopes
+    let center = base + Vec3::Y * size * 0.1;
+    let mut round_cones = vec![RoundCone::new(
+        size,
+        center - dir * size * 0.4,
+        size * rng.random_range(0.6..0.9),
+        center + dir * size * 0.6,
+    )];
+
+    let lump_count = rng.random_range(1..=3);
+    for _ in 0..lump_count {
+        let angle = rng.random_range(0.0..TAU);
+        let offset = Vec3::new(angle.cos(), 0.0, angle.sin()) * size * rng.random_range(0.6..1.1);
+        let radius = size * rng.random_range(0.35..0.6);
+        let lump_center = base + offset;
+        round_cones.push(RoundCone::new(radius, lump_center, radius, lump_center));
+    }
+    round_cones
+}
+
+/// A slightly tapering trunk with roots running down into the ground around it.
+fn build_stump(base: Vec3, size: f32, rng: &mut StdRng) -> Vec<RoundCone> {
+    let radius = size * 0.5;
+    let height = size * rng.random_range(0.8..1.4);
+    let mut round_cones = vec![RoundCone::new(
+        radius,
+        base - Vec3::Y * radius,
+        radius * 0.85,
+        base + Vec3::Y * height,
+    )];
+
+    let root_count = rng.random_range(3..=5);
+    let first_angle = rng.random_range(0.0..TAU);
+    for i in 0..root_count {
+        let angle = first_angle + TAU * i as f32 / root_count as f32 + rng.random_range(-0.3..0.3);
+        let dir = Vec3::new(angle.cos(), 0.0, angle.sin());
+        let reach = size * rng.random_range(0.9..1.4);
+        round_cones.push(RoundCone::new(
+            radius * 0.4,
+            base + dir * radius * 0.5 + Vec3::Y * radius * 0.3,
+            radius * 0.15,
+            base + dir * reach - Vec3::Y * radius * 0.3,
+        ));
+    }
+    round_cones
+}
@@ -0,0 +1,135 @@
+use super::ResourceContainer;
+use crate::vkn::{Buffer, Texture};
+
+/// Separates a container's namespace from the resource name in a binding name.
+///
+/// Binding `tracer__camera_info` resolves `camera_info` in the container of namespace `tracer`
+/// only, GLSL identifiers can't hold the `.` of `tracer.camera_info`.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// What happens when more than one container holds a binding's resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LookupMode {
+    /// The lookup fails with [`ResourceLookupError::Ambiguous`].
+    #[default]
+    Strict,
+    /// The container listed first wins.
+    Priority,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    TextureArray,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ResourceLookupError {
+    #[error("Resource not found: {name}")]
+    NotFound { name: String },
+    /// Only raised in [`LookupMode::Strict`].
+    #[error("Resource '{name}' found in multiple containers: {}", containers.join(", "))]
+    Ambiguous {
+        name: String,
+        containers: Vec<String>,
+    },
+    #[error("Resource '{name}' found as more than one kind of resource in {container}")]
+    KindMismatch { name: String, container: String },
+    #[error("Resource name '{name}' is used more than once in {container}")]
+    Duplicate { name: String, container: String },
+}
+
+#[derive(Clone, Copy)]
+pub enum ResourceRef<'a> {
+    Buffer(&'a Buffer),
+    Texture(&'a Texture),
+    TextureArray(&'a [Texture]),
+}
+
+impl ResourceRef<'_> {
+    pub fn kind(&self) -> ResourceKind {
+        match self {
+            Self::Buffer(_) => ResourceKind::Buffer,
+            Self::Texture(_) => ResourceKind::Texture,
+            Self::TextureArray(_) => ResourceKind::TextureArray,
+        }
+    }
+}
+
+/// A resource and the container it was found in.
+#[derive(Clone, Copy)]
+pub struct ResourceMatch<'a> {
+    /// Index into the containers passed to [`find_resource`].
+    pub container_idx: usize,
+    pub resource: ResourceRef<'a>,
+}
+
+/// Names the container in errors and binding listings, containers without a namespace go by
+/// their position.
+pub fn container_label(container: &dyn ResourceContainer, container_idx: usize) -> String {
+    match container.namespace() {
+        "" => format!("container #{}", container_idx),
+        namespace => namespace.to_string(),
+    }
+}
+
+/// Looks `name` up across `containers`, `None` when none of them holds it.
+///
+/// A name prefixed with the namespace of one of the containers, see [`NAMESPACE_SEPARATOR`], is
+/// only looked up in the containers of that namespace.
+pub fn find_resource<'a>(
+    containers: &[&'a dyn ResourceContainer],
+    name: &str,
+    mode: LookupMode,
+) -> Result<Option<ResourceMatch<'a>>, ResourceLookupError> {
+    let namespaced = name
+        .split_once(NAMESPACE_SEPARATOR)
+        .filter(|(namespace, _)| {
+            containers
+                .iter()
+                .any(|container| container.namespace() == *namespace)
+        });
+    let (namespace, resource_name) = match namespaced {
+        Some((namespace, resource_name)) => (Some(namespace), resource_name),
+        None => (None, name),
+    };
+
+    let mut matches = Vec::new();
+    for (container_idx, container) in containers.iter().enumerate() {
+        if namespace.is_some_and(|namespace| container.namespace() != namespace) {
+            continue;
+        }
+        let resource = match (
+            container.get_buffer(resource_name),
+            container.get_texture(resource_name),
+            container.get_texture_array(resource_name),
+        ) {
+            (Some(buffer), None, None) => ResourceRef::Buffer(buffer),
+            (None, Some(texture), None) => ResourceRef::Texture(texture),
+            (None, None, Some(textures)) => ResourceRef::TextureArray(textures),
+            (None, None, None) => continue,
+            _ => {
+                return Err(ResourceLookupError::KindMismatch {
+                    name: name.to_string(),
+                    container: container_label(*container, container_idx),
+                })
+            }
+        };
+        matches.push(ResourceMatch {
+            container_idx,
+            resource,
+        });
+    }
+
+    if mode == LookupMode::Strict && matches.len() > 1 {
+        return Err(ResourceLookupError::Ambiguous {
+            name: name.to_string(),
+            containers: matches
+                .iter()
+                .map(|m| container_label(containers[m.container_idx], m.container_idx))
+                .collect(),
+        });
+    }
+    Ok(matches.into_iter().next())
+}
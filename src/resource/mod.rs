@@ -1,10 +1,51 @@
+mod texture_residency;
+pub use texture_residency::*;
+
+mod texture_streaming;
+pub use texture_streaming::*;
+
+mod lookup;
+pub use lookup::*;
+
+mod name_tree;
+pub use name_tree::*;
+
 use std::any::Any;
 use std::ops::{Deref, DerefMut};
 
 pub trait ResourceContainer {
     fn get_buffer(&self, name: &str) -> Option<&crate::vkn::Buffer>;
     fn get_texture(&self, name: &str) -> Option<&crate::vkn::Texture>;
-    fn get_resource_names(&self) -> Vec<&'static str>;
+    fn get_resource_names(&self) -> Vec<String>;
+
+    /// Textures bound together to a runtime sized array binding, held as
+    /// `Resource<Vec<Texture>>`.
+    fn get_texture_array(&self, _name: &str) -> Option<&[crate::vkn::Texture]> {
+        None
+    }
+
+    /// Binding names prefixed with it resolve in this container only, see
+    /// [`NAMESPACE_SEPARATOR`]. Empty for containers that can't be addressed that way.
+    fn namespace(&self) -> &'static str {
+        ""
+    }
+
+    /// Checks that no two resources share a name, including the names only known at runtime
+    /// that [`StaticResourceNames`] can't see.
+    fn validate(&self) -> Result<(), ResourceLookupError> {
+        let mut seen = std::collections::HashSet::new();
+        for name in self.get_resource_names() {
+            if seen.contains(&name) {
+                let container = match self.namespace() {
+                    "" => "a container without a namespace".to_string(),
+                    namespace => namespace.to_string(),
+                };
+                return Err(ResourceLookupError::Duplicate { name, container });
+            }
+            seen.insert(name);
+        }
+        Ok(())
+    }
 }
 
 pub struct Resource<T> {
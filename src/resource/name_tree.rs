@@ -0,0 +1,79 @@
+/// The resource names of a container known at compile time, see [`StaticResourceNames`].
+///
+/// Names only known at runtime, the elements of `Vec<Resource<T>>` fields and the sides of a
+/// `PingPongTexture`, are left out, [`super::ResourceContainer::validate`] checks those.
+#[derive(Debug)]
+pub struct ResourceNameTree {
+    pub names: &'static [&'static str],
+    pub nested: &'static [&'static ResourceNameTree],
+}
+
+impl ResourceNameTree {
+    pub const EMPTY: Self = Self {
+        names: &[],
+        nested: &[],
+    };
+
+    /// Whether a name shows up more than once, anywhere in the tree.
+    pub const fn has_duplicates(&self) -> bool {
+        self.has_duplicates_of(self)
+    }
+
+    /// Walks the names of `self` and counts each across all of `root`.
+    const fn has_duplicates_of(&self, root: &ResourceNameTree) -> bool {
+        let mut i = 0;
+        while i < self.names.len() {
+            if root.count(self.names[i]) > 1 {
+                return true;
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.nested.len() {
+            if self.nested[i].has_duplicates_of(root) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    const fn count(&self, name: &str) -> usize {
+        let mut count = 0;
+        let mut i = 0;
+        while i < self.names.len() {
+            if str_eq(self.names[i], name) {
+                count += 1;
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.nested.len() {
+            count += self.nested[i].count(name);
+            i += 1;
+        }
+        count
+    }
+}
+
+/// Implemented by `#[derive(ResourceContainer)]`, which asserts at compile time that the names
+/// don't clash across the nested containers.
+pub trait StaticResourceNames {
+    const RESOURCE_NAMES: ResourceNameTree;
+}
+
+// `==` on strings isn't const
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
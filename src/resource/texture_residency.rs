@@ -0,0 +1,396 @@
+/// A change to the resident mips of a texture, see [`MipResidency::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipChange {
+    /// Drop `level`, the texture falls back to the next coarser one.
+    Evict { texture: usize, level: usize },
+    /// Start uploading `level`, it becomes the finest resident one once the upload is done.
+    Upload { texture: usize, level: usize },
+}
+
+struct TextureMips {
+    /// Size of every mip, the full resolution first and the placeholder last.
+    level_bytes: Vec<u64>,
+    /// Every mip from this one to the placeholder is resident.
+    finest_level: usize,
+    /// The mip right above `finest_level` is being uploaded.
+    is_uploading: bool,
+    /// How much the texture is wanted this frame, 0.0 when nothing asked for it.
+    priority: f32,
+    /// The finest mip this frame's requests asked for.
+    wanted_level: usize,
+}
+
+impl TextureMips {
+    fn placeholder_level(&self) -> usize {
+        self.level_bytes.len() - 1
+    }
+}
+
+/// The mip of a texture seen from `distance`, the full resolution up to `full_res_distance` and
+/// one mip coarser every time the distance doubles.
+pub fn mip_for_distance(distance: f32, full_res_distance: f32) -> usize {
+    (distance / full_res_distance).max(1.0).log2().floor() as usize
+}
+
+/// Which mips of the streamed textures are resident, and what to change so the most wanted ones
+/// get theirs within the budget. Knows nothing of the GPU, the streamer carries the changes out.
+///
+/// The placeholder, the coarsest mip, is always resident and not counted. A texture refines one
+/// mip at a time and keeps the coarser ones, so dropping its finest mip is instant.
+pub struct MipResidency {
+    budget_bytes: u64,
+    /// Mips resident or being uploaded, placeholders aside.
+    resident_bytes: u64,
+    textures: Vec<TextureMips>,
+}
+
+impl MipResidency {
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            budget_bytes,
+            resident_bytes: 0,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Adds a texture with only its placeholder resident, returns its index.
+    pub fn add(&mut self, level_bytes: Vec<u64>) -> usize {
+        assert!(
+            !level_bytes.is_empty(),
+            "A texture has at least a placeholder"
+        );
+        let placeholder_level = level_bytes.len() - 1;
+        self.textures.push(TextureMips {
+            level_bytes,
+            finest_level: placeholder_level,
+            is_uploading: false,
+            priority: 0.0,
+            wanted_level: placeholder_level,
+        });
+        self.textures.len() - 1
+    }
+
+    /// Asks for `level` of `texture` this frame, the highest priority and the finest level asked
+    /// for win.
+    pub fn request(&mut self, texture: usize, priority: f32, level: usize) {
+        let mips = &mut self.textures[texture];
+        mips.priority = mips.priority.max(priority);
+        mips.wanted_level = mips.wanted_level.min(level);
+    }
+
+    pub fn finest_level(&self, texture: usize) -> usize {
+        self.textures[texture].finest_level
+    }
+
+    /// The upload [`Self::plan`] started for `texture` is done.
+    pub fn finish_upload(&mut self, texture: usize) {
+        let mips = &mut self.textures[texture];
+        assert!(mips.is_uploading, "No upload to finish");
+        mips.is_uploading = false;
+        mips.finest_level -= 1;
+    }
+
+    pub fn resident_bytes(&self) -> u64 {
+        self.resident_bytes
+    }
+
+    /// Refines the most wanted textures by a mip, with at most `max_uploads` uploads in flight,
+    /// evicting less wanted mips to stay within the budget. The requests are used up, they're
+    /// renewed every frame so a texture nobody asks for any more becomes the first to go.
+    pub fn plan(&mut self, max_uploads: usize) -> Vec<MipChange> {
+        let mut upload_count = self.textures.iter().filter(|t| t.is_uploading).count();
+        let mut candidates = (0..self.textures.len())
+            .filter(|&idx| {
+                let mips = &self.textures[idx];
+                mips.priority > 0.0 && !mips.is_uploading && mips.finest_level > mips.wanted_level
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|&a, &b| {
+            self.textures[b]
+                .priority
+                .total_cmp(&self.textures[a].priority)
+        });
+
+        let mut changes = Vec::new();
+        let mut has_lost_level = vec![false; self.textures.len()];
+        for idx in candidates {
+            if upload_count >= max_uploads {
+                break;
+            }
+            // it was just outranked, uploading it again would only churn
+            if has_lost_level[idx] {
+                continue;
+            }
+            let level = self.textures[idx].finest_level - 1;
+            let size_bytes = self.textures[idx].level_bytes[level];
+            let Some(victims) = self.find_victims(idx, size_bytes) else {
+                continue;
+            };
+            for (victim_idx, victim_level) in victims {
+                let victim = &mut self.textures[victim_idx];
+                victim.finest_level += 1;
+                self.resident_bytes -= victim.level_bytes[victim_level];
+                has_lost_level[victim_idx] = true;
+                changes.push(MipChange::Evict {
+                    texture: victim_idx,
+                    level: victim_level,
+                });
+            }
+            self.textures[idx].is_uploading = true;
+            self.resident_bytes += size_bytes;
+            upload_count += 1;
+            changes.push(MipChange::Upload {
+                texture: idx,
+                level,
+            });
+        }
+
+        for mips in &mut self.textures {
+            mips.priority = 0.0;
+            mips.wanted_level = mips.placeholder_level();
+        }
+        changes
+    }
+
+    /// The mips to evict so `size_bytes` more fit for `requester`, finest first. Mips finer than
+    /// their texture asked for go before any other, and only mips of less wanted textures go at
+    /// all. `None` when that isn't enough, nothing should be evicted then.
+    fn find_victims(&self, requester: usize, size_bytes: u64) -> Option<Vec<(usize, usize)>> {
+        let requester_priority = self.textures[requester].priority;
+        let mut finest_levels = self
+            .textures
+            .iter()
+            .map(|mips| mips.finest_level)
+            .collect::<Vec<_>>();
+        let mut free_bytes = self.budget_bytes.saturating_sub(self.resident_bytes);
+        let mut victims = Vec::new();
+        while free_bytes < size_bytes {
+            let victim_idx = (0..self.textures.len())
+                .filter(|&idx| {
+                    let mips = &self.textures[idx];
+                    let level = finest_levels[idx];
+                    idx != requester
+                        && !mips.is_uploading
+                        && level < mips.placeholder_level()
+                        && (level < mips.wanted_level || mips.priority < requester_priority)
+                })
+                .min_by(|&a, &b| {
+                    let is_wanted =
+                        |idx: usize| finest_levels[idx] >= self.textures[idx].wanted_level;
+                    is_wanted(a).cmp(&is_wanted(b)).then(
+                        self.textures[a]
+                            .priority
+                            .total_cmp(&self.textures[b].priority),
+                    )
+                })?;
+            let level = finest_levels[victim_idx];
+            free_bytes += self.textures[victim_idx].level_bytes[level];
+            finest_levels[victim_idx] += 1;
+            victims.push((victim_idx, level));
+        }
+        Some(victims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mips of a square texture of `size` texels a side at 4 bytes each, down to 16 texels.
+    fn square_mips(size: u64) -> Vec<u64> {
+        let mut level_bytes = vec![size * size * 4];
+        let mut side = size;
+        while side > 16 {
+            side /= 2;
+            level_bytes.push(side * side * 4);
+        }
+        level_bytes
+    }
+
+    /// Plans and finishes every upload right away, until nothing changes any more.
+    fn settle(residency: &mut MipResidency, requests: &[(usize, f32, usize)]) -> Vec<MipChange> {
+        let mut all_changes = Vec::new();
+        loop {
+            for &(texture, priority, level) in requests {
+                residency.request(texture, priority, level);
+            }
+            let changes = residency.plan(1);
+            if changes.is_empty() {
+                return all_changes;
+            }
+            for change in &changes {
+                if let MipChange::Upload { texture, .. } = change {
+                    residency.finish_upload(*texture);
+                }
+            }
+            assert!(residency.resident_bytes() <= residency.budget_bytes);
+            all_changes.extend(changes);
+        }
+    }
+
+    #[test]
+    fn mips_follow_the_distance() {
+        assert_eq!(mip_for_distance(0.0, 10.0), 0);
+        assert_eq!(mip_for_distance(10.0, 10.0), 0);
+        assert_eq!(mip_for_distance(19.9, 10.0), 0);
+        assert_eq!(mip_for_distance(20.0, 10.0), 1);
+        assert_eq!(mip_for_distance(45.0, 10.0), 2);
+    }
+
+    #[test]
+    fn only_the_placeholder_is_resident_at_first() {
+        let mut residency = MipResidency::new(u64::MAX);
+        let texture = residency.add(square_mips(256));
+        assert_eq!(residency.finest_level(texture), 4);
+        assert_eq!(residency.resident_bytes(), 0);
+        assert!(residency.plan(4).is_empty());
+    }
+
+    #[test]
+    fn refines_one_mip_at_a_time_up_to_the_wanted_one() {
+        let mut residency = MipResidency::new(u64::MAX);
+        let texture = residency.add(square_mips(256));
+
+        residency.request(texture, 1.0, 1);
+        assert_eq!(
+            residency.plan(4),
+            vec![MipChange::Upload { texture, level: 3 }]
+        );
+        // the upload is still in flight
+        residency.request(texture, 1.0, 1);
+        assert!(residency.plan(4).is_empty());
+
+        residency.finish_upload(texture);
+        settle(&mut residency, &[(texture, 1.0, 1)]);
+        assert_eq!(residency.finest_level(texture), 1);
+        assert_eq!(
+            residency.resident_bytes(),
+            128 * 128 * 4 + 64 * 64 * 4 + 32 * 32 * 4
+        );
+    }
+
+    #[test]
+    fn the_most_wanted_upload_first() {
+        let mut residency = MipResidency::new(u64::MAX);
+        let far = residency.add(square_mips(64));
+        let near = residency.add(square_mips(64));
+        let unwanted = residency.add(square_mips(64));
+
+        residency.request(far, 0.2, 0);
+        residency.request(near, 0.9, 0);
+        assert_eq!(
+            residency.plan(1),
+            vec![MipChange::Upload {
+                texture: near,
+                level: 1
+            }]
+        );
+        residency.request(far, 0.2, 0);
+        residency.request(near, 0.9, 0);
+        // one upload is in flight already
+        assert!(residency.plan(1).is_empty());
+        assert_eq!(residency.finest_level(unwanted), 2);
+    }
+
+    #[test]
+    fn stays_within_the_budget() {
+        let one_texture_bytes = 64 * 64 * 4 + 32 * 32 * 4;
+        let mut residency = MipResidency::new(one_texture_bytes * 2);
+        let textures = (0..5)
+            .map(|_| residency.add(square_mips(64)))
+            .collect::<Vec<_>>();
+        let requests = textures
+            .iter()
+            .map(|&texture| (texture, 1.0 / (1.0 + texture as f32), 0))
+            .collect::<Vec<_>>();
+
+        settle(&mut residency, &requests);
+        assert_eq!(residency.resident_bytes(), one_texture_bytes * 2);
+        // the two most wanted got the room
+        let finest_levels = textures
+            .iter()
+            .map(|&texture| residency.finest_level(texture))
+            .collect::<Vec<_>>();
+        assert_eq!(finest_levels, vec![0, 0, 2, 2, 2]);
+    }
+
+    #[test]
+    fn evicts_the_least_wanted_for_a_more_wanted_texture() {
+        let one_texture_bytes = 64 * 64 * 4 + 32 * 32 * 4;
+        let mut residency = MipResidency::new(one_texture_bytes * 2);
+        let a = residency.add(square_mips(64));
+        let b = residency.add(square_mips(64));
+        let c = residency.add(square_mips(64));
+        settle(&mut residency, &[(a, 0.5, 0), (b, 0.3, 0)]);
+
+        let changes = settle(&mut residency, &[(a, 0.5, 0), (b, 0.3, 0), (c, 0.8, 0)]);
+        assert_eq!(changes.len(), 4);
+        assert!(changes.contains(&MipChange::Evict {
+            texture: b,
+            level: 0
+        }));
+        assert!(changes.contains(&MipChange::Evict {
+            texture: b,
+            level: 1
+        }));
+        assert_eq!(residency.finest_level(a), 0);
+        assert_eq!(residency.finest_level(b), 2);
+        assert_eq!(residency.finest_level(c), 0);
+    }
+
+    #[test]
+    fn keeps_a_more_wanted_texture_resident() {
+        let one_texture_bytes = 64 * 64 * 4 + 32 * 32 * 4;
+        let mut residency = MipResidency::new(one_texture_bytes);
+        let a = residency.add(square_mips(64));
+        let b = residency.add(square_mips(64));
+        settle(&mut residency, &[(a, 0.5, 0)]);
+
+        // b gets the room a doesn't use, and nothing more
+        let changes = settle(&mut residency, &[(a, 0.5, 0), (b, 0.3, 0)]);
+        assert!(changes.is_empty());
+        assert_eq!(residency.finest_level(a), 0);
+        assert_eq!(residency.finest_level(b), 2);
+    }
+
+    #[test]
+    fn unwanted_mips_go_before_wanted_ones() {
+        let one_texture_bytes = 64 * 64 * 4 + 32 * 32 * 4;
+        let mut residency = MipResidency::new(one_texture_bytes * 2);
+        let a = residency.add(square_mips(64));
+        let b = residency.add(square_mips(64));
+        let c = residency.add(square_mips(64));
+        settle(&mut residency, &[(a, 0.9, 0), (b, 0.1, 0)]);
+
+        // a is still wanted more than c, but it only needs its coarser mip now
+        let changes = settle(&mut residency, &[(a, 0.9, 1), (b, 0.1, 0), (c, 0.5, 1)]);
+        assert_eq!(
+            changes,
+            vec![
+                MipChange::Evict {
+                    texture: a,
+                    level: 0
+                },
+                MipChange::Upload {
+                    texture: c,
+                    level: 1
+                },
+            ]
+        );
+        assert_eq!(residency.finest_level(b), 0);
+    }
+
+    #[test]
+    fn a_mip_that_cant_fit_evicts_nothing() {
+        let small_bytes = 64 * 64 * 4 + 32 * 32 * 4;
+        let mut residency = MipResidency::new(small_bytes + 64 * 64 * 4 + 32 * 32 * 4);
+        let small = residency.add(square_mips(64));
+        let large = residency.add(square_mips(256));
+        settle(&mut residency, &[(small, 0.1, 0)]);
+
+        settle(&mut residency, &[(small, 0.1, 0), (large, 1.0, 0)]);
+        assert_eq!(residency.finest_level(small), 0);
+        // 128 texels a side wouldn't fit even with small evicted, so small stays
+        assert_eq!(residency.finest_level(large), 2);
+    }
+}
@@ -0,0 +1,281 @@
+use super::{mip_for_distance, MipChange, MipResidency};
+use crate::vkn::{
+    Allocator, Buffer, CommandBuffer, Extent3D, Image, ImageDesc, Texture, TimelineSemaphore,
+    VulkanContext,
+};
+use anyhow::Result;
+use ash::vk;
+
+/// Handle of a texture registered in the [`TextureStreamer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamedTextureId(usize);
+
+/// Produces the full resolution of a streamed texture, called again every time one of its mips is
+/// uploaded since only the resident mips are kept around.
+pub type TextureSource = Box<dyn Fn() -> Result<image::RgbaImage>>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextureStreamingDesc {
+    /// Most memory the streamed mips may take together, placeholders aren't counted.
+    pub budget_bytes: u64,
+    /// Uploads running on the transfer queue at most, the rest wait for later frames.
+    pub max_uploads_in_flight: u32,
+    /// Longest side of the coarsest mip, kept resident for every texture.
+    pub placeholder_size: u32,
+    /// Textures seen from closer get their full resolution, one mip coarser every time the
+    /// distance doubles past it.
+    pub full_res_distance: f32,
+}
+
+impl Default for TextureStreamingDesc {
+    fn default() -> Self {
+        Self {
+            budget_bytes: 256 * 1024 * 1024,
+            max_uploads_in_flight: 2,
+            placeholder_size: 16,
+            full_res_distance: 8.0,
+        }
+    }
+}
+
+struct StreamedTexture {
+    name: String,
+    source: TextureSource,
+    format: vk::Format,
+    /// Resident mips, the full resolution first and the placeholder last. Each is a texture of
+    /// its own, so a mip can come and go without touching the others.
+    levels: Vec<Option<Texture>>,
+}
+
+/// A mip on its way in, it becomes resident once the transfer timeline reaches `transfer_value`.
+struct MipUpload {
+    texture_idx: usize,
+    level: usize,
+    mip: Texture,
+    transfer_value: u64,
+    _staging: Buffer,
+    _cmdbuf: CommandBuffer,
+}
+
+/// Keeps the mips of the streamed textures within a memory budget.
+///
+/// Every registered texture has its coarsest mip resident from the start. The finer ones are
+/// uploaded on the transfer queue once requested, the most wanted first, and evicted again when
+/// a more wanted texture needs the room, see [`MipResidency`].
+pub struct TextureStreamer {
+    vulkan_ctx: VulkanContext,
+    allocator: Allocator,
+    desc: TextureStreamingDesc,
+    textures: Vec<StreamedTexture>,
+    residency: MipResidency,
+    uploads: Vec<MipUpload>,
+    /// The transfer timeline value of the latest upload that became resident.
+    landed_value: u64,
+    /// Evicted mips, along with the timeline value of the last frame that could sample them.
+    retired: Vec<(Texture, u64)>,
+}
+
+impl Drop for TextureStreamer {
+    fn drop(&mut self) {
+        // the uploads in flight still read their staging buffers
+        let transfer_timeline = self.vulkan_ctx.transfer_timeline();
+        transfer_timeline
+            .wait(transfer_timeline.submitted_value())
+            .unwrap();
+    }
+}
+
+impl TextureStreamer {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        desc: TextureStreamingDesc,
+    ) -> Self {
+        Self {
+            vulkan_ctx,
+            allocator,
+            residency: MipResidency::new(desc.budget_bytes),
+            desc,
+            textures: Vec::new(),
+            uploads: Vec::new(),
+            landed_value: 0,
+            retired: Vec::new(),
+        }
+    }
+
+    /// Registers a texture, only its coarsest mip is uploaded right away. Blocks until that one
+    /// is resident.
+    pub fn register(
+        &mut self,
+        name: &str,
+        format: vk::Format,
+        source: TextureSource,
+    ) -> Result<StreamedTextureId> {
+        let full = source()?;
+        let (width, height) = full.dimensions();
+        let mut level_count = 1;
+        while (width.max(height) >> (level_count - 1)) > self.desc.placeholder_size {
+            level_count += 1;
+        }
+        let level_bytes = (0..level_count)
+            .map(|level| {
+                let desc = Self::mip_desc(width, height, level, format);
+                desc.extent.width as u64 * desc.extent.height as u64 * desc.get_pixel_size() as u64
+            })
+            .collect();
+
+        let texture_idx = self.residency.add(level_bytes);
+        self.textures.push(StreamedTexture {
+            name: name.to_string(),
+            source,
+            format,
+            levels: vec![None; level_count as usize],
+        });
+        let placeholder_level = level_count as usize - 1;
+        let upload = self.start_upload(texture_idx, placeholder_level, &full)?;
+        self.vulkan_ctx
+            .transfer_timeline()
+            .wait(upload.transfer_value)?;
+        self.land(upload);
+        Ok(StreamedTextureId(texture_idx))
+    }
+
+    /// Asks for `id` as seen from `distance` this frame, textures seen from closer win.
+    pub fn request(&mut self, id: StreamedTextureId, distance: f32) {
+        let distance = distance.max(0.0);
+        let level = mip_for_distance(distance, self.desc.full_res_distance);
+        self.residency.request(id.0, 1.0 / (1.0 + distance), level);
+    }
+
+    /// The finest resident mip of `id`.
+    pub fn get(&self, id: StreamedTextureId) -> &Texture {
+        self.textures[id.0].levels[self.residency.finest_level(id.0)]
+            .as_ref()
+            .expect("The finest level is resident")
+    }
+
+    /// What frames sampling the streamed textures wait on, the uploads they may see are done by
+    /// then.
+    pub fn upload_wait(&self) -> (&TimelineSemaphore, u64) {
+        (self.vulkan_ctx.transfer_timeline(), self.landed_value)
+    }
+
+    /// Makes the finished uploads resident and starts the ones this frame's requests call for,
+    /// evicting less wanted mips to stay within the budget. Called once per frame, after the
+    /// requests. Returns whether any [`Self::get`] changed, the descriptors holding them need
+    /// updating then.
+    pub fn update(&mut self) -> Result<bool> {
+        let completed_frame_value = self.vulkan_ctx.frame_timeline().completed_value()?;
+        self.retired
+            .retain(|(_, value)| *value > completed_frame_value);
+
+        let mut has_changed = false;
+        let completed_transfer_value = self.vulkan_ctx.transfer_timeline().completed_value()?;
+        let (landed, in_flight) = std::mem::take(&mut self.uploads)
+            .into_iter()
+            .partition::<Vec<_>, _>(|upload| upload.transfer_value <= completed_transfer_value);
+        self.uploads = in_flight;
+        for upload in landed {
+            self.residency.finish_upload(upload.texture_idx);
+            self.land(upload);
+            has_changed = true;
+        }
+
+        for change in self
+            .residency
+            .plan(self.desc.max_uploads_in_flight as usize)
+        {
+            match change {
+                MipChange::Evict { texture, level } => {
+                    let mip = self.textures[texture].levels[level]
+                        .take()
+                        .expect("Only resident mips are evicted");
+                    // mips only change between frames, so the latest submitted frame is the last
+                    // one that could sample it
+                    let last_value = self.vulkan_ctx.frame_timeline().submitted_value();
+                    self.retired.push((mip, last_value));
+                    has_changed = true;
+                }
+                MipChange::Upload { texture, level } => {
+                    let full = (self.textures[texture].source)()?;
+                    let upload = self.start_upload(texture, level, &full)?;
+                    self.uploads.push(upload);
+                }
+            }
+        }
+        Ok(has_changed)
+    }
+
+    /// Downsamples `full` to `level` and records its upload on the transfer queue.
+    fn start_upload(
+        &self,
+        texture_idx: usize,
+        level: usize,
+        full: &image::RgbaImage,
+    ) -> Result<MipUpload> {
+        let texture = &self.textures[texture_idx];
+        let desc = Self::mip_desc(full.width(), full.height(), level as u32, texture.format);
+        let pixels = if level == 0 {
+            full.clone()
+        } else {
+            image::imageops::resize(
+                full,
+                desc.extent.width,
+                desc.extent.height,
+                image::imageops::FilterType::Triangle,
+            )
+        };
+
+        let device = self.vulkan_ctx.device();
+        let image = Image::new_uploadable(device.clone(), self.allocator.clone(), &desc)?;
+        let data = image.convert_rgba_data_to_image_format(&pixels.into_raw())?;
+        let staging = Buffer::new_staging(device.clone(), self.allocator.clone(), data.len() as _);
+        staging
+            .fill(&data)
+            .map_err(|e| anyhow::anyhow!("Failed to fill the staging buffer: {}", e))?;
+
+        let cmdbuf = CommandBuffer::new(device, self.vulkan_ctx.transfer_command_pool());
+        cmdbuf.begin(true);
+        image.record_fill_from_buffer(&cmdbuf, &staging, vk::ImageLayout::GENERAL);
+        cmdbuf.end();
+        let transfer_value = cmdbuf.submit_with_timelines(
+            &self.vulkan_ctx.get_transfer_only_queue(),
+            &[],
+            self.vulkan_ctx.transfer_timeline(),
+        );
+        log::debug!(
+            "Streaming in mip {} of {} ({}x{}), {} of {} bytes resident",
+            level,
+            texture.name,
+            desc.extent.width,
+            desc.extent.height,
+            self.residency.resident_bytes(),
+            self.desc.budget_bytes
+        );
+
+        Ok(MipUpload {
+            texture_idx,
+            level,
+            mip: Texture::from_image(device.clone(), image, &Default::default()),
+            transfer_value,
+            _staging: staging,
+            _cmdbuf: cmdbuf,
+        })
+    }
+
+    fn land(&mut self, upload: MipUpload) {
+        self.textures[upload.texture_idx].levels[upload.level] = Some(upload.mip);
+        self.landed_value = self.landed_value.max(upload.transfer_value);
+    }
+
+    fn mip_desc(width: u32, height: u32, level: u32, format: vk::Format) -> ImageDesc {
+        ImageDesc {
+            extent: Extent3D::new((width >> level).max(1), (height >> level).max(1), 1),
+            format,
+            usage: vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        }
+    }
+}
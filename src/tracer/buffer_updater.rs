@@ -1,7 +1,13 @@
-use crate::tracer::TracerResources;
+use crate::gameplay::{PrecipitationKind, Weather, WindField};
+use crate::tracer::{
+    DebugSettings, DenoiserSettings, DofSettings, FogSettings, FrameResources, GodRaySettings,
+    PlayerColliderDesc, RainLensSettings, SkyMapBand, SpatialDenoiserSettings, StarlightSettings,
+    SunSettings, TemporalDenoiserSettings, VoxelColorSettings, WaterSettings,
+};
+use crate::util::profile_scope;
 use crate::vkn::{Buffer, PlainMemberTypeWithData, StructMemberDataBuilder};
 use anyhow::Result;
-use glam::{Mat4, Vec3};
+use glam::{Mat4, UVec2, UVec4, Vec3};
 
 pub struct BufferUpdater;
 
@@ -11,6 +17,7 @@ impl BufferUpdater {
         view_mat: Mat4,
         proj_mat: Mat4,
     ) -> Result<()> {
+        profile_scope!("update_camera_info");
         let view_proj_mat = proj_mat * view_mat;
 
         let camera_pos = view_mat.inverse().w_axis;
@@ -45,179 +52,183 @@ impl BufferUpdater {
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
     pub fn update_denoiser_info(
         temporal_info: &mut Buffer,
         spatial_info: &mut Buffer,
-        temporal_position_phi: f32,
-        temporal_alpha: f32,
-        phi_c: f32,
-        phi_n: f32,
-        phi_p: f32,
-        min_phi_z: f32,
-        max_phi_z: f32,
-        phi_z_stable_sample_count: f32,
-        is_changing_lum_phi: bool,
-        is_spatial_denoising_enabled: bool,
+        settings: &DenoiserSettings,
+        history_invalid_rect: UVec4,
     ) -> Result<()> {
-        Self::update_temporal_info(temporal_info, temporal_position_phi, temporal_alpha)?;
-        Self::update_spatial_info(
-            spatial_info,
-            phi_c,
-            phi_n,
-            phi_p,
-            min_phi_z,
-            max_phi_z,
-            phi_z_stable_sample_count,
-            is_changing_lum_phi,
-            is_spatial_denoising_enabled,
-        )?;
+        profile_scope!("update_denoiser_info");
+        Self::update_temporal_info(temporal_info, &settings.temporal, history_invalid_rect)?;
+        Self::update_spatial_info(spatial_info, &settings.spatial)?;
         Ok(())
     }
 
     fn update_temporal_info(
         temporal_info: &mut Buffer,
-        temporal_position_phi: f32,
-        temporal_alpha: f32,
+        settings: &TemporalDenoiserSettings,
+        history_invalid_rect: UVec4,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(temporal_info)
             .set_field(
                 "temporal_position_phi",
-                PlainMemberTypeWithData::Float(temporal_position_phi),
+                PlainMemberTypeWithData::Float(settings.position_phi),
             )
             .set_field(
                 "temporal_alpha",
-                PlainMemberTypeWithData::Float(temporal_alpha),
+                PlainMemberTypeWithData::Float(settings.alpha),
+            )
+            .set_field(
+                "history_invalid_rect",
+                PlainMemberTypeWithData::UVec4(history_invalid_rect.to_array()),
             )
             .build()?;
         temporal_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn update_spatial_info(
         spatial_info: &mut Buffer,
-        phi_c: f32,
-        phi_n: f32,
-        phi_p: f32,
-        min_phi_z: f32,
-        max_phi_z: f32,
-        phi_z_stable_sample_count: f32,
-        is_changing_lum_phi: bool,
-        is_spatial_denoising_enabled: bool,
+        settings: &SpatialDenoiserSettings,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(spatial_info)
-            .set_field("phi_c", PlainMemberTypeWithData::Float(phi_c))
-            .set_field("phi_n", PlainMemberTypeWithData::Float(phi_n))
-            .set_field("phi_p", PlainMemberTypeWithData::Float(phi_p))
-            .set_field("min_phi_z", PlainMemberTypeWithData::Float(min_phi_z))
-            .set_field("max_phi_z", PlainMemberTypeWithData::Float(max_phi_z))
+            .set_field("phi_c", PlainMemberTypeWithData::Float(settings.phi_c))
+            .set_field("phi_n", PlainMemberTypeWithData::Float(settings.phi_n))
+            .set_field("phi_p", PlainMemberTypeWithData::Float(settings.phi_p))
+            .set_field(
+                "min_phi_z",
+                PlainMemberTypeWithData::Float(settings.min_phi_z),
+            )
+            .set_field(
+                "max_phi_z",
+                PlainMemberTypeWithData::Float(settings.max_phi_z),
+            )
             .set_field(
                 "phi_z_stable_sample_count",
-                PlainMemberTypeWithData::Float(phi_z_stable_sample_count),
+                PlainMemberTypeWithData::Float(settings.phi_z_stable_sample_count),
             )
             .set_field(
                 "is_changing_lum_phi",
-                PlainMemberTypeWithData::UInt(is_changing_lum_phi as u32),
+                PlainMemberTypeWithData::UInt(settings.is_changing_lum_phi as u32),
             )
             .set_field(
                 "is_spatial_denoising_enabled",
-                PlainMemberTypeWithData::UInt(is_spatial_denoising_enabled as u32),
+                PlainMemberTypeWithData::UInt(settings.is_enabled as u32),
             )
             .build()?;
         spatial_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    pub fn update_gui_input(
-        resources: &TracerResources,
-        debug_float: f32,
-        debug_bool: bool,
-        debug_uint: u32,
-    ) -> Result<()> {
+    pub fn update_gui_input(resources: &FrameResources, settings: &DebugSettings) -> Result<()> {
+        profile_scope!("update_gui_input");
         let data = StructMemberDataBuilder::from_buffer(&resources.gui_input)
-            .set_field("debug_float", PlainMemberTypeWithData::Float(debug_float))
+            .set_field(
+                "debug_float",
+                PlainMemberTypeWithData::Float(settings.float),
+            )
             .set_field(
                 "debug_bool",
-                PlainMemberTypeWithData::UInt(debug_bool as u32),
+                PlainMemberTypeWithData::UInt(settings.bool as u32),
             )
-            .set_field("debug_uint", PlainMemberTypeWithData::UInt(debug_uint))
+            .set_field("debug_uint", PlainMemberTypeWithData::UInt(settings.uint))
             .build()?;
         resources.gui_input.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    pub fn update_sun_info(
-        resources: &TracerResources,
-        sun_dir: Vec3,
-        sun_size: f32,
-        sun_color: Vec3,
-        sun_luminance: f32,
-        sun_altitude: f32,
-        sun_azimuth: f32,
-    ) -> Result<()> {
+    pub fn update_sun_info(resources: &FrameResources, settings: &SunSettings) -> Result<()> {
+        profile_scope!("update_sun_info");
         let data = StructMemberDataBuilder::from_buffer(&resources.sun_info)
-            .set_field("sun_dir", PlainMemberTypeWithData::Vec3(sun_dir.to_array()))
-            .set_field("sun_size", PlainMemberTypeWithData::Float(sun_size))
+            .set_field(
+                "sun_dir",
+                PlainMemberTypeWithData::Vec3(settings.sun_dir().to_array()),
+            )
+            .set_field("sun_size", PlainMemberTypeWithData::Float(settings.size))
             .set_field(
                 "sun_color",
-                PlainMemberTypeWithData::Vec3(sun_color.to_array()),
+                PlainMemberTypeWithData::Vec3(settings.color_vec3().to_array()),
             )
             .set_field(
                 "sun_luminance",
-                PlainMemberTypeWithData::Float(sun_luminance),
+                PlainMemberTypeWithData::Float(settings.luminance),
+            )
+            .set_field(
+                "sun_altitude",
+                PlainMemberTypeWithData::Float(settings.altitude),
+            )
+            .set_field(
+                "sun_azimuth",
+                PlainMemberTypeWithData::Float(settings.azimuth),
             )
-            .set_field("sun_altitude", PlainMemberTypeWithData::Float(sun_altitude))
-            .set_field("sun_azimuth", PlainMemberTypeWithData::Float(sun_azimuth))
             .build()?;
         resources.sun_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    pub fn update_shading_info(resources: &TracerResources, ambient_light: Vec3) -> Result<()> {
+    pub fn update_shading_info(
+        resources: &FrameResources,
+        ambient_light: Vec3,
+        shadow_normal_offset: f32,
+    ) -> Result<()> {
+        profile_scope!("update_shading_info");
         let data = StructMemberDataBuilder::from_buffer(&resources.shading_info)
             .set_field(
                 "ambient_light",
                 PlainMemberTypeWithData::Vec3(ambient_light.to_array()),
             )
+            .set_field(
+                "shadow_normal_offset",
+                PlainMemberTypeWithData::Float(shadow_normal_offset),
+            )
             .build()?;
         resources.shading_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
     pub fn update_starlight_info(
-        resources: &TracerResources,
-        iterations: i32,
-        formuparam: f32,
-        volsteps: i32,
-        stepsize: f32,
-        zoom: f32,
-        tile: f32,
-        speed: f32,
-        brightness: f32,
-        darkmatter: f32,
-        distfading: f32,
-        saturation: f32,
+        resources: &FrameResources,
+        settings: &StarlightSettings,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.starlight_info)
-            .set_field("iterations", PlainMemberTypeWithData::Int(iterations))
-            .set_field("formuparam", PlainMemberTypeWithData::Float(formuparam))
-            .set_field("volsteps", PlainMemberTypeWithData::Int(volsteps))
-            .set_field("stepsize", PlainMemberTypeWithData::Float(stepsize))
-            .set_field("zoom", PlainMemberTypeWithData::Float(zoom))
-            .set_field("tile", PlainMemberTypeWithData::Float(tile))
-            .set_field("speed", PlainMemberTypeWithData::Float(speed))
-            .set_field("brightness", PlainMemberTypeWithData::Float(brightness))
-            .set_field("darkmatter", PlainMemberTypeWithData::Float(darkmatter))
-            .set_field("distfading", PlainMemberTypeWithData::Float(distfading))
-            .set_field("saturation", PlainMemberTypeWithData::Float(saturation))
+            .set_field(
+                "iterations",
+                PlainMemberTypeWithData::Int(settings.iterations),
+            )
+            .set_field(
+                "formuparam",
+                PlainMemberTypeWithData::Float(settings.formuparam),
+            )
+            .set_field("volsteps", PlainMemberTypeWithData::Int(settings.volsteps))
+            .set_field(
+                "stepsize",
+                PlainMemberTypeWithData::Float(settings.stepsize),
+            )
+            .set_field("zoom", PlainMemberTypeWithData::Float(settings.zoom))
+            .set_field("tile", PlainMemberTypeWithData::Float(settings.tile))
+            .set_field("speed", PlainMemberTypeWithData::Float(settings.speed))
+            .set_field(
+                "brightness",
+                PlainMemberTypeWithData::Float(settings.brightness),
+            )
+            .set_field(
+                "darkmatter",
+                PlainMemberTypeWithData::Float(settings.darkmatter),
+            )
+            .set_field(
+                "distfading",
+                PlainMemberTypeWithData::Float(settings.distfading),
+            )
+            .set_field(
+                "saturation",
+                PlainMemberTypeWithData::Float(settings.saturation),
+            )
             .build()?;
         resources.starlight_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    pub fn update_env_info(resources: &TracerResources, frame_serial_idx: u32) -> Result<()> {
+    pub fn update_env_info(resources: &FrameResources, frame_serial_idx: u32) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.env_info)
             .set_field(
                 "frame_serial_idx",
@@ -229,13 +240,11 @@ impl BufferUpdater {
     }
 
     pub fn update_voxel_colors(
-        resources: &TracerResources,
-        sand_color: Vec3,
-        dirt_color: Vec3,
-        rock_color: Vec3,
-        leaf_color: Vec3,
-        trunk_color: Vec3,
+        resources: &FrameResources,
+        settings: &VoxelColorSettings,
     ) -> Result<()> {
+        let [sand_color, dirt_color, rock_color, leaf_color, trunk_color, water_color] =
+            settings.to_vec3s();
         let data = StructMemberDataBuilder::from_buffer(&resources.voxel_colors)
             .set_field(
                 "sand_color",
@@ -257,57 +266,242 @@ impl BufferUpdater {
                 "trunk_color",
                 PlainMemberTypeWithData::Vec3(trunk_color.to_array()),
             )
+            .set_field(
+                "water_color",
+                PlainMemberTypeWithData::Vec3(water_color.to_array()),
+            )
             .build()?;
         resources.voxel_colors.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
-    pub fn update_taa_info(resources: &TracerResources, is_taa_enabled: bool) -> Result<()> {
+    pub fn update_taa_info(
+        resources: &FrameResources,
+        is_taa_enabled: bool,
+        history_invalid_rect: UVec4,
+    ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.taa_info)
             .set_field(
                 "is_taa_enabled",
                 PlainMemberTypeWithData::UInt(is_taa_enabled as u32),
             )
+            .set_field(
+                "history_invalid_rect",
+                PlainMemberTypeWithData::UVec4(history_invalid_rect.to_array()),
+            )
             .build()?;
         resources.taa_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
     pub fn update_god_ray_info(
-        resources: &TracerResources,
-        max_depth: f32,
-        max_checks: u32,
-        weight: f32,
-        color: Vec3,
+        resources: &FrameResources,
+        settings: &GodRaySettings,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.god_ray_info)
-            .set_field("max_depth", PlainMemberTypeWithData::Float(max_depth))
-            .set_field("max_checks", PlainMemberTypeWithData::UInt(max_checks))
-            .set_field("weight", PlainMemberTypeWithData::Float(weight))
-            .set_field("color", PlainMemberTypeWithData::Vec3(color.to_array()))
+            .set_field(
+                "max_depth",
+                PlainMemberTypeWithData::Float(settings.max_depth),
+            )
+            .set_field(
+                "max_checks",
+                PlainMemberTypeWithData::UInt(settings.max_checks),
+            )
+            .set_field("weight", PlainMemberTypeWithData::Float(settings.weight))
+            .set_field(
+                "color",
+                PlainMemberTypeWithData::Vec3(settings.color_vec3().to_array()),
+            )
             .build()?;
         resources.god_ray_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
+    /// `brightness` dims the fog with the time of day.
+    pub fn update_fog_info(
+        resources: &FrameResources,
+        settings: &FogSettings,
+        brightness: f32,
+    ) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.fog_info)
+            .set_field(
+                "color",
+                PlainMemberTypeWithData::Vec3(settings.color_vec3().to_array()),
+            )
+            .set_field("density", PlainMemberTypeWithData::Float(settings.density))
+            .set_field("falloff", PlainMemberTypeWithData::Float(settings.falloff))
+            .set_field(
+                "base_height",
+                PlainMemberTypeWithData::Float(settings.base_height),
+            )
+            .set_field(
+                "max_distance",
+                PlainMemberTypeWithData::Float(settings.max_distance),
+            )
+            .set_field(
+                "max_steps",
+                PlainMemberTypeWithData::UInt(settings.max_steps),
+            )
+            .set_field(
+                "sun_scattering",
+                PlainMemberTypeWithData::Float(settings.sun_scattering),
+            )
+            .set_field("brightness", PlainMemberTypeWithData::Float(brightness))
+            .build()?;
+        resources.fog_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
+    pub fn update_dof_info(resources: &FrameResources, settings: &DofSettings) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.dof_info)
+            .set_field(
+                "focus_distance",
+                PlainMemberTypeWithData::Float(settings.focus_distance),
+            )
+            .set_field(
+                "aperture",
+                PlainMemberTypeWithData::Float(settings.aperture),
+            )
+            .set_field(
+                "max_radius",
+                PlainMemberTypeWithData::Float(settings.max_radius),
+            )
+            .set_field(
+                "sample_count",
+                PlainMemberTypeWithData::UInt(settings.sample_count),
+            )
+            .build()?;
+        resources.dof_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
+    pub fn update_water_info(
+        resources: &FrameResources,
+        settings: &WaterSettings,
+        sea_level: f32,
+        time: f32,
+    ) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.water_info)
+            .set_field("sea_level", PlainMemberTypeWithData::Float(sea_level))
+            .set_field(
+                "absorption",
+                PlainMemberTypeWithData::Float(settings.absorption),
+            )
+            .set_field(
+                "wave_strength",
+                PlainMemberTypeWithData::Float(settings.wave_strength),
+            )
+            .set_field(
+                "wave_speed",
+                PlainMemberTypeWithData::Float(settings.wave_speed),
+            )
+            .set_field("time", PlainMemberTypeWithData::Float(time))
+            .build()?;
+        resources.water_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
     pub fn update_post_processing_info(
-        resources: &TracerResources,
+        resources: &FrameResources,
         scaling_factor: f32,
+        time: f32,
+        camera_pos: Vec3,
+        rain_lens: &RainLensSettings,
+        is_dof_enabled: bool,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.post_processing_info)
             .set_field(
                 "scaling_factor",
                 PlainMemberTypeWithData::Float(scaling_factor),
             )
+            .set_field("time", PlainMemberTypeWithData::Float(time))
+            .set_field(
+                "camera_pos",
+                PlainMemberTypeWithData::Vec3(camera_pos.to_array()),
+            )
+            .set_field(
+                "is_streaks_enabled",
+                PlainMemberTypeWithData::UInt(rain_lens.is_streaks_enabled as u32),
+            )
+            .set_field(
+                "is_lens_wetness_enabled",
+                PlainMemberTypeWithData::UInt(rain_lens.is_lens_wetness_enabled as u32),
+            )
+            .set_field(
+                "distortion",
+                PlainMemberTypeWithData::Float(rain_lens.distortion),
+            )
+            .set_field(
+                "is_dof_enabled",
+                PlainMemberTypeWithData::UInt(is_dof_enabled as u32),
+            )
             .build()?;
         resources.post_processing_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
 
+    /// `wind_velocity` is in world units per second, `time` in seconds.
+    pub fn update_weather_info(
+        resources: &FrameResources,
+        weather: &Weather,
+        wind_velocity: Vec3,
+        time: f32,
+    ) -> Result<()> {
+        let precipitation_kind = match weather.precipitation_kind {
+            PrecipitationKind::Rain => 0,
+            PrecipitationKind::Snow => 1,
+        };
+        let data = StructMemberDataBuilder::from_buffer(&resources.weather_info)
+            .set_field(
+                "wind_velocity",
+                PlainMemberTypeWithData::Vec3(wind_velocity.to_array()),
+            )
+            .set_field(
+                "precipitation",
+                PlainMemberTypeWithData::Float(weather.precipitation()),
+            )
+            .set_field("wetness", PlainMemberTypeWithData::Float(weather.wetness()))
+            .set_field(
+                "wind_strength",
+                PlainMemberTypeWithData::Float(weather.wind_strength),
+            )
+            .set_field(
+                "precipitation_kind",
+                PlainMemberTypeWithData::UInt(precipitation_kind),
+            )
+            .set_field("time", PlainMemberTypeWithData::Float(time))
+            .build()?;
+        resources.weather_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
+    pub fn update_wind_field(resources: &FrameResources, wind_field: &WindField) -> Result<()> {
+        // std140 pads every cell to a vec4
+        let cells = wind_field
+            .winds()
+            .iter()
+            .flat_map(|wind| [wind.x, wind.y, 0.0, 0.0])
+            .collect();
+        let data = StructMemberDataBuilder::from_buffer(&resources.wind_field)
+            .set_field(
+                "cell_count",
+                PlainMemberTypeWithData::UVec2(wind_field.cell_count().to_array()),
+            )
+            .set_field(
+                "cell_size",
+                PlainMemberTypeWithData::Vec2(wind_field.cell_size().to_array()),
+            )
+            .set_field("cells", PlainMemberTypeWithData::Array(cells))
+            .build()?;
+        resources.wind_field.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
     pub fn update_player_collider_info(
-        resources: &TracerResources,
+        resources: &FrameResources,
         player_pos: Vec3,
         camera_front: Vec3,
+        desc: &PlayerColliderDesc,
     ) -> Result<()> {
         let data = StructMemberDataBuilder::from_buffer(&resources.player_collider_info)
             .set_field(
@@ -318,8 +512,75 @@ impl BufferUpdater {
                 "camera_front",
                 PlainMemberTypeWithData::Vec3(camera_front.to_array()),
             )
+            .set_field("ring_count", PlainMemberTypeWithData::UInt(desc.ring_count))
+            .set_field(
+                "ring_radius",
+                PlainMemberTypeWithData::Float(desc.ring_radius),
+            )
             .build()?;
         resources.player_collider_info.fill_with_raw_u8(&data)?;
         Ok(())
     }
+
+    pub fn update_sky_visibility_info(
+        resources: &FrameResources,
+        texel_offset: UVec2,
+        texel_count: UVec2,
+        scene_top: f32,
+    ) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.sky_visibility_info)
+            .set_field(
+                "texel_offset",
+                PlainMemberTypeWithData::UVec2(texel_offset.to_array()),
+            )
+            .set_field(
+                "texel_count",
+                PlainMemberTypeWithData::UVec2(texel_count.to_array()),
+            )
+            .set_field("scene_top", PlainMemberTypeWithData::Float(scene_top))
+            .build()?;
+        resources.sky_visibility_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
+    pub fn update_sky_map_info(resources: &FrameResources, band: &SkyMapBand) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.sky_map_info)
+            .set_field(
+                "sun_dir",
+                PlainMemberTypeWithData::Vec3(band.sun_dir.to_array()),
+            )
+            .set_field(
+                "sun_azimuth",
+                PlainMemberTypeWithData::Float(band.sun_azimuth),
+            )
+            .set_field(
+                "star_visibility",
+                PlainMemberTypeWithData::Float(band.night_sky.star_visibility),
+            )
+            .set_field(
+                "moon_glow",
+                PlainMemberTypeWithData::Float(band.night_sky.moon_glow),
+            )
+            .set_field("row_offset", PlainMemberTypeWithData::UInt(band.row_offset))
+            .set_field("row_count", PlainMemberTypeWithData::UInt(band.row_count))
+            .build()?;
+        resources.sky_map_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
+
+    pub fn update_path_wear_info(
+        resources: &FrameResources,
+        extent: UVec2,
+        path_color: Vec3,
+    ) -> Result<()> {
+        let data = StructMemberDataBuilder::from_buffer(&resources.path_wear_info)
+            .set_field("extent", PlainMemberTypeWithData::UVec2(extent.to_array()))
+            .set_field(
+                "path_color",
+                PlainMemberTypeWithData::Vec3(path_color.to_array()),
+            )
+            .build()?;
+        resources.path_wear_info.fill_with_raw_u8(&data)?;
+        Ok(())
+    }
 }
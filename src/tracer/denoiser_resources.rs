@@ -2,20 +2,65 @@ use ash::vk;
 use resource_container_derive::ResourceContainer;
 
 use crate::resource::Resource;
-use crate::vkn::{
-    Allocator, Buffer, BufferUsage, Device, Extent2D, ImageDesc, ShaderModule, Texture,
-};
+use crate::util::ShaderCompiler;
+use crate::vkn::{Allocator, Device, Extent2D, ImageDesc, PingPongTexture, Texture};
 
+/// Storage precision of the denoiser intermediates.
+///
+/// `Reduced` keeps the radiance in packed 11/11/10 floats, motion in 16-bit floats and the
+/// history length in 8 bits, `Full` widens all of them to 32 bits so artifacts can be A/B tested
+/// against the reduced formats. Positions stay 32-bit in both, 16-bit floats can't resolve a
+/// voxel far from the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DenoiserPrecision {
+    #[default]
+    Reduced,
+    Full,
+}
+
+impl DenoiserPrecision {
+    fn radiance_format(self) -> vk::Format {
+        match self {
+            Self::Reduced => vk::Format::B10G11R11_UFLOAT_PACK32,
+            Self::Full => vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+
+    fn motion_format(self) -> vk::Format {
+        match self {
+            Self::Reduced => vk::Format::R16G16_SFLOAT,
+            Self::Full => vk::Format::R32G32_SFLOAT,
+        }
+    }
+
+    fn hist_len_format(self) -> vk::Format {
+        match self {
+            Self::Reduced => vk::Format::R8_UINT,
+            Self::Full => vk::Format::R32_UINT,
+        }
+    }
+
+    /// Makes the shaders declare the denoiser images with matching format qualifiers, see
+    /// `shader/include/denoiser_formats.glsl`. Must be called before the tracer shaders compile.
+    pub fn define_shader_macros(self, shader_compiler: &mut ShaderCompiler) {
+        let (radiance, motion, hist_len) = match self {
+            Self::Reduced => ("r11f_g11f_b10f", "rg16f", "r8ui"),
+            Self::Full => ("rgba32f", "rg32f", "r32ui"),
+        };
+        shader_compiler.define_macro("DENOISER_RADIANCE_FORMAT", radiance);
+        shader_compiler.define_macro("DENOISER_MOTION_FORMAT", motion);
+        shader_compiler.define_macro("DENOISER_HIST_LEN_FORMAT", hist_len);
+    }
+}
+
+/// The `PingPongTexture`s hold the G-buffer and radiance of the current and the previous frame,
+/// see `Tracer::swap_history`.
 #[derive(ResourceContainer)]
 pub struct DenoiserTextureSet {
-    pub denoiser_normal_tex: Resource<Texture>,
-    pub denoiser_normal_tex_prev: Resource<Texture>,
-    pub denoiser_position_tex: Resource<Texture>,
-    pub denoiser_position_tex_prev: Resource<Texture>,
-    pub denoiser_vox_id_tex: Resource<Texture>,
-    pub denoiser_vox_id_tex_prev: Resource<Texture>,
-    pub denoiser_accumed_tex: Resource<Texture>,
-    pub denoiser_accumed_tex_prev: Resource<Texture>,
+    pub denoiser_normal_tex: PingPongTexture,
+    pub denoiser_position_tex: PingPongTexture,
+    pub denoiser_vox_id_tex: PingPongTexture,
+    pub denoiser_accumed_tex: PingPongTexture,
     pub denoiser_motion_tex: Resource<Texture>,
     pub denoiser_temporal_hist_len_tex: Resource<Texture>,
     pub denoiser_hit_tex: Resource<Texture>,
@@ -26,11 +71,11 @@ pub struct DenoiserTextureSet {
 #[derive(ResourceContainer)]
 pub struct DenoiserResources {
     pub tex: DenoiserTextureSet,
-    pub temporal_info: Resource<Buffer>,
-    pub spatial_info: Resource<Buffer>,
 
     device: Device,
     allocator: Allocator,
+    #[resource(skip)]
+    precision: DenoiserPrecision,
 }
 
 impl DenoiserResources {
@@ -38,35 +83,20 @@ impl DenoiserResources {
         device: Device,
         allocator: Allocator,
         rendering_extent: Extent2D,
-        temporal_sm: &ShaderModule,
-        spatial_sm: &ShaderModule,
+        precision: DenoiserPrecision,
     ) -> Self {
-        let tex = Self::create_textures(device.clone(), allocator.clone(), rendering_extent);
-
-        let temporal_info_layout = temporal_sm.get_buffer_layout("U_TemporalInfo").unwrap();
-        let temporal_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            temporal_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let spatial_info_layout = spatial_sm.get_buffer_layout("U_SpatialInfo").unwrap();
-        let spatial_info = Buffer::from_buffer_layout(
+        let tex = Self::create_textures(
             device.clone(),
             allocator.clone(),
-            spatial_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
+            rendering_extent,
+            precision,
         );
 
         Self {
             device,
             allocator,
+            precision,
             tex,
-            temporal_info: Resource::new(temporal_info),
-            spatial_info: Resource::new(spatial_info),
         }
     }
 
@@ -75,6 +105,7 @@ impl DenoiserResources {
             self.device.clone(),
             self.allocator.clone(),
             rendering_extent,
+            self.precision,
         );
     }
 
@@ -82,6 +113,7 @@ impl DenoiserResources {
         device: Device,
         allocator: Allocator,
         rendering_extent: Extent2D,
+        precision: DenoiserPrecision,
     ) -> DenoiserTextureSet {
         let sam_desc = Default::default();
 
@@ -97,45 +129,41 @@ impl DenoiserResources {
             Texture::new(device.clone(), allocator.clone(), &tex_desc, &sam_desc)
         };
 
+        let create_history = |read_name, write_name, format| {
+            PingPongTexture::new(
+                read_name,
+                write_name,
+                [(); 2].map(|_| create_texture(format, vk::ImageUsageFlags::STORAGE)),
+            )
+        };
+
         DenoiserTextureSet {
-            denoiser_normal_tex: Resource::new(create_texture(
+            denoiser_normal_tex: create_history(
+                "denoiser_normal_tex_read",
+                "denoiser_normal_tex_write",
                 vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
-            )),
-            denoiser_normal_tex_prev: Resource::new(create_texture(
-                vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
-            )),
-            denoiser_position_tex: Resource::new(create_texture(
-                vk::Format::R32G32B32A32_SFLOAT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
-            )),
-            denoiser_position_tex_prev: Resource::new(create_texture(
+            ),
+            denoiser_position_tex: create_history(
+                "denoiser_position_tex_read",
+                "denoiser_position_tex_write",
                 vk::Format::R32G32B32A32_SFLOAT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
-            )),
-            denoiser_vox_id_tex: Resource::new(create_texture(
+            ),
+            denoiser_vox_id_tex: create_history(
+                "denoiser_vox_id_tex_read",
+                "denoiser_vox_id_tex_write",
                 vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
-            )),
-            denoiser_vox_id_tex_prev: Resource::new(create_texture(
-                vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
-            )),
-            denoiser_accumed_tex: Resource::new(create_texture(
+            ),
+            denoiser_accumed_tex: create_history(
+                "denoiser_accumed_tex_read",
+                "denoiser_accumed_tex_write",
                 vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC,
-            )),
-            denoiser_accumed_tex_prev: Resource::new(create_texture(
-                vk::Format::R32_UINT,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
-            )),
+            ),
             denoiser_motion_tex: Resource::new(create_texture(
-                vk::Format::R16G16_SFLOAT,
+                precision.motion_format(),
                 vk::ImageUsageFlags::STORAGE,
             )),
             denoiser_temporal_hist_len_tex: Resource::new(create_texture(
-                vk::Format::R8_UINT,
+                precision.hist_len_format(),
                 vk::ImageUsageFlags::STORAGE,
             )),
             denoiser_hit_tex: Resource::new(create_texture(
@@ -143,11 +171,11 @@ impl DenoiserResources {
                 vk::ImageUsageFlags::STORAGE,
             )),
             denoiser_spatial_ping_tex: Resource::new(create_texture(
-                vk::Format::B10G11R11_UFLOAT_PACK32,
+                precision.radiance_format(),
                 vk::ImageUsageFlags::STORAGE,
             )),
             denoiser_spatial_pong_tex: Resource::new(create_texture(
-                vk::Format::B10G11R11_UFLOAT_PACK32,
+                precision.radiance_format(),
                 vk::ImageUsageFlags::STORAGE,
             )),
         }
@@ -0,0 +1,86 @@
+use super::DynamicResolutionSettings;
+
+/// Frames whose GPU time is averaged before the scaling factor is reconsidered, so a single slow
+/// frame doesn't recreate the textures.
+const SAMPLE_FRAME_COUNT: u32 = 30;
+/// Frames skipped after a change, the profiler still reports the old resolution for a while.
+const SETTLE_FRAME_COUNT: u32 = 8;
+/// The scaling factor moves in steps of this, smaller corrections aren't worth a resize.
+const SCALING_FACTOR_STEP: f32 = 0.05;
+/// The share of the target frame time a new factor aims at, and below which the resolution goes
+/// up again. The gap keeps the factor from bouncing between two steps.
+const AIMED_SHARE: f32 = 0.9;
+const HEADROOM_SHARE: f32 = 0.75;
+
+/// Picks the scaling factor that keeps the GPU time of a frame under the target of
+/// [`DynamicResolutionSettings`].
+///
+/// Each change recreates the extent dependent textures, so the factor is reconsidered only once
+/// every few frames and moves in coarse steps.
+pub struct DynamicResolution {
+    /// The factor the tracer was created with, restored once disabled.
+    base_scaling_factor: f32,
+    gpu_ms_sum: f32,
+    sample_count: u32,
+    settle_frames_left: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(base_scaling_factor: f32) -> Self {
+        Self {
+            base_scaling_factor,
+            gpu_ms_sum: 0.0,
+            sample_count: 0,
+            settle_frames_left: 0,
+        }
+    }
+
+    /// Feeds the GPU time of the last frame, `None` if it wasn't measured.
+    ///
+    /// Returns the factor to render at from now on, `None` to keep `scaling_factor`.
+    pub fn update(
+        &mut self,
+        settings: &DynamicResolutionSettings,
+        scaling_factor: f32,
+        gpu_ms: Option<f32>,
+    ) -> Option<f32> {
+        if !settings.is_enabled {
+            self.reset_samples();
+            return (scaling_factor != self.base_scaling_factor)
+                .then_some(self.base_scaling_factor);
+        }
+        if self.settle_frames_left > 0 {
+            self.settle_frames_left -= 1;
+            return None;
+        }
+        let gpu_ms = gpu_ms?;
+        self.gpu_ms_sum += gpu_ms;
+        self.sample_count += 1;
+        if self.sample_count < SAMPLE_FRAME_COUNT {
+            return None;
+        }
+        let average_ms = self.gpu_ms_sum / self.sample_count as f32;
+        self.reset_samples();
+
+        let target_ms = settings.target_frame_time();
+        let new_factor = if average_ms > target_ms || average_ms < target_ms * HEADROOM_SHARE {
+            // the GPU time grows with the pixel count, the square of the factor
+            let ideal = scaling_factor * (target_ms * AIMED_SHARE / average_ms).sqrt();
+            (ideal / SCALING_FACTOR_STEP).floor() * SCALING_FACTOR_STEP
+        } else {
+            scaling_factor
+        };
+        // also pulls the factor back in when the bounds were moved past it
+        let new_factor = new_factor.clamp(settings.min_scaling_factor, settings.max_scaling_factor);
+        if (new_factor - scaling_factor).abs() < SCALING_FACTOR_STEP * 0.5 {
+            return None;
+        }
+        self.settle_frames_left = SETTLE_FRAME_COUNT;
+        Some(new_factor)
+    }
+
+    fn reset_samples(&mut self) {
+        self.gpu_ms_sum = 0.0;
+        self.sample_count = 0;
+    }
+}
@@ -1,6 +1,10 @@
+use super::{hiz_extent, PassScales};
 use crate::{
     resource::Resource,
-    vkn::{Allocator, Device, Extent2D, ImageDesc, Texture},
+    vkn::{
+        Allocator, Device, Extent2D, FrameGraph, ImageDesc, PingPongTexture, Texture,
+        TransientTextureBuilder,
+    },
 };
 use ash::vk;
 use resource_container_derive::ResourceContainer;
@@ -14,8 +18,11 @@ pub struct ExtentDependentResources {
     pub god_ray_output_tex: Resource<Texture>,
     pub screen_output_tex: Resource<Texture>,
     pub composited_tex: Resource<Texture>,
-    pub taa_tex: Resource<Texture>,
-    pub taa_tex_prev: Resource<Texture>,
+    /// Only written and read while the depth of field is on.
+    pub dof_tex: Resource<Texture>,
+    pub taa_tex: PingPongTexture,
+    /// The depth pyramid of the last frame, see [`super::hiz_level_rect`].
+    pub hiz_tex: Resource<Texture>,
 }
 
 impl ExtentDependentResources {
@@ -24,53 +31,149 @@ impl ExtentDependentResources {
         allocator: Allocator,
         rendering_extent: Extent2D,
         screen_extent: Extent2D,
+        pass_scales: PassScales,
     ) -> Self {
-        let gfx_depth_tex =
-            Self::create_gfx_depth_tex(device.clone(), allocator.clone(), rendering_extent);
-        let compute_depth_tex =
-            Self::create_compute_depth_tex(device.clone(), allocator.clone(), rendering_extent);
-        let compute_output_tex =
-            Self::create_compute_output_tex(device.clone(), allocator.clone(), rendering_extent);
-        let gfx_output_tex =
-            Self::create_gfx_output_tex(device.clone(), allocator.clone(), rendering_extent);
-        let god_ray_output_tex =
-            Self::create_god_ray_output_tex(device.clone(), allocator.clone(), rendering_extent);
-        let screen_output_tex =
-            Self::create_screen_output_tex(device.clone(), allocator.clone(), screen_extent);
-        let composited_tex =
-            Self::create_composited_tex(device.clone(), allocator.clone(), rendering_extent);
-        let taa_tex = Self::create_taa_tex(device.clone(), allocator.clone(), rendering_extent);
-        let taa_tex_prev = Self::create_taa_tex(device, allocator, rendering_extent);
+        let sam_desc = Default::default();
+        // both sides hold history, so they stay out of the aliasing
+        let taa_tex = PingPongTexture::new(
+            "taa_tex_read",
+            "taa_tex_write",
+            [(); 2].map(|_| {
+                Texture::new(
+                    device.clone(),
+                    allocator.clone(),
+                    &Self::taa_tex_desc(rendering_extent),
+                    &sam_desc,
+                )
+            }),
+        );
+
+        // read by the next frame, so it stays out of the aliasing too
+        let hiz_tex = Resource::new(Texture::new(
+            device.clone(),
+            allocator.clone(),
+            &Self::hiz_tex_desc(rendering_extent),
+            &sam_desc,
+        ));
+
+        let mut builder = TransientTextureBuilder::new(device, allocator);
+        let descs = [
+            ("gfx_depth_tex", Self::gfx_depth_tex_desc(rendering_extent)),
+            (
+                "compute_depth_tex",
+                Self::compute_depth_tex_desc(rendering_extent),
+            ),
+            (
+                "compute_output_tex",
+                Self::compute_output_tex_desc(rendering_extent),
+            ),
+            (
+                "gfx_output_tex",
+                Self::gfx_output_tex_desc(rendering_extent),
+            ),
+            (
+                "god_ray_output_tex",
+                Self::god_ray_output_tex_desc(pass_scales.god_ray_extent(rendering_extent)),
+            ),
+            (
+                "screen_output_tex",
+                Self::screen_output_tex_desc(screen_extent),
+            ),
+            (
+                "composited_tex",
+                Self::composited_tex_desc(rendering_extent),
+            ),
+            ("dof_tex", Self::dof_tex_desc(rendering_extent)),
+        ];
+        for (name, desc) in &descs {
+            builder.add(*name, desc, &sam_desc).unwrap();
+        }
+        let mut textures = builder.build(&Self::frame_graph()).unwrap();
+        let mut take = |name: &str| Resource::new(textures.take(name).unwrap());
 
         Self {
-            gfx_depth_tex: Resource::new(gfx_depth_tex),
-            compute_depth_tex: Resource::new(compute_depth_tex),
-            compute_output_tex: Resource::new(compute_output_tex),
-            gfx_output_tex: Resource::new(gfx_output_tex),
-            god_ray_output_tex: Resource::new(god_ray_output_tex),
-            screen_output_tex: Resource::new(screen_output_tex),
-            composited_tex: Resource::new(composited_tex),
-            taa_tex: Resource::new(taa_tex),
-            taa_tex_prev: Resource::new(taa_tex_prev),
+            gfx_depth_tex: take("gfx_depth_tex"),
+            compute_depth_tex: take("compute_depth_tex"),
+            compute_output_tex: take("compute_output_tex"),
+            gfx_output_tex: take("gfx_output_tex"),
+            god_ray_output_tex: take("god_ray_output_tex"),
+            screen_output_tex: take("screen_output_tex"),
+            composited_tex: take("composited_tex"),
+            dof_tex: take("dof_tex"),
+            taa_tex,
+            hiz_tex,
         }
     }
 
+    /// How `Tracer::record_trace` uses these textures, keep the two in sync.
+    ///
+    /// Every texture here is rewritten each frame, so the pass that writes it first has to start
+    /// with a discard barrier since its memory may be shared. The TAA history isn't part of it.
+    fn frame_graph() -> FrameGraph {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass(
+                "clear_render_targets",
+                &[],
+                &["gfx_output_tex", "gfx_depth_tex"],
+            )
+            .add_pass(
+                "flora_and_leaves",
+                &["gfx_output_tex", "gfx_depth_tex"],
+                &["gfx_output_tex", "gfx_depth_tex"],
+            )
+            .add_pass("tracer", &[], &["compute_output_tex", "compute_depth_tex"])
+            .add_pass("hiz", &["compute_depth_tex"], &[])
+            .add_pass(
+                "god_ray",
+                &["gfx_depth_tex", "compute_depth_tex"],
+                &["god_ray_output_tex"],
+            )
+            .add_pass("denoiser_temporal", &["compute_output_tex"], &[])
+            .add_pass("denoiser_spatial", &["compute_depth_tex"], &[])
+            .add_pass("water", &["gfx_depth_tex", "compute_depth_tex"], &[])
+            .add_pass(
+                "composition",
+                &[
+                    "gfx_output_tex",
+                    "gfx_depth_tex",
+                    "compute_depth_tex",
+                    "god_ray_output_tex",
+                ],
+                &["composited_tex"],
+            )
+            .add_pass(
+                "fog",
+                &["gfx_depth_tex", "compute_depth_tex", "composited_tex"],
+                &["composited_tex"],
+            )
+            .add_pass("taa", &["composited_tex"], &[])
+            .add_pass("dof", &["gfx_depth_tex", "compute_depth_tex"], &["dof_tex"])
+            .add_pass("post_processing", &["dof_tex"], &["screen_output_tex"])
+            // blitted to the swapchain after the tracer is done
+            .export("screen_output_tex");
+        graph
+    }
+
     pub fn on_resize(
         &mut self,
         device: Device,
         allocator: Allocator,
         rendering_extent: Extent2D,
         screen_extent: Extent2D,
+        pass_scales: PassScales,
     ) {
-        *self = Self::new(device, allocator, rendering_extent, screen_extent);
+        *self = Self::new(
+            device,
+            allocator,
+            rendering_extent,
+            screen_extent,
+            pass_scales,
+        );
     }
 
-    fn create_gfx_depth_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn gfx_depth_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::D32_SFLOAT,
             usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
@@ -79,48 +182,33 @@ impl ExtentDependentResources {
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::DEPTH,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_compute_depth_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn compute_depth_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::D32_SFLOAT,
             usage: vk::ImageUsageFlags::STORAGE,
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::DEPTH,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_compute_output_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn compute_output_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::R32_UINT,
             usage: vk::ImageUsageFlags::STORAGE,
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_gfx_output_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn gfx_output_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::R8G8B8A8_UNORM,
             usage: vk::ImageUsageFlags::STORAGE
@@ -129,18 +217,15 @@ impl ExtentDependentResources {
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_god_ray_output_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
-            extent: rendering_extent.into(),
-            format: vk::Format::R32_SFLOAT,
+    /// The lit length in `r` and the distance to the hit in `g`, which the upsample in the
+    /// composition weighs the texels by.
+    fn god_ray_output_tex_desc(god_ray_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
+            extent: god_ray_extent.into(),
+            format: vk::Format::R32G32_SFLOAT,
             usage: vk::ImageUsageFlags::STORAGE
                 | vk::ImageUsageFlags::SAMPLED
                 | vk::ImageUsageFlags::TRANSFER_SRC
@@ -148,30 +233,26 @@ impl ExtentDependentResources {
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_screen_output_tex(
-        device: Device,
-        allocator: Allocator,
-        screen_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn screen_output_tex_desc(screen_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: screen_extent.into(),
             format: vk::Format::R8G8B8A8_UNORM,
+            // the world gen preview is blitted onto it
             usage: vk::ImageUsageFlags::STORAGE
                 | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
                 | vk::ImageUsageFlags::COLOR_ATTACHMENT,
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_taa_tex(device: Device, allocator: Allocator, rendering_extent: Extent2D) -> Texture {
-        let tex_desc = ImageDesc {
+    fn taa_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::R16G16B16A16_SFLOAT,
             usage: vk::ImageUsageFlags::STORAGE
@@ -181,23 +262,39 @@ impl ExtentDependentResources {
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
     }
 
-    fn create_composited_tex(
-        device: Device,
-        allocator: Allocator,
-        rendering_extent: Extent2D,
-    ) -> Texture {
-        let tex_desc = ImageDesc {
+    fn hiz_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
+            extent: hiz_extent(rendering_extent).into(),
+            format: vk::Format::R32_SFLOAT,
+            usage: vk::ImageUsageFlags::STORAGE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        }
+    }
+
+    fn composited_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
             extent: rendering_extent.into(),
             format: vk::Format::B10G11R11_UFLOAT_PACK32,
             usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
             initial_layout: vk::ImageLayout::UNDEFINED,
             aspect: vk::ImageAspectFlags::COLOR,
             ..Default::default()
-        };
-        Texture::new(device, allocator, &tex_desc, &Default::default())
+        }
+    }
+
+    fn dof_tex_desc(rendering_extent: Extent2D) -> ImageDesc {
+        ImageDesc {
+            extent: rendering_extent.into(),
+            format: vk::Format::B10G11R11_UFLOAT_PACK32,
+            usage: vk::ImageUsageFlags::STORAGE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        }
     }
 }
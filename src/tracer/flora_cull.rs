@@ -0,0 +1,407 @@
+use super::hiz_level_count;
+use crate::{
+    builder::{Instance, InstancePool, InstanceResource, INSTANCE_POOL_CAPACITY},
+    resource::Resource,
+    util::ShaderCompiler,
+    vkn::{
+        Allocator, Buffer, BufferUsage, CommandBuffer, ComputePipeline, DescriptorPool, Extent2D,
+        Extent3D, MemoryBarrier, PipelineBarrier, PlainMemberTypeWithData, ShaderModule,
+        StructMemberDataBuilder, Texture, VulkanContext, WriteDescriptorSet,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use resource_container_derive::ResourceContainer;
+
+const INSTANCE_SIZE: u64 = std::mem::size_of::<Instance>() as u64;
+const DRAW_COMMAND_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectCommand>() as u64;
+
+/// The draws a frame can cull for, one per flora type and LOD.
+const MAX_CULL_DRAW_COUNT: u64 = 64;
+
+/// A sphere around every flora mesh standing on its instance position, in voxels. The meshes are
+/// at most 13 voxels tall and 5 wide.
+const FLORA_BOUND_CENTER_HEIGHT: f32 = 7.0;
+const FLORA_BOUND_RADIUS: f32 = 8.0;
+/// The furthest the wind moves the tip of a mesh with a sway of 1.0, in voxels, in the gust of a
+/// storm, see `wind.glsl`.
+const MAX_WIND_OFFSET: f32 = 26.0;
+
+/// Mirrors `VkDrawIndexedIndirectCommand`, which doesn't implement `Pod`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DrawIndexedIndirectCommand {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    vertex_offset: i32,
+    first_instance: u32,
+}
+
+/// The instances of one chunk slice, culled by the threads from `first_thread` on.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct FloraCullChunk {
+    first_instance: u32,
+    instance_count: u32,
+    first_thread: u32,
+    draw_idx: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct FloraCullDraw {
+    out_offset: u32,
+    /// In voxels, grows with the wind sway of the draw.
+    bound_radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct FloraCullPushConstant {
+    thread_count: u32,
+    chunk_count: u32,
+    bound_center_height: f32,
+}
+
+/// The depth pyramid of an earlier frame the instances are tested against, see
+/// [`super::hiz_level_rect`].
+pub struct HiZOcclusion<'a> {
+    pub hiz_tex: &'a Texture,
+    /// The extent of the depth the pyramid was built from.
+    pub depth_extent: Extent2D,
+    /// The view projection of the frame the pyramid was built in, `None` while it holds nothing
+    /// yet and only the frustum culls.
+    pub view_proj_mat: Option<Mat4>,
+}
+
+/// The instances of one draw and how it is drawn, see [`FloraCuller::record_cull`].
+pub struct FloraCullBatchDesc<'a> {
+    pub slices: Vec<&'a InstanceResource>,
+    pub index_count: u32,
+    pub wind_sway: f32,
+}
+
+/// The instances of one draw that passed the culling, see [`FloraCuller::record_cull`].
+#[derive(Debug, Clone, Copy)]
+pub struct CulledFloraBatch {
+    /// The first instance of the batch in [`FloraCuller::culled_instances`].
+    pub first_instance: u32,
+    draw_idx: u32,
+}
+
+impl CulledFloraBatch {
+    pub fn byte_offset(&self) -> u64 {
+        self.first_instance as u64 * INSTANCE_SIZE
+    }
+
+    /// Where the draw command of the batch is in [`FloraCuller::indirect_commands`].
+    pub fn indirect_offset(&self) -> u64 {
+        self.draw_idx as u64 * DRAW_COMMAND_SIZE
+    }
+}
+
+/// The buffers the CPU fills for a frame, one set per frame slot.
+#[derive(ResourceContainer)]
+struct FloraCullFrameResources {
+    flora_cull_info: Resource<Buffer>,
+    flora_cull_chunks: Resource<Buffer>,
+    flora_cull_draws: Resource<Buffer>,
+    /// One `VkDrawIndexedIndirectCommand` per draw, the instance counts are added up on the GPU.
+    flora_cull_indirect: Resource<Buffer>,
+}
+
+#[derive(ResourceContainer)]
+struct FloraCullResources {
+    /// The visible instances of every draw, each in its own range, bound as the instance vertex
+    /// buffer. Only the GPU touches it, so the frames share it.
+    flora_culled_instances: Resource<Buffer>,
+}
+
+/// Culls flora instances against the view frustum and the depth of the last frame on the GPU, for
+/// the alpha tested mode.
+///
+/// The chunks are still culled on the CPU, the instances of the chunks left are tested one by one
+/// and the visible ones are compacted per draw. The instance counts end up in indirect draw
+/// commands, so every draw covers all of its chunks in one call.
+///
+/// The occlusion test projects the bounds with the camera of the last frame, where the pyramid
+/// was built, so the flora it hides pops in a frame late when the camera moves fast.
+pub struct FloraCuller {
+    vulkan_ctx: VulkanContext,
+    frame_resources: Vec<FloraCullFrameResources>,
+    resources: FloraCullResources,
+    _pool: DescriptorPool,
+    cull_ppl: ComputePipeline,
+    max_chunk_count: u32,
+    frame_slot: usize,
+    /// The instance pool buffer and the pyramid the pipeline reads in each frame slot, written
+    /// when they change.
+    bound_inputs: Vec<(vk::Buffer, vk::ImageView)>,
+}
+
+impl FloraCuller {
+    /// `max_chunk_count` bounds the chunk slices culled per frame, across all draws.
+    /// `frame_count` is the number of frames in flight.
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        shader_compiler: &ShaderCompiler,
+        max_chunk_count: u32,
+        frame_count: usize,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let cull_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/foliage/flora_cull.comp",
+            "main",
+        )
+        .unwrap();
+
+        let cull_info_layout = cull_sm.get_buffer_layout("U_FloraCullInfo").unwrap();
+        let make_buffer = |flags: vk::BufferUsageFlags, location, size: u64| {
+            Buffer::new_sized(
+                device.clone(),
+                allocator.clone(),
+                BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER | flags),
+                location,
+                size,
+            )
+        };
+        let cpu_to_gpu = gpu_allocator::MemoryLocation::CpuToGpu;
+        let frame_resources = (0..frame_count)
+            .map(|_| FloraCullFrameResources {
+                flora_cull_info: Resource::new(Buffer::from_buffer_layout(
+                    device.clone(),
+                    allocator.clone(),
+                    cull_info_layout.clone(),
+                    BufferUsage::empty(),
+                    cpu_to_gpu,
+                )),
+                flora_cull_chunks: Resource::new(make_buffer(
+                    vk::BufferUsageFlags::empty(),
+                    cpu_to_gpu,
+                    std::mem::size_of::<FloraCullChunk>() as u64 * max_chunk_count.max(1) as u64,
+                )),
+                flora_cull_draws: Resource::new(make_buffer(
+                    vk::BufferUsageFlags::empty(),
+                    cpu_to_gpu,
+                    std::mem::size_of::<FloraCullDraw>() as u64 * MAX_CULL_DRAW_COUNT,
+                )),
+                flora_cull_indirect: Resource::new(make_buffer(
+                    vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    cpu_to_gpu,
+                    DRAW_COMMAND_SIZE * MAX_CULL_DRAW_COUNT,
+                )),
+            })
+            .collect::<Vec<_>>();
+        let resources = FloraCullResources {
+            flora_culled_instances: Resource::new(make_buffer(
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                gpu_allocator::MemoryLocation::GpuOnly,
+                INSTANCE_SIZE * INSTANCE_POOL_CAPACITY,
+            )),
+        };
+
+        let cull_ppl =
+            ComputePipeline::new(device, &cull_sm, &pool, &[&resources, &frame_resources[0]]);
+        for slot in 1..frame_count {
+            cull_ppl.add_frame_slot(&pool)?;
+            cull_ppl.select_frame_slot(slot);
+            cull_ppl.auto_update_descriptor_sets(&[&resources, &frame_resources[slot]])?;
+        }
+        cull_ppl.select_frame_slot(0);
+
+        Ok(Self {
+            vulkan_ctx,
+            frame_resources,
+            resources,
+            _pool: pool,
+            cull_ppl,
+            max_chunk_count,
+            frame_slot: 0,
+            bound_inputs: vec![(vk::Buffer::null(), vk::ImageView::null()); frame_count],
+        })
+    }
+
+    /// Makes the next cull fill the buffers of `slot`, the GPU must be done with the frame last
+    /// culled with them.
+    pub fn select_frame_slot(&mut self, slot: usize) {
+        self.cull_ppl.select_frame_slot(slot);
+        self.frame_slot = slot;
+    }
+
+    pub fn culled_instances(&self) -> &Buffer {
+        &self.resources.flora_culled_instances
+    }
+
+    /// The draw commands of the last cull.
+    pub fn indirect_commands(&self) -> &Buffer {
+        &self.frame_resources[self.frame_slot].flora_cull_indirect
+    }
+
+    /// Records the culling of every batch in one dispatch, the result of each batch is at the
+    /// same index, `None` when its slices hold no instances.
+    ///
+    /// The buffers are written from the CPU, cull once per frame.
+    pub fn record_cull(
+        &mut self,
+        cmdbuf: &CommandBuffer,
+        instance_pool: &InstancePool,
+        batches: &[FloraCullBatchDesc],
+        view_proj_mat: Mat4,
+        occlusion: &HiZOcclusion,
+    ) -> Result<Vec<Option<CulledFloraBatch>>> {
+        if batches.len() as u64 > MAX_CULL_DRAW_COUNT {
+            bail!(
+                "{} flora draws exceed the {} the culling has room for",
+                batches.len(),
+                MAX_CULL_DRAW_COUNT
+            );
+        }
+
+        let mut chunks = Vec::new();
+        let mut draws = Vec::new();
+        let mut commands = Vec::new();
+        let mut culled = Vec::new();
+        let mut thread_count = 0;
+        for batch in batches {
+            let draw_idx = draws.len() as u32;
+            let mut batch_instance_count = 0;
+            for slice in batch.slices.iter().filter(|slice| slice.instances_len > 0) {
+                chunks.push(FloraCullChunk {
+                    first_instance: slice.first_instance(),
+                    instance_count: slice.instances_len,
+                    first_thread: thread_count,
+                    draw_idx,
+                });
+                thread_count += slice.instances_len;
+                batch_instance_count += slice.instances_len;
+            }
+            if batch_instance_count == 0 {
+                culled.push(None);
+                continue;
+            }
+            // every draw gets room for all of its instances, the ranges follow each other
+            let out_offset = thread_count - batch_instance_count;
+            draws.push(FloraCullDraw {
+                out_offset,
+                bound_radius: FLORA_BOUND_RADIUS + MAX_WIND_OFFSET * batch.wind_sway,
+            });
+            commands.push(DrawIndexedIndirectCommand {
+                index_count: batch.index_count,
+                instance_count: 0,
+                first_index: 0,
+                vertex_offset: 0,
+                // the vertex buffer is bound at the range instead
+                first_instance: 0,
+            });
+            culled.push(Some(CulledFloraBatch {
+                first_instance: out_offset,
+                draw_idx,
+            }));
+        }
+        if thread_count == 0 {
+            return Ok(culled);
+        }
+        if chunks.len() > self.max_chunk_count as usize {
+            bail!(
+                "{} flora chunk slices exceed the {} the culling has room for",
+                chunks.len(),
+                self.max_chunk_count
+            );
+        }
+        if thread_count as u64 > INSTANCE_POOL_CAPACITY {
+            bail!(
+                "Culled flora batches of {} instances exceed the capacity of {}",
+                thread_count,
+                INSTANCE_POOL_CAPACITY
+            );
+        }
+
+        let (bound_instances_buf, bound_hiz_view) = &mut self.bound_inputs[self.frame_slot];
+        let instances_buf = &instance_pool.instances_buf;
+        if *bound_instances_buf != instances_buf.as_raw() {
+            self.cull_ppl
+                .write_descriptor_set(1, WriteDescriptorSet::new_buffer_write(0, instances_buf));
+            *bound_instances_buf = instances_buf.as_raw();
+        }
+        let hiz_view = occlusion.hiz_tex.get_image_view().as_raw();
+        if *bound_hiz_view != hiz_view {
+            self.cull_ppl.write_descriptor_set(
+                1,
+                WriteDescriptorSet::new_texture_write(
+                    1,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    occlusion.hiz_tex,
+                    vk::ImageLayout::GENERAL,
+                ),
+            );
+            *bound_hiz_view = hiz_view;
+        }
+
+        let frame_resources = &self.frame_resources[self.frame_slot];
+        let data = StructMemberDataBuilder::from_buffer(&frame_resources.flora_cull_info)
+            .set_field(
+                "view_proj_mat",
+                PlainMemberTypeWithData::Mat4(view_proj_mat.to_cols_array_2d()),
+            )
+            .set_field(
+                "hiz_view_proj_mat",
+                PlainMemberTypeWithData::Mat4(
+                    occlusion
+                        .view_proj_mat
+                        .unwrap_or(Mat4::IDENTITY)
+                        .to_cols_array_2d(),
+                ),
+            )
+            .set_field(
+                "hiz_depth_extent",
+                PlainMemberTypeWithData::UVec2([
+                    occlusion.depth_extent.width,
+                    occlusion.depth_extent.height,
+                ]),
+            )
+            .set_field(
+                "hiz_level_count",
+                PlainMemberTypeWithData::UInt(hiz_level_count(occlusion.depth_extent)),
+            )
+            .set_field(
+                "is_occlusion_enabled",
+                PlainMemberTypeWithData::UInt(occlusion.view_proj_mat.is_some() as u32),
+            )
+            .build()?;
+        frame_resources.flora_cull_info.fill_with_raw_u8(&data)?;
+        frame_resources.flora_cull_chunks.fill(&chunks)?;
+        frame_resources.flora_cull_draws.fill(&draws)?;
+        frame_resources.flora_cull_indirect.fill(&commands)?;
+
+        let push_constant = FloraCullPushConstant {
+            thread_count,
+            chunk_count: chunks.len() as u32,
+            bound_center_height: FLORA_BOUND_CENTER_HEIGHT,
+        };
+        self.cull_ppl.record(
+            cmdbuf,
+            Extent3D::new(thread_count, 1, 1),
+            Some(bytemuck::bytes_of(&push_constant)),
+        );
+
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_INPUT,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            )],
+        )
+        .record_insert(self.vulkan_ctx.device(), cmdbuf);
+
+        Ok(culled)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    builder::{Instance, InstancePool, InstanceResource, INSTANCE_POOL_CAPACITY},
+    resource::Resource,
+    util::ShaderCompiler,
+    vkn::{
+        Allocator, Buffer, BufferUsage, CommandBuffer, ComputePipeline, DescriptorPool, Extent3D,
+        GpuRadixSort, MemoryBarrier, PipelineBarrier, ShaderModule, VulkanContext,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use resource_container_derive::ResourceContainer;
+
+const INSTANCE_SIZE: u64 = std::mem::size_of::<Instance>() as u64;
+
+/// How the flora of the chunks is composited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloraBlendMode {
+    /// Opaque, the LOD transition is dithered. Draws the instances in any order.
+    #[default]
+    AlphaTest,
+    /// The LOD fade is blended, the instances are sorted back to front on the GPU every frame.
+    AlphaBlend,
+}
+
+impl FloraBlendMode {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Flora Blending");
+            ui.selectable_value(self, FloraBlendMode::AlphaTest, "Alpha Test");
+            ui.selectable_value(self, FloraBlendMode::AlphaBlend, "Alpha Blend");
+        });
+    }
+}
+
+/// Sorted instances of one draw, see [`FloraSorter::record_sort`].
+#[derive(Debug, Clone, Copy)]
+pub struct SortedFloraBatch {
+    /// The first instance of the batch in [`FloraSorter::sorted_instances`].
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+impl SortedFloraBatch {
+    pub fn byte_offset(&self) -> u64 {
+        self.first_instance as u64 * INSTANCE_SIZE
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct FloraSortPushConstant {
+    camera_pos: [f32; 3],
+    instance_count: u32,
+    camera_front: [f32; 3],
+    out_offset: u32,
+}
+
+#[derive(ResourceContainer)]
+struct FloraSortResources {
+    /// The instances of the batch being sorted, gathered from their chunk slices.
+    flora_sort_src: Resource<Buffer>,
+    flora_sort_keys: Resource<Buffer>,
+    flora_sort_values: Resource<Buffer>,
+    /// The sorted batches of this frame back to back, bound as the instance vertex buffer.
+    flora_sorted_instances: Resource<Buffer>,
+}
+
+/// Depth sorts flora instances for the alpha blended mode.
+///
+/// All chunks of a draw are sorted as one batch rather than chunk by chunk, so instances of
+/// neighbouring chunks still blend in the right order. The keys are the distances along the view
+/// direction, sorted with [`GpuRadixSort`].
+pub struct FloraSorter {
+    vulkan_ctx: VulkanContext,
+    resources: FloraSortResources,
+    _pool: DescriptorPool,
+    keys_ppl: ComputePipeline,
+    gather_ppl: ComputePipeline,
+    radix_sort: GpuRadixSort,
+}
+
+impl FloraSorter {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        shader_compiler: &ShaderCompiler,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let keys_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/foliage/flora_sort_keys.comp",
+            "main",
+        )
+        .unwrap();
+        let gather_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/foliage/flora_sort_gather.comp",
+            "main",
+        )
+        .unwrap();
+
+        let make_buffer = |flags: vk::BufferUsageFlags, size: u64| {
+            Buffer::new_sized(
+                device.clone(),
+                allocator.clone(),
+                BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER | flags),
+                gpu_allocator::MemoryLocation::GpuOnly,
+                size,
+            )
+        };
+        let transfer = vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST;
+        let key_size = std::mem::size_of::<u32>() as u64 * INSTANCE_POOL_CAPACITY;
+        let resources = FloraSortResources {
+            flora_sort_src: Resource::new(make_buffer(
+                vk::BufferUsageFlags::TRANSFER_DST,
+                INSTANCE_SIZE * INSTANCE_POOL_CAPACITY,
+            )),
+            flora_sort_keys: Resource::new(make_buffer(transfer, key_size)),
+            flora_sort_values: Resource::new(make_buffer(transfer, key_size)),
+            flora_sorted_instances: Resource::new(make_buffer(
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                INSTANCE_SIZE * INSTANCE_POOL_CAPACITY,
+            )),
+        };
+
+        let keys_ppl = ComputePipeline::new(device, &keys_sm, &pool, &[&resources]);
+        let gather_ppl = ComputePipeline::new(device, &gather_sm, &pool, &[&resources]);
+
+        let radix_sort = GpuRadixSort::new(
+            vulkan_ctx.clone(),
+            allocator,
+            shader_compiler,
+            INSTANCE_POOL_CAPACITY as u32,
+        )?;
+
+        Ok(Self {
+            vulkan_ctx,
+            resources,
+            _pool: pool,
+            keys_ppl,
+            gather_ppl,
+            radix_sort,
+        })
+    }
+
+    pub fn sorted_instances(&self) -> &Buffer {
+        &self.resources.flora_sorted_instances
+    }
+
+    /// Records the back to front sort of the instances in `slices`, written to
+    /// [`Self::sorted_instances`] starting at `first_instance`.
+    ///
+    /// Batches of one frame must not overlap, start each one where the last one ended. Returns
+    /// `None` when the slices hold no instances.
+    pub fn record_sort(
+        &self,
+        cmdbuf: &CommandBuffer,
+        instance_pool: &InstancePool,
+        slices: &[&InstanceResource],
+        camera_pos: Vec3,
+        camera_front: Vec3,
+        first_instance: u32,
+    ) -> Result<Option<SortedFloraBatch>> {
+        let instance_count: u32 = slices.iter().map(|slice| slice.instances_len).sum();
+        if instance_count == 0 {
+            return Ok(None);
+        }
+        if (first_instance + instance_count) as u64 > INSTANCE_POOL_CAPACITY {
+            bail!(
+                "Sorted flora batches of {} instances exceed the capacity of {}",
+                first_instance + instance_count,
+                INSTANCE_POOL_CAPACITY
+            );
+        }
+
+        let device = self.vulkan_ctx.device();
+        let barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER
+                | vk::PipelineStageFlags::TRANSFER
+                | vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ
+                    | vk::AccessFlags::SHADER_WRITE
+                    | vk::AccessFlags::TRANSFER_READ
+                    | vk::AccessFlags::TRANSFER_WRITE,
+            )],
+        );
+
+        barrier.record_insert(device, cmdbuf);
+        let mut src_offset = 0;
+        for slice in slices.iter().filter(|slice| slice.instances_len > 0) {
+            let size = slice.instances_len as u64 * INSTANCE_SIZE;
+            instance_pool.instances_buf.record_copy_to_buffer(
+                cmdbuf,
+                &self.resources.flora_sort_src,
+                size,
+                slice.byte_offset(),
+                src_offset,
+            );
+            src_offset += size;
+        }
+        barrier.record_insert(device, cmdbuf);
+
+        let push_constant = FloraSortPushConstant {
+            camera_pos: camera_pos.to_array(),
+            instance_count,
+            camera_front: camera_front.to_array(),
+            out_offset: first_instance,
+        };
+        let extent = Extent3D {
+            width: instance_count,
+            height: 1,
+            depth: 1,
+        };
+        self.keys_ppl
+            .record(cmdbuf, extent, Some(bytemuck::bytes_of(&push_constant)));
+        self.radix_sort.record_sort(
+            cmdbuf,
+            &self.resources.flora_sort_keys,
+            Some(&self.resources.flora_sort_values),
+            instance_count,
+        )?;
+        self.gather_ppl
+            .record(cmdbuf, extent, Some(bytemuck::bytes_of(&push_constant)));
+
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            )],
+        )
+        .record_insert(device, cmdbuf);
+
+        Ok(Some(SortedFloraBatch {
+            first_instance,
+            instance_count,
+        }))
+    }
+}
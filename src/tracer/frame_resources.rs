@@ -0,0 +1,127 @@
+use crate::{
+    resource::Resource,
+    tracer::{ImpostorInstance, ShaderModules, MAX_IMPOSTOR_COUNT},
+    vkn::{Allocator, Buffer, BufferUsage, Extent2D, ShaderModule, VulkanContext},
+};
+use ash::vk;
+use resource_container_derive::ResourceContainer;
+
+/// The buffers the CPU writes every frame or reads back after it, one set per frame slot so a
+/// frame in flight keeps its own while the next one is recorded. Bound by name like
+/// [`super::TracerResources`].
+#[derive(ResourceContainer)]
+pub struct FrameResources {
+    pub gui_input: Resource<Buffer>,
+    pub sun_info: Resource<Buffer>,
+    pub shading_info: Resource<Buffer>,
+    pub camera_info: Resource<Buffer>,
+    pub camera_info_prev_frame: Resource<Buffer>,
+    pub shadow_camera_info: Resource<Buffer>,
+    pub env_info: Resource<Buffer>,
+    pub starlight_info: Resource<Buffer>,
+    pub voxel_colors: Resource<Buffer>,
+    pub taa_info: Resource<Buffer>,
+    pub god_ray_info: Resource<Buffer>,
+    pub fog_info: Resource<Buffer>,
+    pub dof_info: Resource<Buffer>,
+    pub water_info: Resource<Buffer>,
+    pub post_processing_info: Resource<Buffer>,
+    pub weather_info: Resource<Buffer>,
+    pub temporal_info: Resource<Buffer>,
+    pub spatial_info: Resource<Buffer>,
+    pub player_collider_info: Resource<Buffer>,
+    /// Read back once the frame is done, see `Tracer::read_player_collision_result`.
+    pub player_collision_result: Resource<Buffer>,
+    pub sky_visibility_info: Resource<Buffer>,
+    pub canopy_density: Resource<Buffer>,
+    pub sky_map_info: Resource<Buffer>,
+    /// A copy of the [`crate::gameplay::PathWearMap`] for the shading of worn ground.
+    pub path_wear: Resource<Buffer>,
+    pub path_wear_info: Resource<Buffer>,
+    /// A copy of the [`crate::gameplay::WindField`] the foliage sways in.
+    pub wind_field: Resource<Buffer>,
+    /// The far trees drawn as impostors this frame.
+    pub impostor_instances: Resource<Buffer>,
+
+    /// `path_wear` misses a change of the wear, the copy is filled when the slot is next used.
+    pub is_path_wear_stale: bool,
+}
+
+impl FrameResources {
+    pub fn new(
+        vulkan_ctx: &VulkanContext,
+        allocator: Allocator,
+        shader_modules: &ShaderModules,
+        sky_visibility_extent: Extent2D,
+        path_wear_extent: Extent2D,
+    ) -> Self {
+        let device = vulkan_ctx.device();
+
+        let uniform = |sm: &ShaderModule, name: &str| {
+            let layout = sm.get_buffer_layout(name).unwrap();
+            Resource::new(Buffer::from_buffer_layout(
+                device.clone(),
+                allocator.clone(),
+                layout.clone(),
+                BufferUsage::empty(),
+                gpu_allocator::MemoryLocation::CpuToGpu,
+            ))
+        };
+        let storage = |size: u64| {
+            Buffer::new_sized(
+                device.clone(),
+                allocator.clone(),
+                BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+                gpu_allocator::MemoryLocation::CpuToGpu,
+                size,
+            )
+        };
+
+        let path_wear_texel_count = path_wear_extent.width * path_wear_extent.height;
+        let path_wear = storage((path_wear_texel_count * std::mem::size_of::<f32>() as u32) as u64);
+        path_wear
+            .fill(&vec![0.0f32; path_wear_texel_count as usize])
+            .unwrap();
+
+        let impostor_instances =
+            storage((std::mem::size_of::<ImpostorInstance>() * MAX_IMPOSTOR_COUNT as usize) as u64);
+
+        let canopy_density = storage(
+            (sky_visibility_extent.width
+                * sky_visibility_extent.height
+                * std::mem::size_of::<u32>() as u32) as u64,
+        );
+
+        let sm = shader_modules;
+        Self {
+            gui_input: uniform(&sm.tracer_sm, "U_GuiInput"),
+            sun_info: uniform(&sm.tracer_sm, "U_SunInfo"),
+            shading_info: uniform(&sm.tracer_sm, "U_ShadingInfo"),
+            camera_info: uniform(&sm.tracer_sm, "U_CameraInfo"),
+            camera_info_prev_frame: uniform(&sm.tracer_sm, "U_CameraInfoPrevFrame"),
+            shadow_camera_info: uniform(&sm.tracer_shadow_sm, "U_ShadowCameraInfo"),
+            env_info: uniform(&sm.tracer_sm, "U_EnvInfo"),
+            starlight_info: uniform(&sm.sky_map_sm, "U_StarlightInfo"),
+            voxel_colors: uniform(&sm.tracer_sm, "U_VoxelColors"),
+            taa_info: uniform(&sm.taa_sm, "U_TaaInfo"),
+            god_ray_info: uniform(&sm.god_ray_sm, "U_GodRayInfo"),
+            fog_info: uniform(&sm.fog_sm, "U_FogInfo"),
+            dof_info: uniform(&sm.dof_sm, "U_DofInfo"),
+            water_info: uniform(&sm.water_sm, "U_WaterInfo"),
+            post_processing_info: uniform(&sm.post_processing_sm, "U_PostProcessingInfo"),
+            weather_info: uniform(&sm.post_processing_sm, "U_WeatherInfo"),
+            temporal_info: uniform(&sm.temporal_sm, "U_TemporalInfo"),
+            spatial_info: uniform(&sm.spatial_sm, "U_SpatialInfo"),
+            player_collider_info: uniform(&sm.player_collider_sm, "U_PlayerColliderInfo"),
+            player_collision_result: uniform(&sm.player_collider_sm, "B_PlayerCollisionResult"),
+            sky_visibility_info: uniform(&sm.sky_visibility_sm, "U_SkyVisibilityInfo"),
+            canopy_density: Resource::new(canopy_density),
+            sky_map_info: uniform(&sm.sky_map_sm, "U_SkyMapInfo"),
+            path_wear: Resource::new(path_wear),
+            path_wear_info: uniform(&sm.tracer_sm, "U_PathWearInfo"),
+            wind_field: uniform(&sm.flora_vert_sm, "U_WindField"),
+            impostor_instances: Resource::new(impostor_instances),
+            is_path_wear_stale: false,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::vkn::Extent2D;
+use glam::UVec2;
+
+/// The Hi-Z pyramid stops here even when the depth is larger than 8K.
+pub const HIZ_MAX_LEVEL_COUNT: u32 = 13;
+
+/// Where a level of the Hi-Z pyramid lies in the pyramid texture, as offset and size. Mirrors
+/// `hiz_level_rect` in `hiz.glsl`.
+///
+/// Level 0 halves the depth extent and takes the left, the coarser levels stack up on its right.
+/// Each texel holds the farthest depth of the texels below it.
+pub fn hiz_level_rect(depth_extent: Extent2D, level: u32) -> (UVec2, UVec2) {
+    let mut size = hiz_half(UVec2::new(depth_extent.width, depth_extent.height));
+    let mut offset = UVec2::ZERO;
+    for i in 1..=level {
+        if i == 1 {
+            offset = UVec2::new(size.x, 0);
+        } else {
+            offset.y += size.y;
+        }
+        size = hiz_half(size);
+    }
+    (offset, size)
+}
+
+/// Levels down to the first one of a single texel.
+pub fn hiz_level_count(depth_extent: Extent2D) -> u32 {
+    let mut size = hiz_half(UVec2::new(depth_extent.width, depth_extent.height));
+    let mut count = 1;
+    while size.max_element() > 1 && count < HIZ_MAX_LEVEL_COUNT {
+        size = hiz_half(size);
+        count += 1;
+    }
+    count
+}
+
+/// The extent of the texture holding every level.
+pub fn hiz_extent(depth_extent: Extent2D) -> Extent2D {
+    let (_, level0_size) = hiz_level_rect(depth_extent, 0);
+    let mut extent = level0_size;
+    for level in 1..hiz_level_count(depth_extent) {
+        let (offset, size) = hiz_level_rect(depth_extent, level);
+        extent = extent.max(offset + size);
+    }
+    Extent2D::new(extent.x.max(1), extent.y.max(1))
+}
+
+// odd sizes round up, the last texel covers one texel less
+fn hiz_half(size: UVec2) -> UVec2 {
+    (size + UVec2::ONE) / 2
+}
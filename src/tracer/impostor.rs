@@ -0,0 +1,147 @@
+use crate::geom::Aabb3;
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, UVec2, Vec3};
+use std::collections::{HashMap, VecDeque};
+
+/// Side of the square texture holding every impostor, in texels.
+pub const IMPOSTOR_ATLAS_SIZE: u32 = 2048;
+/// Side of the square slot one tree is baked into.
+pub const IMPOSTOR_SLOT_SIZE: u32 = 128;
+/// Mirrored by `SLOTS_PER_ROW` in `impostor.vert`.
+const IMPOSTOR_SLOTS_PER_ROW: u32 = IMPOSTOR_ATLAS_SIZE / IMPOSTOR_SLOT_SIZE;
+/// Trees past this count keep their far LOD at any distance.
+pub const MAX_IMPOSTOR_COUNT: u32 = IMPOSTOR_SLOTS_PER_ROW * IMPOSTOR_SLOTS_PER_ROW;
+/// So a burst of new trees doesn't stall a frame.
+pub const MAX_IMPOSTOR_BAKES_PER_FRAME: usize = 4;
+
+/// One impostor quad of `impostor_instances`, mirrors `ImpostorInstance` in `impostor.vert`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct ImpostorInstance {
+    pub center: [f32; 3],
+    pub half_size: f32,
+    pub slot: u32,
+    /// Fraction of the pixels drawn, below 1.0 while the tree fades in from its far LOD.
+    pub lod_fade: f32,
+    pub _padding: [u32; 2],
+}
+
+/// Where a tree is baked and the box its slot covers, in world units.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeImpostor {
+    pub slot: u32,
+    pub center: Vec3,
+    pub half_size: f32,
+    is_baked: bool,
+}
+
+impl TreeImpostor {
+    /// A slot covers a square around the tree, wide enough for the leaves seen from any side.
+    fn new(slot: u32, aabb: &Aabb3) -> Self {
+        let extent = aabb.max() - aabb.min();
+        let horizontal_radius = Vec3::new(extent.x, 0.0, extent.z).length() * 0.5;
+        Self {
+            slot,
+            center: aabb.center(),
+            half_size: horizontal_radius.max(extent.y * 0.5),
+            is_baked: false,
+        }
+    }
+
+    /// The top left texel of the slot and its size.
+    pub fn slot_rect(&self) -> (UVec2, UVec2) {
+        let offset = UVec2::new(
+            self.slot % IMPOSTOR_SLOTS_PER_ROW,
+            self.slot / IMPOSTOR_SLOTS_PER_ROW,
+        ) * IMPOSTOR_SLOT_SIZE;
+        (offset, UVec2::splat(IMPOSTOR_SLOT_SIZE))
+    }
+
+    /// Looks at the tree along -z, the way `impostor.vert` turns the quad to face the camera
+    /// around the y axis. Flipped in y like the camera.
+    pub fn bake_view_proj_mat(&self) -> Mat4 {
+        let eye = self.center + Vec3::Z * self.half_size;
+        let view = Mat4::look_at_rh(eye, self.center, Vec3::Y);
+        let h = self.half_size;
+        let proj = Mat4::orthographic_rh(-h, h, -h, h, 0.0, 2.0 * h);
+        let flip_y = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0));
+        flip_y * proj * view
+    }
+}
+
+/// Hands out the slots of `impostor_atlas_tex` and tracks which trees still have to be baked.
+///
+/// Every tree is grown on its own, so each one gets its own slot.
+pub struct ImpostorAtlas {
+    impostors: HashMap<u32, TreeImpostor>,
+    free_slots: Vec<u32>,
+    pending_bakes: VecDeque<u32>,
+}
+
+impl ImpostorAtlas {
+    pub fn new() -> Self {
+        Self {
+            impostors: HashMap::new(),
+            // popped from the back, so the slots fill up from the top left
+            free_slots: (0..MAX_IMPOSTOR_COUNT).rev().collect(),
+            pending_bakes: VecDeque::new(),
+        }
+    }
+
+    /// Queues a bake of the tree, again if it was baked before. Without a free slot the tree
+    /// gets no impostor.
+    pub fn request_bake(&mut self, tree_id: u32, aabb: &Aabb3) {
+        let slot = match self.impostors.get(&tree_id) {
+            Some(impostor) => impostor.slot,
+            None => match self.free_slots.pop() {
+                Some(slot) => slot,
+                None => return,
+            },
+        };
+        self.impostors
+            .insert(tree_id, TreeImpostor::new(slot, aabb));
+        if !self.pending_bakes.contains(&tree_id) {
+            self.pending_bakes.push_back(tree_id);
+        }
+    }
+
+    /// The leaves mesh changed, every impostor is baked again. The old bakes are drawn until then.
+    pub fn request_rebake_all(&mut self) {
+        for tree_id in self.impostors.keys() {
+            if !self.pending_bakes.contains(tree_id) {
+                self.pending_bakes.push_back(*tree_id);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, tree_id: u32) {
+        if let Some(impostor) = self.impostors.remove(&tree_id) {
+            self.free_slots.push(impostor.slot);
+        }
+        self.pending_bakes.retain(|&id| id != tree_id);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// The next trees to bake, they count as baked from now on.
+    pub fn take_pending_bakes(&mut self, max_count: usize) -> Vec<(u32, TreeImpostor)> {
+        let count = self.pending_bakes.len().min(max_count);
+        self.pending_bakes
+            .drain(..count)
+            .filter_map(|tree_id| {
+                let impostor = self.impostors.get_mut(&tree_id)?;
+                impostor.is_baked = true;
+                Some((tree_id, *impostor))
+            })
+            .collect()
+    }
+
+    /// Only trees whose slot holds a finished bake.
+    pub fn get(&self, tree_id: u32) -> Option<&TreeImpostor> {
+        self.impostors
+            .get(&tree_id)
+            .filter(|impostor| impostor.is_baked)
+    }
+}
@@ -0,0 +1,159 @@
+use super::LodState;
+use crate::builder::FloraType;
+use crate::geom::Aabb3;
+use glam::{Mat4, Vec3};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// When one kind of entity gives way to its far LOD, in projected screen size.
+#[derive(Debug, Clone, Copy)]
+pub struct LodThresholds {
+    /// Entities covering less than this fraction of the screen height use the far LOD.
+    pub switch_size: f32,
+    /// Relative margin past `switch_size` an entity has to cross before it switches, so an
+    /// entity hovering at the threshold doesn't flicker between the LODs.
+    pub hysteresis: f32,
+    /// Width of the size band around `switch_size` where both LODs are drawn dithered into each
+    /// other, 0.0 switches at once. Flora fades instance by instance, the rest as a whole.
+    pub transition_range: f32,
+}
+
+impl LodThresholds {
+    /// The far LOD's share at `projected_size` inside the transition band, mirrored by
+    /// `instance_lod_fade` in `lod_dither.glsl`.
+    pub fn far_share(&self, projected_size: f32) -> f32 {
+        let band_end = self.switch_size + self.transition_range * 0.5;
+        ((band_end - projected_size) / self.transition_range).clamp(0.0, 1.0)
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui, label: &str) {
+        ui.label(label);
+        ui.add(egui::Slider::new(&mut self.switch_size, 0.0..=2.0).text("Switch Size"));
+        ui.add(egui::Slider::new(&mut self.hysteresis, 0.0..=0.5).text("Hysteresis"));
+        ui.add(egui::Slider::new(&mut self.transition_range, 0.0..=0.5).text("Transition Range"));
+    }
+}
+
+/// LOD thresholds of each flora type.
+#[derive(Debug, Clone)]
+pub struct FloraLodDesc {
+    /// Indexed by [`FloraType`].
+    pub flora: Vec<LodThresholds>,
+    pub leaves: LodThresholds,
+    /// When the far LOD of the leaves gives way to the tree's impostor.
+    pub impostors: LodThresholds,
+}
+
+impl Default for FloraLodDesc {
+    fn default() -> Self {
+        Self {
+            flora: FloraType::all()
+                .map(|flora_type| flora_type.desc().lod)
+                .collect(),
+            leaves: LodThresholds {
+                switch_size: 0.3,
+                hysteresis: 0.1,
+                transition_range: 0.08,
+            },
+            impostors: LodThresholds {
+                switch_size: 0.08,
+                hysteresis: 0.1,
+                transition_range: 0.02,
+            },
+        }
+    }
+}
+
+impl FloraLodDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        for (flora_type, thresholds) in FloraType::all().zip(&mut self.flora) {
+            thresholds.edit_by_gui(ui, flora_type.desc().name);
+            ui.separator();
+        }
+        self.leaves.edit_by_gui(ui, "Leaves");
+        ui.separator();
+        self.impostors.edit_by_gui(ui, "Tree Impostors");
+    }
+}
+
+/// The fraction of the screen height the bounding sphere of `aabb` covers.
+pub fn projected_size(aabb: &Aabb3, camera_pos: Vec3, proj_mat: &Mat4) -> f32 {
+    let distance = (aabb.center() - camera_pos).length().max(1e-4);
+    projected_size_scale(aabb, proj_mat) / distance
+}
+
+/// The largest and the smallest [`projected_size`] of the bounding sphere of `aabb` when it is
+/// centered anywhere inside `aabb`, the spread of the sizes of what the box holds.
+pub fn projected_size_range(aabb: &Aabb3, camera_pos: Vec3, proj_mat: &Mat4) -> (f32, f32) {
+    let radius = bounding_radius(aabb);
+    let distance = (aabb.center() - camera_pos).length();
+    let scale = projected_size_scale(aabb, proj_mat);
+    (
+        scale / (distance - radius).max(1e-4),
+        scale / (distance + radius).max(1e-4),
+    )
+}
+
+/// The projected size of the bounding sphere of `aabb` at a distance of one world unit.
+pub fn projected_size_scale(aabb: &Aabb3, proj_mat: &Mat4) -> f32 {
+    // the y scale of the projection is 1 / tan(fov_y / 2)
+    bounding_radius(aabb) * proj_mat.y_axis.y
+}
+
+fn bounding_radius(aabb: &Aabb3) -> f32 {
+    (aabb.max() - aabb.min()).length() * 0.5
+}
+
+/// Picks the LOD of each entity from its projected screen size, remembering the last pick of the
+/// entities that switch at once so they can be held there by the hysteresis.
+pub struct LodSelector<K> {
+    states: HashMap<K, LodState>,
+}
+
+impl<K> Default for LodSelector<K> {
+    fn default() -> Self {
+        Self {
+            states: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> LodSelector<K> {
+    /// The far LOD's share of `key` this frame, 0.0 draws only the near LOD and 1.0 only the far
+    /// one.
+    pub fn select(&mut self, key: K, projected_size: f32, thresholds: &LodThresholds) -> f32 {
+        if thresholds.transition_range > 0.0 {
+            // the LODs blend into each other across the band, there's no switch to hold back
+            self.states.remove(&key);
+            return thresholds.far_share(projected_size);
+        }
+
+        let shrink_size = thresholds.switch_size * (1.0 - thresholds.hysteresis);
+        let grow_size = thresholds.switch_size * (1.0 + thresholds.hysteresis);
+        let state = self
+            .states
+            .entry(key)
+            .or_insert(if projected_size < thresholds.switch_size {
+                LodState::Lod1
+            } else {
+                LodState::Lod0
+            });
+        *state = match *state {
+            LodState::Lod0 if projected_size < shrink_size => LodState::Lod1,
+            LodState::Lod1 if projected_size > grow_size => LodState::Lod0,
+            state => state,
+        };
+        match *state {
+            LodState::Lod0 => 0.0,
+            LodState::Lod1 => 1.0,
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        self.states.remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
@@ -5,6 +5,9 @@ pub use resources::*;
 mod denoiser_resources;
 pub use denoiser_resources::*;
 
+mod frame_resources;
+use frame_resources::*;
+
 mod extent_dependent_resources;
 pub use extent_dependent_resources::*;
 
@@ -16,45 +19,105 @@ mod voxel_encoding;
 mod voxel_geometry;
 
 mod flora_construct;
+pub use flora_construct::*;
 
 mod leaves_construct;
 
+mod rain_drops;
+
+mod weather_particles;
+use weather_particles::MAX_WEATHER_PARTICLE_COUNT;
+
+mod sky_visibility;
+use sky_visibility::*;
+
+mod shadow_cache;
+use shadow_cache::*;
+
+mod sky_cache;
+use sky_cache::*;
+
+mod scene_ray_queue;
+use scene_ray_queue::*;
+
+mod streamed_textures;
+use streamed_textures::*;
+
+mod dynamic_resolution;
+use dynamic_resolution::*;
+
+mod settings;
+pub use settings::*;
+
+mod shadow_cull;
+pub use shadow_cull::*;
+
+mod lod_selector;
+pub use lod_selector::*;
+
+mod flora_sort;
+pub use flora_sort::*;
+
+mod flora_cull;
+pub use flora_cull::*;
+
+mod hiz;
+pub use hiz::*;
+
+mod impostor;
+pub use impostor::*;
+
+mod terrain_query;
+pub use terrain_query::*;
+
 mod pipeline_builder;
 use pipeline_builder::*;
 
 mod buffer_updater;
 use buffer_updater::*;
 
-use glam::{Mat4, UVec3, Vec2, Vec3};
+mod raster_targets;
+use raster_targets::*;
+
+use glam::{Mat4, UVec2, UVec3, UVec4, Vec2, Vec3};
 use winit::event::KeyEvent;
 
 use crate::audio::SpatialSoundManager;
 use crate::builder::{
-    ContreeBuilderResources, FloraInstanceResources, FloraType, Instance,
-    SceneAccelBuilderResources, SurfaceResources, TreeLeavesInstance,
+    ContreeBuilderResources, FloraInstanceResources, FloraType, Instance, InstancePool,
+    InstanceResource, SceneAccelBuilderResources, SurfaceResources, TreeLeavesInstance,
 };
-use crate::gameplay::{calculate_directional_light_matrices, Camera, CameraDesc, CameraVectors};
-use crate::geom::UAabb3;
+use crate::constants::{MAX_PLAYER_COLLIDER_RING_COUNT, MAX_TERRAIN_QUERIES, VOXEL_DIM};
+use crate::gameplay::{
+    calculate_directional_light_matrices, Camera, CameraDesc, CameraFeelDesc, CameraVectors,
+    CharacterControllerDesc, PathWearMap, PrecipitationKind, Weather, WindField, WorldClock,
+};
+use crate::geom::{Aabb3, Frustum, FrustumCullCache, UAabb3, VoxelPos};
 use crate::resource::ResourceContainer;
-use crate::util::{ShaderCompiler, TimeInfo};
+use crate::util::{full_path_from_relative, profile_scope, ShaderCompiler, TimeInfo};
 use crate::vkn::{
-    execute_one_time_command, Allocator, Buffer, ClearValue, ColorClearValue, CommandBuffer,
-    ComputePipeline, DepthOrStencilClearValue, DescriptorPool, Extent2D, Extent3D, Framebuffer,
-    GraphicsPipeline, MemoryBarrier, PipelineBarrier, PlainMemberTypeWithData, PushConstantInfo,
-    RenderPass, RenderTarget, StructMemberDataBuilder, StructMemberDataReader, Texture, Viewport,
-    VulkanContext,
+    execute_one_time_command, find_fastest_workgroup_size, Allocator, Buffer, ClearValue,
+    ColorClearValue, CommandBuffer, ComputePipeline, DepthOrStencilClearValue, DescriptorPool,
+    Extent2D, Extent3D, GpuPassTiming, GpuProfiler, GraphicsPipeline, MemoryBarrier,
+    PipelineBarrier, PlainMemberTypeWithData, PushConstantInfo, StructMemberDataBuilder,
+    StructMemberDataReader, Texture, TimelineSemaphore, Viewport, VulkanContext,
+    WorkgroupSizeCache,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use ash::vk;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct PushConstantStd140 {
     time: f32,
+    wind_sway: f32,
+    density: f32,
     // `std140` requires a `vec3` to be aligned to 16 bytes.
-    // `time` is 4 bytes, so we need 12 bytes of padding to reach offset 16.
-    _padding1: [u8; 12],
+    // `time`, `wind_sway` and `density` are 12 bytes, so we need 4 bytes of padding to reach
+    // offset 16.
+    _padding1: [u8; 4],
 
     bottom_color: Vec3,
     // After `bottom_color` (12 bytes), we are at offset 16 + 12 = 28.
@@ -63,41 +126,265 @@ struct PushConstantStd140 {
     _padding2: [u8; 4],
 
     tip_color: Vec3,
-    // The total size of the block must be a multiple of 16.
-    // We are at offset 32 + 12 = 44. The next multiple of 16 is 48.
-    // So we need 4 final bytes of padding.
-    _padding3: [u8; 4],
+    // A scalar packs right after a `vec3`, at offset 32 + 12 = 44.
+    lod_fade: f32,
+
+    // The band the flora instances fade across one by one, see `LodThresholds`, unused while
+    // `lod_transition_range` is 0.0. Four scalars from offset 48 bring the block to 64 bytes.
+    lod_switch_size: f32,
+    lod_transition_range: f32,
+    /// The projected size of a chunk at a distance of one world unit.
+    lod_size_scale: f32,
+    is_far_lod: u32,
 }
 
 impl PushConstantStd140 {
     pub fn new(time: f32, bottom_color: Vec3, tip_color: Vec3) -> Self {
         Self {
             time,
-            _padding1: [0; 12],
+            wind_sway: 1.0,
+            density: 1.0,
+            _padding1: [0; 4],
             bottom_color,
             _padding2: [0; 4],
             tip_color,
-            _padding3: [0; 4],
+            lod_fade: 1.0,
+            lod_switch_size: 0.0,
+            lod_transition_range: 0.0,
+            lod_size_scale: 0.0,
+            is_far_lod: 0,
         }
     }
+
+    /// Scales the wind offset of the vertices, 1.0 is the sway of grass.
+    pub fn with_wind_sway(mut self, wind_sway: f32) -> Self {
+        self.wind_sway = wind_sway;
+        self
+    }
+
+    /// Only this fraction of the instances is drawn, the same ones at any density.
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// Only this fraction of the pixels is drawn, dithered against the other LOD.
+    pub fn with_lod_fade(mut self, lod_fade: f32) -> Self {
+        self.lod_fade = lod_fade;
+        self
+    }
+
+    /// Lets each instance work out its own fade inside the transition band of `thresholds`,
+    /// `size_scale` is the [`projected_size_scale`] of the chunks.
+    pub fn with_lod_band(
+        mut self,
+        thresholds: &LodThresholds,
+        size_scale: f32,
+        lod_state: LodState,
+    ) -> Self {
+        self.lod_switch_size = thresholds.switch_size;
+        self.lod_transition_range = thresholds.transition_range;
+        self.lod_size_scale = size_scale;
+        self.is_far_lod = (lod_state == LodState::Lod1) as u32;
+        self
+    }
+}
+
+/// Mirrors the push constants of `impostor_bake.vert`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct ImpostorBakePushConstant {
+    view_proj_mat: [f32; 16],
+    density: f32,
 }
 
+/// Relative to the project root, one file per device.
+const WORKGROUP_SIZE_CACHE_DIR: &str = ".cache/workgroup_sizes/";
+
+/// Timed passes per frame, see `Tracer::gpu_pass_timings`.
+const GPU_PROFILER_MAX_SCOPES: u32 = 16;
+
+/// Rings `Tracer::find_safe_spawn` searches around the requested position.
+const SAFE_SPAWN_RING_COUNT: u32 = 10;
+/// Distance between the search rings, in world units.
+const SAFE_SPAWN_RING_SPACING: f32 = 0.05;
+/// Ground steeper than this, as the up component of its normal, isn't spawned on.
+const SAFE_SPAWN_MIN_NORMAL_Y: f32 = 0.7;
+/// Headroom above the standing camera height, so the first physics step doesn't start in the
+/// ground, in world units.
+const SAFE_SPAWN_CLEARANCE: f32 = 0.02;
+
+/// Ambient light of a full moon high in the night sky.
+const MOONLIGHT_AMBIENT: Vec3 = Vec3::new(0.06, 0.08, 0.14);
+
+/// Horizontal speed of the precipitation in a storm, in world units per second.
+const MAX_WIND_SPEED: f32 = 0.3;
+/// The leaf density moves in steps this fine, so a season passing slowly doesn't bake the
+/// impostors again every frame.
+const LEAF_DENSITY_STEPS: f32 = 32.0;
+
 pub struct TracerDesc {
     pub scaling_factor: f32,
+    pub pass_scales: PassScales,
+    /// Has to match what was passed to [`DenoiserPrecision::define_shader_macros`].
+    pub denoiser_precision: DenoiserPrecision,
+    /// Benchmark workgroup sizes of [`TUNED_PIPELINES`] missing from the per-device cache once the
+    /// first frame has been rendered. Cached sizes are used either way.
+    pub autotune_workgroup_sizes: bool,
+    /// Every frame in flight gets its own copy of the buffers written from the CPU, see
+    /// [`Tracer::select_frame_slot`].
+    pub frames_in_flight: usize,
+}
+
+/// Resolutions of the secondary passes relative to the render extent, the passes run at their
+/// own resolution and are upsampled against the depth in the composition.
+///
+/// Ambient occlusion and indirect light are traced together with the primary rays in the tracer
+/// pass, so they follow `TracerDesc::scaling_factor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassScales {
+    pub god_ray: f32,
+}
+
+impl Default for PassScales {
+    fn default() -> Self {
+        Self { god_ray: 1.0 }
+    }
+}
+
+impl PassScales {
+    pub fn god_ray_extent(&self, render_extent: Extent2D) -> Extent2D {
+        scale_extent(render_extent, self.god_ray)
+    }
+
+    /// Returns whether a scale changed, the textures have to be recreated then.
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.add(
+            egui::Slider::new(&mut self.god_ray, 0.25..=1.0)
+                .step_by(0.25)
+                .text("God Ray Resolution"),
+        )
+        .on_hover_text("Relative to the render resolution")
+        .changed()
+    }
+}
+
+/// At least one texel along each axis.
+fn scale_extent(extent: Extent2D, scale: f32) -> Extent2D {
+    Extent2D::new(
+        ((extent.width as f32 * scale) as u32).max(1),
+        ((extent.height as f32 * scale) as u32).max(1),
+    )
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LodState {
     Lod0,
     Lod1,
+    /// Only trees, their leaves baked into a quad, see [`ImpostorAtlas`].
+    Impostor,
+}
+
+/// Tunable parameters of the player collider pass, written into `player_collider_info`.
+#[derive(Debug, Clone)]
+pub struct PlayerColliderDesc {
+    /// Number of horizontal probe rays, the first one always points forward.
+    pub ring_count: u32,
+    /// Maximum length of the probe rays, ring distances are clamped to this.
+    pub ring_radius: f32,
+    /// Draws the ring samples through the debug draw overlay.
+    pub show_rings: bool,
+}
+
+impl Default for PlayerColliderDesc {
+    fn default() -> Self {
+        Self {
+            ring_count: MAX_PLAYER_COLLIDER_RING_COUNT,
+            ring_radius: 2.0,
+            show_rings: false,
+        }
+    }
+}
+
+impl PlayerColliderDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            egui::Slider::new(&mut self.ring_count, 2..=MAX_PLAYER_COLLIDER_RING_COUNT)
+                .text("Ring Count"),
+        );
+        ui.add(egui::Slider::new(&mut self.ring_radius, 0.05..=2.0).text("Ring Radius"));
+        ui.add(egui::Checkbox::new(&mut self.show_rings, "Show Rings"));
+    }
+}
+
+/// Shadow biases, too little of them shows acne and too much detaches the shadows from their
+/// casters. How much is needed depends on the scale of the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowBiasDesc {
+    /// Constant depth bias of the leaves shadow pass, in depth buffer units.
+    pub depth_bias_constant: f32,
+    /// Depth bias of the leaves shadow pass per unit of depth slope, grows on leaves seen edge-on
+    /// from the sun.
+    pub depth_bias_slope: f32,
+    /// How far the tracer moves a point along its normal before looking it up in the shadow map,
+    /// in world units.
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowBiasDesc {
+    fn default() -> Self {
+        Self {
+            depth_bias_constant: 0.0,
+            depth_bias_slope: 0.0,
+            normal_offset: 0.0,
+        }
+    }
+}
+
+impl ShadowBiasDesc {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            egui::Slider::new(&mut self.depth_bias_constant, 0.0..=16.0).text("Leaf Depth Bias"),
+        );
+        ui.add(egui::Slider::new(&mut self.depth_bias_slope, 0.0..=8.0).text("Leaf Slope Bias"));
+        ui.add(
+            egui::Slider::new(&mut self.normal_offset, 0.0..=0.02)
+                .text("Normal Offset")
+                .step_by(0.0005),
+        );
+        if ui.button("Reset Biases").clicked() {
+            *self = Self::default();
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct PlayerCollisionResult {
     pub ground_distance: f32,
+    /// Free space above the camera, used to decide whether the player can stand up again.
+    pub ceiling_distance: f32,
+    /// What the player stands on, `None` without ground in range.
+    pub ground_material: Option<VoxelMaterial>,
     pub ring_distances: Vec<f32>,
 }
 
+/// The instances of one flora draw, drawn in one call.
+#[derive(Debug, Clone, Copy)]
+enum FloraBatch {
+    /// Back to front, for the alpha blended mode.
+    Sorted(SortedFloraBatch),
+    /// Only the instances in view, for the alpha tested mode.
+    Culled(CulledFloraBatch),
+}
+
+/// What part of the temporal history is stale.
+#[derive(Debug, Clone)]
+enum HistoryInvalidation {
+    FullScreen,
+    /// In world units.
+    Region(Aabb3),
+}
+
 pub struct Tracer {
     vulkan_ctx: VulkanContext,
 
@@ -106,23 +393,90 @@ pub struct Tracer {
 
     allocator: Allocator,
     resources: TracerResources,
+    /// One per frame in flight.
+    frame_resources: Vec<FrameResources>,
+    /// The slot of the frame being recorded, see [`Self::select_frame_slot`].
+    frame_slot: usize,
 
     camera: Camera,
     camera_view_mat_prev_frame: Mat4,
     camera_proj_mat_prev_frame: Mat4,
     current_view_proj_mat: Mat4,
     current_shadow_view_proj_mat: Mat4,
+    /// The shadow camera, kept to fill every frame slot's copy while a cached shadow map is
+    /// reused.
+    shadow_view_mat: Mat4,
+    shadow_proj_mat: Mat4,
+    /// The camera the Hi-Z pyramid was built with, `None` until it is built for the current
+    /// extent.
+    hiz_view_proj_mat: Option<Mat4>,
+
+    chunk_cull_cache: FrustumCullCache,
+    tree_cull_cache: FrustumCullCache,
+    /// The same boxes as `tree_cull_cache`, culled against the shadow camera.
+    shadow_tree_cull_cache: FrustumCullCache,
+    /// Tree ids in the order their AABBs were given to `tree_cull_cache` and
+    /// `shadow_tree_cull_cache`, `None` when the trees changed since.
+    tree_cull_ids: Option<Vec<u32>>,
+    chunk_lod_selector: LodSelector<(UVec3, FloraType)>,
+    tree_lod_selector: LodSelector<u32>,
+    /// Between the far LOD of the leaves and the impostors.
+    tree_impostor_lod_selector: LodSelector<u32>,
+    impostor_atlas: ImpostorAtlas,
+    /// Fraction of the leaf instances drawn, see [`Self::set_leaf_density`].
+    leaf_density: f32,
+    /// Consumed by the next `update_buffers`.
+    pending_history_invalidation: Option<HistoryInvalidation>,
+    sky_visibility: SkyVisibilityMap,
+    shadow_cache: ShadowCache,
+    sky_cache: SkyCache,
+    dynamic_resolution: DynamicResolution,
+    /// Set by `update_world_clock`.
+    night_sky: NightSky,
+    /// The share of the ambient light the sun gives, set by `update_world_clock`.
+    ambient_scale: f32,
+    /// Set by `update_weather`.
+    weather: Weather,
+    /// The wind of the [`WindField`] where the camera is, gusts included, set by
+    /// `update_wind_field`. The precipitation around the camera drifts with it.
+    wind_at_camera: Vec2,
+    /// In world units, set by `set_sea_level`.
+    sea_level: f32,
+    shadow_bias: ShadowBiasDesc,
+    /// Only while the shadow map is rendered every frame, a cached one is reused from other
+    /// views.
+    is_shadow_receiver_culling_enabled: bool,
+    shadow_cull_stats: ShadowCullStats,
 
     compute_pipelines: ComputePipelines,
     graphics_pipelines: GraphicsPipelines,
+    flora_sorter: FloraSorter,
+    flora_culler: FloraCuller,
+    scene_ray_queue: SceneRayQueue,
+    streamed_textures: StreamedTextures,
+    gpu_profiler: GpuProfiler,
 
-    render_target_color_and_depth: RenderTarget,
-    render_target_depth_only: RenderTarget,
+    shader_modules: ShaderModules,
+    workgroup_size_cache: WorkgroupSizeCache,
+    is_workgroup_autotune_pending: bool,
+
+    raster_targets: RasterTargets,
 
     #[allow(dead_code)]
     pool: DescriptorPool,
+    /// Holds the descriptor sets of the pipelines recreated by the autotuner.
+    #[allow(dead_code)]
+    tuned_pool: Option<DescriptorPool>,
+    /// Hold the descriptor sets of the frame slots past the first, one pool per slot.
+    #[allow(dead_code)]
+    frame_slot_pools: Vec<DescriptorPool>,
 
     a_trous_iteration_count: u32,
+    player_collider_ring_count: u32,
+    /// Set by `update_buffers`.
+    is_fog_enabled: bool,
+    /// Set by `update_buffers`.
+    is_dof_enabled: bool,
     spatial_sound_manager: SpatialSoundManager,
 }
 
@@ -158,139 +512,288 @@ impl Tracer {
 
         let pool = DescriptorPool::new(vulkan_ctx.device()).unwrap();
 
+        let sky_visibility = SkyVisibilityMap::new(&chunk_bound);
+        let sky_visibility_extent =
+            Extent2D::new(sky_visibility.extent().x, sky_visibility.extent().y);
+        let path_wear_extent = PathWearMap::extent_of(&chunk_bound);
+
         let shader_modules = PipelineBuilder::create_shader_modules(&vulkan_ctx, shader_compiler)?;
 
         let resources = TracerResources::new(
             &vulkan_ctx,
             allocator.clone(),
-            &shader_modules.tracer_sm,
-            &shader_modules.tracer_shadow_sm,
-            &shader_modules.composition_sm,
-            &shader_modules.temporal_sm,
-            &shader_modules.spatial_sm,
-            &shader_modules.taa_sm,
-            &shader_modules.god_ray_sm,
-            &shader_modules.post_processing_sm,
-            &shader_modules.player_collider_sm,
             &shader_modules.terrain_query_sm,
             render_extent,
             screen_extent,
+            desc.pass_scales,
             Extent2D::new(1024, 1024),
-            1000, // max_terrain_queries
+            sky_visibility_extent,
+            MAX_TERRAIN_QUERIES,
+            desc.denoiser_precision,
+        );
+        let frame_resources = (0..desc.frames_in_flight)
+            .map(|_| {
+                FrameResources::new(
+                    &vulkan_ctx,
+                    allocator.clone(),
+                    &shader_modules,
+                    sky_visibility_extent,
+                    Extent2D::new(path_wear_extent.x, path_wear_extent.y),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let workgroup_size_cache = WorkgroupSizeCache::load(
+            &vulkan_ctx,
+            full_path_from_relative(WORKGROUP_SIZE_CACHE_DIR),
         );
+        let is_workgroup_autotune_pending = desc.autotune_workgroup_sizes
+            && TUNED_PIPELINES.iter().any(|name| {
+                shader_modules.is_tunable(name) && workgroup_size_cache.get(name).is_none()
+            });
+
+        let streamed_textures = StreamedTextures::new(vulkan_ctx.clone(), allocator.clone())?;
 
         let compute_pipelines = PipelineBuilder::create_compute_pipelines(
             &vulkan_ctx,
             &shader_modules,
             &pool,
             &resources,
+            &frame_resources[0],
+            &streamed_textures.resources,
             contree_builder_resources,
             scene_accel_resources,
+            &workgroup_size_cache,
         );
 
-        let render_passes = PipelineBuilder::create_render_passes(
-            &vulkan_ctx,
-            resources.extent_dependent_resources.gfx_output_tex.clone(),
-            resources.extent_dependent_resources.gfx_depth_tex.clone(),
-            resources.shadow_map_tex.clone(),
-        );
+        let raster_targets = RasterTargets::new(&vulkan_ctx, &resources);
 
         let graphics_pipelines = PipelineBuilder::create_graphics_pipelines(
             &vulkan_ctx,
             &shader_modules,
-            &render_passes,
+            &raster_targets,
             &pool,
             &resources,
+            &frame_resources[0],
         );
-
-        let framebuffer_color_and_depth = Self::create_framebuffer_color_and_depth(
-            &vulkan_ctx,
-            &render_passes.render_pass_color_and_depth,
-            &resources.extent_dependent_resources.gfx_output_tex,
-            &resources.extent_dependent_resources.gfx_depth_tex,
-        );
-        let framebuffer_depth_only = Self::create_framebuffer_depth(
-            &vulkan_ctx,
-            &render_passes.render_pass_depth,
-            &resources.shadow_map_tex,
-        );
-
-        let render_target_color_and_depth = RenderTarget::new(
-            render_passes.render_pass_color_and_depth,
-            vec![framebuffer_color_and_depth],
-        );
-        let render_target_depth_only = RenderTarget::new(
-            render_passes.render_pass_depth,
-            vec![framebuffer_depth_only],
+        // the other frame slots get their own copy of every descriptor set, bound by
+        // `update_sets`
+        let frame_slot_pools = (1..desc.frames_in_flight)
+            .map(|_| {
+                let slot_pool = DescriptorPool::new(vulkan_ctx.device())?;
+                for ppl in compute_pipelines.all() {
+                    ppl.add_frame_slot(&slot_pool)?;
+                }
+                for ppl in graphics_pipelines.all() {
+                    ppl.add_frame_slot(&slot_pool)?;
+                }
+                Ok(slot_pool)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let flora_sorter =
+            FloraSorter::new(vulkan_ctx.clone(), allocator.clone(), shader_compiler)?;
+        // every chunk holds one slice per flora type
+        let chunk_extent = chunk_bound.get_extent();
+        let flora_culler = FloraCuller::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            shader_compiler,
+            chunk_extent.width
+                * chunk_extent.height
+                * chunk_extent.depth
+                * FloraType::all().count() as u32,
+            desc.frames_in_flight,
+        )?;
+        let scene_ray_queue = SceneRayQueue::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_modules.terrain_query_sm,
+            contree_builder_resources,
+            scene_accel_resources,
+            MAX_TERRAIN_QUERIES,
         );
+        let gpu_profiler =
+            GpuProfiler::new(&vulkan_ctx, GPU_PROFILER_MAX_SCOPES, desc.frames_in_flight)?;
+        let dynamic_resolution = DynamicResolution::new(desc.scaling_factor);
 
-        Ok(Self {
+        let mut tracer = Self {
             vulkan_ctx,
             desc,
             chunk_bound,
             allocator,
             resources,
+            frame_resources,
+            frame_slot: 0,
             camera,
             camera_view_mat_prev_frame: Mat4::IDENTITY,
             camera_proj_mat_prev_frame: Mat4::IDENTITY,
             current_view_proj_mat: Mat4::IDENTITY,
+            hiz_view_proj_mat: None,
             current_shadow_view_proj_mat: Mat4::IDENTITY,
+            shadow_view_mat: Mat4::IDENTITY,
+            shadow_proj_mat: Mat4::IDENTITY,
+            chunk_cull_cache: FrustumCullCache::default(),
+            tree_cull_cache: FrustumCullCache::default(),
+            shadow_tree_cull_cache: FrustumCullCache::default(),
+            tree_cull_ids: None,
+            chunk_lod_selector: LodSelector::default(),
+            tree_lod_selector: LodSelector::default(),
+            tree_impostor_lod_selector: LodSelector::default(),
+            impostor_atlas: ImpostorAtlas::new(),
+            leaf_density: 1.0,
+            pending_history_invalidation: None,
+            sky_visibility,
+            shadow_cache: ShadowCache::new(),
+            sky_cache: SkyCache::new(),
+            dynamic_resolution,
+            night_sky: NightSky {
+                star_visibility: 0.0,
+                moon_glow: 0.0,
+            },
+            ambient_scale: 1.0,
+            weather: Weather::default(),
+            wind_at_camera: Vec2::ZERO,
+            sea_level: 0.0,
+            shadow_bias: ShadowBiasDesc::default(),
+            is_shadow_receiver_culling_enabled: false,
+            shadow_cull_stats: ShadowCullStats::default(),
             compute_pipelines,
             graphics_pipelines,
-            render_target_color_and_depth,
-            render_target_depth_only,
+            flora_sorter,
+            flora_culler,
+            scene_ray_queue,
+            streamed_textures,
+            gpu_profiler,
+            shader_modules,
+            workgroup_size_cache,
+            is_workgroup_autotune_pending,
+            raster_targets,
             pool,
+            tuned_pool: None,
+            frame_slot_pools,
             a_trous_iteration_count: 3,
+            player_collider_ring_count: MAX_PLAYER_COLLIDER_RING_COUNT,
+            is_fog_enabled: false,
+            is_dof_enabled: false,
             spatial_sound_manager,
-        })
+        };
+        tracer.update_sets(contree_builder_resources, scene_accel_resources);
+        Ok(tracer)
     }
 
-    /// A framebuffer that contains the color and depth textures for the main render pass
-    fn create_framebuffer_color_and_depth(
-        vulkan_ctx: &VulkanContext,
-        render_pass: &RenderPass,
-        target_texture: &Texture,
-        depth_texture: &Texture,
-    ) -> Framebuffer {
-        let target_view = target_texture.get_image_view().as_raw();
-        let depth_image_view = depth_texture.get_image_view().as_raw();
+    /// Makes the frame recorded next use the buffers and descriptor sets of `slot`, and the
+    /// readbacks see what the frame last recorded with it computed. The GPU must be done with
+    /// that frame.
+    pub fn select_frame_slot(&mut self, slot: usize) {
+        self.frame_slot = slot;
+        for ppl in self.compute_pipelines.all() {
+            ppl.select_frame_slot(slot);
+        }
+        for ppl in self.graphics_pipelines.all() {
+            ppl.select_frame_slot(slot);
+        }
+        self.flora_culler.select_frame_slot(slot);
+        self.gpu_profiler.select_frame_slot(slot);
+    }
+
+    /// Benchmarks the workgroup sizes of the tuned pipelines that aren't cached for this device yet,
+    /// stores the winners and recreates those pipelines with them. Does nothing once done.
+    ///
+    /// Has to run after a frame has completed, so the scene is populated and every texture the
+    /// pipelines touch is in its steady state layout.
+    pub fn run_pending_workgroup_autotune(
+        &mut self,
+        contree_builder_resources: &ContreeBuilderResources,
+        scene_accel_resources: &SceneAccelBuilderResources,
+    ) -> Result<()> {
+        if !self.is_workgroup_autotune_pending {
+            return Ok(());
+        }
+        self.is_workgroup_autotune_pending = false;
+        // the benchmarks share the textures with the frames in flight, and those still use the
+        // pipelines replaced below
+        self.vulkan_ctx.device().wait_idle();
 
-        let target_image_extent = target_texture
+        let extent = self
+            .resources
+            .extent_dependent_resources
+            .compute_output_tex
             .get_image()
             .get_desc()
-            .extent
-            .as_extent_2d()
-            .unwrap();
+            .extent;
 
-        Framebuffer::new(
-            vulkan_ctx.clone(),
-            render_pass,
-            &[target_view, depth_image_view],
-            target_image_extent,
-        )
-        .unwrap()
-    }
+        for name in TUNED_PIPELINES {
+            if !self.shader_modules.is_tunable(name)
+                || self.workgroup_size_cache.get(name).is_some()
+            {
+                continue;
+            }
+            // candidates can't free their descriptor sets, so they get a pool of their own
+            let candidate_pool = DescriptorPool::new(self.vulkan_ctx.device())?;
+            let workgroup_size = find_fastest_workgroup_size(
+                &self.vulkan_ctx,
+                |workgroup_size| {
+                    PipelineBuilder::create_tunable_compute_pipeline(
+                        &self.vulkan_ctx,
+                        &self.shader_modules,
+                        name,
+                        &candidate_pool,
+                        &self.resources,
+                        &self.frame_resources[0],
+                        contree_builder_resources,
+                        scene_accel_resources,
+                        Some(workgroup_size),
+                    )
+                },
+                |cmdbuf, ppl| {
+                    // the first a-trous iteration stands in for the others
+                    let push_constants = (name == "spatial").then_some(0u32.to_ne_bytes());
+                    ppl.record(
+                        cmdbuf,
+                        extent,
+                        push_constants.as_ref().map(|p| p.as_slice()),
+                    );
+                },
+            );
+            log::info!("Autotuned workgroup size of {}: {:?}", name, workgroup_size);
+            self.workgroup_size_cache.insert(name, workgroup_size);
+        }
 
-    /// A framebuffer that contains the shadow map texture
-    fn create_framebuffer_depth(
-        vulkan_ctx: &VulkanContext,
-        render_pass: &RenderPass,
-        shadow_map_tex: &Texture,
-    ) -> Framebuffer {
-        let shadow_image_view = shadow_map_tex.get_image_view().as_raw();
-        let shadow_image_extent = shadow_map_tex
-            .get_image()
-            .get_desc()
-            .extent
-            .as_extent_2d()
-            .unwrap();
-        Framebuffer::new(
-            vulkan_ctx.clone(),
-            render_pass,
-            &[shadow_image_view],
-            shadow_image_extent,
-        )
-        .unwrap()
+        if let Err(e) = self.workgroup_size_cache.save() {
+            log::warn!("Failed to save the workgroup size cache: {}", e);
+        }
+
+        let tuned_pool = DescriptorPool::new(self.vulkan_ctx.device())?;
+        let create_tuned_ppl = |name: &str| -> Result<ComputePipeline> {
+            let ppl = PipelineBuilder::create_tunable_compute_pipeline(
+                &self.vulkan_ctx,
+                &self.shader_modules,
+                name,
+                &tuned_pool,
+                &self.resources,
+                &self.frame_resources[0],
+                contree_builder_resources,
+                scene_accel_resources,
+                self.workgroup_size_cache.get(name),
+            );
+            for _ in 1..self.frame_resources.len() {
+                ppl.add_frame_slot(&tuned_pool)?;
+            }
+            Ok(ppl)
+        };
+        let tracer_ppl = create_tuned_ppl("tracer")?;
+        let god_ray_ppl = create_tuned_ppl("god_ray")?;
+        let temporal_ppl = create_tuned_ppl("temporal")?;
+        let spatial_ppl = create_tuned_ppl("spatial")?;
+
+        self.compute_pipelines.tracer_ppl = tracer_ppl;
+        self.compute_pipelines.god_ray_ppl = god_ray_ppl;
+        self.compute_pipelines.temporal_ppl = temporal_ppl;
+        self.compute_pipelines.spatial_ppl = spatial_ppl;
+        self.tuned_pool = Some(tuned_pool);
+        // binds the other frame slots of the new pipelines
+        self.update_sets(contree_builder_resources, scene_accel_resources);
+        Ok(())
     }
 
     pub fn on_resize(
@@ -309,32 +812,19 @@ impl Tracer {
             self.allocator.clone(),
             render_extent,
             screen_extent,
+            self.desc.pass_scales,
         );
 
-        let framebuffer_color_and_depth = Self::create_framebuffer_color_and_depth(
-            &self.vulkan_ctx,
-            self.render_target_color_and_depth.get_render_pass(),
-            &self.resources.extent_dependent_resources.gfx_output_tex,
-            &self.resources.extent_dependent_resources.gfx_depth_tex,
-        );
-        let framebuffer_depth_only = Self::create_framebuffer_depth(
-            &self.vulkan_ctx,
-            self.render_target_depth_only.get_render_pass(),
-            &self.resources.shadow_map_tex,
-        );
-
-        self.render_target_color_and_depth = RenderTarget::new(
-            self.render_target_color_and_depth.get_render_pass().clone(),
-            vec![framebuffer_color_and_depth],
-        );
-        self.render_target_depth_only = RenderTarget::new(
-            self.render_target_depth_only.get_render_pass().clone(),
-            vec![framebuffer_depth_only],
-        );
+        self.raster_targets
+            .on_resize(&self.vulkan_ctx, &self.resources);
+        // the new pyramid holds nothing yet
+        self.hiz_view_proj_mat = None;
 
         self.update_sets(contree_builder_resources, scene_accel_resources);
     }
 
+    /// Binds the resources to the descriptor sets of every frame slot, none of them may be in
+    /// flight.
     fn update_sets(
         &mut self,
         contree_builder_resources: &ContreeBuilderResources,
@@ -348,39 +838,70 @@ impl Tracer {
             ppl.auto_update_descriptor_sets(resources).unwrap()
         };
 
-        // pipelines that need all resources (tracer, scene_accel, contree)
-        let all_resources = &[
-            &self.resources as &dyn ResourceContainer,
-            contree_builder_resources as &dyn ResourceContainer,
-            scene_accel_resources as &dyn ResourceContainer,
-        ];
-        update_compute_fn(&self.compute_pipelines.tracer_ppl, all_resources);
-        update_compute_fn(&self.compute_pipelines.tracer_shadow_ppl, all_resources);
-        update_compute_fn(&self.compute_pipelines.player_collider_ppl, all_resources);
-        update_compute_fn(&self.compute_pipelines.terrain_query_ppl, all_resources);
-
-        // pipelines that only need tracer resources
-        let tracer_resources = &[&self.resources as &dyn ResourceContainer];
-        update_compute_fn(&self.compute_pipelines.vsm_creation_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.vsm_blur_h_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.vsm_blur_v_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.god_ray_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.temporal_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.spatial_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.composition_ppl, tracer_resources);
-        update_compute_fn(&self.compute_pipelines.taa_ppl, tracer_resources);
-        update_compute_fn(
-            &self.compute_pipelines.post_processing_ppl,
-            tracer_resources,
-        );
+        for slot in 0..self.frame_resources.len() {
+            for ppl in self.compute_pipelines.all() {
+                ppl.select_frame_slot(slot);
+            }
+            for ppl in self.graphics_pipelines.all() {
+                ppl.select_frame_slot(slot);
+            }
+            let frame_resources = &self.frame_resources[slot];
+
+            // pipelines that need all resources (tracer, scene_accel, contree)
+            let all_resources = &[
+                &self.resources as &dyn ResourceContainer,
+                frame_resources as &dyn ResourceContainer,
+                contree_builder_resources as &dyn ResourceContainer,
+                scene_accel_resources as &dyn ResourceContainer,
+            ];
+            update_compute_fn(&self.compute_pipelines.tracer_ppl, all_resources);
+            update_compute_fn(&self.compute_pipelines.tracer_shadow_ppl, all_resources);
+            update_compute_fn(&self.compute_pipelines.player_collider_ppl, all_resources);
+            update_compute_fn(&self.compute_pipelines.terrain_query_ppl, all_resources);
+            update_compute_fn(&self.compute_pipelines.sky_visibility_ppl, all_resources);
+            update_compute_fn(&self.compute_pipelines.water_ppl, all_resources);
+
+            // pipelines that only need tracer resources
+            let tracer_resources = &[
+                &self.resources as &dyn ResourceContainer,
+                frame_resources as &dyn ResourceContainer,
+            ];
+            update_compute_fn(&self.compute_pipelines.vsm_creation_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.vsm_blur_h_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.vsm_blur_v_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.god_ray_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.temporal_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.spatial_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.composition_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.hiz_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.fog_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.sky_map_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.taa_ppl, tracer_resources);
+            update_compute_fn(&self.compute_pipelines.dof_ppl, tracer_resources);
+            update_compute_fn(
+                &self.compute_pipelines.post_processing_ppl,
+                &[
+                    &self.resources as &dyn ResourceContainer,
+                    frame_resources,
+                    &self.streamed_textures.resources,
+                ],
+            );
 
-        // update graphics pipelines descriptor sets
-        update_graphics_fn(&self.graphics_pipelines.flora_ppl, tracer_resources);
-        update_graphics_fn(&self.graphics_pipelines.flora_lod_ppl, tracer_resources);
-        update_graphics_fn(
-            &self.graphics_pipelines.leaves_shadow_lod_ppl,
-            tracer_resources,
-        );
+            // update graphics pipelines descriptor sets
+            for ppl in self.graphics_pipelines.all() {
+                update_graphics_fn(ppl, tracer_resources);
+            }
+        }
+        self.scene_ray_queue
+            .update_sets(contree_builder_resources, scene_accel_resources);
+
+        let frame_slot = self.frame_slot;
+        for ppl in self.compute_pipelines.all() {
+            ppl.select_frame_slot(frame_slot);
+        }
+        for ppl in self.graphics_pipelines.all() {
+            ppl.select_frame_slot(frame_slot);
+        }
     }
 
     // create a lower resolution texture for rendering, for better performance,
@@ -392,156 +913,232 @@ impl Tracer {
         )
     }
 
+    /// The render extent relative to the screen extent, moved by the dynamic resolution.
+    pub fn scaling_factor(&self) -> f32 {
+        self.desc.scaling_factor
+    }
+
+    /// Feeds the GPU time of the last frame to the dynamic resolution, call once per frame.
+    ///
+    /// Returns whether the scaling factor changed, it takes effect with the next `on_resize`,
+    /// which recreates the textures.
+    pub fn update_dynamic_resolution(&mut self, settings: &DynamicResolutionSettings) -> bool {
+        let gpu_ms = self
+            .gpu_profiler
+            .is_enabled
+            .then(|| self.gpu_profiler.timings().iter().map(|t| t.last_ms).sum())
+            .filter(|ms: &f32| *ms > 0.0);
+        let Some(scaling_factor) =
+            self.dynamic_resolution
+                .update(settings, self.desc.scaling_factor, gpu_ms)
+        else {
+            return false;
+        };
+        log::debug!(
+            "Render scale {:.2} -> {:.2}",
+            self.desc.scaling_factor,
+            scaling_factor
+        );
+        self.desc.scaling_factor = scaling_factor;
+        // the history doesn't line up with the new pixels
+        self.invalidate_history(None);
+        true
+    }
+
+    pub fn pass_scales(&self) -> PassScales {
+        self.desc.pass_scales
+    }
+
+    /// Takes effect with the next `on_resize`, which recreates the textures.
+    pub fn set_pass_scales(&mut self, pass_scales: PassScales) {
+        self.desc.pass_scales = pass_scales;
+    }
+
     pub fn get_screen_output_tex(&self) -> &Texture {
         &self.resources.extent_dependent_resources.screen_output_tex
     }
 
-    #[allow(clippy::too_many_arguments)]
+    /// Writes the post-processed frame to a PNG at `path`, the GUI isn't part of it.
+    pub fn capture_screenshot(&self, path: &Path) -> Result<()> {
+        // the frames in flight may still write the output
+        self.vulkan_ctx.device().wait_idle();
+        let image = self.get_screen_output_tex().get_image();
+        let extent = image.get_desc().extent;
+        let rgba = image.fetch_data(
+            &self.vulkan_ctx.get_general_queue(),
+            self.vulkan_ctx.command_pool(),
+        )?;
+        let Some(screenshot) = image::RgbaImage::from_raw(extent.width, extent.height, rgba) else {
+            bail!(
+                "The screen output doesn't match its {}x{} extent",
+                extent.width,
+                extent.height
+            );
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        screenshot.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+
+    /// Moves the sun to where `clock` puts it and takes the stars, the moonlight and the strength
+    /// of the ambient light from it, call before `update_buffers`.
+    pub fn update_world_clock(&mut self, clock: &WorldClock, sun: &mut SunSettings) {
+        (sun.altitude, sun.azimuth) = clock.sun_position();
+        self.night_sky = NightSky {
+            star_visibility: clock.star_visibility(),
+            moon_glow: clock.moon_glow(),
+        };
+        self.ambient_scale = clock.ambient_scale();
+    }
+
+    /// Takes the precipitation, the wetness and the wind from `weather`, call before
+    /// `update_buffers`.
+    pub fn update_weather(&mut self, weather: &Weather) {
+        self.weather = weather.clone();
+    }
+
+    /// The height the terrain was flooded to, see `TerrainGenDesc::sea_level`.
+    pub fn set_sea_level(&mut self, sea_level: f32) {
+        self.sea_level = sea_level;
+    }
+
     pub fn update_buffers(
         &mut self,
         time_info: &TimeInfo,
-        debug_float: f32,
-        debug_bool: bool,
-        debug_uint: u32,
-        sun_dir: Vec3,
-        sun_size: f32,
-        sun_color: Vec3,
-        sun_luminance: f32,
-        sun_altitude: f32,
-        sun_azimuth: f32,
-        ambient_light: Vec3,
-        temporal_position_phi: f32,
-        temporal_alpha: f32,
-        phi_c: f32,
-        phi_n: f32,
-        phi_p: f32,
-        min_phi_z: f32,
-        max_phi_z: f32,
-        phi_z_stable_sample_count: f32,
-        is_changing_lum_phi: bool,
-        is_spatial_denoising_enabled: bool,
-        a_trous_iteration_count: u32,
-        is_taa_enabled: bool,
-        god_ray_max_depth: f32,
-        god_ray_max_checks: u32,
-        god_ray_weight: f32,
-        god_ray_color: Vec3,
-        starlight_iterations: i32,
-        starlight_formuparam: f32,
-        starlight_volsteps: i32,
-        starlight_stepsize: f32,
-        starlight_zoom: f32,
-        starlight_tile: f32,
-        starlight_speed: f32,
-        starlight_brightness: f32,
-        starlight_darkmatter: f32,
-        starlight_distfading: f32,
-        starlight_saturation: f32,
-        voxel_sand_color: Vec3,
-        voxel_dirt_color: Vec3,
-        voxel_rock_color: Vec3,
-        voxel_leaf_color: Vec3,
-        voxel_trunk_color: Vec3,
+        settings: &TracerSettings,
     ) -> Result<()> {
+        profile_scope!("update_buffers");
+        let history_invalid_rect = self.take_history_invalid_rect();
+        let frame_resources = &mut self.frame_resources[self.frame_slot];
         // camera info
         let view_mat = self.camera.get_view_mat();
         let proj_mat = self.camera.get_proj_mat();
         self.current_view_proj_mat = proj_mat * view_mat;
-        BufferUpdater::update_camera_info(&mut self.resources.camera_info, view_mat, proj_mat)?;
+        BufferUpdater::update_camera_info(&mut frame_resources.camera_info, view_mat, proj_mat)?;
 
-        // shadow cam info
-        let world_bound = self.chunk_bound.into();
-        let (shadow_view_mat, shadow_proj_mat) =
-            calculate_directional_light_matrices(world_bound, sun_dir);
-        self.current_shadow_view_proj_mat = shadow_proj_mat * shadow_view_mat;
+        if settings.shadow_bias != self.shadow_bias {
+            self.shadow_bias = settings.shadow_bias;
+            self.shadow_cache.mark_dirty();
+        }
+
+        self.is_shadow_receiver_culling_enabled = !settings.is_shadow_caching_enabled;
+
+        // shadow cam info, a cached shadow map keeps the camera it was rendered with
+        let sun_dir = settings.sun.sun_dir();
+        if let Some(shadow_sun_dir) = self
+            .shadow_cache
+            .update(sun_dir, settings.is_shadow_caching_enabled)
+        {
+            let world_bound = self.chunk_bound.into();
+            let (shadow_view_mat, shadow_proj_mat) =
+                calculate_directional_light_matrices(world_bound, shadow_sun_dir);
+            self.current_shadow_view_proj_mat = shadow_proj_mat * shadow_view_mat;
+            self.shadow_view_mat = shadow_view_mat;
+            self.shadow_proj_mat = shadow_proj_mat;
+        }
+        // the other frame slots may hold an older camera
         BufferUpdater::update_camera_info(
-            &mut self.resources.shadow_camera_info,
-            shadow_view_mat,
-            shadow_proj_mat,
+            &mut frame_resources.shadow_camera_info,
+            self.shadow_view_mat,
+            self.shadow_proj_mat,
         )?;
 
         // camera info prev frame
         BufferUpdater::update_camera_info(
-            &mut self.resources.camera_info_prev_frame,
+            &mut frame_resources.camera_info_prev_frame,
             self.camera_view_mat_prev_frame,
             self.camera_proj_mat_prev_frame,
         )?;
 
-        BufferUpdater::update_taa_info(&self.resources, is_taa_enabled)?;
+        BufferUpdater::update_taa_info(
+            frame_resources,
+            settings.is_taa_enabled,
+            history_invalid_rect,
+        )?;
 
-        BufferUpdater::update_god_ray_info(
-            &self.resources,
-            god_ray_max_depth,
-            god_ray_max_checks,
-            god_ray_weight,
-            god_ray_color,
+        BufferUpdater::update_god_ray_info(frame_resources, &settings.god_ray)?;
+        BufferUpdater::update_fog_info(frame_resources, &settings.fog, self.ambient_scale)?;
+        BufferUpdater::update_water_info(
+            frame_resources,
+            &settings.water,
+            self.sea_level,
+            time_info.time_since_start(),
+        )?;
+        self.is_fog_enabled = settings.fog.is_enabled;
+        BufferUpdater::update_dof_info(frame_resources, &settings.dof)?;
+        self.is_dof_enabled = settings.dof.is_enabled;
+
+        let is_lens_wet =
+            settings.rain_lens.is_streaks_enabled || settings.rain_lens.is_lens_wetness_enabled;
+        if is_lens_wet
+            && self.weather.precipitation_kind == PrecipitationKind::Rain
+            && self.weather.precipitation() > 0.0
+        {
+            self.streamed_textures.request_rain_drops();
+        }
+
+        BufferUpdater::update_post_processing_info(
+            frame_resources,
+            self.desc.scaling_factor,
+            time_info.time_since_start(),
+            self.camera.position(),
+            &settings.rain_lens,
+            self.is_dof_enabled,
         )?;
 
-        BufferUpdater::update_post_processing_info(&self.resources, self.desc.scaling_factor)?;
+        let wind_velocity = self.wind_at_camera * MAX_WIND_SPEED;
+        BufferUpdater::update_weather_info(
+            frame_resources,
+            &self.weather,
+            Vec3::new(wind_velocity.x, 0.0, wind_velocity.y),
+            time_info.time_since_start(),
+        )?;
 
         BufferUpdater::update_player_collider_info(
-            &self.resources,
+            frame_resources,
             self.camera.position(),
             self.camera.front(),
+            &settings.player_collider,
         )?;
+        self.player_collider_ring_count = settings.player_collider.ring_count;
 
-        BufferUpdater::update_voxel_colors(
-            &self.resources,
-            voxel_sand_color,
-            voxel_dirt_color,
-            voxel_rock_color,
-            voxel_leaf_color,
-            voxel_trunk_color,
-        )?;
+        BufferUpdater::update_voxel_colors(frame_resources, &settings.voxel_colors)?;
 
-        BufferUpdater::update_gui_input(&self.resources, debug_float, debug_bool, debug_uint)?;
+        BufferUpdater::update_gui_input(frame_resources, &settings.debug)?;
 
-        BufferUpdater::update_sun_info(
-            &self.resources,
-            sun_dir,
-            sun_size,
-            sun_color,
-            sun_luminance,
-            sun_altitude,
-            sun_azimuth,
+        BufferUpdater::update_sun_info(frame_resources, &settings.sun)?;
+
+        let ambient_light = settings.sun.ambient_light_vec3() * self.ambient_scale
+            + MOONLIGHT_AMBIENT * self.night_sky.moon_glow;
+        BufferUpdater::update_shading_info(
+            frame_resources,
+            ambient_light,
+            self.shadow_bias.normal_offset,
         )?;
 
-        BufferUpdater::update_shading_info(&self.resources, ambient_light)?;
+        BufferUpdater::update_starlight_info(frame_resources, &settings.starlight)?;
 
-        BufferUpdater::update_starlight_info(
-            &self.resources,
-            starlight_iterations,
-            starlight_formuparam,
-            starlight_volsteps,
-            starlight_stepsize,
-            starlight_zoom,
-            starlight_tile,
-            starlight_speed,
-            starlight_brightness,
-            starlight_darkmatter,
-            starlight_distfading,
-            starlight_saturation,
-        )?;
+        if let Some(band) = self.sky_cache.update(
+            sun_dir,
+            settings.sun.azimuth,
+            self.night_sky,
+            &settings.starlight,
+        ) {
+            BufferUpdater::update_sky_map_info(frame_resources, &band)?;
+        }
 
-        BufferUpdater::update_env_info(&self.resources, time_info.total_frame_count() as u32)?;
+        BufferUpdater::update_env_info(frame_resources, time_info.total_frame_count() as u32)?;
 
         BufferUpdater::update_denoiser_info(
-            &mut self.resources.denoiser_resources.temporal_info,
-            &mut self.resources.denoiser_resources.spatial_info,
-            temporal_position_phi,
-            temporal_alpha,
-            phi_c,
-            phi_n,
-            phi_p,
-            min_phi_z,
-            max_phi_z,
-            phi_z_stable_sample_count,
-            is_changing_lum_phi,
-            is_spatial_denoising_enabled,
+            &mut frame_resources.temporal_info,
+            &mut frame_resources.spatial_info,
+            &settings.denoiser,
+            history_invalid_rect,
         )?;
 
-        // Update the a_trous_iteration_count field
-        self.a_trous_iteration_count = a_trous_iteration_count;
+        self.a_trous_iteration_count = settings.denoiser.spatial.a_trous_iteration_count;
 
         self.camera_view_mat_prev_frame = self.camera.get_view_mat();
         self.camera_proj_mat_prev_frame = self.camera.get_proj_mat();
@@ -549,88 +1146,224 @@ impl Tracer {
         Ok(())
     }
 
-    /// Returns a list of chunks that need to be drawn this frame.
+    /// Returns the chunks that need to be drawn this frame, by flora type and LOD. A chunk holding
+    /// instances inside the transition band is listed under both LODs.
     fn chunks_needs_to_draw_this_frame<'a>(
-        &self,
+        &mut self,
         surface_resources: &'a SurfaceResources,
-        lod_distance: f32,
-    ) -> HashMap<LodState, Vec<&'a FloraInstanceResources>> {
-        let mut lod0_instances = Vec::new();
-        let mut lod1_instances = Vec::new();
+        flora_lod_desc: &FloraLodDesc,
+    ) -> HashMap<(FloraType, LodState), Vec<&'a FloraInstanceResources>> {
+        let mut result: HashMap<_, Vec<_>> = HashMap::new();
+        for flora_type in FloraType::all() {
+            for lod_state in [LodState::Lod0, LodState::Lod1] {
+                result.insert((flora_type, lod_state), Vec::new());
+            }
+        }
         let camera_pos = self.camera.position();
+        let proj_mat = self.camera.get_proj_mat();
+
+        let chunk_flora_instances = &surface_resources.instances.chunk_flora_instances;
+        if self.chunk_cull_cache.aabb_count() != chunk_flora_instances.len() {
+            self.chunk_cull_cache
+                .set_aabbs(chunk_flora_instances.iter().map(|(aabb, _)| aabb));
+        }
+        let visibility = self.chunk_cull_cache.visibility(self.current_view_proj_mat);
 
-        for (aabb, instances) in &surface_resources.instances.chunk_flora_instances {
-            // perform frustum culling
-            if !aabb.is_inside_frustum(self.current_view_proj_mat) {
+        for ((aabb, instances), is_visible) in chunk_flora_instances.iter().zip(visibility) {
+            if !is_visible {
                 continue;
             }
 
-            // calculate distance from camera to chunk center
-            let chunk_center = aabb.center();
-            let distance = (camera_pos - chunk_center).length();
-
-            if distance <= lod_distance {
-                lod0_instances.push(instances);
-            } else {
-                lod1_instances.push(instances);
+            let size = projected_size(aabb, camera_pos, &proj_mat);
+            let (nearest_size, farthest_size) = projected_size_range(aabb, camera_pos, &proj_mat);
+            for (flora_type, thresholds) in FloraType::all().zip(&flora_lod_desc.flora) {
+                let key = (instances.chunk_id, flora_type);
+                let (is_near, is_far) = if thresholds.transition_range > 0.0 {
+                    // the instances fade on their own, see `instance_lod_fade`, the chunk goes to
+                    // every LOD one of them has a share in
+                    self.chunk_lod_selector.remove(&key);
+                    (
+                        thresholds.far_share(nearest_size) < 1.0,
+                        thresholds.far_share(farthest_size) > 0.0,
+                    )
+                } else {
+                    let is_far = self.chunk_lod_selector.select(key, size, thresholds) > 0.0;
+                    (!is_far, is_far)
+                };
+                for (lod_state, is_drawn) in [(LodState::Lod0, is_near), (LodState::Lod1, is_far)] {
+                    if is_drawn {
+                        result
+                            .get_mut(&(flora_type, lod_state))
+                            .unwrap()
+                            .push(instances);
+                    }
+                }
             }
         }
-
-        let mut result = HashMap::new();
-        result.insert(LodState::Lod0, lod0_instances);
-        result.insert(LodState::Lod1, lod1_instances);
         result
     }
 
+    /// Trees inside a transition band are listed under both LODs, each with the fraction of
+    /// pixels it draws. Only trees with a baked impostor get past their far LOD.
     fn trees_needs_to_draw_this_frame<'a>(
-        &self,
+        &mut self,
         surface_resources: &'a SurfaceResources,
-        lod_distance: f32,
-    ) -> HashMap<LodState, Vec<&'a TreeLeavesInstance>> {
+        flora_lod_desc: &FloraLodDesc,
+    ) -> HashMap<LodState, Vec<(&'a TreeLeavesInstance, f32)>> {
         let mut lod0_instances = Vec::new();
         let mut lod1_instances = Vec::new();
+        let mut impostor_instances = Vec::new();
         let camera_pos = self.camera.position();
+        let proj_mat = self.camera.get_proj_mat();
 
-        for tree_instance in surface_resources.instances.leaves_instances.values() {
-            // perform frustum culling
-            if !tree_instance
-                .aabb
-                .is_inside_frustum(self.current_view_proj_mat)
-            {
+        let leaves_instances = &surface_resources.instances.leaves_instances;
+        let tree_ids = self.take_tree_cull_ids(leaves_instances);
+        let visibility = self.tree_cull_cache.visibility(self.current_view_proj_mat);
+
+        for (tree_id, is_visible) in tree_ids.iter().zip(visibility) {
+            let Some(tree_instance) = leaves_instances.get(tree_id) else {
+                continue;
+            };
+            if !is_visible {
                 continue;
             }
 
-            // calculate distance from camera to tree center
-            let tree_center = tree_instance.aabb.center();
-            let distance = (camera_pos - tree_center).length();
-
-            if distance <= lod_distance {
-                lod0_instances.push(tree_instance);
+            let size = projected_size(&tree_instance.aabb, camera_pos, &proj_mat);
+            let transition = self
+                .tree_lod_selector
+                .select(*tree_id, size, &flora_lod_desc.leaves);
+            // the impostor only takes over from a tree all in its far LOD
+            let has_impostor = self.impostor_atlas.get(*tree_id).is_some();
+            let impostor_transition = if transition >= 1.0 && has_impostor {
+                self.tree_impostor_lod_selector
+                    .select(*tree_id, size, &flora_lod_desc.impostors)
             } else {
-                lod1_instances.push(tree_instance);
+                self.tree_impostor_lod_selector.remove(tree_id);
+                0.0
+            };
+            if transition < 1.0 {
+                lod0_instances.push((tree_instance, 1.0 - transition));
+            }
+            if transition > 0.0 && impostor_transition < 1.0 {
+                lod1_instances.push((tree_instance, transition - impostor_transition));
+            }
+            if impostor_transition > 0.0 {
+                impostor_instances.push((tree_instance, impostor_transition));
             }
         }
+        self.tree_cull_ids = Some(tree_ids);
 
         let mut result = HashMap::new();
         result.insert(LodState::Lod0, lod0_instances);
         result.insert(LodState::Lod1, lod1_instances);
+        result.insert(LodState::Impostor, impostor_instances);
         result
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn record_trace(
+    /// Put them back into `tree_cull_ids` after use.
+    fn take_tree_cull_ids(
         &mut self,
-        cmdbuf: &CommandBuffer,
-        surface_resources: &SurfaceResources,
-        lod_distance: f32,
-        time: f32,
-        grass_bottom_color: Vec3,
-        grass_tip_color: Vec3,
-        lavender_bottom_color: Vec3,
-        lavender_tip_color: Vec3,
-        leaf_bottom_color: Vec3,
+        leaves_instances: &HashMap<u32, TreeLeavesInstance>,
+    ) -> Vec<u32> {
+        match self.tree_cull_ids.take() {
+            Some(tree_ids) if tree_ids.len() == leaves_instances.len() => tree_ids,
+            _ => {
+                let tree_ids: Vec<u32> = leaves_instances.keys().copied().collect();
+                for cull_cache in [&mut self.tree_cull_cache, &mut self.shadow_tree_cull_cache] {
+                    cull_cache.set_aabbs(tree_ids.iter().map(|id| &leaves_instances[id].aabb));
+                }
+                tree_ids
+            }
+        }
+    }
+
+    /// The trees drawn into the shadow map, the ones outside of the shadow camera are culled and,
+    /// while receiver culling is on, the ones whose shadow can't land in the main view.
+    fn trees_casting_shadows_this_frame<'a>(
+        &mut self,
+        surface_resources: &'a SurfaceResources,
+    ) -> Vec<&'a TreeLeavesInstance> {
+        let leaves_instances = &surface_resources.instances.leaves_instances;
+        let tree_ids = self.take_tree_cull_ids(leaves_instances);
+        let light_visibility = self
+            .shadow_tree_cull_cache
+            .visibility(self.current_shadow_view_proj_mat);
+
+        let view_frustum = Frustum::from_view_proj(self.current_view_proj_mat);
+        let receiver_sun_dir = self
+            .shadow_cache
+            .sun_dir()
+            .filter(|_| self.is_shadow_receiver_culling_enabled);
+        let world_bound: Aabb3 = self.chunk_bound.into();
+        let max_shadow_length = world_bound.dimensions().length();
+
+        let mut stats = ShadowCullStats::default();
+        let mut instances = Vec::new();
+        for (tree_id, is_lit) in tree_ids.iter().zip(light_visibility) {
+            let Some(tree_instance) = leaves_instances.get(tree_id) else {
+                continue;
+            };
+            if !is_lit {
+                stats.light_culled += 1;
+                continue;
+            }
+            if let Some(sun_dir) = receiver_sun_dir {
+                let receiver_bound = shadow_receiver_bound(
+                    &tree_instance.aabb,
+                    sun_dir,
+                    world_bound.min().y,
+                    max_shadow_length,
+                );
+                if !view_frustum.intersects_aabb(&receiver_bound) {
+                    stats.receiver_culled += 1;
+                    continue;
+                }
+            }
+            stats.drawn += 1;
+            instances.push(tree_instance);
+        }
+        self.tree_cull_ids = Some(tree_ids);
+        self.shadow_cull_stats = stats;
+        instances
+    }
+
+    /// Trees the last rendered shadow map drew and culled.
+    pub fn shadow_cull_stats(&self) -> ShadowCullStats {
+        self.shadow_cull_stats
+    }
+
+    /// GPU time of the passes of `record_trace`, named after the passes of the frame graph.
+    pub fn gpu_pass_timings(&self) -> &[GpuPassTiming] {
+        self.gpu_profiler.timings()
+    }
+
+    pub fn is_gpu_profiling_enabled(&self) -> bool {
+        self.gpu_profiler.is_enabled
+    }
+
+    pub fn set_gpu_profiling_enabled(&mut self, is_enabled: bool) {
+        self.gpu_profiler.is_enabled = is_enabled;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_trace(
+        &mut self,
+        cmdbuf: &CommandBuffer,
+        surface_resources: &SurfaceResources,
+        flora_lod_desc: &FloraLodDesc,
+        flora_blend_mode: FloraBlendMode,
+        time: f32,
+        flora_colors: &[(Vec3, Vec3)],
+        leaf_bottom_color: Vec3,
         leaf_tip_color: Vec3,
     ) -> Result<()> {
+        profile_scope!("record_trace");
+        // the other frame slots may still bind a mip that was evicted since
+        self.streamed_textures.update()?;
+        self.compute_pipelines
+            .post_processing_ppl
+            .update_matching_descriptor_sets(&[&self.streamed_textures.resources])?;
+
         let shader_access_memory_barrier = MemoryBarrier::new_shader_access();
         let compute_to_compute_barrier = PipelineBarrier::new(
             vk::PipelineStageFlags::COMPUTE_SHADER,
@@ -643,26 +1376,36 @@ impl Tracer {
             vec![shader_access_memory_barrier],
         );
 
+        self.swap_history()?;
+        self.gpu_profiler.begin_frame(cmdbuf);
+        self.record_sky_visibility_pass(cmdbuf)?;
+        self.record_sky_map_pass(cmdbuf);
         self.record_clear_render_targets(cmdbuf);
 
-        self.record_leaves_shadow_lod_pass(
-            cmdbuf,
-            surface_resources,
-            leaf_bottom_color,
-            leaf_tip_color,
-            time,
-        );
-        let frag_to_compute_barrier = PipelineBarrier::new(
-            vk::PipelineStageFlags::FRAGMENT_SHADER,
-            vk::PipelineStageFlags::COMPUTE_SHADER,
-            vec![shader_access_memory_barrier],
-        );
-        frag_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        if self.shadow_cache.is_rendering() {
+            self.gpu_profiler.begin_scope(cmdbuf, "shadow");
+            let shadow_casters = self.trees_casting_shadows_this_frame(surface_resources);
+            self.record_leaves_shadow_lod_pass(
+                cmdbuf,
+                surface_resources,
+                &shadow_casters,
+                leaf_bottom_color,
+                leaf_tip_color,
+                time,
+            );
+            let frag_to_compute_barrier = PipelineBarrier::new(
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vec![shader_access_memory_barrier],
+            );
+            frag_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
 
-        self.record_tracer_shadow_pass(cmdbuf);
-        compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
-        self.record_vsm_filtering_pass(cmdbuf);
-        compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            self.record_tracer_shadow_pass(cmdbuf);
+            compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            self.record_vsm_filtering_pass(cmdbuf);
+            compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            self.gpu_profiler.end_scope(cmdbuf);
+        }
 
         let b1 = PipelineBarrier::new(
             vk::PipelineStageFlags::COMPUTE_SHADER,
@@ -671,51 +1414,48 @@ impl Tracer {
         );
         b1.record_insert(self.vulkan_ctx.device(), cmdbuf);
 
-        let chunks_by_lod = self.chunks_needs_to_draw_this_frame(surface_resources, lod_distance);
-        self.record_flora_pass(
-            cmdbuf,
-            &chunks_by_lod[&LodState::Lod0],
-            LodState::Lod0,
-            FloraType::Grass,
-            grass_bottom_color,
-            grass_tip_color,
-            time,
-        );
-        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
-        self.record_flora_pass(
-            cmdbuf,
-            &chunks_by_lod[&LodState::Lod1],
-            LodState::Lod1,
-            FloraType::Grass,
-            grass_bottom_color,
-            grass_tip_color,
-            time,
-        );
-        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
-        self.record_flora_pass(
-            cmdbuf,
-            &chunks_by_lod[&LodState::Lod0],
-            LodState::Lod0,
-            FloraType::Lavender,
-            lavender_bottom_color,
-            lavender_tip_color,
-            time,
-        );
-        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
-        self.record_flora_pass(
-            cmdbuf,
-            &chunks_by_lod[&LodState::Lod1],
-            LodState::Lod1,
-            FloraType::Lavender,
-            lavender_bottom_color,
-            lavender_tip_color,
-            time,
-        );
-        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.begin_scope(cmdbuf, "flora");
+        let chunks_by_lod = self.chunks_needs_to_draw_this_frame(surface_resources, flora_lod_desc);
+        let flora_batches = match flora_blend_mode {
+            FloraBlendMode::AlphaTest => {
+                self.record_flora_cull(cmdbuf, &surface_resources.instances.pool, &chunks_by_lod)?
+            }
+            FloraBlendMode::AlphaBlend => {
+                self.record_flora_sort(cmdbuf, &surface_resources.instances.pool, &chunks_by_lod)?
+            }
+        };
+        // every chunk has the same size, the first one stands for all of them
+        let flora_lod_size_scale = surface_resources
+            .instances
+            .chunk_flora_instances
+            .first()
+            .map_or(0.0, |(aabb, _)| {
+                projected_size_scale(aabb, &self.camera.get_proj_mat())
+            });
+        // flora_colors holds the (bottom, tip) color of each flora type
+        for (flora_type, &(bottom_color, tip_color)) in FloraType::all().zip(flora_colors) {
+            for lod_state in [LodState::Lod0, LodState::Lod1] {
+                self.record_flora_pass(
+                    cmdbuf,
+                    flora_batches.get(&(flora_type, lod_state)).copied(),
+                    lod_state,
+                    flora_type,
+                    &flora_lod_desc.flora[flora_type.index()],
+                    flora_lod_size_scale,
+                    bottom_color,
+                    tip_color,
+                    time,
+                );
+                frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            }
+        }
+        self.gpu_profiler.end_scope(cmdbuf);
 
-        let trees_by_lod = self.trees_needs_to_draw_this_frame(surface_resources, lod_distance);
+        self.gpu_profiler.begin_scope(cmdbuf, "leaves");
+        let trees_by_lod = self.trees_needs_to_draw_this_frame(surface_resources, flora_lod_desc);
         self.record_leaves_pass(
             cmdbuf,
+            &surface_resources.instances.pool,
             &trees_by_lod[&LodState::Lod0],
             LodState::Lod0,
             leaf_bottom_color,
@@ -725,17 +1465,38 @@ impl Tracer {
         frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
         self.record_leaves_pass(
             cmdbuf,
+            &surface_resources.instances.pool,
             &trees_by_lod[&LodState::Lod1],
             LodState::Lod1,
             leaf_bottom_color,
             leaf_tip_color,
             time,
         );
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        self.gpu_profiler.begin_scope(cmdbuf, "impostor");
+        self.record_impostor_bake_pass(cmdbuf, surface_resources);
+        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.record_impostor_pass(
+            cmdbuf,
+            &trees_by_lod[&LodState::Impostor],
+            leaf_bottom_color,
+            leaf_tip_color,
+            time,
+        )?;
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        self.gpu_profiler.begin_scope(cmdbuf, "weather");
+        frag_to_vert_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.record_weather_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
 
         record_denoiser_resources_transition_barrier(&self.resources.denoiser_resources, cmdbuf);
 
+        self.gpu_profiler.begin_scope(cmdbuf, "tracer");
         self.record_tracer_pass(cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
 
         let b2 = PipelineBarrier::new(
             vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
@@ -744,21 +1505,55 @@ impl Tracer {
         );
         b2.record_insert(self.vulkan_ctx.device(), cmdbuf);
 
+        self.gpu_profiler.begin_scope(cmdbuf, "hiz");
+        self.record_hiz_pass(cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        self.gpu_profiler.begin_scope(cmdbuf, "god_ray");
         self.record_god_ray_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
 
+        self.gpu_profiler.begin_scope(cmdbuf, "denoiser");
         self.record_denoiser_pass(cmdbuf, self.a_trous_iteration_count)?;
+        compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
 
+        self.gpu_profiler.begin_scope(cmdbuf, "water");
+        self.record_water_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        self.gpu_profiler.begin_scope(cmdbuf, "composition");
         self.record_composition_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        if self.is_fog_enabled {
+            self.gpu_profiler.begin_scope(cmdbuf, "fog");
+            self.record_fog_pass(cmdbuf);
+            compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            self.gpu_profiler.end_scope(cmdbuf);
+        }
+
+        self.gpu_profiler.begin_scope(cmdbuf, "taa");
         self.record_taa_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        if self.is_dof_enabled {
+            self.gpu_profiler.begin_scope(cmdbuf, "dof");
+            self.record_dof_pass(cmdbuf);
+            compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+            self.gpu_profiler.end_scope(cmdbuf);
+        }
+
+        self.gpu_profiler.begin_scope(cmdbuf, "post_processing");
         self.record_post_processing_pass(cmdbuf);
         compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        self.gpu_profiler.end_scope(cmdbuf);
         self.record_player_collider_pass(cmdbuf);
-
-        copy_current_to_prev(&self.resources, cmdbuf);
+        self.scene_ray_queue.record(cmdbuf);
 
         return Ok(());
 
@@ -770,54 +1565,64 @@ impl Tracer {
                 tex.get_image()
                     .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
             };
-            tr_fn(&denoiser_resources.tex.denoiser_normal_tex);
-            tr_fn(&denoiser_resources.tex.denoiser_normal_tex_prev);
-            tr_fn(&denoiser_resources.tex.denoiser_position_tex);
-            tr_fn(&denoiser_resources.tex.denoiser_position_tex_prev);
-            tr_fn(&denoiser_resources.tex.denoiser_vox_id_tex);
-            tr_fn(&denoiser_resources.tex.denoiser_vox_id_tex_prev);
-            tr_fn(&denoiser_resources.tex.denoiser_accumed_tex);
-            tr_fn(&denoiser_resources.tex.denoiser_accumed_tex_prev);
+            for history in [
+                &denoiser_resources.tex.denoiser_normal_tex,
+                &denoiser_resources.tex.denoiser_position_tex,
+                &denoiser_resources.tex.denoiser_vox_id_tex,
+                &denoiser_resources.tex.denoiser_accumed_tex,
+            ] {
+                history.textures().iter().for_each(tr_fn);
+            }
             tr_fn(&denoiser_resources.tex.denoiser_motion_tex);
             tr_fn(&denoiser_resources.tex.denoiser_temporal_hist_len_tex);
             tr_fn(&denoiser_resources.tex.denoiser_hit_tex);
             tr_fn(&denoiser_resources.tex.denoiser_spatial_ping_tex);
             tr_fn(&denoiser_resources.tex.denoiser_spatial_pong_tex);
         }
+    }
 
-        fn copy_current_to_prev(resources: &TracerResources, cmdbuf: &CommandBuffer) {
-            let copy_fn = |src_tex: &Texture, dst_tex: &Texture| {
-                src_tex.get_image().record_copy_to(
-                    cmdbuf,
-                    dst_tex.get_image(),
-                    vk::ImageLayout::GENERAL,
-                    vk::ImageLayout::GENERAL,
-                );
-            };
-            copy_fn(
-                &resources.denoiser_resources.tex.denoiser_normal_tex,
-                &resources.denoiser_resources.tex.denoiser_normal_tex_prev,
-            );
-            copy_fn(
-                &resources.denoiser_resources.tex.denoiser_position_tex,
-                &resources.denoiser_resources.tex.denoiser_position_tex_prev,
-            );
-            copy_fn(
-                &resources.denoiser_resources.tex.denoiser_vox_id_tex,
-                &resources.denoiser_resources.tex.denoiser_vox_id_tex_prev,
-            );
-            copy_fn(
-                &resources.denoiser_resources.tex.denoiser_accumed_tex,
-                &resources.denoiser_resources.tex.denoiser_accumed_tex_prev,
-            );
-            copy_fn(
-                &resources.extent_dependent_resources.taa_tex,
-                &resources.extent_dependent_resources.taa_tex_prev,
-            );
+    /// Makes last frame's outputs this frame's history, must run before any pass is recorded
+    /// since the descriptor sets are rewritten.
+    fn swap_history(&mut self) -> Result<()> {
+        let denoiser_tex = &mut self.resources.denoiser_resources.tex;
+        let histories = [
+            &mut denoiser_tex.denoiser_normal_tex,
+            &mut denoiser_tex.denoiser_position_tex,
+            &mut denoiser_tex.denoiser_vox_id_tex,
+            &mut denoiser_tex.denoiser_accumed_tex,
+            &mut self.resources.extent_dependent_resources.taa_tex,
+        ];
+        let mut containers: Vec<&dyn ResourceContainer> = Vec::with_capacity(histories.len());
+        for history in histories {
+            history.swap();
+            containers.push(history);
+        }
+
+        for ppl in [
+            &self.compute_pipelines.tracer_ppl,
+            &self.compute_pipelines.temporal_ppl,
+            &self.compute_pipelines.spatial_ppl,
+            &self.compute_pipelines.taa_ppl,
+            &self.compute_pipelines.dof_ppl,
+            &self.compute_pipelines.post_processing_ppl,
+        ] {
+            ppl.update_matching_descriptor_sets(&containers)?;
         }
+        Ok(())
     }
 
     fn record_clear_render_targets(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_clear_render_targets");
+        // both targets may share memory with textures used later in the previous frame
+        let extent_dependent_resources = &self.resources.extent_dependent_resources;
+        for tex in [
+            &extent_dependent_resources.gfx_output_tex,
+            &extent_dependent_resources.gfx_depth_tex,
+        ] {
+            tex.get_image()
+                .record_discard_barrier(cmdbuf, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        }
+
         self.resources
             .extent_dependent_resources
             .gfx_output_tex
@@ -839,46 +1644,129 @@ impl Tracer {
                 ClearValue::DepthStencil(DepthOrStencilClearValue::Depth(1.0)),
             );
 
-        self.resources.shadow_map_tex.get_image().record_clear(
+        // a cached shadow map is sampled as it is
+        if self.shadow_cache.is_rendering() {
+            self.resources.shadow_map_tex.get_image().record_clear(
+                cmdbuf,
+                Some(vk::ImageLayout::GENERAL),
+                0,
+                ClearValue::DepthStencil(DepthOrStencilClearValue::Depth(1.0)),
+            );
+        }
+    }
+
+    /// Sorts the instances of every flora draw back to front, each draw as one batch.
+    fn record_flora_sort(
+        &self,
+        cmdbuf: &CommandBuffer,
+        instance_pool: &InstancePool,
+        chunks_by_lod: &HashMap<(FloraType, LodState), Vec<&FloraInstanceResources>>,
+    ) -> Result<HashMap<(FloraType, LodState), FloraBatch>> {
+        profile_scope!("record_flora_sort");
+        let mut batches = HashMap::new();
+        let mut first_instance = 0;
+        for (&(flora_type, lod_state), chunks) in chunks_by_lod {
+            let slices: Vec<&InstanceResource> =
+                chunks.iter().map(|chunk| chunk.get(flora_type)).collect();
+            let batch = self.flora_sorter.record_sort(
+                cmdbuf,
+                instance_pool,
+                &slices,
+                self.camera.position(),
+                self.camera.front(),
+                first_instance,
+            )?;
+            if let Some(batch) = batch {
+                first_instance += batch.instance_count;
+                batches.insert((flora_type, lod_state), FloraBatch::Sorted(batch));
+            }
+        }
+        Ok(batches)
+    }
+
+    /// Culls the instances of every flora draw on the GPU, all draws in one dispatch.
+    fn record_flora_cull(
+        &mut self,
+        cmdbuf: &CommandBuffer,
+        instance_pool: &InstancePool,
+        chunks_by_lod: &HashMap<(FloraType, LodState), Vec<&FloraInstanceResources>>,
+    ) -> Result<HashMap<(FloraType, LodState), FloraBatch>> {
+        profile_scope!("record_flora_cull");
+        let keys: Vec<_> = chunks_by_lod.keys().copied().collect();
+        let batch_descs: Vec<_> = keys
+            .iter()
+            .map(|&(flora_type, lod_state)| FloraCullBatchDesc {
+                slices: chunks_by_lod[&(flora_type, lod_state)]
+                    .iter()
+                    .map(|chunk| chunk.get(flora_type))
+                    .collect(),
+                index_count: self.resources.flora_meshes[flora_type.index()].indices_len,
+                wind_sway: flora_type.desc().wind_sway,
+            })
+            .collect();
+        let extent_resources = &self.resources.extent_dependent_resources;
+        let hiz_tex = &extent_resources.hiz_tex;
+        // the pyramid is bound even before it holds anything
+        hiz_tex
+            .get_image()
+            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+        let occlusion = HiZOcclusion {
+            hiz_tex,
+            depth_extent: extent_resources
+                .compute_depth_tex
+                .get_image()
+                .get_desc()
+                .extent
+                .as_extent_2d()
+                .unwrap(),
+            view_proj_mat: self.hiz_view_proj_mat,
+        };
+        let culled = self.flora_culler.record_cull(
             cmdbuf,
-            Some(vk::ImageLayout::GENERAL),
-            0,
-            ClearValue::DepthStencil(DepthOrStencilClearValue::Depth(1.0)),
-        );
+            instance_pool,
+            &batch_descs,
+            self.current_view_proj_mat,
+            &occlusion,
+        )?;
+        Ok(keys
+            .into_iter()
+            .zip(culled)
+            .filter_map(|(key, batch)| Some((key, FloraBatch::Culled(batch?))))
+            .collect())
     }
 
+    /// Draws the instances of `batch` in one call, a sorted batch is blended. Without a batch
+    /// there is nothing to draw.
     #[allow(clippy::too_many_arguments)]
     fn record_flora_pass(
         &self,
         cmdbuf: &CommandBuffer,
-        flora_instances: &[&FloraInstanceResources],
+        batch: Option<FloraBatch>,
         lod_state: LodState,
         flora_type: FloraType,
+        lod_thresholds: &LodThresholds,
+        lod_size_scale: f32,
         bottom_color: Vec3,
         tip_color: Vec3,
         time: f32,
     ) {
-        let pipeline = match lod_state {
-            LodState::Lod0 => &self.graphics_pipelines.flora_ppl,
-            LodState::Lod1 => &self.graphics_pipelines.flora_lod_ppl,
+        profile_scope!("record_flora_pass");
+        let is_sorted = matches!(batch, Some(FloraBatch::Sorted(_)));
+        let pipeline = match (lod_state, is_sorted) {
+            (LodState::Lod0, false) => &self.graphics_pipelines.flora_ppl,
+            (LodState::Lod1, false) => &self.graphics_pipelines.flora_lod_ppl,
+            (LodState::Lod0, true) => &self.graphics_pipelines.flora_blend_ppl,
+            (LodState::Lod1, true) => &self.graphics_pipelines.flora_lod_blend_ppl,
+            (LodState::Impostor, _) => unreachable!("flora has no impostors"),
         };
 
-        let render_target = &self.render_target_color_and_depth;
+        let push_constant = PushConstantStd140::new(time, bottom_color, tip_color)
+            .with_wind_sway(flora_type.desc().wind_sway)
+            .with_lod_band(lod_thresholds, lod_size_scale, lod_state);
 
-        let push_constant = PushConstantStd140::new(time, bottom_color, tip_color);
-
-        let (indices_buf, vertices_buf, indices_len) = match flora_type {
-            FloraType::Grass => (
-                &self.resources.grass_blade_resources.indices,
-                &self.resources.grass_blade_resources.vertices,
-                self.resources.grass_blade_resources.indices_len,
-            ),
-            FloraType::Lavender => (
-                &self.resources.lavender_resources.indices,
-                &self.resources.lavender_resources.vertices,
-                self.resources.lavender_resources.indices_len,
-            ),
-        };
+        let mesh = &self.resources.flora_meshes[flora_type.index()];
+        let (indices_buf, vertices_buf, indices_len) =
+            (&mesh.indices, &mesh.vertices, mesh.indices_len);
 
         pipeline.record_bind(cmdbuf);
 
@@ -896,7 +1784,13 @@ impl Tracer {
             },
         ];
 
-        render_target.record_begin(cmdbuf, &clear_values);
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+            &clear_values,
+        );
 
         let render_extent = self
             .resources
@@ -925,66 +1819,135 @@ impl Tracer {
             );
         }
 
-        for instances in flora_instances {
-            let instances_buf = &instances.get(flora_type).instances_buf;
-            let instances_len = instances.get(flora_type).instances_len;
-
-            // only draw if this chunk actually has grass instances.
-            if instances_len == 0 {
-                continue;
+        let push_constant_info = PushConstantInfo {
+            shader_stage: vk::ShaderStageFlags::VERTEX,
+            push_constants: bytemuck::bytes_of(&push_constant).to_vec(),
+        };
+        // binding point 0: the mesh vertices, binding point 1: the instances of the batch
+        match batch {
+            Some(FloraBatch::Sorted(batch)) => {
+                unsafe {
+                    self.vulkan_ctx.device().cmd_bind_vertex_buffers(
+                        cmdbuf.as_raw(),
+                        0,
+                        &[
+                            vertices_buf.as_raw(),
+                            self.flora_sorter.sorted_instances().as_raw(),
+                        ],
+                        &[0, batch.byte_offset()],
+                    );
+                }
+                pipeline.record_indexed(
+                    cmdbuf,
+                    indices_len,
+                    batch.instance_count,
+                    0,
+                    0,
+                    0,
+                    Some(&push_constant_info),
+                );
             }
-
-            // bind the vertex buffers for this specific chunk.
-            // binding point 0: common grass blade vertices.
-            // binding point 1: per-chunk, per-instance data.
-            unsafe {
-                self.vulkan_ctx.device().cmd_bind_vertex_buffers(
-                    cmdbuf.as_raw(),
-                    0, // firstBinding
-                    &[vertices_buf.as_raw(), instances_buf.as_raw()],
-                    &[0, 0], // offsets
+            Some(FloraBatch::Culled(batch)) => {
+                unsafe {
+                    self.vulkan_ctx.device().cmd_bind_vertex_buffers(
+                        cmdbuf.as_raw(),
+                        0,
+                        &[
+                            vertices_buf.as_raw(),
+                            self.flora_culler.culled_instances().as_raw(),
+                        ],
+                        &[0, batch.byte_offset()],
+                    );
+                }
+                // the instance count was written by the culling
+                pipeline.record_indexed_indirect(
+                    cmdbuf,
+                    self.flora_culler.indirect_commands(),
+                    batch.indirect_offset(),
+                    Some(&push_constant_info),
                 );
             }
+            None => {}
+        }
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+        );
+    }
 
-            // issue the draw call for the current chunk.
-            // no barriers are needed here.
-            pipeline.record_indexed(
-                cmdbuf,
-                indices_len,
-                instances_len,
-                0, // firstIndex
-                0, // vertexOffset
-                0, // firstInstance
-                Some(&PushConstantInfo {
-                    shader_stage: vk::ShaderStageFlags::VERTEX,
-                    push_constants: bytemuck::bytes_of(&push_constant).to_vec(),
-                }),
-            );
+    /// Draws the rain streaks or snowflakes over the flora, more of them the harder it falls.
+    fn record_weather_pass(&self, cmdbuf: &CommandBuffer) {
+        let particle_count =
+            (MAX_WEATHER_PARTICLE_COUNT as f32 * self.weather.precipitation()).round() as u32;
+        if particle_count == 0 {
+            return;
         }
-        render_target.record_end(cmdbuf);
 
-        let desc = render_target.get_desc();
-        self.resources
+        // the attachments are loaded, the clear values are never used
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+            &clear_values,
+        );
+
+        let render_extent = self
+            .resources
             .extent_dependent_resources
             .gfx_output_tex
             .get_image()
-            .set_layout(0, desc.attachments[0].final_layout);
-        self.resources
-            .extent_dependent_resources
-            .gfx_depth_tex
-            .get_image()
-            .set_layout(0, desc.attachments[1].final_layout);
+            .get_desc()
+            .extent;
+        let viewport = Viewport::from_extent(render_extent.as_extent_2d().unwrap());
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D {
+                width: render_extent.width,
+                height: render_extent.height,
+            },
+        };
+
+        let pipeline = &self.graphics_pipelines.weather_ppl;
+        pipeline.record_bind(cmdbuf);
+        pipeline.record_viewport_scissor(cmdbuf, viewport, scissor);
+        // one quad per particle
+        pipeline.record_draw(cmdbuf, 6, particle_count, None);
+
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+        );
     }
 
     fn record_leaves_pass(
         &self,
         cmdbuf: &CommandBuffer,
-        leaves_instances: &[&TreeLeavesInstance],
+        instance_pool: &InstancePool,
+        leaves_instances: &[(&TreeLeavesInstance, f32)],
         lod_state: LodState,
         bottom_color: Vec3,
         tip_color: Vec3,
         time: f32,
     ) {
+        profile_scope!("record_leaves_pass");
         // skip rendering entirely if no leaf instances exist
         if leaves_instances.is_empty() {
             return;
@@ -993,11 +1956,11 @@ impl Tracer {
         let pipeline = match lod_state {
             LodState::Lod0 => &self.graphics_pipelines.flora_ppl,
             LodState::Lod1 => &self.graphics_pipelines.flora_lod_ppl,
+            LodState::Impostor => unreachable!("impostors are drawn by record_impostor_pass"),
         };
 
-        let render_target = &self.render_target_color_and_depth;
-
-        let push_constant = PushConstantStd140::new(time, bottom_color, tip_color);
+        let push_constant =
+            PushConstantStd140::new(time, bottom_color, tip_color).with_density(self.leaf_density);
 
         let (indices_buf, vertices_buf, indices_len) = match lod_state {
             LodState::Lod0 => (
@@ -1005,7 +1968,7 @@ impl Tracer {
                 &self.resources.leaves_resources.vertices,
                 self.resources.leaves_resources.indices_len,
             ),
-            LodState::Lod1 => (
+            LodState::Lod1 | LodState::Impostor => (
                 &self.resources.leaves_resources_lod.indices,
                 &self.resources.leaves_resources_lod.vertices,
                 self.resources.leaves_resources_lod.indices_len,
@@ -1028,7 +1991,13 @@ impl Tracer {
             },
         ];
 
-        render_target.record_begin(cmdbuf, &clear_values);
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+            &clear_values,
+        );
 
         let render_extent = self
             .resources
@@ -1059,21 +2028,19 @@ impl Tracer {
         }
 
         // loop through all tree leaves instances for this LOD level
-        for tree_instance in leaves_instances {
+        for &(tree_instance, lod_fade) in leaves_instances {
             if tree_instance.resources.instances_len == 0 {
                 continue;
             }
+            let push_constant = push_constant.with_lod_fade(lod_fade);
 
             // bind vertex buffers for this instance
             unsafe {
                 self.vulkan_ctx.device().cmd_bind_vertex_buffers(
                     cmdbuf.as_raw(),
                     0,
-                    &[
-                        vertices_buf.as_raw(),
-                        tree_instance.resources.instances_buf.as_raw(),
-                    ],
-                    &[0, 0],
+                    &[vertices_buf.as_raw(), instance_pool.instances_buf.as_raw()],
+                    &[0, tree_instance.resources.byte_offset()],
                 );
             }
 
@@ -1092,34 +2059,288 @@ impl Tracer {
             );
         }
 
-        render_target.record_end(cmdbuf);
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+        );
+    }
 
-        let desc = render_target.get_desc();
-        self.resources
+    /// Bakes a few of the trees waiting for their impostor into their slots of
+    /// `impostor_atlas_tex`.
+    fn record_impostor_bake_pass(
+        &mut self,
+        cmdbuf: &CommandBuffer,
+        surface_resources: &SurfaceResources,
+    ) {
+        profile_scope!("record_impostor_bake_pass");
+        let bakes = self
+            .impostor_atlas
+            .take_pending_bakes(MAX_IMPOSTOR_BAKES_PER_FRAME);
+        if bakes.is_empty() {
+            return;
+        }
+
+        // a slot baked again may still be sampled by the last frame
+        let sample_to_bake_barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vec![],
+        );
+        sample_to_bake_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        for tex in [
+            &self.resources.impostor_atlas_tex,
+            &self.resources.impostor_depth_tex,
+        ] {
+            tex.get_image()
+                .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+        }
+
+        // the slots are cleared one by one below
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::Impostor,
+            &self.resources,
+            &clear_values,
+        );
+
+        let pipeline = &self.graphics_pipelines.impostor_bake_ppl;
+        let leaves_lod = &self.resources.leaves_resources_lod;
+        pipeline.record_bind(cmdbuf);
+        unsafe {
+            self.vulkan_ctx.device().cmd_bind_index_buffer(
+                cmdbuf.as_raw(),
+                leaves_lod.indices.as_raw(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        for (tree_id, impostor) in bakes {
+            let Some(tree_instance) = surface_resources.instances.leaves_instances.get(&tree_id)
+            else {
+                continue;
+            };
+
+            let (offset, size) = impostor.slot_rect();
+            let viewport = Viewport {
+                x: offset.x as f32,
+                y: offset.y as f32,
+                width: size.x as f32,
+                height: size.y as f32,
+                ..Default::default()
+            };
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: offset.x as i32,
+                    y: offset.y as i32,
+                },
+                extent: vk::Extent2D {
+                    width: size.x,
+                    height: size.y,
+                },
+            };
+            pipeline.record_viewport_scissor(cmdbuf, viewport, scissor);
+
+            let clear_attachments = [
+                vk::ClearAttachment {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    color_attachment: 0,
+                    clear_value: clear_values[0],
+                },
+                vk::ClearAttachment {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    color_attachment: 0,
+                    clear_value: clear_values[1],
+                },
+            ];
+            unsafe {
+                self.vulkan_ctx.device().cmd_clear_attachments(
+                    cmdbuf.as_raw(),
+                    &clear_attachments,
+                    &[vk::ClearRect {
+                        rect: scissor,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    }],
+                );
+            }
+            if tree_instance.resources.instances_len == 0 || leaves_lod.indices_len == 0 {
+                continue;
+            }
+
+            unsafe {
+                self.vulkan_ctx.device().cmd_bind_vertex_buffers(
+                    cmdbuf.as_raw(),
+                    0,
+                    &[
+                        leaves_lod.vertices.as_raw(),
+                        surface_resources.instances.pool.instances_buf.as_raw(),
+                    ],
+                    &[0, tree_instance.resources.byte_offset()],
+                );
+            }
+            let push_constant = ImpostorBakePushConstant {
+                view_proj_mat: impostor.bake_view_proj_mat().to_cols_array(),
+                density: self.leaf_density,
+            };
+            pipeline.record_indexed(
+                cmdbuf,
+                leaves_lod.indices_len,
+                tree_instance.resources.instances_len,
+                0,
+                0,
+                0,
+                Some(&PushConstantInfo {
+                    shader_stage: vk::ShaderStageFlags::VERTEX,
+                    push_constants: bytemuck::bytes_of(&push_constant).to_vec(),
+                }),
+            );
+        }
+
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::Impostor,
+            &self.resources,
+        );
+
+        let bake_to_sample_barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            )],
+        );
+        bake_to_sample_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+    }
+
+    /// Draws the trees of `impostor_instances` as one quad each, `(tree, lod_fade)` pairs from the
+    /// impostor bucket of `trees_needs_to_draw_this_frame`.
+    fn record_impostor_pass(
+        &self,
+        cmdbuf: &CommandBuffer,
+        impostor_instances: &[(&TreeLeavesInstance, f32)],
+        bottom_color: Vec3,
+        tip_color: Vec3,
+        time: f32,
+    ) -> Result<()> {
+        profile_scope!("record_impostor_pass");
+        let instances: Vec<ImpostorInstance> = impostor_instances
+            .iter()
+            .filter_map(|&(tree_instance, lod_fade)| {
+                let impostor = self.impostor_atlas.get(tree_instance.tree_id)?;
+                Some(ImpostorInstance {
+                    center: impostor.center.to_array(),
+                    half_size: impostor.half_size,
+                    slot: impostor.slot,
+                    lod_fade,
+                    _padding: [0; 2],
+                })
+            })
+            .collect();
+        if instances.is_empty() {
+            return Ok(());
+        }
+        self.frame_resources[self.frame_slot]
+            .impostor_instances
+            .fill(&instances)?;
+
+        // the attachments are loaded, the clear values are never used
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+            &clear_values,
+        );
+
+        let render_extent = self
+            .resources
             .extent_dependent_resources
             .gfx_output_tex
             .get_image()
-            .set_layout(0, desc.attachments[0].final_layout);
-        self.resources
-            .extent_dependent_resources
-            .gfx_depth_tex
-            .get_image()
-            .set_layout(0, desc.attachments[1].final_layout);
+            .get_desc()
+            .extent;
+        let viewport = Viewport::from_extent(render_extent.as_extent_2d().unwrap());
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D {
+                width: render_extent.width,
+                height: render_extent.height,
+            },
+        };
+
+        let push_constant = PushConstantStd140::new(time, bottom_color, tip_color);
+        let pipeline = &self.graphics_pipelines.impostor_ppl;
+        pipeline.record_bind(cmdbuf);
+        pipeline.record_viewport_scissor(cmdbuf, viewport, scissor);
+        // one quad per tree
+        pipeline.record_draw(
+            cmdbuf,
+            6,
+            instances.len() as u32,
+            Some(&PushConstantInfo {
+                shader_stage: vk::ShaderStageFlags::VERTEX,
+                push_constants: bytemuck::bytes_of(&push_constant).to_vec(),
+            }),
+        );
+
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::ColorAndDepth,
+            &self.resources,
+        );
+        Ok(())
     }
 
     fn record_leaves_shadow_lod_pass(
         &self,
         cmdbuf: &CommandBuffer,
         surface_resources: &SurfaceResources,
+        shadow_casters: &[&TreeLeavesInstance],
         bottom_color: Vec3,
         tip_color: Vec3,
         time: f32,
     ) {
+        profile_scope!("record_leaves_shadow_lod_pass");
         self.graphics_pipelines
             .leaves_shadow_lod_ppl
             .record_bind(cmdbuf);
 
-        let push_constant = PushConstantStd140::new(time, bottom_color, tip_color);
+        let push_constant =
+            PushConstantStd140::new(time, bottom_color, tip_color).with_density(self.leaf_density);
 
         let clear_values = [vk::ClearValue {
             depth_stencil: vk::ClearDepthStencilValue {
@@ -1128,8 +2349,13 @@ impl Tracer {
             },
         }];
 
-        self.render_target_depth_only
-            .record_begin(cmdbuf, &clear_values);
+        self.raster_targets.record_begin(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::DepthOnly,
+            &self.resources,
+            &clear_values,
+        );
 
         let shadow_extent = self.resources.shadow_map_tex.get_image().get_desc().extent;
         let viewport = Viewport::from_extent(shadow_extent.as_extent_2d().unwrap());
@@ -1144,6 +2370,13 @@ impl Tracer {
         self.graphics_pipelines
             .leaves_shadow_lod_ppl
             .record_viewport_scissor(cmdbuf, viewport, scissor);
+        self.graphics_pipelines
+            .leaves_shadow_lod_ppl
+            .record_depth_bias(
+                cmdbuf,
+                self.shadow_bias.depth_bias_constant,
+                self.shadow_bias.depth_bias_slope,
+            );
 
         unsafe {
             self.vulkan_ctx.device().cmd_bind_index_buffer(
@@ -1154,8 +2387,7 @@ impl Tracer {
             );
         }
 
-        // loop through all tree leaves instances
-        for tree_instance in surface_resources.instances.leaves_instances.values() {
+        for tree_instance in shadow_casters {
             if tree_instance.resources.instances_len == 0 {
                 continue;
             }
@@ -1166,9 +2398,9 @@ impl Tracer {
                     0,
                     &[
                         self.resources.leaves_resources_lod.vertices.as_raw(),
-                        tree_instance.resources.instances_buf.as_raw(),
+                        surface_resources.instances.pool.instances_buf.as_raw(),
                     ],
-                    &[0, 0],
+                    &[0, tree_instance.resources.byte_offset()],
                 );
             }
 
@@ -1189,16 +2421,16 @@ impl Tracer {
                 );
         }
 
-        self.render_target_depth_only.record_end(cmdbuf);
-
-        let desc = self.render_target_depth_only.get_desc();
-        self.resources
-            .shadow_map_tex
-            .get_image()
-            .set_layout(0, desc.attachments[0].final_layout);
+        self.raster_targets.record_end(
+            &self.vulkan_ctx,
+            cmdbuf,
+            RasterPass::DepthOnly,
+            &self.resources,
+        );
     }
 
     fn record_tracer_shadow_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_tracer_shadow_pass");
         self.resources
             .shadow_map_tex
             .get_image()
@@ -1211,6 +2443,7 @@ impl Tracer {
     }
 
     fn record_vsm_filtering_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_vsm_filtering_pass");
         // transition shadow map to general
         self.resources
             .shadow_map_tex
@@ -1251,16 +2484,17 @@ impl Tracer {
     }
 
     fn record_tracer_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_tracer_pass");
         self.resources
             .extent_dependent_resources
             .compute_output_tex
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
         self.resources
             .extent_dependent_resources
             .compute_depth_tex
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
 
         self.compute_pipelines.tracer_ppl.record(
             cmdbuf,
@@ -1274,18 +2508,54 @@ impl Tracer {
         );
     }
 
+    /// Builds the depth pyramid the flora of the next frame is occlusion culled with, one level
+    /// after the other.
+    fn record_hiz_pass(&mut self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_hiz_pass");
+        let extent_resources = &self.resources.extent_dependent_resources;
+        extent_resources
+            .hiz_tex
+            .get_image()
+            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+
+        let depth_extent = extent_resources
+            .compute_depth_tex
+            .get_image()
+            .get_desc()
+            .extent
+            .as_extent_2d()
+            .unwrap();
+        let compute_to_compute_barrier = PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vec![MemoryBarrier::new_shader_access()],
+        );
+        for level in 0..hiz_level_count(depth_extent) {
+            let (_, size) = hiz_level_rect(depth_extent, level);
+            self.compute_pipelines.hiz_ppl.record(
+                cmdbuf,
+                Extent3D::new(size.x, size.y, 1),
+                Some(bytemuck::bytes_of(&level)),
+            );
+            compute_to_compute_barrier.record_insert(self.vulkan_ctx.device(), cmdbuf);
+        }
+        self.hiz_view_proj_mat = Some(self.current_view_proj_mat);
+    }
+
     fn record_god_ray_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_god_ray_pass");
         self.resources
             .extent_dependent_resources
             .god_ray_output_tex
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
 
+        // one invocation per god ray texel, which may be fewer than the render extent has
         self.compute_pipelines.god_ray_ppl.record(
             cmdbuf,
             self.resources
                 .extent_dependent_resources
-                .compute_depth_tex
+                .god_ray_output_tex
                 .get_image()
                 .get_desc()
                 .extent,
@@ -1298,6 +2568,7 @@ impl Tracer {
         cmdbuf: &CommandBuffer,
         a_trous_iteration_count: u32,
     ) -> anyhow::Result<()> {
+        profile_scope!("record_denoiser_pass");
         // Validate iteration count - only 1, 3, or 5 are allowed
         if a_trous_iteration_count != 1
             && a_trous_iteration_count != 3
@@ -1344,12 +2615,28 @@ impl Tracer {
         Ok(())
     }
 
+    /// Shades the water surface over the denoised radiance, so its reflections stay sharp.
+    fn record_water_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_water_pass");
+        self.compute_pipelines.water_ppl.record(
+            cmdbuf,
+            self.resources
+                .extent_dependent_resources
+                .compute_output_tex
+                .get_image()
+                .get_desc()
+                .extent,
+            None,
+        );
+    }
+
     fn record_composition_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_composition_pass");
         self.resources
             .extent_dependent_resources
             .composited_tex
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
 
         self.compute_pipelines.composition_ppl.record(
             cmdbuf,
@@ -1363,15 +2650,33 @@ impl Tracer {
         );
     }
 
+    /// Fogs the composited image in place, so it runs before the TAA resolves it.
+    fn record_fog_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_fog_pass");
+        self.compute_pipelines.fog_ppl.record(
+            cmdbuf,
+            self.resources
+                .extent_dependent_resources
+                .composited_tex
+                .get_image()
+                .get_desc()
+                .extent,
+            None,
+        );
+    }
+
     fn record_taa_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_taa_pass");
         self.resources
             .extent_dependent_resources
             .taa_tex
+            .write()
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
         self.resources
             .extent_dependent_resources
-            .taa_tex_prev
+            .taa_tex
+            .read()
             .get_image()
             .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
 
@@ -1380,6 +2685,7 @@ impl Tracer {
             self.resources
                 .extent_dependent_resources
                 .taa_tex
+                .write()
                 .get_image()
                 .get_desc()
                 .extent,
@@ -1387,12 +2693,34 @@ impl Tracer {
         );
     }
 
+    /// Blurs the resolved image into `dof_tex`, which the post processing reads instead.
+    fn record_dof_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_dof_pass");
+        let dof_tex = &self.resources.extent_dependent_resources.dof_tex;
+        dof_tex
+            .get_image()
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
+
+        self.compute_pipelines
+            .dof_ppl
+            .record(cmdbuf, dof_tex.get_image().get_desc().extent, None);
+    }
+
     fn record_post_processing_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_post_processing_pass");
         self.resources
             .extent_dependent_resources
             .screen_output_tex
             .get_image()
-            .record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL);
+            .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
+        if !self.is_dof_enabled {
+            // still bound, so it needs a valid layout even though it isn't read
+            self.resources
+                .extent_dependent_resources
+                .dof_tex
+                .get_image()
+                .record_discard_barrier(cmdbuf, vk::ImageLayout::GENERAL);
+        }
 
         self.compute_pipelines.post_processing_ppl.record(
             cmdbuf,
@@ -1406,7 +2734,68 @@ impl Tracer {
         );
     }
 
+    /// Recomputes the dirty part of the sky visibility map, if any.
+    fn record_sky_visibility_pass(&mut self, cmdbuf: &CommandBuffer) -> Result<()> {
+        profile_scope!("record_sky_visibility_pass");
+        let Some((texel_offset, texel_count)) = self.sky_visibility.take_dirty_texels() else {
+            return Ok(());
+        };
+
+        // just below the top, so the ground rays start inside the scene
+        let scene_top = self.chunk_bound.max().y as f32 - 1.0 / VOXEL_DIM as f32;
+        let frame_resources = &self.frame_resources[self.frame_slot];
+        BufferUpdater::update_sky_visibility_info(
+            frame_resources,
+            texel_offset,
+            texel_count,
+            scene_top,
+        )?;
+        frame_resources
+            .canopy_density
+            .fill(self.sky_visibility.canopy_density())?;
+
+        self.compute_pipelines.sky_visibility_ppl.record(
+            cmdbuf,
+            Extent3D::new(texel_count.x, texel_count.y, 1),
+            None,
+        );
+
+        // read by the tracer and the flora vertex shaders
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::VERTEX_SHADER,
+            vec![MemoryBarrier::new_shader_access()],
+        )
+        .record_insert(self.vulkan_ctx.device(), cmdbuf);
+        Ok(())
+    }
+
+    /// Renders this frame's band of the sky map, if any.
+    fn record_sky_map_pass(&mut self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_sky_map_pass");
+        let Some(band) = self.sky_cache.band() else {
+            return;
+        };
+
+        self.gpu_profiler.begin_scope(cmdbuf, "sky_map");
+        self.compute_pipelines.sky_map_ppl.record(
+            cmdbuf,
+            Extent3D::new(SKY_MAP_EXTENT, band.row_count, 1),
+            None,
+        );
+        self.gpu_profiler.end_scope(cmdbuf);
+
+        // sampled by the composition pass
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vec![MemoryBarrier::new_shader_access()],
+        )
+        .record_insert(self.vulkan_ctx.device(), cmdbuf);
+    }
+
     fn record_player_collider_pass(&self, cmdbuf: &CommandBuffer) {
+        profile_scope!("record_player_collider_pass");
         self.compute_pipelines
             .player_collider_ppl
             .record(cmdbuf, Extent3D::new(1, 1, 1), None);
@@ -1424,68 +2813,302 @@ impl Tracer {
         self.camera.reset_velocity();
     }
 
-    #[allow(dead_code)]
     pub fn camera_vectors(&self) -> &CameraVectors {
         self.camera.vectors()
     }
 
-    pub fn update_camera(&mut self, frame_delta_time: f32, is_fly_mode: bool) {
+    pub fn camera_position(&self) -> Vec3 {
+        self.camera.position()
+    }
+
+    /// Height of the eyes above the ground while standing, in world units.
+    pub fn camera_standing_height(&self) -> f32 {
+        self.camera.standing_height()
+    }
+
+    /// Whether the player stood on the ground in the last walk mode update.
+    pub fn is_player_on_ground(&self) -> bool {
+        self.camera.is_on_ground()
+    }
+
+    /// See [`Camera::set_ground_bare`], call before [`Self::update_camera`].
+    pub fn set_player_ground_bare(&mut self, is_ground_bare: bool) {
+        self.camera.set_ground_bare(is_ground_bare);
+    }
+
+    /// Yaw and pitch of the camera in radians.
+    pub fn camera_orientation(&self) -> (f32, f32) {
+        self.camera.orientation()
+    }
+
+    pub fn set_camera_pose(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        self.camera.set_pose(position, yaw, pitch);
+    }
+
+    /// Makes the denoiser and TAA drop their history for one frame, on the whole screen for
+    /// `None` or where `region` (in world units) lands on screen, so teleports and world edits
+    /// don't leave ghosts behind.
+    pub fn invalidate_history(&mut self, region: Option<Aabb3>) {
+        use HistoryInvalidation::*;
+        self.pending_history_invalidation = match (self.pending_history_invalidation.take(), region)
+        {
+            (Some(FullScreen), _) | (_, None) => Some(FullScreen),
+            (Some(Region(pending)), Some(region)) => Some(Region(pending.union(&region))),
+            (None, Some(region)) => Some(Region(region)),
+        };
+    }
+
+    /// The extent of the sky visibility map in texels and its texels, row major in x.
+    pub fn read_sky_visibility(&self) -> Result<(UVec2, Vec<u8>)> {
+        let image = self.resources.sky_visibility_tex.get_image();
+        let texels = image.fetch_data(
+            &self.vulkan_ctx.get_general_queue(),
+            self.vulkan_ctx.command_pool(),
+        )?;
+        // the sky visibility pass expects the texture in general layout
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| image.record_transition_barrier(cmdbuf, 0, vk::ImageLayout::GENERAL),
+        );
+        Ok((self.sky_visibility.extent(), texels))
+    }
+
+    /// Copies the wind the foliage sways in, call once per frame after the field moved on.
+    pub fn update_wind_field(&mut self, wind_field: &WindField) -> Result<()> {
+        let camera_pos = self.camera.position();
+        self.wind_at_camera = wind_field.wind_at(Vec2::new(camera_pos.x, camera_pos.z));
+        BufferUpdater::update_wind_field(&self.frame_resources[self.frame_slot], wind_field)
+    }
+
+    /// Updates the path wear the shading reads, the wear itself is only copied when
+    /// `is_wear_changed` or the current frame slot missed a change.
+    pub fn update_path_wear(
+        &mut self,
+        path_wear: &PathWearMap,
+        is_wear_changed: bool,
+    ) -> Result<()> {
+        if is_wear_changed {
+            for frame_resources in &mut self.frame_resources {
+                frame_resources.is_path_wear_stale = true;
+            }
+        }
+        let frame_resources = &mut self.frame_resources[self.frame_slot];
+        BufferUpdater::update_path_wear_info(
+            frame_resources,
+            path_wear.extent(),
+            path_wear.desc.path_color_vec3(),
+        )?;
+        if frame_resources.is_path_wear_stale {
+            frame_resources.path_wear.fill(path_wear.wear())?;
+            frame_resources.is_path_wear_stale = false;
+        }
+        Ok(())
+    }
+
+    /// Queues the sky visibility of the columns around `region` (in world units) to be recomputed
+    /// before the next frame, the whole map for `None`.
+    pub fn mark_sky_visibility_dirty(&mut self, region: Option<&Aabb3>) {
+        self.sky_visibility.mark_dirty(region);
+    }
+
+    /// The scene casting shadows changed, so the cached shadow map is rendered again.
+    pub fn mark_shadows_dirty(&mut self) {
+        self.shadow_cache.mark_dirty();
+    }
+
+    /// Thins the leaves of every tree out to `density`, the impostors and the shadows follow.
+    pub fn set_leaf_density(&mut self, density: f32) {
+        let density = (density.clamp(0.0, 1.0) * LEAF_DENSITY_STEPS).round() / LEAF_DENSITY_STEPS;
+        if density == self.leaf_density {
+            return;
+        }
+        self.leaf_density = density;
+        self.impostor_atlas.request_rebake_all();
+        self.shadow_cache.mark_dirty();
+    }
+
+    /// The pixel rect the history is invalid in this frame as min xy and exclusive max xy, empty
+    /// if nothing is pending.
+    fn take_history_invalid_rect(&mut self) -> UVec4 {
+        let extent = self
+            .resources
+            .extent_dependent_resources
+            .composited_tex
+            .get_image()
+            .get_desc()
+            .extent;
+        let full_screen = UVec4::new(0, 0, extent.width, extent.height);
+        let region = match self.pending_history_invalidation.take() {
+            None => return UVec4::ZERO,
+            Some(HistoryInvalidation::FullScreen) => return full_screen,
+            Some(HistoryInvalidation::Region(region)) => region,
+        };
+
+        let mut min = Vec2::MAX;
+        let mut max = Vec2::MIN;
+        for corner in region.get_corners() {
+            let clip = self.current_view_proj_mat * corner.extend(1.0);
+            // the box reaches behind the camera, its projection is unbounded
+            if clip.w <= 0.0 {
+                return full_screen;
+            }
+            let uv = (clip.truncate().truncate() / clip.w + 1.0) * 0.5;
+            min = min.min(uv);
+            max = max.max(uv);
+        }
+        let size = Vec2::new(extent.width as f32, extent.height as f32);
+        let min = (min * size).floor().clamp(Vec2::ZERO, size).as_uvec2();
+        let max = (max * size).ceil().clamp(Vec2::ZERO, size).as_uvec2();
+        UVec4::new(min.x, min.y, max.x, max.y)
+    }
+
+    pub fn update_camera(
+        &mut self,
+        frame_delta_time: f32,
+        is_fly_mode: bool,
+        camera_feel_desc: &CameraFeelDesc,
+        character_controller_desc: &CharacterControllerDesc,
+    ) {
+        profile_scope!("update_camera");
         if is_fly_mode {
             self.camera.update_transform_fly_mode(frame_delta_time);
         } else {
-            let collision_result =
-                get_player_collision_result(&self.resources.player_collision_result).unwrap();
-            self.camera
-                .update_transform_walk_mode(frame_delta_time, collision_result);
+            let collision_result = self.read_player_collision_result().unwrap();
+            self.camera.update_transform_walk_mode(
+                frame_delta_time,
+                collision_result,
+                camera_feel_desc,
+                character_controller_desc,
+            );
         }
+        self.update_listener(frame_delta_time);
+    }
 
-        // update spatial sound manager with camera (listener) position
+    /// Moves the camera to a scripted pose instead of following the input, the listener moves
+    /// along with it.
+    pub fn update_camera_scripted(
+        &mut self,
+        frame_delta_time: f32,
+        position: Vec3,
+        yaw: f32,
+        pitch: f32,
+    ) {
+        profile_scope!("update_camera_scripted");
+        self.camera.update_transform_scripted(position, yaw, pitch);
+        self.update_listener(frame_delta_time);
+    }
+
+    fn update_listener(&mut self, frame_delta_time: f32) {
+        // update spatial sound manager with camera (listener) pose
         self.spatial_sound_manager
-            .update_player_pos(self.camera.position(), self.camera.vectors())
+            .update_listener(
+                self.camera.position(),
+                self.camera.vectors(),
+                frame_delta_time,
+            )
+            .unwrap();
+        self.spatial_sound_manager
+            .update_voices(frame_delta_time)
             .unwrap();
+    }
 
-        fn get_player_collision_result(
-            player_collision_result: &Buffer,
-        ) -> Result<PlayerCollisionResult> {
-            let layout = &player_collision_result.get_layout().unwrap().root_member;
-            let raw_data = player_collision_result.read_back().unwrap();
-            let reader = StructMemberDataReader::new(layout, &raw_data);
+    /// Reads back the player collider output of the frame last recorded with the current frame
+    /// slot, only the active ring rays are returned.
+    pub fn read_player_collision_result(&self) -> Result<PlayerCollisionResult> {
+        let player_collision_result =
+            &self.frame_resources[self.frame_slot].player_collision_result;
+        let layout = &player_collision_result.get_layout().unwrap().root_member;
+        let raw_data = player_collision_result.read_back().unwrap();
+        let reader = StructMemberDataReader::new(layout, &raw_data);
+
+        let ground_distance = if let PlainMemberTypeWithData::Float(val) =
+            reader.get_field("ground_distance").unwrap()
+        {
+            val
+        } else {
+            panic!("Expected Float type for ground_distance");
+        };
 
-            let ground_distance = if let PlainMemberTypeWithData::Float(val) =
-                reader.get_field("ground_distance").unwrap()
-            {
-                val
-            } else {
-                panic!("Expected Float type for ground_distance");
-            };
+        let ceiling_distance = if let PlainMemberTypeWithData::Float(val) =
+            reader.get_field("ceiling_distance").unwrap()
+        {
+            val
+        } else {
+            panic!("Expected Float type for ceiling_distance");
+        };
 
-            let ring_distances = if let PlainMemberTypeWithData::Array(val) =
-                reader.get_field("ring_distances").unwrap()
-            {
-                val
-            } else {
-                panic!("Expected Array type for ring_distances");
-            };
+        let ground_voxel_type = if let PlainMemberTypeWithData::UInt(val) =
+            reader.get_field("ground_voxel_type").unwrap()
+        {
+            val
+        } else {
+            panic!("Expected UInt type for ground_voxel_type");
+        };
 
-            Ok(PlayerCollisionResult {
-                ground_distance,
-                ring_distances,
-            })
+        let mut ring_distances = if let PlainMemberTypeWithData::Array(val) =
+            reader.get_field("ring_distances").unwrap()
+        {
+            val
+        } else {
+            panic!("Expected Array type for ring_distances");
+        };
+        ring_distances.truncate(self.player_collider_ring_count as usize);
+
+        Ok(PlayerCollisionResult {
+            ground_distance,
+            ceiling_distance,
+            ground_material: VoxelMaterial::from_voxel_type(ground_voxel_type),
+            ring_distances,
+        })
+    }
+
+    /// Returns the world-space end points of the ring rays, in the same order as
+    /// `ring_distances`. Mirrors `get_ring_direction` in `player_collider.comp`.
+    pub fn player_collider_ring_samples(&self, ring_distances: &[f32]) -> Vec<Vec3> {
+        let num_rings = ring_distances.len();
+        if num_rings < 2 {
+            return Vec::new();
         }
+
+        let front = self.camera.front();
+        let front = Vec3::new(front.x, 0.0, front.z).normalize_or_zero();
+        let right = front.cross(Vec3::Y).normalize_or_zero();
+        let origin = self.camera.position();
+
+        ring_distances
+            .iter()
+            .enumerate()
+            .map(|(i, distance)| {
+                let direction = if i == 0 {
+                    front
+                } else {
+                    let angle = std::f32::consts::TAU * (i - 1) as f32 / (num_rings - 1) as f32;
+                    (front * angle.cos() + right * angle.sin()).normalize_or_zero()
+                };
+                origin + direction * *distance
+            })
+            .collect()
+    }
+
+    /// View-projection matrix of the main camera used for the current frame.
+    pub fn view_proj_mat(&self) -> Mat4 {
+        self.current_view_proj_mat
     }
 
     pub fn add_tree_leaves(
         &mut self,
         surface_resources: &mut SurfaceResources,
         tree_id: u32,
-        leaf_positions: &[UVec3],
+        leaf_positions: &[VoxelPos],
     ) -> Result<()> {
         use crate::builder::TreeLeavesInstance;
 
         let mut instances_data = Vec::new();
 
         for leaf_pos in leaf_positions.iter() {
-            let voxel_pos = *leaf_pos;
+            let voxel_pos = leaf_pos.0;
 
             // create instance data matching GrassInstance structure
             let instance = Instance {
@@ -1499,43 +3122,42 @@ impl Tracer {
         // calculate AABB based on actual leaf positions
         let scaled_leaf_positions = leaf_positions
             .iter()
-            .map(|leaf| {
-                Vec3::new(
-                    leaf.x as f32 / 256.0,
-                    leaf.y as f32 / 256.0,
-                    leaf.z as f32 / 256.0,
-                )
-            })
+            .map(|leaf| leaf.to_world().0)
             .collect::<Vec<_>>();
         let leaves_aabb = crate::builder::InstanceResources::compute_leaves_aabb(
             &scaled_leaf_positions,
             0.2, // Default margin to cover leaf radius
         );
 
+        let instances = &mut surface_resources.instances;
+        // an existing entry is overwritten, give its slice back first
+        if let Some(mut old_instance) = instances.leaves_instances.remove(&tree_id) {
+            instances.pool.free(&mut old_instance.resources);
+        }
+
         // create new tree leaves instance
-        let mut tree_leaves_instance = TreeLeavesInstance::new(
-            tree_id,
-            leaves_aabb,
-            self.vulkan_ctx.device().clone(),
-            self.allocator.clone(),
-        );
+        let mut tree_leaves_instance = TreeLeavesInstance::new(tree_id, leaves_aabb);
 
         // fill with instance data if we have any
+        instances.pool.resize(
+            &mut tree_leaves_instance.resources,
+            instances_data.len() as u32,
+        )?;
         if !instances_data.is_empty() {
-            tree_leaves_instance
-                .resources
-                .instances_buf
-                .fill(&instances_data)?;
-            tree_leaves_instance.resources.instances_len = instances_data.len() as u32;
-        } else {
-            tree_leaves_instance.resources.instances_len = 0;
+            instances
+                .pool
+                .write(&tree_leaves_instance.resources, 0, &instances_data)?;
         }
+        tree_leaves_instance.resources.instances_len = instances_data.len() as u32;
 
         // add/update the tree instance in HashMap
-        surface_resources
-            .instances
+        self.impostor_atlas
+            .request_bake(tree_id, &tree_leaves_instance.aabb);
+        instances
             .leaves_instances
             .insert(tree_id, tree_leaves_instance);
+        self.tree_cull_ids = None;
+        self.sky_visibility.set_tree(tree_id, leaf_positions);
 
         Ok(())
     }
@@ -1545,16 +3167,19 @@ impl Tracer {
         surface_resources: &mut SurfaceResources,
         tree_id: u32,
     ) -> Result<()> {
-        if let Some(removed_instance) = surface_resources
-            .instances
-            .leaves_instances
-            .remove(&tree_id)
-        {
+        let instances = &mut surface_resources.instances;
+        if let Some(mut removed_instance) = instances.leaves_instances.remove(&tree_id) {
+            self.tree_cull_ids = None;
+            self.tree_lod_selector.remove(&tree_id);
+            self.tree_impostor_lod_selector.remove(&tree_id);
+            self.impostor_atlas.remove(tree_id);
+            self.sky_visibility.remove_tree(tree_id);
             log::info!(
                 "Removed tree {} with {} leaves",
                 tree_id,
                 removed_instance.resources.instances_len
             );
+            instances.pool.free(&mut removed_instance.resources);
         } else {
             log::warn!("Attempted to remove non-existent tree {}", tree_id);
         }
@@ -1566,7 +3191,7 @@ impl Tracer {
         &mut self,
         surface_resources: &mut SurfaceResources,
         tree_id: u32,
-        leaf_positions: &[UVec3],
+        leaf_positions: &[VoxelPos],
     ) -> Result<()> {
         // simply use add_tree_leaves which will overwrite existing entry
         self.add_tree_leaves(surface_resources, tree_id, leaf_positions)
@@ -1577,8 +3202,16 @@ impl Tracer {
         &mut self,
         surface_resources: &mut SurfaceResources,
     ) -> Result<()> {
-        let count = surface_resources.instances.leaves_instances.len();
-        surface_resources.instances.leaves_instances.clear();
+        let instances = &mut surface_resources.instances;
+        let count = instances.leaves_instances.len();
+        for (_, mut leaves_instance) in instances.leaves_instances.drain() {
+            instances.pool.free(&mut leaves_instance.resources);
+        }
+        self.tree_cull_ids = None;
+        self.tree_lod_selector.clear();
+        self.tree_impostor_lod_selector.clear();
+        self.impostor_atlas.clear();
+        self.sky_visibility.clear_trees();
         log::info!("Cleared all {} tree instances", count);
         Ok(())
     }
@@ -1590,6 +3223,10 @@ impl Tracer {
         inner_radius: f32,
         outer_radius: f32,
     ) -> Result<()> {
+        // the frames in flight still draw the old leaves
+        let frame_timeline = self.vulkan_ctx.frame_timeline();
+        frame_timeline.wait(frame_timeline.submitted_value())?;
+
         let device = self.vulkan_ctx.device();
         self.resources.leaves_resources = LeavesResources::new_with_params(
             device.clone(),
@@ -1610,16 +3247,98 @@ impl Tracer {
             outer_radius,
             true,
         );
+        self.impostor_atlas.request_rebake_all();
         Ok(())
     }
 
+    /// Fails with a [`TerrainMiss`] where there's no ground below the position.
     pub fn query_terrain_height(&mut self, pos_xz: Vec2) -> Result<f32> {
         let heights = self.query_terrain_heights_batch(&[pos_xz])?;
-        Ok(heights[0])
+        Ok(heights[0]?)
+    }
+
+    pub fn query_terrain_heights_batch(
+        &mut self,
+        positions: &[Vec2],
+    ) -> Result<Vec<Result<f32, TerrainMiss>>> {
+        let hits = self.query_terrain_batch(positions)?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| hit.map(|hit| hit.height))
+            .collect())
+    }
+
+    /// Height, surface normal and material of the terrain below each position, or why there's
+    /// none. Positions outside the chunk bound always miss.
+    pub fn query_terrain_batch(
+        &mut self,
+        positions: &[Vec2],
+    ) -> Result<Vec<Result<TerrainHit, TerrainMiss>>> {
+        profile_scope!("query_terrain_batch");
+        let rays = positions
+            .iter()
+            .map(|pos| TerrainQueryRay::downward(*pos))
+            .collect::<Vec<_>>();
+        let hits = self.cast_query_rays(&rays)?;
+        let bound_max = Vec2::new(
+            self.chunk_bound.max().x as f32,
+            self.chunk_bound.max().z as f32,
+        );
+        Ok(positions
+            .iter()
+            .zip(hits)
+            .map(|(pos, hit)| {
+                // the ray can't find anything out there, whatever the shader wrote
+                if pos.cmplt(Vec2::ZERO).any() || pos.cmpge(bound_max).any() {
+                    return Err(TerrainMiss::OutOfBounds);
+                }
+                let hit = hit.ok_or(TerrainMiss::NoHit)?;
+                Ok(TerrainHit {
+                    height: hit.position.y,
+                    normal: hit.normal,
+                    material: hit.material,
+                })
+            })
+            .collect())
+    }
+
+    /// The first voxel along the ray through the contree, `None` when it leaves the scene
+    /// without hitting one. `direction` needn't be normalized.
+    pub fn raycast_scene(&mut self, origin: Vec3, direction: Vec3) -> Result<Option<RayHit>> {
+        let hits = self.cast_query_rays(&[TerrainQueryRay::new(origin, direction)])?;
+        Ok(hits[0])
+    }
+
+    /// [`Self::raycast_scene`] for a batch of `(origin, direction)` rays, at most
+    /// `MAX_TERRAIN_QUERIES` of them, cast by the next recorded frame instead of waiting for them.
+    /// Returns `false` while the previous batch hasn't been taken by
+    /// [`Self::take_queued_raycast_hits`] yet, nothing is queued then.
+    pub fn queue_raycast_scene_batch(&mut self, rays: &[(Vec3, Vec3)]) -> Result<bool> {
+        let rays = rays
+            .iter()
+            .map(|(origin, direction)| TerrainQueryRay::new(*origin, *direction))
+            .collect();
+        self.scene_ray_queue.push(rays)
+    }
+
+    /// The hits of the queued batch once the frame that cast it is done, `None` until then.
+    pub fn take_queued_raycast_hits(&mut self) -> Result<Option<Vec<Option<RayHit>>>> {
+        self.scene_ray_queue.take_hits()
+    }
+
+    /// The ray from the eye through a point on the screen, `screen_uv` runs from (0, 0) at the
+    /// top left to (1, 1) at the bottom right. The direction is normalized.
+    pub fn screen_ray(&self, screen_uv: Vec2) -> (Vec3, Vec3) {
+        let inv_view_proj = self.current_view_proj_mat.inverse();
+        // the projection flips y, so the screen and the ndc agree on which way is down
+        let ndc = screen_uv * 2.0 - 1.0;
+        let near = inv_view_proj.project_point3(ndc.extend(0.0));
+        let far = inv_view_proj.project_point3(ndc.extend(1.0));
+        (near, (far - near).normalize())
     }
 
-    pub fn query_terrain_heights_batch(&mut self, positions: &[Vec2]) -> Result<Vec<f32>> {
-        let query_count = positions.len() as u32;
+    fn cast_query_rays(&mut self, rays: &[TerrainQueryRay]) -> Result<Vec<Option<RayHit>>> {
+        let query_count = rays.len() as u32;
         if query_count == 0 {
             return Ok(vec![]);
         }
@@ -1635,14 +3354,11 @@ impl Tracer {
             .terrain_query_count
             .fill_with_raw_u8(&count_data)?;
 
-        // update query positions
-        let mut position_data = Vec::with_capacity(positions.len() * 2);
-        for pos in positions {
-            position_data.push(pos.x);
-            position_data.push(pos.y);
-        }
-        self.resources.terrain_query_info.fill(&position_data)?;
+        // update query rays
+        self.resources.terrain_query_info.fill(rays)?;
 
+        // the scene texture updates run on the compute queue without blocking the host
+        self.vulkan_ctx.wait_compute()?;
         execute_one_time_command(
             self.vulkan_ctx.device(),
             self.vulkan_ctx.command_pool(),
@@ -1657,10 +3373,74 @@ impl Tracer {
         );
 
         // read back results
-        let raw_data = self.resources.terrain_query_result.read_back().unwrap();
-        let height_data: &[f32] = unsafe {
-            std::slice::from_raw_parts(raw_data.as_ptr() as *const f32, query_count as usize)
+        let raw_data = self.resources.terrain_query_result.read_back_range(
+            0,
+            query_count as u64 * std::mem::size_of::<TerrainQueryResult>() as u64,
+        )?;
+        let results: Vec<TerrainQueryResult> = bytemuck::pod_collect_to_vec(&raw_data);
+        Ok(rays
+            .iter()
+            .zip(results)
+            .map(|(ray, result)| result.into_ray_hit(ray))
+            .collect())
+    }
+
+    /// The transfer timeline value frames have to wait for before they sample the streamed
+    /// textures.
+    pub fn texture_upload_wait(&self) -> (&TimelineSemaphore, u64) {
+        self.streamed_textures.upload_wait()
+    }
+
+    /// A camera position standing on the ground near `near_xz` (in world units).
+    ///
+    /// Searches outward in rings for flat ground that isn't part of a tree, then for any ground
+    /// at all. Positions outside the chunk bound are pulled into it first. Fails when there's no
+    /// ground anywhere in the search area.
+    pub fn find_safe_spawn(&mut self, near_xz: Vec2) -> Result<Vec3> {
+        let bound_max = Vec2::new(
+            self.chunk_bound.max().x as f32,
+            self.chunk_bound.max().z as f32,
+        );
+        let margin = Vec2::splat(SAFE_SPAWN_RING_SPACING);
+        let center = near_xz.clamp(margin, bound_max - margin);
+
+        let mut candidates = vec![center];
+        for ring in 1..=SAFE_SPAWN_RING_COUNT {
+            let radius = ring as f32 * SAFE_SPAWN_RING_SPACING;
+            let sample_count = ring * 8;
+            for i in 0..sample_count {
+                let angle = i as f32 / sample_count as f32 * std::f32::consts::TAU;
+                candidates.push(center + Vec2::from_angle(angle) * radius);
+            }
+        }
+
+        let hits = self.query_terrain_batch(&candidates)?;
+        let is_walkable = |hit: &TerrainHit| {
+            hit.normal.y >= SAFE_SPAWN_MIN_NORMAL_Y
+                && !matches!(
+                    hit.material,
+                    Some(VoxelMaterial::Leaf)
+                        | Some(VoxelMaterial::Trunk)
+                        | Some(VoxelMaterial::Water)
+                )
+        };
+        // candidates are ordered by distance, so the first one found is the closest
+        let ground = candidates
+            .iter()
+            .zip(&hits)
+            .find(|(_, hit)| hit.as_ref().is_ok_and(is_walkable))
+            .or_else(|| candidates.iter().zip(&hits).find(|(_, hit)| hit.is_ok()));
+        let Some((pos_xz, Ok(hit))) = ground else {
+            bail!(
+                "No ground within {} of {}",
+                SAFE_SPAWN_RING_COUNT as f32 * SAFE_SPAWN_RING_SPACING,
+                near_xz
+            );
         };
-        Ok(height_data.to_vec())
+        Ok(Vec3::new(
+            pos_xz.x,
+            hit.height + self.camera.standing_height() + SAFE_SPAWN_CLEARANCE,
+            pos_xz.y,
+        ))
     }
 }
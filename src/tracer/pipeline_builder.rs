@@ -1,14 +1,20 @@
 use crate::builder::{ContreeBuilderResources, SceneAccelBuilderResources};
 use crate::resource::ResourceContainer;
-use crate::tracer::TracerResources;
+use crate::tracer::{
+    FrameResources, RasterPass, RasterTargets, StreamedTextureResources, TracerResources,
+};
 use crate::util::ShaderCompiler;
 use crate::vkn::{
     AttachmentDescOuter, AttachmentType, ComputePipeline, DescriptorPool, GraphicsPipeline,
-    GraphicsPipelineDesc, RenderPass, ShaderModule, Texture, VulkanContext,
+    GraphicsPipelineDesc, RenderPass, RenderingTarget, ShaderModule, Texture, VulkanContext,
+    WorkgroupSizeCache,
 };
 use anyhow::Result;
 use ash::vk;
 
+/// The heavy full-screen pipelines whose workgroup size is autotuned per device.
+pub const TUNED_PIPELINES: [&str; 4] = ["tracer", "god_ray", "temporal", "spatial"];
+
 pub struct PipelineBuilder;
 
 impl PipelineBuilder {
@@ -64,26 +70,59 @@ impl PipelineBuilder {
         )
         .unwrap();
 
-        let temporal_sm = ShaderModule::from_glsl(
+        // the tiled variants share the bindings of the originals, so either fits the same resources
+        let use_tiled_denoiser = vulkan_ctx.supports_compute_subgroup_ops(
+            vk::SubgroupFeatureFlags::BASIC | vk::SubgroupFeatureFlags::VOTE,
+        );
+        let (temporal_path, spatial_path) = if use_tiled_denoiser {
+            (
+                "shader/denoiser/temporal_tiled.comp",
+                "shader/denoiser/spatial_tiled.comp",
+            )
+        } else {
+            (
+                "shader/denoiser/temporal.comp",
+                "shader/denoiser/spatial.comp",
+            )
+        };
+        log::info!("Denoiser shaders: {}, {}", temporal_path, spatial_path);
+
+        let temporal_sm =
+            ShaderModule::from_glsl(vulkan_ctx.device(), shader_compiler, temporal_path, "main")
+                .unwrap();
+
+        let spatial_sm =
+            ShaderModule::from_glsl(vulkan_ctx.device(), shader_compiler, spatial_path, "main")
+                .unwrap();
+
+        let composition_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
-            "shader/denoiser/temporal.comp",
+            "shader/tracer/composition.comp",
             "main",
         )
         .unwrap();
 
-        let spatial_sm = ShaderModule::from_glsl(
+        let water_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
-            "shader/denoiser/spatial.comp",
+            "shader/tracer/water.comp",
             "main",
         )
         .unwrap();
 
-        let composition_sm = ShaderModule::from_glsl(
+        let hiz_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
-            "shader/tracer/composition.comp",
+            "shader/tracer/hiz.comp",
+            "main",
+        )
+        .unwrap();
+
+        let fog_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/fog.comp",
             "main",
         )
         .unwrap();
@@ -96,6 +135,14 @@ impl PipelineBuilder {
         )
         .unwrap();
 
+        let dof_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/dof.comp",
+            "main",
+        )
+        .unwrap();
+
         let post_processing_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
@@ -120,6 +167,22 @@ impl PipelineBuilder {
         )
         .unwrap();
 
+        let sky_visibility_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/sky_visibility.comp",
+            "main",
+        )
+        .unwrap();
+
+        let sky_map_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/sky_map.comp",
+            "main",
+        )
+        .unwrap();
+
         let flora_vert_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
@@ -152,6 +215,30 @@ impl PipelineBuilder {
         )
         .unwrap();
 
+        let flora_blend_frag_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/foliage/flora_blend.frag",
+            "main",
+        )
+        .unwrap();
+
+        let weather_vert_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/weather.vert",
+            "main",
+        )
+        .unwrap();
+
+        let weather_frag_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/tracer/weather.frag",
+            "main",
+        )
+        .unwrap();
+
         let leaves_shadow_vert_sm = ShaderModule::from_glsl(
             vulkan_ctx.device(),
             shader_compiler,
@@ -168,6 +255,38 @@ impl PipelineBuilder {
         )
         .unwrap();
 
+        let impostor_bake_vert_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/foliage/impostor_bake.vert",
+            "main",
+        )
+        .unwrap();
+
+        let impostor_bake_frag_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/foliage/impostor_bake.frag",
+            "main",
+        )
+        .unwrap();
+
+        let impostor_vert_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/foliage/impostor.vert",
+            "main",
+        )
+        .unwrap();
+
+        let impostor_frag_sm = ShaderModule::from_glsl(
+            vulkan_ctx.device(),
+            shader_compiler,
+            "shader/foliage/impostor.frag",
+            "main",
+        )
+        .unwrap();
+
         Ok(ShaderModules {
             tracer_sm,
             tracer_shadow_sm,
@@ -178,78 +297,190 @@ impl PipelineBuilder {
             temporal_sm,
             spatial_sm,
             composition_sm,
+            water_sm,
+            hiz_sm,
+            fog_sm,
             taa_sm,
+            dof_sm,
             post_processing_sm,
             player_collider_sm,
             terrain_query_sm,
+            sky_visibility_sm,
+            sky_map_sm,
             flora_vert_sm,
             flora_frag_sm,
             flora_lod_vert_sm,
             flora_lod_frag_sm,
+            flora_blend_frag_sm,
+            weather_vert_sm,
+            weather_frag_sm,
             leaves_shadow_vert_sm,
             leaves_shadow_frag_sm,
+            impostor_bake_vert_sm,
+            impostor_bake_frag_sm,
+            impostor_vert_sm,
+            impostor_frag_sm,
+            use_tiled_denoiser,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_compute_pipelines(
         vulkan_ctx: &VulkanContext,
         shader_modules: &ShaderModules,
         pool: &DescriptorPool,
         resources: &TracerResources,
+        frame_resources: &FrameResources,
+        streamed_texture_resources: &StreamedTextureResources,
         contree_builder_resources: &ContreeBuilderResources,
         scene_accel_resources: &SceneAccelBuilderResources,
+        workgroup_size_cache: &WorkgroupSizeCache,
     ) -> ComputePipelines {
         let device = vulkan_ctx.device();
 
-        let tracer_ppl = ComputePipeline::new(
-            device,
-            &shader_modules.tracer_sm,
-            pool,
-            &[resources, contree_builder_resources, scene_accel_resources],
-        );
+        let create_tunable_ppl = |name: &str| {
+            Self::create_tunable_compute_pipeline(
+                vulkan_ctx,
+                shader_modules,
+                name,
+                pool,
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+                workgroup_size_cache.get(name),
+            )
+        };
+
+        let tracer_ppl = create_tunable_ppl("tracer");
 
         let tracer_shadow_ppl = ComputePipeline::new(
             device,
             &shader_modules.tracer_shadow_sm,
             pool,
-            &[resources, contree_builder_resources, scene_accel_resources],
+            &[
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
         );
 
         let player_collider_ppl = ComputePipeline::new(
             device,
             &shader_modules.player_collider_sm,
             pool,
-            &[resources, contree_builder_resources, scene_accel_resources],
+            &[
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
         );
 
         let terrain_query_ppl = ComputePipeline::new(
             device,
             &shader_modules.terrain_query_sm,
             pool,
-            &[resources, contree_builder_resources, scene_accel_resources],
+            &[
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
         );
 
-        let vsm_creation_ppl =
-            ComputePipeline::new(device, &shader_modules.vsm_creation_sm, pool, &[resources]);
-        let vsm_blur_h_ppl =
-            ComputePipeline::new(device, &shader_modules.vsm_blur_h_sm, pool, &[resources]);
-        let vsm_blur_v_ppl =
-            ComputePipeline::new(device, &shader_modules.vsm_blur_v_sm, pool, &[resources]);
-        let god_ray_ppl =
-            ComputePipeline::new(device, &shader_modules.god_ray_sm, pool, &[resources]);
-        let temporal_ppl =
-            ComputePipeline::new(device, &shader_modules.temporal_sm, pool, &[resources]);
-        let spatial_ppl =
-            ComputePipeline::new(device, &shader_modules.spatial_sm, pool, &[resources]);
-        let composition_ppl =
-            ComputePipeline::new(device, &shader_modules.composition_sm, pool, &[resources]);
-        let taa_ppl = ComputePipeline::new(device, &shader_modules.taa_sm, pool, &[resources]);
+        let sky_visibility_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.sky_visibility_sm,
+            pool,
+            &[
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
+        );
+
+        let water_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.water_sm,
+            pool,
+            &[
+                resources,
+                frame_resources,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
+        );
+
+        let vsm_creation_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.vsm_creation_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let vsm_blur_h_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.vsm_blur_h_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let vsm_blur_v_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.vsm_blur_v_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let god_ray_ppl = create_tunable_ppl("god_ray");
+        let temporal_ppl = create_tunable_ppl("temporal");
+        let spatial_ppl = create_tunable_ppl("spatial");
+        let composition_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.composition_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let hiz_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.hiz_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let fog_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.fog_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let taa_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.taa_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let dof_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.dof_sm,
+            pool,
+            &[resources, frame_resources],
+        );
+        let sky_map_ppl = ComputePipeline::new(
+            device,
+            &shader_modules.sky_map_sm,
+            pool,
+            &[resources, frame_resources],
+        );
 
         let post_processing_ppl = ComputePipeline::new(
             device,
             &shader_modules.post_processing_sm,
             pool,
-            &[resources],
+            &[
+                resources as &dyn ResourceContainer,
+                frame_resources,
+                streamed_texture_resources,
+            ],
         );
 
         ComputePipelines {
@@ -262,18 +493,73 @@ impl PipelineBuilder {
             temporal_ppl,
             spatial_ppl,
             composition_ppl,
+            water_ppl,
+            hiz_ppl,
+            fog_ppl,
             taa_ppl,
+            dof_ppl,
             player_collider_ppl,
             terrain_query_ppl,
+            sky_visibility_ppl,
+            sky_map_ppl,
             post_processing_ppl,
         }
     }
 
+    /// Creates one of [`TUNED_PIPELINES`], with `workgroup_size` overriding the shader's default
+    /// when the shader supports it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_tunable_compute_pipeline(
+        vulkan_ctx: &VulkanContext,
+        shader_modules: &ShaderModules,
+        name: &str,
+        pool: &DescriptorPool,
+        resources: &TracerResources,
+        frame_resources: &FrameResources,
+        contree_builder_resources: &ContreeBuilderResources,
+        scene_accel_resources: &SceneAccelBuilderResources,
+        workgroup_size: Option<[u32; 3]>,
+    ) -> ComputePipeline {
+        let device = vulkan_ctx.device();
+        let shader_module = match name {
+            "tracer" => &shader_modules.tracer_sm,
+            "god_ray" => &shader_modules.god_ray_sm,
+            "temporal" => &shader_modules.temporal_sm,
+            "spatial" => &shader_modules.spatial_sm,
+            _ => panic!("{} is not a tunable pipeline", name),
+        };
+        // only the tracer marches the scene, the others just need the tracer resources
+        let all_resources: [&dyn ResourceContainer; 4] = [
+            resources,
+            frame_resources,
+            contree_builder_resources,
+            scene_accel_resources,
+        ];
+        let resource_containers = if name == "tracer" {
+            &all_resources[..]
+        } else {
+            &all_resources[..2]
+        };
+
+        match workgroup_size.filter(|_| shader_modules.is_tunable(name)) {
+            Some(workgroup_size) => ComputePipeline::with_workgroup_size(
+                device,
+                shader_module,
+                pool,
+                resource_containers,
+                workgroup_size,
+            ),
+            None => ComputePipeline::new(device, shader_module, pool, resource_containers),
+        }
+    }
+
     pub fn create_render_passes(
         vulkan_ctx: &VulkanContext,
         gfx_output_tex: Texture,
         gfx_depth_tex: Texture,
         shadow_map_tex: Texture,
+        impostor_atlas_tex: Texture,
+        impostor_depth_tex: Texture,
     ) -> RenderPasses {
         let render_pass_color_and_depth = Self::create_render_pass_with_color_and_depth(
             vulkan_ctx,
@@ -281,52 +567,134 @@ impl PipelineBuilder {
             gfx_depth_tex.clone(),
         );
         let render_pass_depth = Self::create_render_pass_with_depth(vulkan_ctx, shadow_map_tex);
+        let render_pass_impostor = Self::create_render_pass_with_color_and_depth(
+            vulkan_ctx,
+            impostor_atlas_tex,
+            impostor_depth_tex,
+        );
         RenderPasses {
             render_pass_color_and_depth,
             render_pass_depth,
+            render_pass_impostor,
         }
     }
 
     pub fn create_graphics_pipelines(
         vulkan_ctx: &VulkanContext,
         shader_modules: &ShaderModules,
-        render_passes: &RenderPasses,
+        raster_targets: &RasterTargets,
         pool: &DescriptorPool,
         resources: &TracerResources,
+        frame_resources: &FrameResources,
     ) -> GraphicsPipelines {
         let flora_ppl = Self::create_gfx_pipeline(
             vulkan_ctx,
             &shader_modules.flora_vert_sm,
             &shader_modules.flora_frag_sm,
-            &render_passes.render_pass_color_and_depth,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
             Some(1),
             pool,
-            &[resources],
+            &[resources, frame_resources],
+            false,
         );
 
         let flora_lod_ppl = Self::create_gfx_pipeline(
             vulkan_ctx,
             &shader_modules.flora_lod_vert_sm,
             &shader_modules.flora_lod_frag_sm,
-            &render_passes.render_pass_color_and_depth,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
             Some(1),
             pool,
-            &[resources],
+            &[resources, frame_resources],
+            false,
+        );
+
+        let flora_blend_ppl = Self::create_gfx_pipeline(
+            vulkan_ctx,
+            &shader_modules.flora_vert_sm,
+            &shader_modules.flora_blend_frag_sm,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
+            Some(1),
+            pool,
+            &[resources, frame_resources],
+            false,
+        );
+
+        let flora_lod_blend_ppl = Self::create_gfx_pipeline(
+            vulkan_ctx,
+            &shader_modules.flora_lod_vert_sm,
+            &shader_modules.flora_blend_frag_sm,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
+            Some(1),
+            pool,
+            &[resources, frame_resources],
+            false,
         );
 
         let leaves_shadow_lod_ppl = Self::create_gfx_pipeline(
             vulkan_ctx,
             &shader_modules.leaves_shadow_vert_sm,
             &shader_modules.leaves_shadow_frag_sm,
-            &render_passes.render_pass_depth,
+            raster_targets.rendering_target(RasterPass::DepthOnly),
             Some(1),
             pool,
-            &[resources],
+            &[resources, frame_resources],
+            true,
+        );
+
+        // the quads are built facing the camera, with either winding
+        let weather_ppl = GraphicsPipeline::new(
+            vulkan_ctx.device(),
+            &shader_modules.weather_vert_sm,
+            &shader_modules.weather_frag_sm,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
+            &GraphicsPipelineDesc {
+                cull_mode: vk::CullModeFlags::NONE,
+                depth_test_enable: true,
+                depth_write_enable: true,
+                ..Default::default()
+            },
+            None,
+            pool,
+            &[resources, frame_resources],
+        );
+
+        let impostor_bake_ppl = Self::create_gfx_pipeline(
+            vulkan_ctx,
+            &shader_modules.impostor_bake_vert_sm,
+            &shader_modules.impostor_bake_frag_sm,
+            raster_targets.rendering_target(RasterPass::Impostor),
+            Some(1),
+            pool,
+            &[resources, frame_resources],
+            false,
+        );
+
+        // the quads turn to the camera, with either winding
+        let impostor_ppl = GraphicsPipeline::new(
+            vulkan_ctx.device(),
+            &shader_modules.impostor_vert_sm,
+            &shader_modules.impostor_frag_sm,
+            raster_targets.rendering_target(RasterPass::ColorAndDepth),
+            &GraphicsPipelineDesc {
+                cull_mode: vk::CullModeFlags::NONE,
+                depth_test_enable: true,
+                depth_write_enable: true,
+                ..Default::default()
+            },
+            None,
+            pool,
+            &[resources, frame_resources],
         );
         GraphicsPipelines {
             flora_ppl,
             flora_lod_ppl,
+            flora_blend_ppl,
+            flora_lod_blend_ppl,
             leaves_shadow_lod_ppl,
+            weather_ppl,
+            impostor_bake_ppl,
+            impostor_ppl,
         }
     }
 
@@ -376,20 +744,22 @@ impl PipelineBuilder {
         vulkan_ctx: &VulkanContext,
         vert_sm: &ShaderModule,
         frag_sm: &ShaderModule,
-        render_pass: &RenderPass,
+        target: RenderingTarget,
         instance_rate_starting_location: Option<u32>,
         descriptor_pool: &DescriptorPool,
         resource_containers: &[&dyn ResourceContainer],
+        depth_bias_enable: bool,
     ) -> GraphicsPipeline {
         GraphicsPipeline::new(
             vulkan_ctx.device(),
             vert_sm,
             frag_sm,
-            render_pass,
+            target,
             &GraphicsPipelineDesc {
                 cull_mode: vk::CullModeFlags::BACK,
                 depth_test_enable: true,
                 depth_write_enable: true,
+                depth_bias_enable,
                 ..Default::default()
             },
             instance_rate_starting_location,
@@ -409,16 +779,43 @@ pub struct ShaderModules {
     pub temporal_sm: ShaderModule,
     pub spatial_sm: ShaderModule,
     pub composition_sm: ShaderModule,
+    pub water_sm: ShaderModule,
+    pub hiz_sm: ShaderModule,
+    pub fog_sm: ShaderModule,
     pub taa_sm: ShaderModule,
+    pub dof_sm: ShaderModule,
     pub post_processing_sm: ShaderModule,
     pub player_collider_sm: ShaderModule,
     pub terrain_query_sm: ShaderModule,
+    pub sky_visibility_sm: ShaderModule,
+    pub sky_map_sm: ShaderModule,
     pub flora_vert_sm: ShaderModule,
     pub flora_frag_sm: ShaderModule,
     pub flora_lod_vert_sm: ShaderModule,
     pub flora_lod_frag_sm: ShaderModule,
+    /// Shared by both flora LODs in the alpha blended mode.
+    pub flora_blend_frag_sm: ShaderModule,
+    pub weather_vert_sm: ShaderModule,
+    pub weather_frag_sm: ShaderModule,
     pub leaves_shadow_vert_sm: ShaderModule,
     pub leaves_shadow_frag_sm: ShaderModule,
+    pub impostor_bake_vert_sm: ShaderModule,
+    pub impostor_bake_frag_sm: ShaderModule,
+    pub impostor_vert_sm: ShaderModule,
+    pub impostor_frag_sm: ShaderModule,
+    pub use_tiled_denoiser: bool,
+}
+
+impl ShaderModules {
+    /// Whether the workgroup size of `name` can be overridden, the tiled denoiser variants size
+    /// their shared tiles by a fixed workgroup dimension so they keep their own.
+    pub fn is_tunable(&self, name: &str) -> bool {
+        match name {
+            "tracer" | "god_ray" => true,
+            "temporal" | "spatial" => !self.use_tiled_denoiser,
+            _ => false,
+        }
+    }
 }
 
 pub struct ComputePipelines {
@@ -431,19 +828,72 @@ pub struct ComputePipelines {
     pub temporal_ppl: ComputePipeline,
     pub spatial_ppl: ComputePipeline,
     pub composition_ppl: ComputePipeline,
+    pub water_ppl: ComputePipeline,
+    pub hiz_ppl: ComputePipeline,
+    pub fog_ppl: ComputePipeline,
     pub taa_ppl: ComputePipeline,
+    pub dof_ppl: ComputePipeline,
     pub player_collider_ppl: ComputePipeline,
     pub terrain_query_ppl: ComputePipeline,
+    pub sky_visibility_ppl: ComputePipeline,
+    pub sky_map_ppl: ComputePipeline,
     pub post_processing_ppl: ComputePipeline,
 }
 
+impl ComputePipelines {
+    pub fn all(&self) -> [&ComputePipeline; 19] {
+        [
+            &self.tracer_ppl,
+            &self.tracer_shadow_ppl,
+            &self.vsm_creation_ppl,
+            &self.vsm_blur_h_ppl,
+            &self.vsm_blur_v_ppl,
+            &self.god_ray_ppl,
+            &self.temporal_ppl,
+            &self.spatial_ppl,
+            &self.composition_ppl,
+            &self.water_ppl,
+            &self.hiz_ppl,
+            &self.fog_ppl,
+            &self.taa_ppl,
+            &self.dof_ppl,
+            &self.player_collider_ppl,
+            &self.terrain_query_ppl,
+            &self.sky_visibility_ppl,
+            &self.sky_map_ppl,
+            &self.post_processing_ppl,
+        ]
+    }
+}
+
 pub struct RenderPasses {
     pub render_pass_color_and_depth: RenderPass,
     pub render_pass_depth: RenderPass,
+    pub render_pass_impostor: RenderPass,
 }
 
 pub struct GraphicsPipelines {
     pub flora_ppl: GraphicsPipeline,
     pub flora_lod_ppl: GraphicsPipeline,
+    pub flora_blend_ppl: GraphicsPipeline,
+    pub flora_lod_blend_ppl: GraphicsPipeline,
     pub leaves_shadow_lod_ppl: GraphicsPipeline,
+    pub weather_ppl: GraphicsPipeline,
+    pub impostor_bake_ppl: GraphicsPipeline,
+    pub impostor_ppl: GraphicsPipeline,
+}
+
+impl GraphicsPipelines {
+    pub fn all(&self) -> [&GraphicsPipeline; 8] {
+        [
+            &self.flora_ppl,
+            &self.flora_lod_ppl,
+            &self.flora_blend_ppl,
+            &self.flora_lod_blend_ppl,
+            &self.leaves_shadow_lod_ppl,
+            &self.weather_ppl,
+            &self.impostor_bake_ppl,
+            &self.impostor_ppl,
+        ]
+    }
 }
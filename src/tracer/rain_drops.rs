@@ -0,0 +1,66 @@
+use glam::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Width and height of the raindrop texture, in texels.
+pub const RAIN_DROP_TEX_EXTENT: u32 = 256;
+
+const DROP_COUNT: u32 = 160;
+/// In texels.
+const DROP_RADIUS_MIN: f32 = 2.0;
+const DROP_RADIUS_MAX: f32 = 7.0;
+
+/// Generates the tileable raindrop texture the post processing pass bends the view with.
+///
+/// Each texel is RGBA8, rg is the slope of the drop surface remapped to [0, 1], b the height
+/// of the drop, zero where there's none, and a the phase the drop's animation is offset by.
+pub fn gen_rain_drop_texels(seed: u64) -> Vec<u8> {
+    let extent = RAIN_DROP_TEX_EXTENT as i32;
+    let mut heights = vec![0.0_f32; (extent * extent) as usize];
+    let mut texels = vec![0_u8; (extent * extent * 4) as usize];
+    for texel in texels.chunks_exact_mut(4) {
+        texel[0] = 128;
+        texel[1] = 128;
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..DROP_COUNT {
+        let center = Vec2::new(
+            rng.random_range(0.0..extent as f32),
+            rng.random_range(0.0..extent as f32),
+        );
+        let radius = rng.random_range(DROP_RADIUS_MIN..DROP_RADIUS_MAX);
+        let phase = rng.random_range(0..=255_u8);
+
+        let reach = radius.ceil() as i32;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let offset = Vec2::new(dx as f32, dy as f32) + Vec2::splat(0.5) - center.fract();
+                let dist = offset.length() / radius;
+                if dist >= 1.0 {
+                    continue;
+                }
+                // wraps around so the texture tiles
+                let x = (center.x as i32 + dx).rem_euclid(extent);
+                let y = (center.y as i32 + dy).rem_euclid(extent);
+                let idx = (y * extent + x) as usize;
+
+                // a spherical cap, the taller drop wins where two overlap
+                let height = (1.0 - dist * dist).sqrt();
+                if height <= heights[idx] {
+                    continue;
+                }
+                heights[idx] = height;
+
+                let slope = offset / radius / height.max(0.25);
+                let slope = slope.clamp(Vec2::splat(-1.0), Vec2::splat(1.0));
+                let texel = &mut texels[idx * 4..idx * 4 + 4];
+                texel[0] = ((slope.x * 0.5 + 0.5) * 255.0).round() as u8;
+                texel[1] = ((slope.y * 0.5 + 0.5) * 255.0).round() as u8;
+                texel[2] = (height * 255.0).round().max(1.0) as u8;
+                texel[3] = phase;
+            }
+        }
+    }
+    texels
+}
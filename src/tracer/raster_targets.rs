@@ -0,0 +1,302 @@
+use super::{PipelineBuilder, TracerResources};
+use crate::vkn::{
+    record_begin_rendering, record_end_rendering, AttachmentType, CommandBuffer, Framebuffer,
+    RenderPass, RenderTarget, RenderingAttachment, RenderingFormats, RenderingTarget, Texture,
+    VulkanContext,
+};
+use ash::vk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterPass {
+    /// Flora and leaves into `gfx_output_tex` and `gfx_depth_tex`.
+    ColorAndDepth,
+    /// Leaf shadows into `shadow_map_tex`.
+    DepthOnly,
+    /// Tree bakes into `impostor_atlas_tex` and `impostor_depth_tex`.
+    Impostor,
+}
+
+/// Where the raster passes render to.
+pub enum RasterTargets {
+    /// Framebuffers bound to the textures, rebuilt whenever the textures are recreated.
+    RenderPass {
+        color_and_depth: RenderTarget,
+        depth_only: RenderTarget,
+        /// The atlas is never resized, so this one is built once.
+        impostor: RenderTarget,
+    },
+    /// `VK_KHR_dynamic_rendering`, the textures are named while recording so resizing leaves
+    /// this alone.
+    Dynamic {
+        color_and_depth: RenderingFormats,
+        depth_only: RenderingFormats,
+        impostor: RenderingFormats,
+    },
+}
+
+impl RasterTargets {
+    /// Uses dynamic rendering when the device has it.
+    pub fn new(vulkan_ctx: &VulkanContext, resources: &TracerResources) -> Self {
+        let gfx_output_tex = &resources.extent_dependent_resources.gfx_output_tex;
+        let gfx_depth_tex = &resources.extent_dependent_resources.gfx_depth_tex;
+        if vulkan_ctx.device().dynamic_rendering().is_some() {
+            return Self::Dynamic {
+                color_and_depth: RenderingFormats::from_textures(
+                    &[gfx_output_tex],
+                    Some(gfx_depth_tex),
+                ),
+                depth_only: RenderingFormats::from_textures(&[], Some(&resources.shadow_map_tex)),
+                impostor: RenderingFormats::from_textures(
+                    &[&resources.impostor_atlas_tex],
+                    Some(&resources.impostor_depth_tex),
+                ),
+            };
+        }
+
+        let render_passes = PipelineBuilder::create_render_passes(
+            vulkan_ctx,
+            gfx_output_tex.clone(),
+            gfx_depth_tex.clone(),
+            resources.shadow_map_tex.clone(),
+            resources.impostor_atlas_tex.clone(),
+            resources.impostor_depth_tex.clone(),
+        );
+        let framebuffer_color_and_depth = create_framebuffer_color_and_depth(
+            vulkan_ctx,
+            &render_passes.render_pass_color_and_depth,
+            gfx_output_tex,
+            gfx_depth_tex,
+        );
+        let framebuffer_depth_only = create_framebuffer_depth(
+            vulkan_ctx,
+            &render_passes.render_pass_depth,
+            &resources.shadow_map_tex,
+        );
+        let framebuffer_impostor = create_framebuffer_color_and_depth(
+            vulkan_ctx,
+            &render_passes.render_pass_impostor,
+            &resources.impostor_atlas_tex,
+            &resources.impostor_depth_tex,
+        );
+        Self::RenderPass {
+            color_and_depth: RenderTarget::new(
+                render_passes.render_pass_color_and_depth,
+                vec![framebuffer_color_and_depth],
+            ),
+            depth_only: RenderTarget::new(
+                render_passes.render_pass_depth,
+                vec![framebuffer_depth_only],
+            ),
+            impostor: RenderTarget::new(
+                render_passes.render_pass_impostor,
+                vec![framebuffer_impostor],
+            ),
+        }
+    }
+
+    /// What the pipelines of `pass` are built against.
+    pub fn rendering_target(&self, pass: RasterPass) -> RenderingTarget<'_> {
+        match (self, pass) {
+            (
+                Self::RenderPass {
+                    color_and_depth, ..
+                },
+                RasterPass::ColorAndDepth,
+            ) => RenderingTarget::RenderPass(color_and_depth.get_render_pass()),
+            (Self::RenderPass { depth_only, .. }, RasterPass::DepthOnly) => {
+                RenderingTarget::RenderPass(depth_only.get_render_pass())
+            }
+            (Self::RenderPass { impostor, .. }, RasterPass::Impostor) => {
+                RenderingTarget::RenderPass(impostor.get_render_pass())
+            }
+            (
+                Self::Dynamic {
+                    color_and_depth, ..
+                },
+                RasterPass::ColorAndDepth,
+            ) => RenderingTarget::Dynamic(color_and_depth),
+            (Self::Dynamic { depth_only, .. }, RasterPass::DepthOnly) => {
+                RenderingTarget::Dynamic(depth_only)
+            }
+            (Self::Dynamic { impostor, .. }, RasterPass::Impostor) => {
+                RenderingTarget::Dynamic(impostor)
+            }
+        }
+    }
+
+    /// Rebinds the framebuffers to the recreated textures.
+    pub fn on_resize(&mut self, vulkan_ctx: &VulkanContext, resources: &TracerResources) {
+        let Self::RenderPass {
+            color_and_depth,
+            depth_only,
+            ..
+        } = self
+        else {
+            return;
+        };
+        let framebuffer_color_and_depth = create_framebuffer_color_and_depth(
+            vulkan_ctx,
+            color_and_depth.get_render_pass(),
+            &resources.extent_dependent_resources.gfx_output_tex,
+            &resources.extent_dependent_resources.gfx_depth_tex,
+        );
+        let framebuffer_depth_only = create_framebuffer_depth(
+            vulkan_ctx,
+            depth_only.get_render_pass(),
+            &resources.shadow_map_tex,
+        );
+        *color_and_depth = RenderTarget::new(
+            color_and_depth.get_render_pass().clone(),
+            vec![framebuffer_color_and_depth],
+        );
+        *depth_only = RenderTarget::new(
+            depth_only.get_render_pass().clone(),
+            vec![framebuffer_depth_only],
+        );
+    }
+
+    /// The attachments of `pass` are loaded and stored in `GENERAL`, `clear_values` are in
+    /// attachment order, color first.
+    pub fn record_begin(
+        &self,
+        vulkan_ctx: &VulkanContext,
+        cmdbuf: &CommandBuffer,
+        pass: RasterPass,
+        resources: &TracerResources,
+        clear_values: &[vk::ClearValue],
+    ) {
+        match self {
+            Self::RenderPass {
+                color_and_depth,
+                depth_only,
+                impostor,
+            } => {
+                let render_target = match pass {
+                    RasterPass::ColorAndDepth => color_and_depth,
+                    RasterPass::DepthOnly => depth_only,
+                    RasterPass::Impostor => impostor,
+                };
+                render_target.record_begin(cmdbuf, clear_values);
+            }
+            Self::Dynamic { .. } => {
+                let attachments: Vec<_> = attachments_of(pass, resources)
+                    .into_iter()
+                    .zip(clear_values)
+                    .map(|((texture, ty), clear_value)| RenderingAttachment {
+                        texture,
+                        ty,
+                        load_op: vk::AttachmentLoadOp::LOAD,
+                        store_op: vk::AttachmentStoreOp::STORE,
+                        clear_value: *clear_value,
+                        layout: vk::ImageLayout::GENERAL,
+                    })
+                    .collect();
+                record_begin_rendering(vulkan_ctx.device(), cmdbuf, &attachments);
+            }
+        }
+    }
+
+    /// Leaves the attachments of `pass` in `GENERAL`.
+    pub fn record_end(
+        &self,
+        vulkan_ctx: &VulkanContext,
+        cmdbuf: &CommandBuffer,
+        pass: RasterPass,
+        resources: &TracerResources,
+    ) {
+        match self {
+            Self::RenderPass {
+                color_and_depth,
+                depth_only,
+                impostor,
+            } => {
+                let render_target = match pass {
+                    RasterPass::ColorAndDepth => color_and_depth,
+                    RasterPass::DepthOnly => depth_only,
+                    RasterPass::Impostor => impostor,
+                };
+                render_target.record_end(cmdbuf);
+                let desc = render_target.get_desc();
+                for ((texture, _), attachment) in attachments_of(pass, resources)
+                    .into_iter()
+                    .zip(&desc.attachments)
+                {
+                    texture.get_image().set_layout(0, attachment.final_layout);
+                }
+            }
+            Self::Dynamic { .. } => record_end_rendering(vulkan_ctx.device(), cmdbuf),
+        }
+    }
+}
+
+/// In the order of the render pass attachments.
+fn attachments_of(
+    pass: RasterPass,
+    resources: &TracerResources,
+) -> Vec<(&Texture, AttachmentType)> {
+    match pass {
+        RasterPass::ColorAndDepth => vec![
+            (
+                &resources.extent_dependent_resources.gfx_output_tex,
+                AttachmentType::Color,
+            ),
+            (
+                &resources.extent_dependent_resources.gfx_depth_tex,
+                AttachmentType::Depth,
+            ),
+        ],
+        RasterPass::DepthOnly => vec![(&resources.shadow_map_tex, AttachmentType::Depth)],
+        RasterPass::Impostor => vec![
+            (&resources.impostor_atlas_tex, AttachmentType::Color),
+            (&resources.impostor_depth_tex, AttachmentType::Depth),
+        ],
+    }
+}
+
+/// A framebuffer that contains the color and depth textures for the main render pass
+fn create_framebuffer_color_and_depth(
+    vulkan_ctx: &VulkanContext,
+    render_pass: &RenderPass,
+    target_texture: &Texture,
+    depth_texture: &Texture,
+) -> Framebuffer {
+    let target_view = target_texture.get_image_view().as_raw();
+    let depth_image_view = depth_texture.get_image_view().as_raw();
+
+    let target_image_extent = target_texture
+        .get_image()
+        .get_desc()
+        .extent
+        .as_extent_2d()
+        .unwrap();
+
+    Framebuffer::new(
+        vulkan_ctx.clone(),
+        render_pass,
+        &[target_view, depth_image_view],
+        target_image_extent,
+    )
+    .unwrap()
+}
+
+/// A framebuffer that contains the shadow map texture
+fn create_framebuffer_depth(
+    vulkan_ctx: &VulkanContext,
+    render_pass: &RenderPass,
+    shadow_map_tex: &Texture,
+) -> Framebuffer {
+    let shadow_image_view = shadow_map_tex.get_image_view().as_raw();
+    let shadow_image_extent = shadow_map_tex
+        .get_image()
+        .get_desc()
+        .extent
+        .as_extent_2d()
+        .unwrap();
+    Framebuffer::new(
+        vulkan_ctx.clone(),
+        render_pass,
+        &[shadow_image_view],
+        shadow_image_extent,
+    )
+    .unwrap()
+}
@@ -1,67 +1,38 @@
 use crate::{
+    builder::FloraType,
     resource::Resource,
     tracer::{
-        flora_construct::{gen_grass, gen_lavender},
         leaves_construct::generate_indexed_voxel_leaves,
-        DenoiserResources, ExtentDependentResources, Vertex,
+        terrain_query::{TerrainQueryRay, TerrainQueryResult},
+        weather_particles::gen_weather_particles,
+        DenoiserPrecision, DenoiserResources, ExtentDependentResources, PassScales, Vertex,
+        IMPOSTOR_ATLAS_SIZE, SKY_MAP_EXTENT,
     },
     util::get_project_root,
     vkn::{
-        Allocator, Buffer, BufferUsage, Device, Extent2D, Extent3D, ImageDesc, ShaderModule,
-        Texture, VulkanContext,
+        execute_one_time_command, Allocator, Buffer, BufferUsage, ClearValue, ColorClearValue,
+        Device, Extent2D, Extent3D, ImageDesc, ShaderModule, Texture, VulkanContext,
     },
 };
 use ash::vk;
 use resource_container_derive::ResourceContainer;
 
+/// The voxel mesh of one flora type, drawn once per instance.
 #[derive(ResourceContainer)]
-pub struct GrassBladeResources {
+pub struct FloraMeshResources {
     pub vertices: Resource<Buffer>,
     pub indices: Resource<Buffer>,
     pub indices_len: u32,
 }
 
-impl GrassBladeResources {
-    pub fn new(device: Device, allocator: Allocator, is_lod_used: bool) -> Self {
-        let (vertices_data, indices_data) = gen_grass(is_lod_used).unwrap();
-        let indices_len = indices_data.len() as u32;
-
-        let vertices = Buffer::new_sized(
-            device.clone(),
-            allocator.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::VERTEX_BUFFER),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-            (std::mem::size_of::<Vertex>() * vertices_data.len()) as u64,
-        );
-        vertices.fill(&vertices_data).unwrap();
-
-        let indices = Buffer::new_sized(
-            device.clone(),
-            allocator.clone(),
-            BufferUsage::from_flags(vk::BufferUsageFlags::INDEX_BUFFER),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-            (std::mem::size_of::<u32>() * indices_data.len()) as u64,
-        );
-        indices.fill(&indices_data).unwrap();
-
-        Self {
-            vertices: Resource::new(vertices),
-            indices: Resource::new(indices),
-            indices_len,
-        }
-    }
-}
-
-#[derive(ResourceContainer)]
-pub struct LavenderResources {
-    pub vertices: Resource<Buffer>,
-    pub indices: Resource<Buffer>,
-    pub indices_len: u32,
-}
-
-impl LavenderResources {
-    pub fn new(device: Device, allocator: Allocator, is_lod_used: bool) -> Self {
-        let (vertices_data, indices_data) = gen_lavender(is_lod_used).unwrap();
+impl FloraMeshResources {
+    pub fn new(
+        device: Device,
+        allocator: Allocator,
+        flora_type: FloraType,
+        is_lod_used: bool,
+    ) -> Self {
+        let (vertices_data, indices_data) = (flora_type.desc().mesh)(is_lod_used).unwrap();
         let indices_len = indices_data.len() as u32;
 
         let vertices = Buffer::new_sized(
@@ -164,42 +135,41 @@ impl LeavesResources {
     }
 }
 
+/// The resources shared by all frames in flight, the per-frame ones are in
+/// [`super::FrameResources`].
 #[derive(ResourceContainer)]
 pub struct TracerResources {
-    pub gui_input: Resource<Buffer>,
-    pub sun_info: Resource<Buffer>,
-    pub shading_info: Resource<Buffer>,
-    pub camera_info: Resource<Buffer>,
-    pub camera_info_prev_frame: Resource<Buffer>,
-    pub shadow_camera_info: Resource<Buffer>,
-    pub env_info: Resource<Buffer>,
-    pub starlight_info: Resource<Buffer>,
     // pub grass_info: Resource<Buffer>,
     // pub lavender_info: Resource<Buffer>,
     // pub leaves_info: Resource<Buffer>,
-    pub voxel_colors: Resource<Buffer>,
-    pub taa_info: Resource<Buffer>,
-    pub god_ray_info: Resource<Buffer>,
-    pub post_processing_info: Resource<Buffer>,
-    pub player_collider_info: Resource<Buffer>,
-    pub player_collision_result: Resource<Buffer>,
+    /// The terrain queries block until they're read back, so they don't overlap a frame.
     pub terrain_query_count: Resource<Buffer>,
     pub terrain_query_info: Resource<Buffer>,
     pub terrain_query_result: Resource<Buffer>,
+    /// Drawn as rain streaks or snowflakes by the weather pass, see `gen_weather_particles`.
+    pub weather_particles: Resource<Buffer>,
 
-    pub grass_blade_resources: GrassBladeResources,
-    pub lavender_resources: LavenderResources,
+    /// Indexed by [`FloraType`].
+    pub flora_meshes: Vec<FloraMeshResources>,
     pub leaves_resources: LeavesResources,
 
-    pub grass_blade_resources_lod: GrassBladeResources,
-    pub lavender_resources_lod: LavenderResources,
+    pub flora_meshes_lod: Vec<FloraMeshResources>,
     pub leaves_resources_lod: LeavesResources,
 
     pub shadow_map_tex: Resource<Texture>,
     pub shadow_map_tex_for_vsm_ping: Resource<Texture>,
     pub shadow_map_tex_for_vsm_pong: Resource<Texture>,
 
+    /// The leaves of every tree seen from the side, the color gradient in r and the coverage in
+    /// g, see `ImpostorAtlas`.
+    pub impostor_atlas_tex: Resource<Texture>,
+    /// Only used while baking into `impostor_atlas_tex`.
+    pub impostor_depth_tex: Resource<Texture>,
+
     pub star_noise_tex: Resource<Texture>,
+    pub sky_visibility_tex: Resource<Texture>,
+    /// Octahedral, written by the sky map pass a band at a time.
+    pub sky_map_tex: Resource<Texture>,
 
     pub scalar_bn: Resource<Texture>,
     pub unit_vec2_bn: Resource<Texture>,
@@ -217,160 +187,17 @@ impl TracerResources {
     pub fn new(
         vulkan_ctx: &VulkanContext,
         allocator: Allocator,
-        tracer_sm: &ShaderModule,
-        tracer_shadow_sm: &ShaderModule,
-        composition_sm: &ShaderModule,
-        temporal_sm: &ShaderModule,
-        spatial_sm: &ShaderModule,
-        taa_sm: &ShaderModule,
-        god_ray_sm: &ShaderModule,
-        post_processing_sm: &ShaderModule,
-        player_collider_sm: &ShaderModule,
         terrain_query_sm: &ShaderModule,
         rendering_extent: Extent2D,
         screen_extent: Extent2D,
+        pass_scales: PassScales,
         shadow_map_extent: Extent2D,
+        sky_visibility_extent: Extent2D,
         max_terrain_queries: u32,
+        denoiser_precision: DenoiserPrecision,
     ) -> Self {
         let device = vulkan_ctx.device();
 
-        let gui_input_layout = tracer_sm.get_buffer_layout("U_GuiInput").unwrap();
-        let gui_input = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            gui_input_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let sun_info_layout = tracer_sm.get_buffer_layout("U_SunInfo").unwrap();
-        let sun_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            sun_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let shading_info_layout = tracer_sm.get_buffer_layout("U_ShadingInfo").unwrap();
-        let shading_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            shading_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let camera_info_layout = tracer_sm.get_buffer_layout("U_CameraInfo").unwrap();
-        let camera_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            camera_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let camera_info_prev_frame_layout = tracer_sm
-            .get_buffer_layout("U_CameraInfoPrevFrame")
-            .unwrap();
-        let camera_info_prev_frame = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            camera_info_prev_frame_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let shadow_camera_info_layout = tracer_shadow_sm
-            .get_buffer_layout("U_ShadowCameraInfo")
-            .unwrap();
-        let shadow_camera_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            shadow_camera_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let env_info_layout = tracer_sm.get_buffer_layout("U_EnvInfo").unwrap();
-        let env_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            env_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let starlight_info_layout = composition_sm.get_buffer_layout("U_StarlightInfo").unwrap();
-        let starlight_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            starlight_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let voxel_colors_layout = tracer_sm.get_buffer_layout("U_VoxelColors").unwrap();
-        let voxel_colors = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            voxel_colors_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let taa_info_layout = taa_sm.get_buffer_layout("U_TaaInfo").unwrap();
-        let taa_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            taa_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let god_ray_info_layout = god_ray_sm.get_buffer_layout("U_GodRayInfo").unwrap();
-        let god_ray_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            god_ray_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let post_processing_info_layout = post_processing_sm
-            .get_buffer_layout("U_PostProcessingInfo")
-            .unwrap();
-        let post_processing_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            post_processing_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let player_collider_info_layout = player_collider_sm
-            .get_buffer_layout("U_PlayerColliderInfo")
-            .unwrap();
-        let player_collider_info = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            player_collider_info_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
-        let player_collision_result_layout = player_collider_sm
-            .get_buffer_layout("B_PlayerCollisionResult")
-            .unwrap();
-
-        let player_collision_result = Buffer::from_buffer_layout(
-            device.clone(),
-            allocator.clone(),
-            player_collision_result_layout.clone(),
-            BufferUsage::empty(),
-            gpu_allocator::MemoryLocation::CpuToGpu,
-        );
-
         let terrain_query_count_layout = terrain_query_sm
             .get_buffer_layout("U_TerrainQueryCount")
             .unwrap();
@@ -387,7 +214,7 @@ impl TracerResources {
             allocator.clone(),
             BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
             gpu_allocator::MemoryLocation::CpuToGpu,
-            (max_terrain_queries * 2 * std::mem::size_of::<f32>() as u32) as u64,
+            max_terrain_queries as u64 * std::mem::size_of::<TerrainQueryRay>() as u64,
         );
 
         let terrain_query_result = Buffer::new_sized(
@@ -395,8 +222,18 @@ impl TracerResources {
             allocator.clone(),
             BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
             gpu_allocator::MemoryLocation::CpuToGpu,
-            (max_terrain_queries * std::mem::size_of::<f32>() as u32) as u64,
+            max_terrain_queries as u64 * std::mem::size_of::<TerrainQueryResult>() as u64,
+        );
+
+        let weather_particles_data = gen_weather_particles(0);
+        let weather_particles = Buffer::new_sized(
+            device.clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            std::mem::size_of_val(weather_particles_data.as_slice()) as u64,
         );
+        weather_particles.fill(&weather_particles_data).unwrap();
 
         let shadow_map_tex = Self::create_shadow_map_tex(
             device.clone(),
@@ -414,14 +251,25 @@ impl TracerResources {
             shadow_map_extent.into(),
         );
 
+        let (impostor_atlas_tex, impostor_depth_tex) =
+            Self::create_impostor_textures(device.clone(), allocator.clone());
+
         let star_noise_tex =
             Self::create_star_noise_tex(vulkan_ctx, allocator.clone(), Extent2D::new(128, 128));
+        let sky_visibility_tex =
+            Self::create_sky_visibility_tex(vulkan_ctx, allocator.clone(), sky_visibility_extent);
+        let sky_map_tex = Self::create_sky_map_tex(
+            vulkan_ctx,
+            allocator.clone(),
+            Extent2D::new(SKY_MAP_EXTENT, SKY_MAP_EXTENT),
+        );
 
         let extent_dependent_resources = ExtentDependentResources::new(
             device.clone(),
             allocator.clone(),
             rendering_extent,
             screen_extent,
+            pass_scales,
         );
 
         let scalar_bn = create_bn(
@@ -461,48 +309,44 @@ impl TracerResources {
             "fast/weighted_cosine/out_",
         );
 
-        let grass_blade_resources =
-            GrassBladeResources::new(device.clone(), allocator.clone(), false);
-        let lavender_resources = LavenderResources::new(device.clone(), allocator.clone(), false);
+        let create_flora_meshes = |is_lod_used: bool| {
+            FloraType::all()
+                .map(|flora_type| {
+                    FloraMeshResources::new(
+                        device.clone(),
+                        allocator.clone(),
+                        flora_type,
+                        is_lod_used,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let flora_meshes = create_flora_meshes(false);
         let leaves_resources = LeavesResources::new(device.clone(), allocator.clone(), false);
-        let grass_blade_resources_lod =
-            GrassBladeResources::new(device.clone(), allocator.clone(), true);
-        let lavender_resources_lod =
-            LavenderResources::new(device.clone(), allocator.clone(), true);
+        let flora_meshes_lod = create_flora_meshes(true);
         let leaves_resources_lod = LeavesResources::new(device.clone(), allocator.clone(), true);
 
         return Self {
-            gui_input: Resource::new(gui_input),
-            sun_info: Resource::new(sun_info),
-            shading_info: Resource::new(shading_info),
-            camera_info: Resource::new(camera_info),
-            camera_info_prev_frame: Resource::new(camera_info_prev_frame),
-            shadow_camera_info: Resource::new(shadow_camera_info),
-            env_info: Resource::new(env_info),
-            starlight_info: Resource::new(starlight_info),
             // grass_info: Resource::new(grass_info),
             // lavender_info: Resource::new(lavender_info),
             // leaves_info: Resource::new(leaves_info),
-            voxel_colors: Resource::new(voxel_colors),
-            taa_info: Resource::new(taa_info),
-            god_ray_info: Resource::new(god_ray_info),
-            post_processing_info: Resource::new(post_processing_info),
-            player_collider_info: Resource::new(player_collider_info),
-            player_collision_result: Resource::new(player_collision_result),
             terrain_query_count: Resource::new(terrain_query_count),
             terrain_query_info: Resource::new(terrain_query_info),
             terrain_query_result: Resource::new(terrain_query_result),
-            grass_blade_resources,
-            lavender_resources,
+            weather_particles: Resource::new(weather_particles),
+            flora_meshes,
             leaves_resources,
-            grass_blade_resources_lod,
-            lavender_resources_lod,
+            flora_meshes_lod,
             leaves_resources_lod,
             extent_dependent_resources,
             shadow_map_tex: Resource::new(shadow_map_tex),
             shadow_map_tex_for_vsm_ping: Resource::new(shadow_map_tex_for_vsm_ping),
             shadow_map_tex_for_vsm_pong: Resource::new(shadow_map_tex_for_vsm_pong),
+            impostor_atlas_tex: Resource::new(impostor_atlas_tex),
+            impostor_depth_tex: Resource::new(impostor_depth_tex),
             star_noise_tex: Resource::new(star_noise_tex),
+            sky_visibility_tex: Resource::new(sky_visibility_tex),
+            sky_map_tex: Resource::new(sky_map_tex),
             scalar_bn: Resource::new(scalar_bn),
             unit_vec2_bn: Resource::new(unit_vec2_bn),
             unit_vec3_bn: Resource::new(unit_vec3_bn),
@@ -513,8 +357,7 @@ impl TracerResources {
                 device.clone(),
                 allocator.clone(),
                 rendering_extent,
-                temporal_sm,
-                spatial_sm,
+                denoiser_precision,
             ),
         };
 
@@ -561,12 +404,14 @@ impl TracerResources {
         allocator: Allocator,
         rendering_extent: Extent2D,
         screen_extent: Extent2D,
+        pass_scales: PassScales,
     ) {
         self.extent_dependent_resources.on_resize(
             device,
             allocator,
             rendering_extent,
             screen_extent,
+            pass_scales,
         );
         self.denoiser_resources.on_resize(rendering_extent);
     }
@@ -602,6 +447,76 @@ impl TracerResources {
         tex
     }
 
+    /// Starts fully visible, the sky visibility pass fills it in once the scene is built.
+    fn create_sky_visibility_tex(
+        vulkan_ctx: &VulkanContext,
+        allocator: Allocator,
+        extent: Extent2D,
+    ) -> Texture {
+        let img_desc = ImageDesc {
+            extent: extent.into(),
+            format: vk::Format::R8_UNORM,
+            usage: vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_DST,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        };
+        let sam_desc = Default::default();
+        let tex = Texture::new(vulkan_ctx.device().clone(), allocator, &img_desc, &sam_desc);
+
+        execute_one_time_command(
+            vulkan_ctx.device(),
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                tex.get_image().record_clear(
+                    cmdbuf,
+                    Some(vk::ImageLayout::GENERAL),
+                    0,
+                    ClearValue::Color(ColorClearValue::Float([1.0, 1.0, 1.0, 1.0])),
+                );
+            },
+        );
+        tex
+    }
+
+    /// Starts black, the first frame renders all of it.
+    fn create_sky_map_tex(
+        vulkan_ctx: &VulkanContext,
+        allocator: Allocator,
+        extent: Extent2D,
+    ) -> Texture {
+        let img_desc = ImageDesc {
+            extent: extent.into(),
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            usage: vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_DST,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        };
+        let sam_desc = Default::default();
+        let tex = Texture::new(vulkan_ctx.device().clone(), allocator, &img_desc, &sam_desc);
+
+        execute_one_time_command(
+            vulkan_ctx.device(),
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                tex.get_image().record_clear(
+                    cmdbuf,
+                    Some(vk::ImageLayout::GENERAL),
+                    0,
+                    ClearValue::Color(ColorClearValue::Float([0.0, 0.0, 0.0, 1.0])),
+                );
+            },
+        );
+        tex
+    }
+
     fn create_shadow_map_tex(
         device: Device,
         allocator: Allocator,
@@ -638,4 +553,30 @@ impl TracerResources {
         let sam_desc = Default::default();
         Texture::new(device, allocator, &tex_desc, &sam_desc)
     }
+
+    /// The atlas and the depth it is baked against, both square and kept for the whole run.
+    fn create_impostor_textures(device: Device, allocator: Allocator) -> (Texture, Texture) {
+        let extent = Extent3D::new(IMPOSTOR_ATLAS_SIZE, IMPOSTOR_ATLAS_SIZE, 1);
+        let atlas_desc = ImageDesc {
+            extent,
+            format: vk::Format::R8G8_UNORM,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        };
+        let depth_desc = ImageDesc {
+            extent,
+            format: vk::Format::D32_SFLOAT,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            aspect: vk::ImageAspectFlags::DEPTH,
+            ..Default::default()
+        };
+        let sam_desc = Default::default();
+        (
+            Texture::new(device.clone(), allocator.clone(), &atlas_desc, &sam_desc),
+            Texture::new(device, allocator, &depth_desc, &sam_desc),
+        )
+    }
 }
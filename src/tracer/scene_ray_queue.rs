@@ -0,0 +1,218 @@
+use crate::{
+    builder::{ContreeBuilderResources, SceneAccelBuilderResources},
+    resource::{Resource, ResourceContainer},
+    tracer::terrain_query::{TerrainQueryRay, TerrainQueryResult},
+    vkn::{
+        Allocator, Buffer, BufferUsage, CommandBuffer, ComputePipeline, DescriptorPool, Extent3D,
+        MemoryBarrier, PipelineBarrier, PlainMemberTypeWithData, ShaderModule,
+        StructMemberDataBuilder, VulkanContext,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use resource_container_derive::ResourceContainer;
+
+use super::RayHit;
+
+/// Bound under the names `terrain_query.comp` expects, apart from the tracer's own terrain
+/// query buffers so the blocking queries can't overwrite a batch still in flight.
+#[derive(ResourceContainer)]
+struct SceneRayQueueResources {
+    terrain_query_count: Resource<Buffer>,
+    terrain_query_info: Resource<Buffer>,
+    terrain_query_result: Resource<Buffer>,
+}
+
+enum SceneRayBatch {
+    Idle,
+    /// Written to the buffers, dispatched by the next recorded frame.
+    Queued {
+        rays: Vec<TerrainQueryRay>,
+    },
+    /// Readable once the frame timeline reaches `frame_value`.
+    InFlight {
+        rays: Vec<TerrainQueryRay>,
+        frame_value: u64,
+    },
+}
+
+/// Casts batches of rays through the scene in the frame's command buffer instead of a blocking
+/// submission of their own. A batch is read back a frame or more later, one at a time.
+pub struct SceneRayQueue {
+    vulkan_ctx: VulkanContext,
+    resources: SceneRayQueueResources,
+    _pool: DescriptorPool,
+    ppl: ComputePipeline,
+    batch: SceneRayBatch,
+    max_ray_count: u32,
+}
+
+impl SceneRayQueue {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        terrain_query_sm: &ShaderModule,
+        contree_builder_resources: &ContreeBuilderResources,
+        scene_accel_resources: &SceneAccelBuilderResources,
+        max_ray_count: u32,
+    ) -> Self {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let count_layout = terrain_query_sm
+            .get_buffer_layout("U_TerrainQueryCount")
+            .unwrap();
+        let terrain_query_count = Buffer::from_buffer_layout(
+            device.clone(),
+            allocator.clone(),
+            count_layout.clone(),
+            BufferUsage::empty(),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+        );
+        let terrain_query_info = Buffer::new_sized(
+            device.clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            max_ray_count as u64 * std::mem::size_of::<TerrainQueryRay>() as u64,
+        );
+        let terrain_query_result = Buffer::new_sized(
+            device.clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(vk::BufferUsageFlags::STORAGE_BUFFER),
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            max_ray_count as u64 * std::mem::size_of::<TerrainQueryResult>() as u64,
+        );
+        let resources = SceneRayQueueResources {
+            terrain_query_count: Resource::new(terrain_query_count),
+            terrain_query_info: Resource::new(terrain_query_info),
+            terrain_query_result: Resource::new(terrain_query_result),
+        };
+
+        let ppl = ComputePipeline::new(
+            device,
+            terrain_query_sm,
+            &pool,
+            &[
+                &resources as &dyn ResourceContainer,
+                contree_builder_resources,
+                scene_accel_resources,
+            ],
+        );
+
+        Self {
+            vulkan_ctx,
+            resources,
+            _pool: pool,
+            ppl,
+            batch: SceneRayBatch::Idle,
+            max_ray_count,
+        }
+    }
+
+    pub fn update_sets(
+        &self,
+        contree_builder_resources: &ContreeBuilderResources,
+        scene_accel_resources: &SceneAccelBuilderResources,
+    ) {
+        self.ppl
+            .auto_update_descriptor_sets(&[
+                &self.resources as &dyn ResourceContainer,
+                contree_builder_resources,
+                scene_accel_resources,
+            ])
+            .unwrap();
+    }
+
+    /// Queues a batch for the next recorded frame. Returns `false` without queueing anything while
+    /// the previous batch hasn't been taken by [`Self::take_hits`] yet.
+    pub(super) fn push(&mut self, rays: Vec<TerrainQueryRay>) -> Result<bool> {
+        if !matches!(self.batch, SceneRayBatch::Idle) {
+            return Ok(false);
+        }
+        if rays.len() > self.max_ray_count as usize {
+            bail!(
+                "{} scene rays in a batch, at most {} fit",
+                rays.len(),
+                self.max_ray_count
+            );
+        }
+        if rays.is_empty() {
+            // nothing to cast, the empty hits are there right away
+            self.batch = SceneRayBatch::InFlight {
+                rays,
+                frame_value: 0,
+            };
+            return Ok(true);
+        }
+
+        // nothing on the GPU reads the buffers while no batch is queued or in flight
+        let count_data = StructMemberDataBuilder::from_buffer(&self.resources.terrain_query_count)
+            .set_field(
+                "valid_query_count",
+                PlainMemberTypeWithData::UInt(rays.len() as u32),
+            )
+            .build()?;
+        self.resources
+            .terrain_query_count
+            .fill_with_raw_u8(&count_data)?;
+        self.resources.terrain_query_info.fill(&rays)?;
+        self.batch = SceneRayBatch::Queued { rays };
+        Ok(true)
+    }
+
+    /// Dispatches the queued batch, if any, into the frame being recorded.
+    pub fn record(&mut self, cmdbuf: &CommandBuffer) {
+        let SceneRayBatch::Queued { rays } =
+            std::mem::replace(&mut self.batch, SceneRayBatch::Idle)
+        else {
+            return;
+        };
+        self.ppl
+            .record(cmdbuf, Extent3D::new(rays.len() as u32, 1, 1), None);
+        PipelineBarrier::new(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::HOST,
+            vec![MemoryBarrier::new(
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::HOST_READ,
+            )],
+        )
+        .record_insert(self.vulkan_ctx.device(), cmdbuf);
+
+        // the frame being recorded is submitted next and signals the value after the latest one
+        let frame_value = self.vulkan_ctx.frame_timeline().submitted_value() + 1;
+        self.batch = SceneRayBatch::InFlight { rays, frame_value };
+    }
+
+    /// The hits of the batch in flight, in the order its rays were pushed, once its frame is
+    /// done. `None` while it still runs or when no batch was pushed.
+    pub fn take_hits(&mut self) -> Result<Option<Vec<Option<RayHit>>>> {
+        let SceneRayBatch::InFlight { frame_value, .. } = self.batch else {
+            return Ok(None);
+        };
+        if self.vulkan_ctx.frame_timeline().completed_value()? < frame_value {
+            return Ok(None);
+        }
+        let SceneRayBatch::InFlight { rays, .. } =
+            std::mem::replace(&mut self.batch, SceneRayBatch::Idle)
+        else {
+            unreachable!();
+        };
+        if rays.is_empty() {
+            return Ok(Some(vec![]));
+        }
+
+        let raw_data = self.resources.terrain_query_result.read_back_range(
+            0,
+            rays.len() as u64 * std::mem::size_of::<TerrainQueryResult>() as u64,
+        )?;
+        let results: Vec<TerrainQueryResult> = bytemuck::pod_collect_to_vec(&raw_data);
+        Ok(Some(
+            rays.iter()
+                .zip(results)
+                .map(|(ray, result)| result.into_ray_hit(ray))
+                .collect(),
+        ))
+    }
+}
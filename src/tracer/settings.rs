@@ -0,0 +1,555 @@
+use crate::tracer::{PlayerColliderDesc, ShadowBiasDesc};
+use crate::util::get_sun_dir;
+use glam::Vec3;
+
+/// [0, 1] sRGB, as the shaders take colors.
+fn color_to_vec3(color: egui::Color32) -> Vec3 {
+    Vec3::new(
+        color.r() as f32 / 255.0,
+        color.g() as f32 / 255.0,
+        color.b() as f32 / 255.0,
+    )
+}
+
+fn color_edit(ui: &mut egui::Ui, label: &str, color: &mut egui::Color32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.color_edit_button_srgba(color);
+    });
+}
+
+/// Values handed to the shaders for ad hoc debugging.
+#[derive(Debug, Clone)]
+pub struct DebugSettings {
+    pub float: f32,
+    pub bool: bool,
+    pub uint: u32,
+}
+
+impl Default for DebugSettings {
+    fn default() -> Self {
+        Self {
+            float: 0.0,
+            bool: true,
+            uint: 0,
+        }
+    }
+}
+
+impl DebugSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.float, 0.0..=10.0).text("Debug Float"));
+        ui.add(egui::Slider::new(&mut self.uint, 0..=100).text("Debug UInt"));
+        ui.add(egui::Checkbox::new(&mut self.bool, "Debug Bool"));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SunSettings {
+    /// Normalized to [-1, 1], the sine of the elevation.
+    pub altitude: f32,
+    /// Normalized to [0, 1).
+    pub azimuth: f32,
+    /// Relative to the sky.
+    pub size: f32,
+    pub color: egui::Color32,
+    pub luminance: f32,
+    pub ambient_light: egui::Color32,
+}
+
+impl Default for SunSettings {
+    fn default() -> Self {
+        Self {
+            altitude: 0.25,
+            azimuth: 0.8,
+            size: 0.1,
+            color: egui::Color32::from_rgb(255, 233, 144),
+            luminance: 1.0,
+            ambient_light: egui::Color32::from_rgb(100, 48, 3),
+        }
+    }
+}
+
+impl SunSettings {
+    pub fn sun_dir(&self) -> Vec3 {
+        get_sun_dir(self.altitude.asin().to_degrees(), self.azimuth * 360.0)
+    }
+
+    pub fn color_vec3(&self) -> Vec3 {
+        color_to_vec3(self.color)
+    }
+
+    pub fn ambient_light_vec3(&self) -> Vec3 {
+        color_to_vec3(self.ambient_light)
+    }
+
+    /// Leaves the position of the sun alone, the world clock sets it.
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.size, 0.0..=1.0).text("Size (relative)"));
+        color_edit(ui, "Sun Color:", &mut self.color);
+        ui.add(egui::Slider::new(&mut self.luminance, 0.0..=10.0).text("Sun Luminance"));
+        color_edit(ui, "Ambient Light:", &mut self.ambient_light);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TemporalDenoiserSettings {
+    pub position_phi: f32,
+    pub alpha: f32,
+}
+
+impl Default for TemporalDenoiserSettings {
+    fn default() -> Self {
+        Self {
+            position_phi: 0.8,
+            alpha: 0.08,
+        }
+    }
+}
+
+impl TemporalDenoiserSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.position_phi, 0.0..=1.0).text("Position Phi"));
+        ui.add(egui::Slider::new(&mut self.alpha, 0.0..=1.0).text("Alpha"));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpatialDenoiserSettings {
+    pub phi_c: f32,
+    pub phi_n: f32,
+    pub phi_p: f32,
+    pub min_phi_z: f32,
+    pub max_phi_z: f32,
+    pub phi_z_stable_sample_count: f32,
+    pub is_changing_lum_phi: bool,
+    pub is_enabled: bool,
+    /// Always odd.
+    pub a_trous_iteration_count: u32,
+}
+
+impl Default for SpatialDenoiserSettings {
+    fn default() -> Self {
+        Self {
+            phi_c: 0.75,
+            phi_n: 20.0,
+            phi_p: 0.05,
+            min_phi_z: 0.0,
+            max_phi_z: 0.5,
+            phi_z_stable_sample_count: 0.05,
+            is_changing_lum_phi: true,
+            is_enabled: true,
+            a_trous_iteration_count: 3,
+        }
+    }
+}
+
+impl SpatialDenoiserSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.phi_c, 0.0..=1.0).text("Phi C"));
+        ui.add(egui::Slider::new(&mut self.phi_n, 0.0..=1.0).text("Phi N"));
+        ui.add(egui::Slider::new(&mut self.phi_p, 0.0..=1.0).text("Phi P"));
+        ui.add(egui::Slider::new(&mut self.min_phi_z, 0.0..=1.0).text("Min Phi Z"));
+        ui.add(egui::Slider::new(&mut self.max_phi_z, 0.0..=1.0).text("Max Phi Z"));
+        ui.add(
+            egui::Slider::new(&mut self.phi_z_stable_sample_count, 0.0..=1.0)
+                .text("Phi Z Stable Sample Count"),
+        );
+        ui.add(egui::Checkbox::new(
+            &mut self.is_changing_lum_phi,
+            "Changing Luminance Phi",
+        ));
+        ui.add(egui::Checkbox::new(
+            &mut self.is_enabled,
+            "Enable Spatial Denoising",
+        ));
+        ui.horizontal(|ui| {
+            ui.label("A-Trous Iterations:");
+            let mut iteration_value = self.a_trous_iteration_count as i32;
+            if ui
+                .add(egui::Slider::new(&mut iteration_value, 1..=5).step_by(2.0))
+                .changed()
+            {
+                // Ensure only odd values (1, 3, 5)
+                if iteration_value % 2 == 0 {
+                    iteration_value += 1;
+                }
+                self.a_trous_iteration_count = iteration_value as u32;
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DenoiserSettings {
+    pub temporal: TemporalDenoiserSettings,
+    pub spatial: SpatialDenoiserSettings,
+}
+
+#[derive(Debug, Clone)]
+pub struct GodRaySettings {
+    pub max_depth: f32,
+    pub max_checks: u32,
+    pub weight: f32,
+    pub color: egui::Color32,
+}
+
+impl Default for GodRaySettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 2.0,
+            max_checks: 32,
+            weight: 0.4,
+            color: egui::Color32::from_rgb(255, 240, 178),
+        }
+    }
+}
+
+impl GodRaySettings {
+    pub fn color_vec3(&self) -> Vec3 {
+        color_to_vec3(self.color)
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.max_depth, 0.1..=10.0).text("Max Depth"));
+        ui.add(egui::Slider::new(&mut self.max_checks, 1..=64).text("Max Checks"));
+        ui.add(egui::Slider::new(&mut self.weight, 0.0..=2.0).text("Weight"));
+        color_edit(ui, "Color:", &mut self.color);
+    }
+}
+
+/// Height fog over the whole scene, thickest at the base height and thinning out above it. The
+/// sunlit parts of it are found with the shadow map, like the god rays.
+#[derive(Debug, Clone)]
+pub struct FogSettings {
+    pub is_enabled: bool,
+    /// Per world unit at the base height.
+    pub density: f32,
+    /// How fast the density thins out with height, per world unit.
+    pub falloff: f32,
+    /// In world units.
+    pub base_height: f32,
+    /// The march stops this far away, in world units, the sky is fogged as if it were there.
+    pub max_distance: f32,
+    pub max_steps: u32,
+    /// How much brighter the sunlit fog is than the shadowed one.
+    pub sun_scattering: f32,
+    pub color: egui::Color32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            density: 0.15,
+            falloff: 4.0,
+            base_height: 0.0,
+            max_distance: 8.0,
+            max_steps: 24,
+            sun_scattering: 1.5,
+            color: egui::Color32::from_rgb(190, 205, 220),
+        }
+    }
+}
+
+impl FogSettings {
+    pub fn color_vec3(&self) -> Vec3 {
+        color_to_vec3(self.color)
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Enabled");
+        ui.add(egui::Slider::new(&mut self.density, 0.0..=2.0).text("Density"));
+        ui.add(egui::Slider::new(&mut self.falloff, 0.0..=20.0).text("Height Falloff"));
+        ui.add(egui::Slider::new(&mut self.base_height, -1.0..=2.0).text("Base Height"));
+        ui.add(egui::Slider::new(&mut self.max_distance, 0.5..=20.0).text("Max Distance"));
+        ui.add(egui::Slider::new(&mut self.max_steps, 1..=64).text("Max Steps"));
+        ui.add(egui::Slider::new(&mut self.sun_scattering, 0.0..=4.0).text("Sun Scattering"));
+        color_edit(ui, "Color:", &mut self.color);
+    }
+}
+
+/// Blurs what lies off the focal plane, gathered over the circle of confusion of each pixel after
+/// the TAA. Photo mode turns it on.
+#[derive(Debug, Clone)]
+pub struct DofSettings {
+    pub is_enabled: bool,
+    /// The distance that stays sharp, in world units.
+    pub focus_distance: f32,
+    /// How blurry the far background gets, in render target heights.
+    pub aperture: f32,
+    /// In pixels.
+    pub max_radius: f32,
+    pub sample_count: u32,
+}
+
+impl Default for DofSettings {
+    fn default() -> Self {
+        Self {
+            is_enabled: false,
+            focus_distance: 0.5,
+            aperture: 0.01,
+            max_radius: 12.0,
+            sample_count: 48,
+        }
+    }
+}
+
+impl DofSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Enabled");
+        ui.add(
+            egui::Slider::new(&mut self.focus_distance, 0.02..=20.0)
+                .logarithmic(true)
+                .text("Focus Distance"),
+        );
+        ui.add(egui::Slider::new(&mut self.aperture, 0.0..=0.05).text("Aperture"));
+        ui.add(egui::Slider::new(&mut self.max_radius, 1.0..=32.0).text("Max Radius (px)"));
+        ui.add(egui::Slider::new(&mut self.sample_count, 8..=128).text("Samples"));
+    }
+}
+
+/// How the water surface looks, the sea level itself comes with the terrain.
+#[derive(Debug, Clone)]
+pub struct WaterSettings {
+    /// How fast the water swallows the light, per world unit.
+    pub absorption: f32,
+    /// The steepest slope of the ripples.
+    pub wave_strength: f32,
+    pub wave_speed: f32,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self {
+            absorption: 30.0,
+            wave_strength: 0.15,
+            wave_speed: 1.0,
+        }
+    }
+}
+
+impl WaterSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.absorption, 0.0..=200.0).text("Absorption"));
+        ui.add(egui::Slider::new(&mut self.wave_strength, 0.0..=1.0).text("Wave Strength"));
+        ui.add(egui::Slider::new(&mut self.wave_speed, 0.0..=5.0).text("Wave Speed"));
+    }
+}
+
+/// Lowers the render resolution while the GPU can't keep up with the target framerate and raises
+/// it again once there's room, see [`crate::tracer::Tracer::update_dynamic_resolution`].
+#[derive(Debug, Clone)]
+pub struct DynamicResolutionSettings {
+    pub is_enabled: bool,
+    pub target_fps: f32,
+    /// Bounds of `TracerDesc::scaling_factor`, the factor of the config is restored once disabled.
+    pub min_scaling_factor: f32,
+    pub max_scaling_factor: f32,
+}
+
+impl Default for DynamicResolutionSettings {
+    fn default() -> Self {
+        Self {
+            is_enabled: false,
+            target_fps: 60.0,
+            min_scaling_factor: 0.25,
+            max_scaling_factor: 1.0,
+        }
+    }
+}
+
+impl DynamicResolutionSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.is_enabled, "Dynamic Resolution")
+            .on_hover_text("Needs the GPU timings, the render scale stays put while they're off");
+        ui.add(egui::Slider::new(&mut self.target_fps, 20.0..=240.0).text("Target FPS"));
+        ui.add(
+            egui::Slider::new(&mut self.min_scaling_factor, 0.1..=2.0)
+                .step_by(0.05)
+                .text("Min Render Scale"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.max_scaling_factor, 0.1..=2.0)
+                .step_by(0.05)
+                .text("Max Render Scale"),
+        );
+        self.max_scaling_factor = self.max_scaling_factor.max(self.min_scaling_factor);
+    }
+
+    /// In milliseconds.
+    pub fn target_frame_time(&self) -> f32 {
+        1000.0 / self.target_fps
+    }
+}
+
+/// Raindrops on the camera lens while it rains, both effects can be turned off for people who
+/// dislike camera effects. How hard it rains comes from the weather.
+#[derive(Debug, Clone)]
+pub struct RainLensSettings {
+    /// Drops running down the lens.
+    pub is_streaks_enabled: bool,
+    /// Drops landing on the lens and drying up shortly after.
+    pub is_lens_wetness_enabled: bool,
+    /// How far the drops bend the view, in screen pixels.
+    pub distortion: f32,
+}
+
+impl Default for RainLensSettings {
+    fn default() -> Self {
+        Self {
+            is_streaks_enabled: true,
+            is_lens_wetness_enabled: true,
+            distortion: 12.0,
+        }
+    }
+}
+
+impl RainLensSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Checkbox::new(
+            &mut self.is_streaks_enabled,
+            "Raindrop Streaks",
+        ));
+        ui.add(egui::Checkbox::new(
+            &mut self.is_lens_wetness_enabled,
+            "Lens Wetness",
+        ));
+        ui.add(egui::Slider::new(&mut self.distortion, 0.0..=32.0).text("Distortion (px)"));
+    }
+}
+
+/// Parameters of the volumetric star field of the night sky.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarlightSettings {
+    pub iterations: i32,
+    pub formuparam: f32,
+    pub volsteps: i32,
+    pub stepsize: f32,
+    pub zoom: f32,
+    pub tile: f32,
+    pub speed: f32,
+    pub brightness: f32,
+    pub darkmatter: f32,
+    pub distfading: f32,
+    pub saturation: f32,
+}
+
+impl Default for StarlightSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 18,
+            formuparam: 0.5,
+            volsteps: 10,
+            stepsize: 0.12,
+            zoom: 0.88,
+            tile: 1.1,
+            speed: 0.01,
+            brightness: 0.0005,
+            darkmatter: 0.8,
+            distfading: 0.885,
+            saturation: 1.0,
+        }
+    }
+}
+
+impl StarlightSettings {
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.iterations, 1..=30).text("Iterations"));
+        ui.add(egui::Slider::new(&mut self.formuparam, 0.0..=1.0).text("Form Parameter"));
+        ui.add(egui::Slider::new(&mut self.volsteps, 1..=50).text("Volume Steps"));
+        ui.add(egui::Slider::new(&mut self.stepsize, 0.01..=1.0).text("Step Size"));
+        ui.add(egui::Slider::new(&mut self.zoom, 0.1..=2.0).text("Zoom"));
+        ui.add(egui::Slider::new(&mut self.tile, 0.1..=2.0).text("Tile"));
+        ui.add(egui::Slider::new(&mut self.speed, 0.001..=0.1).text("Speed"));
+        ui.add(egui::Slider::new(&mut self.brightness, 0.0001..=0.01).text("Brightness"));
+        ui.add(egui::Slider::new(&mut self.darkmatter, 0.0..=1.0).text("Dark Matter"));
+        ui.add(egui::Slider::new(&mut self.distfading, 0.0..=1.0).text("Distance Fading"));
+        ui.add(egui::Slider::new(&mut self.saturation, 0.0..=1.0).text("Saturation"));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VoxelColorSettings {
+    pub sand: egui::Color32,
+    pub dirt: egui::Color32,
+    pub rock: egui::Color32,
+    pub leaf: egui::Color32,
+    pub trunk: egui::Color32,
+    pub water: egui::Color32,
+}
+
+impl Default for VoxelColorSettings {
+    fn default() -> Self {
+        Self {
+            sand: egui::Color32::from_rgb(245, 222, 179),
+            dirt: egui::Color32::from_rgb(68, 192, 0),
+            rock: egui::Color32::from_rgb(235, 92, 0),
+            leaf: egui::Color32::from_rgb(242, 199, 36),
+            trunk: egui::Color32::from_rgb(215, 194, 168),
+            water: egui::Color32::from_rgb(24, 92, 110),
+        }
+    }
+}
+
+impl VoxelColorSettings {
+    /// Sand, dirt, rock, leaf, trunk and water, in the order of the voxel types.
+    pub fn to_vec3s(&self) -> [Vec3; 6] {
+        [
+            self.sand, self.dirt, self.rock, self.leaf, self.trunk, self.water,
+        ]
+        .map(color_to_vec3)
+    }
+
+    pub fn edit_by_gui(&mut self, ui: &mut egui::Ui) {
+        color_edit(ui, "Sand Color:", &mut self.sand);
+        color_edit(ui, "Dirt Color:", &mut self.dirt);
+        color_edit(ui, "Rock Color:", &mut self.rock);
+        color_edit(ui, "Leaf Color:", &mut self.leaf);
+        color_edit(ui, "Trunk Color:", &mut self.trunk);
+        color_edit(ui, "Water Color:", &mut self.water);
+    }
+}
+
+/// Everything about the trace the app tunes from frame to frame, see `Tracer::update_buffers`.
+#[derive(Debug, Clone)]
+pub struct TracerSettings {
+    pub debug: DebugSettings,
+    pub sun: SunSettings,
+    pub denoiser: DenoiserSettings,
+    pub god_ray: GodRaySettings,
+    pub fog: FogSettings,
+    pub dof: DofSettings,
+    pub water: WaterSettings,
+    pub dynamic_resolution: DynamicResolutionSettings,
+    pub rain_lens: RainLensSettings,
+    pub starlight: StarlightSettings,
+    pub voxel_colors: VoxelColorSettings,
+    pub is_taa_enabled: bool,
+    /// Reuses the shadow map while the sun and the scene stand still.
+    pub is_shadow_caching_enabled: bool,
+    pub shadow_bias: ShadowBiasDesc,
+    pub player_collider: PlayerColliderDesc,
+}
+
+impl Default for TracerSettings {
+    fn default() -> Self {
+        Self {
+            debug: DebugSettings::default(),
+            sun: SunSettings::default(),
+            denoiser: DenoiserSettings::default(),
+            god_ray: GodRaySettings::default(),
+            fog: FogSettings::default(),
+            dof: DofSettings::default(),
+            water: WaterSettings::default(),
+            dynamic_resolution: DynamicResolutionSettings::default(),
+            rain_lens: RainLensSettings::default(),
+            starlight: StarlightSettings::default(),
+            voxel_colors: VoxelColorSettings::default(),
+            is_taa_enabled: false,
+            is_shadow_caching_enabled: true,
+            shadow_bias: ShadowBiasDesc::default(),
+            player_collider: PlayerColliderDesc::default(),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use glam::Vec3;
+
+/// Sun movement, in radians, the cached shadow map is kept through.
+const SUN_DIR_EPSILON: f32 = 1e-3;
+
+/// Decides when the shadow map has to be rendered again, so frames with a still sun and an
+/// unchanged scene reuse the last one.
+///
+/// Leaves swaying in the wind don't count as a change, their shadows hold still while the map is
+/// cached.
+pub struct ShadowCache {
+    /// The sun direction the cached shadow map was rendered with, `None` before the first render.
+    sun_dir: Option<Vec3>,
+    is_scene_dirty: bool,
+    is_rendering: bool,
+}
+
+impl ShadowCache {
+    pub fn new() -> Self {
+        Self {
+            sun_dir: None,
+            is_scene_dirty: true,
+            is_rendering: true,
+        }
+    }
+
+    /// The geometry casting shadows changed, the next frame renders the shadow map.
+    pub fn mark_dirty(&mut self) {
+        self.is_scene_dirty = true;
+    }
+
+    /// Decides whether this frame renders the shadow map, always when caching is disabled.
+    ///
+    /// Returns the sun direction the shadow map of this frame is rendered with, `None` if the
+    /// cached one is kept.
+    pub fn update(&mut self, sun_dir: Vec3, is_enabled: bool) -> Option<Vec3> {
+        let is_sun_moved = self
+            .sun_dir
+            .is_none_or(|cached_dir| cached_dir.angle_between(sun_dir) > SUN_DIR_EPSILON);
+        self.is_rendering = !is_enabled || self.is_scene_dirty || is_sun_moved;
+        if !self.is_rendering {
+            return None;
+        }
+        self.sun_dir = Some(sun_dir);
+        self.is_scene_dirty = false;
+        Some(sun_dir)
+    }
+
+    /// The sun direction the current shadow map was rendered with.
+    pub fn sun_dir(&self) -> Option<Vec3> {
+        self.sun_dir
+    }
+
+    /// Whether the shadow passes run this frame.
+    pub fn is_rendering(&self) -> bool {
+        self.is_rendering
+    }
+}
@@ -0,0 +1,41 @@
+use crate::geom::Aabb3;
+use glam::Vec3;
+
+/// How many trees the last rendered shadow map drew and why the others were left out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadowCullStats {
+    pub drawn: usize,
+    /// Outside of the shadow camera's ortho box.
+    pub light_culled: usize,
+    /// Inside the shadow camera but their shadow can't reach the main view.
+    pub receiver_culled: usize,
+}
+
+impl ShadowCullStats {
+    pub fn total(&self) -> usize {
+        self.drawn + self.light_culled + self.receiver_culled
+    }
+}
+
+/// The box the shadow of `caster` can fall into, the caster swept away from the sun down to
+/// `ground_height`.
+///
+/// The sweep is capped at `max_length` so a sun at the horizon doesn't stretch it endlessly.
+pub fn shadow_receiver_bound(
+    caster: &Aabb3,
+    sun_dir: Vec3,
+    ground_height: f32,
+    max_length: f32,
+) -> Aabb3 {
+    let light_dir = -sun_dir.normalize();
+    if light_dir.y >= 0.0 {
+        // the sun is below the horizon, no shadow is cast downwards
+        return caster.clone();
+    }
+    let length = ((caster.max().y - ground_height) / -light_dir.y).min(max_length);
+    let swept = Aabb3::new(
+        caster.min() + light_dir * length,
+        caster.max() + light_dir * length,
+    );
+    caster.union(&swept)
+}
@@ -0,0 +1,142 @@
+use super::StarlightSettings;
+use glam::Vec3;
+
+/// Width and height of the octahedral sky map, in texels.
+pub const SKY_MAP_EXTENT: u32 = 1024;
+
+/// Sun movement, in radians, the cached sky map is kept through.
+const SUN_DIR_EPSILON: f32 = 4e-3;
+
+/// Sun movement, in radians, past which the sky map is rendered whole at once rather than
+/// refreshed over several frames, so dragging the time of day doesn't show a torn sky.
+const SUN_DIR_JUMP: f32 = 0.05;
+
+/// Change of the star visibility or the moon glow the cached sky map is kept through.
+const NIGHT_SKY_EPSILON: f32 = 0.01;
+
+/// Change of the star visibility or the moon glow past which the sky map is rendered whole.
+const NIGHT_SKY_JUMP: f32 = 0.1;
+
+/// Frames a refresh after a small sun movement is spread over.
+const REFRESH_FRAME_COUNT: u32 = 8;
+
+/// What the world clock sets in the night sky, see `Tracer::update_world_clock`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NightSky {
+    /// How much the stars show, 0.0 while the sun is up.
+    pub star_visibility: f32,
+    /// How much the moon brightens the night sky and the ambient light.
+    pub moon_glow: f32,
+}
+
+impl NightSky {
+    fn difference(&self, other: &Self) -> f32 {
+        (self.star_visibility - other.star_visibility)
+            .abs()
+            .max((self.moon_glow - other.moon_glow).abs())
+    }
+}
+
+/// The rows of the sky map rendered this frame and the sky they're rendered with.
+#[derive(Debug, Clone, Copy)]
+pub struct SkyMapBand {
+    pub sun_dir: Vec3,
+    pub sun_azimuth: f32,
+    pub night_sky: NightSky,
+    pub row_offset: u32,
+    pub row_count: u32,
+}
+
+/// Decides which part of the sky map is rendered each frame.
+///
+/// The sky only depends on the sun, the night sky and the starlight parameters, so the map is
+/// kept while they hold still. A slowly moving sun or a slowly changing night sky refreshes it a
+/// band of rows per frame, the rows of one refresh all use the sky it started with.
+pub struct SkyCache {
+    /// The sun direction of the last refresh, `None` before the first render.
+    sun_dir: Option<Vec3>,
+    /// The night sky of the last refresh, `None` before the first render.
+    night_sky: Option<NightSky>,
+    /// The starlight of the cached map, `None` before the first render.
+    starlight: Option<StarlightSettings>,
+    /// The sky and the next row of the refresh in progress.
+    refresh: Option<SkyMapBand>,
+    band: Option<SkyMapBand>,
+}
+
+impl SkyCache {
+    pub fn new() -> Self {
+        Self {
+            sun_dir: None,
+            night_sky: None,
+            starlight: None,
+            refresh: None,
+            band: None,
+        }
+    }
+
+    /// Picks the rows to render this frame, `None` if the cached map is kept.
+    pub fn update(
+        &mut self,
+        sun_dir: Vec3,
+        sun_azimuth: f32,
+        night_sky: NightSky,
+        starlight: &StarlightSettings,
+    ) -> Option<SkyMapBand> {
+        let sun_movement = self.sun_dir.map_or(f32::INFINITY, |cached_dir| {
+            cached_dir.angle_between(sun_dir)
+        });
+        let night_sky_change = self
+            .night_sky
+            .map_or(f32::INFINITY, |cached| cached.difference(&night_sky));
+
+        let band = SkyMapBand {
+            sun_dir,
+            sun_azimuth,
+            night_sky,
+            row_offset: 0,
+            row_count: SKY_MAP_EXTENT,
+        };
+        self.band = if self.starlight.as_ref() != Some(starlight)
+            || sun_movement > SUN_DIR_JUMP
+            || night_sky_change > NIGHT_SKY_JUMP
+        {
+            self.starlight = Some(starlight.clone());
+            self.sun_dir = Some(sun_dir);
+            self.night_sky = Some(night_sky);
+            self.refresh = None;
+            Some(band)
+        } else {
+            if self.refresh.is_none()
+                && (sun_movement > SUN_DIR_EPSILON || night_sky_change > NIGHT_SKY_EPSILON)
+            {
+                self.sun_dir = Some(sun_dir);
+                self.night_sky = Some(night_sky);
+                self.refresh = Some(band);
+            }
+            self.take_refresh_band()
+        };
+        self.band
+    }
+
+    fn take_refresh_band(&mut self) -> Option<SkyMapBand> {
+        let refresh = self.refresh?;
+        let row_count = SKY_MAP_EXTENT
+            .div_ceil(REFRESH_FRAME_COUNT)
+            .min(SKY_MAP_EXTENT - refresh.row_offset);
+        let next_row = refresh.row_offset + row_count;
+        self.refresh = (next_row < SKY_MAP_EXTENT).then_some(SkyMapBand {
+            row_offset: next_row,
+            ..refresh
+        });
+        Some(SkyMapBand {
+            row_count,
+            ..refresh
+        })
+    }
+
+    /// The rows rendered this frame.
+    pub fn band(&self) -> Option<SkyMapBand> {
+        self.band
+    }
+}
@@ -0,0 +1,138 @@
+use crate::constants::{SKY_VISIBILITY_OCCLUDER_RANGE, SKY_VISIBILITY_TEXELS_PER_CHUNK};
+use crate::geom::{Aabb3, UAabb3, VoxelPos};
+use glam::{IVec2, UVec2, Vec2, Vec3Swizzles};
+use std::collections::HashMap;
+
+/// A leaf clump is counted into the texels within this many texels of its center.
+const LEAF_SPLAT_RADIUS: i32 = 2;
+
+struct TreeCanopy {
+    /// One entry per texel a leaf was counted into, repeated when leaves overlap.
+    texels: Vec<u32>,
+    bound: Aabb3,
+}
+
+/// CPU side of the sky visibility map, the leaf count of every texel column and the texels
+/// waiting to be recomputed.
+///
+/// Leaves are instances rather than voxels, so the sky visibility pass can't see them in the
+/// scene and reads their density from here instead.
+pub struct SkyVisibilityMap {
+    extent: UVec2,
+    canopy_density: Vec<u32>,
+    trees: HashMap<u32, TreeCanopy>,
+    /// Min inclusive, max exclusive, in texels.
+    dirty_texels: Option<(UVec2, UVec2)>,
+}
+
+impl SkyVisibilityMap {
+    /// The map spans the xz extent of `chunk_bound`.
+    pub fn new(chunk_bound: &UAabb3) -> Self {
+        let extent =
+            UVec2::new(chunk_bound.max().x, chunk_bound.max().z) * SKY_VISIBILITY_TEXELS_PER_CHUNK;
+        Self {
+            extent,
+            canopy_density: vec![0; (extent.x * extent.y) as usize],
+            trees: HashMap::new(),
+            // the scene is built before the tracer, so the first frame computes all of it
+            dirty_texels: Some((UVec2::ZERO, extent)),
+        }
+    }
+
+    /// In texels.
+    pub fn extent(&self) -> UVec2 {
+        self.extent
+    }
+
+    /// Row major in x, matches `B_CanopyDensity`.
+    pub fn canopy_density(&self) -> &[u32] {
+        &self.canopy_density
+    }
+
+    /// Counts the leaves of `tree_id` into the canopy, replacing the ones it had before.
+    pub fn set_tree(&mut self, tree_id: u32, leaf_positions: &[VoxelPos]) {
+        self.remove_tree(tree_id);
+        if leaf_positions.is_empty() {
+            return;
+        }
+
+        let mut texels = Vec::new();
+        for leaf_pos in leaf_positions {
+            let center = self.texel_of(leaf_pos.to_world().0.xz()).as_ivec2();
+            for dy in -LEAF_SPLAT_RADIUS..=LEAF_SPLAT_RADIUS {
+                for dx in -LEAF_SPLAT_RADIUS..=LEAF_SPLAT_RADIUS {
+                    let texel = center + IVec2::new(dx, dy);
+                    if texel.cmplt(IVec2::ZERO).any() || texel.cmpge(self.extent.as_ivec2()).any() {
+                        continue;
+                    }
+                    texels.push(texel.y as u32 * self.extent.x + texel.x as u32);
+                }
+            }
+        }
+        for &texel in &texels {
+            self.canopy_density[texel as usize] += 1;
+        }
+
+        let bound = Aabb3::from_points(
+            &leaf_positions
+                .iter()
+                .map(|leaf_pos| leaf_pos.to_world().0)
+                .collect::<Vec<_>>(),
+        );
+        self.mark_dirty(Some(&bound));
+        self.trees.insert(tree_id, TreeCanopy { texels, bound });
+    }
+
+    pub fn remove_tree(&mut self, tree_id: u32) {
+        let Some(tree) = self.trees.remove(&tree_id) else {
+            return;
+        };
+        for texel in tree.texels {
+            self.canopy_density[texel as usize] -= 1;
+        }
+        self.mark_dirty(Some(&tree.bound));
+    }
+
+    pub fn clear_trees(&mut self) {
+        self.trees.clear();
+        self.canopy_density.fill(0);
+        self.mark_dirty(None);
+    }
+
+    /// Queues the texels whose sky could have changed with the scene inside `region`, `None`
+    /// queues the whole map.
+    pub fn mark_dirty(&mut self, region: Option<&Aabb3>) {
+        let (min, max) = match region {
+            // a column only sees occluders and leaves within the occluder range
+            Some(region) => (
+                self.texel_of(region.min().xz() - Vec2::splat(SKY_VISIBILITY_OCCLUDER_RANGE)),
+                self.texel_of(region.max().xz() + Vec2::splat(SKY_VISIBILITY_OCCLUDER_RANGE))
+                    + UVec2::ONE,
+            ),
+            None => (UVec2::ZERO, self.extent),
+        };
+        let max = max.min(self.extent);
+        if min.cmpge(max).any() {
+            return;
+        }
+
+        self.dirty_texels = Some(match self.dirty_texels {
+            Some((dirty_min, dirty_max)) => (dirty_min.min(min), dirty_max.max(max)),
+            None => (min, max),
+        });
+    }
+
+    /// The offset and size of the texels to recompute, in texels.
+    pub fn take_dirty_texels(&mut self) -> Option<(UVec2, UVec2)> {
+        self.dirty_texels.take().map(|(min, max)| (min, max - min))
+    }
+
+    /// Clamped to the map.
+    fn texel_of(&self, pos_xz: Vec2) -> UVec2 {
+        (pos_xz * SKY_VISIBILITY_TEXELS_PER_CHUNK as f32)
+            .floor()
+            .max(Vec2::ZERO)
+            .as_uvec2()
+            .min(self.extent - UVec2::ONE)
+    }
+}
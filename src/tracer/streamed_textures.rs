@@ -0,0 +1,69 @@
+use crate::{
+    resource::{Resource, StreamedTextureId, TextureStreamer, TextureStreamingDesc},
+    tracer::rain_drops::{gen_rain_drop_texels, RAIN_DROP_TEX_EXTENT},
+    vkn::{Allocator, Texture, TimelineSemaphore, VulkanContext},
+};
+use anyhow::Result;
+use ash::vk;
+use resource_container_derive::ResourceContainer;
+
+/// The finest resident mips of the streamed textures, bound by name like [`super::TracerResources`].
+#[derive(ResourceContainer)]
+pub struct StreamedTextureResources {
+    /// Tileable drops the post processing pass puts on the lens while it rains.
+    pub rain_drop_tex: Resource<Texture>,
+}
+
+/// The tracer's textures that only need their full resolution some of the time.
+pub struct StreamedTextures {
+    streamer: TextureStreamer,
+    rain_drop_id: StreamedTextureId,
+    pub resources: StreamedTextureResources,
+}
+
+impl StreamedTextures {
+    pub fn new(vulkan_ctx: VulkanContext, allocator: Allocator) -> Result<Self> {
+        let mut streamer =
+            TextureStreamer::new(vulkan_ctx, allocator, TextureStreamingDesc::default());
+        let rain_drop_id = streamer.register(
+            "rain drops",
+            vk::Format::R8G8B8A8_UNORM,
+            Box::new(|| {
+                image::RgbaImage::from_raw(
+                    RAIN_DROP_TEX_EXTENT,
+                    RAIN_DROP_TEX_EXTENT,
+                    gen_rain_drop_texels(0),
+                )
+                .ok_or_else(|| anyhow::anyhow!("Rain drop texels don't fill the texture"))
+            }),
+        )?;
+        let resources = StreamedTextureResources {
+            rain_drop_tex: Resource::new(streamer.get(rain_drop_id).clone()),
+        };
+        Ok(Self {
+            streamer,
+            rain_drop_id,
+            resources,
+        })
+    }
+
+    /// Asks for the full resolution of the rain drops this frame, they sit right on the lens.
+    pub fn request_rain_drops(&mut self) {
+        self.streamer.request(self.rain_drop_id, 0.0);
+    }
+
+    /// Streams the mips this frame's requests asked for and points [`Self::resources`] at the
+    /// finest resident ones.
+    pub fn update(&mut self) -> Result<()> {
+        if self.streamer.update()? {
+            self.resources.rain_drop_tex =
+                Resource::new(self.streamer.get(self.rain_drop_id).clone());
+        }
+        Ok(())
+    }
+
+    /// See [`TextureStreamer::upload_wait`].
+    pub fn upload_wait(&self) -> (&TimelineSemaphore, u64) {
+        self.streamer.upload_wait()
+    }
+}
@@ -0,0 +1,132 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec3};
+
+/// Mirrors the voxel types in `voxel_types.glsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoxelMaterial {
+    Sand,
+    Dirt,
+    Rock,
+    Leaf,
+    Trunk,
+    Water,
+}
+
+impl VoxelMaterial {
+    pub(super) fn from_voxel_type(voxel_type: u32) -> Option<Self> {
+        match voxel_type {
+            1 => Some(Self::Sand),
+            2 => Some(Self::Dirt),
+            3 => Some(Self::Rock),
+            4 => Some(Self::Leaf),
+            5 => Some(Self::Trunk),
+            6 => Some(Self::Water),
+            _ => None,
+        }
+    }
+
+    pub fn voxel_type(self) -> u32 {
+        match self {
+            Self::Sand => 1,
+            Self::Dirt => 2,
+            Self::Rock => 3,
+            Self::Leaf => 4,
+            Self::Trunk => 5,
+            Self::Water => 6,
+        }
+    }
+}
+
+/// Distance `terrain_query.comp` writes where the ray hit nothing.
+const TERRAIN_QUERY_MISS_DISTANCE: f32 = -1.0;
+
+/// Height the downward rays of the terrain queries start from, above anything in the scene.
+const TERRAIN_QUERY_RAY_HEIGHT: f32 = 100.0;
+
+/// What a terrain query found straight below its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainHit {
+    pub height: f32,
+    /// Points up where the voxel carries no normal.
+    pub normal: Vec3,
+    /// `None` for voxel types without a material.
+    pub material: Option<VoxelMaterial>,
+}
+
+/// Where a ray cast through the scene hit the first voxel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub position: Vec3,
+    /// Points up where the voxel carries no normal.
+    pub normal: Vec3,
+    /// `None` for voxel types without a material.
+    pub material: Option<VoxelMaterial>,
+}
+
+/// Why a terrain query found no ground below its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TerrainMiss {
+    /// The position lies outside the chunk bound, where nothing is built.
+    #[error("position is outside the chunk bound")]
+    OutOfBounds,
+    /// The ray went down through built chunks without hitting a voxel.
+    #[error("no ground below the position")]
+    NoHit,
+}
+
+/// Mirrors `TerrainQueryRay` in `terrain_query.comp`, std430 pads both vectors to 16 bytes.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub(super) struct TerrainQueryRay {
+    origin: [f32; 3],
+    _padding0: f32,
+    direction: [f32; 3],
+    _padding1: f32,
+}
+
+impl TerrainQueryRay {
+    pub(super) fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin: origin.to_array(),
+            _padding0: 0.0,
+            direction: direction.normalize().to_array(),
+            _padding1: 0.0,
+        }
+    }
+
+    /// Straight down onto the position, from above anything in the scene.
+    pub(super) fn downward(pos_xz: Vec2) -> Self {
+        Self::new(
+            Vec3::new(pos_xz.x, TERRAIN_QUERY_RAY_HEIGHT, pos_xz.y),
+            Vec3::NEG_Y,
+        )
+    }
+}
+
+/// Mirrors `TerrainQueryResult` in `terrain_query.comp`, std430 pads it to 32 bytes.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub(super) struct TerrainQueryResult {
+    normal: [f32; 3],
+    distance: f32,
+    voxel_type: u32,
+    _padding: [u32; 3],
+}
+
+impl TerrainQueryResult {
+    /// `None` where the ray hit nothing.
+    pub(super) fn into_ray_hit(self, ray: &TerrainQueryRay) -> Option<RayHit> {
+        if self.distance == TERRAIN_QUERY_MISS_DISTANCE {
+            return None;
+        }
+        let normal = Vec3::from_array(self.normal)
+            .try_normalize()
+            .unwrap_or(Vec3::Y);
+        Some(RayHit {
+            position: Vec3::from_array(ray.origin)
+                + Vec3::from_array(ray.direction) * self.distance,
+            normal,
+            material: VoxelMaterial::from_voxel_type(self.voxel_type),
+        })
+    }
+}
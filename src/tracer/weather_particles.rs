@@ -0,0 +1,24 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Particles in the weather buffer, a downpour draws all of them.
+pub const MAX_WEATHER_PARTICLE_COUNT: u32 = 16384;
+
+/// Generates the particles the weather pass draws rain streaks and snowflakes from.
+///
+/// Each particle is xyz, its place in the box around the camera in [0, 1), and w, a random
+/// value in [0, 1) its fall speed and size are varied by. The pass animates them from the time,
+/// so the buffer is only written once.
+pub fn gen_weather_particles(seed: u64) -> Vec<[f32; 4]> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..MAX_WEATHER_PARTICLE_COUNT)
+        .map(|_| {
+            [
+                rng.random_range(0.0..1.0),
+                rng.random_range(0.0..1.0),
+                rng.random_range(0.0..1.0),
+                rng.random_range(0.0..1.0),
+            ]
+        })
+        .collect()
+}
@@ -1,5 +1,5 @@
 use crate::geom::RoundCone;
-use glam::Vec3;
+use glam::{Quat, Vec3};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::f32::consts::PI;
@@ -30,6 +30,8 @@ pub struct TreeDesc {
     pub subdivision_count_max: u32,
     pub subdivision_randomness: f32,
     pub subdivision_randomness_progression: f32,
+    /// Rotation around the vertical axis in radians, applied to the built tree.
+    pub rotation: f32,
 }
 
 impl Default for TreeDesc {
@@ -70,6 +72,9 @@ impl Default for TreeDesc {
 
             // Seed
             seed: 122,
+
+            // Placement
+            rotation: 0.0,
         }
     }
 }
@@ -248,7 +253,16 @@ pub struct Tree {
 
 impl Tree {
     pub fn new(desc: TreeDesc) -> Self {
-        let built_objects = Self::build(&desc);
+        let mut built_objects = Self::build(&desc);
+        if desc.rotation != 0.0 {
+            let rotation = Quat::from_rotation_y(desc.rotation);
+            for trunk in &mut built_objects.trunks {
+                trunk.rotate(rotation);
+            }
+            for leaf_pos in &mut built_objects.leaf_positions {
+                *leaf_pos = rotation * *leaf_pos;
+            }
+        }
         Tree { built_objects }
     }
 
@@ -1,7 +1,6 @@
 #![allow(dead_code)]
 
 use glam::UVec3;
-use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 
 /// A single allocation in the texture atlas.
@@ -9,166 +8,236 @@ use std::collections::HashMap;
 pub struct AtlasAllocation {
     pub id: u64,
     pub offset: UVec3,
+    /// The requested dim, the brick reserved for it is this rounded up to powers of two.
     pub dim: UVec3,
 }
 
-/// Simple shelf-based 3-D texture-atlas allocator.
-pub struct AtlasAllocator {
-    atlas_dim: UVec3,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BrickState {
+    Free,
+    Allocated(u64),
+    /// Halved along one axis, the children are the lower and the upper half.
+    Split([usize; 2]),
+    /// Merged back into its parent, the slot waits to be reused by a split.
+    Unused,
+}
 
-    // where the next allocation *could* be placed.
-    cursor: Cell<UVec3>,
-    // height (y) of the current shelf inside the current z-slice.
-    row_height: Cell<u32>,
+#[derive(Clone, Debug)]
+struct Brick {
+    offset: UVec3,
+    dim: UVec3,
+    parent: Option<usize>,
+    state: BrickState,
+}
 
-    // book keeping.
-    next_id: Cell<u64>,
-    allocations: RefCell<HashMap<u64, AtlasAllocation>>,
+/// Buddy allocator for a 3-D texture atlas.
+///
+/// The atlas is a tree of power-of-two bricks, a brick is halved along one axis until it matches
+/// the rounded up request, and freeing a brick merges it back with its buddy once both halves
+/// are free. So freed space is reused as a whole and the atlas never needs compacting.
+pub struct AtlasAllocator {
+    atlas_dim: UVec3,
+    /// Every brick of the tree, the root is at 0.
+    bricks: Vec<Brick>,
+    /// Slots of `bricks` left by merged children, reused by later splits.
+    unused_slots: Vec<usize>,
+
+    next_id: u64,
+    /// Allocation id -> (allocation, brick idx)
+    allocations: HashMap<u64, (AtlasAllocation, usize)>,
 }
 
 impl AtlasAllocator {
-    /// Create an empty allocator that can fill a texture of `atlas_size`.
+    /// Create an empty allocator that can fill a texture of `atlas_dim`, which must be a power of
+    /// two along every axis.
     pub fn new(atlas_dim: UVec3) -> Self {
+        assert!(
+            atlas_dim.to_array().iter().all(|d| d.is_power_of_two()),
+            "AtlasAllocator: atlas_dim must be a power of two along every axis"
+        );
         Self {
             atlas_dim,
-            cursor: Cell::new(UVec3::ZERO),
-            row_height: Cell::new(0),
-            next_id: Cell::new(0),
-            allocations: RefCell::new(HashMap::new()),
+            bricks: vec![Self::root_brick(atlas_dim)],
+            unused_slots: Vec::new(),
+            next_id: 0,
+            allocations: HashMap::new(),
         }
     }
 
-    /// Try to allocate `size` in the atlas.  Returns an `Allocation` on success.
-    pub fn allocate(&self, dim: UVec3) -> Result<AtlasAllocation, String> {
+    /// Try to allocate `dim` in the atlas.  Returns an `Allocation` on success.
+    ///
+    /// The smallest free brick that holds the request is used, so large bricks stay whole for as
+    /// long as possible.
+    pub fn allocate(&mut self, dim: UVec3) -> Result<AtlasAllocation, String> {
         if dim.x == 0 || dim.y == 0 || dim.z == 0 {
             return Err("size must be non-zero in every dimension".into());
         }
         if any_gt(dim, self.atlas_dim) {
             return Err("requested block is larger than the whole atlas".into());
         }
+        let brick_dim = UVec3::new(
+            dim.x.next_power_of_two(),
+            dim.y.next_power_of_two(),
+            dim.z.next_power_of_two(),
+        );
 
-        // try to place it.  `place()` updates internal cursors on success.
-        let offset = self
-            .place(dim)
-            .ok_or_else(|| "atlas is full – no place could be found".to_string())?;
+        let mut brick_idx = self
+            .bricks
+            .iter()
+            .enumerate()
+            .filter(|(_, brick)| brick.state == BrickState::Free && !any_gt(brick_dim, brick.dim))
+            .min_by_key(|(idx, brick)| (volume(brick.dim), *idx))
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| "atlas is full – no free brick is large enough".to_string())?;
+
+        while self.bricks[brick_idx].dim != brick_dim {
+            brick_idx = self.split(brick_idx, brick_dim);
+        }
 
-        let id = self.next_id.get();
-        self.next_id.set(id + 1);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.bricks[brick_idx].state = BrickState::Allocated(id);
 
-        let alloc = AtlasAllocation { id, offset, dim };
-        self.allocations.borrow_mut().insert(id, alloc.clone());
+        let alloc = AtlasAllocation {
+            id,
+            offset: self.bricks[brick_idx].offset,
+            dim,
+        };
+        self.allocations.insert(id, (alloc.clone(), brick_idx));
         Ok(alloc)
     }
 
     /// Look up an allocation by id.
     pub fn lookup(&self, id: u64) -> Option<AtlasAllocation> {
-        self.allocations.borrow().get(&id).cloned()
+        self.allocations.get(&id).map(|(alloc, _)| alloc.clone())
     }
 
-    /// Frees an allocation.
-    pub fn deallocate(&self, id: u64) -> Result<(), String> {
-        let existed = self.allocations.borrow_mut().remove(&id).is_some();
-        if existed {
-            Ok(())
-        } else {
-            Err(format!("no allocation with id {id}"))
+    /// Frees an allocation, merging its brick with the free buddies around it.
+    pub fn deallocate(&mut self, id: u64) -> Result<(), String> {
+        let (_, mut brick_idx) = self
+            .allocations
+            .remove(&id)
+            .ok_or_else(|| format!("no allocation with id {id}"))?;
+        self.bricks[brick_idx].state = BrickState::Free;
+
+        while let Some(parent_idx) = self.bricks[brick_idx].parent {
+            let BrickState::Split(children) = self.bricks[parent_idx].state else {
+                unreachable!("the parent of a brick is always split");
+            };
+            if children
+                .iter()
+                .any(|&child_idx| self.bricks[child_idx].state != BrickState::Free)
+            {
+                break;
+            }
+            for child_idx in children {
+                self.bricks[child_idx].state = BrickState::Unused;
+                self.unused_slots.push(child_idx);
+            }
+            self.bricks[parent_idx].state = BrickState::Free;
+            brick_idx = parent_idx;
         }
+        Ok(())
     }
 
-    /// Compacts the atlas so that all blocks become contiguous again.
-    ///
-    /// The relative order of allocations (by id) is preserved.
-    pub fn cleanup(&self) {
-        // snapshot and sort by id so the result is deterministic.
-        let mut items: Vec<AtlasAllocation> = self.allocations.borrow().values().cloned().collect();
-        items.sort_by_key(|a| a.id);
-
-        // reset packing state.
-        self.cursor.set(UVec3::ZERO);
-        self.row_height.set(0);
-
-        // re-pack one by one.
-        let mut map = self.allocations.borrow_mut();
-        for mut a in items {
-            // `place_no_record` cannot fail because the atlas was big enough
-            // for exactly these items before.
-            a.offset = self
-                .place_no_record(a.dim)
-                .expect("re-packing failed – this is a bug");
-            map.insert(a.id, a);
-        }
+    /// Frees the allocation placed at `offset`, for callers that keep the offset of a region
+    /// rather than its id.
+    pub fn deallocate_at(&mut self, offset: UVec3) -> Result<(), String> {
+        let id = self
+            .allocations
+            .values()
+            .find(|(alloc, _)| alloc.offset == offset)
+            .map(|(alloc, _)| alloc.id)
+            .ok_or_else(|| format!("no allocation at offset {offset}"))?;
+        self.deallocate(id)
     }
 
     /// Drops every allocation and rewinds the allocator to its initial state.
-    pub fn reset(&self) {
-        self.cursor.set(UVec3::ZERO);
-        self.row_height.set(0);
-        self.allocations.borrow_mut().clear();
+    pub fn reset(&mut self) {
+        self.bricks = vec![Self::root_brick(self.atlas_dim)];
+        self.unused_slots.clear();
+        self.allocations.clear();
     }
 
-    /* --------------------------------------------------------------------- */
-    /*                          internal helpers                             */
-    /* --------------------------------------------------------------------- */
-
-    /// Same algorithm that `allocate()` uses but *without* writing to the map.
-    fn place_no_record(&self, dim: UVec3) -> Option<UVec3> {
-        // temporarily remember old cursor in case we have to roll back.
-        let saved_cursor = self.cursor.get();
-        let saved_row_height = self.row_height.get();
+    pub fn atlas_dim(&self) -> UVec3 {
+        self.atlas_dim
+    }
 
-        let res = self.place(dim);
+    /// Texels taken by the allocated bricks, rounding included.
+    pub fn allocated_volume(&self) -> u64 {
+        self.bricks
+            .iter()
+            .filter(|brick| matches!(brick.state, BrickState::Allocated(_)))
+            .map(|brick| volume(brick.dim))
+            .sum()
+    }
 
-        if res.is_none() {
-            // rollback
-            self.cursor.set(saved_cursor);
-            self.row_height.set(saved_row_height);
-        }
-        res
+    pub fn free_volume(&self) -> u64 {
+        volume(self.atlas_dim) - self.allocated_volume()
     }
 
-    /// Shelf allocator:
-    /// 1. If it does not fit in the current row, start a new row.
-    /// 2. If it does not fit in this z-slice, start a new slice.
-    ///
-    /// Updates `cursor` and `row_height` on success.
-    fn place(&self, dim: UVec3) -> Option<UVec3> {
-        let mut cur = self.cursor.get();
-        let mut row_h = self.row_height.get();
-        let atlas_size = self.atlas_dim;
-
-        // helper that checks whether `size` fits starting at `cur`.
-        let fits = |cur: UVec3| {
-            cur.x + dim.x <= atlas_size.x
-                && cur.y + dim.y <= atlas_size.y
-                && cur.z + dim.z <= atlas_size.z
-        };
+    /// The largest free brick, compared with the free volume this tells how fragmented the atlas
+    /// is. Zero if the atlas is full.
+    pub fn largest_free_brick(&self) -> UVec3 {
+        self.bricks
+            .iter()
+            .filter(|brick| brick.state == BrickState::Free)
+            .map(|brick| brick.dim)
+            .max_by_key(|dim| volume(*dim))
+            .unwrap_or(UVec3::ZERO)
+    }
 
-        // start a new row if X overflow.
-        if cur.x + dim.x > atlas_size.x {
-            cur.x = 0;
-            cur.y += row_h;
-            row_h = 0;
-        }
-        // start a new slice if Y overflow.
-        if cur.y + dim.y > atlas_size.y {
-            cur.x = 0;
-            cur.y = 0;
-            row_h = 0;
-            cur.z += 1;
-        }
+    /* --------------------------------------------------------------------- */
+    /*                          internal helpers                             */
+    /* --------------------------------------------------------------------- */
 
-        if !fits(cur) {
-            return None;
+    fn root_brick(atlas_dim: UVec3) -> Brick {
+        Brick {
+            offset: UVec3::ZERO,
+            dim: atlas_dim,
+            parent: None,
+            state: BrickState::Free,
         }
+    }
 
-        // success – record new cursor and row height.
-        let offset = cur;
-        cur.x += dim.x;
-        row_h = row_h.max(dim.y);
-
-        self.cursor.set(cur);
-        self.row_height.set(row_h);
-        Some(offset)
+    /// Halves the free brick at `brick_idx` along the axis it's the most oversized in for
+    /// `target_dim`, returns the lower half.
+    fn split(&mut self, brick_idx: usize, target_dim: UVec3) -> usize {
+        let brick = self.bricks[brick_idx].clone();
+        let ratio = brick.dim / target_dim;
+        // ties go to x, then y, then z
+        let axis = if ratio.x >= ratio.y && ratio.x >= ratio.z {
+            0
+        } else if ratio.y >= ratio.z {
+            1
+        } else {
+            2
+        };
+        let mut half_dim = brick.dim;
+        half_dim[axis] /= 2;
+        let mut upper_offset = brick.offset;
+        upper_offset[axis] += half_dim[axis];
+
+        let children = [brick.offset, upper_offset].map(|offset| {
+            let child = Brick {
+                offset,
+                dim: half_dim,
+                parent: Some(brick_idx),
+                state: BrickState::Free,
+            };
+            match self.unused_slots.pop() {
+                Some(slot) => {
+                    self.bricks[slot] = child;
+                    slot
+                }
+                None => {
+                    self.bricks.push(child);
+                    self.bricks.len() - 1
+                }
+            }
+        });
+        self.bricks[brick_idx].state = BrickState::Split(children);
+        children[0]
     }
 }
 
@@ -177,6 +246,10 @@ fn any_gt(a: UVec3, b: UVec3) -> bool {
     a.x > b.x || a.y > b.y || a.z > b.z
 }
 
+fn volume(dim: UVec3) -> u64 {
+    dim.x as u64 * dim.y as u64 * dim.z as u64
+}
+
 /* ------------------------------------------------------------------------- */
 /*                                    Tests                                 */
 /* ------------------------------------------------------------------------- */
@@ -187,52 +260,121 @@ mod tests {
 
     #[test]
     fn basic_allocation() {
-        let atlas = AtlasAllocator::new(UVec3::new(16, 16, 1));
+        let mut atlas = AtlasAllocator::new(UVec3::new(16, 16, 1));
 
         let a = atlas.allocate(UVec3::new(8, 8, 1)).unwrap();
         assert_eq!(a.offset, UVec3::ZERO);
 
+        // the smallest free brick left over by the split is used first
         let b = atlas.allocate(UVec3::new(4, 8, 1)).unwrap();
-        assert_eq!(b.offset, UVec3::new(8, 0, 0));
+        assert_eq!(b.offset, UVec3::new(0, 8, 0));
 
         let c = atlas.allocate(UVec3::new(8, 4, 1)).unwrap();
-        assert_eq!(c.offset, UVec3::new(0, 8, 0));
+        assert_eq!(c.offset, UVec3::new(8, 0, 0));
     }
 
     #[test]
-    fn deallocate_and_cleanup() {
-        let atlas = AtlasAllocator::new(UVec3::new(16, 16, 1));
+    fn requests_round_up_to_power_of_two_bricks() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(16, 16, 1));
 
-        let a = atlas.allocate(UVec3::new(8, 8, 1)).unwrap();
-        let b = atlas.allocate(UVec3::new(4, 8, 1)).unwrap();
-        let _c = atlas.allocate(UVec3::new(8, 4, 1)).unwrap();
-
-        atlas.deallocate(b.id).unwrap();
+        let a = atlas.allocate(UVec3::new(3, 5, 1)).unwrap();
+        assert_eq!(a.dim, UVec3::new(3, 5, 1));
+        assert_eq!(atlas.allocated_volume(), 4 * 8);
 
-        // after cleanup the remaining allocations should be packed tightly:
-        atlas.cleanup();
-        let a2 = atlas.lookup(a.id).unwrap();
-        assert_eq!(a2.offset, UVec3::ZERO);
-        // the third allocation (`_c`) should now sit right after `a`.
-        let c2 = atlas
-            .allocations
-            .borrow()
-            .values()
-            .find(|al| al.dim == UVec3::new(8, 4, 1))
-            .cloned()
-            .unwrap();
-        assert_eq!(c2.offset, UVec3::new(8, 0, 0));
+        // the rest of the 4x16 column it was split from
+        let b = atlas.allocate(UVec3::new(3, 5, 1)).unwrap();
+        assert_eq!(b.offset, UVec3::new(0, 8, 0));
     }
 
     #[test]
     fn reset_empties_everything() {
-        let atlas = AtlasAllocator::new(UVec3::new(8, 8, 1));
-        atlas.allocate(UVec3::new(4, 4, 1)).unwrap();
-        assert!(!atlas.allocations.borrow().is_empty());
+        let mut atlas = AtlasAllocator::new(UVec3::new(8, 8, 1));
+        let a = atlas.allocate(UVec3::new(4, 4, 1)).unwrap();
+        assert_eq!(atlas.allocated_volume(), 16);
 
         atlas.reset();
-        assert!(atlas.allocations.borrow().is_empty());
-        assert_eq!(atlas.cursor.get(), UVec3::ZERO);
+        assert!(atlas.lookup(a.id).is_none());
+        assert_eq!(atlas.largest_free_brick(), UVec3::new(8, 8, 1));
+        assert_eq!(
+            atlas.allocate(UVec3::new(8, 8, 1)).unwrap().offset,
+            UVec3::ZERO
+        );
+    }
+
+    #[test]
+    fn deallocate_unknown_id_fails() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(8, 8, 1));
+        let a = atlas.allocate(UVec3::new(4, 4, 1)).unwrap();
+        atlas.deallocate(a.id).unwrap();
+        assert!(atlas.deallocate(a.id).is_err());
+    }
+
+    #[test]
+    fn deallocate_at_offset() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(8, 8, 8));
+        let a = atlas.allocate(UVec3::new(4, 4, 4)).unwrap();
+        let b = atlas.allocate(UVec3::new(4, 4, 4)).unwrap();
+
+        atlas.deallocate_at(b.offset).unwrap();
+        assert!(atlas.lookup(b.id).is_none());
+        assert!(atlas.lookup(a.id).is_some());
+        assert!(atlas.deallocate_at(b.offset).is_err());
+
+        atlas.deallocate_at(a.offset).unwrap();
+        assert_eq!(atlas.largest_free_brick(), UVec3::new(8, 8, 8));
+    }
+
+    /// Freed bricks merge with their buddies, so a full atlas can hand out its whole extent
+    /// again once it's emptied.
+    #[test]
+    fn freed_bricks_merge_and_are_reused() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(16, 16, 1));
+
+        let quarters = (0..4)
+            .map(|_| atlas.allocate(UVec3::new(8, 8, 1)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(atlas.allocate(UVec3::new(1, 1, 1)).is_err());
+
+        // a freed quarter is reused by the next request of its size
+        atlas.deallocate(quarters[2].id).unwrap();
+        let reused = atlas.allocate(UVec3::new(8, 8, 1)).unwrap();
+        assert_eq!(reused.offset, quarters[2].offset);
+
+        atlas.deallocate(reused.id).unwrap();
+        for quarter in [&quarters[0], &quarters[1], &quarters[3]] {
+            atlas.deallocate(quarter.id).unwrap();
+        }
+        assert_eq!(atlas.free_volume(), 16 * 16);
+        assert_eq!(atlas.largest_free_brick(), UVec3::new(16, 16, 1));
+        assert_eq!(
+            atlas.allocate(UVec3::new(16, 16, 1)).unwrap().offset,
+            UVec3::ZERO
+        );
+    }
+
+    /// Freeing every other small brick leaves the free space scattered until the rest is freed.
+    #[test]
+    fn fragmentation_clears_up_once_neighbors_are_freed() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(8, 8, 8));
+
+        let bricks = (0..64)
+            .map(|_| atlas.allocate(UVec3::new(2, 2, 2)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(atlas.free_volume(), 0);
+        assert_eq!(atlas.largest_free_brick(), UVec3::ZERO);
+
+        for brick in bricks.iter().step_by(2) {
+            atlas.deallocate(brick.id).unwrap();
+        }
+        assert_eq!(atlas.free_volume(), 32 * 8);
+        // half the atlas is free, but only in bricks as small as the allocations
+        assert_eq!(atlas.largest_free_brick(), UVec3::new(2, 2, 2));
+        assert!(atlas.allocate(UVec3::new(4, 2, 2)).is_err());
+
+        for brick in bricks.iter().skip(1).step_by(2) {
+            atlas.deallocate(brick.id).unwrap();
+        }
+        assert_eq!(atlas.largest_free_brick(), UVec3::new(8, 8, 8));
     }
 
     // ---------------------------------------------------------------------
@@ -243,7 +385,7 @@ mod tests {
     #[test]
     fn three_d_spill_to_next_slice() {
         // two slices of 4×4 texels each.
-        let atlas = AtlasAllocator::new(UVec3::new(4, 4, 2));
+        let mut atlas = AtlasAllocator::new(UVec3::new(4, 4, 2));
 
         // first slice (z == 0).
         let a0 = atlas.allocate(UVec3::new(4, 4, 1)).unwrap();
@@ -258,54 +400,22 @@ mod tests {
         assert!(atlas.allocate(UVec3::new(4, 4, 1)).is_err());
     }
 
-    /// Many small blocks that wrap first in X, then in Y, and finally in Z.
+    /// Small blocks tile every slice without overlapping until the atlas is full.
     #[test]
-    fn three_d_small_blocks_wrap_every_axis() {
-        let atlas = AtlasAllocator::new(UVec3::new(4, 4, 2));
-
-        // slice 0 ──────────────────────────────────────────────
-        // row 0
-        assert_eq!(
-            atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset,
-            UVec3::new(0, 0, 0)
-        );
-        assert_eq!(
-            atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset,
-            UVec3::new(2, 0, 0)
-        );
-        // row 1
-        assert_eq!(
-            atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset,
-            UVec3::new(0, 2, 0)
-        );
-        assert_eq!(
-            atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset,
-            UVec3::new(2, 2, 0)
-        );
-
-        // slice 1 ──────────────────────────────────────────────
-        assert_eq!(
-            atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset,
-            UVec3::new(0, 0, 1)
-        );
-    }
-
-    /// Deallocate something in slice 0, compact, and ensure everything
-    /// is re-packed tightly across Z as well.
-    #[test]
-    fn three_d_cleanup_repacks_across_slices() {
-        let atlas = AtlasAllocator::new(UVec3::new(4, 4, 2));
-
-        let a = atlas.allocate(UVec3::new(4, 4, 1)).unwrap(); // z = 0
-        let b = atlas.allocate(UVec3::new(4, 2, 1)).unwrap(); // z = 1, y-height = 2
-        let c = atlas.allocate(UVec3::new(4, 2, 1)).unwrap(); // z = 1, y-offset = 2
-
-        // remove the first big block in slice 0.
-        atlas.deallocate(a.id).unwrap();
-
-        // after compacting, `b` must now sit at z = 0.
-        atlas.cleanup();
-        assert_eq!(atlas.lookup(b.id).unwrap().offset, UVec3::new(0, 0, 0));
-        assert_eq!(atlas.lookup(c.id).unwrap().offset, UVec3::new(0, 2, 0));
+    fn three_d_small_blocks_tile_the_atlas() {
+        let mut atlas = AtlasAllocator::new(UVec3::new(4, 4, 2));
+
+        let mut offsets = (0..8)
+            .map(|_| {
+                let offset = atlas.allocate(UVec3::new(2, 2, 1)).unwrap().offset;
+                (offset.z, offset.y, offset.x)
+            })
+            .collect::<Vec<_>>();
+        offsets.sort();
+        let expected = (0..2)
+            .flat_map(|z| (0..2).flat_map(move |y| (0..2).map(move |x| (z, y * 2, x * 2))))
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, expected);
+        assert!(atlas.allocate(UVec3::new(1, 1, 1)).is_err());
     }
 }
@@ -14,11 +14,15 @@ pub struct FreeBlock {
 mod strategies;
 pub use strategies::*;
 
+mod trace;
+pub use trace::*;
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::seq::SliceRandom;
     use rand::Rng;
+    use std::path::PathBuf;
     use std::time::Instant;
 
     #[test]
@@ -81,6 +85,92 @@ mod tests {
         assert_eq!(lookup3.offset, 250);
     }
 
+    #[test]
+    fn test_buddy_allocator() {
+        let mut allocator = BuddyAllocator::new(1024);
+        // rounded up to a 256 byte block.
+        let alloc1 = allocator.allocate(200).unwrap();
+        assert_eq!(alloc1.size, 200);
+        assert_eq!(alloc1.offset, 0);
+
+        // splits the free 256 byte buddy of alloc1.
+        let alloc2 = allocator.allocate(100).unwrap();
+        assert_eq!(alloc2.offset, 256);
+        let alloc3 = allocator.allocate(100).unwrap();
+        assert_eq!(alloc3.offset, 384);
+        assert_eq!(allocator.largest_free_block(), 512);
+
+        // the freed halves merge back into one block.
+        allocator.deallocate(alloc2.id).unwrap();
+        allocator.deallocate(alloc3.id).unwrap();
+        assert_eq!(allocator.free_block_count(), 2);
+        let alloc4 = allocator.allocate(256).unwrap();
+        assert_eq!(alloc4.offset, 256);
+
+        // alloc1 can't grow into alloc4, so it moves.
+        let alloc1 = allocator.resize(alloc1.id, 300).unwrap();
+        assert_eq!(alloc1.offset, 512);
+
+        // shrinking stays in place and frees the upper halves.
+        let alloc1 = allocator.resize(alloc1.id, 100).unwrap();
+        assert_eq!(alloc1.offset, 512);
+        assert_eq!(allocator.allocate(128).unwrap().offset, 640);
+
+        // offsets are multiples of the alignment, even if it isn't a power of two.
+        // 10 units are covered by an 8 unit and a 2 unit block.
+        let mut allocator = BuddyAllocator::with_alignment(120, 12);
+        let alloc5 = allocator.allocate(24).unwrap();
+        assert_eq!(alloc5.offset, 96);
+        let alloc = allocator.allocate(12).unwrap();
+        assert_eq!(alloc.offset, 0);
+
+        // reset the allocator.
+        allocator.reset();
+        assert!(allocator.lookup(alloc.id).is_none());
+        assert_eq!(allocator.largest_free_block(), 96);
+    }
+
+    #[test]
+    fn test_tlsf_allocator() {
+        let mut allocator = TlsfAllocator::new(1000);
+        let alloc1 = allocator.allocate(200).unwrap();
+        assert_eq!(alloc1.offset, 0);
+        let alloc2 = allocator.allocate(300).unwrap();
+        assert_eq!(alloc2.offset, 200);
+        let alloc3 = allocator.allocate(100).unwrap();
+        assert_eq!(alloc3.offset, 500);
+
+        // the freed 300 bytes are the smallest size class that fits.
+        allocator.deallocate(alloc2.id).unwrap();
+        assert_eq!(allocator.free_block_count(), 2);
+        let alloc4 = allocator.allocate(250).unwrap();
+        assert_eq!(alloc4.offset, 200);
+
+        // expands into the 50 bytes left after it.
+        let alloc4 = allocator.resize(alloc4.id, 280).unwrap();
+        assert_eq!(alloc4.offset, 200);
+        assert_eq!(allocator.largest_free_block(), 400);
+
+        // the free neighbours merge back into one block.
+        allocator.deallocate(alloc3.id).unwrap();
+        allocator.deallocate(alloc4.id).unwrap();
+        assert_eq!(allocator.free_block_count(), 1);
+        assert_eq!(allocator.largest_free_block(), 800);
+
+        // sizes are rounded up to whole units of the alignment.
+        let mut allocator = TlsfAllocator::with_alignment(120, 12);
+        allocator.allocate(12).unwrap();
+        assert_eq!(allocator.allocate(20).unwrap().offset, 12);
+        let alloc = allocator.allocate(12).unwrap();
+        assert_eq!(alloc.offset, 36);
+        assert_eq!(allocator.largest_free_block(), 72);
+
+        // reset the allocator.
+        allocator.reset();
+        assert!(allocator.lookup(alloc.id).is_none());
+        assert_eq!(allocator.largest_free_block(), 120);
+    }
+
     #[test]
     fn benchmark_allocation_strategies() {
         // configurable parameters:
@@ -90,19 +180,30 @@ mod tests {
         let min_alloc_size: u64 = 2 * 1024 * 1024; // 2MB
         let max_alloc_size: u64 = 5 * 1024 * 1024; // 15MB
 
-        {
-            let mut allocator = FirstFitAllocator::new(pool_size);
+        type NewStrategy = fn(u64) -> Box<dyn AllocationStrategy>;
+        let strategies: [(&str, NewStrategy); 3] = [
+            ("First-Fit", |size| Box::new(FirstFitAllocator::new(size))),
+            ("Buddy", |size| Box::new(BuddyAllocator::new(size))),
+            ("TLSF", |size| Box::new(TlsfAllocator::new(size))),
+        ];
+
+        for (name, new_strategy) in strategies {
+            let mut allocator = new_strategy(pool_size);
             let mut allocations: Vec<BufferAllocation> = Vec::with_capacity(initial_allocations);
             let mut rng = rand::rng();
+            // the buddy blocks are rounded up, so it runs out of space where first-fit doesn't.
+            let mut failed_allocations = 0;
 
             // initial allocations.
             for _ in 0..initial_allocations {
                 let alloc_size = rng.random_range(min_alloc_size..=max_alloc_size);
-                let alloc = allocator.allocate(alloc_size).unwrap();
-                allocations.push(alloc);
+                match allocator.allocate(alloc_size) {
+                    Ok(alloc) => allocations.push(alloc),
+                    Err(_) => failed_allocations += 1,
+                }
             }
 
-            let start_ff = Instant::now();
+            let start = Instant::now();
 
             for _ in 0..iterations {
                 // randomly determine the number of allocations to deallocate (between 1 and 8).
@@ -126,16 +227,110 @@ mod tests {
                 // allocate new blocks with random sizes to replace the ones removed.
                 for _ in 0..num_to_remove {
                     let alloc_size = rng.random_range(min_alloc_size..=max_alloc_size);
-                    let alloc = allocator.allocate(alloc_size).unwrap();
-                    allocations.push(alloc);
+                    match allocator.allocate(alloc_size) {
+                        Ok(alloc) => allocations.push(alloc),
+                        Err(_) => failed_allocations += 1,
+                    }
                 }
             }
-            let duration_ff = start_ff.elapsed();
+            let duration = start.elapsed();
             println!(
-                "First-Fit Benchmark Avg Time: {:?}",
-                duration_ff / iterations as u32
+                "{} Benchmark Avg Time: {:?}, Failed Allocations: {}, Fragmentation: {:.3}, \
+                 Free Blocks: {}",
+                name,
+                duration / iterations as u32,
+                failed_allocations,
+                fragmentation_of(allocator.as_ref()),
+                allocator.free_block_count()
             );
-            println!("{:?}", allocator);
         }
     }
+
+    #[test]
+    fn test_allocation_trace_replay() {
+        let mut trace = AllocationTrace::new(1000);
+        trace.record(AllocationEvent::Allocate { id: 7, size: 300 });
+        trace.record(AllocationEvent::Allocate { id: 9, size: 300 });
+        trace.record(AllocationEvent::Resize { id: 7, size: 100 });
+        trace.record(AllocationEvent::Cleanup);
+        trace.record(AllocationEvent::Deallocate { id: 9 });
+        // never allocated, so it fails
+        trace.record(AllocationEvent::Deallocate { id: 3 });
+
+        let parsed = AllocationTrace::from_text(&trace.to_text()).unwrap();
+        assert_eq!(parsed, trace);
+
+        let mut allocator = FirstFitAllocator::new(trace.pool_size);
+        let report = parsed.replay(&mut allocator);
+        assert_eq!(report.op_count, 6);
+        assert_eq!(report.failed_op_count, 1);
+        assert_eq!(report.peak_allocated, 600);
+        assert_eq!(allocator.allocated_size(), 100);
+        assert_eq!(report.final_fragmentation, 0.0);
+    }
+
+    /// Replays the traces recorded with `REFLORA_ALLOC_TRACE` against every strategy, run with
+    /// `cargo test --release replay_recorded_traces -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn replay_recorded_traces() {
+        type NewStrategy = fn(u64) -> Box<dyn AllocationStrategy>;
+        let strategies: [(&str, NewStrategy); 3] = [
+            ("First-Fit", |size| Box::new(FirstFitAllocator::new(size))),
+            ("Buddy", |size| Box::new(BuddyAllocator::new(size))),
+            ("TLSF", |size| Box::new(TlsfAllocator::new(size))),
+        ];
+
+        let trace_dir = PathBuf::from(
+            std::env::var(ALLOC_TRACE_ENV_VAR).unwrap_or_else(|_| "alloc_traces".to_string()),
+        );
+        let Ok(entries) = std::fs::read_dir(&trace_dir) else {
+            println!("No traces in {}", trace_dir.display());
+            return;
+        };
+        let mut trace_paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "trace"))
+            .collect::<Vec<_>>();
+        trace_paths.sort();
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Trace",
+            "Strategy",
+            "Ops",
+            "Failed",
+            "Mops/s",
+            "Peak Used",
+            "Mean Frag",
+            "Peak Frag",
+            "Final Frag",
+        ]);
+        for trace_path in trace_paths {
+            let trace = AllocationTrace::load(&trace_path).unwrap();
+            for (name, new_strategy) in strategies {
+                let mut strategy = new_strategy(trace.pool_size);
+                let report = trace.replay(strategy.as_mut());
+                table.add_row(vec![
+                    trace_path
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                    name.to_string(),
+                    report.op_count.to_string(),
+                    report.failed_op_count.to_string(),
+                    format!("{:.2}", report.ops_per_second() / 1e6),
+                    format!(
+                        "{:.1}%",
+                        100.0 * report.peak_allocated as f64 / trace.pool_size as f64
+                    ),
+                    format!("{:.3}", report.mean_fragmentation),
+                    format!("{:.3}", report.peak_fragmentation),
+                    format!("{:.3}", report.final_fragmentation),
+                ]);
+            }
+        }
+        println!("{}", table);
+    }
 }
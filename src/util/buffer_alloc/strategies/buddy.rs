@@ -0,0 +1,239 @@
+use super::AllocationStrategy;
+use crate::util::BufferAllocation;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Debug, Formatter};
+
+/// Hands out blocks of a power of two units, a freed block merges with its buddy as soon as both
+/// halves are free. Rounding the sizes up wastes space inside the blocks, in exchange the free
+/// space stays in a few large blocks under churn.
+///
+/// A pool that isn't a power of two units long is covered by several top level blocks.
+#[derive(Clone)]
+pub struct BuddyAllocator {
+    total_size: u64,
+    /// Every offset is a multiple of it (in bytes).
+    unit_size: u64,
+    /// Offsets (in units) of the free blocks by order, a block of order `k` spans `2^k` units.
+    free_lists: Vec<BTreeSet<u64>>,
+    /// The allocations and the order of the block each one sits in.
+    allocated: HashMap<u64, (BufferAllocation, u32)>,
+    next_id: u64,
+}
+
+impl Debug for BuddyAllocator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BuddyAllocator {{ total_size: {}, unit_size: {}, allocated: {}, free_blocks: {} }}",
+            self.total_size,
+            self.unit_size,
+            self.allocated.len(),
+            self.free_block_count()
+        )
+    }
+}
+
+impl BuddyAllocator {
+    /// Creates a new buddy allocator with the given total size (in bytes) and byte sized units.
+    pub fn new(total_size: u64) -> Self {
+        Self::with_alignment(total_size, 1)
+    }
+
+    fn unit_count(&self) -> u64 {
+        self.total_size / self.unit_size
+    }
+
+    /// The smallest order whose blocks hold `size` bytes, nothing is ever empty.
+    fn order_of(&self, size: u64) -> u32 {
+        let units = size.div_ceil(self.unit_size).max(1);
+        units.next_power_of_two().trailing_zeros()
+    }
+
+    /// Covers the units `start..end` with the largest blocks their alignment allows.
+    fn free_range(&mut self, mut start: u64, end: u64) {
+        while start < end {
+            let order = start.trailing_zeros().min(floor_log2(end - start));
+            self.free_lists[order as usize].insert(start);
+            start += 1 << order;
+        }
+    }
+
+    /// Frees a block and merges it with its buddy as long as that one is free as well.
+    fn free_block(&mut self, mut offset: u64, mut order: u32) {
+        while (order as usize) + 1 < self.free_lists.len() {
+            let buddy = offset ^ (1 << order);
+            if !self.free_lists[order as usize].remove(&buddy) {
+                break;
+            }
+            offset = offset.min(buddy);
+            order += 1;
+        }
+        self.free_lists[order as usize].insert(offset);
+    }
+
+    /// Takes the lowest free block of the order, splitting a larger one if there is none.
+    fn take_block(&mut self, order: u32) -> Option<u64> {
+        let mut from_order =
+            (order as usize..self.free_lists.len()).find(|&k| !self.free_lists[k].is_empty())?;
+        let offset = self.free_lists[from_order].pop_first()?;
+        // the upper halves stay free
+        while from_order > order as usize {
+            from_order -= 1;
+            self.free_lists[from_order].insert(offset + (1 << from_order));
+        }
+        Some(offset)
+    }
+
+    fn to_bytes(&self, offset: u64) -> u64 {
+        offset * self.unit_size
+    }
+
+    fn to_units(&self, offset: u64) -> u64 {
+        offset / self.unit_size
+    }
+}
+
+fn floor_log2(x: u64) -> u32 {
+    63 - x.leading_zeros()
+}
+
+impl AllocationStrategy for BuddyAllocator {
+    /// One unit is `alignment` bytes, a pool that isn't a multiple of it loses the remainder.
+    fn with_alignment(total_size: u64, alignment: u64) -> Self {
+        let unit_size = alignment.max(1);
+        let unit_count = total_size / unit_size;
+        let order_count = if unit_count == 0 {
+            1
+        } else {
+            floor_log2(unit_count) as usize + 1
+        };
+        let mut allocator = BuddyAllocator {
+            total_size,
+            unit_size,
+            free_lists: vec![BTreeSet::new(); order_count],
+            allocated: HashMap::new(),
+            next_id: 1,
+        };
+        allocator.free_range(0, unit_count);
+        allocator
+    }
+
+    fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    fn allocated_size(&self) -> u64 {
+        self.allocated.values().map(|(a, _)| a.size).sum()
+    }
+
+    fn largest_free_block(&self) -> u64 {
+        self.free_lists
+            .iter()
+            .rposition(|list| !list.is_empty())
+            .map_or(0, |order| self.to_bytes(1 << order))
+    }
+
+    fn free_block_count(&self) -> usize {
+        self.free_lists.iter().map(|list| list.len()).sum()
+    }
+
+    fn allocate(&mut self, req_size: u64) -> Result<BufferAllocation, String> {
+        let order = self.order_of(req_size);
+        let Some(offset) = self.take_block(order) else {
+            return Err("Not enough free memory".to_string());
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let allocation = BufferAllocation {
+            id,
+            offset: self.to_bytes(offset),
+            size: req_size,
+        };
+        self.allocated.insert(id, (allocation.clone(), order));
+        Ok(allocation)
+    }
+
+    fn lookup(&self, id: u64) -> Option<BufferAllocation> {
+        self.allocated.get(&id).map(|(a, _)| a.clone())
+    }
+
+    fn deallocate(&mut self, id: u64) -> Result<(), String> {
+        if let Some((allocation, order)) = self.allocated.remove(&id) {
+            self.free_block(self.to_units(allocation.offset), order);
+            Ok(())
+        } else {
+            Err("Allocation id not found".to_string())
+        }
+    }
+
+    fn cleanup(&mut self) {
+        let unit_size = self.unit_size;
+        let mut allocs: Vec<&mut (BufferAllocation, u32)> = self.allocated.values_mut().collect();
+        // largest blocks first, so every block lands on a multiple of its own size
+        allocs.sort_by_key(|(a, order)| (std::cmp::Reverse(*order), a.offset));
+        let mut cur = 0;
+        for (a, order) in allocs {
+            a.offset = cur * unit_size;
+            cur += 1 << *order;
+        }
+        self.free_lists.iter_mut().for_each(|list| list.clear());
+        self.free_range(cur, self.unit_count());
+    }
+
+    fn reset(&mut self) {
+        self.allocated.clear();
+        self.free_lists.iter_mut().for_each(|list| list.clear());
+        self.free_range(0, self.unit_count());
+        self.next_id = 1;
+    }
+
+    fn resize(&mut self, id: u64, to_size: u64) -> Result<BufferAllocation, String> {
+        let (old_offset, old_order) = if let Some((a, order)) = self.allocated.get(&id) {
+            (self.to_units(a.offset), *order)
+        } else {
+            return Err("Allocation id not found".into());
+        };
+        let new_order = self.order_of(to_size);
+
+        // shrink or stay in place, the upper halves the block no longer needs are freed
+        if new_order <= old_order {
+            for order in new_order..old_order {
+                self.free_block(old_offset + (1 << order), order);
+            }
+            let (a, order) = self.allocated.get_mut(&id).unwrap();
+            a.size = to_size;
+            *order = new_order;
+            return Ok(a.clone());
+        }
+
+        // expand in place if the block is the lower half of every larger block up to the new
+        // order and the upper halves are all free
+        let can_expand = (new_order as usize) < self.free_lists.len()
+            && old_offset.is_multiple_of(1 << new_order)
+            && (old_order..new_order).all(|order| {
+                self.free_lists[order as usize].contains(&(old_offset + (1 << order)))
+            });
+        if can_expand {
+            for order in old_order..new_order {
+                self.free_lists[order as usize].remove(&(old_offset + (1 << order)));
+            }
+            let (a, order) = self.allocated.get_mut(&id).unwrap();
+            a.size = to_size;
+            *order = new_order;
+            return Ok(a.clone());
+        }
+
+        // otherwise we must move
+        if let Some(new_offset) = self.take_block(new_order) {
+            self.free_block(old_offset, old_order);
+            let new_offset = self.to_bytes(new_offset);
+            let (a, order) = self.allocated.get_mut(&id).unwrap();
+            a.offset = new_offset;
+            a.size = to_size;
+            *order = new_order;
+            return Ok(a.clone());
+        }
+
+        Err("Not enough free memory to resize".into())
+    }
+}
@@ -57,6 +57,27 @@ impl FirstFitAllocator {
 }
 
 impl AllocationStrategy for FirstFitAllocator {
+    /// Offsets are sums of earlier sizes, they stay aligned as long as the sizes are.
+    fn with_alignment(total_size: u64, _alignment: u64) -> Self {
+        Self::new(total_size)
+    }
+
+    fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    fn allocated_size(&self) -> u64 {
+        self.allocated.values().map(|a| a.size).sum()
+    }
+
+    fn largest_free_block(&self) -> u64 {
+        self.free_list.iter().map(|b| b.size).max().unwrap_or(0)
+    }
+
+    fn free_block_count(&self) -> usize {
+        self.free_list.len()
+    }
+
     fn allocate(&mut self, req_size: u64) -> Result<BufferAllocation, String> {
         for i in 0..self.free_list.len() {
             if self.free_list[i].size >= req_size {
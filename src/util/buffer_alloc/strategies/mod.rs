@@ -1,11 +1,63 @@
 #![allow(dead_code)]
 
-use super::BufferAllocation;
+use super::{fragmentation_of, BufferAllocation};
+
+mod buddy;
+pub use buddy::*;
 
 mod first_fit;
 pub use first_fit::*;
 
+mod tlsf;
+pub use tlsf::*;
+
+/// Usage of an allocator's pool, `largest_free_block` vs `free` tells how fragmented it is.
+#[derive(Debug, Clone, Copy)]
+pub struct AllocatorStats {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+    pub free_block_count: usize,
+    pub largest_free_block: u64,
+    /// See [`fragmentation_of`].
+    pub fragmentation: f32,
+}
+
+impl AllocatorStats {
+    pub fn of(strategy: &dyn AllocationStrategy) -> Self {
+        let total = strategy.total_size();
+        let used = strategy.allocated_size();
+        Self {
+            total,
+            used,
+            free: total - used,
+            free_block_count: strategy.free_block_count(),
+            largest_free_block: strategy.largest_free_block(),
+            fragmentation: fragmentation_of(strategy),
+        }
+    }
+}
+
 pub trait AllocationStrategy {
+    /// Creates an empty pool of `total_size` bytes for callers that only ask for multiples of
+    /// `alignment` bytes, the offsets handed out are multiples of it as well.
+    fn with_alignment(total_size: u64, alignment: u64) -> Self
+    where
+        Self: Sized;
+
+    /// Size of the whole pool (in bytes).
+    fn total_size(&self) -> u64;
+
+    /// Sum of all live allocation sizes (in bytes).
+    fn allocated_size(&self) -> u64;
+
+    /// Size of the largest contiguous free block, compared with the total free size this tells
+    /// how fragmented the pool is.
+    fn largest_free_block(&self) -> u64;
+
+    /// Number of separate blocks the free space is split into.
+    fn free_block_count(&self) -> usize;
+
     /// Allocates a continuous block of memory of `req_size` bytes.
     ///
     /// Returns the allocation record if successful.
@@ -0,0 +1,401 @@
+use super::AllocationStrategy;
+use crate::util::BufferAllocation;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+
+/// Every power of two size class is split into this many linear classes, log2 of it.
+const SL_LOG2: u32 = 4;
+const SL_COUNT: usize = 1 << SL_LOG2;
+/// Enough first level classes for any u64 size.
+const FL_COUNT: usize = 64 - SL_LOG2 as usize + 1;
+
+#[derive(Debug, Clone)]
+struct Block {
+    /// In units, like the size.
+    offset: u64,
+    size: u64,
+    is_free: bool,
+    prev_phys: Option<usize>,
+    next_phys: Option<usize>,
+    prev_free: Option<usize>,
+    next_free: Option<usize>,
+}
+
+/// Two-level segregated fit, free blocks are kept in lists by size class and two levels of
+/// bitmaps find the first non-empty list that fits, so allocate and deallocate take constant
+/// time no matter how many blocks are live.
+///
+/// A unit is `alignment` bytes, so offsets are multiples of it.
+#[derive(Clone)]
+pub struct TlsfAllocator {
+    total_size: u64,
+    unit_size: u64,
+    /// Physical blocks, free and used. Slots of merged blocks are reused.
+    blocks: Vec<Block>,
+    unused_block_slots: Vec<usize>,
+    /// Bit `fl` is set if any list of `sl_bitmaps[fl]` is non-empty.
+    fl_bitmap: u64,
+    sl_bitmaps: [u32; FL_COUNT],
+    free_heads: [[Option<usize>; SL_COUNT]; FL_COUNT],
+    free_block_count: usize,
+    /// The allocations and the block each one sits in.
+    allocated: HashMap<u64, (BufferAllocation, usize)>,
+    next_id: u64,
+}
+
+impl Debug for TlsfAllocator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TlsfAllocator {{ total_size: {}, unit_size: {}, allocated: {}, free_blocks: {} }}",
+            self.total_size,
+            self.unit_size,
+            self.allocated.len(),
+            self.free_block_count
+        )
+    }
+}
+
+/// The size class a block of `size` units is listed in.
+fn mapping_insert(size: u64) -> (usize, usize) {
+    if size < SL_COUNT as u64 {
+        return (0, size as usize);
+    }
+    let fl = 63 - size.leading_zeros();
+    let sl = (size >> (fl - SL_LOG2)) as usize - SL_COUNT;
+    ((fl - SL_LOG2 + 1) as usize, sl)
+}
+
+/// The smallest size class whose blocks all hold `size` units.
+fn mapping_search(size: u64) -> (usize, usize) {
+    if size < SL_COUNT as u64 {
+        return mapping_insert(size);
+    }
+    let round = (1 << (63 - size.leading_zeros() - SL_LOG2)) - 1;
+    mapping_insert(size.saturating_add(round))
+}
+
+impl TlsfAllocator {
+    /// Creates a new TLSF allocator with the given total size (in bytes) and byte sized units.
+    pub fn new(total_size: u64) -> Self {
+        Self::with_alignment(total_size, 1)
+    }
+
+    fn unit_count(&self) -> u64 {
+        self.total_size / self.unit_size
+    }
+
+    fn new_block(&mut self, block: Block) -> usize {
+        match self.unused_block_slots.pop() {
+            Some(idx) => {
+                self.blocks[idx] = block;
+                idx
+            }
+            None => {
+                self.blocks.push(block);
+                self.blocks.len() - 1
+            }
+        }
+    }
+
+    fn insert_free(&mut self, idx: usize) {
+        let (fl, sl) = mapping_insert(self.blocks[idx].size);
+        let head = self.free_heads[fl][sl];
+        self.blocks[idx].is_free = true;
+        self.blocks[idx].prev_free = None;
+        self.blocks[idx].next_free = head;
+        if let Some(head) = head {
+            self.blocks[head].prev_free = Some(idx);
+        }
+        self.free_heads[fl][sl] = Some(idx);
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+        self.free_block_count += 1;
+    }
+
+    fn remove_free(&mut self, idx: usize) {
+        let (fl, sl) = mapping_insert(self.blocks[idx].size);
+        let Block {
+            prev_free,
+            next_free,
+            ..
+        } = self.blocks[idx];
+        match prev_free {
+            Some(prev) => self.blocks[prev].next_free = next_free,
+            None => self.free_heads[fl][sl] = next_free,
+        }
+        if let Some(next) = next_free {
+            self.blocks[next].prev_free = prev_free;
+        }
+        if self.free_heads[fl][sl].is_none() {
+            self.sl_bitmaps[fl] &= !(1 << sl);
+            if self.sl_bitmaps[fl] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+        self.blocks[idx].is_free = false;
+        self.free_block_count -= 1;
+    }
+
+    /// A free block of at least `size` units, taken off its list.
+    fn take_suitable(&mut self, size: u64) -> Option<usize> {
+        let (fl, sl) = mapping_search(size);
+        if fl >= FL_COUNT {
+            return None;
+        }
+        let sl_map = self.sl_bitmaps[fl] & (u32::MAX << sl);
+        let (fl, sl) = if sl_map != 0 {
+            (fl, sl_map.trailing_zeros() as usize)
+        } else {
+            let fl_map = self.fl_bitmap & u64::MAX.checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+            let fl = fl_map.trailing_zeros() as usize;
+            (fl, self.sl_bitmaps[fl].trailing_zeros() as usize)
+        };
+        let idx = self.free_heads[fl][sl]?;
+        self.remove_free(idx);
+        Some(idx)
+    }
+
+    /// Cuts the used block down to `size` units, the rest becomes a free block of its own.
+    fn split(&mut self, idx: usize, size: u64) {
+        let block = &self.blocks[idx];
+        if block.size <= size {
+            return;
+        }
+        let rest = Block {
+            offset: block.offset + size,
+            size: block.size - size,
+            is_free: false,
+            prev_phys: Some(idx),
+            next_phys: block.next_phys,
+            prev_free: None,
+            next_free: None,
+        };
+        let rest_idx = self.new_block(rest);
+        if let Some(next) = self.blocks[rest_idx].next_phys {
+            self.blocks[next].prev_phys = Some(rest_idx);
+        }
+        self.blocks[idx].size = size;
+        self.blocks[idx].next_phys = Some(rest_idx);
+        self.free_merged(rest_idx);
+    }
+
+    /// Folds the next block into this one, the next block's slot is released.
+    fn absorb_next(&mut self, idx: usize) {
+        let next = self.blocks[idx].next_phys.unwrap();
+        let Block {
+            size, next_phys, ..
+        } = self.blocks[next];
+        self.blocks[idx].size += size;
+        self.blocks[idx].next_phys = next_phys;
+        if let Some(after) = next_phys {
+            self.blocks[after].prev_phys = Some(idx);
+        }
+        self.unused_block_slots.push(next);
+    }
+
+    /// Frees a block and merges it with its free neighbours.
+    fn free_merged(&mut self, mut idx: usize) {
+        if let Some(next) = self.blocks[idx].next_phys {
+            if self.blocks[next].is_free {
+                self.remove_free(next);
+                self.absorb_next(idx);
+            }
+        }
+        if let Some(prev) = self.blocks[idx].prev_phys {
+            if self.blocks[prev].is_free {
+                self.remove_free(prev);
+                self.absorb_next(prev);
+                idx = prev;
+            }
+        }
+        self.insert_free(idx);
+    }
+
+    fn units_of(&self, size: u64) -> u64 {
+        size.div_ceil(self.unit_size).max(1)
+    }
+
+    /// Drops every block, the live allocations are placed one after another from `0` on.
+    fn rebuild(&mut self, mut allocs: Vec<u64>) {
+        self.blocks.clear();
+        self.unused_block_slots.clear();
+        self.fl_bitmap = 0;
+        self.sl_bitmaps = [0; FL_COUNT];
+        self.free_heads = [[None; SL_COUNT]; FL_COUNT];
+        self.free_block_count = 0;
+
+        allocs.sort_by_key(|id| self.allocated[id].0.offset);
+        let mut cur = 0;
+        let mut prev = None;
+        for id in allocs {
+            let size = self.units_of(self.allocated[&id].0.size);
+            let idx = self.new_block(Block {
+                offset: cur,
+                size,
+                is_free: false,
+                prev_phys: prev,
+                next_phys: None,
+                prev_free: None,
+                next_free: None,
+            });
+            if let Some(prev) = prev {
+                self.blocks[prev].next_phys = Some(idx);
+            }
+            let (a, block_idx) = self.allocated.get_mut(&id).unwrap();
+            a.offset = cur * self.unit_size;
+            *block_idx = idx;
+            cur += size;
+            prev = Some(idx);
+        }
+        let unit_count = self.unit_count();
+        if cur < unit_count {
+            let idx = self.new_block(Block {
+                offset: cur,
+                size: unit_count - cur,
+                is_free: false,
+                prev_phys: prev,
+                next_phys: None,
+                prev_free: None,
+                next_free: None,
+            });
+            if let Some(prev) = prev {
+                self.blocks[prev].next_phys = Some(idx);
+            }
+            self.insert_free(idx);
+        }
+    }
+}
+
+impl AllocationStrategy for TlsfAllocator {
+    /// One unit is `alignment` bytes, a pool that isn't a multiple of it loses the remainder.
+    fn with_alignment(total_size: u64, alignment: u64) -> Self {
+        let mut allocator = TlsfAllocator {
+            total_size,
+            unit_size: alignment.max(1),
+            blocks: Vec::new(),
+            unused_block_slots: Vec::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            free_heads: [[None; SL_COUNT]; FL_COUNT],
+            free_block_count: 0,
+            allocated: HashMap::new(),
+            next_id: 1,
+        };
+        allocator.rebuild(Vec::new());
+        allocator
+    }
+
+    fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    fn allocated_size(&self) -> u64 {
+        self.allocated.values().map(|(a, _)| a.size).sum()
+    }
+
+    fn largest_free_block(&self) -> u64 {
+        if self.fl_bitmap == 0 {
+            return 0;
+        }
+        // only the highest non-empty list can hold it
+        let fl = 63 - self.fl_bitmap.leading_zeros() as usize;
+        let sl = 31 - self.sl_bitmaps[fl].leading_zeros() as usize;
+        let mut largest = 0;
+        let mut cur = self.free_heads[fl][sl];
+        while let Some(idx) = cur {
+            largest = largest.max(self.blocks[idx].size);
+            cur = self.blocks[idx].next_free;
+        }
+        largest * self.unit_size
+    }
+
+    fn free_block_count(&self) -> usize {
+        self.free_block_count
+    }
+
+    fn allocate(&mut self, req_size: u64) -> Result<BufferAllocation, String> {
+        let size = self.units_of(req_size);
+        let Some(idx) = self.take_suitable(size) else {
+            return Err("Not enough free memory".to_string());
+        };
+        self.split(idx, size);
+        let id = self.next_id;
+        self.next_id += 1;
+        let allocation = BufferAllocation {
+            id,
+            offset: self.blocks[idx].offset * self.unit_size,
+            size: req_size,
+        };
+        self.allocated.insert(id, (allocation.clone(), idx));
+        Ok(allocation)
+    }
+
+    fn lookup(&self, id: u64) -> Option<BufferAllocation> {
+        self.allocated.get(&id).map(|(a, _)| a.clone())
+    }
+
+    fn deallocate(&mut self, id: u64) -> Result<(), String> {
+        if let Some((_, idx)) = self.allocated.remove(&id) {
+            self.free_merged(idx);
+            Ok(())
+        } else {
+            Err("Allocation id not found".to_string())
+        }
+    }
+
+    fn cleanup(&mut self) {
+        let allocs = self.allocated.keys().copied().collect();
+        self.rebuild(allocs);
+    }
+
+    fn reset(&mut self) {
+        self.allocated.clear();
+        self.rebuild(Vec::new());
+        self.next_id = 1;
+    }
+
+    fn resize(&mut self, id: u64, to_size: u64) -> Result<BufferAllocation, String> {
+        let Some(&(_, idx)) = self.allocated.get(&id) else {
+            return Err("Allocation id not found".into());
+        };
+        let size = self.units_of(to_size);
+
+        // shrink in place
+        if size <= self.blocks[idx].size {
+            self.split(idx, size);
+            let (a, _) = self.allocated.get_mut(&id).unwrap();
+            a.size = to_size;
+            return Ok(a.clone());
+        }
+
+        // expand in place if the next block is free and large enough
+        if let Some(next) = self.blocks[idx].next_phys {
+            if self.blocks[next].is_free && self.blocks[idx].size + self.blocks[next].size >= size {
+                self.remove_free(next);
+                self.absorb_next(idx);
+                self.split(idx, size);
+                let (a, _) = self.allocated.get_mut(&id).unwrap();
+                a.size = to_size;
+                return Ok(a.clone());
+            }
+        }
+
+        // otherwise we must move
+        if let Some(new_idx) = self.take_suitable(size) {
+            self.split(new_idx, size);
+            self.free_merged(idx);
+            let offset = self.blocks[new_idx].offset * self.unit_size;
+            let (a, block_idx) = self.allocated.get_mut(&id).unwrap();
+            a.offset = offset;
+            a.size = to_size;
+            *block_idx = new_idx;
+            return Ok(a.clone());
+        }
+
+        Err("Not enough free memory to resize".into())
+    }
+}
@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+
+use super::AllocationStrategy;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Set to a directory to record the allocation traces of the contree pools into it.
+pub const ALLOC_TRACE_ENV_VAR: &str = "REFLORA_ALLOC_TRACE";
+
+/// One call made on an allocator, ids are the ones the recording allocator handed out.
+/// `Cleanup` repacks the pool, the offsets change but the ids stay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationEvent {
+    Allocate { id: u64, size: u64 },
+    Resize { id: u64, size: u64 },
+    Deallocate { id: u64 },
+    Cleanup,
+}
+
+/// The calls made on an allocator in order, so a real workload can be replayed against any
+/// [`AllocationStrategy`].
+///
+/// Saved as text, a `pool <size>` line followed by one line per event: `a <id> <size>`,
+/// `r <id> <size>`, `d <id>` or `c`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationTrace {
+    pub pool_size: u64,
+    pub events: Vec<AllocationEvent>,
+}
+
+impl AllocationTrace {
+    pub fn new(pool_size: u64) -> Self {
+        Self {
+            pool_size,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: AllocationEvent) {
+        self.events.push(event);
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("pool {}\n", self.pool_size);
+        for event in &self.events {
+            let line = match event {
+                AllocationEvent::Allocate { id, size } => format!("a {} {}\n", id, size),
+                AllocationEvent::Resize { id, size } => format!("r {} {}\n", id, size),
+                AllocationEvent::Deallocate { id } => format!("d {}\n", id),
+                AllocationEvent::Cleanup => "c\n".to_string(),
+            };
+            text.push_str(&line);
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let pool_size = match lines
+            .next()
+            .map(|(_, l)| l.split_whitespace().collect::<Vec<_>>())
+        {
+            Some(fields) if fields.len() == 2 && fields[0] == "pool" => fields[1].parse()?,
+            _ => anyhow::bail!("Allocation trace must start with a pool line"),
+        };
+
+        let mut events = Vec::new();
+        for (line_idx, line) in lines {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let parse = |idx: usize| -> Result<u64> {
+                fields
+                    .get(idx)
+                    .context("Missing field")?
+                    .parse()
+                    .context("Invalid number")
+            };
+            let event = match fields[0] {
+                "a" => AllocationEvent::Allocate {
+                    id: parse(1)?,
+                    size: parse(2)?,
+                },
+                "r" => AllocationEvent::Resize {
+                    id: parse(1)?,
+                    size: parse(2)?,
+                },
+                "d" => AllocationEvent::Deallocate { id: parse(1)? },
+                "c" => AllocationEvent::Cleanup,
+                op => anyhow::bail!("Unknown op {} on line {}", op, line_idx + 1),
+            };
+            events.push(event);
+        }
+        Ok(Self { pool_size, events })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_text())
+            .with_context(|| format!("Failed to write allocation trace {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read allocation trace {}", path.display()))?;
+        Self::from_text(&text)
+    }
+
+    /// Replays the events against `strategy`, which must start out empty and at least as large
+    /// as the recorded pool.
+    ///
+    /// Events on allocations the strategy failed to make are counted as failed as well.
+    pub fn replay(&self, strategy: &mut dyn AllocationStrategy) -> ReplayReport {
+        // recorded id -> id handed out by the replayed strategy
+        let mut ids = HashMap::new();
+        let mut report = ReplayReport::default();
+        let mut fragmentation_sum = 0.0;
+
+        for event in &self.events {
+            let start = Instant::now();
+            let is_ok = match *event {
+                AllocationEvent::Allocate { id, size } => strategy
+                    .allocate(size)
+                    .map(|allocation| ids.insert(id, allocation.id))
+                    .is_ok(),
+                AllocationEvent::Resize { id, size } => ids
+                    .get(&id)
+                    .is_some_and(|&replayed_id| strategy.resize(replayed_id, size).is_ok()),
+                AllocationEvent::Deallocate { id } => ids
+                    .remove(&id)
+                    .is_some_and(|replayed_id| strategy.deallocate(replayed_id).is_ok()),
+                AllocationEvent::Cleanup => {
+                    strategy.cleanup();
+                    true
+                }
+            };
+            report.duration += start.elapsed();
+
+            report.op_count += 1;
+            if !is_ok {
+                report.failed_op_count += 1;
+            }
+            report.peak_allocated = report.peak_allocated.max(strategy.allocated_size());
+            let fragmentation = fragmentation_of(strategy);
+            fragmentation_sum += fragmentation as f64;
+            report.peak_fragmentation = report.peak_fragmentation.max(fragmentation);
+        }
+
+        if report.op_count > 0 {
+            report.mean_fragmentation = (fragmentation_sum / report.op_count as f64) as f32;
+        }
+        report.final_fragmentation = fragmentation_of(strategy);
+        report
+    }
+}
+
+/// How a strategy coped with a replayed trace.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayReport {
+    pub op_count: usize,
+    pub failed_op_count: usize,
+    /// Time spent inside the strategy, the bookkeeping of the replay is left out.
+    pub duration: Duration,
+    pub peak_allocated: u64,
+    /// Fragmentation after each op, see [`fragmentation_of`].
+    pub mean_fragmentation: f32,
+    pub peak_fragmentation: f32,
+    pub final_fragmentation: f32,
+}
+
+impl ReplayReport {
+    pub fn ops_per_second(&self) -> f64 {
+        self.op_count as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// From 0.0 when the free space is one block to close to 1.0 when it's scattered in small ones.
+pub fn fragmentation_of(strategy: &dyn AllocationStrategy) -> f32 {
+    let free = strategy.total_size() - strategy.allocated_size();
+    if free == 0 {
+        return 0.0;
+    }
+    1.0 - strategy.largest_free_block() as f32 / free as f32
+}
@@ -1,50 +1,143 @@
+use super::full_path_from_relative;
+use crate::constants::SHADER_MACROS;
 use shaderc::{CompileOptions, Compiler, OptimizationLevel};
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Records which files every shader source includes, so dependents of a shared header can be
+/// found when it changes.
+#[derive(Debug, Default)]
+pub struct ShaderDependencyGraph {
+    /// Source file -> files it includes directly, all paths are canonical.
+    includes: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+#[allow(unused)]
+impl ShaderDependencyGraph {
+    fn add_include(&mut self, requesting_source: PathBuf, included: PathBuf) {
+        self.includes
+            .entry(requesting_source)
+            .or_default()
+            .insert(included);
+    }
+
+    fn forget(&mut self, source: &Path) {
+        self.includes.remove(source);
+    }
+
+    /// All files `source` includes, directly or transitively.
+    pub fn dependencies_of(&self, source: &Path) -> Vec<PathBuf> {
+        let mut visited = HashSet::new();
+        let mut stack = vec![source.to_path_buf()];
+        while let Some(current) = stack.pop() {
+            let Some(includes) = self.includes.get(&current) else {
+                continue;
+            };
+            for included in includes {
+                if visited.insert(included.clone()) {
+                    stack.push(included.clone());
+                }
+            }
+        }
+        let mut result: Vec<PathBuf> = visited.into_iter().collect();
+        result.sort();
+        result
+    }
+
+    /// All files that include `header`, directly or transitively.
+    pub fn dependents_of(&self, header: &Path) -> Vec<PathBuf> {
+        let mut result: Vec<PathBuf> = self
+            .includes
+            .keys()
+            .filter(|source| self.dependencies_of(source).iter().any(|d| d == header))
+            .cloned()
+            .collect();
+        result.sort();
+        result
+    }
+}
 
 #[allow(unused)]
 pub struct ShaderCompiler<'a> {
     compiler: Compiler,
     default_options: CompileOptions<'a>,
+    include_search_paths: Arc<Mutex<Vec<PathBuf>>>,
+    dependency_graph: Arc<Mutex<ShaderDependencyGraph>>,
 }
 
 fn custom_include_callback(
     requested_source: &str,
     include_type: shaderc::IncludeType,
     requesting_source: &str,
-    _include_depth: usize,
+    include_search_paths: &[PathBuf],
+    dependency_graph: &Mutex<ShaderDependencyGraph>,
 ) -> Result<shaderc::ResolvedInclude, String> {
-    let base_dir = get_base_dir(include_type, requesting_source)?;
-
-    // create absolute path and normalise "..", ".", symlinks, …
-    let full_path = base_dir
-        .join(requested_source)
-        .canonicalize() // -> absolute, OS-native separators
-        .map_err(|e| format!("{}: {}", requested_source, e))?;
+    let full_path = resolve_include(
+        requested_source,
+        include_type,
+        requesting_source,
+        include_search_paths,
+    )?;
 
     let content = std::fs::read_to_string(&full_path)
         .map_err(|e| format!("{}: {}", full_path.display(), e))?;
 
+    dependency_graph
+        .lock()
+        .unwrap()
+        .add_include(canonical_or_given(requesting_source), full_path.clone());
+
     return Ok(shaderc::ResolvedInclude {
         resolved_name: full_path.to_string_lossy().into_owned(),
         content,
     });
 
-    fn get_base_dir(
+    /// `#include "x"` is looked up next to the requesting file first, then in the search paths.
+    /// `#include <x>` only uses the search paths.
+    fn resolve_include(
+        requested_source: &str,
         include_type: shaderc::IncludeType,
         requesting_source: &str,
+        include_search_paths: &[PathBuf],
     ) -> Result<PathBuf, String> {
-        match include_type {
-            shaderc::IncludeType::Relative => Ok(Path::new(requesting_source)
+        let mut candidates = Vec::new();
+        if include_type == shaderc::IncludeType::Relative {
+            let base_dir = Path::new(requesting_source)
                 .parent()
-                .ok_or_else(|| format!("`{requesting_source}` has no parent directory"))?
-                .to_owned()),
-            shaderc::IncludeType::Standard => {
-                Err("Standard include not supported for now".to_string())
-            }
+                .ok_or_else(|| format!("`{requesting_source}` has no parent directory"))?;
+            candidates.push(base_dir.join(requested_source));
         }
+        candidates.extend(
+            include_search_paths
+                .iter()
+                .map(|dir| dir.join(requested_source)),
+        );
+
+        // create absolute path and normalise "..", ".", symlinks, …
+        candidates
+            .iter()
+            .find_map(|candidate| candidate.canonicalize().ok())
+            .ok_or_else(|| {
+                format!(
+                    "{}: not found, tried {:?}",
+                    requested_source,
+                    candidates
+                        .iter()
+                        .map(|c| c.display().to_string())
+                        .collect::<Vec<_>>()
+                )
+            })
     }
 }
 
+fn canonical_or_given(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
 #[allow(unused)]
 impl<'a> ShaderCompiler<'a> {
     pub fn new() -> Result<Self, String> {
@@ -57,14 +150,64 @@ impl<'a> ShaderCompiler<'a> {
         );
         default_options.set_target_spirv(shaderc::SpirvVersion::V1_6);
         default_options.set_source_language(shaderc::SourceLanguage::GLSL);
-        default_options.set_include_callback(custom_include_callback);
+        for (name, value) in SHADER_MACROS {
+            default_options.add_macro_definition(name, Some(value));
+        }
+
+        let include_search_paths = Arc::new(Mutex::new(vec![PathBuf::from(
+            full_path_from_relative("shader/include"),
+        )]));
+        let dependency_graph = Arc::new(Mutex::new(ShaderDependencyGraph::default()));
+        {
+            let include_search_paths = include_search_paths.clone();
+            let dependency_graph = dependency_graph.clone();
+            default_options.set_include_callback(
+                move |requested_source, include_type, requesting_source, _include_depth| {
+                    let search_paths = include_search_paths.lock().unwrap().clone();
+                    custom_include_callback(
+                        requested_source,
+                        include_type,
+                        requesting_source,
+                        &search_paths,
+                        &dependency_graph,
+                    )
+                },
+            );
+        }
 
         Ok(Self {
             compiler,
             default_options,
+            include_search_paths,
+            dependency_graph,
         })
     }
 
+    /// Adds a directory, relative to the project root, that `#include` directives are resolved
+    /// against. `shader/include` is always searched.
+    pub fn add_include_search_path(&self, relative_dir: &str) {
+        self.include_search_paths
+            .lock()
+            .unwrap()
+            .push(PathBuf::from(full_path_from_relative(relative_dir)));
+    }
+
+    /// Injects the device's `maxComputeWorkGroupCount` as `MAX_DISPATCH_GROUP_COUNT_{X,Y,Z}`,
+    /// which `include/core/indirect_dispatch.glsl` clamps against.
+    pub fn set_dispatch_group_limits(&mut self, limits: [u32; 3]) {
+        for (axis, limit) in ["X", "Y", "Z"].iter().zip(limits) {
+            self.default_options.add_macro_definition(
+                &format!("MAX_DISPATCH_GROUP_COUNT_{}", axis),
+                Some(&limit.to_string()),
+            );
+        }
+    }
+
+    /// Defines `name` as `value` for every shader compiled afterwards.
+    pub fn define_macro(&mut self, name: &str, value: &str) {
+        self.default_options.add_macro_definition(name, Some(value));
+    }
+
     pub fn compile_to_bytecode(
         &self,
         code: &str,
@@ -76,6 +219,13 @@ impl<'a> ShaderCompiler<'a> {
         let mut compile_options = self.default_options.clone().unwrap();
         compile_options.set_optimization_level(optimization_level);
 
+        // the include edges of this file are recorded again during compilation, so removed
+        // includes don't linger
+        self.dependency_graph
+            .lock()
+            .unwrap()
+            .forget(&canonical_or_given(full_path_to_shader_file));
+
         let compilation_artifact = self
             .compiler
             .compile_into_spirv(
@@ -89,3 +239,52 @@ impl<'a> ShaderCompiler<'a> {
         Ok(compilation_artifact.as_binary_u8().into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph_of(edges: &[(&str, &str)]) -> ShaderDependencyGraph {
+        let mut graph = ShaderDependencyGraph::default();
+        for (from, to) in edges {
+            graph.add_include(PathBuf::from(from), PathBuf::from(to));
+        }
+        graph
+    }
+
+    #[test]
+    fn test_transitive_dependencies() {
+        let graph = graph_of(&[
+            ("tracer.comp", "marching.glsl"),
+            ("marching.glsl", "contree_node.glsl"),
+            ("collider.comp", "contree_node.glsl"),
+            ("taa.comp", "color.glsl"),
+        ]);
+
+        assert_eq!(
+            graph.dependencies_of(Path::new("tracer.comp")),
+            vec![
+                PathBuf::from("contree_node.glsl"),
+                PathBuf::from("marching.glsl")
+            ]
+        );
+        assert_eq!(
+            graph.dependents_of(Path::new("contree_node.glsl")),
+            vec![
+                PathBuf::from("collider.comp"),
+                PathBuf::from("marching.glsl"),
+                PathBuf::from("tracer.comp")
+            ]
+        );
+        assert!(graph.dependents_of(Path::new("tracer.comp")).is_empty());
+    }
+
+    #[test]
+    fn test_include_cycle_terminates() {
+        let graph = graph_of(&[("a.glsl", "b.glsl"), ("b.glsl", "a.glsl")]);
+        assert_eq!(
+            graph.dependencies_of(Path::new("a.glsl")),
+            vec![PathBuf::from("a.glsl"), PathBuf::from("b.glsl")]
+        );
+    }
+}
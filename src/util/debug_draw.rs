@@ -0,0 +1,141 @@
+use egui::{Color32, Pos2, Stroke};
+use glam::{Mat4, Vec3, Vec4};
+
+#[derive(Debug, Clone, Copy)]
+struct DebugLine {
+    from: Vec3,
+    to: Vec3,
+    color: Color32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DebugPoint {
+    pos: Vec3,
+    radius: f32,
+    color: Color32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DebugSphere {
+    center: Vec3,
+    radius: f32,
+    color: Color32,
+}
+
+/// Immediate-mode world-space debug shapes, painted as an egui overlay.
+///
+/// Shapes are queued during the frame and consumed by [`DebugDraw::paint`], so every user has to
+/// re-submit its shapes each frame.
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    points: Vec<DebugPoint>,
+    spheres: Vec<DebugSphere>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Color32) {
+        self.lines.push(DebugLine { from, to, color });
+    }
+
+    /// Draws a screen-space dot at a world position, `radius` is in logical pixels.
+    pub fn point(&mut self, pos: Vec3, radius: f32, color: Color32) {
+        self.points.push(DebugPoint { pos, radius, color });
+    }
+
+    /// Draws a disc facing the camera at a world position, `radius` is in world units so it
+    /// shrinks with distance.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Color32) {
+        self.spheres.push(DebugSphere {
+            center,
+            radius,
+            color,
+        });
+    }
+
+    /// Draws a closed polyline through the given points.
+    pub fn line_loop(&mut self, points: &[Vec3], color: Color32) {
+        for (i, from) in points.iter().enumerate() {
+            let to = points[(i + 1) % points.len()];
+            self.line(*from, to, color);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.points.clear();
+        self.spheres.clear();
+    }
+
+    /// Projects the queued shapes with `view_proj_mat` and paints them on top of everything else,
+    /// then clears the queue.
+    pub fn paint(&mut self, ctx: &egui::Context, view_proj_mat: Mat4) {
+        if self.lines.is_empty() && self.points.is_empty() && self.spheres.is_empty() {
+            return;
+        }
+
+        let screen_rect = ctx.viewport_rect();
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("debug_draw"),
+        ));
+
+        let project = |pos: Vec3| -> Option<Pos2> {
+            let clip = view_proj_mat * Vec4::new(pos.x, pos.y, pos.z, 1.0);
+            // behind the camera
+            if clip.w <= 1e-5 {
+                return None;
+            }
+            let ndc = clip / clip.w;
+            // the projection matrix is already y-flipped, so ndc y grows downwards like egui's
+            Some(Pos2::new(
+                screen_rect.min.x + (ndc.x * 0.5 + 0.5) * screen_rect.width(),
+                screen_rect.min.y + (ndc.y * 0.5 + 0.5) * screen_rect.height(),
+            ))
+        };
+
+        for line in &self.lines {
+            if let (Some(from), Some(to)) = (project(line.from), project(line.to)) {
+                painter.line_segment([from, to], Stroke::new(1.5, line.color));
+            }
+        }
+        for point in &self.points {
+            if let Some(pos) = project(point.pos) {
+                painter.circle_filled(pos, point.radius, point.color);
+            }
+        }
+        for sphere in &self.spheres {
+            // the vertical extent on screen is close enough to the projected radius
+            let (Some(center), Some(top)) = (
+                project(sphere.center),
+                project(sphere.center + Vec3::Y * sphere.radius),
+            ) else {
+                continue;
+            };
+            let radius = center.distance(top).max(1.0);
+            painter.circle(
+                center,
+                radius,
+                sphere.color,
+                Stroke::new(1.0, Color32::from_black_alpha(160)),
+            );
+        }
+
+        self.clear();
+    }
+}
+
+/// Maps `t` in [0, 1] to a red - yellow - green gradient, handy for visualizing distances.
+pub fn debug_heat_color(t: f32) -> Color32 {
+    let t = t.clamp(0.0, 1.0);
+    let (r, g) = if t < 0.5 {
+        (1.0, t * 2.0)
+    } else {
+        (1.0 - (t - 0.5) * 2.0, 1.0)
+    };
+    Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, 40)
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use glam::Vec3;
+use once_cell::sync::Lazy;
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::util::full_path_from_relative;
+
+/// Number of log lines kept for the diagnostics bundle.
+const LOG_HISTORY_CAPACITY: usize = 2000;
+
+/// Relative to the project root.
+const DIAGNOSTICS_DIR: &str = "diagnostics/";
+
+/// Minimum time between two frame captures, reading the frame back stalls the GPU.
+pub const FRAME_CAPTURE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A frame read back from the GPU, tightly packed RGBA8.
+#[derive(Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// What the app knows about itself, kept up to date so it is still available when it crashes.
+#[derive(Default)]
+pub struct DiagnosticsState {
+    pub gpu_info: String,
+    pub world_seed: u64,
+    pub camera_pos: Vec3,
+    pub camera_front: Vec3,
+    pub settings: Vec<(&'static str, String)>,
+    pub last_frame: Option<CapturedFrame>,
+    last_frame_time: Option<Instant>,
+    log_history: VecDeque<String>,
+}
+
+impl DiagnosticsState {
+    /// Whether enough time has passed since the last frame capture to take another one.
+    pub fn is_frame_capture_due(&self) -> bool {
+        self.last_frame_time
+            .is_none_or(|t| t.elapsed() >= FRAME_CAPTURE_INTERVAL)
+    }
+
+    pub fn set_last_frame(&mut self, frame: CapturedFrame) {
+        self.last_frame = Some(frame);
+        self.last_frame_time = Some(Instant::now());
+    }
+
+    pub fn push_log_line(&mut self, line: String) {
+        if self.log_history.len() == LOG_HISTORY_CAPACITY {
+            self.log_history.pop_front();
+        }
+        self.log_history.push_back(line);
+    }
+
+    fn summary(&self, reason: &str) -> String {
+        let mut summary = format!(
+            "reason: {}\ntime: {}\nversion: {}\n\n[gpu]\n{}\n\n[world]\nseed: {}\ncamera_pos: {:?}\ncamera_front: {:?}\n\n[settings]\n",
+            reason,
+            chrono::Local::now().to_rfc3339(),
+            env!("CARGO_PKG_VERSION"),
+            self.gpu_info,
+            self.world_seed,
+            self.camera_pos,
+            self.camera_front,
+        );
+        for (name, value) in &self.settings {
+            summary.push_str(&format!("{}: {}\n", name, value));
+        }
+        summary
+    }
+}
+
+pub static DIAGNOSTICS: Lazy<Mutex<DiagnosticsState>> =
+    Lazy::new(|| Mutex::new(DiagnosticsState::default()));
+
+/// Writes a zip with the recent log, the system info, the settings and the last captured frame to
+/// the diagnostics directory, returns its path.
+pub fn write_diagnostics_bundle(reason: &str) -> Result<PathBuf> {
+    // a panic while the lock is held must not stop the bundle from being written
+    let state = DIAGNOSTICS.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = PathBuf::from(full_path_from_relative(DIAGNOSTICS_DIR));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "crash_{}.zip",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+
+    let mut zip = ZipWriter::new(std::fs::File::create(&path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("summary.txt", options)?;
+    zip.write_all(state.summary(reason).as_bytes())?;
+
+    zip.start_file("log.txt", options)?;
+    for line in &state.log_history {
+        zip.write_all(line.as_bytes())?;
+        zip.write_all(b"\n")?;
+    }
+
+    if let Some(frame) = &state.last_frame {
+        let mut png = std::io::Cursor::new(Vec::new());
+        let image = image::RgbaImage::from_raw(frame.width, frame.height, frame.rgba.clone());
+        if let Some(image) = image {
+            image.write_to(&mut png, image::ImageFormat::Png)?;
+            zip.start_file("last_frame.png", options)?;
+            zip.write_all(png.get_ref())?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(path)
+}
+
+/// Writes a diagnostics bundle whenever the app panics, device loss surfaces as a panic on the
+/// failed submit or fence wait.
+pub fn install_crash_handler() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info.to_string();
+        let reason = if message.contains("ERROR_DEVICE_LOST") {
+            format!("device lost: {}", message)
+        } else {
+            format!("panic: {}", message)
+        };
+        match write_diagnostics_bundle(&reason) {
+            Ok(path) => log::error!("Wrote diagnostics bundle to {}", path.display()),
+            Err(e) => log::error!("Failed to write diagnostics bundle: {}", e),
+        }
+        default_hook(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_history_keeps_the_latest_lines() {
+        let mut state = DiagnosticsState::default();
+        for i in 0..LOG_HISTORY_CAPACITY + 5 {
+            state.push_log_line(i.to_string());
+        }
+        assert_eq!(state.log_history.len(), LOG_HISTORY_CAPACITY);
+        assert_eq!(state.log_history.front().unwrap(), "5");
+    }
+}
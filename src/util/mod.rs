@@ -27,3 +27,12 @@ pub use sun_dir::*;
 
 mod merge_with_eq;
 pub use merge_with_eq::*;
+
+mod debug_draw;
+pub use debug_draw::*;
+
+mod diagnostics;
+pub use diagnostics::*;
+
+mod profiling;
+pub use profiling::*;
@@ -0,0 +1,25 @@
+//! CPU zones for the tracy profiler, everything here compiles to nothing unless the `tracy`
+//! feature is enabled.
+
+/// Opens a profiler zone named `$name` that lasts until the end of the enclosing scope.
+macro_rules! profile_scope {
+    ($name:literal) => {
+        #[cfg(feature = "tracy")]
+        let _profile_span = tracy_client::span!($name);
+    };
+}
+pub(crate) use profile_scope;
+
+/// Connects to the profiler, call once before any zone is opened.
+pub fn start_profiler() {
+    #[cfg(feature = "tracy")]
+    let _ = tracy_client::Client::start();
+}
+
+/// Marks the end of a frame, so the profiler can group the zones per frame.
+pub fn mark_frame() {
+    #[cfg(feature = "tracy")]
+    if let Some(client) = tracy_client::Client::running() {
+        client.frame_mark();
+    }
+}
@@ -19,6 +19,9 @@ pub struct TimeInfo {
 
     // --- Overall statistics ---
     total_frame_count: u64,
+
+    // set once the time is stepped by hand, the time since start follows the steps from then on.
+    fixed_elapsed: Option<Duration>,
 }
 
 impl Default for TimeInfo {
@@ -34,6 +37,7 @@ impl Default for TimeInfo {
             fps_frame_count: 0,
             display_fps_value: 0.0,
             total_frame_count: 0,
+            fixed_elapsed: None,
         }
     }
 }
@@ -77,15 +81,26 @@ impl TimeInfo {
         }
     }
 
+    /// Advances the time by exactly `dt` seconds instead of following the wall clock.
+    ///
+    /// Used for offline rendering, where every frame must see the same time however long it
+    /// takes to render.
+    pub fn step_fixed(&mut self, dt: f32) {
+        let elapsed = self.fixed_elapsed.unwrap_or_default() + Duration::from_secs_f32(dt);
+        self.fixed_elapsed = Some(elapsed);
+        self.dt = dt;
+        self.total_frame_count += 1;
+    }
+
     /// Returns the total time in seconds since the `TimeInfo` was created.
     pub fn time_since_start(&self) -> f32 {
-        self.start_instant.elapsed().as_secs_f32()
+        self.time_since_start_duration().as_secs_f32()
     }
 
     /// Returns the total time as a `Duration` since the `TimeInfo` was created.
-    #[allow(dead_code)]
     pub fn time_since_start_duration(&self) -> Duration {
-        self.start_instant.elapsed()
+        self.fixed_elapsed
+            .unwrap_or_else(|| self.start_instant.elapsed())
     }
 
     /// Returns the delta time of the last frame, scaled by the `time_scale` factor.
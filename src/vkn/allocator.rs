@@ -39,6 +39,12 @@ impl Allocator {
         unsafe { self.device.destroy_buffer(buffer, None) };
     }
 
+    pub fn free_memory(&mut self, allocation: Allocation) {
+        self.get_allocator()
+            .free(allocation)
+            .expect("Failed to free memory");
+    }
+
     pub fn destroy_image(&mut self, image: vk::Image, allocation: Allocation) {
         let mut allocator = self.get_allocator();
 
@@ -1,5 +1,5 @@
 use super::CommandPool;
-use crate::vkn::{Device, Fence, Queue};
+use crate::vkn::{Device, Fence, Queue, TimelineSemaphore};
 use ash::vk;
 use std::sync::Arc;
 
@@ -83,6 +83,40 @@ impl CommandBuffer {
                 .unwrap();
         }
     }
+
+    /// Submits once the timelines in `waits` reach their values, and returns the value the
+    /// submission signals on `signal` when it's done.
+    pub fn submit_with_timelines(
+        &self,
+        queue: &Queue,
+        waits: &[(&TimelineSemaphore, u64)],
+        signal: &TimelineSemaphore,
+    ) -> u64 {
+        let command_buffers = [self.as_raw()];
+        let wait_semaphores: Vec<_> = waits.iter().map(|(sem, _)| sem.as_raw()).collect();
+        let wait_values: Vec<_> = waits.iter().map(|(_, value)| *value).collect();
+        let wait_stages = vec![vk::PipelineStageFlags::ALL_COMMANDS; waits.len()];
+        let signal_semaphores = [signal.as_raw()];
+        let signal_value = signal.advance();
+        let signal_values = [signal_value];
+
+        let mut timeline_submit_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_submit_info);
+        unsafe {
+            self.0
+                .device
+                .queue_submit(queue.as_raw(), &[submit_info], vk::Fence::null())
+                .unwrap();
+        }
+        signal_value
+    }
 }
 
 fn create_cmdbuf(device: &Device, command_pool: vk::CommandPool) -> vk::CommandBuffer {
@@ -7,6 +7,16 @@ use std::sync::Arc;
 
 struct DeviceInner {
     device: ash::Device,
+    /// `None` when the device lacks `VK_KHR_dynamic_rendering`.
+    dynamic_rendering: Option<ash::khr::dynamic_rendering::Device>,
+    /// Whether runtime sized, partially bound descriptor arrays can be used, see
+    /// [`supports_descriptor_indexing`].
+    is_descriptor_indexing_enabled: bool,
+    /// The families buffers and images are shared between, empty when everything runs on the
+    /// general family.
+    concurrent_queue_families: Vec<u32>,
+    /// The same with the transfer family added, empty when all of them are the general family.
+    upload_queue_families: Vec<u32>,
 }
 
 impl Drop for DeviceInner {
@@ -46,18 +56,98 @@ impl Device {
         physical_device: &PhysicalDevice,
         queue_family_indices: &QueueFamilyIndices,
     ) -> Self {
-        let device = create_device(
+        let (device, features) = create_device(
             instance.as_raw(),
             physical_device.as_raw(),
             queue_family_indices,
         );
-        Self(Arc::new(DeviceInner { device }))
+        let dynamic_rendering = features
+            .is_dynamic_rendering_enabled
+            .then(|| ash::khr::dynamic_rendering::Device::new(instance.as_raw(), &device));
+        log::info!(
+            "Dynamic rendering: {}",
+            if dynamic_rendering.is_some() {
+                "enabled"
+            } else {
+                "unavailable, falling back to render passes"
+            }
+        );
+        log::info!(
+            "Descriptor indexing: {}",
+            if features.is_descriptor_indexing_enabled {
+                "enabled"
+            } else {
+                "unavailable, texture arrays can't be bound"
+            }
+        );
+        let concurrent_queue_families =
+            if queue_family_indices.compute_only != queue_family_indices.general {
+                vec![
+                    queue_family_indices.general,
+                    queue_family_indices.compute_only,
+                ]
+            } else {
+                vec![]
+            };
+        let mut upload_queue_families = vec![queue_family_indices.general];
+        for family in [
+            queue_family_indices.compute_only,
+            queue_family_indices.transfer_only,
+        ] {
+            if !upload_queue_families.contains(&family) {
+                upload_queue_families.push(family);
+            }
+        }
+        if upload_queue_families.len() == 1 {
+            upload_queue_families.clear();
+        }
+        Self(Arc::new(DeviceInner {
+            device,
+            dynamic_rendering,
+            is_descriptor_indexing_enabled: features.is_descriptor_indexing_enabled,
+            concurrent_queue_families,
+            upload_queue_families,
+        }))
     }
 
     pub fn as_raw(&self) -> &ash::Device {
         &self.0.device
     }
 
+    /// The `VK_KHR_dynamic_rendering` commands, `None` if the device doesn't support them.
+    pub fn dynamic_rendering(&self) -> Option<&ash::khr::dynamic_rendering::Device> {
+        self.0.dynamic_rendering.as_ref()
+    }
+
+    /// Whether descriptor sets may hold variable sized arrays of textures, indexed non-uniformly
+    /// from the shaders.
+    pub fn is_descriptor_indexing_enabled(&self) -> bool {
+        self.0.is_descriptor_indexing_enabled
+    }
+
+    /// How buffers and images are shared: concurrently between the general and the compute family
+    /// when those differ, so builders can write them without ownership transfers.
+    pub fn resource_sharing(&self) -> (vk::SharingMode, &[u32]) {
+        if self.0.concurrent_queue_families.is_empty() {
+            (vk::SharingMode::EXCLUSIVE, &[])
+        } else {
+            (
+                vk::SharingMode::CONCURRENT,
+                &self.0.concurrent_queue_families,
+            )
+        }
+    }
+
+    /// Like [`Self::resource_sharing`] with the transfer family in too, for what the transfer
+    /// queue uploads in the background.
+    pub fn upload_sharing(&self) -> (vk::SharingMode, &[u32]) {
+        if self.0.upload_queue_families.is_empty() {
+            (vk::SharingMode::EXCLUSIVE, &[])
+        } else {
+            (vk::SharingMode::CONCURRENT, &self.0.upload_queue_families)
+        }
+    }
+
     pub fn wait_queue_idle(&self, queue: &Queue) {
         unsafe { self.as_raw().queue_wait_idle(queue.as_raw()).unwrap() };
     }
@@ -74,11 +164,61 @@ impl Device {
     }
 }
 
+/// Whether the device has `VK_KHR_dynamic_rendering` and its feature, it's optional as the raster
+/// passes can fall back to render passes.
+fn supports_dynamic_rendering(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> bool {
+    let extension_props = unsafe {
+        instance
+            .enumerate_device_extension_properties(physical_device)
+            .unwrap_or_default()
+    };
+    let has_extension = extension_props.iter().any(|ext| {
+        ext.extension_name_as_c_str()
+            .is_ok_and(|name| name == ash::khr::dynamic_rendering::NAME)
+    });
+    if !has_extension {
+        return false;
+    }
+
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+    let mut features =
+        vk::PhysicalDeviceFeatures2::default().push_next(&mut dynamic_rendering_features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+    dynamic_rendering_features.dynamic_rendering == vk::TRUE
+}
+
+/// Whether the device can bind runtime sized arrays of sampled and storage images, partially
+/// bound and with a count picked at allocation. It's optional, only texture arrays need it.
+fn supports_descriptor_indexing(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> bool {
+    let mut descriptor_indexing_features = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+    let mut features =
+        vk::PhysicalDeviceFeatures2::default().push_next(&mut descriptor_indexing_features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+    descriptor_indexing_features.runtime_descriptor_array == vk::TRUE
+        && descriptor_indexing_features.descriptor_binding_partially_bound == vk::TRUE
+        && descriptor_indexing_features.descriptor_binding_variable_descriptor_count == vk::TRUE
+        && descriptor_indexing_features.shader_sampled_image_array_non_uniform_indexing == vk::TRUE
+        && descriptor_indexing_features.shader_storage_image_array_non_uniform_indexing == vk::TRUE
+}
+
+/// The optional features [`create_device`] enabled.
+struct OptionalFeatures {
+    is_dynamic_rendering_enabled: bool,
+    is_descriptor_indexing_enabled: bool,
+}
+
+/// Returns: the device and which of the optional features are enabled on it
 fn create_device(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
     queue_family_indices: &QueueFamilyIndices,
-) -> ash::Device {
+) -> (ash::Device, OptionalFeatures) {
     let queue_priorities = [1.0f32];
     let queue_create_infos = {
         let mut indices = HashSet::new();
@@ -95,7 +235,10 @@ fn create_device(
             .collect::<Vec<_>>()
     };
 
-    let device_extensions_ptrs = [
+    let is_dynamic_rendering_enabled = supports_dynamic_rendering(instance, physical_device);
+    let is_descriptor_indexing_enabled = supports_descriptor_indexing(instance, physical_device);
+
+    let mut device_extensions_ptrs = vec![
         vk::KHR_SWAPCHAIN_NAME.as_ptr(),
         #[cfg(any(target_os = "macos", target_os = "ios"))]
         ash::khr::portability_subset::NAME.as_ptr(),
@@ -108,6 +251,9 @@ fn create_device(
         // vk::KHR_PIPELINE_LIBRARY_NAME.as_ptr(),
         // vk::KHR_BUFFER_DEVICE_ADDRESS_NAME.as_ptr(),
     ];
+    if is_dynamic_rendering_enabled {
+        device_extensions_ptrs.push(ash::khr::dynamic_rendering::NAME.as_ptr());
+    }
 
     let physical_device_features = vk::PhysicalDeviceFeatures {
         shader_int64: vk::TRUE,
@@ -147,17 +293,50 @@ fn create_device(
         ..Default::default()
     };
 
-    let device_create_info = vk::DeviceCreateInfo::default()
+    let mut timeline_semaphore_features = vk::PhysicalDeviceTimelineSemaphoreFeatures {
+        timeline_semaphore: vk::TRUE,
+        ..Default::default()
+    };
+
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures {
+        dynamic_rendering: vk::TRUE,
+        ..Default::default()
+    };
+
+    let mut descriptor_indexing_features = vk::PhysicalDeviceDescriptorIndexingFeatures {
+        runtime_descriptor_array: vk::TRUE,
+        descriptor_binding_partially_bound: vk::TRUE,
+        descriptor_binding_variable_descriptor_count: vk::TRUE,
+        shader_sampled_image_array_non_uniform_indexing: vk::TRUE,
+        shader_storage_image_array_non_uniform_indexing: vk::TRUE,
+        ..Default::default()
+    };
+
+    let mut device_create_info = vk::DeviceCreateInfo::default()
         .queue_create_infos(&queue_create_infos)
         .enabled_extension_names(&device_extensions_ptrs)
         .enabled_features(&physical_device_features)
         .push_next(&mut buffer_device_address_features)
         .push_next(&mut physical_device_shader_clock_features_khr)
-        .push_next(&mut physical_device_shader_atomic_float_features_khr);
+        .push_next(&mut physical_device_shader_atomic_float_features_khr)
+        .push_next(&mut timeline_semaphore_features);
+    if is_dynamic_rendering_enabled {
+        device_create_info = device_create_info.push_next(&mut dynamic_rendering_features);
+    }
+    if is_descriptor_indexing_enabled {
+        device_create_info = device_create_info.push_next(&mut descriptor_indexing_features);
+    }
 
-    unsafe {
+    let device = unsafe {
         instance
             .create_device(physical_device, &device_create_info, None)
             .expect("Failed to create logical device")
-    }
+    };
+    (
+        device,
+        OptionalFeatures {
+            is_dynamic_rendering_enabled,
+            is_descriptor_indexing_enabled,
+        },
+    )
 }
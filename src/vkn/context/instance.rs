@@ -5,11 +5,15 @@ use ash::{
 };
 use std::{
     ffi::{CStr, CString},
-    os::raw::{c_char, c_void},
+    os::raw::c_char,
     sync::Arc,
 };
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
+use super::validation::{set_validation_layer_active, vulkan_debug_callback};
+
+const VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
+
 struct InstanceInner {
     instance: ash::Instance,
     debug_utils: debug_utils::Instance,
@@ -37,9 +41,15 @@ impl std::ops::Deref for Instance {
 }
 
 impl Instance {
-    pub fn new(entry: &Entry, window: &Window, title: &str) -> Self {
+    /// Without a window no surface extensions are enabled, the instance can only render offscreen.
+    pub fn new(
+        entry: &Entry,
+        window: Option<&Window>,
+        title: &str,
+        enable_validation: bool,
+    ) -> Self {
         let (instance, debug_utils, debug_utils_messenger) =
-            create_vulkan_instance(entry, window, title);
+            create_vulkan_instance(entry, window, title, enable_validation);
         Self(Arc::new(InstanceInner {
             instance,
             debug_utils,
@@ -54,8 +64,9 @@ impl Instance {
 
 pub fn create_vulkan_instance(
     entry: &Entry,
-    window: &Window,
+    window: Option<&Window>,
     title: &str,
+    enable_validation: bool,
 ) -> (
     ash::Instance,
     debug_utils::Instance,
@@ -69,10 +80,14 @@ pub fn create_vulkan_instance(
         .engine_version(vk::make_api_version(0, 0, 1, 0))
         .api_version(vk::make_api_version(0, 1, 3, 0));
 
-    let mut extension_names =
-        ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw())
-            .unwrap()
-            .to_vec();
+    let mut extension_names = match window {
+        Some(window) => {
+            ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw())
+                .unwrap()
+                .to_vec()
+        }
+        None => Vec::new(),
+    };
     extension_names.push(debug_utils::NAME.as_ptr());
 
     #[cfg(any(target_os = "macos", target_os = "ios"))]
@@ -87,18 +102,13 @@ pub fn create_vulkan_instance(
         vk::InstanceCreateFlags::default()
     };
 
-    let layer_names_raw: Vec<*const c_char> = {
-        #[cfg(not(feature = "no_validation_layer"))]
-        {
-            {
-                let layer_names = [c"VK_LAYER_KHRONOS_validation"];
-                layer_names
-                    .iter()
-                    .map(|raw_name| raw_name.as_ptr())
-                    .collect()
-            }
-        }
-        #[cfg(feature = "no_validation_layer")]
+    let enable_validation = enable_validation && is_layer_available(entry, VALIDATION_LAYER_NAME);
+    set_validation_layer_active(enable_validation);
+    log::info!("Validation layer enabled: {}", enable_validation);
+
+    let layer_names_raw: Vec<*const c_char> = if enable_validation {
+        vec![VALIDATION_LAYER_NAME.as_ptr()]
+    } else {
         Vec::new()
     };
 
@@ -136,82 +146,22 @@ pub fn create_vulkan_instance(
     (instance, debug_utils, debug_utils_messenger)
 }
 
-struct ValidationCallbackDesc {
-    pretty_print: bool,
-    log_level: log::Level,
-}
-
-const DEFAULT_VALIDATION_CALLBACK_DESC: ValidationCallbackDesc = ValidationCallbackDesc {
-    pretty_print: true,
-    log_level: log::Level::Error,
-};
-
-unsafe extern "system" fn vulkan_debug_callback(
-    flag: vk::DebugUtilsMessageSeverityFlagsEXT,
-    ty: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _: *mut c_void,
-) -> vk::Bool32 {
-    use vk::DebugUtilsMessageSeverityFlagsEXT as Flag;
-
-    let message_level = match flag {
-        Flag::VERBOSE => log::Level::Debug,
-        Flag::INFO => log::Level::Info,
-        Flag::WARNING => log::Level::Warn,
-        Flag::ERROR => log::Level::Error,
-        _ => log::Level::Error, // Treat unknown flags as errors.
+fn is_layer_available(entry: &Entry, layer_name: &CStr) -> bool {
+    let layers = unsafe {
+        entry
+            .enumerate_instance_layer_properties()
+            .unwrap_or_default()
     };
-
-    // ERROR is the lowest level in the enum
-    if message_level > DEFAULT_VALIDATION_CALLBACK_DESC.log_level {
-        return vk::FALSE;
+    let is_available = layers.iter().any(|layer| {
+        layer
+            .layer_name_as_c_str()
+            .is_ok_and(|name| name == layer_name)
+    });
+    if !is_available {
+        log::warn!(
+            "{} was requested but is not installed",
+            layer_name.to_string_lossy()
+        );
     }
-
-    let message = CStr::from_ptr((*p_callback_data).p_message).to_string_lossy();
-    let header = format!("[Validation] {:?}", ty);
-
-    if DEFAULT_VALIDATION_CALLBACK_DESC.pretty_print {
-        let short_message = if let Some((msg, _)) = message.split_once(" (https://") {
-            msg
-        } else {
-            &message
-        };
-
-        let formatted_parts = short_message
-            .split('|')
-            .map(|s| s.trim())
-            .collect::<Vec<&str>>()
-            .join("\n");
-
-        let final_message = format!("\n* {}\n{}", header, formatted_parts);
-
-        match flag {
-            Flag::VERBOSE => log::debug!("{final_message}\n"),
-            Flag::INFO => log::info!("{final_message}\n"),
-            Flag::WARNING => log::warn!("{final_message}\n"),
-            Flag::ERROR => log::error!("{final_message}\n"),
-            _ => log::error!(
-                "\n* {} with unknown severity {:?}\n{}\n",
-                header,
-                flag,
-                formatted_parts
-            ),
-        }
-    } else {
-        // raw message output
-        match flag {
-            Flag::VERBOSE => log::debug!("{header} | {message}\n"),
-            Flag::INFO => log::info!("{header} | {message}\n"),
-            Flag::WARNING => log::warn!("{header} | {message}\n"),
-            Flag::ERROR => log::error!("{header} | {message}\n"),
-            _ => log::error!(
-                "{} with unknown severity {:?} | {}\n",
-                header,
-                flag,
-                message
-            ),
-        }
-    }
-
-    vk::FALSE
+    is_available
 }
@@ -2,6 +2,9 @@ mod instance;
 mod physical_device;
 mod surface;
 
+mod validation;
+pub use validation::*;
+
 mod vulkan_context;
 pub use vulkan_context::*;
 
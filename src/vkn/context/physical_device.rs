@@ -12,12 +12,9 @@ pub struct PhysicalDevice {
 }
 
 impl PhysicalDevice {
-    pub fn new(instance: &Instance, surface: &Surface) -> (Self, QueueFamilyIndices) {
-        let (device, queue_family_indices) = create_physical_device(
-            instance.as_raw(),
-            surface.surface_instance(),
-            surface.surface_khr(),
-        );
+    /// Without a surface, as in headless contexts, presenting isn't required of the device.
+    pub fn new(instance: &Instance, surface: Option<&Surface>) -> (Self, QueueFamilyIndices) {
+        let (device, queue_family_indices) = create_physical_device(instance.as_raw(), surface);
         (Self { device }, queue_family_indices)
     }
 
@@ -163,6 +160,10 @@ fn print_selected_queue_families(qf_indices: &QueueFamilyIndices) {
         "Dedicated Transfer (if available)",
         &qf_indices.transfer_only.to_string(),
     ]);
+    table.add_row(vec![
+        "Async Compute (if available)",
+        &qf_indices.compute_only.to_string(),
+    ]);
 
     println!("{}", table);
 }
@@ -187,8 +188,7 @@ impl QueueFamilyIndexCandidates {
 
 fn gather_queue_family_candidates(
     instance: &ash::Instance,
-    surface_loader: &ash::khr::surface::Instance,
-    surface_khr: vk::SurfaceKHR,
+    surface: Option<&Surface>,
     device: vk::PhysicalDevice,
 ) -> QueueFamilyIndexCandidates {
     let props = unsafe { instance.get_physical_device_queue_family_properties(device) };
@@ -218,10 +218,15 @@ fn gather_queue_family_candidates(
             sparse_binding.push(index);
         }
 
-        let present_support = unsafe {
-            surface_loader
-                .get_physical_device_surface_support(device, index, surface_khr)
-                .unwrap_or(false) // Assume no support on error
+        // nothing is presented without a surface, any graphics queue will do
+        let present_support = match surface {
+            Some(surface) => unsafe {
+                surface
+                    .surface_instance()
+                    .get_physical_device_surface_support(device, index, surface.surface_khr())
+                    .unwrap_or(false) // Assume no support on error
+            },
+            None => family.queue_flags.contains(vk::QueueFlags::GRAPHICS),
         };
         if present_support {
             present.push(index);
@@ -288,9 +293,18 @@ fn pick_best_queue_family_indices(
             .unwrap_or(general_idx) // Fallback: use the general queue if no other option exists.
     };
 
+    // A compute family without graphics runs builder work asynchronously to rendering.
+    let compute_only_idx = queue_family_index_candidates
+        .compute
+        .iter()
+        .find(|&&idx| !queue_family_index_candidates.graphics.contains(&idx))
+        .cloned()
+        .unwrap_or(general_idx);
+
     Some(QueueFamilyIndices {
         general: general_idx,
         transfer_only: transfer_only_idx,
+        compute_only: compute_only_idx,
     })
 }
 
@@ -308,8 +322,7 @@ fn pick_best_queue_family_indices(
 ///    preferring dedicated queues for transfer operations where possible.
 pub fn create_physical_device(
     instance: &ash::Instance,
-    surface_loader: &ash::khr::surface::Instance,
-    surface_khr: vk::SurfaceKHR,
+    surface: Option<&Surface>,
 ) -> (vk::PhysicalDevice, QueueFamilyIndices) {
     // A temporary struct to hold evaluation data for all devices.
     struct DeviceEvaluation {
@@ -415,8 +428,7 @@ pub fn create_physical_device(
             let score = gpu_type_score + mem_score;
 
             let missing_extensions = get_missing_required_extensions(instance, dev);
-            let queue_family_candidates =
-                gather_queue_family_candidates(instance, surface_loader, surface_khr, dev);
+            let queue_family_candidates = gather_queue_family_candidates(instance, surface, dev);
             let queue_families_complete = queue_family_candidates.is_complete();
             let has_all_purpose_queue =
                 pick_best_queue_family_indices(&queue_family_candidates).is_some();
@@ -464,12 +476,8 @@ pub fn create_physical_device(
     // 6. Select the best device and get its queue information.
     let best_device_info = &suitable_devices[0];
 
-    let queue_family_index_candidates = gather_queue_family_candidates(
-        instance,
-        surface_loader,
-        surface_khr,
-        best_device_info.device,
-    );
+    let queue_family_index_candidates =
+        gather_queue_family_candidates(instance, surface, best_device_info.device);
 
     print_queue_family_info(
         instance,
@@ -6,10 +6,13 @@ pub struct QueueFamilyIndices {
     /// Exclusive to transfer operations, may be slower, but enables
     /// potential parallelism for background transfer operations
     pub transfer_only: u32,
+    /// Compute without graphics, so builder work can run next to rendering. Falls back to
+    /// `general` when the device has no such family
+    pub compute_only: u32,
 }
 
 impl QueueFamilyIndices {
     pub fn get_all_indices(&self) -> Vec<u32> {
-        vec![self.general, self.transfer_only]
+        vec![self.general, self.transfer_only, self.compute_only]
     }
 }
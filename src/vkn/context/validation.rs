@@ -0,0 +1,200 @@
+use ash::vk;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    hash::{DefaultHasher, Hash, Hasher},
+    os::raw::c_void,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Set to `1` or `0` to force the validation layer on or off for a run, overriding the
+/// `no_validation_layer` feature.
+pub const VALIDATION_ENV_VAR: &str = "REFLORA_VALIDATION";
+
+/// How validation messages are routed into the log, can be changed while running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationSettings {
+    /// The least severe level that still gets logged.
+    pub min_level: log::Level,
+    /// Log each distinct message once and only count its repeats.
+    pub deduplicate: bool,
+    /// Abort with a backtrace on the first error, so an attached debugger stops at the call that
+    /// caused it.
+    pub break_on_error: bool,
+    /// Split the `|` separated sections of a message onto their own lines.
+    pub pretty_print: bool,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            min_level: log::Level::Error,
+            deduplicate: true,
+            break_on_error: false,
+            pretty_print: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidationStats {
+    pub unique_messages: usize,
+    pub suppressed_duplicates: u64,
+}
+
+#[derive(Default)]
+struct ValidationState {
+    settings: ValidationSettings,
+    /// Message hash -> number of times it was reported.
+    occurrences: HashMap<u64, u64>,
+    suppressed_duplicates: u64,
+}
+
+impl ValidationState {
+    /// Counts the message and returns how often it has been seen, including this time.
+    fn record(&mut self, message_hash: u64) -> u64 {
+        let count = self.occurrences.entry(message_hash).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            self.suppressed_duplicates += 1;
+        }
+        *count
+    }
+}
+
+static VALIDATION_STATE: Lazy<Mutex<ValidationState>> =
+    Lazy::new(|| Mutex::new(ValidationState::default()));
+
+static IS_VALIDATION_LAYER_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether this run asks for the validation layer, see [`VALIDATION_ENV_VAR`].
+pub fn is_validation_requested() -> bool {
+    match std::env::var(VALIDATION_ENV_VAR).as_deref() {
+        Ok("1") => true,
+        Ok("0") => false,
+        _ => cfg!(not(feature = "no_validation_layer")),
+    }
+}
+
+/// Whether the validation layer was actually loaded, it may be requested but not installed.
+pub fn is_validation_layer_active() -> bool {
+    IS_VALIDATION_LAYER_ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(super) fn set_validation_layer_active(active: bool) {
+    IS_VALIDATION_LAYER_ACTIVE.store(active, Ordering::Relaxed);
+}
+
+pub fn validation_settings() -> ValidationSettings {
+    VALIDATION_STATE.lock().unwrap().settings
+}
+
+pub fn set_validation_settings(settings: ValidationSettings) {
+    VALIDATION_STATE.lock().unwrap().settings = settings;
+}
+
+pub fn validation_stats() -> ValidationStats {
+    let state = VALIDATION_STATE.lock().unwrap();
+    ValidationStats {
+        unique_messages: state.occurrences.len(),
+        suppressed_duplicates: state.suppressed_duplicates,
+    }
+}
+
+/// Forgets the seen messages, so the next occurrence of each is logged again.
+pub fn reset_validation_messages() {
+    let mut state = VALIDATION_STATE.lock().unwrap();
+    state.occurrences.clear();
+    state.suppressed_duplicates = 0;
+}
+
+pub(super) unsafe extern "system" fn vulkan_debug_callback(
+    flag: vk::DebugUtilsMessageSeverityFlagsEXT,
+    ty: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _: *mut c_void,
+) -> vk::Bool32 {
+    use vk::DebugUtilsMessageSeverityFlagsEXT as Flag;
+
+    let message_level = match flag {
+        Flag::VERBOSE => log::Level::Debug,
+        Flag::INFO => log::Level::Info,
+        Flag::WARNING => log::Level::Warn,
+        Flag::ERROR => log::Level::Error,
+        _ => log::Level::Error, // Treat unknown flags as errors.
+    };
+
+    // the callback can run on driver threads, so the state is only held while deciding
+    let message = CStr::from_ptr((*p_callback_data).p_message).to_string_lossy();
+    let (settings, count) = {
+        let mut state = VALIDATION_STATE.lock().unwrap();
+        let settings = state.settings;
+        // ERROR is the lowest level in the enum
+        if message_level > settings.min_level {
+            return vk::FALSE;
+        }
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        (settings, state.record(hasher.finish()))
+    };
+
+    let header = format!("[Validation] {:?}", ty);
+    if settings.deduplicate && count > 1 {
+        // keep a trace of messages that flood every frame without repeating them
+        if count.is_power_of_two() && count >= 16 {
+            log::log!(
+                message_level,
+                "{} repeated {} times: {}",
+                header,
+                count,
+                message.split(" | ").next().unwrap_or(&message)
+            );
+        }
+    } else if settings.pretty_print {
+        let short_message = if let Some((msg, _)) = message.split_once(" (https://") {
+            msg
+        } else {
+            &message
+        };
+
+        let formatted_parts = short_message
+            .split('|')
+            .map(|s| s.trim())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        log::log!(message_level, "\n* {}\n{}\n", header, formatted_parts);
+    } else {
+        log::log!(message_level, "{header} | {message}\n");
+    }
+
+    if settings.break_on_error && flag == Flag::ERROR {
+        log::error!(
+            "Breaking on validation error, backtrace:\n{}",
+            std::backtrace::Backtrace::force_capture()
+        );
+        std::process::abort();
+    }
+
+    vk::FALSE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_repeats_count_as_suppressed() {
+        let mut state = ValidationState::default();
+        assert_eq!(state.record(1), 1);
+        assert_eq!(state.record(2), 1);
+        assert_eq!(state.record(1), 2);
+        assert_eq!(state.record(1), 3);
+        assert_eq!(state.occurrences.len(), 2);
+        assert_eq!(state.suppressed_duplicates, 2);
+    }
+}
@@ -1,15 +1,18 @@
-use crate::vkn::CommandPool;
+use crate::vkn::{CommandBuffer, CommandPool, TimelineSemaphore};
 
 use super::{
     device::Device, instance::Instance, physical_device::PhysicalDevice, queue::QueueFamilyIndices,
     surface::Surface, Queue,
 };
+use anyhow::Result;
 use ash::{prelude::VkResult, vk, Entry};
 use std::sync::Arc;
 use winit::window::Window;
 
 pub struct VulkanContextDesc {
     pub name: String,
+    /// Load the Khronos validation layer if it's installed, see [`crate::vkn::is_validation_requested`].
+    pub enable_validation: bool,
 }
 
 struct VulkanContextInner {
@@ -17,7 +20,8 @@ struct VulkanContextInner {
     fast_access_items: FastAccessItems,
 
     device: Device,
-    surface: Surface,
+    /// `None` for headless contexts.
+    surface: Option<Surface>,
     instance: Instance,
     physical_device: PhysicalDevice,
     queue_family_indices: QueueFamilyIndices,
@@ -31,12 +35,29 @@ impl Drop for VulkanContextInner {
 
 struct FastAccessItems {
     command_pool: CommandPool,
+    compute_command_pool: CommandPool,
+    transfer_command_pool: CommandPool,
+    frame_timeline: TimelineSemaphore,
+    compute_timeline: TimelineSemaphore,
+    transfer_timeline: TimelineSemaphore,
 }
 
 impl FastAccessItems {
     pub fn new(device: &Device, queue_family_indices: &QueueFamilyIndices) -> Self {
         let command_pool = CommandPool::new(device, queue_family_indices.general);
-        Self { command_pool }
+        let compute_command_pool = CommandPool::new(device, queue_family_indices.compute_only);
+        let transfer_command_pool = CommandPool::new(device, queue_family_indices.transfer_only);
+        let frame_timeline = TimelineSemaphore::new(device);
+        let compute_timeline = TimelineSemaphore::new(device);
+        let transfer_timeline = TimelineSemaphore::new(device);
+        Self {
+            command_pool,
+            compute_command_pool,
+            transfer_command_pool,
+            frame_timeline,
+            compute_timeline,
+            transfer_timeline,
+        }
     }
 }
 
@@ -45,16 +66,27 @@ pub struct VulkanContext(Arc<VulkanContextInner>);
 
 impl VulkanContext {
     pub fn new(window: &Window, desc: VulkanContextDesc) -> Self {
+        Self::create(Some(window), desc)
+    }
+
+    /// A context without a surface or swapchain, for rendering offscreen without opening a
+    /// window.
+    pub fn new_headless(desc: VulkanContextDesc) -> Self {
+        Self::create(None, desc)
+    }
+
+    fn create(window: Option<&Window>, desc: VulkanContextDesc) -> Self {
         let entry = Entry::linked();
 
-        let instance = Instance::new(&entry, window, &desc.name);
-        let surface = Surface::new(&entry, &instance, window);
-        let (physical_device, queue_family_indices) = PhysicalDevice::new(&instance, &surface);
+        let instance = Instance::new(&entry, window, &desc.name, desc.enable_validation);
+        let surface = window.map(|window| Surface::new(&entry, &instance, window));
+        let (physical_device, queue_family_indices) =
+            PhysicalDevice::new(&instance, surface.as_ref());
         let device = Device::new(&instance, &physical_device, &queue_family_indices);
 
         let fast_access_items = FastAccessItems::new(&device, &queue_family_indices);
 
-        Self(Arc::new(VulkanContextInner {
+        let vulkan_ctx = Self(Arc::new(VulkanContextInner {
             fast_access_items,
 
             device,
@@ -62,7 +94,16 @@ impl VulkanContext {
             instance,
             physical_device,
             queue_family_indices,
-        }))
+        }));
+        log::info!(
+            "Async compute: {}",
+            if vulkan_ctx.has_async_compute() {
+                "enabled"
+            } else {
+                "unavailable, builders share the general queue"
+            }
+        );
+        vulkan_ctx
     }
 
     /// Wait for all fences without a timeout
@@ -79,15 +120,21 @@ impl VulkanContext {
         self.device().get_queue(self.0.queue_family_indices.general)
     }
 
-    /// Obtains the transfer-only queue from the device
-    #[allow(dead_code)]
-    pub fn get_transfer_only_queue(&self) -> vk::Queue {
-        unsafe {
-            self.0
-                .device
-                .as_raw()
-                .get_device_queue(self.0.queue_family_indices.transfer_only, 0)
-        }
+    /// The queue builders submit to, see [`QueueFamilyIndices::compute_only`].
+    pub fn get_compute_queue(&self) -> Queue {
+        self.device()
+            .get_queue(self.0.queue_family_indices.compute_only)
+    }
+
+    /// Whether the compute queue runs next to the general one rather than being the same queue.
+    pub fn has_async_compute(&self) -> bool {
+        self.0.queue_family_indices.compute_only != self.0.queue_family_indices.general
+    }
+
+    /// The queue uploads run on in the background, see [`QueueFamilyIndices::transfer_only`].
+    pub fn get_transfer_only_queue(&self) -> Queue {
+        self.device()
+            .get_queue(self.0.queue_family_indices.transfer_only)
     }
 
     /// Expose references to inner fields if needed
@@ -95,8 +142,17 @@ impl VulkanContext {
         &self.0.device
     }
 
+    /// Panics for headless contexts, which have nothing to present to.
     pub fn surface(&self) -> &Surface {
-        &self.0.surface
+        self.0
+            .surface
+            .as_ref()
+            .expect("Headless contexts have no surface")
+    }
+
+    #[allow(dead_code)]
+    pub fn is_headless(&self) -> bool {
+        self.0.surface.is_none()
     }
 
     pub fn instance(&self) -> &Instance {
@@ -110,4 +166,147 @@ impl VulkanContext {
     pub fn command_pool(&self) -> &CommandPool {
         &self.0.fast_access_items.command_pool
     }
+
+    /// Allocates command buffers for [`VulkanContext::get_compute_queue`].
+    pub fn compute_command_pool(&self) -> &CommandPool {
+        &self.0.fast_access_items.compute_command_pool
+    }
+
+    /// Signalled by every compute queue submission, frames wait on it before reading what the
+    /// builders wrote.
+    pub fn compute_timeline(&self) -> &TimelineSemaphore {
+        &self.0.fast_access_items.compute_timeline
+    }
+
+    /// Allocates command buffers for [`VulkanContext::get_transfer_only_queue`].
+    pub fn transfer_command_pool(&self) -> &CommandPool {
+        &self.0.fast_access_items.transfer_command_pool
+    }
+
+    /// Signalled by every transfer queue submission, frames wait on it before sampling what was
+    /// uploaded.
+    pub fn transfer_timeline(&self) -> &TimelineSemaphore {
+        &self.0.fast_access_items.transfer_timeline
+    }
+
+    /// Submits `cmdbuf` to the compute queue once every earlier compute submission and `waits`
+    /// are done, so builder work is chained on the GPU. Returns the compute timeline value it
+    /// signals when it's done itself.
+    pub fn submit_compute(
+        &self,
+        cmdbuf: &CommandBuffer,
+        waits: &[(&TimelineSemaphore, u64)],
+    ) -> u64 {
+        let compute_timeline = self.compute_timeline();
+        let mut all_waits = vec![(compute_timeline, compute_timeline.submitted_value())];
+        all_waits.extend_from_slice(waits);
+        cmdbuf.submit_with_timelines(&self.get_compute_queue(), &all_waits, compute_timeline)
+    }
+
+    /// Blocks until the compute queue is done with everything submitted so far, needed before
+    /// general queue work outside of the frames touches what the builders write.
+    pub fn wait_compute(&self) -> Result<()> {
+        let compute_timeline = self.compute_timeline();
+        compute_timeline.wait(compute_timeline.submitted_value())
+    }
+
+    /// Signalled by every frame submission, tells which frames the GPU is done with.
+    pub fn frame_timeline(&self) -> &TimelineSemaphore {
+        &self.0.fast_access_items.frame_timeline
+    }
+
+    /// Returns `maxComputeWorkGroupCount` of the physical device.
+    pub fn max_compute_work_group_count(&self) -> [u32; 3] {
+        let properties = unsafe {
+            self.0
+                .instance
+                .as_raw()
+                .get_physical_device_properties(self.0.physical_device.as_raw())
+        };
+        properties.limits.max_compute_work_group_count
+    }
+
+    /// Returns `maxComputeWorkGroupInvocations` of the physical device.
+    pub fn max_compute_work_group_invocations(&self) -> u32 {
+        let properties = unsafe {
+            self.0
+                .instance
+                .as_raw()
+                .get_physical_device_properties(self.0.physical_device.as_raw())
+        };
+        properties.limits.max_compute_work_group_invocations
+    }
+
+    /// A file-name friendly key that identifies the physical device and its driver version, used to
+    /// key per-device caches.
+    pub fn device_cache_key(&self) -> String {
+        let properties = unsafe {
+            self.0
+                .instance
+                .as_raw()
+                .get_physical_device_properties(self.0.physical_device.as_raw())
+        };
+        format!(
+            "{:04x}_{:04x}_{:x}",
+            properties.vendor_id, properties.device_id, properties.driver_version
+        )
+    }
+
+    /// Human readable device name, ids and driver / api versions, for bug reports.
+    pub fn device_summary(&self) -> String {
+        let properties = unsafe {
+            self.0
+                .instance
+                .as_raw()
+                .get_physical_device_properties(self.0.physical_device.as_raw())
+        };
+        let device_name = properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        format!(
+            "device: {} ({:?})\nvendor_id: {:#06x}\ndevice_id: {:#06x}\ndriver_version: {:#x}\napi_version: {}.{}.{}",
+            device_name,
+            properties.device_type,
+            properties.vendor_id,
+            properties.device_id,
+            properties.driver_version,
+            vk::api_version_major(properties.api_version),
+            vk::api_version_minor(properties.api_version),
+            vk::api_version_patch(properties.api_version),
+        )
+    }
+
+    /// Nanoseconds per timestamp tick, `None` if the general queue can't write timestamps.
+    pub fn timestamp_period_ns(&self) -> Option<f32> {
+        let instance = self.0.instance.as_raw();
+        let physical_device = self.0.physical_device.as_raw();
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let valid_bits = queue_families
+            .get(self.0.queue_family_indices.general as usize)
+            .map_or(0, |family| family.timestamp_valid_bits);
+        (valid_bits > 0 && properties.limits.timestamp_period > 0.0)
+            .then_some(properties.limits.timestamp_period)
+    }
+
+    /// Whether compute shaders can use every subgroup operation in `operations`.
+    pub fn supports_compute_subgroup_ops(&self, operations: vk::SubgroupFeatureFlags) -> bool {
+        let mut subgroup_properties = vk::PhysicalDeviceSubgroupProperties::default();
+        let mut properties =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut subgroup_properties);
+        unsafe {
+            self.0
+                .instance
+                .as_raw()
+                .get_physical_device_properties2(self.0.physical_device.as_raw(), &mut properties)
+        };
+        subgroup_properties
+            .supported_stages
+            .contains(vk::ShaderStageFlags::COMPUTE)
+            && subgroup_properties
+                .supported_operations
+                .contains(operations)
+    }
 }
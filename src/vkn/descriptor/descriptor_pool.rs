@@ -93,9 +93,16 @@ impl DescriptorPool {
     /// Allocates a descriptor set from this pool and stores it internally.
     pub fn allocate_set(&self, layout: &DescriptorSetLayout) -> Result<DescriptorSet> {
         let set_layouts = [layout.as_raw()];
-        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+        let variable_counts = [layout.variable_descriptor_count().unwrap_or(0)];
+        let mut variable_count_info =
+            vk::DescriptorSetVariableDescriptorCountAllocateInfo::default()
+                .descriptor_counts(&variable_counts);
+        let mut alloc_info = vk::DescriptorSetAllocateInfo::default()
             .descriptor_pool(self.0.descriptor_pool)
             .set_layouts(&set_layouts);
+        if layout.variable_descriptor_count().is_some() {
+            alloc_info = alloc_info.push_next(&mut variable_count_info);
+        }
 
         let set = unsafe {
             self.0
@@ -65,6 +65,33 @@ impl<'a> WriteDescriptorSet<'a> {
         }
     }
 
+    /// Writes `textures` to the first elements of an array binding, the rest keep what they held.
+    pub fn new_texture_array_write(
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        textures: &[Texture],
+        image_layout: vk::ImageLayout,
+    ) -> Self {
+        let image_infos = textures
+            .iter()
+            .map(|texture| {
+                vk::DescriptorImageInfo::default()
+                    .image_layout(image_layout)
+                    .image_view(texture.get_image_view().as_raw())
+                    .sampler(texture.get_sampler().as_raw())
+            })
+            .collect();
+
+        Self {
+            binding,
+            descriptor_type,
+            image_infos: Some(image_infos),
+            buffer_infos: None,
+            accel_struct_infos: None,
+            _accel_handles: None,
+        }
+    }
+
     pub fn new_buffer_write(binding: u32, buffer: &Buffer) -> Self {
         let buffer_info = vk::DescriptorBufferInfo::default()
             .buffer(buffer.as_raw())
@@ -3,6 +3,10 @@ use anyhow::Result;
 use ash::vk;
 use std::{collections::HashMap, sync::Arc};
 
+/// Descriptors reserved for a runtime sized array binding, `sampler2D textures[]` in GLSL. Sets
+/// are allocated with room for this many, fewer may be written.
+pub const MAX_VARIABLE_DESCRIPTOR_COUNT: u32 = 1024;
+
 #[derive(Debug)]
 struct DescriptorSetLayoutInner {
     device: Device,
@@ -32,9 +36,36 @@ impl std::ops::Deref for DescriptorSetLayout {
 impl DescriptorSetLayout {
     /// Use the builder pattern to create a new DescriptorSetLayout
     fn new(device: &Device, bindings: &HashMap<u32, DescriptorSetLayoutBinding>) -> Result<Self> {
+        let variable_binding = bindings.values().find(|b| b.is_variable_count());
+        if let Some(variable_binding) = variable_binding {
+            if !device.is_descriptor_indexing_enabled() {
+                return Err(anyhow::anyhow!(
+                    "Binding '{}' is a runtime sized array, the device lacks descriptor indexing",
+                    variable_binding.name
+                ));
+            }
+            // vulkan only allows the last binding of a set to vary in size
+            if bindings.keys().any(|no| *no > variable_binding.no) {
+                return Err(anyhow::anyhow!(
+                    "Binding '{}' is a runtime sized array but not the last binding of its set",
+                    variable_binding.name
+                ));
+            }
+        }
+
         let raw_bindings = bindings.iter().map(|b| b.1.as_raw()).collect::<Vec<_>>();
-        let descriptor_set_create_info =
+        let binding_flags = bindings
+            .values()
+            .map(|b| b.binding_flags())
+            .collect::<Vec<_>>();
+        let mut binding_flags_create_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+        let mut descriptor_set_create_info =
             vk::DescriptorSetLayoutCreateInfo::default().bindings(&raw_bindings);
+        if variable_binding.is_some() {
+            descriptor_set_create_info =
+                descriptor_set_create_info.push_next(&mut binding_flags_create_info);
+        }
         let descriptor_set_layout = unsafe {
             device
                 .create_descriptor_set_layout(&descriptor_set_create_info, None)
@@ -56,6 +87,16 @@ impl DescriptorSetLayout {
         self.0.bindings.values().cloned().collect::<Vec<_>>()
     }
 
+    /// The descriptor count to allocate the runtime sized array of this layout with, `None`
+    /// without one.
+    pub fn variable_descriptor_count(&self) -> Option<u32> {
+        self.0
+            .bindings
+            .values()
+            .any(|b| b.is_variable_count())
+            .then_some(MAX_VARIABLE_DESCRIPTOR_COUNT)
+    }
+
     pub fn merge(&self, other: &DescriptorSetLayout) -> Result<Self> {
         if self.0.device != other.0.device {
             return Err(anyhow::anyhow!(
@@ -74,18 +115,38 @@ pub struct DescriptorSetLayoutBinding {
     pub no: u32,
     pub name: String,
     pub descriptor_type: vk::DescriptorType,
+    /// Zero for a runtime sized array, see [`MAX_VARIABLE_DESCRIPTOR_COUNT`].
     pub descriptor_count: u32,
     pub stage_flags: vk::ShaderStageFlags,
 }
 
 impl DescriptorSetLayoutBinding {
+    /// Whether it's a runtime sized array, its descriptors are partially bound then.
+    pub fn is_variable_count(&self) -> bool {
+        self.descriptor_count == 0
+    }
+
     fn as_raw(&self) -> vk::DescriptorSetLayoutBinding<'_> {
+        let descriptor_count = if self.is_variable_count() {
+            MAX_VARIABLE_DESCRIPTOR_COUNT
+        } else {
+            self.descriptor_count
+        };
         vk::DescriptorSetLayoutBinding::default()
             .binding(self.no)
             .descriptor_type(self.descriptor_type)
-            .descriptor_count(self.descriptor_count)
+            .descriptor_count(descriptor_count)
             .stage_flags(self.stage_flags)
     }
+
+    fn binding_flags(&self) -> vk::DescriptorBindingFlags {
+        if self.is_variable_count() {
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+        } else {
+            vk::DescriptorBindingFlags::empty()
+        }
+    }
 }
 
 pub struct DescriptorSetLayoutBuilder {
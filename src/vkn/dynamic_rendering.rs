@@ -0,0 +1,124 @@
+use crate::vkn::{AttachmentType, CommandBuffer, Device, RenderPass, Texture};
+use ash::vk;
+
+/// The attachment formats a pipeline renders into with dynamic rendering, in place of a
+/// render pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderingFormats {
+    pub color_formats: Vec<vk::Format>,
+    pub depth_format: Option<vk::Format>,
+}
+
+impl RenderingFormats {
+    pub fn from_textures(color_textures: &[&Texture], depth_texture: Option<&Texture>) -> Self {
+        Self {
+            color_formats: color_textures
+                .iter()
+                .map(|tex| tex.get_image().get_desc().format)
+                .collect(),
+            depth_format: depth_texture.map(|tex| tex.get_image().get_desc().format),
+        }
+    }
+}
+
+/// What a graphics pipeline is built to render into.
+#[derive(Clone, Copy)]
+pub enum RenderingTarget<'a> {
+    RenderPass(&'a RenderPass),
+    /// Needs `VK_KHR_dynamic_rendering`, see [`Device::dynamic_rendering`].
+    Dynamic(&'a RenderingFormats),
+}
+
+impl RenderingTarget<'_> {
+    pub fn color_attachment_count(&self) -> usize {
+        match self {
+            Self::RenderPass(render_pass) => render_pass
+                .get_desc()
+                .subpasses
+                .first()
+                .map_or(0, |subpass| subpass.color_attachments.len()),
+            Self::Dynamic(formats) => formats.color_formats.len(),
+        }
+    }
+}
+
+/// An attachment of a dynamic rendering instance, named while recording so nothing has to be
+/// rebuilt when the textures are recreated.
+pub struct RenderingAttachment<'a> {
+    pub texture: &'a Texture,
+    pub ty: AttachmentType,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    /// Only used with `AttachmentLoadOp::CLEAR`.
+    pub clear_value: vk::ClearValue,
+    /// The texture is transitioned to it before rendering and left in it.
+    pub layout: vk::ImageLayout,
+}
+
+/// Begins rendering into `attachments`, the render area is the extent of the first one.
+///
+/// Panics if the device has no dynamic rendering.
+pub fn record_begin_rendering(
+    device: &Device,
+    cmdbuf: &CommandBuffer,
+    attachments: &[RenderingAttachment],
+) {
+    let dynamic_rendering = device
+        .dynamic_rendering()
+        .expect("Dynamic rendering isn't enabled on this device");
+    let extent = attachments
+        .first()
+        .expect("Rendering needs at least one attachment")
+        .texture
+        .get_image()
+        .get_desc()
+        .extent;
+
+    for attachment in attachments {
+        attachment
+            .texture
+            .get_image()
+            .record_transition_barrier(cmdbuf, 0, attachment.layout);
+    }
+
+    let to_info = |attachment: &RenderingAttachment| {
+        vk::RenderingAttachmentInfo::default()
+            .image_view(attachment.texture.get_image_view().as_raw())
+            .image_layout(attachment.layout)
+            .load_op(attachment.load_op)
+            .store_op(attachment.store_op)
+            .clear_value(attachment.clear_value)
+    };
+    let color_infos: Vec<_> = attachments
+        .iter()
+        .filter(|attachment| attachment.ty == AttachmentType::Color)
+        .map(to_info)
+        .collect();
+    let depth_info = attachments
+        .iter()
+        .find(|attachment| attachment.ty == AttachmentType::Depth)
+        .map(to_info);
+
+    let mut rendering_info = vk::RenderingInfo::default()
+        .render_area(vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D {
+                width: extent.width,
+                height: extent.height,
+            },
+        })
+        .layer_count(1)
+        .color_attachments(&color_infos);
+    if let Some(depth_info) = depth_info.as_ref() {
+        rendering_info = rendering_info.depth_attachment(depth_info);
+    }
+
+    unsafe { dynamic_rendering.cmd_begin_rendering(cmdbuf.as_raw(), &rendering_info) };
+}
+
+pub fn record_end_rendering(device: &Device, cmdbuf: &CommandBuffer) {
+    let dynamic_rendering = device
+        .dynamic_rendering()
+        .expect("Dynamic rendering isn't enabled on this device");
+    unsafe { dynamic_rendering.cmd_end_rendering(cmdbuf.as_raw()) };
+}
@@ -0,0 +1,246 @@
+use ash::vk;
+use std::collections::HashSet;
+
+#[derive(Debug)]
+struct FramePass {
+    #[allow(dead_code)]
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+/// The passes of a frame in submission order, with the resources each of them accesses.
+#[derive(Debug, Default)]
+pub struct FrameGraph {
+    passes: Vec<FramePass>,
+    exported: HashSet<&'static str>,
+}
+
+/// Inclusive range of pass indices during which a resource holds meaningful data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLifetime {
+    pub first_pass: usize,
+    pub last_pass: usize,
+}
+
+impl ResourceLifetime {
+    /// Lifetime of resources that must never share memory with anything else.
+    pub const WHOLE_FRAME: Self = Self {
+        first_pass: 0,
+        last_pass: usize::MAX,
+    };
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.first_pass <= other.last_pass && other.first_pass <= self.last_pass
+    }
+}
+
+impl FrameGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[&'static str],
+        writes: &[&'static str],
+    ) -> &mut Self {
+        self.passes.push(FramePass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+        self
+    }
+
+    /// Marks a resource as consumed after the last pass, e.g. copied to the swapchain image.
+    pub fn export(&mut self, resource: &'static str) -> &mut Self {
+        self.exported.insert(resource);
+        self
+    }
+
+    /// Returns `None` if no pass touches the resource.
+    ///
+    /// A resource that is read before it is written carries data over from the previous frame,
+    /// so it lives for the whole frame.
+    pub fn lifetime(&self, resource: &str) -> Option<ResourceLifetime> {
+        let mut lifetime: Option<ResourceLifetime> = None;
+        let mut is_history = false;
+
+        for (pass_idx, pass) in self.passes.iter().enumerate() {
+            let reads = pass.reads.contains(&resource);
+            let writes = pass.writes.contains(&resource);
+            if !reads && !writes {
+                continue;
+            }
+            match &mut lifetime {
+                Some(lifetime) => lifetime.last_pass = pass_idx,
+                None => {
+                    is_history = reads;
+                    lifetime = Some(ResourceLifetime {
+                        first_pass: pass_idx,
+                        last_pass: pass_idx,
+                    });
+                }
+            }
+        }
+
+        let mut lifetime = lifetime?;
+        if is_history {
+            return Some(ResourceLifetime::WHOLE_FRAME);
+        }
+        if self.exported.contains(resource) {
+            lifetime.last_pass = usize::MAX;
+        }
+        Some(lifetime)
+    }
+}
+
+/// A group of resources placed on one memory block, none of their lifetimes overlap.
+#[derive(Debug, Clone)]
+pub struct AliasSlot {
+    /// Indices into the candidates passed to [`plan_aliasing`].
+    pub members: Vec<usize>,
+    /// Union of the requirements of all members.
+    pub requirements: vk::MemoryRequirements,
+}
+
+/// Greedily packs resources into as few memory blocks as possible, largest first.
+///
+/// A resource joins the first slot whose members are all dead during its lifetime and that
+/// shares at least one memory type with it.
+pub fn plan_aliasing(candidates: &[(ResourceLifetime, vk::MemoryRequirements)]) -> Vec<AliasSlot> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(candidates[i].1.size));
+
+    let mut slots: Vec<AliasSlot> = Vec::new();
+    for idx in order {
+        let (lifetime, requirements) = &candidates[idx];
+        let slot = slots.iter_mut().find(|slot| {
+            slot.requirements.memory_type_bits & requirements.memory_type_bits != 0
+                && slot
+                    .members
+                    .iter()
+                    .all(|&member| !candidates[member].0.overlaps(lifetime))
+        });
+        match slot {
+            Some(slot) => {
+                slot.members.push(idx);
+                slot.requirements.size = slot.requirements.size.max(requirements.size);
+                slot.requirements.alignment =
+                    slot.requirements.alignment.max(requirements.alignment);
+                slot.requirements.memory_type_bits &= requirements.memory_type_bits;
+            }
+            None => slots.push(AliasSlot {
+                members: vec![idx],
+                requirements: *requirements,
+            }),
+        }
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirements(size: u64) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size,
+            alignment: 256,
+            memory_type_bits: 0b1,
+        }
+    }
+
+    fn test_graph() -> FrameGraph {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass("trace", &[], &["color", "depth"])
+            .add_pass("blur", &["color"], &["blurred"])
+            .add_pass("resolve", &["blurred", "depth", "history"], &["resolved"])
+            .add_pass("present", &["resolved"], &["screen"])
+            .add_pass("store_history", &["resolved"], &["history"])
+            .export("screen");
+        graph
+    }
+
+    #[test]
+    fn lifetimes_follow_pass_order() {
+        let graph = test_graph();
+        let lifetime = |first_pass, last_pass| {
+            Some(ResourceLifetime {
+                first_pass,
+                last_pass,
+            })
+        };
+        assert_eq!(graph.lifetime("color"), lifetime(0, 1));
+        assert_eq!(graph.lifetime("depth"), lifetime(0, 2));
+        assert_eq!(graph.lifetime("resolved"), lifetime(2, 4));
+        assert_eq!(graph.lifetime("screen"), lifetime(3, usize::MAX));
+        assert_eq!(
+            graph.lifetime("history"),
+            Some(ResourceLifetime::WHOLE_FRAME)
+        );
+        assert_eq!(graph.lifetime("unused"), None);
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_a_slot() {
+        let graph = test_graph();
+        let names = ["color", "depth", "blurred", "resolved", "screen", "history"];
+        let candidates: Vec<_> = names
+            .iter()
+            .map(|name| (graph.lifetime(name).unwrap(), requirements(1024)))
+            .collect();
+        let slots = plan_aliasing(&candidates);
+
+        // history can't alias, color/resolved and depth/screen can
+        assert_eq!(slots.len(), 4);
+        for slot in &slots {
+            for (i, &a) in slot.members.iter().enumerate() {
+                for &b in &slot.members[i + 1..] {
+                    assert!(!candidates[a].0.overlaps(&candidates[b].0));
+                }
+            }
+        }
+        assert_eq!(
+            slots.iter().map(|s| s.members.len()).sum::<usize>(),
+            names.len()
+        );
+    }
+
+    #[test]
+    fn slot_takes_the_largest_requirements() {
+        let a = ResourceLifetime {
+            first_pass: 0,
+            last_pass: 1,
+        };
+        let b = ResourceLifetime {
+            first_pass: 2,
+            last_pass: 3,
+        };
+        let mut small = requirements(512);
+        small.alignment = 4096;
+        let slots = plan_aliasing(&[(a, small), (b, requirements(2048))]);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].requirements.size, 2048);
+        assert_eq!(slots[0].requirements.alignment, 4096);
+    }
+
+    #[test]
+    fn incompatible_memory_types_never_alias() {
+        let a = ResourceLifetime {
+            first_pass: 0,
+            last_pass: 0,
+        };
+        let b = ResourceLifetime {
+            first_pass: 1,
+            last_pass: 1,
+        };
+        let mut other_type = requirements(1024);
+        other_type.memory_type_bits = 0b10;
+        let slots = plan_aliasing(&[(a, requirements(1024)), (b, other_type)]);
+        assert_eq!(slots.len(), 2);
+    }
+}
@@ -0,0 +1,11 @@
+//! Frame-level resource lifetime analysis and memory aliasing of transient textures.
+//!
+//! A [`FrameGraph`] only describes which passes of a frame read and write which resources, the
+//! passes are still recorded by hand. Textures whose lifetimes don't overlap are placed on the
+//! same memory by [`TransientTextureBuilder`].
+
+mod lifetime;
+pub use lifetime::*;
+
+mod transient_textures;
+pub use transient_textures::*;
@@ -0,0 +1,123 @@
+use super::{plan_aliasing, FrameGraph, ResourceLifetime};
+use crate::vkn::{AliasedMemory, Allocator, Device, Image, ImageDesc, SamplerDesc, Texture};
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+struct TransientEntry {
+    name: &'static str,
+    desc: ImageDesc,
+    sampler_desc: SamplerDesc,
+    image: vk::Image,
+    requirements: vk::MemoryRequirements,
+}
+
+/// Collects textures that are recreated together and places the ones with disjoint lifetimes
+/// in the given [`FrameGraph`] on shared memory.
+///
+/// Textures the graph doesn't mention get memory of their own.
+pub struct TransientTextureBuilder {
+    device: Device,
+    allocator: Allocator,
+    entries: Vec<Option<TransientEntry>>,
+}
+
+impl TransientTextureBuilder {
+    pub fn new(device: Device, allocator: Allocator) -> Self {
+        Self {
+            device,
+            allocator,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        name: &'static str,
+        desc: &ImageDesc,
+        sampler_desc: &SamplerDesc,
+    ) -> Result<()> {
+        let (image, requirements) = Image::create_unbound(&self.device, desc)?;
+        self.entries.push(Some(TransientEntry {
+            name,
+            desc: *desc,
+            sampler_desc: *sampler_desc,
+            image,
+            requirements,
+        }));
+        Ok(())
+    }
+
+    pub fn build(mut self, graph: &FrameGraph) -> Result<TransientTextures> {
+        let candidates: Vec<_> = self
+            .entries
+            .iter()
+            .flatten()
+            .map(|entry| {
+                let lifetime = graph
+                    .lifetime(entry.name)
+                    .unwrap_or(ResourceLifetime::WHOLE_FRAME);
+                (lifetime, entry.requirements)
+            })
+            .collect();
+        let slots = plan_aliasing(&candidates);
+
+        let mut textures = HashMap::new();
+        let mut memory_size = 0;
+        for slot in &slots {
+            let memory = Arc::new(AliasedMemory::new(
+                self.allocator.clone(),
+                slot.requirements,
+            )?);
+            memory_size += memory.size();
+
+            for &member in &slot.members {
+                let entry = self.entries[member].take().unwrap();
+                let image = Image::new_aliased(
+                    self.device.clone(),
+                    self.allocator.clone(),
+                    &entry.desc,
+                    entry.image,
+                    memory.clone(),
+                )?;
+                let texture = Texture::from_image(self.device.clone(), image, &entry.sampler_desc);
+                textures.insert(entry.name, texture);
+            }
+        }
+
+        let unaliased_size: vk::DeviceSize = candidates.iter().map(|(_, req)| req.size).sum();
+        log::debug!(
+            "Transient textures: {} textures in {} memory blocks, {:.1} MiB instead of {:.1} MiB",
+            candidates.len(),
+            slots.len(),
+            memory_size as f64 / (1024.0 * 1024.0),
+            unaliased_size as f64 / (1024.0 * 1024.0),
+        );
+
+        Ok(TransientTextures { textures })
+    }
+}
+
+impl Drop for TransientTextureBuilder {
+    fn drop(&mut self) {
+        // images that never made it into a texture are still unbound
+        for entry in self.entries.drain(..).flatten() {
+            unsafe { self.device.destroy_image(entry.image, None) };
+        }
+    }
+}
+
+/// The textures produced by [`TransientTextureBuilder::build`], keyed by the name they were
+/// added with.
+pub struct TransientTextures {
+    textures: HashMap<&'static str, Texture>,
+}
+
+impl TransientTextures {
+    pub fn take(&mut self, name: &str) -> Result<Texture> {
+        self.textures
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No transient texture named {}", name))
+    }
+}
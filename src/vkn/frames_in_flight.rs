@@ -0,0 +1,111 @@
+use crate::vkn::{CommandBuffer, Fence, Semaphore, VulkanContext};
+
+/// The command buffer and sync objects of one frame slot.
+pub struct FrameSlot {
+    pub cmdbuf: CommandBuffer,
+    /// Signalled once the acquired swapchain image can be written.
+    pub image_available_semaphore: Semaphore,
+    /// Signalled once the GPU is done with the slot's submission.
+    pub fence: Fence,
+    /// A frame was submitted with the slot and not finished yet, see
+    /// [`FramesInFlight::finish_current`].
+    is_submitted: bool,
+}
+
+impl FrameSlot {
+    fn new(vulkan_ctx: &VulkanContext) -> Self {
+        let device = vulkan_ctx.device();
+        Self {
+            cmdbuf: CommandBuffer::new(device, vulkan_ctx.command_pool()),
+            image_available_semaphore: Semaphore::new(device),
+            fence: Fence::new(device, true),
+            is_submitted: false,
+        }
+    }
+}
+
+/// A ring of frame slots so the CPU records a frame while the GPU still runs the previous ones.
+///
+/// A slot is reused once the frame submitted with it `frame_count` frames ago is done. Resources
+/// written from the CPU need a copy per slot, indexed by [`Self::current_idx`]. Resources shared
+/// by all frames are the caller's business, see [`VulkanContext::frame_timeline`].
+pub struct FramesInFlight {
+    vulkan_ctx: VulkanContext,
+    slots: Vec<FrameSlot>,
+    slot_idx: usize,
+    /// One per swapchain image rather than per slot, the presentation engine holds it until the
+    /// image is acquired again.
+    render_finished_semaphores: Vec<Semaphore>,
+}
+
+impl FramesInFlight {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        frame_count: usize,
+        swapchain_image_count: usize,
+    ) -> Self {
+        assert!(frame_count > 0, "At least one frame must be in flight");
+        let slots = (0..frame_count)
+            .map(|_| FrameSlot::new(&vulkan_ctx))
+            .collect();
+        let render_finished_semaphores = (0..swapchain_image_count)
+            .map(|_| Semaphore::new(vulkan_ctx.device()))
+            .collect();
+        Self {
+            vulkan_ctx,
+            slots,
+            slot_idx: 0,
+            render_finished_semaphores,
+        }
+    }
+
+    /// Waits until the GPU is done with the current slot so it can be recorded again. Returns
+    /// whether a frame was submitted with it since the last call, its results can be read back
+    /// then.
+    pub fn finish_current(&mut self) -> bool {
+        self.vulkan_ctx
+            .wait_for_fences(&[self.current().fence.as_raw()])
+            .unwrap();
+        std::mem::take(&mut self.slots[self.slot_idx].is_submitted)
+    }
+
+    /// Unsignals the current slot's fence, right before it's submitted with.
+    pub fn reset_current(&self) {
+        unsafe {
+            self.vulkan_ctx
+                .device()
+                .reset_fences(&[self.current().fence.as_raw()])
+                .expect("Failed to reset fences")
+        };
+    }
+
+    pub fn current(&self) -> &FrameSlot {
+        &self.slots[self.slot_idx]
+    }
+
+    /// Which copy of the per-frame resources the current frame uses.
+    pub fn current_idx(&self) -> usize {
+        self.slot_idx
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn render_finished_semaphore(&self, image_idx: u32) -> &Semaphore {
+        &self.render_finished_semaphores[image_idx as usize]
+    }
+
+    /// Moves on to the next slot, after the current one was submitted.
+    pub fn advance(&mut self) {
+        self.slots[self.slot_idx].is_submitted = true;
+        self.slot_idx = (self.slot_idx + 1) % self.slots.len();
+    }
+
+    /// The device must be idle, the recreated swapchain may hold a different number of images.
+    pub fn on_swapchain_recreated(&mut self, swapchain_image_count: usize) {
+        self.render_finished_semaphores = (0..swapchain_image_count)
+            .map(|_| Semaphore::new(self.vulkan_ctx.device()))
+            .collect();
+    }
+}
@@ -0,0 +1,90 @@
+//! Reusable compute primitives (prefix sum, radix sort) over plain `u32` buffers.
+//!
+//! The primitives own their scratch buffers and copy the user data in and out, so the buffers
+//! passed in have to be created with `TRANSFER_SRC` / `TRANSFER_DST` usage.
+
+mod prefix_sum;
+pub use prefix_sum::*;
+
+mod radix_sort;
+pub use radix_sort::*;
+
+use crate::vkn::{CommandBuffer, Device, MemoryBarrier, PipelineBarrier};
+use ash::vk;
+
+/// Makes previous compute and transfer writes visible to the following compute and transfer work.
+fn record_compute_transfer_barrier(device: &Device, cmdbuf: &CommandBuffer) {
+    let stages = vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER;
+    PipelineBarrier::new(
+        stages,
+        stages,
+        vec![MemoryBarrier::new(
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::SHADER_READ
+                | vk::AccessFlags::SHADER_WRITE
+                | vk::AccessFlags::TRANSFER_READ
+                | vk::AccessFlags::TRANSFER_WRITE,
+        )],
+    )
+    .record_insert(device, cmdbuf);
+}
+
+#[cfg(test)]
+mod test_utils {
+    use crate::util::ShaderCompiler;
+    use crate::vkn::{
+        is_validation_requested, Allocator, Buffer, BufferUsage, VulkanContext, VulkanContextDesc,
+    };
+    use ash::vk;
+    use gpu_allocator::vulkan::AllocatorCreateDesc;
+    use std::sync::{Arc, Mutex};
+
+    /// A headless context, an allocator and a shader compiler for the GPU tests of the
+    /// primitives.
+    pub fn create_test_env() -> (VulkanContext, Allocator, ShaderCompiler<'static>) {
+        let vulkan_ctx = VulkanContext::new_headless(VulkanContextDesc {
+            name: "gpu_primitives test".into(),
+            enable_validation: is_validation_requested(),
+        });
+        let device = vulkan_ctx.device();
+        let gpu_allocator = gpu_allocator::vulkan::Allocator::new(&AllocatorCreateDesc {
+            instance: vulkan_ctx.instance().as_raw().clone(),
+            device: device.as_raw().clone(),
+            physical_device: vulkan_ctx.physical_device().as_raw(),
+            debug_settings: Default::default(),
+            buffer_device_address: true,
+            allocation_sizes: Default::default(),
+        })
+        .expect("Failed to create gpu allocator");
+        let allocator = Allocator::new(device, Arc::new(Mutex::new(gpu_allocator)));
+
+        let mut shader_compiler = ShaderCompiler::new().unwrap();
+        shader_compiler.set_dispatch_group_limits(vulkan_ctx.max_compute_work_group_count());
+        (vulkan_ctx, allocator, shader_compiler)
+    }
+
+    /// A host visible buffer holding `data` that the primitives can copy in and out of.
+    pub fn create_test_buffer(
+        vulkan_ctx: &VulkanContext,
+        allocator: &Allocator,
+        data: &[u32],
+    ) -> Buffer {
+        let buffer = Buffer::new_sized(
+            vulkan_ctx.device().clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            gpu_allocator::MemoryLocation::GpuToCpu,
+            std::mem::size_of_val(data) as u64,
+        );
+        buffer.fill(data).unwrap();
+        buffer
+    }
+
+    pub fn read_u32s(buffer: &Buffer) -> Vec<u32> {
+        bytemuck::pod_collect_to_vec(&buffer.read_back().unwrap())
+    }
+}
@@ -0,0 +1,335 @@
+use super::record_compute_transfer_barrier;
+use crate::{
+    resource::Resource,
+    util::ShaderCompiler,
+    vkn::{
+        execute_one_time_command, Allocator, Buffer, BufferUsage, CommandBuffer, ComputePipeline,
+        DescriptorPool, Device, Extent3D, ShaderModule, VulkanContext,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use resource_container_derive::ResourceContainer;
+
+/// Must match `SCAN_BLOCK_SIZE` in the prefix sum shaders.
+const SCAN_BLOCK_SIZE: u32 = 256;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct PrefixSumPushConstant {
+    data_offset: u32,
+    block_sum_offset: u32,
+    element_count: u32,
+}
+
+#[derive(ResourceContainer)]
+struct PrefixSumResources {
+    /// All levels of the scan hierarchy, packed back to back.
+    prefix_sum_data: Resource<Buffer>,
+}
+
+/// Exclusive prefix sum over `u32` elements.
+///
+/// Works as a multi-level block scan: every level is scanned in blocks, the block totals form the
+/// next level, and the scanned totals are added back top-down.
+#[allow(dead_code)]
+pub struct GpuPrefixSum {
+    vulkan_ctx: VulkanContext,
+    resources: PrefixSumResources,
+    pool: DescriptorPool,
+    scan_ppl: ComputePipeline,
+    add_ppl: ComputePipeline,
+    max_element_count: u32,
+}
+
+#[allow(dead_code)]
+impl GpuPrefixSum {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        shader_compiler: &ShaderCompiler,
+        max_element_count: u32,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let scan_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/vkn/prefix_sum_scan.comp",
+            "main",
+        )
+        .unwrap();
+        let add_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/vkn/prefix_sum_add.comp",
+            "main",
+        )
+        .unwrap();
+
+        let scratch_len: u64 = scan_levels(max_element_count.max(1))
+            .iter()
+            .map(|level| level.len as u64)
+            .sum();
+        let prefix_sum_data = Buffer::new_sized(
+            device.clone(),
+            allocator,
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            gpu_allocator::MemoryLocation::GpuOnly,
+            scratch_len * std::mem::size_of::<u32>() as u64,
+        );
+        let resources = PrefixSumResources {
+            prefix_sum_data: Resource::new(prefix_sum_data),
+        };
+
+        let scan_ppl = ComputePipeline::new(device, &scan_sm, &pool, &[&resources]);
+        let add_ppl = ComputePipeline::new(device, &add_sm, &pool, &[&resources]);
+
+        Ok(Self {
+            vulkan_ctx,
+            resources,
+            pool,
+            scan_ppl,
+            add_ppl,
+            max_element_count,
+        })
+    }
+
+    pub fn max_element_count(&self) -> u32 {
+        self.max_element_count
+    }
+
+    /// Records an exclusive scan of the first `element_count` elements of `src` into `dst`.
+    ///
+    /// `src` and `dst` may be the same buffer. Barriers are inserted before reading `src` and after
+    /// writing `dst`, so the caller doesn't need extra synchronization for compute / transfer work.
+    pub fn record_exclusive_scan(
+        &self,
+        cmdbuf: &CommandBuffer,
+        src: &Buffer,
+        dst: &Buffer,
+        element_count: u32,
+    ) -> Result<()> {
+        if element_count > self.max_element_count {
+            bail!(
+                "Prefix sum over {} elements exceeds the capacity of {}",
+                element_count,
+                self.max_element_count
+            );
+        }
+        if element_count == 0 {
+            return Ok(());
+        }
+
+        let device = self.vulkan_ctx.device();
+        let data = &self.resources.prefix_sum_data;
+        let size_bytes = element_count as u64 * std::mem::size_of::<u32>() as u64;
+
+        record_compute_transfer_barrier(device, cmdbuf);
+        src.record_copy_to_buffer(cmdbuf, data, size_bytes, 0, 0);
+        record_compute_transfer_barrier(device, cmdbuf);
+
+        // the last level only holds the grand total, it never needs to be scanned
+        let levels = scan_levels(element_count);
+        for pair in levels.windows(2) {
+            self.record_pass(cmdbuf, &self.scan_ppl, &pair[0], &pair[1]);
+            record_compute_transfer_barrier(device, cmdbuf);
+        }
+        for pair in levels.windows(2).rev().skip(1) {
+            self.record_pass(cmdbuf, &self.add_ppl, &pair[0], &pair[1]);
+            record_compute_transfer_barrier(device, cmdbuf);
+        }
+
+        data.record_copy_to_buffer(cmdbuf, dst, size_bytes, 0, 0);
+        record_compute_transfer_barrier(device, cmdbuf);
+        Ok(())
+    }
+
+    /// Runs [`Self::record_exclusive_scan`] in a one-time command buffer and waits for it.
+    pub fn exclusive_scan(&self, src: &Buffer, dst: &Buffer, element_count: u32) -> Result<()> {
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| self.record_exclusive_scan(cmdbuf, src, dst, element_count),
+        )
+    }
+
+    fn record_pass(
+        &self,
+        cmdbuf: &CommandBuffer,
+        ppl: &ComputePipeline,
+        level: &ScanLevel,
+        next_level: &ScanLevel,
+    ) {
+        let push_constant = PrefixSumPushConstant {
+            data_offset: level.offset,
+            block_sum_offset: next_level.offset,
+            element_count: level.len,
+        };
+        ppl.record(
+            cmdbuf,
+            Extent3D {
+                width: level.len,
+                height: 1,
+                depth: 1,
+            },
+            Some(bytemuck::bytes_of(&push_constant)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ScanLevel {
+    /// Offset in elements inside the scratch buffer.
+    offset: u32,
+    len: u32,
+}
+
+/// Level layout of the scan hierarchy, level 0 is the input and the last level holds one element.
+fn scan_levels(element_count: u32) -> Vec<ScanLevel> {
+    let mut levels = vec![ScanLevel {
+        offset: 0,
+        len: element_count,
+    }];
+    loop {
+        let last = *levels.last().unwrap();
+        let len = last.len.div_ceil(SCAN_BLOCK_SIZE);
+        levels.push(ScanLevel {
+            offset: last.offset + last.len,
+            len,
+        });
+        if len <= 1 {
+            return levels;
+        }
+    }
+}
+
+/// CPU reference of [`GpuPrefixSum::record_exclusive_scan`].
+#[allow(dead_code)]
+pub fn exclusive_scan_cpu(data: &[u32]) -> Vec<u32> {
+    let mut sum = 0u32;
+    data.iter()
+        .map(|&v| {
+            let res = sum;
+            sum = sum.wrapping_add(v);
+            res
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::*;
+    use super::*;
+
+    /// Runs the same level decomposition the GPU passes use, on the CPU.
+    fn emulate_block_scan(input: &[u32]) -> Vec<u32> {
+        let levels = scan_levels(input.len() as u32);
+        let total_len = levels.iter().map(|l| l.len as usize).sum::<usize>();
+        let mut data = vec![0u32; total_len];
+        data[..input.len()].copy_from_slice(input);
+
+        for pair in levels.windows(2) {
+            let (level, next) = (pair[0], pair[1]);
+            let block_count = level.len.div_ceil(SCAN_BLOCK_SIZE);
+            for block in 0..block_count {
+                let begin = (level.offset + block * SCAN_BLOCK_SIZE) as usize;
+                let end =
+                    (begin + SCAN_BLOCK_SIZE as usize).min((level.offset + level.len) as usize);
+                let mut sum = 0u32;
+                for v in &mut data[begin..end] {
+                    let old = *v;
+                    *v = sum;
+                    sum = sum.wrapping_add(old);
+                }
+                data[(next.offset + block) as usize] = sum;
+            }
+        }
+        for pair in levels.windows(2).rev().skip(1) {
+            let (level, next) = (pair[0], pair[1]);
+            for i in 0..level.len {
+                let block_sum = data[(next.offset + i / SCAN_BLOCK_SIZE) as usize];
+                let v = &mut data[(level.offset + i) as usize];
+                *v = v.wrapping_add(block_sum);
+            }
+        }
+        data.truncate(input.len());
+        data
+    }
+
+    #[test]
+    fn test_scan_levels_layout() {
+        let levels = scan_levels(1000);
+        assert_eq!(
+            levels,
+            vec![
+                ScanLevel {
+                    offset: 0,
+                    len: 1000
+                },
+                ScanLevel {
+                    offset: 1000,
+                    len: 4
+                },
+                ScanLevel {
+                    offset: 1004,
+                    len: 1
+                },
+            ]
+        );
+        assert_eq!(scan_levels(1).len(), 2);
+        assert_eq!(scan_levels(SCAN_BLOCK_SIZE).len(), 2);
+        assert_eq!(scan_levels(SCAN_BLOCK_SIZE + 1).len(), 3);
+    }
+
+    #[test]
+    fn test_block_scan_matches_cpu_reference() {
+        let sizes = [1, 2, 255, 256, 257, 1000, 65536, 65537, 200_000];
+        for &size in &sizes {
+            let input: Vec<u32> = (0..size)
+                .map(|i: u32| i.wrapping_mul(2654435761) >> 24)
+                .collect();
+            assert_eq!(
+                emulate_block_scan(&input),
+                exclusive_scan_cpu(&input),
+                "mismatch for size {}",
+                size
+            );
+        }
+    }
+
+    /// Needs a Vulkan device, run with `cargo test gpu_exclusive_scan -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_gpu_exclusive_scan_matches_cpu_reference() {
+        let (vulkan_ctx, allocator, shader_compiler) = create_test_env();
+        let sizes = [1, 255, 256, 257, 1000, 65537, 200_000];
+        let prefix_sum = GpuPrefixSum::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            200_000,
+        )
+        .unwrap();
+        for &size in &sizes {
+            let input: Vec<u32> = (0..size)
+                .map(|i: u32| i.wrapping_mul(2654435761) >> 24)
+                .collect();
+            let buffer = create_test_buffer(&vulkan_ctx, &allocator, &input);
+            prefix_sum.exclusive_scan(&buffer, &buffer, size).unwrap();
+            assert_eq!(
+                read_u32s(&buffer),
+                emulate_block_scan(&input),
+                "mismatch for size {}",
+                size
+            );
+        }
+    }
+}
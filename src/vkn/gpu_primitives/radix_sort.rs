@@ -0,0 +1,358 @@
+use super::{record_compute_transfer_barrier, GpuPrefixSum};
+use crate::{
+    resource::Resource,
+    util::ShaderCompiler,
+    vkn::{
+        execute_one_time_command, Allocator, Buffer, BufferUsage, CommandBuffer, ComputePipeline,
+        DescriptorPool, Extent3D, ShaderModule, VulkanContext,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use resource_container_derive::ResourceContainer;
+
+/// Must match `RADIX_BLOCK_SIZE` in the radix sort shaders.
+const RADIX_BLOCK_SIZE: u32 = 256;
+const RADIX_BITS: u32 = 4;
+const RADIX: u32 = 1 << RADIX_BITS;
+const PASS_COUNT: u32 = u32::BITS / RADIX_BITS;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct RadixSortPushConstant {
+    in_offset: u32,
+    out_offset: u32,
+    element_count: u32,
+    shift: u32,
+    block_count: u32,
+    with_values: u32,
+}
+
+#[derive(ResourceContainer)]
+struct RadixSortResources {
+    /// Ping-pong key storage, the first half holds the input and the final result.
+    sort_keys: Resource<Buffer>,
+    /// Ping-pong value storage, same layout as the keys.
+    sort_values: Resource<Buffer>,
+    sort_histogram: Resource<Buffer>,
+}
+
+/// Stable LSD radix sort of `u32` keys with optional `u32` payloads, 4 bits per pass.
+#[allow(dead_code)]
+pub struct GpuRadixSort {
+    vulkan_ctx: VulkanContext,
+    resources: RadixSortResources,
+    pool: DescriptorPool,
+    histogram_ppl: ComputePipeline,
+    scatter_ppl: ComputePipeline,
+    prefix_sum: GpuPrefixSum,
+    max_element_count: u32,
+}
+
+#[allow(dead_code)]
+impl GpuRadixSort {
+    pub fn new(
+        vulkan_ctx: VulkanContext,
+        allocator: Allocator,
+        shader_compiler: &ShaderCompiler,
+        max_element_count: u32,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device();
+        let pool = DescriptorPool::new(device).unwrap();
+
+        let histogram_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/vkn/radix_sort_histogram.comp",
+            "main",
+        )
+        .unwrap();
+        let scatter_sm = ShaderModule::from_glsl(
+            device,
+            shader_compiler,
+            "shader/vkn/radix_sort_scatter.comp",
+            "main",
+        )
+        .unwrap();
+
+        let max_element_count = max_element_count.max(1);
+        let histogram_len = RADIX * max_element_count.div_ceil(RADIX_BLOCK_SIZE);
+        let usage = BufferUsage::from_flags(
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        );
+        let make_buffer = |len: u32| {
+            Buffer::new_sized(
+                device.clone(),
+                allocator.clone(),
+                usage,
+                gpu_allocator::MemoryLocation::GpuOnly,
+                len as u64 * std::mem::size_of::<u32>() as u64,
+            )
+        };
+        let resources = RadixSortResources {
+            sort_keys: Resource::new(make_buffer(max_element_count * 2)),
+            sort_values: Resource::new(make_buffer(max_element_count * 2)),
+            sort_histogram: Resource::new(make_buffer(histogram_len)),
+        };
+
+        let histogram_ppl = ComputePipeline::new(device, &histogram_sm, &pool, &[&resources]);
+        let scatter_ppl = ComputePipeline::new(device, &scatter_sm, &pool, &[&resources]);
+
+        let prefix_sum = GpuPrefixSum::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            shader_compiler,
+            histogram_len,
+        )?;
+
+        Ok(Self {
+            vulkan_ctx,
+            resources,
+            pool,
+            histogram_ppl,
+            scatter_ppl,
+            prefix_sum,
+            max_element_count,
+        })
+    }
+
+    pub fn max_element_count(&self) -> u32 {
+        self.max_element_count
+    }
+
+    /// Records an ascending sort of the first `element_count` keys, in place.
+    ///
+    /// When `values` is given, it is reordered along with the keys. Barriers are inserted before
+    /// reading and after writing the buffers.
+    pub fn record_sort(
+        &self,
+        cmdbuf: &CommandBuffer,
+        keys: &Buffer,
+        values: Option<&Buffer>,
+        element_count: u32,
+    ) -> Result<()> {
+        if element_count > self.max_element_count {
+            bail!(
+                "Radix sort over {} elements exceeds the capacity of {}",
+                element_count,
+                self.max_element_count
+            );
+        }
+        if element_count <= 1 {
+            return Ok(());
+        }
+
+        let device = self.vulkan_ctx.device();
+        let size_bytes = element_count as u64 * std::mem::size_of::<u32>() as u64;
+        let block_count = element_count.div_ceil(RADIX_BLOCK_SIZE);
+
+        record_compute_transfer_barrier(device, cmdbuf);
+        keys.record_copy_to_buffer(cmdbuf, &self.resources.sort_keys, size_bytes, 0, 0);
+        if let Some(values) = values {
+            values.record_copy_to_buffer(cmdbuf, &self.resources.sort_values, size_bytes, 0, 0);
+        }
+        record_compute_transfer_barrier(device, cmdbuf);
+
+        let extent = Extent3D {
+            width: element_count,
+            height: 1,
+            depth: 1,
+        };
+        for pass in 0..PASS_COUNT {
+            // an even pass count leaves the result in the first half
+            let (in_offset, out_offset) = if pass % 2 == 0 {
+                (0, self.max_element_count)
+            } else {
+                (self.max_element_count, 0)
+            };
+            let push_constant = RadixSortPushConstant {
+                in_offset,
+                out_offset,
+                element_count,
+                shift: pass * RADIX_BITS,
+                block_count,
+                with_values: values.is_some() as u32,
+            };
+
+            self.histogram_ppl
+                .record(cmdbuf, extent, Some(bytemuck::bytes_of(&push_constant)));
+            self.prefix_sum.record_exclusive_scan(
+                cmdbuf,
+                &self.resources.sort_histogram,
+                &self.resources.sort_histogram,
+                RADIX * block_count,
+            )?;
+            self.scatter_ppl
+                .record(cmdbuf, extent, Some(bytemuck::bytes_of(&push_constant)));
+            record_compute_transfer_barrier(device, cmdbuf);
+        }
+
+        self.resources
+            .sort_keys
+            .record_copy_to_buffer(cmdbuf, keys, size_bytes, 0, 0);
+        if let Some(values) = values {
+            self.resources
+                .sort_values
+                .record_copy_to_buffer(cmdbuf, values, size_bytes, 0, 0);
+        }
+        record_compute_transfer_barrier(device, cmdbuf);
+        Ok(())
+    }
+
+    /// Runs [`Self::record_sort`] in a one-time command buffer and waits for it.
+    pub fn sort(&self, keys: &Buffer, values: Option<&Buffer>, element_count: u32) -> Result<()> {
+        execute_one_time_command(
+            self.vulkan_ctx.device(),
+            self.vulkan_ctx.command_pool(),
+            &self.vulkan_ctx.get_general_queue(),
+            |cmdbuf| self.record_sort(cmdbuf, keys, values, element_count),
+        )
+    }
+}
+
+/// CPU reference of [`GpuRadixSort::record_sort`], stable like the GPU version.
+#[allow(dead_code)]
+pub fn radix_sort_cpu(keys: &mut [u32], values: Option<&mut [u32]>) {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by_key(|&i| keys[i]);
+
+    let sorted_keys: Vec<u32> = order.iter().map(|&i| keys[i]).collect();
+    keys.copy_from_slice(&sorted_keys);
+    if let Some(values) = values {
+        let sorted_values: Vec<u32> = order.iter().map(|&i| values[i]).collect();
+        values.copy_from_slice(&sorted_values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::*;
+    use super::*;
+    use crate::vkn::exclusive_scan_cpu;
+
+    /// Runs the histogram / scan / scatter passes the GPU uses, on the CPU.
+    fn emulate_radix_sort(keys: &mut [u32], values: &mut [u32]) {
+        let element_count = keys.len() as u32;
+        let block_count = element_count.div_ceil(RADIX_BLOCK_SIZE);
+
+        for pass in 0..PASS_COUNT {
+            let shift = pass * RADIX_BITS;
+            let digit_of = |key: u32| ((key >> shift) & (RADIX - 1)) as usize;
+
+            let mut histogram = vec![0u32; (RADIX * block_count) as usize];
+            for (i, &key) in keys.iter().enumerate() {
+                let block = i / RADIX_BLOCK_SIZE as usize;
+                histogram[digit_of(key) * block_count as usize + block] += 1;
+            }
+            let offsets = exclusive_scan_cpu(&histogram);
+
+            let mut out_keys = vec![0u32; keys.len()];
+            let mut out_values = vec![0u32; values.len()];
+            for (i, &key) in keys.iter().enumerate() {
+                let block = i / RADIX_BLOCK_SIZE as usize;
+                let block_begin = block * RADIX_BLOCK_SIZE as usize;
+                let rank = keys[block_begin..i]
+                    .iter()
+                    .filter(|&&k| digit_of(k) == digit_of(key))
+                    .count();
+                let dst = offsets[digit_of(key) * block_count as usize + block] as usize + rank;
+                out_keys[dst] = key;
+                out_values[dst] = values[i];
+            }
+            keys.copy_from_slice(&out_keys);
+            values.copy_from_slice(&out_values);
+        }
+    }
+
+    #[test]
+    fn test_pass_count_is_even() {
+        // the result is copied back from the first half of the ping-pong buffer
+        assert_eq!(PASS_COUNT % 2, 0);
+    }
+
+    #[test]
+    fn test_radix_passes_match_cpu_reference() {
+        let sizes = [2, 255, 256, 257, 1000, 5000];
+        for &size in &sizes {
+            let keys: Vec<u32> = (0..size)
+                .map(|i: u32| i.wrapping_mul(2654435761u32) ^ (i >> 3))
+                .collect();
+            let values: Vec<u32> = (0..size).collect();
+
+            let mut expected_keys = keys.clone();
+            let mut expected_values = values.clone();
+            radix_sort_cpu(&mut expected_keys, Some(&mut expected_values));
+
+            let mut actual_keys = keys;
+            let mut actual_values = values;
+            emulate_radix_sort(&mut actual_keys, &mut actual_values);
+
+            assert_eq!(actual_keys, expected_keys, "key mismatch for size {}", size);
+            assert_eq!(
+                actual_values, expected_values,
+                "value mismatch for size {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_radix_sort_is_stable_for_duplicate_keys() {
+        let mut keys: Vec<u32> = (0..600).map(|i| i % 7).collect();
+        let mut values: Vec<u32> = (0..600).collect();
+        emulate_radix_sort(&mut keys, &mut values);
+
+        for pair in keys.windows(2).zip(values.windows(2)) {
+            let (k, v) = pair;
+            assert!(k[0] <= k[1]);
+            if k[0] == k[1] {
+                assert!(v[0] < v[1]);
+            }
+        }
+    }
+
+    /// Needs a Vulkan device, run with `cargo test gpu_radix_sort -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_gpu_radix_sort_matches_cpu_reference() {
+        let (vulkan_ctx, allocator, shader_compiler) = create_test_env();
+        let sizes = [2, 255, 256, 257, 1000, 5000];
+        let radix_sort = GpuRadixSort::new(
+            vulkan_ctx.clone(),
+            allocator.clone(),
+            &shader_compiler,
+            5000,
+        )
+        .unwrap();
+        for &size in &sizes {
+            let keys: Vec<u32> = (0..size)
+                .map(|i: u32| i.wrapping_mul(2654435761u32) ^ (i >> 3))
+                .collect();
+            let values: Vec<u32> = (0..size).collect();
+            let key_buffer = create_test_buffer(&vulkan_ctx, &allocator, &keys);
+            let value_buffer = create_test_buffer(&vulkan_ctx, &allocator, &values);
+            radix_sort
+                .sort(&key_buffer, Some(&value_buffer), size)
+                .unwrap();
+
+            let mut expected_keys = keys;
+            let mut expected_values = values;
+            emulate_radix_sort(&mut expected_keys, &mut expected_values);
+            assert_eq!(
+                read_u32s(&key_buffer),
+                expected_keys,
+                "key mismatch for size {}",
+                size
+            );
+            assert_eq!(
+                read_u32s(&value_buffer),
+                expected_values,
+                "value mismatch for size {}",
+                size
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::vkn::{CommandBuffer, Device, VulkanContext};
+use anyhow::Result;
+use ash::vk;
+
+/// Weight of the newest frame in [`GpuPassTiming::average_ms`].
+const AVERAGE_WEIGHT: f32 = 0.05;
+
+/// GPU time of one pass, see [`GpuProfiler`].
+#[derive(Debug, Clone, Copy)]
+pub struct GpuPassTiming {
+    pub name: &'static str,
+    /// Zero if the pass didn't run last frame.
+    pub last_ms: f32,
+    pub average_ms: f32,
+}
+
+/// Measures the GPU time of the passes of a frame with timestamp queries.
+///
+/// Passes are bracketed with [`Self::begin_scope`] and [`Self::end_scope`]. Every frame slot has
+/// its own range of queries, the results are read back once the slot comes around again and its
+/// queries are known to be done. Scopes don't nest, scopes sharing a name are summed.
+pub struct GpuProfiler {
+    device: Device,
+    query_pool: vk::QueryPool,
+    max_scope_count: u32,
+    /// `None` when the queue can't write timestamps, nothing is recorded then.
+    timestamp_period_ns: Option<f32>,
+    /// The scopes recorded into the frame in flight of each slot in order, scope `i` owns
+    /// queries `2i` and `2i + 1` of the slot's range.
+    recorded_scopes: Vec<Vec<&'static str>>,
+    frame_slot: usize,
+    open_scope: Option<&'static str>,
+    timings: Vec<GpuPassTiming>,
+    pub is_enabled: bool,
+}
+
+impl Drop for GpuProfiler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+impl GpuProfiler {
+    pub fn new(
+        vulkan_ctx: &VulkanContext,
+        max_scope_count: u32,
+        frame_count: usize,
+    ) -> Result<Self> {
+        let device = vulkan_ctx.device().clone();
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(max_scope_count * 2 * frame_count as u32);
+        let query_pool = unsafe { device.create_query_pool(&create_info, None)? };
+
+        let timestamp_period_ns = vulkan_ctx.timestamp_period_ns();
+        if timestamp_period_ns.is_none() {
+            log::warn!("The general queue doesn't support timestamps, GPU timings are disabled");
+        }
+
+        Ok(Self {
+            device,
+            query_pool,
+            max_scope_count,
+            timestamp_period_ns,
+            recorded_scopes: vec![Vec::new(); frame_count],
+            frame_slot: 0,
+            open_scope: None,
+            timings: Vec::new(),
+            is_enabled: true,
+        })
+    }
+
+    fn is_recording(&self) -> bool {
+        self.is_enabled && self.timestamp_period_ns.is_some()
+    }
+
+    /// Makes the queries of `slot` the ones the next frame records into and reads the timings of
+    /// the frame last recorded with them.
+    ///
+    /// That frame must be done on the GPU, results that aren't available yet are dropped.
+    pub fn select_frame_slot(&mut self, slot: usize) {
+        self.frame_slot = slot;
+        self.resolve_frame_slot();
+    }
+
+    /// Resets the queries of the selected slot, record before any scope.
+    pub fn begin_frame(&mut self, cmdbuf: &CommandBuffer) {
+        self.recorded_scopes[self.frame_slot].clear();
+        self.open_scope = None;
+        if !self.is_recording() {
+            return;
+        }
+        unsafe {
+            self.device.cmd_reset_query_pool(
+                cmdbuf.as_raw(),
+                self.query_pool,
+                self.first_query(),
+                self.max_scope_count * 2,
+            );
+        }
+    }
+
+    fn first_query(&self) -> u32 {
+        self.frame_slot as u32 * self.max_scope_count * 2
+    }
+
+    /// Starts timing `name`, ignored when all scopes of the frame are taken.
+    pub fn begin_scope(&mut self, cmdbuf: &CommandBuffer, name: &'static str) {
+        let scope_count = self.recorded_scopes[self.frame_slot].len() as u32;
+        if !self.is_recording() || scope_count >= self.max_scope_count {
+            return;
+        }
+        debug_assert!(self.open_scope.is_none(), "GPU profiler scopes don't nest");
+        let query = self.first_query() + scope_count * 2;
+        unsafe {
+            self.device.cmd_write_timestamp(
+                cmdbuf.as_raw(),
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                query,
+            );
+        }
+        self.open_scope = Some(name);
+    }
+
+    pub fn end_scope(&mut self, cmdbuf: &CommandBuffer) {
+        let Some(name) = self.open_scope.take() else {
+            return;
+        };
+        let query = self.first_query() + self.recorded_scopes[self.frame_slot].len() as u32 * 2 + 1;
+        unsafe {
+            self.device.cmd_write_timestamp(
+                cmdbuf.as_raw(),
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                query,
+            );
+        }
+        self.recorded_scopes[self.frame_slot].push(name);
+    }
+
+    /// Every pass timed so far, in the order they were first recorded.
+    pub fn timings(&self) -> &[GpuPassTiming] {
+        &self.timings
+    }
+
+    fn resolve_frame_slot(&mut self) {
+        let scopes = std::mem::take(&mut self.recorded_scopes[self.frame_slot]);
+        let Some(timestamp_period_ns) = self.timestamp_period_ns else {
+            return;
+        };
+        if scopes.is_empty() {
+            return;
+        }
+
+        // each query is followed by its availability
+        let mut results = vec![[0u64; 2]; scopes.len() * 2];
+        let query_result = unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                self.first_query(),
+                &mut results,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WITH_AVAILABILITY,
+            )
+        };
+        if let Err(e) = query_result {
+            if e != vk::Result::NOT_READY {
+                log::warn!("Failed to read the GPU timestamps: {}", e);
+                return;
+            }
+        }
+
+        for timing in &mut self.timings {
+            timing.last_ms = 0.0;
+        }
+        for (name, queries) in scopes.iter().zip(results.chunks_exact(2)) {
+            let ([begin, begin_available], [end, end_available]) = (queries[0], queries[1]);
+            if begin_available == 0 || end_available == 0 {
+                continue;
+            }
+            let ms = end.wrapping_sub(begin) as f32 * timestamp_period_ns / 1e6;
+            // a pass timed in several scopes adds up
+            match self.timings.iter_mut().find(|timing| timing.name == *name) {
+                Some(timing) => timing.last_ms += ms,
+                None => self.timings.push(GpuPassTiming {
+                    name: *name,
+                    last_ms: ms,
+                    average_ms: ms,
+                }),
+            }
+        }
+        for timing in &mut self.timings {
+            timing.average_ms += (timing.last_ms - timing.average_ms) * AVERAGE_WEIGHT;
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    resource::{ResourceContainer, ResourceNameTree, StaticResourceNames},
+    vkn::{
+        execute_one_time_command, Allocator, Buffer, BufferUsage, CommandBuffer, Device, Texture,
+        VulkanContext,
+    },
+};
+use anyhow::{bail, Result};
+use ash::vk;
+use glam::UVec3;
+
+const DISPATCH_SIZE_BYTES: u64 = 3 * std::mem::size_of::<u32>() as u64;
+
+/// A `VkDispatchIndirectCommand` buffer that is written by one shader and consumed by
+/// [`crate::vkn::ComputePipeline::record_indirect`].
+///
+/// Behaves like a resource container holding a single buffer named `binding_name`, so it can be
+/// placed directly into a `#[derive(ResourceContainer)]` struct and gets auto-bound like any
+/// `Resource<Buffer>` field.
+///
+/// Producing shaders should clamp their group counts with `clamp_dispatch_group_count` from
+/// `include/core/indirect_dispatch.glsl`, the device limits are injected by
+/// [`crate::util::ShaderCompiler::set_dispatch_group_limits`].
+pub struct IndirectDispatch {
+    buffer: Buffer,
+    binding_name: &'static str,
+    max_group_count: UVec3,
+    /// Only used in debug builds, see [`IndirectDispatch::validate`].
+    readback: Buffer,
+    validation_enabled: bool,
+}
+
+impl IndirectDispatch {
+    pub fn new(
+        device: Device,
+        allocator: Allocator,
+        binding_name: &'static str,
+        max_group_count: UVec3,
+    ) -> Self {
+        let buffer = Buffer::new_sized(
+            device.clone(),
+            allocator.clone(),
+            BufferUsage::from_flags(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::INDIRECT_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            gpu_allocator::MemoryLocation::GpuOnly,
+            DISPATCH_SIZE_BYTES,
+        );
+        let readback = Buffer::new_sized(
+            device,
+            allocator,
+            BufferUsage::from_flags(vk::BufferUsageFlags::TRANSFER_DST),
+            gpu_allocator::MemoryLocation::GpuToCpu,
+            DISPATCH_SIZE_BYTES,
+        );
+
+        Self {
+            buffer,
+            binding_name,
+            max_group_count,
+            readback,
+            validation_enabled: cfg!(debug_assertions),
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn max_group_count(&self) -> UVec3 {
+        self.max_group_count
+    }
+
+    /// Enables the readback in [`IndirectDispatch::validate`], on by default in debug builds.
+    ///
+    /// Has no effect in release builds.
+    #[allow(dead_code)]
+    pub fn set_validation_enabled(&mut self, enabled: bool) {
+        self.validation_enabled = enabled;
+    }
+
+    /// Whether [`IndirectDispatch::validate`] reads anything back, callers that would have to
+    /// wait for the GPU only for it can skip the wait otherwise.
+    pub fn is_validation_enabled(&self) -> bool {
+        cfg!(debug_assertions) && self.validation_enabled
+    }
+
+    /// Records a reset of the group counts to zero, so an unwritten dispatch becomes a no-op.
+    #[allow(dead_code)]
+    pub fn record_reset(&self, device: &Device, cmdbuf: &CommandBuffer) {
+        unsafe {
+            device.cmd_fill_buffer(
+                cmdbuf.as_raw(),
+                self.buffer.as_raw(),
+                0,
+                DISPATCH_SIZE_BYTES,
+                0,
+            );
+        }
+    }
+
+    /// Records a CPU-side update of the group counts, clamped against the device limits.
+    #[allow(dead_code)]
+    pub fn record_update(&self, device: &Device, cmdbuf: &CommandBuffer, group_count: UVec3) {
+        let group_count = self.clamp(group_count);
+        unsafe {
+            device.cmd_update_buffer(
+                cmdbuf.as_raw(),
+                self.buffer.as_raw(),
+                0,
+                bytemuck::cast_slice(&group_count.to_array()),
+            );
+        }
+    }
+
+    pub fn clamp(&self, group_count: UVec3) -> UVec3 {
+        group_count.min(self.max_group_count)
+    }
+
+    /// Reads back the group counts written on the GPU and checks them against the device limits.
+    ///
+    /// The buffer must not be in use by pending work. In release builds, or when validation is
+    /// disabled, this returns `Ok(None)` without touching the GPU.
+    pub fn validate(&self, vulkan_ctx: &VulkanContext) -> Result<Option<UVec3>> {
+        if !self.is_validation_enabled() {
+            return Ok(None);
+        }
+
+        execute_one_time_command(
+            vulkan_ctx.device(),
+            vulkan_ctx.command_pool(),
+            &vulkan_ctx.get_general_queue(),
+            |cmdbuf| {
+                self.buffer.record_copy_to_buffer(
+                    cmdbuf,
+                    &self.readback,
+                    DISPATCH_SIZE_BYTES,
+                    0,
+                    0,
+                );
+            },
+        );
+        let raw = self.readback.read_back()?;
+        let counts: &[u32] = bytemuck::cast_slice(&raw[..DISPATCH_SIZE_BYTES as usize]);
+        let group_count = UVec3::new(counts[0], counts[1], counts[2]);
+
+        if group_count.cmpgt(self.max_group_count).any() {
+            bail!(
+                "Indirect dispatch '{}' has group count {} exceeding the device limit {}",
+                self.binding_name,
+                group_count,
+                self.max_group_count
+            );
+        }
+        Ok(Some(group_count))
+    }
+}
+
+// the binding name is picked at runtime, `ResourceContainer::validate` checks it
+impl StaticResourceNames for IndirectDispatch {
+    const RESOURCE_NAMES: ResourceNameTree = ResourceNameTree::EMPTY;
+}
+
+impl ResourceContainer for IndirectDispatch {
+    fn get_buffer(&self, name: &str) -> Option<&Buffer> {
+        (name == self.binding_name).then_some(&self.buffer)
+    }
+
+    fn get_texture(&self, _name: &str) -> Option<&Texture> {
+        None
+    }
+
+    fn get_resource_names(&self) -> Vec<String> {
+        vec![self.binding_name.to_string()]
+    }
+}
@@ -1,4 +1,4 @@
-use crate::vkn::{Allocator, BufferLayout, CommandBuffer, Device};
+use crate::vkn::{Allocator, BufferLayout, CommandBuffer, Device, ShaderStruct};
 
 use super::BufferUsage;
 use anyhow::Result;
@@ -117,10 +117,12 @@ impl Buffer {
         let mut usages = BufferUsage::from_reflect_descriptor_type(layout.descriptor_type);
         usages.union_with(&additional_usages);
 
+        let (sharing_mode, queue_families) = device.resource_sharing();
         let buffer_info = vk::BufferCreateInfo::default()
             .size(layout.get_size_bytes() * element_length)
             .usage(usages.as_raw())
-            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(queue_families);
 
         let buffer = unsafe { device.create_buffer(&buffer_info, None).unwrap() };
         let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
@@ -160,16 +162,54 @@ impl Buffer {
 
     // TODO: deprecate this one?
     pub fn new_sized(
+        device: Device,
+        allocator: Allocator,
+        usage: BufferUsage,
+        location: MemoryLocation,
+        size: u64,
+    ) -> Self {
+        let (sharing_mode, queue_families) = device.resource_sharing();
+        let queue_families = queue_families.to_vec();
+        Self::create_sized(
+            device,
+            allocator,
+            usage,
+            location,
+            size,
+            sharing_mode,
+            &queue_families,
+        )
+    }
+
+    /// A host visible source for uploads the transfer queue runs, see [`Device::upload_sharing`].
+    pub fn new_staging(device: Device, allocator: Allocator, size: u64) -> Self {
+        let (sharing_mode, queue_families) = device.upload_sharing();
+        let queue_families = queue_families.to_vec();
+        Self::create_sized(
+            device,
+            allocator,
+            BufferUsage::from_flags(vk::BufferUsageFlags::TRANSFER_SRC),
+            MemoryLocation::CpuToGpu,
+            size,
+            sharing_mode,
+            &queue_families,
+        )
+    }
+
+    fn create_sized(
         device: Device,
         mut allocator: Allocator,
         usage: BufferUsage,
         location: MemoryLocation,
         size: u64,
+        sharing_mode: vk::SharingMode,
+        queue_families: &[u32],
     ) -> Self {
         let buffer_info = vk::BufferCreateInfo::default()
             .size(size as _)
             .usage(usage.as_raw())
-            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(queue_families);
 
         let buffer = unsafe { device.create_buffer(&buffer_info, None).unwrap() };
         let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
@@ -281,6 +321,31 @@ impl Buffer {
         self.map_buffer_mem_and_write(data, offset)
     }
 
+    /// Writes consecutive elements starting at `first_element_idx`, `data` has to hold a whole
+    /// number of elements.
+    pub fn fill_elements_with_raw_u8(&self, data: &[u8], first_element_idx: u64) -> Result<()> {
+        let element_size = self.get_element_size_bytes();
+        if data.len() as u64 % element_size != 0 {
+            return Err(anyhow::anyhow!(
+                "Data size {} is not a multiple of element size {}",
+                data.len(),
+                element_size
+            ));
+        }
+
+        let element_count = data.len() as u64 / element_size;
+        if first_element_idx + element_count > self.desc.element_length {
+            return Err(anyhow::anyhow!(
+                "Elements {}..{} out of bounds for element length {}",
+                first_element_idx,
+                first_element_idx + element_count,
+                self.desc.element_length
+            ));
+        }
+
+        self.map_buffer_mem_and_write(data, first_element_idx * element_size)
+    }
+
     pub fn fill_with_raw_u8(&self, data: &[u8]) -> Result<()> {
         // validation: check if data size matches buffer size
         if data.len() != self.get_size_bytes() as usize {
@@ -328,6 +393,48 @@ impl Buffer {
         Err(anyhow::anyhow!("Failed to map buffer memory"))
     }
 
+    /// Like [`Buffer::fill`], but starting `byte_offset` bytes into the buffer.
+    pub fn fill_at<T: Copy>(&self, data: &[T], byte_offset: u64) -> Result<()> {
+        let size_of_slice = std::mem::size_of_val(data) as u64;
+        if byte_offset + size_of_slice > self.get_size_bytes() {
+            return Err(anyhow::anyhow!(
+                "Writing {} bytes at offset {} overflows the buffer of {} bytes",
+                size_of_slice,
+                byte_offset,
+                self.get_size_bytes()
+            ));
+        }
+        let data_u8: &[u8] =
+            unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_slice as usize) };
+        self.map_buffer_mem_and_write(data_u8, byte_offset)
+    }
+
+    /// Type-checked counterpart of [`Buffer::fill_with_raw_u8`] for the generated shader structs.
+    ///
+    /// Fails if the buffer was created from the layout of a different block, or if the sizes
+    /// disagree (the shader changed but the build script didn't rerun).
+    pub fn fill_struct<T: ShaderStruct>(&self, data: &T) -> Result<()> {
+        if let Some(layout) = self.get_layout() {
+            if layout.root_member.ty != T::BLOCK_NAME {
+                return Err(anyhow::anyhow!(
+                    "Buffer holds `{}`, but was filled with `{}`",
+                    layout.root_member.ty,
+                    T::BLOCK_NAME
+                ));
+            }
+            let expected_size = layout.get_size_bytes() as usize;
+            if expected_size != std::mem::size_of::<T>() {
+                return Err(anyhow::anyhow!(
+                    "`{}` is {} bytes in the shader but {} bytes in Rust",
+                    T::BLOCK_NAME,
+                    expected_size,
+                    std::mem::size_of::<T>()
+                ));
+            }
+        }
+        self.fill_with_raw_u8(bytemuck::bytes_of(data))
+    }
+
     /// Reads raw data from the buffer.
     ///
     /// # Returns
@@ -347,7 +454,31 @@ impl Buffer {
         }
     }
 
-    #[allow(dead_code)]
+    /// Reads `size` bytes starting `byte_offset` bytes into the buffer.
+    pub fn read_back_range(&self, byte_offset: u64, size: u64) -> Result<Vec<u8>> {
+        if byte_offset + size > self.get_size_bytes() {
+            return Err(anyhow::anyhow!(
+                "Reading {} bytes at offset {} overflows the buffer of {} bytes",
+                size,
+                byte_offset,
+                self.get_size_bytes()
+            ));
+        }
+        if let Some(ptr) = self.allocated_mem.mapped_ptr() {
+            let mut data: Vec<u8> = vec![0; size as usize];
+            unsafe {
+                let mapped_slice: &[u8] = slice::from_raw_parts(
+                    ptr.as_ptr().cast::<u8>().add(byte_offset as usize),
+                    size as usize,
+                );
+                data.copy_from_slice(mapped_slice);
+            }
+            Ok(data)
+        } else {
+            Err(anyhow::anyhow!("Failed to map buffer memory"))
+        }
+    }
+
     pub fn record_copy_to_buffer(
         &self,
         cmdbuf: &CommandBuffer,
@@ -17,15 +17,63 @@ struct ImageInner {
     desc: ImageDesc,
     image: vk::Image,
     allocator: Allocator,
-    allocated_mem: Allocation,
+    memory: ImageMemory,
     current_layout: Mutex<Vec<vk::ImageLayout>>,
     size: vk::DeviceSize,
 }
 
+enum ImageMemory {
+    Dedicated(Allocation),
+    Aliased(#[allow(dead_code)] Arc<AliasedMemory>),
+}
+
 impl Drop for ImageInner {
+    fn drop(&mut self) {
+        match &mut self.memory {
+            ImageMemory::Dedicated(allocated_mem) => {
+                let allocated_mem = std::mem::take(allocated_mem);
+                self.allocator.destroy_image(self.image, allocated_mem);
+            }
+            // the shared memory is freed once the last image placed on it is dropped
+            ImageMemory::Aliased(_) => unsafe { self.device.destroy_image(self.image, None) },
+        }
+    }
+}
+
+/// A memory block that several images with disjoint lifetimes are placed on.
+pub struct AliasedMemory {
+    allocator: Allocator,
+    allocated_mem: Allocation,
+}
+
+impl AliasedMemory {
+    /// Allocates a `GpuOnly` block that satisfies `requirements`, which should already be the
+    /// union of the requirements of every image placed on it.
+    pub fn new(mut allocator: Allocator, requirements: vk::MemoryRequirements) -> Result<Self> {
+        let allocated_mem = allocator
+            .allocate_memory(&AllocationCreateDesc {
+                name: "aliased image memory",
+                requirements,
+                location: MemoryLocation::GpuOnly,
+                linear: false,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to allocate aliased image memory: {}", e))?;
+        Ok(Self {
+            allocator,
+            allocated_mem,
+        })
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.allocated_mem.size()
+    }
+}
+
+impl Drop for AliasedMemory {
     fn drop(&mut self) {
         let allocated_mem = std::mem::take(&mut self.allocated_mem);
-        self.allocator.destroy_image(self.image, allocated_mem);
+        self.allocator.free_memory(allocated_mem);
     }
 }
 
@@ -63,7 +111,80 @@ impl std::ops::Deref for Image {
 }
 
 impl Image {
-    pub fn new(device: Device, mut allocator: Allocator, desc: &ImageDesc) -> Result<Self> {
+    pub fn new(device: Device, allocator: Allocator, desc: &ImageDesc) -> Result<Self> {
+        let (image, requirements) = Self::create_unbound(&device, desc)?;
+        Ok(Self::new_bound(
+            device,
+            allocator,
+            desc,
+            image,
+            requirements,
+        ))
+    }
+
+    /// Like [`Image::new`], also shared with the transfer family so the transfer queue can upload
+    /// into it, see [`Image::record_fill_from_buffer`].
+    pub fn new_uploadable(device: Device, allocator: Allocator, desc: &ImageDesc) -> Result<Self> {
+        let (sharing_mode, queue_families) = device.upload_sharing();
+        let (image, requirements) =
+            Self::create_unbound_shared(&device, desc, sharing_mode, queue_families)?;
+        Ok(Self::new_bound(
+            device,
+            allocator,
+            desc,
+            image,
+            requirements,
+        ))
+    }
+
+    fn new_bound(
+        device: Device,
+        mut allocator: Allocator,
+        desc: &ImageDesc,
+        image: vk::Image,
+        requirements: vk::MemoryRequirements,
+    ) -> Self {
+        let allocated_mem = allocator
+            .allocate_memory(&AllocationCreateDesc {
+                name: "",
+                requirements,
+                location: MemoryLocation::GpuOnly,
+                linear: false,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            })
+            .expect("Failed to allocate image memory");
+
+        unsafe {
+            device
+                .bind_image_memory(image, allocated_mem.memory(), allocated_mem.offset())
+                .unwrap()
+        };
+
+        Self::from_bound(
+            device,
+            allocator,
+            desc,
+            image,
+            ImageMemory::Dedicated(allocated_mem),
+        )
+    }
+
+    /// Creates the image handle without any memory bound to it, so the caller can decide where
+    /// to place it based on the returned requirements.
+    pub fn create_unbound(
+        device: &Device,
+        desc: &ImageDesc,
+    ) -> Result<(vk::Image, vk::MemoryRequirements)> {
+        let (sharing_mode, queue_families) = device.resource_sharing();
+        Self::create_unbound_shared(device, desc, sharing_mode, queue_families)
+    }
+
+    fn create_unbound_shared(
+        device: &Device,
+        desc: &ImageDesc,
+        sharing_mode: vk::SharingMode,
+        queue_families: &[u32],
+    ) -> Result<(vk::Image, vk::MemoryRequirements)> {
         // for vulkan spec, initial_layout must be either UNDEFINED or PREINITIALIZED,
         if desc.initial_layout != ImageLayout::UNDEFINED
             && desc.initial_layout != ImageLayout::PREINITIALIZED
@@ -80,29 +201,50 @@ impl Image {
             .tiling(desc.tilting)
             .initial_layout(ImageLayout::UNDEFINED)
             .usage(desc.usage)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(queue_families)
             .samples(desc.samples)
             .flags(vk::ImageCreateFlags::empty());
 
-        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let image = unsafe { device.create_image(&image_info, None)? };
         let requirements = unsafe { device.get_image_memory_requirements(image) };
+        Ok((image, requirements))
+    }
 
-        let allocated_mem = allocator
-            .allocate_memory(&AllocationCreateDesc {
-                name: "",
-                requirements,
-                location: MemoryLocation::GpuOnly,
-                linear: false,
-                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
-            })
-            .expect("Failed to allocate image memory");
-
+    /// Binds an image from [`Image::create_unbound`] to the start of `memory`.
+    ///
+    /// Images sharing the same memory must never be live at the same time, the first use of such
+    /// an image in a frame has to go through [`Image::record_discard_barrier`].
+    pub fn new_aliased(
+        device: Device,
+        allocator: Allocator,
+        desc: &ImageDesc,
+        image: vk::Image,
+        memory: Arc<AliasedMemory>,
+    ) -> Result<Self> {
         unsafe {
-            device
-                .bind_image_memory(image, allocated_mem.memory(), allocated_mem.offset())
-                .unwrap()
+            device.bind_image_memory(
+                image,
+                memory.allocated_mem.memory(),
+                memory.allocated_mem.offset(),
+            )?
         };
+        Ok(Self::from_bound(
+            device,
+            allocator,
+            desc,
+            image,
+            ImageMemory::Aliased(memory),
+        ))
+    }
 
+    fn from_bound(
+        device: Device,
+        allocator: Allocator,
+        desc: &ImageDesc,
+        image: vk::Image,
+        memory: ImageMemory,
+    ) -> Self {
         let size = desc.extent.width as vk::DeviceSize
             * desc.extent.height as vk::DeviceSize
             * desc.extent.depth as vk::DeviceSize
@@ -111,22 +253,21 @@ impl Image {
         // initialize one entry per array layer
         let layouts = vec![desc.initial_layout; desc.array_len as usize];
 
-        Ok(Self(Arc::new(ImageInner {
-            device: device.clone(),
+        Self(Arc::new(ImageInner {
+            device,
             image,
             desc: *desc,
             allocator,
-            allocated_mem,
+            memory,
             current_layout: Mutex::new(layouts),
             size,
-        })))
+        }))
     }
 
     pub fn get_desc(&self) -> &ImageDesc {
         &self.0.desc
     }
 
-    #[allow(dead_code)]
     pub fn copy_image_to_buffer(
         &self,
         buffer: &mut Buffer,
@@ -175,6 +316,7 @@ impl Image {
         });
     }
 
+    #[allow(dead_code)]
     pub fn record_copy_to(
         &self,
         cmdbuf: &CommandBuffer,
@@ -333,6 +475,42 @@ impl Image {
         self.record_transition_barrier(cmdbuf, base_array_layer, target_layout);
     }
 
+    /// Clears a color image that is already in `GENERAL` without any barrier, unlike
+    /// [`Image::record_clear`] this can be recorded on a compute-only queue. Ordering it against
+    /// the shaders using the image is up to the caller.
+    pub fn record_clear_in_general(
+        &self,
+        cmdbuf: &CommandBuffer,
+        base_array_layer: u32,
+        clear_value: ColorClearValue,
+    ) {
+        assert_eq!(
+            self.get_layout(base_array_layer),
+            vk::ImageLayout::GENERAL,
+            "The image must be in GENERAL to be cleared in place"
+        );
+        let clear_value = match clear_value {
+            ColorClearValue::UInt(v) => vk::ClearColorValue { uint32: v },
+            ColorClearValue::Float(v) => vk::ClearColorValue { float32: v },
+            ColorClearValue::Int(v) => vk::ClearColorValue { int32: v },
+        };
+        unsafe {
+            self.0.device.cmd_clear_color_image(
+                cmdbuf.as_raw(),
+                self.0.image,
+                vk::ImageLayout::GENERAL,
+                &clear_value,
+                &[vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer,
+                    layer_count: 1,
+                }],
+            );
+        }
+    }
+
     /// Transition just `array_layer` from its current layout → `target_layout`
     pub fn record_transition_barrier(
         &self,
@@ -365,6 +543,46 @@ impl Image {
         layouts[idx] = target_layout;
     }
 
+    /// Transitions every array layer from `UNDEFINED` to `target_layout`, dropping the contents.
+    ///
+    /// Used on the first write of an aliased image in a frame: the memory may have been written
+    /// through another image since, so the tracked layout can't be trusted anymore. Waits for all
+    /// prior commands so the previous owner of the memory is done with it.
+    pub fn record_discard_barrier(&self, cmdbuf: &CommandBuffer, target_layout: vk::ImageLayout) {
+        let mut layouts = self.0.current_layout.lock().unwrap();
+        let (dst_access, dst_stage) = map_dst_stage_access_flags(target_layout);
+
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(target_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.0.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: self.0.desc.get_aspect_mask(),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: self.0.desc.array_len,
+            })
+            .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+            .dst_access_mask(dst_access);
+
+        unsafe {
+            self.0.device.cmd_pipeline_barrier(
+                cmdbuf.as_raw(),
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            )
+        }
+
+        layouts.fill(target_layout);
+    }
+
     /// Force set the layout for the given array layer.
     #[allow(dead_code)]
     pub fn set_layout(&self, array_layer: u32, new_layout: vk::ImageLayout) {
@@ -400,7 +618,8 @@ impl Image {
         Ok(data)
     }
 
-    fn convert_rgba_data_to_image_format(&self, data: &[u8]) -> Result<Vec<u8>> {
+    /// Converts tightly packed RGBA8 pixels into the layout of the image's format.
+    pub fn convert_rgba_data_to_image_format(&self, data: &[u8]) -> Result<Vec<u8>> {
         use ash::vk::Format;
         let fmt = self.0.desc.format;
         // data is &[R, G, B, A,  R, G, B, A,  …]
@@ -527,10 +746,82 @@ impl Image {
         Ok(())
     }
 
+    /// Records copying `buffer` over the whole first layer, which ends up in `dst_image_layout`.
+    ///
+    /// Unlike [`Image::fill_with_raw_u8`] it only uses stages every queue has, so it can be
+    /// recorded on the transfer queue. Whoever uses the image next has to wait for the submission
+    /// with a semaphore, the barrier after the copy makes nothing visible to other stages.
+    pub fn record_fill_from_buffer(
+        &self,
+        cmdbuf: &CommandBuffer,
+        buffer: &Buffer,
+        dst_image_layout: vk::ImageLayout,
+    ) {
+        let device = &self.0.device;
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: self.0.desc.get_aspect_mask(),
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let to_transfer_dst = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.0.image)
+            .subresource_range(subresource_range)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: self.0.desc.get_aspect_mask(),
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(self.0.desc.extent.as_raw());
+        let to_dst_layout = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(dst_image_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.0.image)
+            .subresource_range(subresource_range)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmdbuf.as_raw(),
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer_dst],
+            );
+            device.cmd_copy_buffer_to_image(
+                cmdbuf.as_raw(),
+                buffer.as_raw(),
+                self.0.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
+            device.cmd_pipeline_barrier(
+                cmdbuf.as_raw(),
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_dst_layout],
+            );
+        }
+        self.0.current_layout.lock().unwrap()[0] = dst_image_layout;
+    }
+
     /// Obtain the image data from the texture of the full image region.
     // TODO: Add support for regions and other formats. Add support for
     // array layers.
-    #[allow(dead_code)]
     pub fn fetch_data(&self, queue: &Queue, command_pool: &CommandPool) -> Result<Vec<u8>> {
         let device = &self.0.device;
 
@@ -15,3 +15,6 @@ pub use region::*;
 
 mod desc;
 pub use desc::*;
+
+mod ping_pong;
+pub use ping_pong::*;
@@ -0,0 +1,70 @@
+use super::Texture;
+use crate::resource::{ResourceContainer, ResourceNameTree, StaticResourceNames};
+
+/// Two textures of the same description for history effects, one read from and one written
+/// to each frame.
+///
+/// [`Self::swap`] turns this frame's output into next frame's history without copying. The
+/// sides are bound by name, so pipelines using them need their descriptor sets updated after
+/// every swap.
+pub struct PingPongTexture {
+    read_name: &'static str,
+    write_name: &'static str,
+    textures: [Texture; 2],
+    write_idx: usize,
+}
+
+impl PingPongTexture {
+    /// By convention the names are `X_read` and `X_write`, matching the shader declarations.
+    pub fn new(read_name: &'static str, write_name: &'static str, textures: [Texture; 2]) -> Self {
+        Self {
+            read_name,
+            write_name,
+            textures,
+            write_idx: 0,
+        }
+    }
+
+    /// Holds what was written before the last swap.
+    pub fn read(&self) -> &Texture {
+        &self.textures[1 - self.write_idx]
+    }
+
+    pub fn write(&self) -> &Texture {
+        &self.textures[self.write_idx]
+    }
+
+    pub fn swap(&mut self) {
+        self.write_idx = 1 - self.write_idx;
+    }
+
+    /// Both sides, in no particular order.
+    pub fn textures(&self) -> &[Texture; 2] {
+        &self.textures
+    }
+}
+
+// the names are picked at runtime, `ResourceContainer::validate` checks them
+impl StaticResourceNames for PingPongTexture {
+    const RESOURCE_NAMES: ResourceNameTree = ResourceNameTree::EMPTY;
+}
+
+impl ResourceContainer for PingPongTexture {
+    fn get_buffer(&self, _name: &str) -> Option<&crate::vkn::Buffer> {
+        None
+    }
+
+    fn get_texture(&self, name: &str) -> Option<&Texture> {
+        if name == self.read_name {
+            Some(self.read())
+        } else if name == self.write_name {
+            Some(self.write())
+        } else {
+            None
+        }
+    }
+
+    fn get_resource_names(&self) -> Vec<String> {
+        vec![self.read_name.to_string(), self.write_name.to_string()]
+    }
+}
@@ -22,7 +22,12 @@ impl Texture {
         sampler_desc: &SamplerDesc,
     ) -> Self {
         let image = Image::new(device.clone(), allocator, img_desc).unwrap();
+        Self::from_image(device, image, sampler_desc)
+    }
 
+    /// Wraps an already created image, e.g. one placed on aliased memory.
+    pub fn from_image(device: Device, image: Image, sampler_desc: &SamplerDesc) -> Self {
+        let img_desc = *image.get_desc();
         let image_view_desc = ImageViewDesc {
             image: image.as_raw(),
             format: img_desc.format,
@@ -36,7 +41,7 @@ impl Texture {
             layer_count: img_desc.array_len,
         };
         let image_view = ImageView::new(device.clone(), image_view_desc);
-        let sampler = Sampler::new(device.clone(), sampler_desc);
+        let sampler = Sampler::new(device, sampler_desc);
 
         Self {
             image,
@@ -10,6 +10,9 @@ pub use context::*;
 mod swapchain;
 pub use swapchain::*;
 
+mod frames_in_flight;
+pub use frames_in_flight::*;
+
 mod shader;
 pub use shader::*;
 
@@ -22,6 +25,18 @@ pub use command::*;
 mod pipeline;
 pub use pipeline::*;
 
+mod frame_graph;
+pub use frame_graph::*;
+
+mod gpu_primitives;
+pub use gpu_primitives::*;
+
+mod gpu_profiler;
+pub use gpu_profiler::*;
+
+mod indirect_dispatch;
+pub use indirect_dispatch::*;
+
 mod descriptor;
 pub use descriptor::*;
 
@@ -37,6 +52,9 @@ pub use framebuffer::*;
 mod render_target;
 pub use render_target::*;
 
+mod dynamic_rendering;
+pub use dynamic_rendering::*;
+
 mod extent;
 pub use extent::*;
 
@@ -1,6 +1,6 @@
-use super::descriptor_set_utils;
+use super::descriptor_set_utils::{self, BindingSource};
 use crate::{
-    resource::ResourceContainer,
+    resource::{LookupMode, ResourceContainer},
     vkn::{
         Buffer, CommandBuffer, DescriptorPool, DescriptorSet, DescriptorSetLayoutBinding, Device,
         Extent3D, PipelineLayout, ShaderModule, WriteDescriptorSet,
@@ -11,7 +11,10 @@ use ash::vk;
 use std::{
     collections::HashMap,
     ops::Deref,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 struct ComputePipelineInner {
@@ -19,7 +22,10 @@ struct ComputePipelineInner {
     pipeline: vk::Pipeline,
     pipeline_layout: PipelineLayout,
     workgroup_size: [u32; 3],
-    descriptor_sets: Mutex<Vec<DescriptorSet>>,
+    /// One copy of the descriptor sets per frame slot, see [`ComputePipeline::add_frame_slot`].
+    descriptor_sets: Mutex<Vec<Vec<DescriptorSet>>>,
+    /// The copy the updates and the records use.
+    frame_slot: AtomicUsize,
     descriptor_sets_bindings: HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
 }
 
@@ -49,9 +55,62 @@ impl ComputePipeline {
         descriptor_pool: &DescriptorPool,
         resource_containers: &[&dyn ResourceContainer],
     ) -> Self {
-        let stage_info = shader_module.get_shader_stage_create_info();
+        Self::create(
+            device,
+            shader_module,
+            descriptor_pool,
+            resource_containers,
+            None,
+        )
+    }
+
+    /// Creates the pipeline with the workgroup size overridden through specialization constants.
+    ///
+    /// The shader has to declare `local_size_x_id = 0, local_size_y_id = 1` (and optionally
+    /// `local_size_z_id = 2`), otherwise the override is ignored by the driver while dispatches
+    /// are still sized by `workgroup_size`.
+    pub fn with_workgroup_size(
+        device: &Device,
+        shader_module: &ShaderModule,
+        descriptor_pool: &DescriptorPool,
+        resource_containers: &[&dyn ResourceContainer],
+        workgroup_size: [u32; 3],
+    ) -> Self {
+        Self::create(
+            device,
+            shader_module,
+            descriptor_pool,
+            resource_containers,
+            Some(workgroup_size),
+        )
+    }
+
+    fn create(
+        device: &Device,
+        shader_module: &ShaderModule,
+        descriptor_pool: &DescriptorPool,
+        resource_containers: &[&dyn ResourceContainer],
+        workgroup_size_override: Option<[u32; 3]>,
+    ) -> Self {
         let pipeline_layout = PipelineLayout::from_shader_module(device, shader_module);
-        let workgroup_size = shader_module.get_workgroup_size().unwrap();
+        let workgroup_size =
+            workgroup_size_override.unwrap_or_else(|| shader_module.get_workgroup_size().unwrap());
+
+        let map_entries = [0, 1, 2].map(|i: u32| {
+            vk::SpecializationMapEntry::default()
+                .constant_id(i)
+                .offset(i * std::mem::size_of::<u32>() as u32)
+                .size(std::mem::size_of::<u32>())
+        });
+        let specialization_data: &[u8] = bytemuck::cast_slice(&workgroup_size[..]);
+        let specialization_info = vk::SpecializationInfo::default()
+            .map_entries(&map_entries)
+            .data(specialization_data);
+
+        let mut stage_info = shader_module.get_shader_stage_create_info();
+        if workgroup_size_override.is_some() {
+            stage_info = stage_info.specialization_info(&specialization_info);
+        }
 
         let pipeline_info = vk::ComputePipelineCreateInfo::default()
             .stage(stage_info)
@@ -70,26 +129,54 @@ impl ComputePipeline {
 
         let descriptor_sets_bindings = shader_module.get_descriptor_sets_bindings();
 
-        let pipeline_instance = Self(Arc::new(ComputePipelineInner {
+        // auto-create descriptor sets
+        let descriptor_sets = descriptor_set_utils::auto_create_descriptor_sets(
+            descriptor_pool,
+            resource_containers,
+            &pipeline_layout,
+            &descriptor_sets_bindings,
+        )
+        .unwrap();
+
+        Self(Arc::new(ComputePipelineInner {
             device: device.clone(),
             pipeline,
             pipeline_layout,
             workgroup_size,
-            descriptor_sets: Mutex::new(vec![]),
+            descriptor_sets: Mutex::new(vec![descriptor_sets]),
+            frame_slot: AtomicUsize::new(0),
             descriptor_sets_bindings,
-        }));
+        }))
+    }
 
-        // auto-create descriptor sets
-        descriptor_set_utils::auto_create_descriptor_sets(
+    /// Adds a copy of the descriptor sets for one more frame slot, so the sets a frame in flight
+    /// was recorded with aren't rewritten while the next frame is recorded. The copy starts
+    /// unbound, select its slot and update it before recording with it.
+    pub fn add_frame_slot(&self, descriptor_pool: &DescriptorPool) -> Result<()> {
+        let descriptor_sets = descriptor_set_utils::allocate_descriptor_sets(
             descriptor_pool,
-            resource_containers,
-            &pipeline_instance.0.pipeline_layout,
-            &pipeline_instance.0.descriptor_sets_bindings,
-            &pipeline_instance.0.descriptor_sets,
-        )
-        .unwrap();
+            &self.0.pipeline_layout,
+            &self.0.descriptor_sets_bindings,
+        )?;
+        self.0.descriptor_sets.lock().unwrap().push(descriptor_sets);
+        Ok(())
+    }
+
+    /// Makes the updates and the records use the descriptor sets of `slot` from now on.
+    pub fn select_frame_slot(&self, slot: usize) {
+        let slot_count = self.0.descriptor_sets.lock().unwrap().len();
+        assert!(
+            slot < slot_count,
+            "Frame slot {} selected, the pipeline has {}",
+            slot,
+            slot_count
+        );
+        self.0.frame_slot.store(slot, Ordering::Relaxed);
+    }
 
-        pipeline_instance
+    fn with_descriptor_sets<R>(&self, f: impl FnOnce(&[DescriptorSet]) -> R) -> R {
+        let slots = self.0.descriptor_sets.lock().unwrap();
+        f(&slots[self.0.frame_slot.load(Ordering::Relaxed)])
     }
 
     /// Updates existing descriptor sets with new resources.
@@ -97,35 +184,84 @@ impl ComputePipeline {
         &self,
         resource_containers: &[&dyn ResourceContainer],
     ) -> Result<()> {
-        descriptor_set_utils::auto_update_descriptor_sets(
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_set_utils::auto_update_descriptor_sets(
+                resource_containers,
+                &self.0.descriptor_sets_bindings,
+                descriptor_sets,
+                LookupMode::Strict,
+            )
+        })
+    }
+
+    /// Like [`Self::auto_update_descriptor_sets`], but a resource held by several containers is
+    /// taken from the one listed first instead of failing.
+    #[allow(dead_code)]
+    pub fn auto_update_descriptor_sets_by_priority(
+        &self,
+        resource_containers: &[&dyn ResourceContainer],
+    ) -> Result<()> {
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_set_utils::auto_update_descriptor_sets(
+                resource_containers,
+                &self.0.descriptor_sets_bindings,
+                descriptor_sets,
+                LookupMode::Priority,
+            )
+        })
+    }
+
+    /// Which container each binding would be bound from, nothing is written.
+    #[allow(dead_code)]
+    pub fn binding_sources(
+        &self,
+        resource_containers: &[&dyn ResourceContainer],
+        mode: LookupMode,
+    ) -> Result<Vec<BindingSource>> {
+        descriptor_set_utils::resolve_binding_sources(
             resource_containers,
             &self.0.descriptor_sets_bindings,
-            &self.0.descriptor_sets,
+            mode,
         )
     }
 
+    /// Rebinds the resources found in the containers and leaves every other binding alone.
+    pub fn update_matching_descriptor_sets(
+        &self,
+        resource_containers: &[&dyn ResourceContainer],
+    ) -> Result<()> {
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_set_utils::update_matching_descriptor_sets(
+                resource_containers,
+                &self.0.descriptor_sets_bindings,
+                descriptor_sets,
+            )
+        })
+    }
+
     pub fn write_descriptor_set(&self, set_no: u32, write: WriteDescriptorSet) {
-        let guard = self.0.descriptor_sets.lock().unwrap();
-        guard[set_no as usize].perform_writes(&mut [write]);
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_sets[set_no as usize].perform_writes(&mut [write]);
+        });
     }
 
-    fn record_bind_descriptor_sets(
-        &self,
-        cmdbuf: &CommandBuffer,
-        descriptor_sets: &[DescriptorSet],
-        first_set: u32,
-    ) {
-        let descriptor_sets = descriptor_sets
-            .iter()
-            .map(|s| s.as_raw())
-            .collect::<Vec<_>>();
+    fn record_bind_descriptor_sets(&self, cmdbuf: &CommandBuffer) {
+        let descriptor_sets = self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_sets
+                .iter()
+                .map(|s| s.as_raw())
+                .collect::<Vec<_>>()
+        });
+        if descriptor_sets.is_empty() {
+            return;
+        }
 
         unsafe {
             self.0.device.cmd_bind_descriptor_sets(
                 cmdbuf.as_raw(),
                 vk::PipelineBindPoint::COMPUTE,
                 self.0.pipeline_layout.as_raw(),
-                first_set,
+                0,
                 &descriptor_sets,
                 &[],
             );
@@ -173,9 +309,7 @@ impl ComputePipeline {
         push_constants: Option<&[u8]>,
     ) {
         self.record_bind(cmdbuf);
-        if !self.0.descriptor_sets.lock().unwrap().is_empty() {
-            self.record_bind_descriptor_sets(cmdbuf, &self.0.descriptor_sets.lock().unwrap(), 0);
-        }
+        self.record_bind_descriptor_sets(cmdbuf);
         if let Some(push_constants) = push_constants {
             self.record_push_constants(cmdbuf, push_constants);
         }
@@ -199,9 +333,7 @@ impl ComputePipeline {
         push_constants: Option<&[u8]>,
     ) {
         self.record_bind(cmdbuf);
-        if !self.0.descriptor_sets.lock().unwrap().is_empty() {
-            self.record_bind_descriptor_sets(cmdbuf, &self.0.descriptor_sets.lock().unwrap(), 0);
-        }
+        self.record_bind_descriptor_sets(cmdbuf);
         if let Some(push_constants) = push_constants {
             self.record_push_constants(cmdbuf, push_constants);
         }
@@ -1,13 +1,19 @@
 use crate::{
-    resource::ResourceContainer,
+    resource::{
+        container_label, find_resource, LookupMode, ResourceContainer, ResourceKind,
+        ResourceLookupError, ResourceRef,
+    },
     vkn::{
         DescriptorPool, DescriptorSet, DescriptorSetLayoutBinding, PipelineLayout,
-        WriteDescriptorSet,
+        WriteDescriptorSet, MAX_VARIABLE_DESCRIPTOR_COUNT,
     },
 };
 use anyhow::Result;
 use ash::vk;
-use std::{collections::HashMap, sync::Mutex};
+use std::collections::HashMap;
+
+/// Bindings named with this prefix aren't looked up in the containers, they're written by hand.
+const MANUAL_BINDING_PREFIX: &str = "manual_";
 
 /// Creates descriptor sets for a pipeline using automatic resource binding.
 pub fn auto_create_descriptor_sets(
@@ -15,43 +21,130 @@ pub fn auto_create_descriptor_sets(
     resource_containers: &[&dyn ResourceContainer],
     pipeline_layout: &PipelineLayout,
     descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
-    descriptor_sets_storage: &Mutex<Vec<DescriptorSet>>,
-) -> Result<()> {
-    let mut descriptor_sets = Vec::new();
-    let mut sorted_sets: Vec<_> = descriptor_sets_bindings.iter().collect();
-    sorted_sets.sort_by_key(|(set_no, _)| *set_no);
-
-    // allocate descriptor sets from the pool
-    for (set_no, _) in sorted_sets {
-        let descriptor_set = descriptor_pool
-            .allocate_set(&pipeline_layout.get_descriptor_set_layouts()[set_no])
-            .unwrap();
-        descriptor_sets.push(descriptor_set);
+) -> Result<Vec<DescriptorSet>> {
+    // clashes the compile time check can't see surface here, before any frame is recorded
+    for container in resource_containers {
+        container.validate()?;
     }
 
-    // store the allocated descriptor sets
-    {
-        let mut guard = descriptor_sets_storage.lock().unwrap();
-        *guard = descriptor_sets;
-    }
+    let descriptor_sets =
+        allocate_descriptor_sets(descriptor_pool, pipeline_layout, descriptor_sets_bindings)?;
 
     // update the descriptor sets with the provided resources
     auto_update_descriptor_sets(
         resource_containers,
         descriptor_sets_bindings,
-        descriptor_sets_storage,
+        &descriptor_sets,
+        LookupMode::Strict,
     )?;
 
-    Ok(())
+    Ok(descriptor_sets)
+}
+
+/// Allocates the descriptor sets of a pipeline in set order, nothing is bound yet.
+pub fn allocate_descriptor_sets(
+    descriptor_pool: &DescriptorPool,
+    pipeline_layout: &PipelineLayout,
+    descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
+) -> Result<Vec<DescriptorSet>> {
+    let mut sorted_sets: Vec<_> = descriptor_sets_bindings.iter().collect();
+    sorted_sets.sort_by_key(|(set_no, _)| *set_no);
+
+    sorted_sets
+        .into_iter()
+        .map(|(set_no, _)| {
+            descriptor_pool.allocate_set(&pipeline_layout.get_descriptor_set_layouts()[set_no])
+        })
+        .collect()
 }
 
 /// Updates existing descriptor sets with new resources.
 pub fn auto_update_descriptor_sets(
     resource_containers: &[&dyn ResourceContainer],
     descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
-    descriptor_sets_storage: &Mutex<Vec<DescriptorSet>>,
+    descriptor_sets: &[DescriptorSet],
+    mode: LookupMode,
+) -> Result<()> {
+    update_descriptor_sets(
+        resource_containers,
+        descriptor_sets_bindings,
+        descriptor_sets,
+        mode,
+        false,
+    )
+}
+
+/// Rewrites only the bindings whose resource is found in the containers, the rest keep what
+/// they were bound to.
+pub fn update_matching_descriptor_sets(
+    resource_containers: &[&dyn ResourceContainer],
+    descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
+    descriptor_sets: &[DescriptorSet],
+) -> Result<()> {
+    update_descriptor_sets(
+        resource_containers,
+        descriptor_sets_bindings,
+        descriptor_sets,
+        LookupMode::Strict,
+        true,
+    )
+}
+
+/// Which container satisfies a binding, see [`resolve_binding_sources`].
+#[derive(Debug, Clone)]
+pub struct BindingSource {
+    pub set_no: u32,
+    pub binding_no: u32,
+    pub binding_name: String,
+    /// `None` for bindings left for manual binding.
+    pub source: Option<(String, ResourceKind)>,
+}
+
+/// Resolves every binding the way the descriptor set updates do without writing anything, for
+/// debugging which container a binding ends up with.
+pub fn resolve_binding_sources(
+    resource_containers: &[&dyn ResourceContainer],
+    descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
+    mode: LookupMode,
+) -> Result<Vec<BindingSource>> {
+    let mut sources = Vec::new();
+    for (set_no, bindings) in descriptor_sets_bindings {
+        for binding in bindings.values() {
+            let source = match find_resource(resource_containers, &binding.name, mode)? {
+                Some(found) => Some((
+                    container_label(
+                        resource_containers[found.container_idx],
+                        found.container_idx,
+                    ),
+                    found.resource.kind(),
+                )),
+                None if binding.name.starts_with(MANUAL_BINDING_PREFIX) => None,
+                None => {
+                    return Err(ResourceLookupError::NotFound {
+                        name: binding.name.clone(),
+                    }
+                    .into())
+                }
+            };
+            sources.push(BindingSource {
+                set_no: *set_no,
+                binding_no: binding.no,
+                binding_name: binding.name.clone(),
+                source,
+            });
+        }
+    }
+    sources.sort_by_key(|source| (source.set_no, source.binding_no));
+    Ok(sources)
+}
+
+fn update_descriptor_sets(
+    resource_containers: &[&dyn ResourceContainer],
+    descriptor_sets_bindings: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
+    descriptor_sets: &[DescriptorSet],
+    mode: LookupMode,
+    skip_missing: bool,
 ) -> Result<()> {
-    let descriptor_sets = descriptor_sets_storage.lock().unwrap();
     let mut sorted_sets: Vec<_> = descriptor_sets_bindings.iter().collect();
     sorted_sets.sort_by_key(|(set_no, _)| *set_no);
 
@@ -59,63 +152,49 @@ pub fn auto_update_descriptor_sets(
         let descriptor_set = &descriptor_sets[set_idx];
 
         for (_binding_idx, binding) in bindings.iter() {
-            // find the exact resource for this binding across all resource containers
-            let mut found_buffer_containers = Vec::new();
-            let mut found_texture_containers = Vec::new();
+            let Some(found) = find_resource(resource_containers, &binding.name, mode)? else {
+                // bindings prefixed with "manual_" are left for manual binding
+                if !skip_missing && !binding.name.starts_with(MANUAL_BINDING_PREFIX) {
+                    return Err(ResourceLookupError::NotFound {
+                        name: binding.name.clone(),
+                    }
+                    .into());
+                }
+                continue;
+            };
 
-            for (i, container) in resource_containers.iter().enumerate() {
-                if container.get_buffer(&binding.name).is_some() {
-                    found_buffer_containers.push(i);
+            match found.resource {
+                ResourceRef::Buffer(buffer) => {
+                    descriptor_set.perform_writes(&mut [WriteDescriptorSet::new_buffer_write(
+                        binding.no, buffer,
+                    )]);
                 }
-                if container.get_texture(&binding.name).is_some() {
-                    found_texture_containers.push(i);
+                ResourceRef::Texture(texture) => {
+                    descriptor_set.perform_writes(&mut [WriteDescriptorSet::new_texture_write(
+                        binding.no,
+                        binding.descriptor_type,
+                        texture,
+                        vk::ImageLayout::GENERAL,
+                    )]);
                 }
-            }
-
-            // ensure that only one resource container has that resource
-            let total_found = found_buffer_containers.len() + found_texture_containers.len();
-            if total_found == 0 {
-                // if binding.name starts with "manual_", ignore it, it's left for manual binding
-                if !binding.name.starts_with("manual_") {
-                    return Err(anyhow::anyhow!("Resource not found: {}", binding.name));
-                } else {
-                    continue;
+                ResourceRef::TextureArray(textures) => {
+                    if textures.len() as u32 > MAX_VARIABLE_DESCRIPTOR_COUNT {
+                        return Err(anyhow::anyhow!(
+                            "Texture array '{}' holds {} textures, at most {} can be bound",
+                            binding.name,
+                            textures.len(),
+                            MAX_VARIABLE_DESCRIPTOR_COUNT
+                        ));
+                    }
+                    descriptor_set.perform_writes(&mut [
+                        WriteDescriptorSet::new_texture_array_write(
+                            binding.no,
+                            binding.descriptor_type,
+                            textures,
+                            vk::ImageLayout::GENERAL,
+                        ),
+                    ]);
                 }
-            } else if total_found > 1 {
-                return Err(anyhow::anyhow!(
-                    "Resource '{}' found in multiple containers: {} buffer containers, {} texture containers",
-                    binding.name,
-                    found_buffer_containers.len(),
-                    found_texture_containers.len()
-                ));
-            }
-
-            // each resource may be Buffer or Texture, but not both
-            if !found_buffer_containers.is_empty() && !found_texture_containers.is_empty() {
-                return Err(anyhow::anyhow!(
-                    "Resource '{}' found as both Buffer and Texture",
-                    binding.name
-                ));
-            }
-
-            // write the descriptor set based on the found resource
-            if let Some(container_idx) = found_buffer_containers.first() {
-                let resource = resource_containers[*container_idx]
-                    .get_buffer(&binding.name)
-                    .unwrap();
-                descriptor_set.perform_writes(&mut [WriteDescriptorSet::new_buffer_write(
-                    binding.no, resource,
-                )]);
-            } else if let Some(container_idx) = found_texture_containers.first() {
-                let resource = resource_containers[*container_idx]
-                    .get_texture(&binding.name)
-                    .unwrap();
-                descriptor_set.perform_writes(&mut [WriteDescriptorSet::new_texture_write(
-                    binding.no,
-                    binding.descriptor_type,
-                    resource,
-                    vk::ImageLayout::GENERAL,
-                )]);
             }
         }
     }
@@ -1,11 +1,11 @@
-use super::descriptor_set_utils;
+use super::descriptor_set_utils::{self, BindingSource};
 use crate::util::MergeWithEq;
 use crate::vkn::WriteDescriptorSet;
 use crate::{
-    resource::ResourceContainer,
+    resource::{LookupMode, ResourceContainer},
     vkn::{
-        CommandBuffer, DescriptorPool, DescriptorSet, DescriptorSetLayoutBinding, Device,
-        FormatOverride, PipelineLayout, RenderPass, ShaderModule, Viewport,
+        Buffer, CommandBuffer, DescriptorPool, DescriptorSet, DescriptorSetLayoutBinding, Device,
+        FormatOverride, PipelineLayout, RenderingTarget, ShaderModule, Viewport,
     },
 };
 use anyhow::Result;
@@ -13,14 +13,20 @@ use ash::vk;
 use std::{
     collections::HashMap,
     ops::Deref,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 struct GraphicsPipelineInner {
     device: Device,
     pipeline: vk::Pipeline,
     pipeline_layout: PipelineLayout,
-    descriptor_sets: Mutex<Vec<DescriptorSet>>,
+    /// One copy of the descriptor sets per frame slot, see [`GraphicsPipeline::add_frame_slot`].
+    descriptor_sets: Mutex<Vec<Vec<DescriptorSet>>>,
+    /// The copy the updates and the records use.
+    frame_slot: AtomicUsize,
     descriptor_sets_bindings: HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
 }
 
@@ -55,6 +61,8 @@ pub struct GraphicsPipelineDesc {
     pub front_face: vk::FrontFace,
     pub depth_test_enable: bool,
     pub depth_write_enable: bool,
+    /// The bias factors are set while recording, see [`GraphicsPipeline::record_depth_bias`].
+    pub depth_bias_enable: bool,
 }
 
 impl Default for GraphicsPipelineDesc {
@@ -65,6 +73,7 @@ impl Default for GraphicsPipelineDesc {
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             depth_test_enable: false,
             depth_write_enable: false,
+            depth_bias_enable: false,
         }
     }
 }
@@ -75,7 +84,7 @@ impl GraphicsPipeline {
         device: &Device,
         vert_shader_module: &ShaderModule,
         frag_shader_module: &ShaderModule,
-        render_pass: &RenderPass,
+        target: RenderingTarget,
         desc: &GraphicsPipelineDesc,
         instance_rate_starting_location: Option<u32>,
         descriptor_pool: &DescriptorPool,
@@ -109,7 +118,7 @@ impl GraphicsPipeline {
             .line_width(1.0)
             .cull_mode(desc.cull_mode)
             .front_face(desc.front_face)
-            .depth_bias_enable(false)
+            .depth_bias_enable(desc.depth_bias_enable)
             .depth_bias_constant_factor(0.0)
             .depth_bias_clamp(0.0)
             .depth_bias_slope_factor(0.0);
@@ -127,7 +136,7 @@ impl GraphicsPipeline {
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false);
 
-        let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
             .color_write_mask(
                 vk::ColorComponentFlags::R
                     | vk::ColorComponentFlags::G
@@ -140,7 +149,8 @@ impl GraphicsPipeline {
             .color_blend_op(vk::BlendOp::ADD)
             .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
             .dst_alpha_blend_factor(vk::BlendFactor::ONE)
-            .alpha_blend_op(vk::BlendOp::ADD)];
+            .alpha_blend_op(vk::BlendOp::ADD);
+        let color_blend_attachments = vec![color_blend_attachment; target.color_attachment_count()];
         let color_blending_info = vk::PipelineColorBlendStateCreateInfo::default()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
@@ -154,13 +164,15 @@ impl GraphicsPipeline {
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false);
 
-        let dynamic_states = [vk::DynamicState::SCISSOR, vk::DynamicState::VIEWPORT];
+        let mut dynamic_states = vec![vk::DynamicState::SCISSOR, vk::DynamicState::VIEWPORT];
+        if desc.depth_bias_enable {
+            dynamic_states.push(vk::DynamicState::DEPTH_BIAS);
+        }
         let dynamic_states_info =
             vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
 
-        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        let mut pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_states_infos)
-            .render_pass(render_pass.as_raw())
             .layout(pipeline_layout.as_raw())
             .vertex_input_state(&vertex_input_info)
             .input_assembly_state(&input_assembly_info)
@@ -171,6 +183,19 @@ impl GraphicsPipeline {
             .depth_stencil_state(&depth_stencil_state_create_info)
             .dynamic_state(&dynamic_states_info);
 
+        let mut rendering_info = vk::PipelineRenderingCreateInfo::default();
+        match target {
+            RenderingTarget::RenderPass(render_pass) => {
+                pipeline_info = pipeline_info.render_pass(render_pass.as_raw());
+            }
+            RenderingTarget::Dynamic(formats) => {
+                rendering_info = rendering_info
+                    .color_attachment_formats(&formats.color_formats)
+                    .depth_attachment_format(formats.depth_format.unwrap_or(vk::Format::UNDEFINED));
+                pipeline_info = pipeline_info.push_next(&mut rendering_info);
+            }
+        }
+
         let pipeline = Self::create_pipeline(device, &pipeline_info);
 
         let vert_descriptor_sets_bindings = vert_shader_module.get_descriptor_sets_bindings();
@@ -181,25 +206,23 @@ impl GraphicsPipeline {
         )
         .unwrap();
 
-        let pipeline_instance = Self(Arc::new(GraphicsPipelineInner {
-            device: device.clone(),
-            pipeline,
-            pipeline_layout,
-            descriptor_sets: Mutex::new(Vec::new()),
-            descriptor_sets_bindings,
-        }));
-
         // auto-create descriptor sets
-        descriptor_set_utils::auto_create_descriptor_sets(
+        let descriptor_sets = descriptor_set_utils::auto_create_descriptor_sets(
             descriptor_pool,
             resource_containers,
-            &pipeline_instance.0.pipeline_layout,
-            &pipeline_instance.0.descriptor_sets_bindings,
-            &pipeline_instance.0.descriptor_sets,
+            &pipeline_layout,
+            &descriptor_sets_bindings,
         )
         .unwrap();
 
-        return pipeline_instance;
+        return Self(Arc::new(GraphicsPipelineInner {
+            device: device.clone(),
+            pipeline,
+            pipeline_layout,
+            descriptor_sets: Mutex::new(vec![descriptor_sets]),
+            frame_slot: AtomicUsize::new(0),
+            descriptor_sets_bindings,
+        }));
 
         fn merge_descriptor_sets_bindings(
             bindings_1: &HashMap<u32, HashMap<u32, DescriptorSetLayoutBinding>>,
@@ -239,23 +262,53 @@ impl GraphicsPipeline {
         &self.0.pipeline_layout
     }
 
-    fn record_bind_descriptor_sets(
-        &self,
-        cmdbuf: &CommandBuffer,
-        descriptor_sets: &[DescriptorSet],
-        first_set: u32,
-    ) {
-        let descriptor_sets = descriptor_sets
-            .iter()
-            .map(|s| s.as_raw())
-            .collect::<Vec<_>>();
+    /// Adds a copy of the descriptor sets for one more frame slot, so the sets a frame in flight
+    /// was recorded with aren't rewritten while the next frame is recorded. The copy starts
+    /// unbound, select its slot and update it before recording with it.
+    pub fn add_frame_slot(&self, descriptor_pool: &DescriptorPool) -> Result<()> {
+        let descriptor_sets = descriptor_set_utils::allocate_descriptor_sets(
+            descriptor_pool,
+            &self.0.pipeline_layout,
+            &self.0.descriptor_sets_bindings,
+        )?;
+        self.0.descriptor_sets.lock().unwrap().push(descriptor_sets);
+        Ok(())
+    }
+
+    /// Makes the updates and the records use the descriptor sets of `slot` from now on.
+    pub fn select_frame_slot(&self, slot: usize) {
+        let slot_count = self.0.descriptor_sets.lock().unwrap().len();
+        assert!(
+            slot < slot_count,
+            "Frame slot {} selected, the pipeline has {}",
+            slot,
+            slot_count
+        );
+        self.0.frame_slot.store(slot, Ordering::Relaxed);
+    }
+
+    fn with_descriptor_sets<R>(&self, f: impl FnOnce(&[DescriptorSet]) -> R) -> R {
+        let slots = self.0.descriptor_sets.lock().unwrap();
+        f(&slots[self.0.frame_slot.load(Ordering::Relaxed)])
+    }
+
+    fn record_bind_descriptor_sets(&self, cmdbuf: &CommandBuffer) {
+        let descriptor_sets = self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_sets
+                .iter()
+                .map(|s| s.as_raw())
+                .collect::<Vec<_>>()
+        });
+        if descriptor_sets.is_empty() {
+            return;
+        }
 
         unsafe {
             self.0.device.cmd_bind_descriptor_sets(
                 cmdbuf.as_raw(),
                 vk::PipelineBindPoint::GRAPHICS,
                 self.0.pipeline_layout.as_raw(),
-                first_set,
+                0,
                 &descriptor_sets,
                 &[],
             );
@@ -316,6 +369,20 @@ impl GraphicsPipeline {
         }
     }
 
+    /// Only for pipelines created with `depth_bias_enable`, the bias is left unclamped.
+    pub fn record_depth_bias(
+        &self,
+        cmdbuf: &CommandBuffer,
+        constant_factor: f32,
+        slope_factor: f32,
+    ) {
+        unsafe {
+            self.0
+                .device
+                .cmd_set_depth_bias(cmdbuf.as_raw(), constant_factor, 0.0, slope_factor);
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn record_indexed(
         &self,
@@ -328,9 +395,7 @@ impl GraphicsPipeline {
         push_constants: Option<&PushConstantInfo>,
     ) {
         self.record_bind(cmdbuf);
-        if !self.0.descriptor_sets.lock().unwrap().is_empty() {
-            self.record_bind_descriptor_sets(cmdbuf, &self.0.descriptor_sets.lock().unwrap(), 0);
-        }
+        self.record_bind_descriptor_sets(cmdbuf);
         if let Some(push_constants) = push_constants {
             self.record_push_constants(cmdbuf, push_constants);
         }
@@ -344,6 +409,52 @@ impl GraphicsPipeline {
         );
     }
 
+    /// Draws with the `VkDrawIndexedIndirectCommand` at `offset` in `indirect_buf`, written on the
+    /// GPU.
+    pub fn record_indexed_indirect(
+        &self,
+        cmdbuf: &CommandBuffer,
+        indirect_buf: &Buffer,
+        offset: u64,
+        push_constants: Option<&PushConstantInfo>,
+    ) {
+        self.record_bind(cmdbuf);
+        self.record_bind_descriptor_sets(cmdbuf);
+        if let Some(push_constants) = push_constants {
+            self.record_push_constants(cmdbuf, push_constants);
+        }
+        unsafe {
+            self.0.device.cmd_draw_indexed_indirect(
+                cmdbuf.as_raw(),
+                indirect_buf.as_raw(),
+                offset,
+                1,
+                std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+            );
+        }
+    }
+
+    /// Draws without vertex or index buffers, the vertex shader builds the vertices from the
+    /// vertex and instance indices.
+    pub fn record_draw(
+        &self,
+        cmdbuf: &CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        push_constants: Option<&PushConstantInfo>,
+    ) {
+        self.record_bind(cmdbuf);
+        self.record_bind_descriptor_sets(cmdbuf);
+        if let Some(push_constants) = push_constants {
+            self.record_push_constants(cmdbuf, push_constants);
+        }
+        unsafe {
+            self.0
+                .device
+                .cmd_draw(cmdbuf.as_raw(), vertex_count, instance_count, 0, 0);
+        }
+    }
+
     fn record_draw_indexed(
         &self,
         cmdbuf: &CommandBuffer,
@@ -366,8 +477,9 @@ impl GraphicsPipeline {
     }
 
     pub fn write_descriptor_set(&self, set_no: u32, write: WriteDescriptorSet) {
-        let guard = self.0.descriptor_sets.lock().unwrap();
-        guard[set_no as usize].perform_writes(&mut [write]);
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_sets[set_no as usize].perform_writes(&mut [write]);
+        });
     }
 
     /// Updates existing descriptor sets with new resources.
@@ -376,10 +488,27 @@ impl GraphicsPipeline {
         &self,
         resource_containers: &[&dyn ResourceContainer],
     ) -> Result<()> {
-        descriptor_set_utils::auto_update_descriptor_sets(
+        self.with_descriptor_sets(|descriptor_sets| {
+            descriptor_set_utils::auto_update_descriptor_sets(
+                resource_containers,
+                &self.0.descriptor_sets_bindings,
+                descriptor_sets,
+                LookupMode::Strict,
+            )
+        })
+    }
+
+    /// Which container each binding would be bound from, nothing is written.
+    #[allow(dead_code)]
+    pub fn binding_sources(
+        &self,
+        resource_containers: &[&dyn ResourceContainer],
+        mode: LookupMode,
+    ) -> Result<Vec<BindingSource>> {
+        descriptor_set_utils::resolve_binding_sources(
             resource_containers,
             &self.0.descriptor_sets_bindings,
-            &self.0.descriptor_sets,
+            mode,
         )
     }
 }
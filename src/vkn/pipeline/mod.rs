@@ -8,3 +8,7 @@ mod pipeline_layout;
 pub use pipeline_layout::*;
 
 mod descriptor_set_utils;
+pub use descriptor_set_utils::BindingSource;
+
+mod workgroup_autotune;
+pub use workgroup_autotune::*;
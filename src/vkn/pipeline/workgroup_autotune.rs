@@ -0,0 +1,143 @@
+use crate::vkn::{execute_one_time_command, CommandBuffer, ComputePipeline, VulkanContext};
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Workgroup sizes tried by [`find_fastest_workgroup_size`], candidates above the device's
+/// invocation limit are skipped.
+pub const WORKGROUP_SIZE_CANDIDATES: [[u32; 3]; 6] = [
+    [8, 8, 1],
+    [16, 8, 1],
+    [8, 16, 1],
+    [16, 16, 1],
+    [32, 8, 1],
+    [8, 4, 1],
+];
+
+const WARMUP_ITERATIONS: u32 = 2;
+const TIMED_ITERATIONS: u32 = 8;
+
+/// The fastest known workgroup size per pipeline name, persisted per device.
+///
+/// Stored as plain text, one `name x y z` line per pipeline, in a file named after
+/// [`VulkanContext::device_cache_key`] so switching GPUs or drivers retunes from scratch.
+pub struct WorkgroupSizeCache {
+    path: PathBuf,
+    sizes: BTreeMap<String, [u32; 3]>,
+}
+
+impl WorkgroupSizeCache {
+    /// Loads the cache of the current device from `dir`, a missing or malformed file yields an
+    /// empty cache.
+    pub fn load(vulkan_ctx: &VulkanContext, dir: impl AsRef<Path>) -> Self {
+        let path = dir
+            .as_ref()
+            .join(format!("{}.txt", vulkan_ctx.device_cache_key()));
+        let sizes = std::fs::read_to_string(&path)
+            .map(|content| parse_sizes(&content))
+            .unwrap_or_default();
+        Self { path, sizes }
+    }
+
+    pub fn get(&self, name: &str) -> Option<[u32; 3]> {
+        self.sizes.get(name).copied()
+    }
+
+    pub fn insert(&mut self, name: &str, workgroup_size: [u32; 3]) {
+        self.sizes.insert(name.to_string(), workgroup_size);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serialize_sizes(&self.sizes))?;
+        Ok(())
+    }
+}
+
+fn parse_sizes(content: &str) -> BTreeMap<String, [u32; 3]> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let mut size = [0; 3];
+            for s in size.iter_mut() {
+                *s = parts.next()?.parse().ok()?;
+            }
+            Some((name.to_string(), size))
+        })
+        .collect()
+}
+
+fn serialize_sizes(sizes: &BTreeMap<String, [u32; 3]>) -> String {
+    sizes
+        .iter()
+        .map(|(name, [x, y, z])| format!("{} {} {} {}\n", name, x, y, z))
+        .collect()
+}
+
+/// Times `record` with a pipeline built for each of [`WORKGROUP_SIZE_CANDIDATES`] and returns the
+/// fastest workgroup size.
+///
+/// The timing covers submission and a queue wait, which is a constant overhead across candidates
+/// and so doesn't change the ranking. The resources the pipelines bind must already be in the
+/// layouts the shader expects.
+pub fn find_fastest_workgroup_size(
+    vulkan_ctx: &VulkanContext,
+    create_pipeline: impl Fn([u32; 3]) -> ComputePipeline,
+    record: impl Fn(&CommandBuffer, &ComputePipeline),
+) -> [u32; 3] {
+    let max_invocations = vulkan_ctx.max_compute_work_group_invocations();
+    let device = vulkan_ctx.device();
+    let queue = vulkan_ctx.get_general_queue();
+
+    let mut fastest = (WORKGROUP_SIZE_CANDIDATES[0], Duration::MAX);
+    for workgroup_size in WORKGROUP_SIZE_CANDIDATES {
+        if workgroup_size.iter().product::<u32>() > max_invocations {
+            continue;
+        }
+        let pipeline = create_pipeline(workgroup_size);
+
+        execute_one_time_command(device, vulkan_ctx.command_pool(), &queue, |cmdbuf| {
+            for _ in 0..WARMUP_ITERATIONS {
+                record(cmdbuf, &pipeline);
+            }
+        });
+
+        let start = Instant::now();
+        execute_one_time_command(device, vulkan_ctx.command_pool(), &queue, |cmdbuf| {
+            for _ in 0..TIMED_ITERATIONS {
+                record(cmdbuf, &pipeline);
+            }
+        });
+        let elapsed = start.elapsed();
+
+        log::debug!("workgroup size {:?}: {:?}", workgroup_size, elapsed);
+        if elapsed < fastest.1 {
+            fastest = (workgroup_size, elapsed);
+        }
+    }
+    fastest.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_round_trip_and_skip_malformed_lines() {
+        let mut sizes = BTreeMap::new();
+        sizes.insert("tracer".to_string(), [16, 8, 1]);
+        sizes.insert("god_ray".to_string(), [8, 8, 1]);
+
+        let mut content = serialize_sizes(&sizes);
+        content.push_str("spatial 8 x 1\ntemporal 8\n");
+
+        assert_eq!(parse_sizes(&content), sizes);
+    }
+}
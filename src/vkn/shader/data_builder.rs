@@ -286,3 +286,76 @@ impl<'a> StructMemberDataBuilder<'a> {
         }
     }
 }
+
+/// Lays out a slice of structs back to back, so the whole array reaches the buffer in a single
+/// write instead of one write per element.
+///
+/// Each element is set through its own [`StructMemberDataBuilder`] and padded to the element
+/// stride of the buffer.
+pub struct StructArrayDataBuilder<'a> {
+    layout: &'a StructMemberLayout,
+    stride: u64,
+    data: Vec<u8>,
+    errors: Vec<anyhow::Error>,
+}
+
+impl<'a> StructArrayDataBuilder<'a> {
+    pub fn from_buffer(buffer: &'a Buffer) -> Self {
+        let layout = &buffer
+            .get_layout()
+            .expect("The buffer doesn't have a layout")
+            .root_member;
+        Self {
+            layout,
+            stride: buffer.get_element_size_bytes(),
+            data: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Appends one element, its fields are set by `set_fields`.
+    /// All errors are stored inside `self.errors` and **not** returned.
+    pub fn push_element(
+        &mut self,
+        set_fields: impl FnOnce(&mut StructMemberDataBuilder<'a>),
+    ) -> &mut Self {
+        let mut element = StructMemberDataBuilder::from_layout(self.layout);
+        set_fields(&mut element);
+
+        let element_idx = self.data.len() as u64 / self.stride;
+        let mut bytes = match element.build() {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.errors
+                    .push(anyhow::anyhow!("Element {}: {}", element_idx, e));
+                Vec::new()
+            }
+        };
+        if bytes.len() as u64 > self.stride {
+            self.errors.push(anyhow::anyhow!(
+                "Element {} takes {} bytes, more than the stride {}",
+                element_idx,
+                bytes.len(),
+                self.stride
+            ));
+        }
+        bytes.resize(self.stride as usize, 0);
+        self.data.extend_from_slice(&bytes);
+        self
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>> {
+        if self.errors.is_empty() {
+            Ok(self.data.clone())
+        } else {
+            Err(anyhow::anyhow!(
+                "{}",
+                self.errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))
+        }
+    }
+}
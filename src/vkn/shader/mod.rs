@@ -7,5 +7,15 @@ pub use data_builder::*;
 mod data_reader;
 pub use data_reader::*;
 
+mod layout_reflect;
+
 mod shader_module;
 pub use shader_module::*;
+
+mod rust_codegen;
+pub use rust_codegen::*;
+
+mod shader_struct;
+pub use shader_struct::*;
+
+pub mod shader_structs;
@@ -0,0 +1,291 @@
+use super::struct_layout::*;
+use anyhow::{bail, Result};
+use std::{collections::HashMap, fmt::Write};
+
+/// Emits `#[repr(C)]` Rust definitions that mirror reflected buffer layouts byte for byte.
+///
+/// Gaps between members (std140 / std430 alignment) become explicit `_padN` fields, so the
+/// structs can be copied straight into a mapped buffer. Structs shared between several layouts,
+/// usually declared in a common include, are emitted once; if two layouts disagree on the size of
+/// a shared struct, generation fails instead of silently producing a mismatching definition.
+///
+/// Array members are emitted as `f32` words because the element type and stride are not reflected.
+///
+/// Root structs also get a [`crate::vkn::ShaderStruct`] impl and a `From` conversion into raw
+/// bytes, see [`crate::vkn::Buffer::fill_struct`].
+#[derive(Debug, Default)]
+pub struct RustStructGenerator {
+    /// Rust struct name -> size in bytes of the already emitted definition.
+    emitted: HashMap<String, u64>,
+    output: String,
+}
+
+#[allow(dead_code)]
+impl RustStructGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emits the definition of `layout` and every struct it contains, returns the Rust name of
+    /// the root struct.
+    pub fn add_buffer_layout(&mut self, layout: &BufferLayout) -> Result<String> {
+        let root = &layout.root_member;
+        let name = rust_type_name(&root.ty);
+        let is_new = !self.emitted.contains_key(&name);
+        self.emit_struct(root, &name, 0, root.get_size_bytes())?;
+
+        if is_new {
+            writeln!(
+                self.output,
+                "impl crate::vkn::ShaderStruct for {} {{\n    const BLOCK_NAME: &'static str = \"{}\";\n}}\n",
+                name, root.ty
+            )?;
+            writeln!(
+                self.output,
+                "impl From<&{0}> for Vec<u8> {{\n    fn from(value: &{0}) -> Self {{\n        bytemuck::bytes_of(value).to_vec()\n    }}\n}}\n",
+                name
+            )?;
+        }
+        Ok(name)
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+
+    fn emit_struct(
+        &mut self,
+        layout: &StructMemberLayout,
+        name: &str,
+        base_offset: u64,
+        size: u64,
+    ) -> Result<()> {
+        if let Some(&emitted_size) = self.emitted.get(name) {
+            if emitted_size != size {
+                bail!(
+                    "Struct `{}` is {} bytes here but {} bytes in a previously emitted layout",
+                    name,
+                    size,
+                    emitted_size
+                );
+            }
+            return Ok(());
+        }
+        self.emitted.insert(name.to_string(), size);
+
+        let mut members: Vec<(u64, &MemberLayout)> = layout
+            .name_member_table
+            .values()
+            .filter_map(|member| member_range(member).map(|(offset, _)| (offset, member)))
+            .collect();
+        members.sort_by_key(|(offset, _)| *offset);
+
+        let mut body = String::new();
+        let mut nested = Vec::new();
+        let mut cursor = base_offset;
+        let mut pad_idx = 0;
+        for (offset, member) in members {
+            if offset > cursor {
+                push_padding(&mut body, &mut pad_idx, offset - cursor);
+            }
+            match member {
+                MemberLayout::Plain(plain) => {
+                    writeln!(
+                        body,
+                        "    pub {}: {},",
+                        rust_field_name(&plain.name),
+                        rust_plain_type(&plain.ty, plain.size)
+                    )?;
+                    cursor = offset + plain.size;
+                }
+                MemberLayout::Struct(member_struct) => {
+                    let (start, end) = member_range(member).unwrap();
+                    let member_type = rust_type_name(&member_struct.ty);
+                    writeln!(
+                        body,
+                        "    pub {}: {},",
+                        rust_field_name(&member_struct.name),
+                        member_type
+                    )?;
+                    nested.push((member_struct, member_type, start, end - start));
+                    cursor = end;
+                }
+            }
+        }
+        if base_offset + size > cursor {
+            push_padding(&mut body, &mut pad_idx, base_offset + size - cursor);
+        }
+
+        writeln!(self.output, "/// Mirrors `{}` ({} bytes).", layout.ty, size)?;
+        writeln!(self.output, "#[repr(C)]")?;
+        writeln!(
+            self.output,
+            "#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]"
+        )?;
+        writeln!(self.output, "pub struct {} {{", name)?;
+        self.output.push_str(&body);
+        writeln!(self.output, "}}\n")?;
+
+        for (member_struct, member_type, start, member_size) in nested {
+            self.emit_struct(member_struct, &member_type, start, member_size)?;
+        }
+        Ok(())
+    }
+}
+
+fn push_padding(body: &mut String, pad_idx: &mut u32, bytes: u64) {
+    // std140 / std430 offsets are always 4-byte aligned
+    let _ = writeln!(body, "    pub _pad{}: [u32; {}],", pad_idx, bytes / 4);
+    *pad_idx += 1;
+}
+
+/// Returns the absolute byte range covered by the member, `None` for members without storage
+/// (runtime arrays).
+fn member_range(member: &MemberLayout) -> Option<(u64, u64)> {
+    match member {
+        MemberLayout::Plain(plain) => {
+            (plain.size > 0).then_some((plain.offset, plain.offset + plain.size))
+        }
+        MemberLayout::Struct(member_struct) => member_struct
+            .name_member_table
+            .values()
+            .filter_map(member_range)
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))),
+    }
+}
+
+/// GLSL member names may collide with Rust keywords (`type`, `ref`, ...).
+fn rust_field_name(glsl_name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "box", "break", "const", "continue", "dyn", "else", "enum", "extern", "fn", "for",
+        "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "static", "struct", "trait", "type", "unsafe", "use", "where", "while", "async", "await",
+        "yield", "macro", "abstract", "final", "override", "virtual",
+    ];
+    if KEYWORDS.contains(&glsl_name) {
+        format!("r#{}", glsl_name)
+    } else {
+        glsl_name.to_string()
+    }
+}
+
+/// `U_PlayerColliderInfo` -> `UPlayerColliderInfo`
+fn rust_type_name(glsl_name: &str) -> String {
+    glsl_name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn rust_plain_type(ty: &PlainMemberType, size: u64) -> String {
+    use PlainMemberType::*;
+    match ty {
+        Int => "i32".into(),
+        UInt => "u32".into(),
+        Int64 => "i64".into(),
+        UInt64 => "u64".into(),
+        Float => "f32".into(),
+        Vec2 => "[f32; 2]".into(),
+        Vec3 => "[f32; 3]".into(),
+        Vec4 => "[f32; 4]".into(),
+        IVec2 => "[i32; 2]".into(),
+        IVec3 => "[i32; 3]".into(),
+        IVec4 => "[i32; 4]".into(),
+        UVec2 => "[u32; 2]".into(),
+        UVec3 => "[u32; 3]".into(),
+        UVec4 => "[u32; 4]".into(),
+        // matrix columns are padded to vec4 in std140, so the column width follows the size
+        Mat2 if size == 32 => "[[f32; 4]; 2]".into(),
+        Mat2 => "[[f32; 2]; 2]".into(),
+        Mat3 if size == 48 => "[[f32; 4]; 3]".into(),
+        Mat3 => "[[f32; 3]; 3]".into(),
+        Mat4 => "[[f32; 4]; 4]".into(),
+        Mat3x4 => "[[f32; 4]; 3]".into(),
+        Array => format!("[f32; {}]", size / 4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv_reflect::types::ReflectDescriptorType;
+
+    fn plain(name: &str, ty: PlainMemberType, offset: u64, size: u64) -> (String, MemberLayout) {
+        (
+            name.to_string(),
+            MemberLayout::Plain(PlainMemberLayout {
+                name: name.to_string(),
+                ty,
+                offset,
+                size,
+                padded_size: size,
+            }),
+        )
+    }
+
+    fn buffer_layout(ty: &str, members: Vec<(String, MemberLayout)>) -> BufferLayout {
+        BufferLayout {
+            root_member: StructMemberLayout {
+                name: ty.to_lowercase(),
+                ty: ty.to_string(),
+                name_member_table: members.into_iter().collect(),
+            },
+            descriptor_type: ReflectDescriptorType::UniformBuffer,
+        }
+    }
+
+    #[test]
+    fn test_std140_vec3_padding() {
+        // uniform U_Info { vec3 pos; vec3 front; uint count; }
+        let layout = buffer_layout(
+            "U_Info",
+            vec![
+                plain("pos", PlainMemberType::Vec3, 0, 12),
+                plain("front", PlainMemberType::Vec3, 16, 12),
+                plain("count", PlainMemberType::UInt, 28, 4),
+            ],
+        );
+
+        let mut generator = RustStructGenerator::new();
+        assert_eq!(generator.add_buffer_layout(&layout).unwrap(), "UInfo");
+        let code = generator.finish();
+
+        let expected = "pub struct UInfo {\n    pub pos: [f32; 3],\n    pub _pad0: [u32; 1],\n    pub front: [f32; 3],\n    pub count: u32,\n}";
+        assert!(code.contains(expected), "{}", code);
+    }
+
+    #[test]
+    fn test_shared_struct_emitted_once_and_checked() {
+        let node = |offset: u64, size: u64| {
+            (
+                "node".to_string(),
+                MemberLayout::Struct(StructMemberLayout {
+                    name: "node".to_string(),
+                    ty: "ContreeNode".to_string(),
+                    name_member_table: [plain("child_mask", PlainMemberType::UInt, offset, size)]
+                        .into_iter()
+                        .collect(),
+                }),
+            )
+        };
+
+        let mut generator = RustStructGenerator::new();
+        generator
+            .add_buffer_layout(&buffer_layout("B_A", vec![node(0, 4)]))
+            .unwrap();
+        generator
+            .add_buffer_layout(&buffer_layout("B_B", vec![node(16, 4)]))
+            .unwrap();
+        assert!(generator
+            .add_buffer_layout(&buffer_layout("B_C", vec![node(0, 8)]))
+            .is_err());
+
+        let code = generator.finish();
+        assert_eq!(code.matches("pub struct ContreeNode").count(), 1);
+    }
+}
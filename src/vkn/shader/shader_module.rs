@@ -1,4 +1,4 @@
-use super::struct_layout::*;
+use super::{layout_reflect::extract_buffer_layouts, struct_layout::*};
 use crate::{
     util::{full_path_from_relative, ShaderCompiler},
     vkn::{DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutBuilder, Device},
@@ -7,9 +7,7 @@ use anyhow::Result;
 use ash::vk;
 use shaderc::ShaderKind;
 use spirv_reflect::{
-    types::{
-        ReflectDescriptorSet, ReflectDescriptorType, ReflectTypeDescriptionTraits, ReflectTypeFlags,
-    },
+    types::{ReflectDescriptorSet, ReflectDescriptorType},
     ShaderModule as ReflectShaderModule,
 };
 use std::{collections::HashMap, ffi::CString, fmt::Debug, sync::Arc};
@@ -545,191 +543,6 @@ fn u8_to_u32(byte_code: &[u8]) -> Vec<u32> {
         .collect()
 }
 
-fn extract_buffer_layouts(
-    reflect_module: &ReflectShaderModule,
-) -> Result<HashMap<String, BufferLayout>, String> {
-    let bindings = match reflect_module.enumerate_descriptor_bindings(None) {
-        Ok(binding) => binding,
-        Err(_) => return Err("Failed to enumerate descriptor bindings".to_string()),
-    };
-
-    let mut result = HashMap::new();
-
-    for binding in bindings {
-        if !is_buffer_type(binding.descriptor_type) {
-            continue;
-        }
-
-        let type_description = &binding.type_description.unwrap();
-        let ty = type_description.type_name.clone();
-        let name = binding.name.clone();
-        let descriptor_type = binding.descriptor_type;
-        let block = binding.block;
-        let members = parse_members_recursive(&block.members);
-
-        let root_member = StructMemberLayout {
-            name,
-            ty: ty.clone(),
-            name_member_table: members,
-        };
-
-        let layout = BufferLayout {
-            root_member,
-            descriptor_type,
-        };
-
-        result.insert(ty, layout);
-    }
-
-    return Ok(result);
-
-    fn is_buffer_type(ty: ReflectDescriptorType) -> bool {
-        ty == ReflectDescriptorType::UniformBuffer || ty == ReflectDescriptorType::StorageBuffer
-    }
-
-    fn parse_members_recursive(
-        reflect_members: &[spirv_reflect::types::ReflectBlockVariable],
-    ) -> HashMap<String, MemberLayout> {
-        let mut result = HashMap::new();
-        for reflect_member in reflect_members.iter() {
-            let member_name = reflect_member.name.clone();
-            let type_description = reflect_member.type_description.as_ref().unwrap();
-            let type_flags = &type_description.type_flags;
-            let member_type = get_general_member_type(type_flags);
-
-            let member: MemberLayout = match member_type {
-                GeneralMemberType::Array | GeneralMemberType::Plain => {
-                    let size = reflect_member.size as u64;
-                    // notice: u64 is not supported yet in the reflect lib, but we use u64 in our code for the best extensibility
-                    let offset = reflect_member.offset as u64;
-                    let padded_size = reflect_member.padded_size as u64;
-
-                    let ty =
-                        get_plain_member_type(type_flags, &type_description.traits, size).unwrap();
-                    MemberLayout::Plain(PlainMemberLayout {
-                        name: member_name.clone(),
-                        ty,
-                        offset,
-                        size,
-                        padded_size,
-                    })
-                }
-                GeneralMemberType::Struct => {
-                    let ty = type_description.type_name.clone();
-                    let members = parse_members_recursive(&reflect_member.members);
-                    MemberLayout::Struct(StructMemberLayout {
-                        name: member_name.clone(),
-                        ty,
-                        name_member_table: members,
-                    })
-                }
-            };
-            result.insert(member_name.clone(), member);
-        }
-        return result;
-
-        fn get_general_member_type(type_flags: &ReflectTypeFlags) -> GeneralMemberType {
-            if type_flags.contains(ReflectTypeFlags::STRUCT) {
-                GeneralMemberType::Struct
-            } else {
-                GeneralMemberType::Plain
-                // notice: Array type is not supported yet, and is counted as plain type
-            }
-        }
-
-        fn get_plain_member_type(
-            type_flags: &ReflectTypeFlags,
-            traits: &ReflectTypeDescriptionTraits,
-            size: u64,
-        ) -> Result<PlainMemberType, String> {
-            assert!(
-                get_general_member_type(type_flags) == GeneralMemberType::Plain,
-                "Expected plain member type",
-            );
-
-            let numeric = &traits.numeric;
-
-            if type_flags.contains(ReflectTypeFlags::ARRAY) {
-                return Ok(PlainMemberType::Array);
-            }
-
-            // matrices
-            if type_flags.contains(ReflectTypeFlags::MATRIX) {
-                let cols = numeric.matrix.column_count;
-                let rows = numeric.matrix.row_count;
-                return match (rows, cols) {
-                    (4, 4) => Ok(PlainMemberType::Mat4),
-                    (3, 3) => Ok(PlainMemberType::Mat3),
-                    (2, 2) => Ok(PlainMemberType::Mat2),
-                    (4, 3) => Ok(PlainMemberType::Mat3x4),
-                    _ => Err(format!("Unsupported matrix size: {}x{}", rows, cols)),
-                };
-            }
-
-            // vectors
-            if type_flags.contains(ReflectTypeFlags::VECTOR) {
-                let comp_count = numeric.vector.component_count;
-                // distinguish float-based vs int-based vs uint-based
-                let is_float = type_flags.contains(ReflectTypeFlags::FLOAT);
-                let is_int = type_flags.contains(ReflectTypeFlags::INT);
-                let signedness = numeric.scalar.signedness;
-
-                if is_float {
-                    return match comp_count {
-                        2 => Ok(PlainMemberType::Vec2),
-                        3 => Ok(PlainMemberType::Vec3),
-                        4 => Ok(PlainMemberType::Vec4),
-                        _ => Err("Unsupported vector size".to_string()),
-                    };
-                } else if is_int {
-                    // signedness == 1 => ivec..., else uvec...
-                    if signedness == 1 {
-                        return match comp_count {
-                            2 => Ok(PlainMemberType::IVec2),
-                            3 => Ok(PlainMemberType::IVec3),
-                            4 => Ok(PlainMemberType::IVec4),
-                            _ => Err("Unsupported vector size".to_string()),
-                        };
-                    } else {
-                        return match comp_count {
-                            2 => Ok(PlainMemberType::UVec2),
-                            3 => Ok(PlainMemberType::UVec3),
-                            4 => Ok(PlainMemberType::UVec4),
-                            _ => Err("Unsupported vector size".to_string()),
-                        };
-                    }
-                }
-            }
-
-            // scalars
-            if type_flags.contains(ReflectTypeFlags::FLOAT) {
-                return Ok(PlainMemberType::Float);
-            }
-
-            if type_flags.contains(ReflectTypeFlags::INT) {
-                // "bool" in GLSL is 32-bit in SPIR-V, typically stored as int.
-                let signed = numeric.scalar.signedness;
-                if size == 4 {
-                    return if signed == 1 {
-                        Ok(PlainMemberType::Int)
-                    } else {
-                        Ok(PlainMemberType::UInt)
-                    };
-                }
-                if size == 8 {
-                    return if signed == 1 {
-                        Ok(PlainMemberType::Int64)
-                    } else {
-                        Ok(PlainMemberType::UInt64)
-                    };
-                }
-            }
-
-            Err("Unsupported plain member type".to_string())
-        }
-    }
-}
-
 fn reflect_descriptor_type_to_descriptor_type(
     reflect_type: ReflectDescriptorType,
 ) -> vk::DescriptorType {
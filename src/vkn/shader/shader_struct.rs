@@ -0,0 +1,6 @@
+/// A `#[repr(C)]` mirror of a shader buffer block, generated by the build script into
+/// [`crate::vkn::shader_structs`].
+pub trait ShaderStruct: bytemuck::Pod {
+    /// Type name of the mirrored GLSL block, e.g. `U_SceneTexUpdateInfo`.
+    const BLOCK_NAME: &'static str;
+}
@@ -0,0 +1,8 @@
+//! Rust mirrors of the buffer blocks of every shader under `shader/`, generated by `build.rs` from
+//! the reflected SPIR-V.
+//!
+//! There is one module per shader file, named after its path, e.g.
+//! `shader/builder/scene_accel/update_scene_tex.comp` -> `builder_scene_accel_update_scene_tex_comp`.
+#![allow(dead_code, clippy::all)]
+
+include!(concat!(env!("OUT_DIR"), "/shader_structs.rs"));
@@ -78,6 +78,10 @@ impl Swapchain {
         self.image_views = image_views;
     }
 
+    pub fn image_count(&self) -> usize {
+        self.image_views.len()
+    }
+
     pub fn get_image(&self, index: u32) -> vk::Image {
         unsafe {
             self.swapchain_device
@@ -1,6 +1,9 @@
 mod semaphore;
 pub use semaphore::*;
 
+mod timeline_semaphore;
+pub use timeline_semaphore::*;
+
 mod fence;
 pub use fence::*;
 
@@ -0,0 +1,88 @@
+use crate::vkn::Device;
+use anyhow::Result;
+use ash::vk;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+struct TimelineSemaphoreInner {
+    device: Device,
+    semaphore: vk::Semaphore,
+    /// The value signalled by the latest submission.
+    submitted_value: AtomicU64,
+}
+
+impl Drop for TimelineSemaphoreInner {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
+
+/// A semaphore counting up as the GPU finishes submissions, each submission signals the value
+/// reserved for it by [`TimelineSemaphore::advance`].
+#[derive(Clone)]
+pub struct TimelineSemaphore(Arc<TimelineSemaphoreInner>);
+
+impl std::ops::Deref for TimelineSemaphore {
+    type Target = vk::Semaphore;
+    fn deref(&self) -> &Self::Target {
+        &self.0.semaphore
+    }
+}
+
+impl TimelineSemaphore {
+    pub fn new(device: &Device) -> Self {
+        let semaphore = Self::create_semaphore(device);
+        Self(Arc::new(TimelineSemaphoreInner {
+            device: device.clone(),
+            semaphore,
+            submitted_value: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn as_raw(&self) -> vk::Semaphore {
+        self.0.semaphore
+    }
+
+    /// Reserves the value the next submission signals.
+    pub fn advance(&self) -> u64 {
+        self.0.submitted_value.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// The value of the latest submission, the GPU may not have reached it yet.
+    pub fn submitted_value(&self) -> u64 {
+        self.0.submitted_value.load(Ordering::Relaxed)
+    }
+
+    /// The value the GPU has reached, every submission up to it is done.
+    pub fn completed_value(&self) -> Result<u64> {
+        let value = unsafe {
+            self.0
+                .device
+                .get_semaphore_counter_value(self.0.semaphore)?
+        };
+        Ok(value)
+    }
+
+    /// Blocks until the GPU reaches `value`.
+    pub fn wait(&self, value: u64) -> Result<()> {
+        let semaphores = [self.0.semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(&semaphores)
+            .values(&values);
+        unsafe { self.0.device.wait_semaphores(&wait_info, u64::MAX)? };
+        Ok(())
+    }
+
+    fn create_semaphore(device: &Device) -> vk::Semaphore {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let semaphore_info = vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+        unsafe { device.create_semaphore(&semaphore_info, None).unwrap() }
+    }
+}